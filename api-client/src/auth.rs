@@ -0,0 +1,65 @@
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+
+use crate::{ApiClient, Result};
+
+#[derive(Serialize)]
+struct LoginReq<'a> {
+  user_name: &'a str,
+  password: &'a str,
+}
+
+#[derive(Serialize)]
+struct RequestOtpReq<'a> {
+  user_name: &'a str,
+}
+
+#[derive(Serialize)]
+struct LoginOtpReq<'a> {
+  user_name: &'a str,
+  code: &'a str,
+}
+
+#[derive(Deserialize)]
+struct LoginRes {
+  token: String,
+}
+
+/// `/auth` endpoints.
+pub struct Auth<'a> {
+  pub(crate) client: &'a ApiClient,
+}
+
+impl Auth<'_> {
+  /// Returns the access token, pass it to `ApiClient::with_token`.
+  pub async fn login(&self, user_name: &str, password: &str) -> Result<String> {
+    let request = self
+      .client
+      .request(Method::POST, "/auth/login")
+      .json(&LoginReq {
+        user_name,
+        password,
+      });
+    let res: LoginRes = self.client.send(request).await?;
+    Ok(res.token)
+  }
+
+  /// Emails the user a one-time code for `login_otp`. Succeeds for unknown users too.
+  pub async fn request_otp(&self, user_name: &str) -> Result<()> {
+    let request = self
+      .client
+      .request(Method::POST, "/auth/request_otp")
+      .json(&RequestOtpReq { user_name });
+    self.client.send_empty(request).await
+  }
+
+  /// Exchanges the emailed code for an access token, like `login`.
+  pub async fn login_otp(&self, user_name: &str, code: &str) -> Result<String> {
+    let request = self
+      .client
+      .request(Method::POST, "/auth/login_otp")
+      .json(&LoginOtpReq { user_name, code });
+    let res: LoginRes = self.client.send(request).await?;
+    Ok(res.token)
+  }
+}
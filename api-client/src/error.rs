@@ -0,0 +1,59 @@
+use std::fmt;
+
+use crate::models::{FieldError, Status};
+
+#[derive(Debug)]
+pub enum ApiError {
+  /// The server answered with an error status, `fields` lists rejected fields on a
+  /// validation failure
+  Api {
+    status: Status,
+    fields: Vec<FieldError>,
+  },
+  /// The request never got an answer or the answer could not be decoded
+  Http(reqwest::Error),
+  /// A successful answer that should have carried data did not
+  MissingData,
+}
+
+impl ApiError {
+  /// HTTP status of an error answered by the server.
+  pub fn status(&self) -> Option<u16> {
+    match self {
+      ApiError::Api { status, .. } => Some(status.status),
+      _ => None,
+    }
+  }
+}
+
+impl fmt::Display for ApiError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      ApiError::Api { status, .. } => match &status.request_id {
+        Some(request_id) => write!(
+          f,
+          "{} {}: {} (request {})",
+          status.status, status.code, status.message, request_id
+        ),
+        None => write!(f, "{} {}: {}", status.status, status.code, status.message),
+      },
+      ApiError::Http(e) => write!(f, "HTTP error: {}", e),
+      ApiError::MissingData => write!(f, "Response has no data"),
+    }
+  }
+}
+
+impl std::error::Error for ApiError {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    match self {
+      ApiError::Http(e) => Some(e),
+      _ => None,
+    }
+  }
+}
+
+impl From<reqwest::Error> for ApiError {
+  fn from(e: reqwest::Error) -> Self {
+    ApiError::Http(e)
+  }
+}
@@ -0,0 +1,126 @@
+//! Typed client for the CRUD API, so Rust services don't hand-write its HTTP calls.
+//!
+//! ```no_run
+//! # async fn run() -> Result<(), api_client::ApiError> {
+//! use api_client::ApiClient;
+//!
+//! let client = ApiClient::new("http://localhost:8080");
+//! let token = client.auth().login("admin", "secret").await?;
+//! let client = client.with_token(token);
+//! let user = client.users().get_by_id(1).await?;
+//! println!("{} <{}>", user.name, user.email);
+//! # Ok(())
+//! # }
+//! ```
+
+mod auth;
+mod error;
+pub mod models;
+mod roles;
+mod users;
+
+pub use auth::Auth;
+pub use error::ApiError;
+pub use roles::Roles;
+pub use users::Users;
+
+use reqwest::{Method, RequestBuilder};
+use serde::de::DeserializeOwned;
+
+use crate::models::{Envelope, FieldError};
+
+// Version of the API this client is written against
+const API_PREFIX: &str = "/api/v2";
+
+pub type Result<T> = std::result::Result<T, ApiError>;
+
+#[derive(Clone)]
+pub struct ApiClient {
+  http: reqwest::Client,
+  base_url: String,
+  token: Option<String>,
+}
+
+impl ApiClient {
+  /// `base_url` is the server root, e.g. `http://localhost:8080`.
+  pub fn new(base_url: &str) -> Self {
+    Self::with_http(reqwest::Client::new(), base_url)
+  }
+
+  /// Uses a preconfigured `reqwest::Client`, for timeouts, proxies or custom TLS roots.
+  pub fn with_http(http: reqwest::Client, base_url: &str) -> Self {
+    Self {
+      http,
+      base_url: format!("{}{}", base_url.trim_end_matches('/'), API_PREFIX),
+      token: None,
+    }
+  }
+
+  /// Sends `token` as a bearer token on every request.
+  pub fn with_token(mut self, token: impl Into<String>) -> Self {
+    self.token = Some(token.into());
+    self
+  }
+
+  pub fn auth(&self) -> Auth<'_> {
+    Auth { client: self }
+  }
+
+  pub fn users(&self) -> Users<'_> {
+    Users { client: self }
+  }
+
+  pub fn roles(&self) -> Roles<'_> {
+    Roles { client: self }
+  }
+
+  pub(crate) fn request(&self, method: Method, path: &str) -> RequestBuilder {
+    let request = self
+      .http
+      .request(method, format!("{}{}", self.base_url, path));
+    match &self.token {
+      Some(token) => request.bearer_auth(token),
+      None => request,
+    }
+  }
+
+  // The `data` of a successful answer
+  pub(crate) async fn send<T: DeserializeOwned>(&self, request: RequestBuilder) -> Result<T> {
+    let response = request.send().await?;
+    if !response.status().is_success() {
+      return Err(Self::error(response).await);
+    }
+    let envelope: Envelope<T> = response.json().await?;
+    envelope.data.ok_or(ApiError::MissingData)
+  }
+
+  // For endpoints that answer a bare status
+  pub(crate) async fn send_empty(&self, request: RequestBuilder) -> Result<()> {
+    let response = request.send().await?;
+    if !response.status().is_success() {
+      return Err(Self::error(response).await);
+    }
+    Ok(())
+  }
+
+  async fn error(response: reqwest::Response) -> ApiError {
+    let status = response.status();
+    let envelope = match response.json::<Envelope<serde_json::Value>>().await {
+      Ok(envelope) => envelope,
+      Err(e) => return ApiError::Http(e),
+    };
+    // Validation failures carry the rejected fields as data
+    let fields = envelope
+      .data
+      .and_then(|data| serde_json::from_value::<Vec<FieldError>>(data).ok())
+      .unwrap_or_default();
+    let mut status_body = envelope.status;
+    if status_body.status == 0 {
+      status_body.status = status.as_u16();
+    }
+    ApiError::Api {
+      status: status_body,
+      fields,
+    }
+  }
+}
@@ -0,0 +1,154 @@
+use serde::{Deserialize, Serialize};
+
+/// Outcome of a request, sent alone or next to `data`.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct Status {
+  #[serde(default)]
+  pub message: String,
+  #[serde(default)]
+  pub code: String,
+  pub status: u16,
+  /// Id of the failed request, quote it when reporting a problem
+  #[serde(default)]
+  pub request_id: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub(crate) struct Envelope<T> {
+  pub data: Option<T>,
+  #[serde(default)]
+  pub status: Status,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct FieldError {
+  pub field: String,
+  pub code: String,
+  pub message: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Page<T> {
+  pub items: Vec<T>,
+  pub page: i32,
+  pub page_size: i32,
+  pub total_count: i32,
+  pub total_pages: i32,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortDir {
+  #[default]
+  Asc,
+  Desc,
+}
+
+// --- Users --- //
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct User {
+  pub id: i32,
+  pub user_name: String,
+  pub name: String,
+  pub email: String,
+  /// Legacy role stored on the user, e.g. `Admin`
+  pub role: String,
+  pub is_active: bool,
+  /// Effective role names, the legacy role plus roles assigned from the roles table
+  #[serde(default)]
+  pub roles: Vec<String>,
+  /// Last successful login as an RFC 3339 timestamp, `None` until the user logged in once
+  #[serde(default)]
+  pub last_login_at: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Me {
+  pub user: User,
+  pub roles: Vec<UserRole>,
+  pub permissions: Vec<String>,
+  pub features: Vec<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum UserSortBy {
+  #[default]
+  Id,
+  UserName,
+  Name,
+  Email,
+  Role,
+  IsActive,
+  CreatedAt,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ListUsers {
+  pub page: i32,
+  pub page_size: i32,
+  pub sort_by: UserSortBy,
+  pub sort_dir: SortDir,
+}
+
+impl Default for ListUsers {
+  fn default() -> Self {
+    Self {
+      page: 1,
+      page_size: 20,
+      sort_by: UserSortBy::default(),
+      sort_dir: SortDir::default(),
+    }
+  }
+}
+
+/// Changes of `PUT /users/{id}`, fields left `None` are kept.
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct UserChanges {
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub name: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub email: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub role: Option<String>,
+}
+
+// --- Roles --- //
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Role {
+  pub id: i32,
+  pub name: String,
+  pub description: Option<String>,
+}
+
+/// A role and whether the user has it.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct UserRole {
+  pub role_id: i32,
+  pub role_name: String,
+  pub is_in_role: bool,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RoleSortBy {
+  #[default]
+  Id,
+  Name,
+  CreatedAt,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct ListRoles {
+  pub sort_by: RoleSortBy,
+  pub sort_dir: SortDir,
+}
+
+/// Body of creating or updating a role.
+#[derive(Serialize, Debug, Clone)]
+pub struct RoleInput {
+  pub name: String,
+  pub description: Option<String>,
+}
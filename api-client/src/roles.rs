@@ -0,0 +1,70 @@
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  ApiClient, Result,
+  models::{ListRoles, Role, RoleInput},
+};
+
+#[derive(Serialize)]
+struct RoleUsers<'a> {
+  user_ids: &'a [i32],
+}
+
+#[derive(Deserialize)]
+struct Created {
+  id: i32,
+}
+
+/// `/roles` endpoints.
+pub struct Roles<'a> {
+  pub(crate) client: &'a ApiClient,
+}
+
+impl Roles<'_> {
+  pub async fn list(&self, query: &ListRoles) -> Result<Vec<Role>> {
+    let request = self.client.request(Method::GET, "/roles").query(query);
+    self.client.send(request).await
+  }
+
+  /// Returns the id of the new role.
+  pub async fn create(&self, role: &RoleInput) -> Result<i32> {
+    let request = self.client.request(Method::POST, "/roles").json(role);
+    let created: Created = self.client.send(request).await?;
+    Ok(created.id)
+  }
+
+  pub async fn update(&self, id: i32, role: &RoleInput) -> Result<()> {
+    let request = self
+      .client
+      .request(Method::PUT, &format!("/roles/{}", id))
+      .json(role);
+    self.client.send_empty(request).await
+  }
+
+  /// `force` also removes the role from users still assigned to it, otherwise that fails.
+  pub async fn delete(&self, id: i32, force: bool) -> Result<()> {
+    let request = self
+      .client
+      .request(Method::DELETE, &format!("/roles/{}", id))
+      .query(&[("force", force)]);
+    self.client.send_empty(request).await
+  }
+
+  pub async fn assign_user(&self, role_id: i32, user_id: i32) -> Result<()> {
+    let request = self.client.request(
+      Method::PUT,
+      &format!("/roles/{}/users/{}", role_id, user_id),
+    );
+    self.client.send_empty(request).await
+  }
+
+  /// Assigns every user in one transaction, none are assigned if one fails.
+  pub async fn assign_users(&self, role_id: i32, user_ids: &[i32]) -> Result<()> {
+    let request = self
+      .client
+      .request(Method::POST, &format!("/roles/{}/users", role_id))
+      .json(&RoleUsers { user_ids });
+    self.client.send_empty(request).await
+  }
+}
@@ -0,0 +1,59 @@
+use reqwest::Method;
+
+use crate::{
+  ApiClient, Result,
+  models::{ListUsers, Me, Page, User, UserChanges, UserRole},
+};
+
+/// `/users` endpoints.
+pub struct Users<'a> {
+  pub(crate) client: &'a ApiClient,
+}
+
+impl Users<'_> {
+  /// The user the token belongs to, with roles, permissions and feature flags.
+  pub async fn me(&self) -> Result<Me> {
+    let request = self.client.request(Method::GET, "/users/me");
+    self.client.send(request).await
+  }
+
+  pub async fn get_by_id(&self, id: i32) -> Result<User> {
+    let request = self.client.request(Method::GET, &format!("/users/{}", id));
+    self.client.send(request).await
+  }
+
+  pub async fn list(&self, query: &ListUsers) -> Result<Page<User>> {
+    let request = self.client.request(Method::GET, "/users").query(query);
+    self.client.send(request).await
+  }
+
+  pub async fn update(&self, id: i32, changes: &UserChanges) -> Result<()> {
+    let request = self
+      .client
+      .request(Method::PUT, &format!("/users/{}", id))
+      .json(changes);
+    self.client.send_empty(request).await
+  }
+
+  pub async fn activate(&self, id: i32) -> Result<()> {
+    let request = self
+      .client
+      .request(Method::POST, &format!("/users/{}/activate", id));
+    self.client.send_empty(request).await
+  }
+
+  pub async fn deactivate(&self, id: i32) -> Result<()> {
+    let request = self
+      .client
+      .request(Method::POST, &format!("/users/{}/deactivate", id));
+    self.client.send_empty(request).await
+  }
+
+  /// Every role, flagged with whether the user has it.
+  pub async fn roles(&self, id: i32) -> Result<Vec<UserRole>> {
+    let request = self
+      .client
+      .request(Method::GET, &format!("/users/{}/roles", id));
+    self.client.send(request).await
+  }
+}
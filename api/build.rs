@@ -0,0 +1,26 @@
+use std::{
+  process::Command,
+  time::{SystemTime, UNIX_EPOCH},
+};
+
+// Bakes build metadata into the binary for /api/v1/meta
+fn main() {
+  let git_sha = Command::new("git")
+    .args(["rev-parse", "--short", "HEAD"])
+    .output()
+    .ok()
+    .filter(|output| output.status.success())
+    .and_then(|output| String::from_utf8(output.stdout).ok())
+    .map(|sha| sha.trim().to_string())
+    .unwrap_or_else(|| "unknown".to_string());
+
+  let build_timestamp = SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map(|d| d.as_secs())
+    .unwrap_or_default();
+
+  println!("cargo:rustc-env=BUILD_GIT_SHA={}", git_sha);
+  println!("cargo:rustc-env=BUILD_TIMESTAMP={}", build_timestamp);
+  println!("cargo:rerun-if-changed=../.git/HEAD");
+  println!("cargo:rerun-if-changed=../.git/refs/heads");
+}
@@ -0,0 +1,436 @@
+-- Baseline schema: tables and stored procedures used by the API.
+-- Tables are only created when missing so this can be applied to an existing database.
+
+IF OBJECT_ID(N'dbo.users', N'U') IS NULL
+CREATE TABLE dbo.users (
+  id INT IDENTITY(1, 1) NOT NULL PRIMARY KEY,
+  name NVARCHAR(100) NOT NULL,
+  user_name NVARCHAR(50) NOT NULL UNIQUE,
+  email NVARCHAR(255) NOT NULL,
+  password NVARCHAR(255) NOT NULL,
+  role NVARCHAR(50) NOT NULL,
+  is_active BIT NOT NULL DEFAULT 1,
+  created_at DATETIME2 NOT NULL DEFAULT SYSUTCDATETIME(),
+  updated_at DATETIME2 NULL
+);
+GO
+
+IF OBJECT_ID(N'dbo.roles', N'U') IS NULL
+CREATE TABLE dbo.roles (
+  id INT IDENTITY(1, 1) NOT NULL PRIMARY KEY,
+  name NVARCHAR(50) NOT NULL UNIQUE,
+  description NVARCHAR(255) NULL,
+  created_at DATETIME2 NOT NULL DEFAULT SYSUTCDATETIME(),
+  updated_at DATETIME2 NULL
+);
+GO
+
+IF OBJECT_ID(N'dbo.user_roles', N'U') IS NULL
+CREATE TABLE dbo.user_roles (
+  id INT IDENTITY(1, 1) NOT NULL PRIMARY KEY,
+  user_id INT NOT NULL REFERENCES dbo.users (id),
+  role_id INT NOT NULL REFERENCES dbo.roles (id),
+  CONSTRAINT uq_user_roles UNIQUE (user_id, role_id)
+);
+GO
+
+IF OBJECT_ID(N'dbo.permissions', N'U') IS NULL
+CREATE TABLE dbo.permissions (
+  id INT IDENTITY(1, 1) NOT NULL PRIMARY KEY,
+  name NVARCHAR(100) NOT NULL UNIQUE,
+  description NVARCHAR(255) NULL,
+  created_at DATETIME2 NOT NULL DEFAULT SYSUTCDATETIME(),
+  updated_at DATETIME2 NULL
+);
+GO
+
+IF OBJECT_ID(N'dbo.role_permissions', N'U') IS NULL
+CREATE TABLE dbo.role_permissions (
+  id INT IDENTITY(1, 1) NOT NULL PRIMARY KEY,
+  role_id INT NOT NULL REFERENCES dbo.roles (id),
+  permission_id INT NOT NULL REFERENCES dbo.permissions (id),
+  CONSTRAINT uq_role_permissions UNIQUE (role_id, permission_id)
+);
+GO
+
+IF OBJECT_ID(N'dbo.audit_logs', N'U') IS NULL
+CREATE TABLE dbo.audit_logs (
+  id INT IDENTITY(1, 1) NOT NULL PRIMARY KEY,
+  actor_id INT NULL,
+  action NVARCHAR(50) NOT NULL,
+  target NVARCHAR(255) NULL,
+  details NVARCHAR(MAX) NULL,
+  request_id NVARCHAR(100) NULL,
+  created_at DATETIME2 NOT NULL DEFAULT SYSUTCDATETIME()
+);
+GO
+
+IF OBJECT_ID(N'dbo.email_change_requests', N'U') IS NULL
+CREATE TABLE dbo.email_change_requests (
+  id INT IDENTITY(1, 1) NOT NULL PRIMARY KEY,
+  user_id INT NOT NULL REFERENCES dbo.users (id),
+  new_email NVARCHAR(255) NOT NULL,
+  token NVARCHAR(100) NOT NULL UNIQUE,
+  expires_at DATETIME2 NOT NULL,
+  created_at DATETIME2 NOT NULL DEFAULT SYSUTCDATETIME()
+);
+GO
+
+-- ---------- Users ---------- --
+
+CREATE OR ALTER PROCEDURE dbo.create_user
+  @name NVARCHAR(100),
+  @user_name NVARCHAR(50),
+  @email NVARCHAR(255),
+  @password NVARCHAR(255),
+  @role NVARCHAR(50)
+AS
+BEGIN
+  INSERT INTO dbo.users (name, user_name, email, password, role)
+  VALUES (@name, @user_name, @email, @password, @role);
+END
+GO
+
+CREATE OR ALTER PROCEDURE dbo.select_user
+  @id INT
+AS
+BEGIN
+  SELECT * FROM dbo.users WHERE id = @id;
+END
+GO
+
+CREATE OR ALTER PROCEDURE dbo.select_user_by_user_name
+  @user_name NVARCHAR(50)
+AS
+BEGIN
+  SELECT * FROM dbo.users WHERE user_name = @user_name;
+END
+GO
+
+CREATE OR ALTER PROCEDURE dbo.select_users
+AS
+BEGIN
+  SELECT * FROM dbo.users ORDER BY id;
+END
+GO
+
+CREATE OR ALTER PROCEDURE dbo.select_users_paged
+  @page INT,
+  @page_size INT
+AS
+BEGIN
+  SELECT *, COUNT(*) OVER () AS total_count
+  FROM dbo.users
+  ORDER BY id
+  OFFSET (@page - 1) * @page_size ROWS FETCH NEXT @page_size ROWS ONLY;
+END
+GO
+
+CREATE OR ALTER PROCEDURE dbo.update_user
+  @name NVARCHAR(100),
+  @user_name NVARCHAR(50),
+  @email NVARCHAR(255),
+  @role NVARCHAR(50)
+AS
+BEGIN
+  UPDATE dbo.users
+  SET name = @name, email = @email, role = @role, updated_at = SYSUTCDATETIME()
+  WHERE user_name = @user_name;
+END
+GO
+
+CREATE OR ALTER PROCEDURE dbo.update_user_active
+  @id INT,
+  @is_active BIT
+AS
+BEGIN
+  UPDATE dbo.users SET is_active = @is_active, updated_at = SYSUTCDATETIME() WHERE id = @id;
+END
+GO
+
+CREATE OR ALTER PROCEDURE dbo.update_user_email
+  @user_id INT,
+  @email NVARCHAR(255)
+AS
+BEGIN
+  UPDATE dbo.users SET email = @email, updated_at = SYSUTCDATETIME() WHERE id = @user_id;
+END
+GO
+
+CREATE OR ALTER PROCEDURE dbo.create_email_change_request
+  @user_id INT,
+  @new_email NVARCHAR(255),
+  @token NVARCHAR(100),
+  @expiration_minutes INT
+AS
+BEGIN
+  DELETE FROM dbo.email_change_requests WHERE user_id = @user_id;
+  INSERT INTO dbo.email_change_requests (user_id, new_email, token, expires_at)
+  VALUES (@user_id, @new_email, @token, DATEADD(MINUTE, @expiration_minutes, SYSUTCDATETIME()));
+END
+GO
+
+CREATE OR ALTER PROCEDURE dbo.select_email_change_request
+  @token NVARCHAR(100)
+AS
+BEGIN
+  SELECT * FROM dbo.email_change_requests WHERE token = @token;
+END
+GO
+
+CREATE OR ALTER PROCEDURE dbo.delete_email_change_request
+  @user_id INT
+AS
+BEGIN
+  DELETE FROM dbo.email_change_requests WHERE user_id = @user_id;
+END
+GO
+
+-- ---------- Roles ---------- --
+
+CREATE OR ALTER PROCEDURE dbo.create_role
+  @name NVARCHAR(50),
+  @description NVARCHAR(255)
+AS
+BEGIN
+  INSERT INTO dbo.roles (name, description) VALUES (@name, NULLIF(@description, ''));
+END
+GO
+
+CREATE OR ALTER PROCEDURE dbo.update_role
+  @id INT,
+  @name NVARCHAR(50),
+  @description NVARCHAR(255)
+AS
+BEGIN
+  UPDATE dbo.roles
+  SET name = @name, description = NULLIF(@description, ''), updated_at = SYSUTCDATETIME()
+  WHERE id = @id;
+END
+GO
+
+CREATE OR ALTER PROCEDURE dbo.select_role_by_name
+  @name NVARCHAR(50)
+AS
+BEGIN
+  SELECT * FROM dbo.roles WHERE name = @name;
+END
+GO
+
+CREATE OR ALTER PROCEDURE dbo.select_role_by_id
+  @id INT
+AS
+BEGIN
+  SELECT * FROM dbo.roles WHERE id = @id;
+END
+GO
+
+CREATE OR ALTER PROCEDURE dbo.select_roles
+AS
+BEGIN
+  SELECT * FROM dbo.roles ORDER BY id;
+END
+GO
+
+CREATE OR ALTER PROCEDURE dbo.delete_role
+  @id INT
+AS
+BEGIN
+  DELETE FROM dbo.role_permissions WHERE role_id = @id;
+  DELETE FROM dbo.roles WHERE id = @id;
+END
+GO
+
+CREATE OR ALTER PROCEDURE dbo.select_user_role
+  @user_id INT
+AS
+BEGIN
+  SELECT
+    r.id AS role_id,
+    r.name AS role_name,
+    CAST(CASE WHEN ur.id IS NULL THEN 0 ELSE 1 END AS BIT) AS is_in_role
+  FROM dbo.roles r
+  LEFT JOIN dbo.user_roles ur ON ur.role_id = r.id AND ur.user_id = @user_id
+  ORDER BY r.id;
+END
+GO
+
+CREATE OR ALTER PROCEDURE dbo.is_user_role_exist
+  @user_id INT,
+  @role_id INT
+AS
+BEGIN
+  SELECT * FROM dbo.user_roles WHERE user_id = @user_id AND role_id = @role_id;
+END
+GO
+
+CREATE OR ALTER PROCEDURE dbo.assign_user_role
+  @user_id INT,
+  @role_id INT
+AS
+BEGIN
+  INSERT INTO dbo.user_roles (user_id, role_id) VALUES (@user_id, @role_id);
+END
+GO
+
+CREATE OR ALTER PROCEDURE dbo.count_users_in_role
+  @role_id INT
+AS
+BEGIN
+  SELECT COUNT(*) AS total FROM dbo.user_roles WHERE role_id = @role_id;
+END
+GO
+
+CREATE OR ALTER PROCEDURE dbo.delete_user_roles_by_role
+  @role_id INT
+AS
+BEGIN
+  DELETE FROM dbo.user_roles WHERE role_id = @role_id;
+END
+GO
+
+CREATE OR ALTER PROCEDURE dbo.select_user_role_counts
+AS
+BEGIN
+  SELECT role, COUNT(*) AS total FROM dbo.users GROUP BY role ORDER BY role;
+END
+GO
+
+-- ---------- Permissions ---------- --
+
+CREATE OR ALTER PROCEDURE dbo.create_permission
+  @name NVARCHAR(100),
+  @description NVARCHAR(255)
+AS
+BEGIN
+  INSERT INTO dbo.permissions (name, description) VALUES (@name, NULLIF(@description, ''));
+END
+GO
+
+CREATE OR ALTER PROCEDURE dbo.update_permission
+  @id INT,
+  @name NVARCHAR(100),
+  @description NVARCHAR(255)
+AS
+BEGIN
+  UPDATE dbo.permissions
+  SET name = @name, description = NULLIF(@description, ''), updated_at = SYSUTCDATETIME()
+  WHERE id = @id;
+END
+GO
+
+CREATE OR ALTER PROCEDURE dbo.delete_permission
+  @id INT
+AS
+BEGIN
+  DELETE FROM dbo.role_permissions WHERE permission_id = @id;
+  DELETE FROM dbo.permissions WHERE id = @id;
+END
+GO
+
+CREATE OR ALTER PROCEDURE dbo.select_permission_by_id
+  @id INT
+AS
+BEGIN
+  SELECT * FROM dbo.permissions WHERE id = @id;
+END
+GO
+
+CREATE OR ALTER PROCEDURE dbo.select_permission_by_name
+  @name NVARCHAR(100)
+AS
+BEGIN
+  SELECT * FROM dbo.permissions WHERE name = @name;
+END
+GO
+
+CREATE OR ALTER PROCEDURE dbo.select_permissions
+AS
+BEGIN
+  SELECT * FROM dbo.permissions ORDER BY name;
+END
+GO
+
+CREATE OR ALTER PROCEDURE dbo.select_role_permissions
+  @role_id INT
+AS
+BEGIN
+  SELECT p.*
+  FROM dbo.permissions p
+  JOIN dbo.role_permissions rp ON rp.permission_id = p.id
+  WHERE rp.role_id = @role_id
+  ORDER BY p.name;
+END
+GO
+
+CREATE OR ALTER PROCEDURE dbo.select_user_permissions
+  @user_id INT
+AS
+BEGIN
+  SELECT DISTINCT p.*
+  FROM dbo.permissions p
+  JOIN dbo.role_permissions rp ON rp.permission_id = p.id
+  JOIN dbo.user_roles ur ON ur.role_id = rp.role_id
+  WHERE ur.user_id = @user_id;
+END
+GO
+
+CREATE OR ALTER PROCEDURE dbo.assign_role_permission
+  @role_id INT,
+  @permission_id INT
+AS
+BEGIN
+  IF NOT EXISTS (
+    SELECT 1 FROM dbo.role_permissions WHERE role_id = @role_id AND permission_id = @permission_id
+  )
+  INSERT INTO dbo.role_permissions (role_id, permission_id) VALUES (@role_id, @permission_id);
+END
+GO
+
+CREATE OR ALTER PROCEDURE dbo.revoke_role_permission
+  @role_id INT,
+  @permission_id INT
+AS
+BEGIN
+  DELETE FROM dbo.role_permissions WHERE role_id = @role_id AND permission_id = @permission_id;
+END
+GO
+
+-- ---------- Audit ---------- --
+
+CREATE OR ALTER PROCEDURE dbo.create_audit_log
+  @actor_id INT,
+  @action NVARCHAR(50),
+  @target NVARCHAR(255),
+  @details NVARCHAR(MAX),
+  @request_id NVARCHAR(100)
+AS
+BEGIN
+  INSERT INTO dbo.audit_logs (actor_id, action, target, details, request_id)
+  VALUES (
+    NULLIF(@actor_id, 0),
+    @action,
+    NULLIF(@target, ''),
+    NULLIF(@details, ''),
+    NULLIF(@request_id, '')
+  );
+END
+GO
+
+CREATE OR ALTER PROCEDURE dbo.select_audit_logs_paged
+  @actor_id INT,
+  @action NVARCHAR(50),
+  @target NVARCHAR(255),
+  @page INT,
+  @page_size INT
+AS
+BEGIN
+  SELECT *, COUNT(*) OVER () AS total_count
+  FROM dbo.audit_logs
+  WHERE (@actor_id = 0 OR actor_id = @actor_id)
+    AND (@action = '' OR action = @action)
+    AND (@target = '' OR target = @target)
+  ORDER BY created_at DESC, id DESC
+  OFFSET (@page - 1) * @page_size ROWS FETCH NEXT @page_size ROWS ONLY;
+END
+GO
@@ -0,0 +1,8 @@
+-- Used by the scheduler's purge_expired_tokens task
+
+CREATE OR ALTER PROCEDURE dbo.purge_expired_email_change_requests
+AS
+BEGIN
+  DELETE FROM dbo.email_change_requests WHERE expires_at < SYSUTCDATETIME();
+END
+GO
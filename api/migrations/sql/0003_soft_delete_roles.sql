@@ -0,0 +1,77 @@
+-- Roles are soft deleted, see `db::soft_delete`. Deleted rows stay until the scheduler's
+-- purge_soft_deleted task removes them, reads only see the rows where deleted_at is NULL.
+
+IF COL_LENGTH(N'dbo.roles', N'deleted_at') IS NULL
+ALTER TABLE dbo.roles ADD deleted_at DATETIME2 NULL;
+GO
+
+-- A deleted role must not keep its name taken, so the unique constraint becomes an index
+-- over the live rows only
+DECLARE @constraint SYSNAME = (
+  SELECT kc.name
+  FROM sys.key_constraints kc
+  WHERE kc.parent_object_id = OBJECT_ID(N'dbo.roles') AND kc.type = 'UQ'
+);
+IF @constraint IS NOT NULL
+EXEC (N'ALTER TABLE dbo.roles DROP CONSTRAINT ' + QUOTENAME(@constraint));
+GO
+
+IF NOT EXISTS (SELECT 1 FROM sys.indexes WHERE name = N'ux_roles_name' AND object_id = OBJECT_ID(N'dbo.roles'))
+CREATE UNIQUE INDEX ux_roles_name ON dbo.roles (name) WHERE deleted_at IS NULL;
+GO
+
+-- Replaced by SqlRepo::soft_delete
+DROP PROCEDURE IF EXISTS dbo.delete_role;
+GO
+
+CREATE OR ALTER PROCEDURE dbo.select_role_by_name
+  @name NVARCHAR(50)
+AS
+BEGIN
+  SELECT * FROM dbo.roles WHERE name = @name AND deleted_at IS NULL;
+END
+GO
+
+CREATE OR ALTER PROCEDURE dbo.select_role_by_id
+  @id INT
+AS
+BEGIN
+  SELECT * FROM dbo.roles WHERE id = @id AND deleted_at IS NULL;
+END
+GO
+
+CREATE OR ALTER PROCEDURE dbo.select_roles
+AS
+BEGIN
+  SELECT * FROM dbo.roles WHERE deleted_at IS NULL ORDER BY id;
+END
+GO
+
+CREATE OR ALTER PROCEDURE dbo.select_user_role
+  @user_id INT
+AS
+BEGIN
+  SELECT
+    r.id AS role_id,
+    r.name AS role_name,
+    CAST(CASE WHEN ur.id IS NULL THEN 0 ELSE 1 END AS BIT) AS is_in_role
+  FROM dbo.roles r
+  LEFT JOIN dbo.user_roles ur ON ur.role_id = r.id AND ur.user_id = @user_id
+  WHERE r.deleted_at IS NULL
+  ORDER BY r.id;
+END
+GO
+
+-- Permissions granted to a deleted role no longer count
+CREATE OR ALTER PROCEDURE dbo.select_user_permissions
+  @user_id INT
+AS
+BEGIN
+  SELECT DISTINCT p.*
+  FROM dbo.permissions p
+  JOIN dbo.role_permissions rp ON rp.permission_id = p.id
+  JOIN dbo.user_roles ur ON ur.role_id = rp.role_id
+  JOIN dbo.roles r ON r.id = rp.role_id AND r.deleted_at IS NULL
+  WHERE ur.user_id = @user_id;
+END
+GO
@@ -0,0 +1,60 @@
+-- Every login attempt, successful or not, and the time of each user's last successful login.
+-- Attempts on a user name that doesn't exist are kept with a NULL user_id.
+
+IF OBJECT_ID(N'dbo.login_history', N'U') IS NULL
+CREATE TABLE dbo.login_history (
+  id INT IDENTITY(1, 1) NOT NULL PRIMARY KEY,
+  user_id INT NULL REFERENCES dbo.users (id),
+  user_name NVARCHAR(50) NOT NULL,
+  outcome NVARCHAR(30) NOT NULL,
+  ip_address NVARCHAR(45) NULL,
+  user_agent NVARCHAR(512) NULL,
+  created_at DATETIME2 NOT NULL DEFAULT SYSUTCDATETIME()
+);
+GO
+
+IF NOT EXISTS (SELECT 1 FROM sys.indexes WHERE name = N'ix_login_history_user' AND object_id = OBJECT_ID(N'dbo.login_history'))
+CREATE INDEX ix_login_history_user ON dbo.login_history (user_id, created_at DESC);
+GO
+
+IF COL_LENGTH(N'dbo.users', N'last_login_at') IS NULL
+ALTER TABLE dbo.users ADD last_login_at DATETIME2 NULL;
+GO
+
+CREATE OR ALTER PROCEDURE dbo.create_login_attempt
+  @user_id INT,
+  @user_name NVARCHAR(50),
+  @outcome NVARCHAR(30),
+  @ip_address NVARCHAR(45),
+  @user_agent NVARCHAR(512)
+AS
+BEGIN
+  INSERT INTO dbo.login_history (user_id, user_name, outcome, ip_address, user_agent)
+  VALUES (
+    NULLIF(@user_id, 0),
+    @user_name,
+    @outcome,
+    NULLIF(@ip_address, ''),
+    NULLIF(@user_agent, '')
+  );
+
+  IF @outcome = 'success'
+  UPDATE dbo.users SET last_login_at = SYSUTCDATETIME() WHERE id = @user_id;
+END
+GO
+
+CREATE OR ALTER PROCEDURE dbo.select_login_attempts_paged
+  @user_id INT,
+  @outcome NVARCHAR(30),
+  @page INT,
+  @page_size INT
+AS
+BEGIN
+  SELECT *, COUNT(*) OVER () AS total_count
+  FROM dbo.login_history
+  WHERE (@user_id = 0 OR user_id = @user_id)
+    AND (@outcome = '' OR outcome = @outcome)
+  ORDER BY created_at DESC, id DESC
+  OFFSET (@page - 1) * @page_size ROWS FETCH NEXT @page_size ROWS ONLY;
+END
+GO
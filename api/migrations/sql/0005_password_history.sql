@@ -0,0 +1,53 @@
+-- Hashes of the passwords users had before their current one, so a change can refuse the last
+-- few. Only the newest rows are kept, see `password.history_size`.
+
+IF OBJECT_ID(N'dbo.password_history', N'U') IS NULL
+CREATE TABLE dbo.password_history (
+  id INT IDENTITY(1, 1) NOT NULL PRIMARY KEY,
+  user_id INT NOT NULL REFERENCES dbo.users (id),
+  password NVARCHAR(255) NOT NULL,
+  created_at DATETIME2 NOT NULL DEFAULT SYSUTCDATETIME()
+);
+GO
+
+IF NOT EXISTS (SELECT 1 FROM sys.indexes WHERE name = N'ix_password_history_user' AND object_id = OBJECT_ID(N'dbo.password_history'))
+CREATE INDEX ix_password_history_user ON dbo.password_history (user_id, created_at DESC);
+GO
+
+CREATE OR ALTER PROCEDURE dbo.select_password_history
+  @user_id INT,
+  @count INT
+AS
+BEGIN
+  SELECT TOP (@count) password
+  FROM dbo.password_history
+  WHERE user_id = @user_id
+  ORDER BY created_at DESC, id DESC;
+END
+GO
+
+-- Moves the current hash to the history and keeps only the newest @keep rows of it
+CREATE OR ALTER PROCEDURE dbo.update_user_password
+  @user_id INT,
+  @password NVARCHAR(255),
+  @keep INT
+AS
+BEGIN
+  SET NOCOUNT ON;
+
+  INSERT INTO dbo.password_history (user_id, password)
+  SELECT id, password FROM dbo.users WHERE id = @user_id AND @keep > 0;
+
+  DELETE FROM dbo.password_history
+  WHERE user_id = @user_id
+    AND id NOT IN (
+      SELECT TOP (@keep) id
+      FROM dbo.password_history
+      WHERE user_id = @user_id
+      ORDER BY created_at DESC, id DESC
+    );
+
+  SET NOCOUNT OFF;
+  UPDATE dbo.users SET password = @password, updated_at = SYSUTCDATETIME() WHERE id = @user_id;
+END
+GO
@@ -0,0 +1,26 @@
+-- Registration figures for the user report, see /admin/reports/users
+
+IF NOT EXISTS (SELECT 1 FROM sys.indexes WHERE name = N'ix_users_created_at' AND object_id = OBJECT_ID(N'dbo.users'))
+CREATE INDEX ix_users_created_at ON dbo.users (created_at DESC);
+GO
+
+CREATE OR ALTER PROCEDURE dbo.select_registration_counts
+AS
+BEGIN
+  SELECT
+    ISNULL(SUM(CASE WHEN created_at >= DATEADD(DAY, -7, SYSUTCDATETIME()) THEN 1 ELSE 0 END), 0) AS last_7_days,
+    COUNT(*) AS last_30_days
+  FROM dbo.users
+  WHERE created_at >= DATEADD(DAY, -30, SYSUTCDATETIME());
+END
+GO
+
+CREATE OR ALTER PROCEDURE dbo.select_recent_registrations
+  @count INT
+AS
+BEGIN
+  SELECT TOP (@count) *
+  FROM dbo.users
+  ORDER BY created_at DESC, id DESC;
+END
+GO
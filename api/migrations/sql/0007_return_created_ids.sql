@@ -0,0 +1,28 @@
+-- The create procs answer with the id of the new row, see `SqlRepo::execute_insert_returning_id`.
+-- SCOPE_IDENTITY only sees this proc's insert, not ones made by triggers.
+
+CREATE OR ALTER PROCEDURE dbo.create_user
+  @name NVARCHAR(100),
+  @user_name NVARCHAR(50),
+  @email NVARCHAR(255),
+  @password NVARCHAR(255),
+  @role NVARCHAR(50)
+AS
+BEGIN
+  SET NOCOUNT ON;
+  INSERT INTO dbo.users (name, user_name, email, password, role)
+  VALUES (@name, @user_name, @email, @password, @role);
+  SELECT CAST(SCOPE_IDENTITY() AS INT) AS id;
+END
+GO
+
+CREATE OR ALTER PROCEDURE dbo.create_role
+  @name NVARCHAR(50),
+  @description NVARCHAR(255)
+AS
+BEGIN
+  SET NOCOUNT ON;
+  INSERT INTO dbo.roles (name, description) VALUES (@name, NULLIF(@description, ''));
+  SELECT CAST(SCOPE_IDENTITY() AS INT) AS id;
+END
+GO
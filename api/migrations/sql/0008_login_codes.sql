@@ -0,0 +1,57 @@
+-- One-time codes emailed by /auth/request_otp. A user has at most one pending code, its hash is
+-- stored like a password and failed guesses are counted so the code can be given up on.
+
+IF OBJECT_ID(N'dbo.login_codes', N'U') IS NULL
+CREATE TABLE dbo.login_codes (
+  user_id INT NOT NULL PRIMARY KEY REFERENCES dbo.users (id),
+  code_hash NVARCHAR(255) NOT NULL,
+  attempts INT NOT NULL DEFAULT 0,
+  expires_at DATETIME2 NOT NULL,
+  created_at DATETIME2 NOT NULL DEFAULT SYSUTCDATETIME()
+);
+GO
+
+-- Replaces any pending code of the user
+CREATE OR ALTER PROCEDURE dbo.create_login_code
+  @user_id INT,
+  @code_hash NVARCHAR(255),
+  @expiration_minutes INT
+AS
+BEGIN
+  DELETE FROM dbo.login_codes WHERE user_id = @user_id;
+  INSERT INTO dbo.login_codes (user_id, code_hash, expires_at)
+  VALUES (@user_id, @code_hash, DATEADD(MINUTE, @expiration_minutes, SYSUTCDATETIME()));
+END
+GO
+
+CREATE OR ALTER PROCEDURE dbo.select_login_code
+  @user_id INT
+AS
+BEGIN
+  SELECT user_id, code_hash, attempts, expires_at FROM dbo.login_codes WHERE user_id = @user_id;
+END
+GO
+
+CREATE OR ALTER PROCEDURE dbo.increment_login_code_attempts
+  @user_id INT
+AS
+BEGIN
+  UPDATE dbo.login_codes SET attempts = attempts + 1 WHERE user_id = @user_id;
+END
+GO
+
+CREATE OR ALTER PROCEDURE dbo.delete_login_code
+  @user_id INT
+AS
+BEGIN
+  DELETE FROM dbo.login_codes WHERE user_id = @user_id;
+END
+GO
+
+-- Used by the scheduler's purge_expired_tokens task
+CREATE OR ALTER PROCEDURE dbo.purge_expired_login_codes
+AS
+BEGIN
+  DELETE FROM dbo.login_codes WHERE expires_at < SYSUTCDATETIME();
+END
+GO
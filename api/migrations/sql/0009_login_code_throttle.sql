@@ -0,0 +1,53 @@
+-- Requesting a new login code no longer resets the wrong guesses counted against the user, and a
+-- user gets at most @max_codes codes per window so /auth/request_otp can't flood their mailbox.
+-- The row of a user outlives their code until the window it was opened in is over.
+
+IF COL_LENGTH(N'dbo.login_codes', N'issued_count') IS NULL
+ALTER TABLE dbo.login_codes ADD
+  issued_count INT NOT NULL DEFAULT 1,
+  window_started_at DATETIME2 NOT NULL DEFAULT SYSUTCDATETIME();
+GO
+
+-- Replaces the pending code of the user, keeping its attempts and issued count while the window
+-- lasts. Answers issued = 0 when the user already got @max_codes codes in it
+CREATE OR ALTER PROCEDURE dbo.create_login_code
+  @user_id INT,
+  @code_hash NVARCHAR(255),
+  @expiration_minutes INT,
+  @max_codes INT,
+  @window_minutes INT
+AS
+BEGIN
+  SET NOCOUNT ON;
+  DECLARE @now DATETIME2 = SYSUTCDATETIME();
+
+  DELETE FROM dbo.login_codes
+  WHERE user_id = @user_id AND window_started_at <= DATEADD(MINUTE, -@window_minutes, @now);
+
+  IF EXISTS (SELECT 1 FROM dbo.login_codes WHERE user_id = @user_id)
+  BEGIN
+    UPDATE dbo.login_codes
+    SET code_hash = @code_hash,
+        expires_at = DATEADD(MINUTE, @expiration_minutes, @now),
+        issued_count = issued_count + 1
+    WHERE user_id = @user_id AND issued_count < @max_codes;
+    SELECT CAST(@@ROWCOUNT AS BIT) AS issued;
+    RETURN;
+  END
+
+  INSERT INTO dbo.login_codes (user_id, code_hash, expires_at, window_started_at)
+  VALUES (@user_id, @code_hash, DATEADD(MINUTE, @expiration_minutes, @now), @now);
+  SELECT CAST(1 AS BIT) AS issued;
+END
+GO
+
+-- Used by the scheduler's purge_expired_tokens task, rows still counting towards a window stay
+CREATE OR ALTER PROCEDURE dbo.purge_expired_login_codes
+  @window_minutes INT
+AS
+BEGIN
+  DECLARE @now DATETIME2 = SYSUTCDATETIME();
+  DELETE FROM dbo.login_codes
+  WHERE expires_at < @now AND window_started_at <= DATEADD(MINUTE, -@window_minutes, @now);
+END
+GO
@@ -0,0 +1,13 @@
+-- The three parts of the user report in one round trip, read by AdminRepo::get_user_report as
+-- three result sets in this order
+
+CREATE OR ALTER PROCEDURE dbo.select_user_report
+  @recent_count INT
+AS
+BEGIN
+  SET NOCOUNT ON;
+  EXEC dbo.select_user_role_counts;
+  EXEC dbo.select_registration_counts;
+  EXEC dbo.select_recent_registrations @recent_count;
+END
+GO
@@ -0,0 +1,44 @@
+-- Tokens emailed by /user/forgot_password. A user has at most one pending reset, only the SHA-256
+-- of its token is stored so a leaked table can't be used to take over accounts.
+
+IF OBJECT_ID(N'dbo.password_reset_requests', N'U') IS NULL
+CREATE TABLE dbo.password_reset_requests (
+  user_id INT NOT NULL PRIMARY KEY REFERENCES dbo.users (id),
+  token_hash NVARCHAR(64) NOT NULL UNIQUE,
+  expires_at DATETIME2 NOT NULL,
+  created_at DATETIME2 NOT NULL DEFAULT SYSUTCDATETIME()
+);
+GO
+
+-- Replaces any pending reset of the user
+CREATE OR ALTER PROCEDURE dbo.create_password_reset_request
+  @user_id INT,
+  @token_hash NVARCHAR(64),
+  @expiration_minutes INT
+AS
+BEGIN
+  DELETE FROM dbo.password_reset_requests WHERE user_id = @user_id;
+  INSERT INTO dbo.password_reset_requests (user_id, token_hash, expires_at)
+  VALUES (@user_id, @token_hash, DATEADD(MINUTE, @expiration_minutes, SYSUTCDATETIME()));
+END
+GO
+
+-- Deletes the reset and answers its user in one statement, so two requests racing with the same
+-- token can't both get it. Answers no row when the token is unknown or expired
+CREATE OR ALTER PROCEDURE dbo.consume_password_reset_request
+  @token_hash NVARCHAR(64)
+AS
+BEGIN
+  DELETE FROM dbo.password_reset_requests
+  OUTPUT deleted.user_id
+  WHERE token_hash = @token_hash AND expires_at > SYSUTCDATETIME();
+END
+GO
+
+-- Used by the scheduler's purge_expired_tokens task
+CREATE OR ALTER PROCEDURE dbo.purge_expired_password_reset_requests
+AS
+BEGIN
+  DELETE FROM dbo.password_reset_requests WHERE expires_at < SYSUTCDATETIME();
+END
+GO
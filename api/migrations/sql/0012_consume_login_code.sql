@@ -0,0 +1,23 @@
+-- Signing in with a code removes it in the statement that checks it is still the pending code,
+-- unexpired and not given up on, so two requests racing with the same code can't both sign in.
+-- Answers consumed = 0 when another request got there first
+
+CREATE OR ALTER PROCEDURE dbo.consume_login_code
+  @user_id INT,
+  @code_hash NVARCHAR(255),
+  @max_attempts INT
+AS
+BEGIN
+  SET NOCOUNT ON;
+  DELETE FROM dbo.login_codes
+  WHERE user_id = @user_id
+    AND code_hash = @code_hash
+    AND expires_at >= SYSUTCDATETIME()
+    AND attempts < @max_attempts;
+  SELECT CAST(@@ROWCOUNT AS BIT) AS consumed;
+END
+GO
+
+-- Replaced by consume_login_code
+DROP PROCEDURE IF EXISTS dbo.delete_login_code;
+GO
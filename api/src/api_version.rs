@@ -0,0 +1,122 @@
+use actix_web::{middleware::DefaultHeaders, web};
+
+use crate::features::{
+  admin::admin_route::admin_routes,
+  audit::audit_route::audit_routes,
+  auth::auth_route::auth_routes,
+  batch::batch_route::batch_routes,
+  dev::dev_route::dev_routes,
+  health_check::{health_checker_handler, meta_handler, readiness_handler},
+  oidc::oidc_route::introspect_route,
+  operations::operations_route::operations_routes,
+  permissions::permissions_route::{permission_routes, permissions_routes},
+  realtime::realtime_route::{events_routes, realtime_routes},
+  roles::roles_route::{role_routes, roles_routes},
+  saml::saml_route::saml_routes,
+  users::user_route::{user_routes, users_routes},
+};
+
+/// Versions served side by side, each under `/api/<name>`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ApiVersion {
+  V1,
+  V2,
+}
+
+impl ApiVersion {
+  pub const ALL: [ApiVersion; 2] = [ApiVersion::V1, ApiVersion::V2];
+  pub const LATEST: ApiVersion = ApiVersion::V2;
+
+  pub fn name(&self) -> &'static str {
+    match self {
+      ApiVersion::V1 => "v1",
+      ApiVersion::V2 => "v2",
+    }
+  }
+
+  pub fn from_name(name: &str) -> Option<ApiVersion> {
+    ApiVersion::ALL
+      .into_iter()
+      .find(|version| version.name() == name)
+  }
+
+  /// Version a request path such as `/api/v1/roles` is served under.
+  pub fn of_path(path: &str) -> Option<ApiVersion> {
+    path
+      .strip_prefix("/api/")?
+      .split('/')
+      .next()
+      .and_then(ApiVersion::from_name)
+  }
+
+  pub fn prefix(&self) -> String {
+    format!("/api/{}", self.name())
+  }
+
+  // Superseded versions keep working but point clients at their successor
+  pub fn successor(&self) -> Option<ApiVersion> {
+    match self {
+      ApiVersion::V1 => Some(ApiVersion::V2),
+      ApiVersion::V2 => None,
+    }
+  }
+
+  pub fn is_deprecated(&self) -> bool {
+    self.successor().is_some()
+  }
+
+  fn deprecation_headers(&self) -> DefaultHeaders {
+    match self.successor() {
+      Some(successor) => DefaultHeaders::new().add(("Deprecation", "true")).add((
+        "Link",
+        format!("<{}>; rel=\"successor-version\"", successor.prefix()),
+      )),
+      None => DefaultHeaders::new(),
+    }
+  }
+}
+
+/// Flags a legacy route kept only as an alias of its resource-style replacement.
+pub fn deprecated_alias() -> DefaultHeaders {
+  DefaultHeaders::new().add(("Deprecation", "true"))
+}
+
+// Routes shared by every version. A version that changes a DTO registers its own handler
+// for that route here, matched on `version`, and leaves the others untouched.
+fn configure_version(cfg: &mut web::ServiceConfig, _version: ApiVersion, is_dev: bool) {
+  cfg
+    .service(health_checker_handler)
+    .service(readiness_handler)
+    .service(meta_handler)
+    .service(saml_routes())
+    .service(introspect_route())
+    .service(auth_routes())
+    .service(users_routes())
+    .service(roles_routes())
+    .service(permissions_routes())
+    .service(user_routes())
+    .service(role_routes().wrap(deprecated_alias()))
+    .service(permission_routes().wrap(deprecated_alias()))
+    .service(admin_routes())
+    .service(audit_routes())
+    .service(batch_routes())
+    .service(operations_routes())
+    .service(realtime_routes())
+    .service(events_routes());
+  // Dev-only routes, never mounted outside dev environments
+  if is_dev {
+    cfg.service(dev_routes());
+  }
+}
+
+/// Mounts `/api/<version>` for every version, deprecated ones answer with
+/// `Deprecation` and `Link: rel="successor-version"` headers.
+pub fn configure(cfg: &mut web::ServiceConfig, is_dev: bool) {
+  for version in ApiVersion::ALL {
+    cfg.service(
+      web::scope(&version.prefix())
+        .wrap(version.deprecation_headers())
+        .configure(|cfg| configure_version(cfg, version, is_dev)),
+    );
+  }
+}
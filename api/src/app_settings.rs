@@ -1,23 +1,1313 @@
+use std::{
+  collections::{BTreeMap, HashMap},
+  net::IpAddr,
+  path::Path,
+};
+
+use actix_web::http::header::{HeaderName, HeaderValue};
+use anyhow::{Result, bail};
+use chrono::NaiveTime;
+use ipnet::IpNet;
 use serde::Deserialize;
 
+use crate::{
+  notifications::{ChannelKind, NotificationKind},
+  utils::custom_claims::{self, CustomClaims},
+};
+
+// Read from the working directory unless `--config` says otherwise
+pub const CONFIG_PATH: &str = "appsettings.json";
+
 #[derive(Deserialize, Clone)]
 pub struct AppSetting {
+  #[serde(default = "default_environment")]
+  pub environment: String,
   pub rust_log: String,
   pub server: ServerSetting,
   pub database: DatabaseSetting,
   pub jwt: JwtSetting,
   pub cookie: CookieSetting,
+  #[serde(default)]
+  pub auth: AuthSetting,
+  #[serde(default)]
+  pub quota: QuotaSetting,
+  #[serde(default)]
+  pub notification: NotificationSetting,
+  #[serde(default)]
+  pub feature_flags: HashMap<String, FeatureFlagSetting>,
+  #[serde(default)]
+  pub cors: CorsSetting,
+  #[serde(default)]
+  pub rate_limit: RateLimitSetting,
+  #[serde(default)]
+  pub limits: PayloadLimitSetting,
+  #[serde(default)]
+  pub security: SecuritySetting,
+  #[serde(default)]
+  pub access_log: AccessLogSetting,
+  #[serde(default)]
+  pub metrics: MetricsSetting,
+  #[serde(default)]
+  pub password: PasswordSetting,
+  #[serde(default)]
+  pub otp_login: OtpLoginSetting,
+  #[serde(default)]
+  pub captcha: CaptchaSetting,
+  #[serde(default)]
+  pub docs: DocsSetting,
+  #[serde(default)]
+  pub scheduler: SchedulerSetting,
+  #[serde(default)]
+  pub mailer: MailerSetting,
+  #[serde(default)]
+  pub storage: StorageSetting,
+  #[serde(default)]
+  pub operations: OperationSetting,
+  #[serde(default)]
+  pub security_log: SecurityLogSetting,
+  /// Shared cache for hot lookups, left out to run without one
+  pub redis: Option<RedisSetting>,
+  /// SAML single sign-on with an enterprise IdP, left out to turn it off
+  #[serde(default)]
+  pub saml: Option<SamlSetting>,
+}
+
+/// The part of the config that `config_watcher` swaps in without a restart.
+#[derive(Clone)]
+pub struct RuntimeSetting {
+  pub rust_log: String,
+  pub feature_flags: HashMap<String, FeatureFlagSetting>,
+  pub cors: CorsSetting,
+  pub rate_limit: RateLimitSetting,
+}
+
+impl From<&AppSetting> for RuntimeSetting {
+  fn from(config: &AppSetting) -> Self {
+    Self {
+      rust_log: config.rust_log.clone(),
+      feature_flags: config.feature_flags.clone(),
+      cors: config.cors.clone(),
+      rate_limit: config.rate_limit.clone(),
+    }
+  }
+}
+
+// Longest token lifetime accepted, 30 days
+const MAX_EXPIRATION_MINUTES: usize = 30 * 24 * 60;
+// Compliance policies rarely ask for more than the last 24 passwords
+const MAX_PASSWORD_HISTORY: u32 = 24;
+// Keeps DATEADD in the purge query within the range of DATETIME2
+const MAX_SOFT_DELETE_RETENTION_DAYS: u32 = 100 * 365;
+// Keys that name the server in an ADO.NET style connection string
+const SERVER_KEYS: [&str; 4] = ["server", "data source", "address", "addr"];
+// ADO.NET keys for Azure AD sign-in, which the driver ignores and then signs in without
+// credentials, `azure_ad` is used instead
+const UNSUPPORTED_AUTH_KEYS: [&str; 2] = ["authentication", "access token"];
+
+// `key` is where the connection sits in the config, e.g. `database.sql_server`
+fn check_conn_str(key: &str, conn_str: &str) -> Option<String> {
+  if conn_str.trim().is_empty() {
+    return Some(format!("{}.conn_str is empty", key));
+  }
+  let mut has_server = false;
+  for part in conn_str.split(';').filter(|part| !part.trim().is_empty()) {
+    let Some((name, value)) = part.split_once('=') else {
+      return Some(format!(
+        "{}.conn_str has '{}' without a '=', expected key=value pairs",
+        key,
+        part.trim()
+      ));
+    };
+    let name = name.trim().to_lowercase();
+    if SERVER_KEYS.contains(&name.as_str()) && !value.trim().is_empty() {
+      has_server = true;
+    }
+    if UNSUPPORTED_AUTH_KEYS.contains(&name.as_str()) {
+      return Some(format!(
+        "{}.conn_str sets '{}', configure Azure AD authentication with {}.azure_ad instead",
+        key, name, key
+      ));
+    }
+  }
+  if has_server {
+    None
+  } else {
+    Some(format!(
+      "{}.conn_str does not name a server (Server=host,port)",
+      key
+    ))
+  }
+}
+
+fn check_connection(key: &str, db: &DatabaseConnectionInfo, problems: &mut Vec<String>) {
+  problems.extend(check_conn_str(key, &db.conn_str));
+  if db.pool_name.trim().is_empty() {
+    problems.push(format!("{}.pool_name is empty", key));
+  }
+  if db.pool_size == 0 {
+    problems.push(format!("{}.pool_size must be at least 1", key));
+  }
+  if db.checkout_timeout_ms == 0 {
+    problems.push(format!("{}.checkout_timeout_ms must be at least 1", key));
+  }
+  if db.retry_attempts == 0 {
+    problems.push(format!("{}.retry_attempts must be at least 1", key));
+  }
+  if db
+    .failover_partner
+    .as_ref()
+    .is_some_and(|partner| partner.trim().is_empty())
+  {
+    problems.push(format!("{}.failover_partner is empty", key));
+  }
+  if let Some(AzureAdSetting {
+    token_source: AzureAdTokenSource::Secret { token },
+    ..
+  }) = &db.azure_ad
+    && token.trim().is_empty()
+  {
+    problems.push(format!("{}.azure_ad.token_source.token is empty", key));
+  }
+  if let Some(ca_path) = &db.tls.ca_path {
+    if !Path::new(ca_path).is_file() {
+      problems.push(format!("{}.tls.ca_path '{}' does not exist", key, ca_path));
+    }
+    // The sql client can't both trust every certificate and check one against a CA
+    if db.tls.verify_server == Some(false) {
+      problems.push(format!(
+        "{}.tls.ca_path needs verify_server, it is set to false",
+        key
+      ));
+    }
+  }
+}
+
+impl AppSetting {
+  /// Checks the whole config and reports every problem at once, run after secrets resolve.
+  pub fn validate(&self) -> Result<()> {
+    let mut problems: Vec<String> = Vec::new();
+
+    if self.server.host.trim().is_empty() {
+      problems.push("server.host is empty".to_string());
+    }
+    if self.server.port == 0 {
+      problems.push("server.port must be between 1 and 65535".to_string());
+    }
+    if let Some(tls) = &self.server.tls {
+      for (key, path) in [("cert_path", &tls.cert_path), ("key_path", &tls.key_path)] {
+        if !Path::new(path).is_file() {
+          problems.push(format!("server.tls.{} '{}' does not exist", key, path));
+        }
+      }
+      if tls.redirect_http_port == Some(self.server.port) {
+        problems.push("server.tls.redirect_http_port must differ from server.port".to_string());
+      }
+      match &tls.public_host {
+        None if tls.redirect_http_port.is_some() => {
+          problems.push("server.tls.redirect_http_port needs server.tls.public_host".to_string());
+        }
+        Some(host)
+          if !url::Url::parse(&format!("https://{}/", host)).is_ok_and(|url| {
+            url.host_str().is_some_and(|h| h.eq_ignore_ascii_case(host)) && url.port().is_none()
+          }) =>
+        {
+          problems.push(format!(
+            "server.tls.public_host '{}' is not a bare host name",
+            host
+          ));
+        }
+        _ => {}
+      }
+      if let Some(client_auth) = &tls.client_auth {
+        if !Path::new(&client_auth.ca_path).is_file() {
+          problems.push(format!(
+            "server.tls.client_auth.ca_path '{}' does not exist",
+            client_auth.ca_path
+          ));
+        }
+        if client_auth
+          .principals
+          .values()
+          .any(|user| user.trim().is_empty())
+        {
+          problems.push("server.tls.client_auth.principals has an empty user name".to_string());
+        }
+      }
+    }
+
+    check_connection(
+      "database.sql_server",
+      &self.database.sql_server,
+      &mut problems,
+    );
+    let mut pool_names = vec![&self.database.sql_server.pool_name];
+    for (name, db) in &self.database.databases {
+      let key = format!("database.databases.{}", name);
+      check_connection(&key, db, &mut problems);
+      // Pools are looked up by name, a second one would replace the first
+      if pool_names.contains(&&db.pool_name) {
+        problems.push(format!(
+          "{}.pool_name '{}' is already used by another database",
+          key, db.pool_name
+        ));
+      }
+      pool_names.push(&db.pool_name);
+    }
+    if self.database.startup_max_wait_secs > 0 && self.database.startup_retry_delay_ms == 0 {
+      problems.push("database.startup_retry_delay_ms must be at least 1".to_string());
+    }
+    if let Some(tenants) = &self.database.tenants {
+      if !tenants.conn_str_template.contains("{tenant}") {
+        problems.push("database.tenants.conn_str_template has no {tenant} placeholder".to_string());
+      }
+      problems.extend(check_conn_str(
+        "database.tenants",
+        &tenants.conn_str_template.replace("{tenant}", "x"),
+      ));
+      if tenants.pool_size == 0 {
+        problems.push("database.tenants.pool_size must be at least 1".to_string());
+      }
+      if tenants.max_pools == 0 {
+        problems.push("database.tenants.max_pools must be at least 1".to_string());
+      }
+    }
+
+    if self.jwt.secret_key.trim().is_empty() {
+      problems.push("jwt.secret_key is empty".to_string());
+    }
+    if !(1..=MAX_EXPIRATION_MINUTES).contains(&self.jwt.expiration_minutes) {
+      problems.push(format!(
+        "jwt.expiration_minutes must be between 1 and {}",
+        MAX_EXPIRATION_MINUTES
+      ));
+    }
+    if !(1..=MAX_EXPIRATION_MINUTES).contains(&self.jwt.impersonation_minutes) {
+      problems.push(format!(
+        "jwt.impersonation_minutes must be between 1 and {}",
+        MAX_EXPIRATION_MINUTES
+      ));
+    }
+    let accepted = std::iter::once(("accepted", &self.jwt.accepted)).chain(
+      self
+        .jwt
+        .accepted_by_environment
+        .values()
+        .map(|accepted| ("accepted_by_environment", accepted)),
+    );
+    for (key, accepted) in accepted {
+      if accepted
+        .issuers
+        .iter()
+        .chain(&accepted.audiences)
+        .any(|value| value.trim().is_empty())
+      {
+        problems.push(format!("jwt.{} has an empty issuer or audience", key));
+      }
+    }
+    for name in self.jwt.static_claims.keys() {
+      if custom_claims::is_reserved(name) {
+        problems.push(format!(
+          "jwt.static_claims can't set the reserved claim '{}'",
+          name
+        ));
+      }
+    }
+    if HeaderName::from_bytes(self.jwt.device_binding.client_hint_header.as_bytes()).is_err() {
+      problems.push(format!(
+        "jwt.device_binding.client_hint_header '{}' is not a valid header name",
+        self.jwt.device_binding.client_hint_header
+      ));
+    }
+    if self.cookie.name.trim().is_empty() {
+      problems.push("cookie.name is empty".to_string());
+    }
+    if self.auth.credential_sources.is_empty() {
+      problems.push("auth.credential_sources is empty".to_string());
+    }
+    for source in &self.auth.credential_sources {
+      if let CredentialSource::Header { name } = source
+        && HeaderName::from_bytes(name.as_bytes()).is_err()
+      {
+        problems.push(format!(
+          "auth.credential_sources header '{}' is not a valid header name",
+          name
+        ));
+      }
+    }
+    for client in &self.auth.introspection_clients {
+      if client.client_id.trim().is_empty() || client.client_id.contains(':') {
+        problems.push(format!(
+          "auth.introspection_clients client_id '{}' must be non-empty and without ':'",
+          client.client_id
+        ));
+      }
+      if client.client_secret.is_empty() {
+        problems.push(format!(
+          "auth.introspection_clients '{}' has an empty client_secret",
+          client.client_id
+        ));
+      }
+    }
+    if let Some(public_url) = &self.auth.public_url
+      && !url::Url::parse(public_url).is_ok_and(|url| url.scheme().starts_with("http"))
+    {
+      problems.push(format!(
+        "auth.public_url '{}' is not an http(s) URL",
+        public_url
+      ));
+    }
+
+    let limits = &self.limits;
+    if limits.json_max_bytes == 0 || limits.payload_max_bytes == 0 || limits.json_max_depth == 0 {
+      problems.push(
+        "limits.json_max_bytes, payload_max_bytes and json_max_depth must be at least 1"
+          .to_string(),
+      );
+    }
+
+    let security = &self.security;
+    let header_values = [
+      ("frame_options", Some(&security.frame_options)),
+      ("referrer_policy", Some(&security.referrer_policy)),
+      (
+        "content_security_policy",
+        security.content_security_policy.as_ref(),
+      ),
+    ];
+    for (key, value) in header_values {
+      if value.is_some_and(|value| HeaderValue::from_str(value).is_err()) {
+        problems.push(format!("security.{} is not a valid header value", key));
+      }
+    }
+    if security.login_tarpit_enabled
+      && (security.login_tarpit_window_secs == 0
+        || security.login_tarpit_base_delay_ms > security.login_tarpit_max_delay_ms)
+    {
+      problems.push(
+        "security.login_tarpit_window_secs must be at least 1 and login_tarpit_base_delay_ms at most login_tarpit_max_delay_ms"
+          .to_string(),
+      );
+    }
+    if security.login_tarpit_block_after > 0
+      && security.login_tarpit_block_after <= security.login_tarpit_free_failures
+    {
+      problems.push(
+        "security.login_tarpit_block_after must be above login_tarpit_free_failures".to_string(),
+      );
+    }
+
+    if self.password.history_size > MAX_PASSWORD_HISTORY {
+      problems.push(format!(
+        "password.history_size must be at most {}",
+        MAX_PASSWORD_HISTORY
+      ));
+    }
+
+    let otp_login = &self.otp_login;
+    if !(4..=10).contains(&otp_login.code_length) {
+      problems.push("otp_login.code_length must be 4-10".to_string());
+    }
+    if otp_login.expiration_minutes < 1
+      || otp_login.max_attempts < 1
+      || otp_login.max_codes_per_window < 1
+    {
+      problems.push(
+        "otp_login.expiration_minutes, max_attempts and max_codes_per_window must be at least 1"
+          .to_string(),
+      );
+    }
+    if otp_login.window_minutes < otp_login.expiration_minutes {
+      problems.push("otp_login.window_minutes must be at least expiration_minutes".to_string());
+    }
+    if otp_login.enabled
+      && !self
+        .notification
+        .routes
+        .contains_key(&NotificationKind::LoginCode)
+    {
+      problems
+        .push("otp_login.enabled needs a notification.routes entry for login_code".to_string());
+    }
+
+    if self.docs.access == DocsAccess::Basic {
+      match &self.docs.basic_auth {
+        Some(basic) if basic.username.trim().is_empty() || basic.password.is_empty() => {
+          problems.push("docs.basic_auth.username and password must not be empty".to_string());
+        }
+        None => problems.push("docs.basic_auth is required when docs.access is basic".to_string()),
+        _ => {}
+      }
+    }
+
+    let captcha = &self.captcha;
+    if captcha.enabled_for(&self.environment) {
+      if captcha.secret_key.is_empty() {
+        problems.push("captcha.secret_key is empty".to_string());
+      }
+      if HeaderName::from_bytes(captcha.token_header.as_bytes()).is_err() {
+        problems.push(format!(
+          "captcha.token_header '{}' is not a valid header name",
+          captcha.token_header
+        ));
+      }
+      if captcha.failed_login_window_secs == 0 {
+        problems.push("captcha.failed_login_window_secs must be at least 1".to_string());
+      }
+      if captcha
+        .min_score
+        .is_some_and(|score| !(0.0..=1.0).contains(&score))
+      {
+        problems.push("captcha.min_score must be between 0 and 1".to_string());
+      }
+    }
+
+    match &self.storage.backend {
+      StorageBackendSetting::Local { root } if root.trim().is_empty() => {
+        problems.push("storage.backend.root is empty".to_string());
+      }
+      StorageBackendSetting::S3(s3) => {
+        if !url::Url::parse(&s3.endpoint).is_ok_and(|url| url.scheme().starts_with("http")) {
+          problems.push(format!(
+            "storage.backend.endpoint '{}' is not an http(s) URL",
+            s3.endpoint
+          ));
+        }
+        let required = [
+          ("bucket", &s3.bucket),
+          ("region", &s3.region),
+          ("access_key_id", &s3.access_key_id),
+          ("secret_access_key", &s3.secret_access_key),
+        ];
+        for (key, value) in required {
+          if value.trim().is_empty() {
+            problems.push(format!("storage.backend.{} is empty", key));
+          }
+        }
+      }
+      _ => {}
+    }
+    let buckets = &self.access_log.latency_buckets_ms;
+    if buckets.is_empty() || buckets.windows(2).any(|pair| pair[0] >= pair[1]) {
+      problems.push("access_log.latency_buckets_ms must be non-empty and ascending".to_string());
+    }
+    if self.storage.max_avatar_bytes == 0 {
+      problems.push("storage.max_avatar_bytes must be at least 1".to_string());
+    }
+    let images = &self.storage.images;
+    if images
+      .variant_sizes
+      .iter()
+      .any(|&size| size == 0 || size > images.max_dimension)
+    {
+      problems.push(format!(
+        "storage.images.variant_sizes must be between 1 and max_dimension ({})",
+        images.max_dimension
+      ));
+    }
+    if !(1..=100).contains(&images.jpeg_quality) {
+      problems.push("storage.images.jpeg_quality must be between 1 and 100".to_string());
+    }
+    if self.operations.max_concurrent == 0 {
+      problems.push("operations.max_concurrent must be at least 1".to_string());
+    }
+    if self.operations.max_pending < self.operations.max_concurrent {
+      problems.push("operations.max_pending must be at least max_concurrent".to_string());
+    }
+
+    let security_log = &self.security_log;
+    if security_log.queue_capacity == 0 || security_log.max_attempts == 0 {
+      problems.push("security_log.queue_capacity and max_attempts must be at least 1".to_string());
+    }
+    match &security_log.sink {
+      Some(SecurityLogSink::File { path }) if path.trim().is_empty() => {
+        problems.push("security_log.sink.path is required".to_string());
+      }
+      Some(SecurityLogSink::Syslog { address, .. }) if address.trim().is_empty() => {
+        problems.push("security_log.sink.address is required".to_string());
+      }
+      Some(SecurityLogSink::Webhook(webhook)) if webhook.url.trim().is_empty() => {
+        problems.push("security_log.sink.url is required".to_string());
+      }
+      _ => {}
+    }
+
+    if let Some(saml) = &self.saml {
+      let required = [
+        ("entity_id", &saml.entity_id),
+        ("acs_url", &saml.acs_url),
+        ("idp_entity_id", &saml.idp_entity_id),
+        ("idp_sso_url", &saml.idp_sso_url),
+      ];
+      for (key, value) in required {
+        if value.trim().is_empty() {
+          problems.push(format!("saml.{} is empty", key));
+        }
+      }
+      if !Path::new(&saml.idp_cert_path).is_file() {
+        problems.push(format!(
+          "saml.idp_cert_path '{}' does not exist",
+          saml.idp_cert_path
+        ));
+      }
+    }
+
+    let rate_limit = &self.rate_limit;
+    if rate_limit.enabled && (rate_limit.requests_per_window == 0 || rate_limit.window_secs == 0) {
+      problems.push(
+        "rate_limit.requests_per_window and rate_limit.window_secs must be at least 1".to_string(),
+      );
+    }
+    // Without them any client could name its own address
+    if rate_limit.use_forwarded_for && rate_limit.trusted_proxies.is_empty() {
+      problems.push("rate_limit.use_forwarded_for needs rate_limit.trusted_proxies".to_string());
+    }
+
+    let mailer = &self.mailer;
+    if mailer.from.parse::<lettre::message::Mailbox>().is_err() {
+      problems.push(format!(
+        "mailer.from '{}' is not an email address",
+        mailer.from
+      ));
+    }
+    if !mailer.dev_mode {
+      match &mailer.smtp {
+        Some(smtp) if smtp.host.trim().is_empty() => {
+          problems.push("mailer.smtp.host is empty".to_string());
+        }
+        None => problems.push("mailer.smtp is required unless mailer.dev_mode is set".to_string()),
+        _ => {}
+      }
+    }
+
+    for (name, task) in self.scheduler.tasks() {
+      match &task.at {
+        Some(at) if NaiveTime::parse_from_str(at, "%H:%M").is_err() => {
+          problems.push(format!("scheduler.{}.at must be HH:MM", name));
+        }
+        None if task.interval_secs == 0 => {
+          problems.push(format!(
+            "scheduler.{}.interval_secs must be at least 1",
+            name
+          ));
+        }
+        _ => {}
+      }
+    }
+    if self.scheduler.soft_delete_retention_days > MAX_SOFT_DELETE_RETENTION_DAYS {
+      problems.push(format!(
+        "scheduler.soft_delete_retention_days must be at most {}",
+        MAX_SOFT_DELETE_RETENTION_DAYS
+      ));
+    }
+
+    for (name, flag) in &self.feature_flags {
+      if flag.percentage.is_some_and(|percentage| percentage > 100) {
+        problems.push(format!("feature_flags.{}.percentage must be 0-100", name));
+      }
+    }
+    for (kind, channel) in &self.notification.routes {
+      let configured = match channel {
+        ChannelKind::Email => true,
+        ChannelKind::Webhook => self.notification.webhook.is_some(),
+        ChannelKind::Sms => self.notification.sms.is_some(),
+      };
+      if !configured {
+        problems.push(format!(
+          "notification.routes sends {:?} to {:?}, which is not configured",
+          kind, channel
+        ));
+      }
+    }
+
+    if problems.is_empty() {
+      return Ok(());
+    }
+    bail!(
+      "Invalid configuration:\n{}",
+      problems
+        .iter()
+        .map(|problem| format!("  - {}", problem))
+        .collect::<Vec<_>>()
+        .join("\n")
+    )
+  }
+}
+
+// Default value for environment, dev-only features stay off unless explicitly enabled
+fn default_environment() -> String {
+  "production".to_string()
+}
+
+impl AppSetting {
+  pub fn is_dev(&self) -> bool {
+    matches!(
+      self.environment.to_lowercase().as_str(),
+      "dev" | "development" | "local"
+    )
+  }
+}
+
+// Origins are exact (`https://app.example.com`), `*`, a single wildcard
+// (`https://*.example.com`) or a regex prefixed with `regex:`
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct CorsSetting {
+  pub allowed_origins: Vec<String>,
+  /// Replaces `allowed_origins` when the key matches `environment`
+  pub origins_by_environment: HashMap<String, Vec<String>>,
+  pub allowed_methods: Vec<String>,
+  pub allowed_headers: Vec<String>,
+  pub supports_credentials: bool,
+  /// How long browsers may cache a preflight response, `None` leaves it to the browser
+  pub max_age_secs: Option<usize>,
+}
+
+impl Default for CorsSetting {
+  fn default() -> Self {
+    CorsSetting {
+      allowed_origins: vec![
+        "http://localhost:3000".to_string(),
+        "http://localhost:8000".to_string(),
+      ],
+      origins_by_environment: HashMap::new(),
+      allowed_methods: vec!["GET".to_string(), "POST".to_string()],
+      allowed_headers: vec![
+        "content-type".to_string(),
+        "authorization".to_string(),
+        "accept".to_string(),
+      ],
+      supports_credentials: true,
+      max_age_secs: None,
+    }
+  }
+}
+
+impl CorsSetting {
+  pub fn origins_for(&self, environment: &str) -> &[String] {
+    self
+      .origins_by_environment
+      .iter()
+      .find(|(env, _)| env.eq_ignore_ascii_case(environment))
+      .map(|(_, origins)| origins.as_slice())
+      .unwrap_or(&self.allowed_origins)
+  }
+}
+
+// One JSON line per request on stdout, see `middleware::access_log`
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct AccessLogSetting {
+  pub enabled: bool,
+  /// Upper bounds of the `latency_bucket` field, in milliseconds and ascending
+  pub latency_buckets_ms: Vec<u64>,
+  /// Paths never logged, e.g. probes polled every few seconds
+  pub skip_paths: Vec<String>,
+}
+
+impl Default for AccessLogSetting {
+  fn default() -> Self {
+    AccessLogSetting {
+      enabled: true,
+      latency_buckets_ms: vec![10, 50, 100, 250, 500, 1000, 2500],
+      skip_paths: vec![
+        "/livez".to_string(),
+        "/readyz".to_string(),
+        "/metrics".to_string(),
+      ],
+    }
+  }
+}
+
+// Prometheus scrape endpoint, see `features::metrics`
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct MetricsSetting {
+  /// Serve `GET /metrics`, unauthenticated like the probes
+  pub enabled: bool,
+}
+
+impl Default for MetricsSetting {
+  fn default() -> Self {
+    MetricsSetting { enabled: true }
+  }
+}
+
+// Headers added to every response that doesn't set them itself, and the login tarpit
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct SecuritySetting {
+  /// Strict-Transport-Security, browsers only honor it over HTTPS
+  pub hsts_enabled: bool,
+  pub hsts_max_age_secs: u64,
+  pub hsts_include_subdomains: bool,
+  /// X-Frame-Options, `DENY` or `SAMEORIGIN`
+  pub frame_options: String,
+  pub referrer_policy: String,
+  /// Content-Security-Policy, `None` leaves it out
+  pub content_security_policy: Option<String>,
+  /// Slows down `/auth/login` for client IPs that keep failing, see `LoginTarpit`
+  pub login_tarpit_enabled: bool,
+  /// Failures from one IP within the window that get no delay
+  pub login_tarpit_free_failures: u32,
+  /// Delay after the first failure past the free ones, doubled on each further failure
+  pub login_tarpit_base_delay_ms: u64,
+  pub login_tarpit_max_delay_ms: u64,
+  /// Failures after which the IP gets a 429 instead of a delay, 0 never blocks
+  pub login_tarpit_block_after: u32,
+  /// How long failures are remembered, counted from the last one
+  pub login_tarpit_window_secs: u64,
+}
+
+impl Default for SecuritySetting {
+  fn default() -> Self {
+    SecuritySetting {
+      hsts_enabled: true,
+      hsts_max_age_secs: 365 * 24 * 60 * 60,
+      hsts_include_subdomains: true,
+      frame_options: "DENY".to_string(),
+      referrer_policy: "no-referrer".to_string(),
+      // Keeps the API docs usable while still refusing to be framed
+      content_security_policy: Some("frame-ancestors 'none'".to_string()),
+      login_tarpit_enabled: true,
+      login_tarpit_free_failures: 3,
+      login_tarpit_base_delay_ms: 500,
+      login_tarpit_max_delay_ms: 10_000,
+      login_tarpit_block_after: 30,
+      login_tarpit_window_secs: 15 * 60,
+    }
+  }
+}
+
+// Who can open the API docs
+#[derive(Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum DocsAccess {
+  #[default]
+  Public,
+  /// HTTP Basic auth with `docs.basic_auth`
+  Basic,
+  /// A signed-in admin, as for the `/admin` routes
+  Admin,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct BasicAuthSetting {
+  pub username: String,
+  pub password: String,
+}
+
+// API documentation pages and the OpenAPI documents they load
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct DocsSetting {
+  pub enabled: bool,
+  /// Replaces `enabled` when the key matches `environment`
+  pub enabled_by_environment: HashMap<String, bool>,
+  pub swagger_ui: bool,
+  pub redoc: bool,
+  pub rapidoc: bool,
+  pub access: DocsAccess,
+  pub basic_auth: Option<BasicAuthSetting>,
+}
+
+impl Default for DocsSetting {
+  fn default() -> Self {
+    DocsSetting {
+      enabled: true,
+      enabled_by_environment: HashMap::new(),
+      swagger_ui: true,
+      redoc: true,
+      rapidoc: true,
+      access: DocsAccess::Public,
+      basic_auth: None,
+    }
+  }
+}
+
+impl DocsSetting {
+  pub fn enabled_for(&self, environment: &str) -> bool {
+    self
+      .enabled_by_environment
+      .iter()
+      .find(|(env, _)| env.eq_ignore_ascii_case(environment))
+      .map(|(_, enabled)| *enabled)
+      .unwrap_or(self.enabled)
+  }
+}
+
+// Rules applied when a user changes their password
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct PasswordSetting {
+  /// How many of the latest passwords, the current one included, can't be reused; 0 allows any
+  pub history_size: u32,
+}
+
+impl Default for PasswordSetting {
+  fn default() -> Self {
+    PasswordSetting { history_size: 5 }
+  }
+}
+
+// Passwordless login with a code emailed by `/auth/request_otp`
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct OtpLoginSetting {
+  pub enabled: bool,
+  /// Digits in a code
+  pub code_length: u32,
+  pub expiration_minutes: i32,
+  /// Wrong codes accepted per window, requesting a new code doesn't reset them
+  pub max_attempts: i32,
+  /// Codes a user can be sent per window
+  pub max_codes_per_window: i32,
+  /// How long attempts and sent codes keep counting, starting with the first code
+  pub window_minutes: i32,
+}
+
+impl Default for OtpLoginSetting {
+  fn default() -> Self {
+    OtpLoginSetting {
+      enabled: false,
+      code_length: 6,
+      expiration_minutes: 10,
+      max_attempts: 5,
+      max_codes_per_window: 3,
+      window_minutes: 60,
+    }
+  }
+}
+
+// Request body limits, bodies over them are refused before a handler runs
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct PayloadLimitSetting {
+  /// Largest JSON body accepted by `web::Json` extractors
+  pub json_max_bytes: usize,
+  /// Largest body for any other extractor (`Bytes`, `String`, forms)
+  pub payload_max_bytes: usize,
+  /// Deepest nesting of objects and arrays in a JSON body
+  pub json_max_depth: usize,
+}
+
+impl Default for PayloadLimitSetting {
+  fn default() -> Self {
+    PayloadLimitSetting {
+      json_max_bytes: 2 * 1024 * 1024,
+      payload_max_bytes: 256 * 1024,
+      json_max_depth: 32,
+    }
+  }
+}
+
+// Human check on /auth/register, and on /auth/login once a user name keeps failing
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct CaptchaSetting {
+  pub enabled: bool,
+  /// Replaces `enabled` when the key matches `environment`
+  pub enabled_by_environment: HashMap<String, bool>,
+  pub provider: CaptchaProvider,
+  pub secret_key: String,
+  /// Header the client puts the widget's response token in
+  pub token_header: String,
+  /// Failed logins of a user name before login asks for a CAPTCHA too, 0 always asks
+  pub failed_logins_before_captcha: u32,
+  /// How long failed logins are remembered after the last one
+  pub failed_login_window_secs: u64,
+  /// Lowest score accepted from reCAPTCHA v3, ignored by the other providers
+  pub min_score: Option<f64>,
+}
+
+impl Default for CaptchaSetting {
+  fn default() -> Self {
+    CaptchaSetting {
+      enabled: false,
+      enabled_by_environment: HashMap::new(),
+      provider: CaptchaProvider::Turnstile,
+      secret_key: String::new(),
+      token_header: "X-Captcha-Token".to_string(),
+      failed_logins_before_captcha: 3,
+      failed_login_window_secs: 15 * 60,
+      min_score: None,
+    }
+  }
+}
+
+impl CaptchaSetting {
+  pub fn enabled_for(&self, environment: &str) -> bool {
+    self
+      .enabled_by_environment
+      .iter()
+      .find(|(env, _)| env.eq_ignore_ascii_case(environment))
+      .map(|(_, enabled)| *enabled)
+      .unwrap_or(self.enabled)
+  }
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum CaptchaProvider {
+  Recaptcha,
+  Hcaptcha,
+  Turnstile,
+}
+
+// Uploaded and generated files, see the `storage` module
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct StorageSetting {
+  pub backend: StorageBackendSetting,
+  /// Largest avatar image accepted by /users/me/avatar
+  pub max_avatar_bytes: usize,
+  pub images: ImageProcessingSetting,
+}
+
+impl Default for StorageSetting {
+  fn default() -> Self {
+    StorageSetting {
+      backend: StorageBackendSetting::Local {
+        root: "data/storage".to_string(),
+      },
+      max_avatar_bytes: 2 * 1024 * 1024,
+      images: ImageProcessingSetting::default(),
+    }
+  }
+}
+
+// Long-running work answered with 202 Accepted, see `utils::operations`
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct OperationSetting {
+  /// Operations running at once, further ones wait their turn
+  pub max_concurrent: usize,
+  /// Operations queued or running at once, starting one more answers 503
+  pub max_pending: usize,
+  /// How long a finished operation and its result file can still be fetched
+  pub retention_secs: u64,
+}
+
+impl Default for OperationSetting {
+  fn default() -> Self {
+    OperationSetting {
+      max_concurrent: 2,
+      max_pending: 20,
+      retention_secs: 60 * 60,
+    }
+  }
+}
+
+// Security events shipped to a SIEM, see `security_log`
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct SecurityLogSetting {
+  /// Where events are written, `None` turns the log off
+  pub sink: Option<SecurityLogSink>,
+  /// Events waiting for the sink, further ones are dropped with a log line
+  pub queue_capacity: usize,
+  /// Tries per event before it is dropped
+  pub max_attempts: u32,
+}
+
+impl Default for SecurityLogSetting {
+  fn default() -> Self {
+    SecurityLogSetting {
+      sink: None,
+      queue_capacity: 1000,
+      max_attempts: 3,
+    }
+  }
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SecurityLogSink {
+  /// One JSON line per event appended to `path`, reopened for every event so it can be rotated
+  File { path: String },
+  /// RFC 5424 messages over UDP to `address`, e.g. `siem.internal:514`
+  Syslog {
+    address: String,
+    #[serde(default = "default_syslog_app_name")]
+    app_name: String,
+  },
+  /// Each event POSTed as JSON
+  Webhook(WebhookChannelSetting),
+}
+
+// Default value for app_name
+fn default_syslog_app_name() -> String {
+  "api".to_string()
+}
+
+// Resizing and metadata stripping of uploaded images, done by `storage::images`
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct ImageProcessingSetting {
+  /// Longest side in pixels of each resized variant, served at `.../avatar/{size}`
+  pub variant_sizes: Vec<u32>,
+  /// Uploads wider or taller than this are refused when processed
+  pub max_dimension: u32,
+  pub jpeg_quality: u8,
+  pub queue_capacity: usize,
+}
+
+impl Default for ImageProcessingSetting {
+  fn default() -> Self {
+    ImageProcessingSetting {
+      variant_sizes: vec![64, 256],
+      max_dimension: 8192,
+      jpeg_quality: 85,
+      queue_capacity: 100,
+    }
+  }
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StorageBackendSetting {
+  /// Files under `root`, relative to the working directory
+  Local { root: String },
+  /// An S3 bucket or S3-compatible service
+  S3(S3StorageSetting),
+}
+
+#[derive(Deserialize, Clone)]
+pub struct S3StorageSetting {
+  /// e.g. `https://s3.eu-west-1.amazonaws.com` or `http://minio:9000`
+  pub endpoint: String,
+  pub bucket: String,
+  pub region: String,
+  pub access_key_id: String,
+  pub secret_access_key: String,
+  #[serde(default)]
+  pub session_token: Option<String>,
+  /// `endpoint/bucket/key` URLs, most S3-compatible services need them
+  #[serde(default = "default_enabled")]
+  pub path_style: bool,
+  /// Put in front of every key, e.g. `api/` to share a bucket
+  #[serde(default)]
+  pub prefix: String,
+}
+
+// Fixed window per client IP, applied to every route
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct RateLimitSetting {
+  pub enabled: bool,
+  pub requests_per_window: u32,
+  pub window_secs: u64,
+  /// Addresses that are never limited, e.g. load balancer health checks
+  pub allowlist: Vec<IpAddr>,
+  /// Take the client address from X-Forwarded-For when the request comes through one of
+  /// `trusted_proxies`
+  pub use_forwarded_for: bool,
+  /// Networks of the proxies in front of the API, e.g. `10.0.0.0/8`. X-Forwarded-For is read
+  /// from the right and the first address outside them is the client.
+  pub trusted_proxies: Vec<IpNet>,
+}
+
+impl Default for RateLimitSetting {
+  fn default() -> Self {
+    RateLimitSetting {
+      enabled: false,
+      requests_per_window: 300,
+      window_secs: 60,
+      allowlist: Vec::new(),
+      use_forwarded_for: false,
+      trusted_proxies: Vec::new(),
+    }
+  }
+}
+
+// Recurring maintenance run in the background, see `scheduler`
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct SchedulerSetting {
+  /// Turns every task off at once, e.g. on all but one instance
+  pub enabled: bool,
+  pub purge_expired_tokens: ScheduledTaskSetting,
+  pub recycle_idle_connections: ScheduledTaskSetting,
+  pub daily_stats: ScheduledTaskSetting,
+  pub purge_soft_deleted: ScheduledTaskSetting,
+  /// Days a soft-deleted row can still be restored before `purge_soft_deleted` removes it
+  pub soft_delete_retention_days: u32,
+}
+
+impl Default for SchedulerSetting {
+  fn default() -> Self {
+    SchedulerSetting {
+      enabled: true,
+      purge_expired_tokens: ScheduledTaskSetting::every(60 * 60),
+      recycle_idle_connections: ScheduledTaskSetting::every(5 * 60),
+      daily_stats: ScheduledTaskSetting {
+        enabled: true,
+        interval_secs: 24 * 60 * 60,
+        at: Some("00:00".to_string()),
+      },
+      purge_soft_deleted: ScheduledTaskSetting {
+        enabled: true,
+        interval_secs: 24 * 60 * 60,
+        at: Some("03:00".to_string()),
+      },
+      soft_delete_retention_days: 30,
+    }
+  }
+}
+
+impl SchedulerSetting {
+  pub fn tasks(&self) -> [(&'static str, &ScheduledTaskSetting); 4] {
+    [
+      ("purge_expired_tokens", &self.purge_expired_tokens),
+      ("recycle_idle_connections", &self.recycle_idle_connections),
+      ("daily_stats", &self.daily_stats),
+      ("purge_soft_deleted", &self.purge_soft_deleted),
+    ]
+  }
+}
+
+#[derive(Deserialize, Clone)]
+pub struct ScheduledTaskSetting {
+  #[serde(default = "default_enabled")]
+  pub enabled: bool,
+  #[serde(default)]
+  pub interval_secs: u64,
+  /// UTC time of day (`HH:MM`) to run once a day at, replaces `interval_secs`
+  #[serde(default)]
+  pub at: Option<String>,
+}
+
+impl ScheduledTaskSetting {
+  fn every(interval_secs: u64) -> Self {
+    ScheduledTaskSetting {
+      enabled: true,
+      interval_secs,
+      at: None,
+    }
+  }
 }
 
 #[derive(Deserialize, Clone)]
 pub struct ServerSetting {
   pub host: String,
   pub port: u16,
+  /// How long in-flight requests and checked out connections get to finish on SIGTERM
+  #[serde(default = "default_shutdown_timeout_secs")]
+  pub shutdown_timeout_secs: u64,
+  /// Terminate TLS in the API itself, leave out when a reverse proxy does it
+  pub tls: Option<TlsSetting>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct TlsSetting {
+  /// PEM file with the certificate chain, leaf first
+  pub cert_path: String,
+  /// PEM file with the PKCS#8, PKCS#1 or SEC1 private key
+  pub key_path: String,
+  /// Plain HTTP port answering every request with a redirect to HTTPS
+  pub redirect_http_port: Option<u16>,
+  /// Host name the redirect points at, e.g. `api.example.com`, required with `redirect_http_port`
+  #[serde(default)]
+  pub public_host: Option<String>,
+  /// Client certificates for machine callers, left out to not ask for one
+  #[serde(default)]
+  pub client_auth: Option<ClientAuthSetting>,
+}
+
+// Callers presenting a certificate issued by `ca_path` act as the user mapped to its subject
+#[derive(Deserialize, Clone)]
+pub struct ClientAuthSetting {
+  /// PEM file with the CA certificates client certificates must be issued by
+  pub ca_path: String,
+  /// Refuses connections without a certificate, otherwise those use JWT as usual
+  #[serde(default)]
+  pub required: bool,
+  /// Certificate subject to user name, keyed by the full subject (`CN=billing, O=Acme`)
+  /// or only its common name (`billing`)
+  #[serde(default)]
+  pub principals: HashMap<String, String>,
+}
+
+// Default value for shutdown_timeout_secs
+fn default_shutdown_timeout_secs() -> u64 {
+  30
 }
 
 #[derive(Deserialize, Clone)]
 pub struct DatabaseSetting {
   pub sql_server: DatabaseConnectionInfo,
+  /// Apply pending migrations before the server starts, otherwise run `cargo run -- migrate`
+  #[serde(default)]
+  pub migrate_on_startup: bool,
+  /// Run the seeders after migrations when the server starts, otherwise run `cli seed`
+  #[serde(default)]
+  pub seed_on_startup: bool,
+  /// Further databases by name, e.g. `audit` or `reporting`. A repo that asks for a name not
+  /// listed here uses `sql_server`.
+  #[serde(default)]
+  pub databases: BTreeMap<String, DatabaseConnectionInfo>,
+  /// Database per tenant, pools are opened on first use and closed again when idle
+  #[serde(default)]
+  pub tenants: Option<TenantDatabaseSetting>,
+  /// How long startup keeps retrying a database that isn't reachable yet, 0 fails at once
+  #[serde(default = "default_startup_max_wait_secs")]
+  pub startup_max_wait_secs: u64,
+  /// Wait before the second try at startup, doubled for every further one up to 10 seconds
+  #[serde(default = "default_startup_retry_delay_ms")]
+  pub startup_retry_delay_ms: u64,
+}
+
+// Default value for startup_max_wait_secs
+fn default_startup_max_wait_secs() -> u64 {
+  60
+}
+
+// Default value for startup_retry_delay_ms
+fn default_startup_retry_delay_ms() -> u64 {
+  500
+}
+
+impl DatabaseSetting {
+  /// The database named `name`, or `sql_server` when there is none of that name.
+  pub fn connection(&self, name: &str) -> &DatabaseConnectionInfo {
+    self.databases.get(name).unwrap_or(&self.sql_server)
+  }
+
+  /// `sql_server` followed by every named database.
+  pub fn all_connections(&self) -> impl Iterator<Item = &DatabaseConnectionInfo> {
+    std::iter::once(&self.sql_server).chain(self.databases.values())
+  }
+}
+
+#[derive(Deserialize, Clone)]
+pub struct TenantDatabaseSetting {
+  /// Connection string with `{tenant}` standing for the tenant name,
+  /// e.g. `Server=db,1433;Database=tenant_{tenant};...`
+  pub conn_str_template: String,
+  /// Connections of each tenant pool
+  #[serde(default = "default_tenant_pool_size")]
+  pub pool_size: u32,
+  /// Tenant pools open at once, the least recently used idle one is closed to make room
+  #[serde(default = "default_tenant_max_pools")]
+  pub max_pools: u32,
+  /// A tenant pool nobody checked out from for this long is closed by the
+  /// `recycle_idle_connections` task
+  #[serde(default = "default_tenant_idle_timeout_secs")]
+  pub idle_timeout_secs: u64,
+}
+
+// Default value for pool_size
+fn default_tenant_pool_size() -> u32 {
+  5
+}
+
+// Default value for max_pools
+fn default_tenant_max_pools() -> u32 {
+  20
+}
+
+// Default value for idle_timeout_secs
+fn default_tenant_idle_timeout_secs() -> u64 {
+  600
 }
 
 #[derive(Deserialize, Clone)]
@@ -25,6 +1315,207 @@ pub struct DatabaseConnectionInfo {
   pub conn_str: String,
   pub pool_size: u32,
   pub pool_name: String,
+  /// Run `SELECT 1` on checkout and rebuild the pool when the connection is dead
+  #[serde(default = "default_validate_on_checkout")]
+  pub validate_on_checkout: bool,
+  /// How long a checkout waits for a free connection before failing with pool exhausted
+  #[serde(default = "default_checkout_timeout_ms")]
+  pub checkout_timeout_ms: u64,
+  /// Default limit for a single query, 0 disables it
+  #[serde(default = "default_query_timeout_secs")]
+  pub query_timeout_secs: u64,
+  /// Queries running at least this long are logged as slow, 0 disables it
+  #[serde(default = "default_slow_query_ms")]
+  pub slow_query_ms: u64,
+  /// Tries of an idempotent read that fails with a transient error, 1 disables retrying
+  #[serde(default = "default_retry_attempts")]
+  pub retry_attempts: u32,
+  /// Wait before the second try, doubled for every further one and jittered
+  #[serde(default = "default_retry_base_delay_ms")]
+  pub retry_base_delay_ms: u64,
+  /// A pool nobody checked out from for this long gets fresh connections, 0 disables it
+  #[serde(default = "default_idle_timeout_secs")]
+  pub idle_timeout_secs: u64,
+  /// Connections older than this are replaced once nothing is checked out, 0 disables it
+  #[serde(default = "default_max_lifetime_secs")]
+  pub max_lifetime_secs: u64,
+  /// Statements each connection keeps prepared for `SqlRepo::execute_prepared_query_as`, 0
+  /// runs them unprepared
+  #[serde(default = "default_statement_cache_size")]
+  pub statement_cache_size: usize,
+  #[serde(default)]
+  pub tls: DatabaseTlsSetting,
+  /// Connect with `ApplicationIntent=ReadOnly`, so an availability group listener routes the
+  /// pool to a readable secondary. Migrations skip such a database.
+  #[serde(default)]
+  pub read_only: bool,
+  /// Server (`host,port`) the pool switches to when the current one can't be reached, and
+  /// back again when that one fails
+  #[serde(default)]
+  pub failover_partner: Option<String>,
+  /// Sign in with an Azure AD access token instead of `User Id` and `Password`
+  #[serde(default)]
+  pub azure_ad: Option<AzureAdSetting>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct AzureAdSetting {
+  pub token_source: AzureAdTokenSource,
+  /// A token this close to expiring is fetched again before a connection is opened with it
+  #[serde(default = "default_azure_ad_refresh_before_secs")]
+  pub refresh_before_secs: u64,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AzureAdTokenSource {
+  /// The managed identity of the VM, container or App Service the API runs on
+  ManagedIdentity {
+    /// Client id of a user-assigned identity, the system-assigned one when unset
+    #[serde(default)]
+    client_id: Option<String>,
+  },
+  /// A token read through a secret reference such as `file:///var/run/secrets/sql-token`,
+  /// written by a sidecar. Read again when it is about to expire, so it isn't resolved at
+  /// startup like other secrets.
+  Secret { token: String },
+}
+
+// Default value for refresh_before_secs
+fn default_azure_ad_refresh_before_secs() -> u64 {
+  300
+}
+
+// Replaces the keys of `overrides` in an ADO.NET style connection string, a server override
+// replaces every way of naming the server
+fn override_keys(conn_str: &str, overrides: Vec<(&str, String)>) -> String {
+  if overrides.is_empty() {
+    return conn_str.to_string();
+  }
+  let is_server = |name: &str| SERVER_KEYS.contains(&name.to_lowercase().as_str());
+  let mut parts: Vec<String> = conn_str
+    .split(';')
+    .map(str::trim)
+    .filter(|part| {
+      let name = part.split_once('=').map_or(*part, |(name, _)| name).trim();
+      !part.is_empty()
+        && !overrides
+          .iter()
+          .any(|(key, _)| key.eq_ignore_ascii_case(name) || (is_server(key) && is_server(name)))
+    })
+    .map(str::to_string)
+    .collect();
+  parts.extend(
+    overrides
+      .into_iter()
+      .map(|(key, value)| format!("{}={}", key, value)),
+  );
+  parts.join(";")
+}
+
+impl DatabaseConnectionInfo {
+  /// `conn_str` with the `tls` and `read_only` settings applied, replacing the same keys
+  /// written in it.
+  pub fn effective_conn_str(&self) -> String {
+    let tls = &self.tls;
+    let mut overrides: Vec<(&str, String)> = Vec::new();
+    if let Some(encryption) = tls.encryption {
+      let value = match encryption {
+        DbEncryption::Off => "false",
+        DbEncryption::Required => "true",
+        DbEncryption::Plaintext => "DANGER_PLAINTEXT",
+      };
+      overrides.push(("Encrypt", value.to_string()));
+    }
+    // A CA bundle only means something when the certificate is checked
+    let verify_server = tls.verify_server.or(tls.ca_path.as_ref().map(|_| true));
+    if let Some(verify_server) = verify_server {
+      overrides.push(("TrustServerCertificate", (!verify_server).to_string()));
+    }
+    if let Some(ca_path) = &tls.ca_path {
+      overrides.push(("TrustServerCertificateCA", ca_path.clone()));
+    }
+    if self.read_only {
+      overrides.push(("ApplicationIntent", "ReadOnly".to_string()));
+    }
+    override_keys(&self.conn_str, overrides)
+  }
+
+  /// `effective_conn_str` pointed at `failover_partner`, `None` without a partner.
+  pub fn failover_conn_str(&self) -> Option<String> {
+    let partner = self.failover_partner.as_ref()?;
+    Some(override_keys(
+      &self.effective_conn_str(),
+      vec![("Server", partner.clone())],
+    ))
+  }
+}
+
+/// TLS to SQL Server. Unset fields leave the connection string as written.
+#[derive(Deserialize, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct DatabaseTlsSetting {
+  pub encryption: Option<DbEncryption>,
+  /// CA bundle (PEM) the server certificate is checked against besides the system store
+  pub ca_path: Option<String>,
+  /// Check the server certificate and that it names the host, `false` trusts any certificate
+  pub verify_server: Option<bool>,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DbEncryption {
+  /// Only the login is encrypted
+  Off,
+  /// The whole session is encrypted
+  Required,
+  /// Nothing is encrypted, not even the password, for local development only
+  Plaintext,
+}
+
+// Default value for validate_on_checkout
+fn default_validate_on_checkout() -> bool {
+  true
+}
+
+// Default value for checkout_timeout_ms
+fn default_checkout_timeout_ms() -> u64 {
+  5000
+}
+
+// Default value for query_timeout_secs
+fn default_query_timeout_secs() -> u64 {
+  30
+}
+
+// Default value for slow_query_ms
+fn default_slow_query_ms() -> u64 {
+  500
+}
+
+// Default value for retry_attempts
+fn default_retry_attempts() -> u32 {
+  3
+}
+
+// Default value for retry_base_delay_ms
+fn default_retry_base_delay_ms() -> u64 {
+  100
+}
+
+// Default value for idle_timeout_secs
+fn default_idle_timeout_secs() -> u64 {
+  600
+}
+
+// Default value for max_lifetime_secs
+fn default_max_lifetime_secs() -> u64 {
+  1800
+}
+
+// Default value for statement_cache_size
+fn default_statement_cache_size() -> usize {
+  16
 }
 
 #[derive(Deserialize, Clone)]
@@ -33,9 +1524,264 @@ pub struct JwtSetting {
   pub expiration_minutes: usize,
   pub issuer: String,
   pub audience: String,
+  /// Lifetime of the tokens minted by /admin/impersonate
+  #[serde(default = "default_impersonation_minutes")]
+  pub impersonation_minutes: usize,
+  /// Added to every token, e.g. `{"tenant": "acme", "department": "sales"}`
+  #[serde(default)]
+  pub static_claims: CustomClaims,
+  /// Also accepted when validating, e.g. tokens of a previous issuer during a migration
+  #[serde(default)]
+  pub accepted: AcceptedTokenSetting,
+  /// Replaces `accepted` when the key matches `environment`
+  #[serde(default)]
+  pub accepted_by_environment: HashMap<String, AcceptedTokenSetting>,
+  /// Ties login tokens to the device they were issued to
+  #[serde(default)]
+  pub device_binding: DeviceBindingSetting,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct DeviceBindingSetting {
+  pub enabled: bool,
+  /// Header the client sends at login and on every request, hashed with the User-Agent
+  pub client_hint_header: String,
+}
+
+impl Default for DeviceBindingSetting {
+  fn default() -> Self {
+    DeviceBindingSetting {
+      enabled: false,
+      client_hint_header: "X-Device-Id".to_string(),
+    }
+  }
+}
+
+#[derive(Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct AcceptedTokenSetting {
+  pub issuers: Vec<String>,
+  pub audiences: Vec<String>,
+}
+
+impl JwtSetting {
+  /// Issuers and audiences a token may have in `environment`, `issuer` and `audience` included.
+  pub fn accepted_for(&self, environment: &str) -> AcceptedTokenSetting {
+    let extra = self
+      .accepted_by_environment
+      .iter()
+      .find(|(env, _)| env.eq_ignore_ascii_case(environment))
+      .map(|(_, accepted)| accepted)
+      .unwrap_or(&self.accepted);
+    AcceptedTokenSetting {
+      issuers: std::iter::once(&self.issuer)
+        .chain(&extra.issuers)
+        .cloned()
+        .collect(),
+      audiences: std::iter::once(&self.audience)
+        .chain(&extra.audiences)
+        .cloned()
+        .collect(),
+    }
+  }
+}
+
+// Default value for impersonation_minutes
+fn default_impersonation_minutes() -> usize {
+  15
 }
 
 #[derive(Deserialize, Clone)]
 pub struct CookieSetting {
   pub name: String,
 }
+
+// Where a request may carry its token
+#[derive(Deserialize, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CredentialSource {
+  /// The cookie named by `cookie.name`
+  Cookie,
+  /// `Authorization: Bearer <token>`
+  Bearer,
+  /// The bare token in a header of its own, e.g. `X-Api-Token`
+  Header { name: String },
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct AuthSetting {
+  /// Tried in order, the first one holding a token wins
+  pub credential_sources: Vec<CredentialSource>,
+  /// Resource servers and gateways allowed to call /auth/introspect
+  pub introspection_clients: Vec<IntrospectionClientSetting>,
+  /// Base URL advertised by /.well-known/openid-configuration, e.g. `https://api.example.com`.
+  /// Taken from the request's host when left out
+  pub public_url: Option<String>,
+}
+
+impl Default for AuthSetting {
+  fn default() -> Self {
+    AuthSetting {
+      credential_sources: vec![CredentialSource::Cookie, CredentialSource::Bearer],
+      introspection_clients: Vec::new(),
+      public_url: None,
+    }
+  }
+}
+
+// Authenticates with HTTP Basic auth (client_secret_basic)
+#[derive(Deserialize, Clone)]
+pub struct IntrospectionClientSetting {
+  pub client_id: String,
+  pub client_secret: String,
+}
+
+// Soft limits, `None` means unlimited. Checked by counting before the insert, so requests
+// racing each other can go a little over
+#[derive(Deserialize, Clone, Default)]
+pub struct QuotaSetting {
+  pub max_roles: Option<i32>,
+  pub max_members_per_role: Option<i32>,
+}
+
+// Rollout rules of a flag, flags left out of the config are fully on
+#[derive(Deserialize, Clone)]
+pub struct FeatureFlagSetting {
+  #[serde(default = "default_enabled")]
+  pub enabled: bool,
+  /// 0-100, share of users (by stable hash of the user id) that get the flag
+  pub percentage: Option<u8>,
+  /// Roles that always get the flag, e.g. `["admin"]`
+  #[serde(default)]
+  pub roles: Vec<String>,
+}
+
+// Default value for enabled
+fn default_enabled() -> bool {
+  true
+}
+
+// Channels left out of the config are disabled
+#[derive(Deserialize, Clone, Default)]
+pub struct NotificationSetting {
+  #[serde(default)]
+  pub routes: HashMap<NotificationKind, ChannelKind>,
+  pub webhook: Option<WebhookChannelSetting>,
+  pub sms: Option<SmsChannelSetting>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct RedisSetting {
+  /// `redis://[:password@]host:6379/0`, or `rediss://` for TLS
+  pub url: String,
+  /// Prepended to every key so several apps can share one Redis
+  #[serde(default = "default_redis_key_prefix")]
+  pub key_prefix: String,
+  /// Upper bound on how stale a cached entry can get if an invalidation is missed
+  #[serde(default = "default_redis_ttl_secs")]
+  pub ttl_secs: u64,
+}
+
+// Default value for key_prefix
+fn default_redis_key_prefix() -> String {
+  "api:".to_string()
+}
+
+// Default value for ttl_secs
+fn default_redis_ttl_secs() -> u64 {
+  300
+}
+
+// This API as a SAML service provider, see `features::saml`
+#[derive(Deserialize, Clone)]
+pub struct SamlSetting {
+  /// Our entity id, the audience assertions must be issued for
+  pub entity_id: String,
+  /// Public URL of `/auth/saml/acs`, e.g. `https://api.example.com/api/v2/auth/saml/acs`
+  pub acs_url: String,
+  pub idp_entity_id: String,
+  /// Where `/auth/saml/login` sends the browser with the AuthnRequest
+  pub idp_sso_url: String,
+  /// PEM file with the certificate the IdP signs responses with
+  pub idp_cert_path: String,
+  /// Attribute holding the user name, the NameID when left out
+  #[serde(default)]
+  pub user_name_attribute: Option<String>,
+  #[serde(default)]
+  pub email_attribute: Option<String>,
+  #[serde(default)]
+  pub display_name_attribute: Option<String>,
+  /// Attribute whose values are looked up in `role_mapping`, e.g. `groups`
+  #[serde(default)]
+  pub role_attribute: Option<String>,
+  /// Attribute value to the role names it grants, e.g. `{"Domain Admins": ["admin"]}`
+  #[serde(default)]
+  pub role_mapping: HashMap<String, Vec<String>>,
+  /// Creates unknown users on their first login instead of refusing them
+  #[serde(default)]
+  pub auto_provision: bool,
+  /// Tolerated clock difference with the IdP
+  #[serde(default = "default_saml_clock_skew_secs")]
+  pub clock_skew_secs: u64,
+}
+
+// Default value for clock_skew_secs
+fn default_saml_clock_skew_secs() -> u64 {
+  120
+}
+
+// Outgoing email, queued and sent in the background by `utils::mailer`
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct MailerSetting {
+  /// Print emails to the log instead of sending them
+  pub dev_mode: bool,
+  pub from: String,
+  pub smtp: Option<SmtpSetting>,
+  /// Emails waiting to be sent, new ones are dropped once it is full
+  pub queue_capacity: usize,
+  /// Tries per email, with a doubling delay between them
+  pub max_attempts: u32,
+}
+
+impl Default for MailerSetting {
+  fn default() -> Self {
+    MailerSetting {
+      dev_mode: true,
+      from: "no-reply@example.com".to_string(),
+      smtp: None,
+      queue_capacity: 1000,
+      max_attempts: 3,
+    }
+  }
+}
+
+#[derive(Deserialize, Clone)]
+pub struct SmtpSetting {
+  pub host: String,
+  #[serde(default = "default_smtp_port")]
+  pub port: u16,
+  pub username: String,
+  pub password: String,
+}
+
+// Default value for port, STARTTLS submission
+fn default_smtp_port() -> u16 {
+  587
+}
+
+#[derive(Deserialize, Clone)]
+pub struct WebhookChannelSetting {
+  pub url: String,
+  pub bearer_token: Option<String>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct SmsChannelSetting {
+  pub api_url: String,
+  pub account_sid: String,
+  pub auth_token: String,
+  pub from_number: String,
+}
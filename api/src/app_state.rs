@@ -1,37 +1,265 @@
-use crate::app_settings::AppSetting;
+use std::{
+  sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+  },
+  time::{Duration, Instant},
+};
 
-use anyhow::Result;
-use domner_tech_sql_client::pool_manager::DbManager;
+use arc_swap::ArcSwap;
+
+use crate::{
+  app_settings::{AppSetting, RuntimeSetting},
+  db::{DbConnection, DbManager},
+  events::EventBus,
+  features::{
+    admin::admin_dto::DashboardCache,
+    permissions::permissions_repo::PermissionRepository,
+    roles::roles_repo::RoleRepository,
+    users::{user_dto::UserDto, user_repo::UserRepository},
+  },
+  middleware::{rate_limit::RateLimit, transaction::DbTransaction},
+  notifications::Notifier,
+  repositories::{RepositoryProvider, SqlServerRepositories},
+  scheduler::Scheduler,
+  secrets::SecretResolvers,
+  security_log::SecurityLog,
+  storage::{self, Storage, images::ImageProcessor},
+  utils::{
+    captcha::{CaptchaVerifier, SiteVerifyClient},
+    custom_claims::{ClaimsEnricher, NoClaimsEnricher},
+    login_tarpit::LoginTarpit,
+    mailer::Mailer,
+    operations::Operations,
+    redis_cache::RedisCache,
+    ttl_cache::TtlMap,
+  },
+};
+
+// How long resolved roles and permissions are reused before hitting the DB again
+const PERMISSION_CACHE_TTL: Duration = Duration::from_secs(60);
+// Kept short since other instances' updates only show up once an entry expires
+const USER_CACHE_TTL: Duration = Duration::from_secs(15);
+// How long the IdP has to answer an AuthnRequest of /auth/saml/login
+const SAML_REQUEST_TTL: Duration = Duration::from_secs(10 * 60);
+// Longest wait between two tries of a database that isn't reachable at startup
+const MAX_STARTUP_RETRY_DELAY: Duration = Duration::from_secs(10);
+
+use anyhow::{Context, Result};
 
 #[derive(Clone)]
 pub struct AppState {
   pub config: AppSetting,
   pub db_manager: DbManager,
+  pub dashboard_cache: DashboardCache,
+  pub notifier: Notifier,
+  // Queue of outgoing emails, also fed by `notifier`
+  pub mailer: Mailer,
+  // Domain events pushed to admins over `/ws`
+  pub events: EventBus,
+  // Last-run reports of the background maintenance tasks
+  pub scheduler: Scheduler,
+  // Effective permissions by user id, cleared whenever a grant changes
+  pub permission_cache: TtlMap<i32, Vec<String>>,
+  // Role names assigned through user_roles by user id
+  pub role_cache: TtlMap<i32, Vec<String>>,
+  // Active users as resolved by the auth middleware, by user id
+  pub user_cache: TtlMap<i32, UserDto>,
+  // Pending SAML AuthnRequest ids with their `return_to`, local to this instance
+  pub saml_requests: TtlMap<String, Option<String>>,
+  pub repositories: Arc<dyn RepositoryProvider>,
+  // Adds application claims to minted tokens, see `ClaimsEnricher`
+  pub claims_enricher: Arc<dyn ClaimsEnricher>,
+  // Checks CAPTCHA tokens, `None` when `captcha` is off in this environment
+  pub captcha: Option<Arc<dyn CaptchaVerifier>>,
+  // Recent failed logins by lowercased user name, local to this instance
+  pub failed_logins: TtlMap<String, u32>,
+  // Per-IP request limits, shared by all workers
+  pub rate_limit: RateLimit,
+  // Delays for client IPs that keep failing `/auth/login`
+  pub login_tarpit: LoginTarpit,
+  // Logins, grants and impersonations shipped to `security_log.sink`
+  pub security_log: SecurityLog,
+  // Uploaded files, on disk or in S3 per `storage.backend`
+  pub storage: Arc<dyn Storage>,
+  // Background resizing and metadata stripping of uploaded images
+  pub images: ImageProcessor,
+  // Long-running work polled through `/operations/{id}`, local to this instance
+  pub operations: Operations,
+  // Read-through cache of users and their roles, `None` when `redis` isn't configured
+  pub redis: Option<RedisCache>,
+  // Reloadable settings, read through `runtime()` instead of `config`
+  runtime: Arc<ArcSwap<RuntimeSetting>>,
+  // Flipped once pools are initialized and migrations applied, see `/readyz`
+  startup_complete: Arc<AtomicBool>,
 }
 impl AppState {
-  // Load config from file manually
+  // Load config from file manually, pools are opened separately by `init_db_manager`
   pub async fn load_setting(path: &str) -> Result<Self> {
-    let file = std::fs::File::open(path).expect(&format!("Failed to open config file: {}", path));
-    let config: AppSetting = serde_json::from_reader(file).expect("Failed to parse JSON config");
+    let file =
+      std::fs::File::open(path).with_context(|| format!("Failed to open config file: {}", path))?;
+    let config: AppSetting = serde_json::from_reader(file)
+      .with_context(|| format!("Failed to parse JSON config: {}", path))?;
+    Self::from_setting(config).await
+  }
 
-    match Self::init_db_manager(&mut config.clone()).await {
-      Ok(db_manager) => Ok(Self { config, db_manager }),
-      Err(e) => Err(e),
-    }
+  /// Same as `load_setting` with settings built in code, e.g. by the integration tests.
+  pub async fn from_setting(mut config: AppSetting) -> Result<Self> {
+    // Secrets referenced by URI are fetched here so they never have to be on disk
+    SecretResolvers::from_env()
+      .resolve_setting(&mut config)
+      .await?;
+    config.validate()?;
+
+    let mailer = Mailer::start(&config.mailer)?;
+    // An unreachable Redis only costs the cache, requests fall back to the database
+    let redis = match &config.redis {
+      Some(setting) => match RedisCache::connect(setting).await {
+        Ok(cache) => Some(cache),
+        Err(e) => {
+          eprintln!("Redis is unavailable, running without the cache: {}", e);
+          None
+        }
+      },
+      None => None,
+    };
+
+    let captcha = config
+      .captcha
+      .enabled_for(&config.environment)
+      .then(|| Arc::new(SiteVerifyClient::new(&config.captcha)) as Arc<dyn CaptchaVerifier>);
+    let failed_logins = TtlMap::new(Duration::from_secs(config.captcha.failed_login_window_secs));
+    let login_tarpit = LoginTarpit::new(&config.security, redis.clone());
+    let security_log = SecurityLog::start(&config.security_log);
+
+    let storage = storage::from_setting(&config.storage.backend);
+    let images = ImageProcessor::start(&config.storage.images, storage.clone());
+    let operations = Operations::new(&config.operations, storage.clone());
+
+    Ok(Self {
+      notifier: Notifier::from_setting(&config.notification, &mailer),
+      mailer,
+      events: EventBus::new(),
+      scheduler: Scheduler::from_setting(&config.scheduler),
+      runtime: Arc::new(ArcSwap::from_pointee(RuntimeSetting::from(&config))),
+      config,
+      db_manager: DbManager::new(),
+      dashboard_cache: DashboardCache::default(),
+      permission_cache: TtlMap::new(PERMISSION_CACHE_TTL),
+      role_cache: TtlMap::new(PERMISSION_CACHE_TTL),
+      user_cache: TtlMap::new(USER_CACHE_TTL),
+      saml_requests: TtlMap::new(SAML_REQUEST_TTL),
+      repositories: Arc::new(SqlServerRepositories),
+      claims_enricher: Arc::new(NoClaimsEnricher),
+      captcha,
+      failed_logins,
+      rate_limit: RateLimit::new(),
+      login_tarpit,
+      security_log,
+      storage,
+      images,
+      operations,
+      redis,
+      startup_complete: Arc::new(AtomicBool::new(false)),
+    })
+  }
+
+  pub fn runtime(&self) -> Arc<RuntimeSetting> {
+    self.runtime.load_full()
+  }
+
+  pub fn reload_runtime(&self, runtime: RuntimeSetting) {
+    self.runtime.store(Arc::new(runtime));
+  }
+
+  pub fn user_repo(&self) -> Box<dyn UserRepository + '_> {
+    self.repositories.users(self, None)
+  }
+
+  // Same repository, running every query inside the request's transaction
+  pub fn user_repo_in(&self, tx: &DbTransaction) -> Box<dyn UserRepository + '_> {
+    self.repositories.users(self, Some(tx))
+  }
+
+  pub fn role_repo(&self) -> Box<dyn RoleRepository + '_> {
+    self.repositories.roles(self, None)
+  }
+
+  pub fn role_repo_in(&self, tx: &DbTransaction) -> Box<dyn RoleRepository + '_> {
+    self.repositories.roles(self, Some(tx))
+  }
+
+  pub fn permission_repo(&self) -> Box<dyn PermissionRepository + '_> {
+    self.repositories.permissions(self)
+  }
+
+  // Drop cached roles and permissions after role assignments or grants change
+  pub fn invalidate_access_cache(&self) {
+    self.permission_cache.clear();
+    self.role_cache.clear();
+    self.user_cache.clear();
+  }
+
+  // Drop the cached copy of a user after it was updated, activated or deactivated
+  pub fn invalidate_user(&self, user_id: i32) {
+    self.user_cache.remove(&user_id);
+    self.role_cache.remove(&user_id);
   }
 
   // Initialize the database manager with connection pools
-  async fn init_db_manager(setting: &mut AppSetting) -> Result<DbManager> {
-    let db_manager = DbManager::new();
-
-    // Initialize the SQL connection pool
-    db_manager
-      .init_pool(
-        setting.database.sql_server.pool_name.as_str(),
-        &setting.database.sql_server.conn_str,
-        setting.database.sql_server.pool_size,
-      )
-      .await?;
-    Ok(db_manager)
+  /// Opens a pool for `sql_server` and one for every named database. A database that isn't
+  /// reachable yet, e.g. a container still starting, is retried with backoff for up to
+  /// `database.startup_max_wait_secs`.
+  pub async fn init_db_manager(&self) -> Result<()> {
+    let database = &self.config.database;
+    let max_wait = Duration::from_secs(database.startup_max_wait_secs);
+    let started = Instant::now();
+    for setting in database.all_connections() {
+      let mut delay = Duration::from_millis(database.startup_retry_delay_ms);
+      let mut attempt = 1;
+      loop {
+        match self.db_manager.open_pool(setting).await {
+          Ok(()) => break,
+          Err(e) if started.elapsed() + delay <= max_wait => {
+            eprintln!(
+              "Database of pool '{}' is not reachable yet (attempt {}), retrying in {} ms: {:#}",
+              setting.pool_name,
+              attempt,
+              delay.as_millis(),
+              e
+            );
+            tokio::time::sleep(delay).await;
+            delay = (delay * 2).min(MAX_STARTUP_RETRY_DELAY);
+            attempt += 1;
+          }
+          Err(e) => {
+            return Err(anyhow::anyhow!(
+              "Database of pool '{}' is still not reachable after {} attempt(s) in {} s, giving up: {:#}",
+              setting.pool_name,
+              attempt,
+              started.elapsed().as_secs(),
+              e
+            ));
+          }
+        }
+      }
+    }
+    Ok(())
+  }
+
+  /// A connection to the database of `tenant`, see `database.tenants`.
+  pub async fn tenant_client(&self, tenant: &str) -> Result<DbConnection> {
+    self
+      .db_manager
+      .get_tenant_client(&self.config.database, tenant)
+      .await
+  }
+
+  pub fn is_ready(&self) -> bool {
+    self.startup_complete.load(Ordering::Acquire)
+  }
+
+  pub fn mark_ready(&self) {
+    self.startup_complete.store(true, Ordering::Release);
   }
 }
@@ -0,0 +1,11 @@
+use api::cli::{self, Cli};
+use clap::Parser;
+
+// Admin tasks for deployments, e.g. `cli create-admin --user-name admin --email admin@example.com`
+#[actix_web::main]
+async fn main() {
+  unsafe {
+    openssl_probe::init_openssl_env_vars();
+  }
+  std::process::exit(cli::execute(Cli::parse()).await);
+}
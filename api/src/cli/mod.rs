@@ -0,0 +1,290 @@
+pub mod schema_diff;
+pub mod schema_repo;
+
+use clap::{Parser, Subcommand, ValueEnum};
+use validator::Validate;
+
+use crate::{
+  api_version::ApiVersion,
+  app_settings::CONFIG_PATH,
+  app_state::AppState,
+  features::{
+    health_check::{HealthState, check_pool},
+    users::{user_dto::UserRegisterReqDto, user_entity::UserRole},
+  },
+  i18n::Locale,
+  migrations, seed, swaggers,
+  utils::mailer::EmailTemplate,
+};
+
+/// Bootstrap and maintenance tasks, run against the same settings and database as the server.
+#[derive(Parser)]
+#[command(name = "cli", version)]
+pub struct Cli {
+  /// Settings file to use
+  #[arg(long, global = true, default_value = CONFIG_PATH)]
+  pub config: String,
+  #[command(subcommand)]
+  pub command: Command,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+  /// Create an admin user, e.g. the first login of a new deployment
+  CreateAdmin {
+    #[arg(long)]
+    user_name: String,
+    #[arg(long)]
+    email: String,
+    /// Display name, defaults to the user name
+    #[arg(long)]
+    name: Option<String>,
+    /// Generated and printed when left out
+    #[arg(long, env = "ADMIN_PASSWORD", hide_env_values = true)]
+    password: Option<String>,
+  },
+  /// Write a new random `jwt.secret_key` to the settings file, signing every user out
+  RotateJwtSecret,
+  /// Apply the migrations not yet recorded in the database
+  Migrate,
+  /// Insert the default roles, plus demo users in dev, when they are missing
+  Seed,
+  /// Check that every database pool answers
+  PingDb,
+  /// Compare the live database with migrations/manifest.json
+  SchemaDiff,
+  /// Print every email template in every language with placeholder values
+  EmailPreview,
+  /// Write the OpenAPI document to a file, e.g. for client generators in CI
+  DumpOpenapi {
+    /// Output file, `-` for stdout
+    path: String,
+    /// Defaults to YAML for `.yaml`/`.yml` paths and JSON otherwise
+    #[arg(long, value_enum)]
+    format: Option<DocFormat>,
+    /// `v1`, `v2`, ..., the latest version by default
+    #[arg(long)]
+    api_version: Option<String>,
+  },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum DocFormat {
+  Json,
+  Yaml,
+}
+
+// Needs neither settings nor a database, the document is built from the compiled handlers
+fn dump_openapi(path: &str, format: Option<DocFormat>, api_version: Option<&str>) -> i32 {
+  let version = match api_version {
+    Some(name) => match ApiVersion::from_name(name) {
+      Some(version) => version,
+      None => {
+        eprintln!("Unknown API version '{}'", name);
+        return 2;
+      }
+    },
+    None => ApiVersion::LATEST,
+  };
+  let format = format.unwrap_or(if path.ends_with(".yaml") || path.ends_with(".yml") {
+    DocFormat::Yaml
+  } else {
+    DocFormat::Json
+  });
+
+  let openapi = swaggers::openapi_for(version);
+  let document = match format {
+    DocFormat::Json => openapi.to_pretty_json().map_err(anyhow::Error::from),
+    DocFormat::Yaml => openapi.to_yaml().map_err(anyhow::Error::from),
+  };
+  let written = document.and_then(|document| {
+    if path == "-" {
+      println!("{}", document);
+      Ok(())
+    } else {
+      Ok(std::fs::write(path, document + "\n")?)
+    }
+  });
+  match written {
+    Ok(()) => 0,
+    Err(e) => {
+      eprintln!("Failed to write the OpenAPI document to {}: {}", path, e);
+      1
+    }
+  }
+}
+
+// Prints the text part of every email template in every language with placeholder values
+fn email_preview() -> i32 {
+  for template in EmailTemplate::samples() {
+    for locale in Locale::ALL {
+      match template.render(locale) {
+        Ok(email) => println!(
+          "[{} / {}]\nSubject: {}\n\n{}\n",
+          template.name(),
+          locale.tag(),
+          email.subject,
+          email.text
+        ),
+        Err(e) => {
+          eprintln!(
+            "Failed to render {} ({}): {}",
+            template.name(),
+            locale.tag(),
+            e
+          );
+          return 1;
+        }
+      }
+    }
+  }
+  0
+}
+
+// Edits the file itself, the server picks the new secret up on its next start
+fn rotate_jwt_secret(config_path: &str) -> i32 {
+  let result = std::fs::read_to_string(config_path)
+    .map_err(anyhow::Error::from)
+    .and_then(|content| Ok(serde_json::from_str::<serde_json::Value>(&content)?));
+  let mut config = match result {
+    Ok(config) => config,
+    Err(e) => {
+      eprintln!("Failed to read {}: {}", config_path, e);
+      return 1;
+    }
+  };
+  let Some(secret_key) = config.pointer_mut("/jwt/secret_key") else {
+    eprintln!("{} has no jwt.secret_key", config_path);
+    return 1;
+  };
+
+  let secret = format!(
+    "{}{}",
+    uuid::Uuid::new_v4().simple(),
+    uuid::Uuid::new_v4().simple()
+  );
+  // A `env://`, `file://`, ... reference is kept, the secret has to be stored where it points
+  if let Some(reference) = secret_key.as_str().filter(|value| value.contains("://")) {
+    println!(
+      "jwt.secret_key is read from {}, store this new secret there:\n{}",
+      reference, secret
+    );
+    return 0;
+  }
+  *secret_key = serde_json::Value::String(secret);
+
+  let written = serde_json::to_string_pretty(&config)
+    .map_err(anyhow::Error::from)
+    .and_then(|content| Ok(std::fs::write(config_path, content + "\n")?));
+  match written {
+    Ok(_) => {
+      println!(
+        "Rotated jwt.secret_key in {}, restart the server to apply it",
+        config_path
+      );
+      0
+    }
+    Err(e) => {
+      eprintln!("Failed to write {}: {}", config_path, e);
+      1
+    }
+  }
+}
+
+async fn create_admin(
+  state: &AppState,
+  user_name: String,
+  email: String,
+  name: Option<String>,
+  password: Option<String>,
+) -> i32 {
+  let generated = password.is_none();
+  let user = UserRegisterReqDto {
+    name: name.unwrap_or_else(|| user_name.clone()),
+    user_name,
+    email,
+    password: password.unwrap_or_else(|| uuid::Uuid::new_v4().simple().to_string()),
+    role: UserRole::Admin.to_str().to_string(),
+  };
+  if let Err(e) = user.validate() {
+    eprintln!("Invalid admin user: {}", e);
+    return 2;
+  }
+
+  match state.user_repo().create(&user).await {
+    Ok(_) => {
+      println!("Created admin user {}", user.user_name);
+      if generated {
+        println!("Password: {}", user.password);
+      }
+      0
+    }
+    Err(e) => {
+      eprintln!("Failed to create admin user: {}", e);
+      1
+    }
+  }
+}
+
+// Returns 1 when any pool is unreachable
+async fn ping_db(state: &AppState) -> i32 {
+  let mut code = 0;
+  for pool_name in state.db_manager.pool_names() {
+    let health = check_pool(state, pool_name).await;
+    match health.error {
+      Some(error) => {
+        eprintln!("{}: unreachable ({})", health.name, error);
+        code = 1;
+      }
+      None if health.status == HealthState::Degraded => {
+        println!("{}: slow, {} ms", health.name, health.latency_ms)
+      }
+      None => println!("{}: ok, {} ms", health.name, health.latency_ms),
+    }
+  }
+  code
+}
+
+/// Runs `cli.command` and returns the process exit code. Settings and pools are only loaded
+/// for the commands that need them.
+pub async fn execute(cli: Cli) -> i32 {
+  let command = match cli.command {
+    Command::EmailPreview => return email_preview(),
+    Command::DumpOpenapi {
+      path,
+      format,
+      api_version,
+    } => return dump_openapi(&path, format, api_version.as_deref()),
+    Command::RotateJwtSecret => return rotate_jwt_secret(&cli.config),
+    command => command,
+  };
+
+  let state = match AppState::load_setting(&cli.config).await {
+    Ok(state) => state,
+    Err(e) => {
+      eprintln!("Failed to initialize app state: {:#}", e);
+      return 1;
+    }
+  };
+  if let Err(e) = state.init_db_manager().await {
+    eprintln!("Failed to initialize app state: {}", e);
+    return 1;
+  }
+
+  match command {
+    Command::CreateAdmin {
+      user_name,
+      email,
+      name,
+      password,
+    } => create_admin(&state, user_name, email, name, password).await,
+    Command::Migrate => migrations::run(&state).await,
+    Command::Seed => seed::run(&state).await,
+    Command::PingDb => ping_db(&state).await,
+    Command::SchemaDiff => schema_diff::run(&state).await,
+    // Answered above without loading the settings
+    Command::EmailPreview | Command::RotateJwtSecret | Command::DumpOpenapi { .. } => {
+      unreachable!()
+    }
+  }
+}
@@ -0,0 +1,113 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::Deserialize;
+
+use crate::{app_state::AppState, cli::schema_repo::SchemaRepo};
+
+const MANIFEST: &str = include_str!("../../migrations/manifest.json");
+
+#[derive(Deserialize)]
+pub struct SchemaManifest {
+  pub schema: String,
+  pub tables: Vec<TableManifest>,
+  pub procedures: Vec<ProcedureManifest>,
+}
+
+#[derive(Deserialize)]
+pub struct TableManifest {
+  pub name: String,
+  pub columns: Vec<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ProcedureManifest {
+  pub name: String,
+  pub parameter_count: i32,
+}
+
+impl SchemaManifest {
+  pub fn load() -> Self {
+    serde_json::from_str(MANIFEST).expect("Failed to parse migrations/manifest.json")
+  }
+}
+
+// Compare the live database with the manifest, returns 0 when they match
+pub async fn run(state: &AppState) -> i32 {
+  let manifest = SchemaManifest::load();
+  let mut repo = SchemaRepo::new(state);
+
+  let live_columns = match repo.get_table_columns(&manifest.schema).await {
+    Ok(columns) => columns,
+    Err(e) => {
+      eprintln!("Failed to read tables: {}", e);
+      return 1;
+    }
+  };
+  let live_procedures = match repo.get_procedures(&manifest.schema).await {
+    Ok(procedures) => procedures,
+    Err(e) => {
+      eprintln!("Failed to read procedures: {}", e);
+      return 1;
+    }
+  };
+
+  let mut live_tables: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+  for c in live_columns {
+    live_tables
+      .entry(c.table_name.to_lowercase())
+      .or_default()
+      .insert(c.column_name.to_lowercase());
+  }
+  let live_procs: BTreeMap<String, i32> = live_procedures
+    .into_iter()
+    .map(|p| (p.name.to_lowercase(), p.parameter_count))
+    .collect();
+
+  let mut problems: Vec<String> = Vec::new();
+  let schema = &manifest.schema;
+
+  for table in &manifest.tables {
+    let expected: BTreeSet<String> = table.columns.iter().map(|c| c.to_lowercase()).collect();
+    match live_tables.remove(&table.name.to_lowercase()) {
+      None => problems.push(format!("Missing table: [{}].[{}]", schema, table.name)),
+      Some(actual) => {
+        let missing: Vec<&String> = expected.difference(&actual).collect();
+        let extra: Vec<&String> = actual.difference(&expected).collect();
+        if !missing.is_empty() || !extra.is_empty() {
+          problems.push(format!(
+            "Mismatched table: [{}].[{}] missing columns {:?}, extra columns {:?}",
+            schema, table.name, missing, extra
+          ));
+        }
+      }
+    }
+  }
+  for name in live_tables.keys() {
+    problems.push(format!("Extra table: [{}].[{}]", schema, name));
+  }
+
+  let mut live_procs = live_procs;
+  for proc in &manifest.procedures {
+    match live_procs.remove(&proc.name.to_lowercase()) {
+      None => problems.push(format!("Missing procedure: [{}].[{}]", schema, proc.name)),
+      Some(count) if count != proc.parameter_count => problems.push(format!(
+        "Mismatched procedure: [{}].[{}] expected {} parameters, found {}",
+        schema, proc.name, proc.parameter_count, count
+      )),
+      Some(_) => {}
+    }
+  }
+  for name in live_procs.keys() {
+    problems.push(format!("Extra procedure: [{}].[{}]", schema, name));
+  }
+
+  if problems.is_empty() {
+    println!("Database schema matches the migration manifest");
+    return 0;
+  }
+  for p in &problems {
+    println!("{}", p);
+  }
+  println!("{} difference(s) found", problems.len());
+  1
+}
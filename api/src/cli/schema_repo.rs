@@ -0,0 +1,83 @@
+use crate::{
+  app_state::AppState,
+  db::{DbConnection, RowExt, SqlRepo},
+};
+
+use anyhow::Result;
+use domner_tech_sql_client::CommandType;
+
+pub struct TableColumn {
+  pub table_name: String,
+  pub column_name: String,
+}
+
+pub struct ProcedureInfo {
+  pub name: String,
+  pub parameter_count: i32,
+}
+
+pub struct SchemaRepo<'a> {
+  pub app_state: &'a AppState,
+}
+
+impl<'a> SchemaRepo<'a> {
+  pub fn new(app_state: &'a AppState) -> Self {
+    Self { app_state }
+  }
+
+  async fn get_client(&self) -> DbConnection {
+    match self
+      .app_state
+      .db_manager
+      .get_client(&self.app_state.config.database.sql_server.pool_name)
+      .await
+    {
+      Ok(client) => client,
+      Err(e) => panic!("Failed to get DB client: {}", e),
+    }
+  }
+
+  pub async fn get_table_columns(&mut self, schema: &str) -> Result<Vec<TableColumn>> {
+    let mut client_pool = self.get_client().await;
+
+    let columns = SqlRepo::execute_command_query(
+      &mut client_pool,
+      "SELECT t.name AS table_name, c.name AS column_name
+       FROM sys.tables t
+       JOIN sys.columns c ON c.object_id = t.object_id
+       WHERE SCHEMA_NAME(t.schema_id) = @P1",
+      &[&schema],
+      CommandType::Text,
+      |row| {
+        Ok(TableColumn {
+          table_name: row.column("table_name")?,
+          column_name: row.column("column_name")?,
+        })
+      },
+    )
+    .await?;
+    Ok(columns)
+  }
+
+  pub async fn get_procedures(&mut self, schema: &str) -> Result<Vec<ProcedureInfo>> {
+    let mut client_pool = self.get_client().await;
+
+    let procedures = SqlRepo::execute_command_query(
+      &mut client_pool,
+      "SELECT p.name AS name,
+         (SELECT COUNT(*) FROM sys.parameters pa WHERE pa.object_id = p.object_id) AS parameter_count
+       FROM sys.procedures p
+       WHERE SCHEMA_NAME(p.schema_id) = @P1",
+      &[&schema],
+      CommandType::Text,
+      |row| {
+        Ok(ProcedureInfo {
+          name: row.column("name")?,
+          parameter_count: row.column("parameter_count")?,
+        })
+      },
+    )
+    .await?;
+    Ok(procedures)
+  }
+}
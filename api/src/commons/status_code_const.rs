@@ -9,4 +9,42 @@ impl StatusCodeConst {
   pub const UQIQUE_CONSTRAINT: &'static str = "UQIQUE_CONSTRAINT";
   pub const TOKEN_MISSING: &'static str = "TOKEN_MISSING";
   pub const FORBIDDEN: &'static str = "FORBIDDEN";
+  pub const ACCOUNT_DISABLED: &'static str = "ACCOUNT_DISABLED";
+  pub const QUOTA_EXCEEDED: &'static str = "QUOTA_EXCEEDED";
+  pub const POOL_EXHAUSTED: &'static str = "POOL_EXHAUSTED";
+  pub const QUERY_TIMEOUT: &'static str = "QUERY_TIMEOUT";
+  pub const PAYLOAD_TOO_LARGE: &'static str = "PAYLOAD_TOO_LARGE";
+  pub const RATE_LIMITED: &'static str = "RATE_LIMITED";
+  pub const UNHEALTHY: &'static str = "UNHEALTHY";
+  pub const METHOD_NOT_ALLOWED: &'static str = "METHOD_NOT_ALLOWED";
+  pub const VALIDATION_FAILED: &'static str = "VALIDATION_FAILED";
+  pub const ROLLED_BACK: &'static str = "ROLLED_BACK";
+  pub const DEVICE_MISMATCH: &'static str = "DEVICE_MISMATCH";
+  pub const CAPTCHA_REQUIRED: &'static str = "CAPTCHA_REQUIRED";
+  pub const BUSY: &'static str = "BUSY";
+
+  /// Every code above, listed as the `code` enum of the OpenAPI documents.
+  pub const ALL: [&'static str; 21] = [
+    Self::SUCCESS,
+    Self::ERROR,
+    Self::SERVER_ERROR,
+    Self::NOT_FOUND,
+    Self::UNAUTHORIZED,
+    Self::UQIQUE_CONSTRAINT,
+    Self::TOKEN_MISSING,
+    Self::FORBIDDEN,
+    Self::ACCOUNT_DISABLED,
+    Self::QUOTA_EXCEEDED,
+    Self::POOL_EXHAUSTED,
+    Self::QUERY_TIMEOUT,
+    Self::PAYLOAD_TOO_LARGE,
+    Self::RATE_LIMITED,
+    Self::UNHEALTHY,
+    Self::METHOD_NOT_ALLOWED,
+    Self::VALIDATION_FAILED,
+    Self::ROLLED_BACK,
+    Self::DEVICE_MISMATCH,
+    Self::CAPTCHA_REQUIRED,
+    Self::BUSY,
+  ];
 }
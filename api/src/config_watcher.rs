@@ -0,0 +1,128 @@
+use std::{
+  str::FromStr,
+  time::{Duration, SystemTime},
+};
+
+use actix_web::web;
+use anyhow::Result;
+use log::LevelFilter;
+
+use crate::{
+  app_settings::{AppSetting, DatabaseConnectionInfo, RuntimeSetting},
+  app_state::AppState,
+  middleware::cors::CorsPolicy,
+};
+
+// How often the config file's modification time is checked
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+// `rust_log` is a bare level such as `debug`, anything else leaves the level as is
+pub fn apply_log_level(rust_log: &str) {
+  if let Ok(level) = LevelFilter::from_str(rust_log) {
+    log::set_max_level(level);
+  }
+}
+
+fn modified_at(path: &str) -> Option<SystemTime> {
+  std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+fn read_setting(path: &str) -> Result<AppSetting> {
+  let file = std::fs::File::open(path)?;
+  Ok(serde_json::from_reader(file)?)
+}
+
+fn connection_changed(current: &DatabaseConnectionInfo, reloaded: &DatabaseConnectionInfo) -> bool {
+  current.conn_str != reloaded.conn_str
+    || current.pool_size != reloaded.pool_size
+    || current.pool_name != reloaded.pool_name
+    || current.tls != reloaded.tls
+    || current.read_only != reloaded.read_only
+    || current.failover_partner != reloaded.failover_partner
+}
+
+// Pools, listeners and secrets are built once at startup and are not reloaded
+fn warn_structural_changes(current: &AppSetting, reloaded: &AppSetting) {
+  let server = &current.server;
+  let db = &current.database.sql_server;
+  let changed = [
+    ("environment", current.environment != reloaded.environment),
+    (
+      "server",
+      server.host != reloaded.server.host || server.port != reloaded.server.port,
+    ),
+    (
+      "database",
+      connection_changed(db, &reloaded.database.sql_server)
+        || current.database.databases.len() != reloaded.database.databases.len()
+        || current.database.databases.iter().any(|(name, db)| {
+          reloaded
+            .database
+            .databases
+            .get(name)
+            .is_none_or(|reloaded| connection_changed(db, reloaded))
+        })
+        || current
+          .database
+          .tenants
+          .as_ref()
+          .map(|t| &t.conn_str_template)
+          != reloaded
+            .database
+            .tenants
+            .as_ref()
+            .map(|t| &t.conn_str_template),
+    ),
+    ("jwt", current.jwt.secret_key != reloaded.jwt.secret_key),
+  ];
+  for (section, _) in changed.iter().filter(|(_, changed)| *changed) {
+    eprintln!(
+      "Config section '{}' changed, restart the API to apply it",
+      section
+    );
+  }
+}
+
+// `previous` is the file as last read, secret references unresolved like the reloaded one
+fn reload(
+  state: &AppState,
+  cors: &CorsPolicy,
+  path: &str,
+  previous: &mut Option<AppSetting>,
+) -> Result<()> {
+  let reloaded = read_setting(path)?;
+  let runtime = RuntimeSetting::from(&reloaded);
+  // Origins are the only part that can be rejected, so nothing is swapped when they fail
+  cors.reload_origins(&runtime.cors, &state.config.environment)?;
+  if let Some(previous) = previous.as_ref() {
+    warn_structural_changes(previous, &reloaded);
+  }
+
+  apply_log_level(&runtime.rust_log);
+  state.reload_runtime(runtime);
+  *previous = Some(reloaded);
+  Ok(())
+}
+
+/// Polls the config file and swaps in log level, feature flags and CORS origins when it
+/// changes. A file that fails to parse is reported and the previous settings stay active.
+pub fn spawn(state: web::Data<AppState>, cors: CorsPolicy, path: &'static str) {
+  actix_web::rt::spawn(async move {
+    let mut last_modified = modified_at(path);
+    let mut previous = read_setting(path).ok();
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+    loop {
+      interval.tick().await;
+      let modified = modified_at(path);
+      if modified.is_none() || modified == last_modified {
+        continue;
+      }
+      last_modified = modified;
+
+      match reload(&state, &cors, path, &mut previous) {
+        Ok(()) => println!("Reloaded runtime settings from {}", path),
+        Err(e) => eprintln!("Failed to reload {}, keeping current settings: {}", path, e),
+      }
+    }
+  });
+}
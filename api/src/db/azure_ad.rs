@@ -0,0 +1,155 @@
+use std::sync::Arc;
+
+use anyhow::{Context, Result, anyhow};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use chrono::{DateTime, Duration, Utc};
+use serde_json::Value;
+use tokio::sync::Mutex;
+
+use crate::{
+  app_settings::{AzureAdSetting, AzureAdTokenSource},
+  secrets::SecretResolvers,
+};
+
+// Audience of tokens for Azure SQL Database and SQL Managed Instance
+const SQL_RESOURCE: &str = "https://database.windows.net/";
+// Instance Metadata Service of Azure VMs and AKS nodes
+const IMDS_TOKEN_URL: &str = "http://169.254.169.254/metadata/identity/oauth2/token";
+
+struct CachedToken {
+  value: String,
+  expires_at: DateTime<Utc>,
+}
+
+/// Azure AD access token the connections of a pool sign in with. It is fetched again once it
+/// is within `refresh_before_secs` of expiring, so a newly opened connection never presents
+/// an expired token. Clones share the token.
+#[derive(Clone)]
+pub struct AzureAdToken {
+  setting: AzureAdSetting,
+  client: reqwest::Client,
+  // Held while fetching, so connections opened at once share one request
+  current: Arc<Mutex<Option<CachedToken>>>,
+}
+
+impl AzureAdToken {
+  pub fn new(setting: &AzureAdSetting) -> Self {
+    Self {
+      setting: setting.clone(),
+      client: reqwest::Client::new(),
+      current: Arc::new(Mutex::new(None)),
+    }
+  }
+
+  /// The current token, fetched again when it is about to expire.
+  pub async fn get(&self) -> Result<String> {
+    let mut current = self.current.lock().await;
+    let now = Utc::now();
+    let refresh_before = Duration::seconds(self.setting.refresh_before_secs as i64);
+    if let Some(token) = current.as_ref()
+      && token.expires_at - refresh_before > now
+    {
+      return Ok(token.value.clone());
+    }
+
+    match self.fetch().await {
+      Ok(token) => {
+        let value = token.value.clone();
+        *current = Some(token);
+        Ok(value)
+      }
+      // Still good for a while, the next connection tries again
+      Err(e)
+        if let Some(token) = current.as_ref()
+          && token.expires_at > now =>
+      {
+        eprintln!(
+          "Failed to refresh the Azure AD token, using the current one: {:#}",
+          e
+        );
+        Ok(token.value.clone())
+      }
+      Err(e) => Err(e),
+    }
+  }
+
+  async fn fetch(&self) -> Result<CachedToken> {
+    match &self.setting.token_source {
+      AzureAdTokenSource::ManagedIdentity { client_id } => {
+        self.fetch_managed_identity(client_id.as_deref()).await
+      }
+      AzureAdTokenSource::Secret { token } => {
+        let value = SecretResolvers::from_env().resolve(token).await?;
+        let expires_at = token_expiry(&value)?;
+        Ok(CachedToken { value, expires_at })
+      }
+    }
+  }
+
+  // App Service and Container Apps set IDENTITY_ENDPOINT, VMs and AKS answer on IMDS
+  async fn fetch_managed_identity(&self, client_id: Option<&str>) -> Result<CachedToken> {
+    let mut params = vec![("resource", SQL_RESOURCE)];
+    if let Some(client_id) = client_id {
+      params.push(("client_id", client_id));
+    }
+    let request = match (
+      std::env::var("IDENTITY_ENDPOINT"),
+      std::env::var("IDENTITY_HEADER"),
+    ) {
+      (Ok(endpoint), Ok(header)) => {
+        params.push(("api-version", "2019-08-01"));
+        self
+          .client
+          .get(url::Url::parse_with_params(&endpoint, &params)?)
+          .header("X-IDENTITY-HEADER", header)
+      }
+      _ => {
+        params.push(("api-version", "2018-02-01"));
+        self
+          .client
+          .get(url::Url::parse_with_params(IMDS_TOKEN_URL, &params)?)
+          .header("Metadata", "true")
+      }
+    };
+    let body: Value = request
+      .send()
+      .await?
+      .error_for_status()?
+      .json()
+      .await
+      .context("Failed to read the managed identity token")?;
+
+    let value = body["access_token"]
+      .as_str()
+      .ok_or_else(|| anyhow!("The managed identity endpoint answered without access_token"))?
+      .to_string();
+    // A string of seconds since the epoch, a number on some hosts
+    let expires_on = match &body["expires_on"] {
+      Value::String(secs) => secs.parse::<i64>().ok(),
+      secs => secs.as_i64(),
+    };
+    let expires_at = match expires_on.and_then(|secs| DateTime::from_timestamp(secs, 0)) {
+      Some(expires_at) => expires_at,
+      None => token_expiry(&value)?,
+    };
+    Ok(CachedToken { value, expires_at })
+  }
+}
+
+/// When `token` expires, read from the `exp` claim of the JWT without checking its signature,
+/// SQL Server does that.
+pub fn token_expiry(token: &str) -> Result<DateTime<Utc>> {
+  let payload = token
+    .split('.')
+    .nth(1)
+    .ok_or_else(|| anyhow!("The Azure AD token is not a JWT"))?;
+  let claims: Value = serde_json::from_slice(
+    &URL_SAFE_NO_PAD
+      .decode(payload.trim_end_matches('='))
+      .context("The Azure AD token payload is not base64url")?,
+  )?;
+  claims["exp"]
+    .as_i64()
+    .and_then(|exp| DateTime::from_timestamp(exp, 0))
+    .ok_or_else(|| anyhow!("The Azure AD token has no exp claim"))
+}
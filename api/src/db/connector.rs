@@ -0,0 +1,139 @@
+use std::ops::{Deref, DerefMut};
+
+use crate::db::{azure_ad::AzureAdToken, statement_cache::StatementCache};
+use anyhow::Result;
+use deadpool::managed::{self, Metrics, RecycleResult};
+use domner_tech_sql_client::{
+  pool_manager::{DbClient, DbManager as SqlDbManager, PooledClient},
+  types::sql::mssql::{AuthMethod, Client, Config},
+};
+use tokio::net::TcpStream;
+use tokio_util::compat::TokioAsyncWriteCompatExt;
+
+/// The connections of one named pool of `DbManager`.
+pub type Pool = managed::Pool<Connector>;
+
+/// A pooled connection, shaped as the `PooledClient` the sql client's `SqlRepo` runs on,
+/// with the statements prepared on it.
+pub struct PoolEntry(PooledClient, StatementCache);
+
+impl PoolEntry {
+  /// The driver's client next to the statements prepared on it.
+  pub fn with_statements(&mut self) -> (&mut DbClient, &mut StatementCache) {
+    (self.0.client(), &mut self.1)
+  }
+}
+
+impl Deref for PoolEntry {
+  type Target = PooledClient;
+
+  fn deref(&self) -> &Self::Target {
+    &self.0
+  }
+}
+
+impl DerefMut for PoolEntry {
+  fn deref_mut(&mut self) -> &mut Self::Target {
+    &mut self.0
+  }
+}
+
+impl Drop for PoolEntry {
+  fn drop(&mut self) {
+    // Closes the connection, the sql client would try to hand it back to its own pools
+    drop(self.0.client.take());
+  }
+}
+
+/// Opens the connections of a pool from its connection string, the pool itself is deadpool's.
+pub struct Connector {
+  pool_name: String,
+  conn_str: String,
+  statement_cache_size: usize,
+  // Signs in with this instead of the connection string's credentials
+  azure_ad: Option<AzureAdToken>,
+  // Never holds a connection, `PooledClient` just needs one to point at
+  detached: SqlDbManager,
+}
+
+impl Connector {
+  pub fn new(
+    pool_name: &str,
+    conn_str: &str,
+    statement_cache_size: usize,
+    azure_ad: Option<AzureAdToken>,
+  ) -> Self {
+    Self {
+      pool_name: pool_name.to_string(),
+      conn_str: conn_str.to_string(),
+      statement_cache_size,
+      azure_ad,
+      detached: SqlDbManager::new(),
+    }
+  }
+
+  /// A pool of up to `max_size` connections, opened on first use.
+  pub fn pool(self, max_size: u32) -> Result<Pool> {
+    Ok(Pool::builder(self).max_size(max_size as usize).build()?)
+  }
+
+  // Accepts ADO.NET and JDBC connection strings like the sql client did
+  fn config(&self) -> Result<Config> {
+    let mut config = Config::from_ado_string(&self.conn_str)
+      .or_else(|_| Config::from_jdbc_string(&self.conn_str))
+      .map_err(|e| {
+        anyhow::anyhow!(
+          "Invalid connection string for pool '{}': {}",
+          self.pool_name,
+          e
+        )
+      })?;
+    // The sql client trusted any server certificate, kept for connection strings that don't
+    // say how to check it so existing deployments keep connecting
+    let sets_trust = self.conn_str.split(';').any(|pair| {
+      pair.split('=').next().is_some_and(|key| {
+        key
+          .trim()
+          .to_ascii_lowercase()
+          .starts_with("trustservercertificate")
+      })
+    });
+    if !sets_trust {
+      config.trust_cert();
+    }
+    Ok(config)
+  }
+}
+
+impl managed::Manager for Connector {
+  type Type = PoolEntry;
+
+  type Error = anyhow::Error;
+
+  async fn create(&self) -> Result<PoolEntry> {
+    let mut config = self.config()?;
+    if let Some(azure_ad) = &self.azure_ad {
+      config.authentication(AuthMethod::AADToken(azure_ad.get().await?));
+    }
+    let tcp = TcpStream::connect(config.get_addr()).await?;
+    tcp.set_nodelay(true)?;
+    let client = Client::connect(config, tcp.compat_write()).await?;
+    Ok(PoolEntry(
+      PooledClient {
+        name: self.pool_name.clone(),
+        client: Some(DbClient::Mssql(client)),
+        manager: self.detached.clone(),
+      },
+      StatementCache::new(self.statement_cache_size),
+    ))
+  }
+
+  // Checked by `DbManager` on checkout when `validate_on_checkout` is set
+  async fn recycle(
+    &self,
+    _entry: &mut PoolEntry,
+    _metrics: &Metrics,
+  ) -> RecycleResult<Self::Error> {
+    Ok(())
+  }
+}
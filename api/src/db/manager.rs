@@ -0,0 +1,687 @@
+use std::{
+  collections::HashMap,
+  fmt,
+  ops::{Deref, DerefMut},
+  sync::{
+    Arc, RwLock,
+    atomic::{AtomicBool, AtomicU64, Ordering},
+  },
+  time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use deadpool::managed::{Object, PoolError};
+use domner_tech_sql_client::{
+  CommandType, SqlRepo,
+  pool_manager::{DbClient, PooledClient},
+};
+use tokio::sync::{Mutex, OwnedSemaphorePermit, Semaphore};
+
+use crate::{
+  app_settings::{DatabaseConnectionInfo, DatabaseSetting, TenantDatabaseSetting},
+  db::{
+    QueryMetrics,
+    azure_ad::AzureAdToken,
+    connector::{Connector, Pool},
+    statement_cache::StatementCache,
+  },
+};
+
+/// Pools opened by `DbManager::get_tenant_client` are named this followed by the tenant.
+pub const TENANT_POOL_PREFIX: &str = "tenant:";
+// The name ends up in a connection string, keep it to what a database name allows
+const MAX_TENANT_NAME_LEN: usize = 64;
+
+// What is needed to rebuild a pool after the server went away
+#[derive(Clone)]
+struct PoolConfig {
+  // Swapped for a new pool to replace the connections, the old one closes its connections as
+  // they are dropped
+  inner: Arc<RwLock<Pool>>,
+  conn_str: String,
+  // Same connection string pointed at `failover_partner`
+  failover_conn_str: Option<String>,
+  // Whether the pool currently runs on the failover partner
+  on_partner: Arc<AtomicBool>,
+  pool_size: u32,
+  statement_cache_size: usize,
+  // Shared by every pool built for this config, a replaced pool keeps the cached token
+  azure_ad: Option<AzureAdToken>,
+  validate_on_checkout: bool,
+  checkout_timeout: Duration,
+  query_timeout: Option<Duration>,
+  slow_query: Option<Duration>,
+  idle_timeout: Option<Duration>,
+  max_lifetime: Option<Duration>,
+  // One permit per connection, checkouts wait here instead of failing on an empty pool
+  permits: Arc<Semaphore>,
+  counters: Arc<PoolCounters>,
+}
+
+// Running totals of one pool, updated on every checkout
+#[derive(Default)]
+struct PoolCounters {
+  waiters: AtomicU64,
+  checkouts: AtomicU64,
+  timeouts: AtomicU64,
+  reconnects: AtomicU64,
+  checkout_micros_total: AtomicU64,
+  checkout_micros_max: AtomicU64,
+  // Unix time in ms of the last checkout, or of pool creation before the first one
+  last_checkout_ms: AtomicU64,
+  // Unix time in ms the pool's connections were last (re)opened
+  opened_ms: AtomicU64,
+}
+
+fn unix_ms() -> u64 {
+  chrono::Utc::now().timestamp_millis().max(0) as u64
+}
+
+impl PoolConfig {
+  fn active_conn_str(&self) -> &str {
+    match &self.failover_conn_str {
+      Some(partner) if self.on_partner.load(Ordering::Relaxed) => partner,
+      _ => &self.conn_str,
+    }
+  }
+
+  fn pool(&self) -> Result<Pool> {
+    self
+      .inner
+      .read()
+      .map(|pool| pool.clone())
+      .map_err(|_| anyhow::anyhow!("Pool lock poisoned"))
+  }
+
+  // Replaces the pool's connections, the ones still checked out are closed when dropped
+  fn replace_connections(&self, name: &str) -> Result<()> {
+    let pool = Connector::new(
+      name,
+      self.active_conn_str(),
+      self.statement_cache_size,
+      self.azure_ad.clone(),
+    )
+    .pool(self.pool_size)?;
+    if let Ok(mut inner) = self.inner.write() {
+      *inner = pool;
+    }
+    self.counters.record_opened();
+    Ok(())
+  }
+}
+
+impl PoolCounters {
+  fn record_checkout(&self, elapsed: Duration) {
+    let micros = elapsed.as_micros() as u64;
+    self.checkouts.fetch_add(1, Ordering::Relaxed);
+    self
+      .checkout_micros_total
+      .fetch_add(micros, Ordering::Relaxed);
+    self
+      .checkout_micros_max
+      .fetch_max(micros, Ordering::Relaxed);
+    self.last_checkout_ms.store(unix_ms(), Ordering::Relaxed);
+  }
+
+  fn idle_for(&self) -> Duration {
+    Duration::from_millis(unix_ms().saturating_sub(self.last_checkout_ms.load(Ordering::Relaxed)))
+  }
+
+  fn age(&self) -> Duration {
+    Duration::from_millis(unix_ms().saturating_sub(self.opened_ms.load(Ordering::Relaxed)))
+  }
+
+  fn record_opened(&self) {
+    let now = unix_ms();
+    self.opened_ms.store(now, Ordering::Relaxed);
+    self.last_checkout_ms.store(now, Ordering::Relaxed);
+  }
+}
+
+/// Point-in-time view of a pool, used to diagnose pool sizing.
+pub struct PoolStats {
+  pub pool_name: String,
+  pub pool_size: u32,
+  pub in_use: u32,
+  pub waiters: u64,
+  pub checkouts: u64,
+  pub timeouts: u64,
+  pub reconnects: u64,
+  pub avg_checkout_ms: f64,
+  pub max_checkout_ms: f64,
+}
+
+/// Returned when no connection was given back to the pool within `checkout_timeout_ms`.
+#[derive(Debug)]
+pub struct PoolExhausted {
+  pub pool_name: String,
+  pub waited_ms: u128,
+}
+
+impl fmt::Display for PoolExhausted {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(
+      f,
+      "Pool '{}' exhausted, no connection available after {} ms",
+      self.pool_name, self.waited_ms
+    )
+  }
+}
+
+impl std::error::Error for PoolExhausted {}
+
+/// A checked out client, its pool slot is released when this is dropped.
+pub struct DbConnection {
+  // Declared before the permit so the connection is back in the pool when a waiter gets it
+  client: Object<Connector>,
+  pool_name: String,
+  query_timeout: Option<Duration>,
+  slow_query: Option<Duration>,
+  metrics: QueryMetrics,
+  _permit: OwnedSemaphorePermit,
+}
+
+impl DbConnection {
+  /// Longest a single query may run on this connection, `None` means no limit.
+  pub fn query_timeout(&self) -> Option<Duration> {
+    self.query_timeout
+  }
+
+  pub fn set_query_timeout(&mut self, timeout: Option<Duration>) {
+    self.query_timeout = timeout;
+  }
+
+  pub fn pool_name(&self) -> &str {
+    &self.pool_name
+  }
+
+  /// Queries running at least this long are logged as slow, `None` means never.
+  pub fn slow_query_threshold(&self) -> Option<Duration> {
+    self.slow_query
+  }
+
+  pub fn metrics(&self) -> &QueryMetrics {
+    &self.metrics
+  }
+
+  /// The driver's client next to the statements prepared on this connection.
+  pub fn with_statements(&mut self) -> (&mut DbClient, &mut StatementCache) {
+    self.client.with_statements()
+  }
+}
+
+impl Deref for DbConnection {
+  type Target = PooledClient;
+
+  fn deref(&self) -> &Self::Target {
+    &self.client
+  }
+}
+
+impl DerefMut for DbConnection {
+  fn deref_mut(&mut self) -> &mut Self::Target {
+    &mut self.client
+  }
+}
+
+/// Named deadpool pools of SQL Server connections. Connections broken by a database restart
+/// are detected on checkout and the pool is re-established without restarting the API.
+///
+/// deadpool keeps the connections and opens them on first use. Waiting is first come first
+/// served through the pool's semaphore, health checks are `validate_on_checkout`, and the
+/// counters feed `stats` and `/metrics`.
+#[derive(Clone)]
+pub struct DbManager {
+  pools: Arc<RwLock<HashMap<String, PoolConfig>>>,
+  // Serializes rebuilds so concurrent checkouts don't all reconnect at once
+  reconnecting: Arc<Mutex<()>>,
+  // Serializes opening tenant pools so `max_pools` holds and a tenant gets a single pool
+  opening_tenant: Arc<Mutex<()>>,
+  // Shared with every checked out connection, which records its queries here
+  query_metrics: QueryMetrics,
+}
+
+impl Default for DbManager {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl DbManager {
+  pub fn new() -> Self {
+    Self {
+      pools: Arc::new(RwLock::new(HashMap::new())),
+      reconnecting: Arc::new(Mutex::new(())),
+      opening_tenant: Arc::new(Mutex::new(())),
+      query_metrics: QueryMetrics::default(),
+    }
+  }
+
+  /// Calls and latency of every command run on connections of this manager.
+  pub fn query_metrics(&self) -> &QueryMetrics {
+    &self.query_metrics
+  }
+
+  pub async fn init_pool(&self, setting: &DatabaseConnectionInfo) -> Result<()> {
+    let config = Self::pool_config_for(setting)?;
+    if let Ok(mut pools) = self.pools.write() {
+      pools.insert(setting.pool_name.clone(), config);
+    }
+    Ok(())
+  }
+
+  /// Opens the pool and runs `SELECT 1` on one of its connections, so a server that isn't
+  /// reachable fails here instead of on the first request. Fails over to the partner when
+  /// there is one.
+  pub async fn open_pool(&self, setting: &DatabaseConnectionInfo) -> Result<()> {
+    let name = &setting.pool_name;
+    let opened = self.init_pool(setting).await;
+    let config = self.pool_config(name)?;
+    let checked = match opened {
+      Ok(()) => Self::checkout(&config, name, true).await.map(|_| ()),
+      Err(e) => Err(e),
+    };
+    match checked {
+      Err(e) if config.failover_conn_str.is_some() => Self::fail_over(&config, name, e).await,
+      checked => checked,
+    }
+  }
+
+  fn pool_config_for(setting: &DatabaseConnectionInfo) -> Result<PoolConfig> {
+    let azure_ad = setting.azure_ad.as_ref().map(AzureAdToken::new);
+    let pool = Connector::new(
+      &setting.pool_name,
+      &setting.effective_conn_str(),
+      setting.statement_cache_size,
+      azure_ad.clone(),
+    )
+    .pool(setting.pool_size)?;
+    let config = PoolConfig {
+      inner: Arc::new(RwLock::new(pool)),
+      conn_str: setting.effective_conn_str(),
+      failover_conn_str: setting.failover_conn_str(),
+      on_partner: Arc::new(AtomicBool::new(false)),
+      pool_size: setting.pool_size,
+      statement_cache_size: setting.statement_cache_size,
+      azure_ad,
+      validate_on_checkout: setting.validate_on_checkout,
+      checkout_timeout: Duration::from_millis(setting.checkout_timeout_ms),
+      query_timeout: match setting.query_timeout_secs {
+        0 => None,
+        secs => Some(Duration::from_secs(secs)),
+      },
+      slow_query: match setting.slow_query_ms {
+        0 => None,
+        ms => Some(Duration::from_millis(ms)),
+      },
+      idle_timeout: match setting.idle_timeout_secs {
+        0 => None,
+        secs => Some(Duration::from_secs(secs)),
+      },
+      max_lifetime: match setting.max_lifetime_secs {
+        0 => None,
+        secs => Some(Duration::from_secs(secs)),
+      },
+      permits: Arc::new(Semaphore::new(setting.pool_size as usize)),
+      counters: Arc::new(PoolCounters::default()),
+    };
+    config.counters.record_opened();
+    Ok(config)
+  }
+
+  fn pool_config(&self, name: &str) -> Result<PoolConfig> {
+    self
+      .pools
+      .read()
+      .ok()
+      .and_then(|pools| pools.get(name).cloned())
+      .ok_or_else(|| anyhow::anyhow!("Pool '{}' is not initialized", name))
+  }
+
+  /// Names of every initialized pool, sorted.
+  pub fn pool_names(&self) -> Vec<String> {
+    let Ok(pools) = self.pools.read() else {
+      return Vec::new();
+    };
+    let mut names: Vec<String> = pools.keys().cloned().collect();
+    names.sort();
+    names
+  }
+
+  pub fn stats(&self) -> Vec<PoolStats> {
+    let Ok(pools) = self.pools.read() else {
+      return Vec::new();
+    };
+    let mut stats: Vec<PoolStats> = pools
+      .iter()
+      .map(|(name, config)| {
+        let counters = &config.counters;
+        let checkouts = counters.checkouts.load(Ordering::Relaxed);
+        let total_micros = counters.checkout_micros_total.load(Ordering::Relaxed);
+        PoolStats {
+          pool_name: name.clone(),
+          pool_size: config.pool_size,
+          in_use: config
+            .pool_size
+            .saturating_sub(config.permits.available_permits() as u32),
+          waiters: counters.waiters.load(Ordering::Relaxed),
+          checkouts,
+          timeouts: counters.timeouts.load(Ordering::Relaxed),
+          reconnects: counters.reconnects.load(Ordering::Relaxed),
+          avg_checkout_ms: if checkouts == 0 {
+            0.0
+          } else {
+            total_micros as f64 / checkouts as f64 / 1000.0
+          },
+          max_checkout_ms: counters.checkout_micros_max.load(Ordering::Relaxed) as f64 / 1000.0,
+        }
+      })
+      .collect();
+    stats.sort_by(|a, b| a.pool_name.cmp(&b.pool_name));
+    stats
+  }
+
+  // Only called with a permit held, so deadpool has a connection or room to open one
+  async fn checkout(config: &PoolConfig, name: &str, validate: bool) -> Result<Object<Connector>> {
+    let mut client = config.pool()?.get().await.map_err(|e| match e {
+      PoolError::Backend(e) => e,
+      e => anyhow::anyhow!("Pool '{}': {}", name, e),
+    })?;
+    if validate
+      && let Err(e) =
+        SqlRepo::execute_command_none_query(&mut client, "SELECT 1", &[], CommandType::Text).await
+    {
+      // Closed rather than handed back, a broken connection has no place in the pool
+      drop(Object::take(client));
+      return Err(e);
+    }
+    Ok(client)
+  }
+
+  // Waits for a free slot up to the pool's checkout timeout
+  async fn acquire_permit(&self, name: &str, config: &PoolConfig) -> Result<OwnedSemaphorePermit> {
+    let started = Instant::now();
+    config.counters.waiters.fetch_add(1, Ordering::Relaxed);
+    let acquired = tokio::time::timeout(
+      config.checkout_timeout,
+      config.permits.clone().acquire_owned(),
+    )
+    .await;
+    config.counters.waiters.fetch_sub(1, Ordering::Relaxed);
+
+    match acquired {
+      Ok(Ok(permit)) => Ok(permit),
+      // The semaphore is only closed by `shutdown`
+      Ok(Err(_)) => Err(anyhow::anyhow!("Pool '{}' is shut down", name)),
+      Err(_) => {
+        config.counters.timeouts.fetch_add(1, Ordering::Relaxed);
+        Err(
+          PoolExhausted {
+            pool_name: name.to_string(),
+            waited_ms: started.elapsed().as_millis(),
+          }
+          .into(),
+        )
+      }
+    }
+  }
+
+  pub async fn get_client(&self, name: &str) -> Result<DbConnection> {
+    let config = self.pool_config(name)?;
+    let started = Instant::now();
+    let permit = self.acquire_permit(name, &config).await?;
+
+    let client = match Self::checkout(&config, name, config.validate_on_checkout).await {
+      Ok(client) => client,
+      Err(e) => {
+        eprintln!(
+          "Connection from pool '{}' is unusable, reconnecting: {}",
+          name, e
+        );
+        self.reconnect(name, &config).await?;
+        Self::checkout(&config, name, config.validate_on_checkout).await?
+      }
+    };
+    config.counters.record_checkout(started.elapsed());
+    Ok(DbConnection {
+      client,
+      pool_name: name.to_string(),
+      query_timeout: config.query_timeout,
+      slow_query: config.slow_query,
+      metrics: self.query_metrics.clone(),
+      _permit: permit,
+    })
+  }
+
+  /// A connection to the database of `tenant`, its pool is opened on first use from
+  /// `database.tenants` and inherits every other setting from `database.sql_server`.
+  pub async fn get_tenant_client(
+    &self,
+    setting: &DatabaseSetting,
+    tenant: &str,
+  ) -> Result<DbConnection> {
+    let Some(tenants) = &setting.tenants else {
+      anyhow::bail!("Tenant databases are not configured");
+    };
+    if tenant.is_empty()
+      || tenant.len() > MAX_TENANT_NAME_LEN
+      || !tenant
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+      anyhow::bail!("Invalid tenant name '{}'", tenant);
+    }
+
+    let name = format!("{}{}", TENANT_POOL_PREFIX, tenant);
+    if self.pool_config(&name).is_err() {
+      self
+        .open_tenant_pool(&name, tenant, tenants, &setting.sql_server)
+        .await?;
+    }
+    self.get_client(&name).await
+  }
+
+  async fn open_tenant_pool(
+    &self,
+    name: &str,
+    tenant: &str,
+    tenants: &TenantDatabaseSetting,
+    base: &DatabaseConnectionInfo,
+  ) -> Result<()> {
+    let _guard = self.opening_tenant.lock().await;
+    // Another request for the same tenant may have opened it while this one waited
+    if self.pool_config(name).is_ok() {
+      return Ok(());
+    }
+
+    let by_idle = self.tenant_pools_by_idle();
+    if by_idle.len() >= tenants.max_pools as usize
+      && !by_idle.iter().any(|(open, _)| self.close_tenant_pool(open))
+    {
+      // Every open tenant pool has a connection checked out
+      return Err(
+        PoolExhausted {
+          pool_name: name.to_string(),
+          waited_ms: 0,
+        }
+        .into(),
+      );
+    }
+
+    let setting = DatabaseConnectionInfo {
+      conn_str: tenants.conn_str_template.replace("{tenant}", tenant),
+      pool_size: tenants.pool_size,
+      pool_name: name.to_string(),
+      ..base.clone()
+    };
+    let config = Self::pool_config_for(&setting)?;
+    if let Ok(mut pools) = self.pools.write() {
+      pools.insert(name.to_string(), config);
+    }
+    Ok(())
+  }
+
+  // Open tenant pools with how long they have been idle, longest first
+  fn tenant_pools_by_idle(&self) -> Vec<(String, Duration)> {
+    let Ok(pools) = self.pools.read() else {
+      return Vec::new();
+    };
+    let mut open: Vec<(String, Duration)> = pools
+      .iter()
+      .filter(|(name, _)| name.starts_with(TENANT_POOL_PREFIX))
+      .map(|(name, config)| (name.clone(), config.counters.idle_for()))
+      .collect();
+    open.sort_by_key(|(_, idle)| std::cmp::Reverse(*idle));
+    open
+  }
+
+  // Drops the tenant pool and with it its connections, unless one is checked out
+  fn close_tenant_pool(&self, name: &str) -> bool {
+    let Ok(mut pools) = self.pools.write() else {
+      return false;
+    };
+    let Some(config) = pools.get(name) else {
+      return false;
+    };
+    if config
+      .permits
+      .clone()
+      .try_acquire_many_owned(config.pool_size)
+      .is_err()
+    {
+      return false;
+    }
+    // A checkout that already looked the pool up fails instead of using a closed pool
+    config.permits.close();
+    pools.remove(name);
+    true
+  }
+
+  /// Closes every tenant pool nobody checked out from for `idle_after`. Returns the names of
+  /// the closed pools.
+  pub fn evict_idle_tenant_pools(&self, idle_after: Duration) -> Vec<String> {
+    self
+      .tenant_pools_by_idle()
+      .into_iter()
+      .filter(|(name, idle)| *idle >= idle_after && self.close_tenant_pool(name))
+      .map(|(name, _)| name)
+      .collect()
+  }
+
+  /// Refuses new checkouts and waits up to `timeout` for checked out connections to be
+  /// returned, then closes the pools' connections before the runtime stops.
+  pub async fn shutdown(&self, timeout: Duration) {
+    let configs: Vec<(String, PoolConfig)> = match self.pools.read() {
+      Ok(pools) => pools
+        .iter()
+        .map(|(name, config)| (name.clone(), config.clone()))
+        .collect(),
+      Err(_) => return,
+    };
+    for (_, config) in &configs {
+      config.permits.close();
+    }
+
+    let deadline = Instant::now() + timeout;
+    for (name, config) in &configs {
+      while config.permits.available_permits() < config.pool_size as usize {
+        if Instant::now() >= deadline {
+          eprintln!(
+            "Pool '{}' still had {} connection(s) checked out at shutdown",
+            name,
+            config.pool_size as usize - config.permits.available_permits()
+          );
+          break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+      }
+    }
+    // Closes the idle connections now instead of when the runtime drops them
+    for (_, config) in &configs {
+      if let Ok(pool) = config.pool() {
+        pool.close();
+      }
+    }
+  }
+
+  /// Rebuilds every pool nobody checked out from for its `idle_timeout_secs` or whose
+  /// connections are older than its `max_lifetime_secs`, so connections a firewall or load
+  /// balancer may have silently dropped are replaced before a request runs into them. Returns
+  /// the recycled pools with the reason.
+  pub async fn recycle_stale(&self) -> Vec<(String, &'static str)> {
+    let configs: Vec<(String, PoolConfig)> = match self.pools.read() {
+      Ok(pools) => pools
+        .iter()
+        .map(|(name, config)| (name.clone(), config.clone()))
+        .collect(),
+      Err(_) => return Vec::new(),
+    };
+
+    let mut recycled = Vec::new();
+    for (name, config) in configs {
+      let expired =
+        |limit: Option<Duration>, elapsed: Duration| limit.is_some_and(|l| elapsed >= l);
+      let reason = if expired(config.max_lifetime, config.counters.age()) {
+        "max lifetime"
+      } else if !name.starts_with(TENANT_POOL_PREFIX)
+        && expired(config.idle_timeout, config.counters.idle_for())
+      {
+        // Idle tenant pools are closed by `evict_idle_tenant_pools` instead
+        "idle"
+      } else {
+        continue;
+      };
+      // Holding every permit keeps checkouts out while the connections are replaced, a pool
+      // with anything checked out is left alone until the next run
+      let Ok(_permits) = config
+        .permits
+        .clone()
+        .try_acquire_many_owned(config.pool_size)
+      else {
+        continue;
+      };
+      let _guard = self.reconnecting.lock().await;
+      match config.replace_connections(&name) {
+        Ok(()) => recycled.push((name, reason)),
+        Err(e) => eprintln!("Failed to recycle pool '{}' ({}): {}", name, reason, e),
+      }
+    }
+    recycled
+  }
+
+  async fn reconnect(&self, name: &str, config: &PoolConfig) -> Result<()> {
+    let _guard = self.reconnecting.lock().await;
+
+    // Another checkout may have rebuilt the pool while this one waited
+    if Self::checkout(config, name, true).await.is_ok() {
+      return Ok(());
+    }
+    config.counters.reconnects.fetch_add(1, Ordering::Relaxed);
+    match Self::rebuild(config, name).await {
+      Err(e) if config.failover_conn_str.is_some() => Self::fail_over(config, name, e).await,
+      rebuilt => rebuilt,
+    }
+  }
+
+  // Re-initializing a named pool replaces all of its connections, one is then checked so an
+  // unreachable server fails here
+  async fn rebuild(config: &PoolConfig, name: &str) -> Result<()> {
+    config.replace_connections(name)?;
+    Self::checkout(config, name, true).await.map(drop)
+  }
+
+  // Moves the pool to the other of its two servers after `error` on the current one
+  async fn fail_over(config: &PoolConfig, name: &str, error: anyhow::Error) -> Result<()> {
+    let was_on_partner = config.on_partner.fetch_xor(true, Ordering::Relaxed);
+    let (from, to) = if was_on_partner {
+      ("failover partner", "primary")
+    } else {
+      ("primary", "failover partner")
+    };
+    eprintln!(
+      "Pool '{}' can't reach its {}, failing over to the {}: {:#}",
+      name, from, to, error
+    );
+    Self::rebuild(config, name).await
+  }
+}
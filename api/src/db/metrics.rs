@@ -0,0 +1,176 @@
+use std::{
+  collections::BTreeMap,
+  fmt::Write,
+  sync::{Arc, Mutex},
+  time::Duration,
+};
+
+use crate::db::PoolStats;
+
+/// Upper bounds of the latency histogram buckets, in seconds.
+pub const LATENCY_BUCKETS: [f64; 11] = [
+  0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Calls, failures and latency histogram of one command.
+#[derive(Clone, Default)]
+pub struct CommandStats {
+  pub calls: u64,
+  /// Failed calls, timeouts included
+  pub errors: u64,
+  pub total_secs: f64,
+  /// Calls at or under each of `LATENCY_BUCKETS`, not cumulative
+  pub buckets: [u64; LATENCY_BUCKETS.len()],
+}
+
+/// Per-command totals of every call through `SqlRepo`, keyed by procedure name or the first
+/// line of ad-hoc SQL. Cheap to clone, every clone records into the same totals.
+#[derive(Clone, Default)]
+pub struct QueryMetrics {
+  commands: Arc<Mutex<BTreeMap<String, CommandStats>>>,
+}
+
+impl QueryMetrics {
+  pub fn record(&self, command: &str, elapsed: Duration, failed: bool) {
+    let secs = elapsed.as_secs_f64();
+    let Ok(mut commands) = self.commands.lock() else {
+      return;
+    };
+    let stats = match commands.get_mut(command) {
+      Some(stats) => stats,
+      None => commands.entry(command.to_string()).or_default(),
+    };
+    stats.calls += 1;
+    stats.total_secs += secs;
+    if failed {
+      stats.errors += 1;
+    }
+    if let Some(bucket) = LATENCY_BUCKETS.iter().position(|&upper| secs <= upper) {
+      stats.buckets[bucket] += 1;
+    }
+  }
+
+  pub fn snapshot(&self) -> BTreeMap<String, CommandStats> {
+    self
+      .commands
+      .lock()
+      .map(|commands| commands.clone())
+      .unwrap_or_default()
+  }
+}
+
+fn escape_label(value: &str) -> String {
+  value
+    .replace('\\', "\\\\")
+    .replace('"', "\\\"")
+    .replace('\n', "\\n")
+}
+
+/// The totals in the Prometheus text format.
+pub fn render_prometheus(commands: &BTreeMap<String, CommandStats>, out: &mut String) {
+  out.push_str("# HELP db_query_duration_seconds Time spent in database calls, by command.\n");
+  out.push_str("# TYPE db_query_duration_seconds histogram\n");
+  for (command, stats) in commands {
+    let command = escape_label(command);
+    let mut cumulative = 0;
+    for (upper, count) in LATENCY_BUCKETS.iter().zip(stats.buckets) {
+      cumulative += count;
+      let _ = writeln!(
+        out,
+        "db_query_duration_seconds_bucket{{command=\"{}\",le=\"{}\"}} {}",
+        command, upper, cumulative
+      );
+    }
+    let _ = writeln!(
+      out,
+      "db_query_duration_seconds_bucket{{command=\"{}\",le=\"+Inf\"}} {}",
+      command, stats.calls
+    );
+    let _ = writeln!(
+      out,
+      "db_query_duration_seconds_sum{{command=\"{}\"}} {}",
+      command, stats.total_secs
+    );
+    let _ = writeln!(
+      out,
+      "db_query_duration_seconds_count{{command=\"{}\"}} {}",
+      command, stats.calls
+    );
+  }
+
+  out.push_str("# HELP db_query_errors_total Failed database calls, timeouts included.\n");
+  out.push_str("# TYPE db_query_errors_total counter\n");
+  for (command, stats) in commands {
+    let _ = writeln!(
+      out,
+      "db_query_errors_total{{command=\"{}\"}} {}",
+      escape_label(command),
+      stats.errors
+    );
+  }
+}
+
+fn render_pool_series(
+  out: &mut String,
+  pools: &[PoolStats],
+  (name, kind, help): (&str, &str, &str),
+  value: impl Fn(&PoolStats) -> u64,
+) {
+  let _ = writeln!(out, "# HELP {} {}", name, help);
+  let _ = writeln!(out, "# TYPE {} {}", name, kind);
+  for pool in pools {
+    let _ = writeln!(
+      out,
+      "{}{{pool=\"{}\"}} {}",
+      name,
+      escape_label(&pool.pool_name),
+      value(pool)
+    );
+  }
+}
+
+/// Size, usage and checkout totals of every pool in the Prometheus text format.
+pub fn render_pools(pools: &[PoolStats], out: &mut String) {
+  render_pool_series(
+    out,
+    pools,
+    ("db_pool_size", "gauge", "Connections in the pool."),
+    |p| p.pool_size as u64,
+  );
+  render_pool_series(
+    out,
+    pools,
+    ("db_pool_in_use", "gauge", "Connections checked out."),
+    |p| p.in_use as u64,
+  );
+  render_pool_series(
+    out,
+    pools,
+    (
+      "db_pool_waiters",
+      "gauge",
+      "Checkouts waiting for a connection.",
+    ),
+    |p| p.waiters,
+  );
+  render_pool_series(
+    out,
+    pools,
+    (
+      "db_pool_checkouts_total",
+      "counter",
+      "Connections checked out.",
+    ),
+    |p| p.checkouts,
+  );
+  render_pool_series(
+    out,
+    pools,
+    (
+      "db_pool_timeouts_total",
+      "counter",
+      "Checkouts that gave up waiting.",
+    ),
+    |p| p.timeouts,
+  );
+}
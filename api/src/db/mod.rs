@@ -0,0 +1,19 @@
+pub mod azure_ad;
+pub mod connector;
+pub mod manager;
+pub mod metrics;
+pub mod row;
+pub mod soft_delete;
+pub mod sql;
+pub mod statement_cache;
+pub mod stream;
+
+/// Names repos ask `DatabaseSetting::connection` for, so audit and reporting data can live
+/// in their own databases. Unconfigured names fall back to `sql_server`.
+pub const AUDIT_DATABASE: &str = "audit";
+pub const REPORTING_DATABASE: &str = "reporting";
+
+pub use manager::{DbConnection, DbManager, PoolExhausted, PoolStats, TENANT_POOL_PREFIX};
+pub use metrics::QueryMetrics;
+pub use row::{FromRow, RowExt, RowMappingError};
+pub use sql::{QueryTimeout, ResultSets, RetryPolicy, SqlRepo};
@@ -0,0 +1,92 @@
+use std::fmt;
+
+use anyhow::{Context, Result, anyhow};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use domner_tech_sql_client::pool_manager::DbRow;
+
+/// Returned when a row of `command` could not be mapped, e.g. a NULL in a column the entity
+/// requires. Answered as a 500 like any other database failure.
+#[derive(Debug)]
+pub struct RowMappingError {
+  pub command: String,
+  pub error: anyhow::Error,
+}
+
+impl fmt::Display for RowMappingError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(
+      f,
+      "Failed to map a row of '{}': {:#}",
+      self.command, self.error
+    )
+  }
+}
+
+impl std::error::Error for RowMappingError {}
+
+/// Builds a value from one result row, see `SqlRepo::execute_query_as`.
+pub trait FromRow: Sized {
+  fn from_row(row: &DbRow<'_>) -> Result<Self>;
+}
+
+/// A type a column can be read as.
+pub trait Column: Sized {
+  fn read(row: &DbRow<'_>, column: &str) -> Result<Option<Self>>;
+}
+
+impl Column for i32 {
+  fn read(row: &DbRow<'_>, column: &str) -> Result<Option<Self>> {
+    row.get_mssql::<i32>(column)
+  }
+}
+
+impl Column for bool {
+  fn read(row: &DbRow<'_>, column: &str) -> Result<Option<Self>> {
+    row.get_mssql::<bool>(column)
+  }
+}
+
+impl Column for String {
+  fn read(row: &DbRow<'_>, column: &str) -> Result<Option<Self>> {
+    Ok(row.get_mssql::<&str>(column)?.map(str::to_string))
+  }
+}
+
+impl Column for NaiveDateTime {
+  fn read(row: &DbRow<'_>, column: &str) -> Result<Option<Self>> {
+    row.get_mssql::<NaiveDateTime>(column)
+  }
+}
+
+// DATETIME2 columns hold UTC
+impl Column for DateTime<Utc> {
+  fn read(row: &DbRow<'_>, column: &str) -> Result<Option<Self>> {
+    Ok(
+      row
+        .get_mssql::<NaiveDateTime>(column)?
+        .map(|naive| DateTime::<Utc>::from_naive_utc_and_offset(naive, Utc)),
+    )
+  }
+}
+
+/// Column reads whose errors name the column, so a missing column, a type change or an
+/// unexpected NULL fails the query instead of panicking in the mapper.
+pub trait RowExt {
+  /// Fails when the column is missing, of another type or NULL.
+  fn column<T: Column>(&self, column: &str) -> Result<T>;
+
+  /// NULL is `None`, a missing column or another type still fails.
+  fn nullable<T: Column>(&self, column: &str) -> Result<Option<T>>;
+}
+
+impl RowExt for DbRow<'_> {
+  fn column<T: Column>(&self, column: &str) -> Result<T> {
+    self
+      .nullable(column)?
+      .ok_or_else(|| anyhow!("Column '{}' is NULL", column))
+  }
+
+  fn nullable<T: Column>(&self, column: &str) -> Result<Option<T>> {
+    T::read(self, column).with_context(|| format!("Failed to read column '{}'", column))
+  }
+}
@@ -0,0 +1,74 @@
+use anyhow::Result;
+use domner_tech_sql_client::CommandType;
+
+use crate::db::{DbConnection, SqlRepo};
+
+/// Filter of the rows a soft-deleting table still serves, e.g.
+/// `format!("SELECT * FROM [dbo].[roles] WHERE {}", NOT_DELETED)`.
+pub const NOT_DELETED: &str = "[deleted_at] IS NULL";
+
+// Rows deleted more than @P1 days ago
+const PURGEABLE: &str = "[deleted_at] < DATEADD(DAY, -@P1, SYSUTCDATETIME())";
+
+/// A table whose rows get a `deleted_at` timestamp instead of being removed, until the
+/// scheduler's `purge_soft_deleted` task removes them for good once the retention has passed.
+///
+/// Opting a table in takes a nullable `deleted_at DATETIME2` column, an `id` primary key,
+/// `NOT_DELETED` in its reads and an entry in `SOFT_DELETE_TABLES`.
+pub struct SoftDeleteTable {
+  pub name: &'static str,
+  /// `(table, column)` pairs referencing this table, deleted together with a purged row
+  pub dependents: &'static [(&'static str, &'static str)],
+}
+
+pub const ROLES: SoftDeleteTable = SoftDeleteTable {
+  name: "roles",
+  dependents: &[("user_roles", "role_id"), ("role_permissions", "role_id")],
+};
+
+/// Every table purged by the scheduler.
+pub const SOFT_DELETE_TABLES: &[SoftDeleteTable] = &[ROLES];
+
+// Table and column names only ever come from the constants above, never from a request
+impl SqlRepo {
+  /// Flags the row as deleted, returns 0 when it doesn't exist or already was.
+  pub async fn soft_delete(
+    client: &mut DbConnection,
+    table: &SoftDeleteTable,
+    id: i32,
+  ) -> Result<u64> {
+    let query = format!(
+      "UPDATE [dbo].[{}] SET [deleted_at] = SYSUTCDATETIME() WHERE [id] = @P1 AND {}",
+      table.name, NOT_DELETED
+    );
+    SqlRepo::execute_command_none_query(client, &query, &[&id], CommandType::Text).await
+  }
+
+  /// Brings a soft-deleted row back, returns 0 when it isn't deleted.
+  pub async fn restore(client: &mut DbConnection, table: &SoftDeleteTable, id: i32) -> Result<u64> {
+    let query = format!(
+      "UPDATE [dbo].[{}] SET [deleted_at] = NULL WHERE [id] = @P1 AND [deleted_at] IS NOT NULL",
+      table.name
+    );
+    SqlRepo::execute_command_none_query(client, &query, &[&id], CommandType::Text).await
+  }
+
+  /// Removes the rows deleted more than `retention_days` ago with their dependents, returns
+  /// how many rows of `table` itself were removed.
+  pub async fn purge_deleted(
+    client: &mut DbConnection,
+    table: &SoftDeleteTable,
+    retention_days: i32,
+  ) -> Result<u64> {
+    for (dependent, column) in table.dependents {
+      let query = format!(
+        "DELETE FROM [dbo].[{}] WHERE [{}] IN (SELECT [id] FROM [dbo].[{}] WHERE {})",
+        dependent, column, table.name, PURGEABLE
+      );
+      SqlRepo::execute_command_none_query(client, &query, &[&retention_days], CommandType::Text)
+        .await?;
+    }
+    let query = format!("DELETE FROM [dbo].[{}] WHERE {}", table.name, PURGEABLE);
+    SqlRepo::execute_command_none_query(client, &query, &[&retention_days], CommandType::Text).await
+  }
+}
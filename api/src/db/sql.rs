@@ -0,0 +1,586 @@
+use std::{
+  fmt,
+  future::Future,
+  time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use domner_tech_sql_client::{
+  CommandType, SqlRepo as ClientSqlRepo, UnifiedToSql,
+  pool_manager::{DbClient, DbRow},
+  types::sql::mssql::{ColumnData, QueryItem, QueryStream, Row, ToSql},
+};
+use futures::TryStreamExt;
+
+use crate::{
+  app_settings::DatabaseConnectionInfo,
+  db::{DbConnection, FromRow, QueryMetrics, RowExt, RowMappingError, row::Column},
+};
+
+/// Returned when a query runs longer than the connection's query timeout.
+#[derive(Debug)]
+pub struct QueryTimeout {
+  pub command: String,
+  pub timeout_ms: u128,
+}
+
+impl fmt::Display for QueryTimeout {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(
+      f,
+      "Query '{}' timed out after {} ms",
+      self.command, self.timeout_ms
+    )
+  }
+}
+
+impl std::error::Error for QueryTimeout {}
+
+// Alias `execute_scalar` reads the value from
+const SCALAR_COLUMN: &str = "value";
+// Alias of the windowed count `execute_paged_query` reads the total from
+const TOTAL_COUNT_COLUMN: &str = "total_count";
+
+fn mapping_error(command_text: &str, error: anyhow::Error) -> anyhow::Error {
+  RowMappingError {
+    command: command_label(command_text),
+    error,
+  }
+  .into()
+}
+
+// Procs are named in full, ad-hoc text is cut to its first line
+fn command_label(command_text: &str) -> String {
+  command_text
+    .lines()
+    .next()
+    .unwrap_or_default()
+    .trim()
+    .chars()
+    .take(80)
+    .collect()
+}
+
+// The text the sql client sends for `command_type`, e.g. `EXEC [dbo].[proc] @P1, @P2`
+fn command_with_params(
+  command_text: &str,
+  command_type: CommandType,
+  param_count: usize,
+) -> String {
+  match command_type {
+    CommandType::Text => command_text.to_string(),
+    CommandType::StoreProcedure if param_count == 0 => format!("EXEC {}", command_text),
+    CommandType::StoreProcedure => {
+      let placeholders: Vec<String> = (1..=param_count).map(|i| format!("@P{}", i)).collect();
+      format!("EXEC {} {}", command_text, placeholders.join(", "))
+    }
+    CommandType::TableDirect => format!("SELECT * FROM {}", command_text),
+  }
+}
+
+// `QueryStream::into_results` drops an empty set that follows another set, which would hand
+// the rows of the next one out in its place
+async fn collect_result_sets(mut stream: QueryStream<'_>) -> Result<Vec<Vec<Row>>> {
+  let mut sets: Vec<Vec<Row>> = Vec::new();
+  while let Some(item) = stream.try_next().await? {
+    match item {
+      QueryItem::Metadata(_) => sets.push(Vec::new()),
+      QueryItem::Row(row) => match sets.last_mut() {
+        Some(set) => set.push(row),
+        None => sets.push(vec![row]),
+      },
+    }
+  }
+  Ok(sets)
+}
+
+// Every result set of the command, none when it produced no rows at all. Goes to the driver
+// directly since the sql client only hands out the first set and panics when there is none.
+async fn query_result_sets(
+  client: &mut DbConnection,
+  command_text: &str,
+  params: &[&dyn UnifiedToSql],
+  command_type: CommandType,
+) -> Result<Vec<Vec<Row>>> {
+  let DbClient::Mssql(mssql) = client.client();
+  let mssql_params = params
+    .iter()
+    .map(|p| p.to_mssql_param())
+    .collect::<Result<Vec<&dyn ToSql>>>()?;
+  let query = command_with_params(command_text, command_type, params.len());
+  collect_result_sets(mssql.query(query, mssql_params.as_slice()).await?).await
+}
+
+// Raised by `sp_execute` for a handle the session doesn't know
+const UNKNOWN_HANDLE_ERROR: u32 = 8179;
+
+// How a parameter is declared to `sp_prepexec`, `None` for types that run unprepared
+fn declared_type(param: &dyn ToSql) -> Option<&'static str> {
+  Some(match param.to_sql() {
+    ColumnData::U8(_) => "tinyint",
+    ColumnData::I16(_) => "smallint",
+    ColumnData::I32(_) => "int",
+    ColumnData::I64(_) => "bigint",
+    ColumnData::F32(_) => "real",
+    ColumnData::F64(_) => "float",
+    ColumnData::Bit(_) => "bit",
+    // Same split as the driver, so a long value doesn't get cut
+    ColumnData::String(Some(value)) if value.chars().count() > 4000 => "nvarchar(max)",
+    ColumnData::String(_) => "nvarchar(4000)",
+    ColumnData::Guid(_) => "uniqueidentifier",
+    _ => return None,
+  })
+}
+
+// `, @P{first}, @P{first + 1}, ...` for `count` parameters
+fn placeholders_from(first: usize, count: usize) -> String {
+  (first..first + count)
+    .map(|i| format!(", @P{}", i))
+    .collect()
+}
+
+// Runs the proc through the handle prepared for it on this connection, preparing it with
+// `sp_prepexec` the first time
+async fn query_prepared(
+  client: &mut DbConnection,
+  proc_name: &str,
+  params: &[&dyn UnifiedToSql],
+) -> Result<Vec<Vec<Row>>> {
+  let mssql_params = params
+    .iter()
+    .map(|p| p.to_mssql_param())
+    .collect::<Result<Vec<&dyn ToSql>>>()?;
+  let declarations = mssql_params
+    .iter()
+    .enumerate()
+    .map(|(i, p)| declared_type(*p).map(|t| format!("@P{} {}", i + 1, t)))
+    .collect::<Option<Vec<String>>>();
+  let (DbClient::Mssql(mssql), statements) = client.with_statements();
+  let statement = command_with_params(proc_name, CommandType::StoreProcedure, params.len());
+  let Some(declarations) = declarations.filter(|_| statements.capacity() > 0) else {
+    return collect_result_sets(mssql.query(statement, mssql_params.as_slice()).await?).await;
+  };
+  let declarations = declarations.join(", ");
+  let key = format!("{}|{}", statement, declarations);
+
+  if let Some(handle) = statements.get(&key) {
+    let mut exec_params: Vec<&dyn ToSql> = vec![&handle];
+    exec_params.extend(mssql_params.iter().copied());
+    let query = format!("EXEC sp_execute @P1{}", placeholders_from(2, params.len()));
+    match mssql.query(query, exec_params.as_slice()).await {
+      Ok(stream) => return collect_result_sets(stream).await,
+      // Prepared again below
+      Err(e) if e.code() == Some(UNKNOWN_HANDLE_ERROR) => statements.remove(&key),
+      Err(e) => return Err(e.into()),
+    }
+  }
+
+  let mut prepare_params: Vec<&dyn ToSql> = vec![&declarations, &statement];
+  prepare_params.extend(mssql_params.iter().copied());
+  let query = format!(
+    "DECLARE @handle INT; EXEC sp_prepexec @handle OUTPUT, @P1, @P2{}; SELECT @handle AS handle;",
+    placeholders_from(3, params.len())
+  );
+  let mut sets = collect_result_sets(mssql.query(query, prepare_params.as_slice()).await?).await?;
+  // The handle comes last, after whatever the proc answered with
+  let handle = sets
+    .pop()
+    .and_then(|set| set.into_iter().next())
+    .and_then(|row| row.get::<i32, _>("handle"))
+    .ok_or_else(|| anyhow::anyhow!("'{}' was not prepared", command_label(proc_name)))?;
+  if let Some(evicted) = statements.insert(key, handle) {
+    mssql.execute("EXEC sp_unprepare @P1", &[&evicted]).await?;
+  }
+  Ok(sets)
+}
+
+/// Result sets of one command in the order it produced them, see
+/// `SqlRepo::execute_command_multi_query`.
+pub struct ResultSets {
+  command: String,
+  sets: std::vec::IntoIter<Vec<Row>>,
+}
+
+impl ResultSets {
+  /// Maps the rows of the next set, empty once every set was read.
+  pub fn next_with<T, F>(&mut self, map_row: F) -> Result<Vec<T>>
+  where
+    F: Fn(&DbRow) -> Result<T>,
+  {
+    self
+      .sets
+      .next()
+      .unwrap_or_default()
+      .iter()
+      .map(|row| map_row(&DbRow::Mssql(row)))
+      .collect::<Result<Vec<T>>>()
+      .map_err(|e| mapping_error(&self.command, e))
+  }
+
+  /// `next_with` mapping every row with `T::from_row`.
+  pub fn next_as<T: FromRow>(&mut self) -> Result<Vec<T>> {
+    self.next_with(T::from_row)
+  }
+}
+
+// Cancels the query by dropping its future once the timeout elapses
+async fn with_timeout<T>(
+  timeout: Option<Duration>,
+  command_text: &str,
+  query: impl Future<Output = Result<T>>,
+) -> Result<T> {
+  let Some(timeout) = timeout else {
+    return query.await;
+  };
+  match tokio::time::timeout(timeout, query).await {
+    Ok(result) => result,
+    Err(_) => Err(
+      QueryTimeout {
+        command: command_label(command_text),
+        timeout_ms: timeout.as_millis(),
+      }
+      .into(),
+    ),
+  }
+}
+
+// What `run` needs from the connection, read before the query borrows it
+struct QueryContext<'a> {
+  command_text: &'a str,
+  param_count: usize,
+  timeout: Option<Duration>,
+  slow_query: Option<Duration>,
+  pool_name: String,
+  metrics: QueryMetrics,
+}
+
+impl<'a> QueryContext<'a> {
+  fn new(client: &DbConnection, command_text: &'a str, params: &[&dyn UnifiedToSql]) -> Self {
+    Self {
+      command_text,
+      param_count: params.len(),
+      timeout: client.query_timeout(),
+      slow_query: client.slow_query_threshold(),
+      pool_name: client.pool_name().to_string(),
+      metrics: client.metrics().clone(),
+    }
+  }
+}
+
+// Runs the query within the connection's timeout, records it in the query metrics and warns
+// when it was slow. Only the number of parameters is logged, their values may be personal
+// data or secrets.
+async fn run<T>(context: QueryContext<'_>, query: impl Future<Output = Result<T>>) -> Result<T> {
+  let started = Instant::now();
+  let result = with_timeout(context.timeout, context.command_text, query).await;
+  let elapsed = started.elapsed();
+  let command = command_label(context.command_text);
+  context.metrics.record(&command, elapsed, result.is_err());
+  if let Some(threshold) = context.slow_query
+    && elapsed >= threshold
+  {
+    eprintln!(
+      "Slow query on pool '{}': '{}' with {} parameter(s) took {} ms (threshold {} ms)",
+      context.pool_name,
+      command,
+      context.param_count,
+      elapsed.as_millis(),
+      threshold.as_millis()
+    );
+  }
+  result
+}
+
+/// Same calls as the sql client's `SqlRepo`, bounded by the connection's query timeout and
+/// logged when slower than `slow_query_ms`. Row mappers return a `Result`, the first row that
+/// can't be mapped fails the call with a `RowMappingError`.
+/// A cancelled query can leave its connection mid-response, `validate_on_checkout`
+/// makes sure such a connection is replaced before it is used again.
+/// Hot procs go through `execute_prepared_query_as`, which keeps them prepared on each
+/// connection up to the pool's `statement_cache_size`.
+pub struct SqlRepo;
+
+impl SqlRepo {
+  pub async fn execute_command_none_query(
+    client: &mut DbConnection,
+    command_text: &str,
+    params: &[&dyn UnifiedToSql],
+    command_type: CommandType,
+  ) -> Result<u64> {
+    let context = QueryContext::new(client, command_text, params);
+    run(
+      context,
+      ClientSqlRepo::execute_command_none_query(client, command_text, params, command_type),
+    )
+    .await
+  }
+
+  pub async fn execute_command_query<T, F>(
+    client: &mut DbConnection,
+    command_text: &str,
+    params: &[&dyn UnifiedToSql],
+    command_type: CommandType,
+    map_row: F,
+  ) -> Result<Vec<T>>
+  where
+    F: Fn(&DbRow) -> Result<T>,
+  {
+    Self::execute_command_multi_query(client, command_text, params, command_type)
+      .await?
+      .next_with(map_row)
+  }
+
+  pub async fn execute_command_single_query<T, F>(
+    client: &mut DbConnection,
+    command_text: &str,
+    params: &[&dyn UnifiedToSql],
+    command_type: CommandType,
+    map_row: F,
+  ) -> Result<Option<T>>
+  where
+    F: Fn(&DbRow) -> Result<T>,
+  {
+    // The last row, as the sql client always picked
+    let mut rows =
+      Self::execute_command_query(client, command_text, params, command_type, map_row).await?;
+    Ok(rows.pop())
+  }
+
+  /// Runs a command answering with several result sets, e.g. a proc with a `SELECT` for a page
+  /// of rows and another for their details, in one round trip. Sets are read in order with
+  /// `next_as`/`next_with`, a set the command didn't produce reads as empty.
+  pub async fn execute_command_multi_query(
+    client: &mut DbConnection,
+    command_text: &str,
+    params: &[&dyn UnifiedToSql],
+    command_type: CommandType,
+  ) -> Result<ResultSets> {
+    let context = QueryContext::new(client, command_text, params);
+    let sets = run(
+      context,
+      query_result_sets(client, command_text, params, command_type),
+    )
+    .await?;
+    Ok(ResultSets {
+      command: command_label(command_text),
+      sets: sets.into_iter(),
+    })
+  }
+
+  /// The `value` column of the first row, for queries answering with a single value such as
+  /// `SELECT COUNT(*) AS value ...`. `None` when there is no row or the value is NULL.
+  pub async fn execute_scalar<T: Column>(
+    client: &mut DbConnection,
+    command_text: &str,
+    params: &[&dyn UnifiedToSql],
+    command_type: CommandType,
+  ) -> Result<Option<T>> {
+    let value =
+      Self::execute_command_single_query(client, command_text, params, command_type, |row| {
+        row.nullable::<T>(SCALAR_COLUMN)
+      })
+      .await?;
+    Ok(value.flatten())
+  }
+
+  /// Runs an insert that answers with the new row's `id`, e.g. a proc ending in
+  /// `SELECT CAST(SCOPE_IDENTITY() AS INT) AS id` or a statement with `OUTPUT INSERTED.id`.
+  pub async fn execute_insert_returning_id(
+    client: &mut DbConnection,
+    command_text: &str,
+    params: &[&dyn UnifiedToSql],
+    command_type: CommandType,
+  ) -> Result<i32> {
+    Self::execute_command_single_query(client, command_text, params, command_type, |row| {
+      row.column::<i32>("id")
+    })
+    .await?
+    .ok_or_else(|| anyhow::anyhow!("'{}' returned no id", command_label(command_text)))
+  }
+
+  /// Runs a page query whose rows also carry the total, e.g. `SELECT *, COUNT(*) OVER () AS
+  /// total_count ... OFFSET ... FETCH NEXT ...`, so the page and the total take one round
+  /// trip. A page past the last one has no rows to read the total from, the total then comes
+  /// from `count_text`, a `SELECT COUNT(*) AS value ...` text query over the same rows.
+  pub async fn execute_paged_query<T: FromRow>(
+    client: &mut DbConnection,
+    command_text: &str,
+    params: &[&dyn UnifiedToSql],
+    command_type: CommandType,
+    count_text: &str,
+    count_params: &[&dyn UnifiedToSql],
+  ) -> Result<(Vec<T>, i32)> {
+    let rows = Self::execute_command_query(client, command_text, params, command_type, |row| {
+      Ok((T::from_row(row)?, row.column::<i32>(TOTAL_COUNT_COLUMN)?))
+    })
+    .await?;
+    let total_count = match rows.first() {
+      Some((_, total)) => *total,
+      None => Self::execute_scalar::<i32>(client, count_text, count_params, CommandType::Text)
+        .await?
+        .unwrap_or_default(),
+    };
+    Ok((
+      rows.into_iter().map(|(item, _)| item).collect(),
+      total_count,
+    ))
+  }
+
+  /// `execute_command_query` mapping every row with `T::from_row`.
+  pub async fn execute_query_as<T: FromRow>(
+    client: &mut DbConnection,
+    command_text: &str,
+    params: &[&dyn UnifiedToSql],
+    command_type: CommandType,
+  ) -> Result<Vec<T>> {
+    Self::execute_command_query(client, command_text, params, command_type, T::from_row).await
+  }
+
+  /// Calls the stored proc `proc_name` through a handle prepared on this connection, so later
+  /// calls skip parsing the `EXEC` batch. Handles are cached per connection up to the pool's
+  /// `statement_cache_size`, the least recently used one is unprepared to make room. Runs
+  /// unprepared when the cache is off or a parameter has a type that isn't declared here.
+  pub async fn execute_prepared_query_as<T: FromRow>(
+    client: &mut DbConnection,
+    proc_name: &str,
+    params: &[&dyn UnifiedToSql],
+  ) -> Result<Vec<T>> {
+    let context = QueryContext::new(client, proc_name, params);
+    let sets = run(context, query_prepared(client, proc_name, params)).await?;
+    ResultSets {
+      command: command_label(proc_name),
+      sets: sets.into_iter(),
+    }
+    .next_as()
+  }
+
+  /// `execute_prepared_query_as` for procs answering with at most one row.
+  pub async fn execute_prepared_single_query_as<T: FromRow>(
+    client: &mut DbConnection,
+    proc_name: &str,
+    params: &[&dyn UnifiedToSql],
+  ) -> Result<Option<T>> {
+    let mut rows = Self::execute_prepared_query_as(client, proc_name, params).await?;
+    Ok(rows.pop())
+  }
+
+  /// `execute_command_single_query` mapping the row with `T::from_row`.
+  pub async fn execute_single_query_as<T: FromRow>(
+    client: &mut DbConnection,
+    command_text: &str,
+    params: &[&dyn UnifiedToSql],
+    command_type: CommandType,
+  ) -> Result<Option<T>> {
+    Self::execute_command_single_query(client, command_text, params, command_type, T::from_row)
+      .await
+  }
+}
+
+// SQL Server errors that a second attempt can get past: deadlock victim, lock request
+// timeout, and the Azure SQL throttling and failover codes
+const TRANSIENT_ERROR_CODES: [u32; 9] = [1205, 1222, 233, 10053, 10054, 40197, 40501, 40613, 49918];
+
+// Longest wait between two attempts, however many there were
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+// The `code` of a server error, formatted by tiberius as `(code: 1205, state: 51, class: 13)`
+fn server_error_code(message: &str) -> Option<u32> {
+  let (_, rest) = message.rsplit_once("(code: ")?;
+  rest.split(',').next()?.trim().parse().ok()
+}
+
+/// Whether `e` is worth retrying: a query timeout, a dropped connection or one of the
+/// transient SQL Server error codes. The sql client wraps the driver's errors, so codes are
+/// read from the message.
+pub fn is_transient(e: &anyhow::Error) -> bool {
+  e.chain().any(|cause| {
+    if cause.is::<QueryTimeout>() {
+      return true;
+    }
+    if let Some(io) = cause.downcast_ref::<std::io::Error>() {
+      return matches!(
+        io.kind(),
+        std::io::ErrorKind::ConnectionReset
+          | std::io::ErrorKind::ConnectionAborted
+          | std::io::ErrorKind::BrokenPipe
+          | std::io::ErrorKind::UnexpectedEof
+          | std::io::ErrorKind::TimedOut
+      );
+    }
+    let message = cause.to_string();
+    if let Some(code) = server_error_code(&message) {
+      return TRANSIENT_ERROR_CODES.contains(&code);
+    }
+    let message = message.to_lowercase();
+    ["connection reset", "connection aborted", "broken pipe"]
+      .iter()
+      .any(|text| message.contains(text))
+  })
+}
+
+/// How often `execute_with_retry` tries and how long it waits in between.
+#[derive(Clone, Copy)]
+pub struct RetryPolicy {
+  pub max_attempts: u32,
+  pub base_delay: Duration,
+}
+
+impl RetryPolicy {
+  /// A single attempt, for queries inside a transaction: a deadlock rolls the whole
+  /// transaction back, so repeating only the last statement would be wrong.
+  pub fn none() -> Self {
+    Self {
+      max_attempts: 1,
+      base_delay: Duration::ZERO,
+    }
+  }
+
+  pub fn from_setting(setting: &DatabaseConnectionInfo) -> Self {
+    Self {
+      max_attempts: setting.retry_attempts.max(1),
+      base_delay: Duration::from_millis(setting.retry_base_delay_ms),
+    }
+  }
+
+  // Doubles per attempt, then a random half of it is dropped so clients that failed
+  // together don't retry together
+  fn delay(&self, attempt: u32) -> Duration {
+    let delay = self
+      .base_delay
+      .saturating_mul(2u32.saturating_pow(attempt - 1))
+      .min(MAX_RETRY_DELAY);
+    let jitter = (uuid::Uuid::new_v4().as_u128() % 1000) as u32;
+    delay / 2 + delay / 2 * jitter / 1000
+  }
+}
+
+impl SqlRepo {
+  /// Runs `attempt` until it succeeds, fails with an error that is not `is_transient` or
+  /// `policy.max_attempts` is used up. Only for idempotent reads: a write that timed out
+  /// may still have been applied. `attempt` should check out its own connection, the one a
+  /// failed attempt used may be broken.
+  pub async fn execute_with_retry<T, F, Fut>(policy: RetryPolicy, mut attempt: F) -> Result<T>
+  where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+  {
+    let mut tried = 1;
+    loop {
+      match attempt().await {
+        Err(e) if tried < policy.max_attempts && is_transient(&e) => {
+          let delay = policy.delay(tried);
+          eprintln!(
+            "Transient database error on attempt {} of {}, retrying in {} ms: {:#}",
+            tried,
+            policy.max_attempts,
+            delay.as_millis(),
+            e
+          );
+          tokio::time::sleep(delay).await;
+          tried += 1;
+        }
+        result => return result,
+      }
+    }
+  }
+}
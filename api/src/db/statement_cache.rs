@@ -0,0 +1,61 @@
+use indexmap::IndexMap;
+
+/// Handles of the statements prepared on one connection, least recently used first.
+///
+/// A handle only lives as long as the session that prepared it, so every pooled connection
+/// keeps its own cache and it goes away with the connection.
+pub struct StatementCache {
+  capacity: usize,
+  handles: IndexMap<String, i32>,
+}
+
+impl StatementCache {
+  /// Holds up to `capacity` handles, 0 turns caching off.
+  pub fn new(capacity: usize) -> Self {
+    Self {
+      capacity,
+      handles: IndexMap::new(),
+    }
+  }
+
+  pub fn capacity(&self) -> usize {
+    self.capacity
+  }
+
+  pub fn len(&self) -> usize {
+    self.handles.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.handles.is_empty()
+  }
+
+  /// The handle prepared for `key`, which becomes the most recently used.
+  pub fn get(&mut self, key: &str) -> Option<i32> {
+    let index = self.handles.get_index_of(key)?;
+    let last = self.handles.len() - 1;
+    self.handles.move_index(index, last);
+    self.handles.get_index(last).map(|(_, handle)| *handle)
+  }
+
+  /// Keeps `handle` for `key`. Returns the handle dropped to make room, the caller still has
+  /// to unprepare it on the connection.
+  pub fn insert(&mut self, key: String, handle: i32) -> Option<i32> {
+    if self.capacity == 0 {
+      return Some(handle);
+    }
+    let evicted = if self.handles.len() >= self.capacity && !self.handles.contains_key(&key) {
+      self.handles.shift_remove_index(0).map(|(_, handle)| handle)
+    } else {
+      None
+    };
+    let replaced = self.handles.shift_remove(&key);
+    self.handles.insert(key, handle);
+    evicted.or(replaced)
+  }
+
+  /// Forgets `key`, e.g. after the server no longer knew its handle.
+  pub fn remove(&mut self, key: &str) {
+    self.handles.shift_remove(key);
+  }
+}
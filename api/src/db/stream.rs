@@ -0,0 +1,47 @@
+use std::future::Future;
+
+use anyhow::Result;
+use futures::{Stream, StreamExt, stream};
+
+// Paging state carried between fetches
+struct PagedState<F> {
+  fetch_page: F,
+  page: i32,
+  finished: bool,
+}
+
+/// Yields mapped rows one at a time while holding at most one page of them in memory.
+///
+/// `fetch_page` gets the 1-based page number and returns up to `page_size` rows; a short
+/// page ends the stream, an error is yielded once and ends it as well.
+pub fn paged_stream<T, F, Fut>(page_size: i32, fetch_page: F) -> impl Stream<Item = Result<T>>
+where
+  F: FnMut(i32) -> Fut,
+  Fut: Future<Output = Result<Vec<T>>>,
+{
+  let state = PagedState {
+    fetch_page,
+    page: 1,
+    finished: false,
+  };
+
+  stream::unfold(state, move |mut state| async move {
+    if state.finished {
+      return None;
+    }
+
+    let rows: Vec<Result<T>> = match (state.fetch_page)(state.page).await {
+      Ok(rows) => {
+        state.finished = (rows.len() as i32) < page_size;
+        rows.into_iter().map(Ok).collect()
+      }
+      Err(e) => {
+        state.finished = true;
+        vec![Err(e)]
+      }
+    };
+    state.page += 1;
+    Some((stream::iter(rows), state))
+  })
+  .flatten()
+}
@@ -10,6 +10,14 @@ pub struct BaseResDto<T: ToSchema> {
   pub status: Status,
 }
 
+/// Body of every error response, `BaseResDto` without data.
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct ErrorResDto {
+  #[schema(value_type = Option<Object>)]
+  pub data: Option<()>,
+  pub status: Status,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
 pub struct Status {
   #[serde(default)]
@@ -17,6 +25,9 @@ pub struct Status {
   #[serde(default = "default_code")]
   pub code: String, // defaults to "SUCCESS"
   pub status: u16,
+  /// Id of the failed request, quote it when reporting a problem
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub request_id: Option<String>,
 }
 
 // Default value for code
@@ -31,6 +42,7 @@ impl Default for Status {
       message: String::new(),
       code: default_code(),
       status: 200,
+      request_id: None,
     }
   }
 }
@@ -0,0 +1,8 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Answer of an endpoint that created something, carries the id it was given.
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct CreatedResDto {
+  pub id: i32,
+}
@@ -0,0 +1,102 @@
+use std::marker::PhantomData;
+
+use actix_web::{FromRequest, HttpRequest, dev::Payload, web};
+use futures::future::{Ready, ready};
+use serde::{Deserialize, Serialize};
+use utoipa::{
+  IntoParams, PartialSchema,
+  openapi::{RefOr, schema::Schema},
+};
+
+use crate::dto::validated_json::{FieldErrorDto, ValidationFailed};
+
+// Properties of `T`'s OpenAPI schema, which are the fields it serializes
+fn field_names<T: PartialSchema>() -> Vec<String> {
+  match T::schema() {
+    RefOr::T(Schema::Object(object)) => object.properties.keys().cloned().collect(),
+    _ => Vec::new(),
+  }
+}
+
+// Other query parameters of the endpoint are ignored here
+#[derive(Deserialize, IntoParams)]
+pub struct FieldsParam {
+  /// Comma separated fields to return for each item, e.g. `id,user_name`
+  pub fields: Option<String>,
+}
+
+/// `?fields=id,user_name` on a list endpoint, checked against the fields `T` has. Without
+/// the parameter every field is returned.
+pub struct FieldSelection<T> {
+  fields: Option<Vec<String>>,
+  _dto: PhantomData<fn() -> T>,
+}
+
+impl<T: Serialize> FieldSelection<T> {
+  /// `value` as JSON, reduced to the selected fields when there are any.
+  pub fn apply(&self, value: &T) -> serde_json::Value {
+    let value = serde_json::to_value(value).unwrap_or_default();
+    match (&self.fields, value) {
+      (Some(fields), serde_json::Value::Object(mut object)) => {
+        object.retain(|key, _| fields.contains(key));
+        serde_json::Value::Object(object)
+      }
+      (_, value) => value,
+    }
+  }
+}
+
+impl<T: PartialSchema> FieldSelection<T> {
+  fn parse(query: &str) -> Result<Self, ValidationFailed> {
+    let requested = web::Query::<FieldsParam>::from_query(query)
+      .ok()
+      .and_then(|param| param.into_inner().fields);
+    let Some(requested) = requested else {
+      return Ok(Self {
+        fields: None,
+        _dto: PhantomData,
+      });
+    };
+
+    let known = field_names::<T>();
+    let mut fields: Vec<String> = Vec::new();
+    let mut errors: Vec<FieldErrorDto> = Vec::new();
+    for field in requested
+      .split(',')
+      .map(str::trim)
+      .filter(|f| !f.is_empty())
+    {
+      if !known.iter().any(|k| k == field) {
+        errors.push(FieldErrorDto {
+          field: "fields".to_string(),
+          code: "unknown_field".to_string(),
+          message: format!(
+            "Unknown field '{}', expected any of {}",
+            field,
+            known.join(", ")
+          ),
+        });
+      } else if !fields.iter().any(|f| f == field) {
+        fields.push(field.to_string());
+      }
+    }
+    if !errors.is_empty() {
+      return Err(ValidationFailed(errors));
+    }
+    // `fields=` with nothing in it means the default, not an empty object per item
+    Ok(Self {
+      fields: (!fields.is_empty()).then_some(fields),
+      _dto: PhantomData,
+    })
+  }
+}
+
+impl<T: PartialSchema> FromRequest for FieldSelection<T> {
+  type Error = actix_web::Error;
+
+  type Future = Ready<Result<Self, Self::Error>>;
+
+  fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+    ready(Self::parse(req.query_string()).map_err(actix_web::Error::from))
+  }
+}
@@ -1 +1,7 @@
 pub mod base_res_dto;
+pub mod created_res_dto;
+pub mod field_selection;
+pub mod paged_res_dto;
+pub mod sort;
+pub mod validated_json;
+pub mod versioned_dto;
@@ -0,0 +1,44 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+// Upper bound for page_size on every paged endpoint
+pub const MAX_PAGE_SIZE: i32 = 100;
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct PagedResDto<T: ToSchema> {
+  pub items: Vec<T>,
+  pub page: i32,
+  pub page_size: i32,
+  pub total_count: i32,
+  pub total_pages: i32,
+}
+
+impl<T: ToSchema> PagedResDto<T> {
+  pub fn new(items: Vec<T>, page: i32, page_size: i32, total_count: i32) -> Self {
+    let total_pages = if page_size > 0 {
+      (total_count + page_size - 1) / page_size
+    } else {
+      0
+    };
+    PagedResDto {
+      items,
+      page,
+      page_size,
+      total_count,
+      total_pages,
+    }
+  }
+}
+
+// Implement Default for PagedResDto<T>
+impl<T: ToSchema> Default for PagedResDto<T> {
+  fn default() -> Self {
+    PagedResDto {
+      items: Vec::new(),
+      page: 1,
+      page_size: 0,
+      total_count: 0,
+      total_pages: 0,
+    }
+  }
+}
@@ -0,0 +1,27 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// `sort_dir` of a list endpoint, ascending unless asked otherwise.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SortDir {
+  #[default]
+  Asc,
+  Desc,
+}
+
+impl SortDir {
+  /// `ORDER BY` clause for a column picked by the repo, never for a name sent by the client.
+  /// Ties are broken by `id` so pages stay stable.
+  pub fn order_by(self, column: &'static str) -> String {
+    let dir = match self {
+      SortDir::Asc => "ASC",
+      SortDir::Desc => "DESC",
+    };
+    if column == "id" {
+      format!("ORDER BY [id] {}", dir)
+    } else {
+      format!("ORDER BY [{}] {}, [id] {}", column, dir, dir)
+    }
+  }
+}
@@ -0,0 +1,156 @@
+use std::{fmt, ops};
+
+use actix_web::{FromRequest, HttpRequest, HttpResponse, ResponseError, dev::Payload, web};
+use futures::future::{LocalBoxFuture, ready};
+use serde::{Serialize, de::DeserializeOwned};
+use utoipa::ToSchema;
+use validator::{Validate, ValidationError, ValidationErrors, ValidationErrorsKind};
+
+use crate::dto::base_res_dto::{BaseResDto, Status};
+
+/// One rejected field, `code` is the rule that failed (`length`, `email`, `range`, ...).
+#[derive(Serialize, Debug, Clone, ToSchema)]
+pub struct FieldErrorDto {
+  pub field: String,
+  pub code: String,
+  pub message: String,
+}
+
+fn describe(error: &ValidationError) -> String {
+  if let Some(message) = &error.message {
+    return message.to_string();
+  }
+  let param = |name: &str| error.params.get(name).map(|v| v.to_string());
+  match (error.code.as_ref(), param("min"), param("max")) {
+    ("length", Some(min), Some(max)) => format!("Must be {} to {} characters long", min, max),
+    ("length", Some(min), None) => format!("Must be at least {} characters long", min),
+    ("length", None, Some(max)) => format!("Cannot exceed {} characters", max),
+    ("range", Some(min), Some(max)) => format!("Must be between {} and {}", min, max),
+    ("range", Some(min), None) => format!("Must be at least {}", min),
+    ("range", None, Some(max)) => format!("Cannot be greater than {}", max),
+    ("email", _, _) => "Must be a valid email address".to_string(),
+    ("required", _, _) => "Is required".to_string(),
+    _ => "Is invalid".to_string(),
+  }
+}
+
+// Nested structs and lists report their fields as `parent.child` and `list[0].child`
+fn collect(prefix: &str, errors: &ValidationErrors, out: &mut Vec<FieldErrorDto>) {
+  for (field, kind) in errors.errors() {
+    let path = if prefix.is_empty() {
+      field.to_string()
+    } else {
+      format!("{}.{}", prefix, field)
+    };
+    match kind {
+      ValidationErrorsKind::Field(errors) => out.extend(errors.iter().map(|error| FieldErrorDto {
+        field: path.clone(),
+        code: error.code.to_string(),
+        message: describe(error),
+      })),
+      ValidationErrorsKind::Struct(errors) => collect(&path, errors, out),
+      ValidationErrorsKind::List(items) => {
+        for (index, errors) in items {
+          collect(&format!("{}[{}]", path, index), errors, out);
+        }
+      }
+    }
+  }
+}
+
+/// Every field error of a request body, answered as a single 400.
+#[derive(Debug)]
+pub struct ValidationFailed(pub Vec<FieldErrorDto>);
+
+impl From<ValidationErrors> for ValidationFailed {
+  fn from(errors: ValidationErrors) -> Self {
+    let mut fields = Vec::new();
+    collect("", &errors, &mut fields);
+    fields.sort_by(|a, b| a.field.cmp(&b.field));
+    ValidationFailed(fields)
+  }
+}
+
+impl fmt::Display for ValidationFailed {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{} invalid field(s)", self.0.len())
+  }
+}
+
+impl ResponseError for ValidationFailed {
+  fn error_response(&self) -> HttpResponse {
+    HttpResponse::BadRequest().json(BaseResDto {
+      data: Some(self.0.clone()),
+      status: Status::validation_failed(),
+    })
+  }
+}
+
+/// Json extractor that runs the DTO's `#[validate]` rules before the handler is called.
+pub struct ValidatedJson<T>(pub T);
+
+impl<T> ValidatedJson<T> {
+  pub fn into_inner(self) -> T {
+    self.0
+  }
+}
+
+impl<T> ops::Deref for ValidatedJson<T> {
+  type Target = T;
+
+  fn deref(&self) -> &Self::Target {
+    &self.0
+  }
+}
+
+impl<T: DeserializeOwned + Validate + 'static> FromRequest for ValidatedJson<T> {
+  type Error = actix_web::Error;
+
+  type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+  fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+    let json = web::Json::<T>::from_request(req, payload);
+
+    Box::pin(async move {
+      let dto = json.await?.into_inner();
+      dto.validate().map_err(ValidationFailed::from)?;
+      Ok(ValidatedJson(dto))
+    })
+  }
+}
+
+/// Query string counterpart of `ValidatedJson`.
+pub struct ValidatedQuery<T>(pub T);
+
+impl<T> ValidatedQuery<T> {
+  pub fn into_inner(self) -> T {
+    self.0
+  }
+}
+
+impl<T> ops::Deref for ValidatedQuery<T> {
+  type Target = T;
+
+  fn deref(&self) -> &Self::Target {
+    &self.0
+  }
+}
+
+impl<T: DeserializeOwned + Validate + 'static> FromRequest for ValidatedQuery<T> {
+  type Error = actix_web::Error;
+
+  type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+  fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+    let result = web::Query::<T>::from_query(req.query_string())
+      .map_err(|e| actix_web::Error::from(Status::bad_request(e.to_string())))
+      .and_then(|query| {
+        let dto = query.into_inner();
+        dto
+          .validate()
+          .map_err(|errors| ValidationFailed::from(errors).into())
+          .map(|_| ValidatedQuery(dto))
+      });
+    Box::pin(ready(result))
+  }
+}
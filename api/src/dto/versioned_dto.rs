@@ -0,0 +1,76 @@
+use std::ops;
+
+use actix_web::{FromRequest, HttpRequest, dev::Payload, web};
+use futures::future::LocalBoxFuture;
+use serde::de::DeserializeOwned;
+use validator::Validate;
+
+use crate::{
+  dto::{base_res_dto::Status, validated_json::ValidationFailed},
+  error::StatusMessage,
+};
+
+pub const SCHEMA_VERSION_FIELD: &str = "schema_version";
+
+/// A request body whose shape may change over time.
+///
+/// Clients send a `schema_version` field (defaults to `1` when omitted) and the
+/// implementor upgrades older payloads into the latest internal shape, so handlers
+/// only ever deal with `Self`.
+pub trait VersionedDto: Sized + DeserializeOwned {
+  const LATEST_VERSION: u32;
+
+  /// Deserialize a payload sent with an older `schema_version` into the latest shape.
+  /// Only called for versions below `LATEST_VERSION`.
+  fn from_version(version: u32, value: serde_json::Value) -> Result<Self, StatusMessage> {
+    let _ = value;
+    Err(StatusMessage::UnsupportedSchemaVersion(version))
+  }
+}
+
+/// Json extractor that resolves `schema_version`, migrates the body to the latest `T` and
+/// runs its `#[validate]` rules.
+pub struct VersionedJson<T>(pub T);
+
+impl<T> ops::Deref for VersionedJson<T> {
+  type Target = T;
+
+  fn deref(&self) -> &Self::Target {
+    &self.0
+  }
+}
+
+impl<T: VersionedDto + Validate + 'static> FromRequest for VersionedJson<T> {
+  type Error = actix_web::Error;
+
+  type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+  fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+    let json = web::Json::<serde_json::Value>::from_request(req, payload);
+
+    Box::pin(async move {
+      let mut value = json.await?.into_inner();
+
+      let version = match value.get(SCHEMA_VERSION_FIELD) {
+        None => 1,
+        Some(v) => v
+          .as_u64()
+          .and_then(|v| u32::try_from(v).ok())
+          .ok_or(Status::bad_request(StatusMessage::WrongParams))?,
+      };
+      if let Some(obj) = value.as_object_mut() {
+        obj.remove(SCHEMA_VERSION_FIELD);
+      }
+
+      let dto = if version == T::LATEST_VERSION {
+        serde_json::from_value::<T>(value).map_err(|e| Status::bad_request(e.to_string()))?
+      } else if version > 0 && version < T::LATEST_VERSION {
+        T::from_version(version, value).map_err(Status::bad_request)?
+      } else {
+        return Err(Status::bad_request(StatusMessage::UnsupportedSchemaVersion(version)).into());
+      };
+      dto.validate().map_err(ValidationFailed::from)?;
+      Ok(VersionedJson(dto))
+    })
+  }
+}
@@ -1,11 +1,14 @@
 use core::fmt;
 
-use actix_web::{HttpResponse, ResponseError, body};
+use actix_web::{HttpResponse, ResponseError, body, http::StatusCode};
 use utoipa::ToSchema;
 
 use crate::{
   commons::status_code_const::StatusCodeConst,
+  db::{PoolExhausted, QueryTimeout, RowMappingError},
   dto::base_res_dto::{BaseResDto, Status},
+  i18n::{self, Locale},
+  middleware::request_id,
 };
 
 impl fmt::Display for Status {
@@ -23,38 +26,56 @@ pub enum StatusMessage {
   PermissionDenied,
   UserNameExisted,
   Existed(String),
-  UserNameExeedMaxLength(usize),
   WrongParams,
   DecodeTokenErr,
+  TokenMissing,
+  UnsupportedSchemaVersion(u32),
+  QuotaExceeded(String, i32),
+  AccountDisabled,
+  TokenExpired,
+  EmailChangeRequiresVerification,
+  RoleInUse(i32),
+  QueryTimeout,
+  RateLimited(u64),
+  PayloadTooLarge(usize),
+  JsonTooDeep(usize),
+  ValidationFailed,
+  RouteNotFound,
+  MethodNotAllowed,
+  BatchRolledBack,
+  CannotImpersonate,
+  NotImpersonating,
+  AdminSessionExpired,
+  WrongPassword,
+  PasswordReused(u32),
+  SsoRejected,
+  DeviceMismatch,
+  CaptchaRequired,
+  UnsupportedImage,
 }
 
-impl ToString for StatusMessage {
-  fn to_string(&self) -> String {
-    self.to_str().to_owned()
+impl fmt::Display for StatusMessage {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.write_str(&self.to_str())
   }
 }
 
-impl Into<String> for StatusMessage {
-  fn into(self) -> String {
-    self.to_string()
+impl From<StatusMessage> for String {
+  fn from(message: StatusMessage) -> Self {
+    message.to_string()
   }
 }
 
 impl StatusMessage {
+  /// The message in the language negotiated for the current request.
   pub fn to_str(&self) -> String {
-    match self {
-      StatusMessage::Success => "Success".to_string(),
-      StatusMessage::ServerError => "Server error, Please try again later".to_string(),
-      StatusMessage::NotFound(item_name) => format!("{} not found", item_name),
-      StatusMessage::Unauthorized => "Invalid credentials".to_string(),
-      StatusMessage::PermissionDenied => "Permission denied".to_string(),
-      StatusMessage::UserNameExisted => "Username already existed".to_string(),
-      StatusMessage::Existed(ex) => format!("{} already existed", ex),
-      StatusMessage::UserNameExeedMaxLength(max_length) => {
-        format!("Username cannot exceed {} characters", max_length)
-      }
-      StatusMessage::WrongParams => "Invalid input".to_string(),
-      StatusMessage::DecodeTokenErr => "Decoded token failed".to_string(),
+    self.localized(i18n::current())
+  }
+
+  pub fn localized(&self, locale: Locale) -> String {
+    match locale {
+      Locale::En => i18n::en::message(self),
+      Locale::Km => i18n::km::message(self),
     }
   }
 }
@@ -73,6 +94,7 @@ impl Status {
       status: 200,
       message: StatusMessage::Success.to_str(),
       code: StatusCodeConst::SUCCESS.to_string(),
+      request_id: None,
     }
   }
 
@@ -83,6 +105,20 @@ impl Status {
         status: 200,
         message: StatusMessage::Success.to_str(),
         code: StatusCodeConst::SUCCESS.to_string(),
+        request_id: None,
+      },
+    }
+  }
+
+  /// Same as `success_with_data` for a request whose work goes on in the background.
+  pub fn accepted_with_data<T: ToSchema>(data: T) -> BaseResDto<T> {
+    BaseResDto {
+      data: Some(data),
+      status: Status {
+        status: 202,
+        message: StatusMessage::Success.to_str(),
+        code: StatusCodeConst::SUCCESS.to_string(),
+        request_id: None,
       },
     }
   }
@@ -92,6 +128,7 @@ impl Status {
       status: 500,
       message: message.into(),
       code: StatusCodeConst::SERVER_ERROR.to_string(),
+      request_id: request_id::current(),
     }
   }
 
@@ -100,6 +137,16 @@ impl Status {
       status: 400,
       message: message.into(),
       code: StatusCodeConst::ERROR.to_string(),
+      request_id: request_id::current(),
+    }
+  }
+
+  pub fn validation_failed() -> Self {
+    Status {
+      status: 400,
+      message: StatusMessage::ValidationFailed.to_str(),
+      code: StatusCodeConst::VALIDATION_FAILED.to_string(),
+      request_id: request_id::current(),
     }
   }
 
@@ -108,14 +155,34 @@ impl Status {
       status: 401,
       message: message.into(),
       code: StatusCodeConst::UNAUTHORIZED.to_string(),
+      request_id: request_id::current(),
+    }
+  }
+
+  pub fn device_mismatch() -> Self {
+    Status {
+      status: 401,
+      message: StatusMessage::DeviceMismatch.to_str(),
+      code: StatusCodeConst::DEVICE_MISMATCH.to_string(),
+      request_id: request_id::current(),
+    }
+  }
+
+  pub fn captcha_required() -> Self {
+    Status {
+      status: 400,
+      message: StatusMessage::CaptchaRequired.to_str(),
+      code: StatusCodeConst::CAPTCHA_REQUIRED.to_string(),
+      request_id: request_id::current(),
     }
   }
 
   pub fn token_missing() -> Self {
     Status {
       status: 401,
-      message: "Unauthorized, token missing".into(),
+      message: StatusMessage::TokenMissing.to_str(),
       code: StatusCodeConst::TOKEN_MISSING.to_string(),
+      request_id: request_id::current(),
     }
   }
 
@@ -124,6 +191,7 @@ impl Status {
       status: 409,
       message: message.into(),
       code: StatusCodeConst::UQIQUE_CONSTRAINT.to_string(),
+      request_id: request_id::current(),
     }
   }
 
@@ -132,6 +200,7 @@ impl Status {
       status: 404,
       message: message.into(),
       code: StatusCodeConst::NOT_FOUND.to_string(),
+      request_id: request_id::current(),
     }
   }
 
@@ -140,6 +209,132 @@ impl Status {
       status: 403,
       message: StatusMessage::PermissionDenied.to_str(),
       code: StatusCodeConst::FORBIDDEN.to_string(),
+      request_id: request_id::current(),
+    }
+  }
+
+  pub fn account_disabled() -> Self {
+    Status {
+      status: 403,
+      message: StatusMessage::AccountDisabled.to_str(),
+      code: StatusCodeConst::ACCOUNT_DISABLED.to_string(),
+      request_id: request_id::current(),
+    }
+  }
+
+  pub fn quota_exceeded(message: impl Into<String>) -> Self {
+    Status {
+      status: 422,
+      message: message.into(),
+      code: StatusCodeConst::QUOTA_EXCEEDED.to_string(),
+      request_id: request_id::current(),
+    }
+  }
+
+  pub fn pool_exhausted(message: impl Into<String>) -> Self {
+    Status {
+      status: 503,
+      message: message.into(),
+      code: StatusCodeConst::POOL_EXHAUSTED.to_string(),
+      request_id: request_id::current(),
+    }
+  }
+
+  // Too much background work already queued, the client should retry later
+  pub fn busy(message: impl Into<String>) -> Self {
+    Status {
+      status: 503,
+      message: message.into(),
+      code: StatusCodeConst::BUSY.to_string(),
+      request_id: request_id::current(),
+    }
+  }
+
+  pub fn payload_too_large(limit: usize) -> Self {
+    Status {
+      status: 413,
+      message: StatusMessage::PayloadTooLarge(limit).to_str(),
+      code: StatusCodeConst::PAYLOAD_TOO_LARGE.to_string(),
+      request_id: request_id::current(),
+    }
+  }
+
+  pub fn rate_limited(retry_after_secs: u64) -> Self {
+    Status {
+      status: 429,
+      message: StatusMessage::RateLimited(retry_after_secs).to_str(),
+      code: StatusCodeConst::RATE_LIMITED.to_string(),
+      request_id: request_id::current(),
+    }
+  }
+
+  // An operation of an atomic batch undone, or never run, because another one failed
+  pub fn rolled_back() -> Self {
+    Status {
+      status: 424,
+      message: StatusMessage::BatchRolledBack.to_str(),
+      code: StatusCodeConst::ROLLED_BACK.to_string(),
+      request_id: request_id::current(),
+    }
+  }
+
+  pub fn unhealthy(message: impl Into<String>) -> Self {
+    Status {
+      status: 503,
+      message: message.into(),
+      code: StatusCodeConst::UNHEALTHY.to_string(),
+      request_id: request_id::current(),
+    }
+  }
+
+  // Errors raised by actix itself (unknown route, wrong method, ...) that carry no body of ours
+  pub fn from_http_status(status: StatusCode) -> Self {
+    let (message, code) = match status {
+      StatusCode::NOT_FOUND => (
+        StatusMessage::RouteNotFound.to_str(),
+        StatusCodeConst::NOT_FOUND,
+      ),
+      StatusCode::METHOD_NOT_ALLOWED => (
+        StatusMessage::MethodNotAllowed.to_str(),
+        StatusCodeConst::METHOD_NOT_ALLOWED,
+      ),
+      status if status.is_server_error() => (
+        StatusMessage::ServerError.to_str(),
+        StatusCodeConst::SERVER_ERROR,
+      ),
+      status => (
+        status.canonical_reason().unwrap_or("Error").to_string(),
+        StatusCodeConst::ERROR,
+      ),
+    };
+    Status {
+      status: status.as_u16(),
+      message,
+      code: code.to_string(),
+      request_id: request_id::current(),
+    }
+  }
+
+  pub fn query_timeout() -> Self {
+    Status {
+      status: 504,
+      message: StatusMessage::QueryTimeout.to_str(),
+      code: StatusCodeConst::QUERY_TIMEOUT.to_string(),
+      request_id: request_id::current(),
+    }
+  }
+
+  // A timed out query answers 504, a row that can't be mapped 500 and a full pool 503,
+  // whatever status the caller would have used otherwise
+  pub fn or_query_timeout(self, e: &anyhow::Error) -> Self {
+    if e.downcast_ref::<QueryTimeout>().is_some() {
+      Status::query_timeout()
+    } else if e.downcast_ref::<RowMappingError>().is_some() {
+      Status::server_error(e.to_string())
+    } else if e.downcast_ref::<PoolExhausted>().is_some() {
+      Status::pool_exhausted(e.to_string())
+    } else {
+      self
     }
   }
 
@@ -165,10 +360,34 @@ impl Status {
         data: None,
         status: self,
       }),
+      405 => HttpResponse::MethodNotAllowed().json(BaseResDto::<()> {
+        data: None,
+        status: self,
+      }),
       409 => HttpResponse::Conflict().json(BaseResDto::<()> {
         data: None,
         status: self,
       }),
+      422 => HttpResponse::UnprocessableEntity().json(BaseResDto::<()> {
+        data: None,
+        status: self,
+      }),
+      413 => HttpResponse::PayloadTooLarge().json(BaseResDto::<()> {
+        data: None,
+        status: self,
+      }),
+      429 => HttpResponse::TooManyRequests().json(BaseResDto::<()> {
+        data: None,
+        status: self,
+      }),
+      503 => HttpResponse::ServiceUnavailable().json(BaseResDto::<()> {
+        data: None,
+        status: self,
+      }),
+      504 => HttpResponse::GatewayTimeout().json(BaseResDto::<()> {
+        data: None,
+        status: self,
+      }),
       _ => {
         eprintln!(
           "Warning: Missing pattern match. Converted status code {} to 500",
@@ -179,6 +398,7 @@ impl Status {
             message: StatusMessage::ServerError.into(),
             code: StatusCodeConst::SERVER_ERROR.to_string(),
             status: 500,
+            request_id: request_id::current(),
           },
           data: None,
         })
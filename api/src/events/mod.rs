@@ -0,0 +1,131 @@
+use std::{
+  collections::VecDeque,
+  sync::{Arc, Mutex},
+};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+// Events a slow subscriber may fall behind by before it starts missing some
+const CHANNEL_CAPACITY: usize = 256;
+// Recent events kept for SSE clients resuming with Last-Event-ID
+const REPLAY_CAPACITY: usize = 1000;
+
+/// Something that changed in the domain, pushed to admins watching in real time.
+#[derive(Serialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DomainEvent {
+  UserRegistered { user_name: String },
+  UserUpdated { user_id: i32 },
+  RoleAssigned { role_id: i32, user_ids: Vec<i32> },
+}
+
+impl DomainEvent {
+  pub fn kind(&self) -> &'static str {
+    match self {
+      DomainEvent::UserRegistered { .. } => "user_registered",
+      DomainEvent::UserUpdated { .. } => "user_updated",
+      DomainEvent::RoleAssigned { .. } => "role_assigned",
+    }
+  }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct EventEnvelope {
+  /// Increases by one for every event published since startup
+  pub id: u64,
+  pub at: DateTime<Utc>,
+  #[serde(flatten)]
+  pub event: DomainEvent,
+}
+
+struct Recent {
+  next_id: u64,
+  events: VecDeque<EventEnvelope>,
+}
+
+/// Events replayed to a resuming subscriber, followed by the live feed.
+pub struct Resumed {
+  pub replay: Vec<EventEnvelope>,
+  /// Events the subscriber asked for that already left the replay buffer
+  pub missed: u64,
+  pub live: broadcast::Receiver<EventEnvelope>,
+}
+
+/// In-process fan-out of domain events, handlers publish and each connection subscribes.
+#[derive(Clone)]
+pub struct EventBus {
+  sender: broadcast::Sender<EventEnvelope>,
+  recent: Arc<Mutex<Recent>>,
+}
+
+impl Default for EventBus {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl EventBus {
+  pub fn new() -> Self {
+    let (sender, _) = broadcast::channel(CHANNEL_CAPACITY);
+    Self {
+      sender,
+      recent: Arc::new(Mutex::new(Recent {
+        next_id: 1,
+        events: VecDeque::with_capacity(REPLAY_CAPACITY),
+      })),
+    }
+  }
+
+  // Never blocks on subscribers, publishing with nobody subscribed only fills the replay buffer
+  pub fn publish(&self, event: DomainEvent) {
+    let Ok(mut recent) = self.recent.lock() else {
+      return;
+    };
+    let envelope = EventEnvelope {
+      id: recent.next_id,
+      at: Utc::now(),
+      event,
+    };
+    recent.next_id += 1;
+    if recent.events.len() == REPLAY_CAPACITY {
+      recent.events.pop_front();
+    }
+    recent.events.push_back(envelope.clone());
+    // Sent under the lock so `resume` never sees an event both replayed and live
+    let _ = self.sender.send(envelope);
+  }
+
+  pub fn subscribe(&self) -> broadcast::Receiver<EventEnvelope> {
+    self.sender.subscribe()
+  }
+
+  /// Subscribes and returns the buffered events published after `last_event_id`.
+  pub fn resume(&self, last_event_id: u64) -> Resumed {
+    let Ok(recent) = self.recent.lock() else {
+      return Resumed {
+        replay: Vec::new(),
+        missed: 0,
+        live: self.sender.subscribe(),
+      };
+    };
+    // Ids restart with the process, an id from before a restart replays everything buffered
+    let last_event_id = if last_event_id >= recent.next_id {
+      0
+    } else {
+      last_event_id
+    };
+    let oldest = recent.events.front().map_or(recent.next_id, |e| e.id);
+    Resumed {
+      replay: recent
+        .events
+        .iter()
+        .filter(|e| e.id > last_event_id)
+        .cloned()
+        .collect(),
+      missed: oldest.saturating_sub(last_event_id + 1),
+      live: self.sender.subscribe(),
+    }
+  }
+}
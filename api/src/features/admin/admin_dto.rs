@@ -0,0 +1,189 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use validator::Validate;
+
+use crate::{
+  db::PoolStats,
+  features::{audit::audit_dto::AuditLogDto, login_history::login_history_dto::LoginAttemptDto},
+  reports::ReportFormat,
+  scheduler::TaskReport,
+  utils::ttl_cache::TtlCell,
+};
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub struct RoleCountDto {
+  pub role: String,
+  pub total: i32,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub struct RegistrationCountsDto {
+  pub last_7_days: i32,
+  pub last_30_days: i32,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub struct UserStatsDto {
+  pub total_users: i32,
+  pub by_role: Vec<RoleCountDto>,
+  pub generated_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub struct PoolHealthDto {
+  pub pool_name: String,
+  pub pool_size: u32,
+  pub healthy: bool,
+  pub checkout_ms: u128,
+  pub error: Option<String>,
+  pub generated_at: DateTime<Utc>,
+}
+
+// Counters since startup of this instance
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub struct RateLimitHitsDto {
+  /// Requests answered with 429 by the per-IP rate limit
+  pub limited: u64,
+  /// Login attempts held back by the login tarpit
+  pub login_delayed: u64,
+  /// Login attempts refused by the login tarpit
+  pub login_blocked: u64,
+  pub generated_at: DateTime<Utc>,
+}
+
+// Work queued on this instance
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub struct JobBacklogDto {
+  /// Queued and running `/operations`
+  pub operations_pending: usize,
+  pub operations_max_pending: usize,
+  pub emails_queued: usize,
+  pub images_queued: usize,
+  pub generated_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub struct PoolStatsDto {
+  pub pool_name: String,
+  pub pool_size: u32,
+  pub in_use: u32,
+  pub idle: u32,
+  pub waiters: u64,
+  pub checkouts: u64,
+  pub timeouts: u64,
+  pub reconnects: u64,
+  pub avg_checkout_ms: f64,
+  pub max_checkout_ms: f64,
+}
+
+impl From<PoolStats> for PoolStatsDto {
+  fn from(stats: PoolStats) -> Self {
+    PoolStatsDto {
+      idle: stats.pool_size.saturating_sub(stats.in_use),
+      pool_name: stats.pool_name,
+      pool_size: stats.pool_size,
+      in_use: stats.in_use,
+      waiters: stats.waiters,
+      checkouts: stats.checkouts,
+      timeouts: stats.timeouts,
+      reconnects: stats.reconnects,
+      avg_checkout_ms: stats.avg_checkout_ms,
+      max_checkout_ms: stats.max_checkout_ms,
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub struct ScheduledTaskDto {
+  pub name: String,
+  pub enabled: bool,
+  /// `every 3600s` or `daily at 00:00 UTC`
+  pub schedule: String,
+  pub next_run_at: Option<DateTime<Utc>>,
+  pub last_started_at: Option<DateTime<Utc>>,
+  pub last_duration_ms: Option<u128>,
+  pub last_outcome: Option<String>,
+  pub last_error: Option<String>,
+  pub runs: u64,
+  pub failures: u64,
+}
+
+impl From<TaskReport> for ScheduledTaskDto {
+  fn from(report: TaskReport) -> Self {
+    ScheduledTaskDto {
+      name: report.task.name().to_string(),
+      enabled: report.enabled,
+      schedule: report.schedule.describe(),
+      next_run_at: report.next_run_at,
+      last_started_at: report.last_started_at,
+      last_duration_ms: report.last_duration_ms,
+      last_outcome: report.last_outcome,
+      last_error: report.last_error,
+      runs: report.runs,
+      failures: report.failures,
+    }
+  }
+}
+
+// ---------- Request Dto --------- //
+
+#[derive(Serialize, Deserialize, Clone, ToSchema, Validate)]
+pub struct ImpersonateReqDto {
+  pub user_id: i32,
+  /// Why the session is needed, kept in the audit log
+  #[validate(length(max = 255))]
+  pub reason: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema, IntoParams, Validate)]
+pub struct UserReportReqDto {
+  /// `pdf` or `xlsx`
+  pub format: ReportFormat,
+}
+
+// ---------- Response Dto --------- //
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub struct ImpersonationResDto {
+  pub token: String,
+  pub user_id: i32,
+  pub expires_at: DateTime<Utc>,
+}
+
+// Each section is optional so a failing section doesn't fail the whole dashboard
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub struct DashboardResDto {
+  pub user_stats: Option<UserStatsDto>,
+  pub pool_health: Option<PoolHealthDto>,
+  pub recent_activity: Option<Vec<AuditLogDto>>,
+  pub recent_logins: Option<Vec<LoginAttemptDto>>,
+  pub rate_limit_hits: Option<RateLimitHitsDto>,
+  pub job_backlog: Option<JobBacklogDto>,
+}
+
+// Per-section caches shared by all workers through AppState
+#[derive(Clone)]
+pub struct DashboardCache {
+  pub user_stats: TtlCell<UserStatsDto>,
+  pub pool_health: TtlCell<PoolHealthDto>,
+  pub recent_activity: TtlCell<Vec<AuditLogDto>>,
+  pub recent_logins: TtlCell<Vec<LoginAttemptDto>>,
+  pub rate_limit_hits: TtlCell<RateLimitHitsDto>,
+  pub job_backlog: TtlCell<JobBacklogDto>,
+}
+
+impl Default for DashboardCache {
+  fn default() -> Self {
+    DashboardCache {
+      user_stats: TtlCell::new(Duration::from_secs(60)),
+      pool_health: TtlCell::new(Duration::from_secs(5)),
+      recent_activity: TtlCell::new(Duration::from_secs(10)),
+      recent_logins: TtlCell::new(Duration::from_secs(10)),
+      rate_limit_hits: TtlCell::new(Duration::from_secs(5)),
+      job_backlog: TtlCell::new(Duration::from_secs(5)),
+    }
+  }
+}
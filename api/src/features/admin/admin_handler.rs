@@ -0,0 +1,726 @@
+use std::time::Instant;
+
+use actix_web::{
+  HttpMessage, HttpRequest, HttpResponse, Responder,
+  cookie::{
+    Cookie,
+    time::{Duration, OffsetDateTime},
+  },
+  http::header::{ContentDisposition, DispositionParam, DispositionType, LOCATION},
+  web,
+};
+use chrono::{DateTime, Utc};
+
+use crate::{
+  app_state::AppState,
+  document,
+  dto::{
+    base_res_dto::{BaseResDto, Status},
+    validated_json::{ValidatedJson, ValidatedQuery},
+  },
+  error::StatusMessage,
+  features::{
+    admin::{
+      admin_dto::{
+        DashboardResDto, ImpersonateReqDto, ImpersonationResDto, JobBacklogDto, PoolHealthDto,
+        PoolStatsDto, RateLimitHitsDto, ScheduledTaskDto, UserReportReqDto, UserStatsDto,
+      },
+      admin_repo::AdminRepo,
+    },
+    audit::{
+      audit_dto::{AuditLogDto, GetAuditLogsReqDto},
+      audit_entity::{AuditAction, AuditLogEntity},
+      audit_repo::AuditRepo,
+    },
+    auth::auth_dto::LoginResDto,
+    login_history::{
+      login_history_dto::{GetLoginHistoryReqDto, LoginAttemptDto},
+      login_history_repo::LoginHistoryRepo,
+    },
+    operations::{operations_dto::OperationResDto, operations_handler::version_prefix},
+    users::{user_dto::UserDto, user_entity::UserRole},
+  },
+  middleware::auth::{Authenticated, Impersonation},
+  reports::{self, Report, ReportCell, ReportFormat, ReportSection},
+  security_log::{SecurityEvent, SecurityEventType},
+  storage::StoredObject,
+  utils::jwt_util::JwtUtil,
+  utils::operations::{OperationProgress, OperationResult},
+};
+
+const RECENT_ACTIVITY_SIZE: i32 = 10;
+const RECENT_LOGINS_SIZE: i32 = 10;
+
+async fn load_user_stats(data: &AppState) -> Option<UserStatsDto> {
+  if let Some(cached) = data.dashboard_cache.user_stats.get() {
+    return Some(cached);
+  }
+
+  let mut repo = AdminRepo::new(data);
+  match repo.get_user_role_counts().await {
+    Ok(by_role) => {
+      let stats = UserStatsDto {
+        total_users: by_role.iter().map(|r| r.total).sum(),
+        by_role,
+        generated_at: Utc::now(),
+      };
+      data.dashboard_cache.user_stats.set(stats.clone());
+      Some(stats)
+    }
+    Err(e) => {
+      eprintln!("Failed to load user stats: {}", e);
+      None
+    }
+  }
+}
+
+async fn load_pool_health(data: &AppState) -> Option<PoolHealthDto> {
+  if let Some(cached) = data.dashboard_cache.pool_health.get() {
+    return Some(cached);
+  }
+
+  let setting = &data.config.database.sql_server;
+  let started = Instant::now();
+  let checkout = data.db_manager.get_client(&setting.pool_name).await;
+  let health = PoolHealthDto {
+    pool_name: setting.pool_name.clone(),
+    pool_size: setting.pool_size,
+    healthy: checkout.is_ok(),
+    checkout_ms: started.elapsed().as_millis(),
+    error: checkout.err().map(|e| e.to_string()),
+    generated_at: Utc::now(),
+  };
+  data.dashboard_cache.pool_health.set(health.clone());
+  Some(health)
+}
+
+async fn load_recent_activity(data: &AppState) -> Option<Vec<AuditLogDto>> {
+  if let Some(cached) = data.dashboard_cache.recent_activity.get() {
+    return Some(cached);
+  }
+
+  let filter = GetAuditLogsReqDto {
+    actor_id: None,
+    action: None,
+    target: None,
+    page: 1,
+    page_size: RECENT_ACTIVITY_SIZE,
+  };
+  match AuditRepo::new(data).get_paged(&filter).await {
+    Ok((logs, _)) => {
+      let activity: Vec<AuditLogDto> = logs.into_iter().map(AuditLogDto::from).collect();
+      data.dashboard_cache.recent_activity.set(activity.clone());
+      Some(activity)
+    }
+    Err(e) => {
+      eprintln!("Failed to load recent activity: {}", e);
+      None
+    }
+  }
+}
+
+async fn load_recent_logins(data: &AppState) -> Option<Vec<LoginAttemptDto>> {
+  if let Some(cached) = data.dashboard_cache.recent_logins.get() {
+    return Some(cached);
+  }
+
+  let filter = GetLoginHistoryReqDto {
+    user_id: None,
+    outcome: None,
+    page: 1,
+    page_size: RECENT_LOGINS_SIZE,
+  };
+  match LoginHistoryRepo::new(data).get_paged(&filter).await {
+    Ok((attempts, _)) => {
+      let logins: Vec<LoginAttemptDto> = attempts.into_iter().map(LoginAttemptDto::from).collect();
+      data.dashboard_cache.recent_logins.set(logins.clone());
+      Some(logins)
+    }
+    Err(e) => {
+      eprintln!("Failed to load recent logins: {}", e);
+      None
+    }
+  }
+}
+
+fn load_rate_limit_hits(data: &AppState) -> Option<RateLimitHitsDto> {
+  if let Some(cached) = data.dashboard_cache.rate_limit_hits.get() {
+    return Some(cached);
+  }
+
+  let hits = RateLimitHitsDto {
+    limited: data.rate_limit.limited_total(),
+    login_delayed: data.login_tarpit.delayed_total(),
+    login_blocked: data.login_tarpit.blocked_total(),
+    generated_at: Utc::now(),
+  };
+  data.dashboard_cache.rate_limit_hits.set(hits.clone());
+  Some(hits)
+}
+
+fn load_job_backlog(data: &AppState) -> Option<JobBacklogDto> {
+  if let Some(cached) = data.dashboard_cache.job_backlog.get() {
+    return Some(cached);
+  }
+
+  let (operations_pending, operations_max_pending) = data.operations.backlog();
+  let backlog = JobBacklogDto {
+    operations_pending,
+    operations_max_pending,
+    emails_queued: data.mailer.queued(),
+    images_queued: data.images.queued(),
+    generated_at: Utc::now(),
+  };
+  data.dashboard_cache.job_backlog.set(backlog.clone());
+  Some(backlog)
+}
+
+document!(get_dashboard);
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/dashboard",
+    tag = "Admin",
+    responses( 
+        (
+            status=200, 
+            description= "Get dashboard successfully", 
+            body= BaseResDto<DashboardResDto>
+        ),
+        (
+            status=401, 
+            description= "Unauthorized", 
+            body= Status
+        ),
+        (
+            status=403, 
+            description= "Permission denied", 
+            body= Status
+        ),
+    )
+)]
+pub async fn get_dashboard(data: web::Data<AppState>) -> impl Responder {
+  let (user_stats, pool_health, recent_activity, recent_logins) = futures::join!(
+    load_user_stats(&data),
+    load_pool_health(&data),
+    load_recent_activity(&data),
+    load_recent_logins(&data)
+  );
+
+  HttpResponse::Ok().json(Status::success_with_data(DashboardResDto {
+    user_stats,
+    pool_health,
+    recent_activity,
+    recent_logins,
+    rate_limit_hits: load_rate_limit_hits(&data),
+    job_backlog: load_job_backlog(&data),
+  }))
+}
+
+document!(get_pools);
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/pools",
+    tag = "Admin",
+    responses( 
+        (
+            status=200, 
+            description= "Get connection pool stats successfully", 
+            body= BaseResDto<Vec<PoolStatsDto>>
+        ),
+        (
+            status=401, 
+            description= "Unauthorized", 
+            body= Status
+        ),
+        (
+            status=403, 
+            description= "Permission denied", 
+            body= Status
+        ),
+    )
+)]
+pub async fn get_pools(data: web::Data<AppState>) -> impl Responder {
+  let pools: Vec<PoolStatsDto> = data
+    .db_manager
+    .stats()
+    .into_iter()
+    .map(PoolStatsDto::from)
+    .collect();
+  HttpResponse::Ok().json(Status::success_with_data(pools))
+}
+
+document!(get_scheduler);
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/scheduler",
+    tag = "Admin",
+    responses( 
+        (
+            status=200, 
+            description= "Get scheduled tasks and their last run successfully", 
+            body= BaseResDto<Vec<ScheduledTaskDto>>
+        ),
+        (
+            status=401, 
+            description= "Unauthorized", 
+            body= Status
+        ),
+        (
+            status=403, 
+            description= "Permission denied", 
+            body= Status
+        ),
+    )
+)]
+pub async fn get_scheduler(data: web::Data<AppState>) -> impl Responder {
+  let tasks: Vec<ScheduledTaskDto> = data
+    .scheduler
+    .reports()
+    .into_iter()
+    .map(ScheduledTaskDto::from)
+    .collect();
+  HttpResponse::Ok().json(Status::success_with_data(tasks))
+}
+
+const REPORT_RECENT_REGISTRATIONS: i32 = 20;
+
+async fn load_user_report(data: &AppState) -> anyhow::Result<Report> {
+  let (by_role, registrations, recent) = AdminRepo::new(data)
+    .get_user_report(REPORT_RECENT_REGISTRATIONS)
+    .await?;
+
+  let metric = |name: &str, value: i32| {
+    vec![
+      ReportCell::Text(name.to_string()),
+      ReportCell::Number(value as f64),
+    ]
+  };
+  let sections = vec![
+    ReportSection {
+      title: "Summary".to_string(),
+      columns: vec!["Metric".to_string(), "Value".to_string()],
+      rows: vec![
+        metric("Users", by_role.iter().map(|r| r.total).sum()),
+        metric("Registered in the last 7 days", registrations.last_7_days),
+        metric("Registered in the last 30 days", registrations.last_30_days),
+      ],
+    },
+    ReportSection {
+      title: "Users per role".to_string(),
+      columns: vec!["Role".to_string(), "Users".to_string()],
+      rows: by_role
+        .into_iter()
+        .map(|r| vec![ReportCell::Text(r.role), ReportCell::Number(r.total as f64)])
+        .collect(),
+    },
+    ReportSection {
+      title: "Recent registrations".to_string(),
+      columns: [
+        "Id",
+        "User name",
+        "Name",
+        "Email",
+        "Role",
+        "Active",
+        "Registered",
+      ]
+      .map(String::from)
+      .to_vec(),
+      rows: recent
+        .into_iter()
+        .map(|user| {
+          vec![
+            ReportCell::Number(user.id as f64),
+            ReportCell::Text(user.user_name),
+            ReportCell::Text(user.name),
+            ReportCell::Text(user.email),
+            ReportCell::Text(user.role.into()),
+            ReportCell::Text(if user.is_active { "yes" } else { "no" }.to_string()),
+            ReportCell::DateTime(user.created_at),
+          ]
+        })
+        .collect(),
+    },
+  ];
+  Ok(Report {
+    title: "User report".to_string(),
+    generated_at: Utc::now(),
+    sections,
+  })
+}
+
+fn report_file_name(report: &Report, format: ReportFormat) -> String {
+  format!(
+    "users-report-{}.{}",
+    report.generated_at.format("%Y%m%d-%H%M"),
+    format.extension()
+  )
+}
+
+document!(get_user_report);
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/reports/users",
+    tag = "Admin",
+    params(UserReportReqDto),
+    responses( 
+        (
+            status=200, 
+            description= "User and role summary with recent registrations, as a file download", 
+            content(
+                (Vec<u8> = "application/pdf"),
+                (Vec<u8> = "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet")
+            )
+        ),
+        (
+            status=400, 
+            description= "Unknown format", 
+            body= Status
+        ),
+        (
+            status=401, 
+            description= "Unauthorized", 
+            body= Status
+        ),
+        (
+            status=403, 
+            description= "Permission denied", 
+            body= Status
+        ),
+        (
+            status=500, 
+            description= "Internal Server Error", 
+            body= Status 
+        ),
+    )
+)]
+pub async fn get_user_report(
+  query: ValidatedQuery<UserReportReqDto>,
+  data: web::Data<AppState>,
+) -> impl Responder {
+  let format = query.format;
+  let report = match load_user_report(&data).await {
+    Ok(report) => report,
+    Err(e) => {
+      return Status::server_error(format!("Failed to load user report: {}", e))
+        .or_query_timeout(&e)
+        .into_http_response();
+    }
+  };
+  let file_name = report_file_name(&report, format);
+
+  match web::block(move || reports::render(&report, format)).await {
+    Ok(Ok(bytes)) => HttpResponse::Ok()
+      .content_type(format.content_type())
+      .insert_header(ContentDisposition {
+        disposition: DispositionType::Attachment,
+        parameters: vec![DispositionParam::Filename(file_name)],
+      })
+      .body(bytes),
+    Ok(Err(e)) => {
+      Status::server_error(format!("Failed to render user report: {}", e)).into_http_response()
+    }
+    Err(e) => {
+      Status::server_error(format!("Failed to render user report: {}", e)).into_http_response()
+    }
+  }
+}
+
+// Loads, renders and stores the report, run as an operation
+async fn build_user_report(
+  data: web::Data<AppState>,
+  format: ReportFormat,
+  progress: OperationProgress,
+) -> anyhow::Result<OperationResult> {
+  let report = load_user_report(&data).await?;
+  progress.set(40);
+  let file_name = report_file_name(&report, format);
+  let bytes = web::block(move || reports::render(&report, format)).await??;
+  progress.set(80);
+  let key = format!("operations/{}.{}", progress.id(), format.extension());
+  let object = StoredObject {
+    content_type: format.content_type().to_string(),
+    bytes: bytes.into(),
+  };
+  data.storage.put(&key, object).await?;
+  Ok(OperationResult { key, file_name })
+}
+
+document!(start_user_report);
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/reports/users",
+    tag = "Admin",
+    params(UserReportReqDto),
+    responses( 
+        (
+            status=202, 
+            description= "Report queued, poll the operation at `Location` for its `result_url`", 
+            body= BaseResDto<OperationResDto>
+        ),
+        (
+            status=400, 
+            description= "Unknown format", 
+            body= Status
+        ),
+        (
+            status=401, 
+            description= "Unauthorized", 
+            body= Status
+        ),
+        (
+            status=403, 
+            description= "Permission denied", 
+            body= Status
+        ),
+        (
+            status=503, 
+            description= "`operations.max_pending` operations are already queued or running", 
+            body= Status
+        ),
+    )
+)]
+pub async fn start_user_report(
+  query: ValidatedQuery<UserReportReqDto>,
+  req: HttpRequest,
+  current_user: Authenticated,
+  data: web::Data<AppState>,
+) -> impl Responder {
+  let format = query.format;
+  let state = data.clone();
+  let started = data
+    .operations
+    .start("user_report", current_user.id, move |progress| {
+      build_user_report(state, format, progress)
+    });
+  match started {
+    Ok(operation) => {
+      let prefix = version_prefix(&req);
+      HttpResponse::Accepted()
+        .insert_header((LOCATION, format!("{}/operations/{}", prefix, operation.id)))
+        .json(Status::accepted_with_data(OperationResDto::new(
+          operation, &prefix,
+        )))
+    }
+    Err(e) => Status::busy(e.to_string()).into_http_response(),
+  }
+}
+
+// The user with the roles and permissions a token for them carries, `None` when missing
+async fn load_user_access(
+  data: &AppState,
+  user_id: i32,
+) -> Result<Option<(UserDto, Vec<String>)>, Status> {
+  let user = match data.user_repo().get_by_id(user_id).await {
+    Ok(Some(user)) => user,
+    Ok(None) => return Ok(None),
+    Err(e) => return Err(Status::server_error(e.to_string()).or_query_timeout(&e)),
+  };
+  let role_names = data
+    .role_repo()
+    .get_user_role_names(user_id)
+    .await
+    .map_err(|e| Status::server_error(e.to_string()).or_query_timeout(&e))?;
+  let permissions = data
+    .permission_repo()
+    .get_user_permissions(user_id)
+    .await
+    .map_err(|e| Status::server_error(e.to_string()).or_query_timeout(&e))?;
+  Ok(Some((
+    UserDto::from(user).with_roles(role_names),
+    permissions,
+  )))
+}
+
+fn auth_cookie(data: &AppState, token: &str, minutes: usize) -> Cookie<'static> {
+  Cookie::build(data.config.cookie.name.clone(), token.to_string())
+    .path("/")
+    .http_only(true)
+    .expires(OffsetDateTime::now_utc() + Duration::minutes(minutes as i64))
+    .finish()
+}
+
+document!(impersonate);
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/impersonate",
+    tag = "Admin",
+    request_body(
+        content = ImpersonateReqDto,
+        description = "",
+        example = json!({ "user_id": 2, "reason": "Ticket #123, orders page is empty" })),
+    responses(
+        (
+            status=200,
+            description= "Short-lived token acting as the user, also set as the auth cookie",
+            body= BaseResDto<ImpersonationResDto>
+        ),
+        (
+            status=400,
+            description= "Validation Errors, or the user can't be impersonated",
+            body= Status
+        ),
+        (
+            status=403,
+            description= "Permission denied",
+            body= Status
+        ),
+        (
+            status=404,
+            description= "User not found",
+            body= Status
+        ),
+    )
+)]
+pub async fn impersonate(
+  req: ValidatedJson<ImpersonateReqDto>,
+  current_user: Authenticated,
+  http_req: HttpRequest,
+  data: web::Data<AppState>,
+) -> impl Responder {
+  if req.user_id == current_user.id {
+    return Status::bad_request(StatusMessage::CannotImpersonate).into_http_response();
+  }
+  let (user, permissions) = match load_user_access(&data, req.user_id).await {
+    Ok(Some(access)) => access,
+    Ok(None) => {
+      return Status::not_found(StatusMessage::NotFound("User".into())).into_http_response();
+    }
+    Err(status) => return status.into_http_response(),
+  };
+  // Acting as another admin would only hide who did what
+  if !user.is_active || user.has_role(UserRole::Admin.to_str()) {
+    return Status::bad_request(StatusMessage::CannotImpersonate).into_http_response();
+  }
+
+  let jwt = &data.config.jwt;
+  // Never outlives the admin's own token
+  let expires_at = Utc::now() + chrono::Duration::minutes(jwt.impersonation_minutes as i64);
+  let expires_at = match current_user
+    .expires_at
+    .and_then(|exp| DateTime::from_timestamp(exp as i64, 0))
+  {
+    Some(admin_expires_at) => expires_at.min(admin_expires_at),
+    None => expires_at,
+  };
+  let token = match JwtUtil::from_state(&data)
+    .bound_to_device(&http_req)
+    .create_impersonation_token(
+      current_user.id,
+      &user,
+      &permissions,
+      current_user.expires_at,
+    ) {
+    Ok(token) => token,
+    Err(e) => return Status::server_error(e.to_string()).into_http_response(),
+  };
+  let mut entry = AuditLogEntity::new(
+    AuditAction::Impersonate,
+    Some(current_user.id),
+    format!("user:{}", user.id),
+  );
+  let mut event = SecurityEvent::new(SecurityEventType::ImpersonationStarted)
+    .actor(Some(current_user.id))
+    .user_name(&user.user_name)
+    .target(format!("user:{}", user.id));
+  if let Some(reason) = &req.reason {
+    entry = entry.with_details(format!("reason={}", reason));
+    event = event.details(format!("reason={}", reason));
+  }
+  AuditRepo::new(&data)
+    .record(entry.with_request(&http_req))
+    .await;
+  data.security_log.emit(event.with_request(&http_req));
+
+  let minutes = (expires_at - Utc::now()).num_minutes().max(0) as usize;
+  HttpResponse::Ok()
+    .cookie(auth_cookie(&data, &token, minutes))
+    .json(Status::success_with_data(ImpersonationResDto {
+      token,
+      user_id: user.id,
+      expires_at,
+    }))
+}
+
+document!(stop_impersonation);
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/stop_impersonation",
+    tag = "Admin",
+    responses(
+        (
+            status=200,
+            description= "The admin's own token again, also set as the auth cookie",
+            body= BaseResDto<LoginResDto>
+        ),
+        (
+            status=400,
+            description= "The session is not impersonating anyone",
+            body= Status
+        ),
+        (
+            status=401,
+            description= "Unauthorized, or the admin's own session has ended",
+            body= Status
+        ),
+    )
+)]
+pub async fn stop_impersonation(
+  current_user: Authenticated,
+  http_req: HttpRequest,
+  data: web::Data<AppState>,
+) -> impl Responder {
+  let Some(impersonation) = http_req.extensions().get::<Impersonation>().copied() else {
+    return Status::bad_request(StatusMessage::NotImpersonating).into_http_response();
+  };
+  let admin_id = impersonation.admin_id;
+  // The admin gets back the rest of the session they started from, not a fresh one, so an
+  // impersonation token can't be traded for a longer admin session
+  let remaining_minutes = impersonation
+    .admin_expires_at
+    .and_then(|exp| DateTime::from_timestamp(exp as i64, 0))
+    .map(|admin_expires_at| (admin_expires_at - Utc::now()).num_minutes())
+    .filter(|minutes| *minutes > 0);
+  let Some(remaining_minutes) = remaining_minutes else {
+    return Status::unauthorized(StatusMessage::AdminSessionExpired).into_http_response();
+  };
+  let (admin, permissions) = match load_user_access(&data, admin_id).await {
+    Ok(Some(access)) => access,
+    Ok(None) => {
+      return Status::not_found(StatusMessage::NotFound("User".into())).into_http_response();
+    }
+    Err(status) => return status.into_http_response(),
+  };
+  if !admin.is_active || !admin.has_role(UserRole::Admin.to_str()) {
+    return Status::unauthorized(StatusMessage::AdminSessionExpired).into_http_response();
+  }
+
+  let token = match JwtUtil::from_state(&data)
+    .bound_to_device(&http_req)
+    .create_token_with_expiration(&admin, &permissions, remaining_minutes)
+  {
+    Ok(token) => token,
+    Err(e) => return Status::server_error(e.to_string()).into_http_response(),
+  };
+  AuditRepo::new(&data)
+    .record(
+      AuditLogEntity::new(
+        AuditAction::StopImpersonation,
+        Some(admin_id),
+        format!("user:{}", current_user.id),
+      )
+      .with_request(&http_req),
+    )
+    .await;
+  data.security_log.emit(
+    SecurityEvent::new(SecurityEventType::ImpersonationStopped)
+      .actor(Some(admin_id))
+      .user_name(&current_user.user_name)
+      .target(format!("user:{}", current_user.id))
+      .with_request(&http_req),
+  );
+
+  HttpResponse::Ok()
+    .cookie(auth_cookie(&data, &token, remaining_minutes as usize))
+    .json(Status::success_with_data(LoginResDto { token }))
+}
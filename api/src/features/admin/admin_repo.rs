@@ -0,0 +1,98 @@
+use crate::{
+  app_state::AppState,
+  db::{DbConnection, REPORTING_DATABASE, RowExt, SqlRepo},
+  features::{
+    admin::admin_dto::{RegistrationCountsDto, RoleCountDto},
+    users::user_entity::User,
+  },
+};
+
+use anyhow::Result;
+use domner_tech_sql_client::{CommandType, UnifiedToSql};
+
+pub struct AdminRepo<'a> {
+  pub app_state: &'a AppState,
+}
+
+impl<'a> AdminRepo<'a> {
+  pub fn new(app_state: &'a AppState) -> Self {
+    Self { app_state }
+  }
+
+  async fn get_client(&self) -> DbConnection {
+    match self
+      .app_state
+      .db_manager
+      .get_client(
+        &self
+          .app_state
+          .config
+          .database
+          .connection(REPORTING_DATABASE)
+          .pool_name,
+      )
+      .await
+    {
+      Ok(client) => client,
+      Err(e) => panic!("Failed to get DB client: {}", e),
+    }
+  }
+
+  pub async fn get_user_role_counts(&mut self) -> Result<Vec<RoleCountDto>> {
+    let mut client_pool = self.get_client().await;
+
+    let counts = SqlRepo::execute_command_query(
+      &mut client_pool,
+      "[dbo].[select_user_role_counts]",
+      &[],
+      CommandType::StoreProcedure,
+      |row| {
+        Ok(RoleCountDto {
+          role: row.column("role")?,
+          total: row.column("total")?,
+        })
+      },
+    )
+    .await?;
+    Ok(counts)
+  }
+
+  /// Role counts, registration counts and the `recent_count` newest users of the user report,
+  /// read in one round trip.
+  pub async fn get_user_report(
+    &mut self,
+    recent_count: i32,
+  ) -> Result<(Vec<RoleCountDto>, RegistrationCountsDto, Vec<User>)> {
+    let mut client_pool = self.get_client().await;
+
+    let params: Vec<&dyn UnifiedToSql> = vec![&recent_count];
+    let mut sets = SqlRepo::execute_command_multi_query(
+      &mut client_pool,
+      "[dbo].[select_user_report]",
+      &params,
+      CommandType::StoreProcedure,
+    )
+    .await?;
+    let by_role = sets.next_with(|row| {
+      Ok(RoleCountDto {
+        role: row.column("role")?,
+        total: row.column("total")?,
+      })
+    })?;
+    let registrations = sets
+      .next_with(|row| {
+        Ok(RegistrationCountsDto {
+          last_7_days: row.column("last_7_days")?,
+          last_30_days: row.column("last_30_days")?,
+        })
+      })?
+      .into_iter()
+      .next()
+      .unwrap_or(RegistrationCountsDto {
+        last_7_days: 0,
+        last_30_days: 0,
+      });
+    let recent = sets.next_as::<User>()?;
+    Ok((by_role, registrations, recent))
+  }
+}
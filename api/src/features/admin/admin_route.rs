@@ -0,0 +1,70 @@
+use actix_web::{Scope, web};
+
+use crate::{
+  features::{
+    admin::admin_handler::{
+      get_dashboard, get_pools, get_scheduler, get_user_report, impersonate, start_user_report,
+      stop_impersonation,
+    },
+    login_history::login_history_handler::get_login_history,
+    users::user_entity::UserRole,
+  },
+  middleware::auth::RequireAuth,
+};
+
+pub fn admin_routes() -> Scope {
+  web::scope("/admin")
+    .route(
+      "/dashboard",
+      web::get()
+        .to(get_dashboard)
+        .wrap(RequireAuth::allow_roles(vec![UserRole::Admin])),
+    )
+    .route(
+      "/pools",
+      web::get()
+        .to(get_pools)
+        .wrap(RequireAuth::allow_roles(vec![UserRole::Admin])),
+    )
+    .route(
+      "/scheduler",
+      web::get()
+        .to(get_scheduler)
+        .wrap(RequireAuth::allow_roles(vec![UserRole::Admin])),
+    )
+    .route(
+      "/reports/users",
+      web::get()
+        .to(get_user_report)
+        .wrap(RequireAuth::allow_roles(vec![UserRole::Admin])),
+    )
+    .route(
+      "/reports/users",
+      web::post()
+        .to(start_user_report)
+        .wrap(RequireAuth::allow_roles(vec![UserRole::Admin])),
+    )
+    .route(
+      "/logins",
+      web::get()
+        .to(get_login_history)
+        .wrap(RequireAuth::allow_roles(vec![UserRole::Admin])),
+    )
+    .route(
+      "/impersonate",
+      web::post()
+        .to(impersonate)
+        .wrap(RequireAuth::allow_roles(vec![UserRole::Admin])),
+    )
+    // Called with the impersonation token, so by whoever is being impersonated
+    .route(
+      "/stop_impersonation",
+      web::post()
+        .to(stop_impersonation)
+        .wrap(RequireAuth::allow_roles(vec![
+          UserRole::User,
+          UserRole::Moderator,
+          UserRole::Admin,
+        ])),
+    )
+}
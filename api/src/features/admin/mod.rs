@@ -0,0 +1,4 @@
+pub mod admin_dto;
+pub mod admin_handler;
+pub mod admin_repo;
+pub mod admin_route;
@@ -0,0 +1,56 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::Validate;
+
+use crate::{dto::paged_res_dto::MAX_PAGE_SIZE, features::audit::audit_entity::AuditLogEntity};
+
+#[derive(Deserialize, Serialize, Clone, ToSchema)]
+pub struct AuditLogDto {
+  pub id: i32,
+  pub actor_id: Option<i32>,
+  pub action: String,
+  pub target: Option<String>,
+  pub details: Option<String>,
+  pub request_id: Option<String>,
+  pub created_at: DateTime<Utc>,
+}
+
+impl From<AuditLogEntity> for AuditLogDto {
+  fn from(value: AuditLogEntity) -> Self {
+    Self {
+      id: value.id,
+      actor_id: value.actor_id,
+      action: value.action,
+      target: value.target,
+      details: value.details,
+      request_id: value.request_id,
+      created_at: value.created_at,
+    }
+  }
+}
+
+// --- Request Dto --- //
+
+#[derive(Deserialize, Serialize, Debug, ToSchema, Validate)]
+pub struct GetAuditLogsReqDto {
+  pub actor_id: Option<i32>,
+  pub action: Option<String>,
+  pub target: Option<String>,
+  #[serde(default = "default_page")]
+  #[validate(range(min = 1))]
+  pub page: i32,
+  #[serde(default = "default_page_size")]
+  #[validate(range(min = 1, max = MAX_PAGE_SIZE))]
+  pub page_size: i32,
+}
+
+// Default value for page
+fn default_page() -> i32 {
+  1
+}
+
+// Default value for page_size
+fn default_page_size() -> i32 {
+  20
+}
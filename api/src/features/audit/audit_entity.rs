@@ -0,0 +1,122 @@
+use actix_web::{HttpMessage, HttpRequest};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use domner_tech_sql_client::pool_manager::DbRow;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  db::{FromRow, RowExt},
+  middleware::{auth::Impersonation, request_id::RequestId},
+};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum AuditAction {
+  Login,
+  LoginFailed,
+  CreateRole,
+  UpdateRole,
+  DeleteRole,
+  AssignUserRole,
+  CreatePermission,
+  UpdatePermission,
+  DeletePermission,
+  AssignRolePermission,
+  RevokeRolePermission,
+  UpdateUser,
+  ActivateUser,
+  DeactivateUser,
+  RequestEmailChange,
+  ChangeEmail,
+  Impersonate,
+  StopImpersonation,
+  ChangePassword,
+  RequestLoginCode,
+  RequestPasswordReset,
+  ResetPassword,
+}
+
+impl AuditAction {
+  pub fn to_str(&self) -> &str {
+    match self {
+      AuditAction::Login => "login",
+      AuditAction::LoginFailed => "login_failed",
+      AuditAction::CreateRole => "create_role",
+      AuditAction::UpdateRole => "update_role",
+      AuditAction::DeleteRole => "delete_role",
+      AuditAction::AssignUserRole => "assign_user_role",
+      AuditAction::CreatePermission => "create_permission",
+      AuditAction::UpdatePermission => "update_permission",
+      AuditAction::DeletePermission => "delete_permission",
+      AuditAction::AssignRolePermission => "assign_role_permission",
+      AuditAction::RevokeRolePermission => "revoke_role_permission",
+      AuditAction::UpdateUser => "update_user",
+      AuditAction::ActivateUser => "activate_user",
+      AuditAction::DeactivateUser => "deactivate_user",
+      AuditAction::RequestEmailChange => "request_email_change",
+      AuditAction::ChangeEmail => "change_email",
+      AuditAction::Impersonate => "impersonate",
+      AuditAction::StopImpersonation => "stop_impersonation",
+      AuditAction::ChangePassword => "change_password",
+      AuditAction::RequestLoginCode => "request_login_code",
+      AuditAction::RequestPasswordReset => "request_password_reset",
+      AuditAction::ResetPassword => "reset_password",
+    }
+  }
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct AuditLogEntity {
+  pub id: i32,
+  pub actor_id: Option<i32>,
+  pub action: String,
+  pub target: Option<String>,
+  pub details: Option<String>,
+  pub request_id: Option<String>,
+  pub created_at: DateTime<Utc>,
+}
+
+impl AuditLogEntity {
+  pub fn new(action: AuditAction, actor_id: Option<i32>, target: impl Into<String>) -> Self {
+    Self {
+      id: 0,
+      actor_id,
+      action: action.to_str().to_string(),
+      target: Some(target.into()),
+      details: None,
+      request_id: None,
+      created_at: Utc::now(),
+    }
+  }
+
+  pub fn with_details(mut self, details: impl Into<String>) -> Self {
+    self.details = Some(details.into());
+    self
+  }
+
+  // Also names the admin behind an impersonated request, whatever the action
+  pub fn with_request(mut self, req: &HttpRequest) -> Self {
+    self.request_id = RequestId::of(req);
+    if let Some(impersonation) = req.extensions().get::<Impersonation>() {
+      let by = format!("impersonated_by={}", impersonation.admin_id);
+      self.details = Some(match self.details {
+        Some(details) => format!("{}; {}", details, by),
+        None => by,
+      });
+    }
+    self
+  }
+}
+
+impl FromRow for AuditLogEntity {
+  fn from_row(row: &DbRow<'_>) -> Result<Self> {
+    Ok(Self {
+      id: row.column("id")?,
+      actor_id: row.nullable("actor_id")?,
+      action: row.column("action")?,
+      target: row.nullable("target")?,
+      details: row.nullable("details")?,
+      request_id: row.nullable("request_id")?,
+      created_at: row.column("created_at")?,
+    })
+  }
+}
@@ -0,0 +1,67 @@
+use actix_web::{HttpResponse, Responder, web};
+
+use crate::{
+  app_state::AppState,
+  document,
+  dto::{
+    base_res_dto::{BaseResDto, Status},
+    paged_res_dto::PagedResDto,
+    validated_json::ValidatedJson,
+  },
+  features::audit::{
+    audit_dto::{AuditLogDto, GetAuditLogsReqDto},
+    audit_repo::AuditRepo,
+  },
+};
+
+document!(get_audit_logs);
+#[utoipa::path(
+    post,
+    path = "/api/v1/audit/logs",
+    tag = "Audit",
+    request_body(
+        content = GetAuditLogsReqDto,
+        description = "",
+        example = json!({
+          "actor_id": 1,
+          "action": "create_role",
+          "page": 1,
+          "page_size": 20
+        })),
+    responses( 
+        (
+            status=200, 
+            description= "Get audit logs successfully", 
+            body= BaseResDto<PagedResDto<AuditLogDto>>
+        ),
+        (
+            status=400, 
+            description= "Validation Errors", 
+            body= Status
+        ),
+        (
+            status=500, 
+            description= "Internal Server Error", 
+            body= Status 
+        ),
+    )
+)]
+pub async fn get_audit_logs(
+  req: ValidatedJson<GetAuditLogsReqDto>,
+  data: web::Data<AppState>,
+) -> impl Responder {
+  let mut repo = AuditRepo::new(&data);
+  match repo.get_paged(&req).await {
+    Ok((logs, total_count)) => {
+      HttpResponse::Ok().json(Status::success_with_data(PagedResDto::new(
+        logs.into_iter().map(AuditLogDto::from).collect(),
+        req.page,
+        req.page_size,
+        total_count,
+      )))
+    }
+    Err(e) => Status::bad_request(format!("Failed to get audit logs: {}", e))
+      .or_query_timeout(&e)
+      .into_http_response(),
+  }
+}
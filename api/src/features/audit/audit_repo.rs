@@ -0,0 +1,87 @@
+use crate::{
+  app_state::AppState,
+  db::{AUDIT_DATABASE, DbConnection, SqlRepo},
+  features::audit::{audit_dto::GetAuditLogsReqDto, audit_entity::AuditLogEntity},
+};
+
+use anyhow::Result;
+use domner_tech_sql_client::{CommandType, UnifiedToSql};
+
+pub struct AuditRepo<'a> {
+  pub app_state: &'a AppState,
+}
+
+impl<'a> AuditRepo<'a> {
+  pub fn new(app_state: &'a AppState) -> Self {
+    Self { app_state }
+  }
+
+  // Fails instead of panicking, so `record` never takes the audited request down with it
+  async fn get_client(&self) -> Result<DbConnection> {
+    self
+      .app_state
+      .db_manager
+      .get_client(
+        &self
+          .app_state
+          .config
+          .database
+          .connection(AUDIT_DATABASE)
+          .pool_name,
+      )
+      .await
+  }
+
+  pub async fn create(&mut self, entry: &AuditLogEntity) -> Result<u64> {
+    let mut client_pool = self.get_client().await?;
+
+    // Empty values are stored as NULL by the proc
+    let actor_id = entry.actor_id.unwrap_or_default();
+    let target = entry.target.clone().unwrap_or_default();
+    let details = entry.details.clone().unwrap_or_default();
+    let request_id = entry.request_id.clone().unwrap_or_default();
+    let params: Vec<&dyn UnifiedToSql> =
+      vec![&actor_id, &entry.action, &target, &details, &request_id];
+    let result = SqlRepo::execute_command_none_query(
+      &mut client_pool,
+      "[dbo].[create_audit_log]",
+      &params,
+      CommandType::StoreProcedure,
+    )
+    .await?;
+    Ok(result)
+  }
+
+  // Audit failures are logged but never fail the audited operation
+  pub async fn record(&mut self, entry: AuditLogEntity) {
+    if let Err(e) = self.create(&entry).await {
+      eprintln!("Failed to write audit log '{}': {}", entry.action, e);
+    }
+  }
+
+  pub async fn get_paged(
+    &mut self,
+    filter: &GetAuditLogsReqDto,
+  ) -> Result<(Vec<AuditLogEntity>, i32)> {
+    let mut client_pool = self.get_client().await?;
+
+    // 0 and empty strings mean "no filter"
+    let actor_id = filter.actor_id.unwrap_or_default();
+    let action = filter.action.clone().unwrap_or_default();
+    let target = filter.target.clone().unwrap_or_default();
+    let params: Vec<&dyn UnifiedToSql> =
+      vec![&actor_id, &action, &target, &filter.page, &filter.page_size];
+    SqlRepo::execute_paged_query::<AuditLogEntity>(
+      &mut client_pool,
+      "[dbo].[select_audit_logs_paged]",
+      &params,
+      CommandType::StoreProcedure,
+      // Same filter as the proc
+      "SELECT COUNT(*) AS value FROM [dbo].[audit_logs] \
+       WHERE (@P1 = 0 OR actor_id = @P1) AND (@P2 = '' OR action = @P2) \
+       AND (@P3 = '' OR target = @P3)",
+      &params[..3],
+    )
+    .await
+  }
+}
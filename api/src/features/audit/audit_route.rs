@@ -0,0 +1,15 @@
+use actix_web::{Scope, web};
+
+use crate::{
+  features::{audit::audit_handler::get_audit_logs, users::user_entity::UserRole},
+  middleware::auth::RequireAuth,
+};
+
+pub fn audit_routes() -> Scope {
+  web::scope("/audit").route(
+    "/logs",
+    web::post()
+      .to(get_audit_logs)
+      .wrap(RequireAuth::allow_roles(vec![UserRole::Admin])),
+  )
+}
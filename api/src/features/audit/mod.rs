@@ -0,0 +1,5 @@
+pub mod audit_dto;
+pub mod audit_entity;
+pub mod audit_handler;
+pub mod audit_repo;
+pub mod audit_route;
@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
+use crate::utils::custom_claims::CustomClaims;
+
 #[derive(Serialize, Deserialize, Clone, ToSchema)]
 pub struct LoginResDto {
   pub token: String,
@@ -20,6 +22,25 @@ pub struct Claims {
   pub exp: usize,  // Expiration time (Unix timestamp)
   pub iss: String, // Issuer
   pub aud: String, // Audience
+  #[serde(default)]
+  pub roles: Vec<String>, // Role names at login, resolved from the roles table
+  #[serde(default)]
+  pub permissions: Vec<String>, // Effective permissions at login, e.g. "user:read"
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub act_as: Option<i32>, // User the admin in `sub` is impersonating, see /admin/impersonate
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub act_exp: Option<usize>, // Expiry of the admin's own token the impersonation started from
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub dfp: Option<String>, // Fingerprint of the device the token was issued to, see `jwt.device_binding`
+  #[serde(flatten)]
+  pub custom: CustomClaims, // `jwt.static_claims` and whatever the claims enricher added
+}
+
+impl Claims {
+  /// The user requests are made as, the impersonated one when `act_as` is set.
+  pub fn user_id(&self) -> i32 {
+    self.act_as.unwrap_or(self.sub)
+  }
 }
 
 // --- Request Dto --- //
@@ -28,3 +49,15 @@ pub struct LoginReqDto {
   pub user_name: String,
   pub password: String,
 }
+
+#[derive(Deserialize, Serialize, Clone, ToSchema)]
+pub struct RequestOtpReqDto {
+  pub user_name: String,
+}
+
+#[derive(Deserialize, Serialize, Clone, ToSchema)]
+pub struct LoginOtpReqDto {
+  pub user_name: String,
+  /// The code emailed by `/auth/request_otp`
+  pub code: String,
+}
@@ -1,26 +1,49 @@
+use std::{net::IpAddr, sync::LazyLock};
+
 use actix_web::{
-  HttpResponse, Responder,
+  HttpRequest, HttpResponse, Responder,
   cookie::{
     self, Cookie,
     time::{Duration, OffsetDateTime},
   },
+  http::header::{HeaderValue, RETRY_AFTER},
   web,
 };
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use chrono::Utc;
 
 use crate::{
   app_state::AppState,
-  dto::base_res_dto::Status,
+  document,
+  dto::{
+    base_res_dto::{BaseResDto, Status},
+    created_res_dto::CreatedResDto,
+    versioned_dto::VersionedJson,
+  },
   error::StatusMessage,
+  events::DomainEvent,
   features::{
-    auth::auth_dto::{LoginReqDto, LoginResDto},
+    audit::{
+      audit_entity::{AuditAction, AuditLogEntity},
+      audit_repo::AuditRepo,
+    },
+    auth::auth_dto::{LoginOtpReqDto, LoginReqDto, LoginResDto, RequestOtpReqDto},
+    login_history::{
+      login_history_entity::{LoginAttemptEntity, LoginOutcome},
+      login_history_repo::LoginHistoryRepo,
+    },
     users::{
       user_dto::{UserDto, UserRegisterReqDto},
-      user_repo::UserRepo,
+      user_entity::User,
     },
   },
-  utils::{jwt_util::JwtUtil, password_hashing::PasswordHashing},
+  middleware::{rate_limit::client_ip, transaction::DbTransaction},
+  notifications::NotificationKind,
+  security_log::{SecurityEvent, SecurityEventType},
+  utils::{captcha, jwt_util::JwtUtil, mailer::EmailTemplate, password_hashing::PasswordHashing},
 };
 
+document!(register);
 #[utoipa::path(
     post,
     path = "/api/v1/auth/register",
@@ -30,21 +53,25 @@ use crate::{
         description = "Credentials to create account",
         example = json!(
             {
+                "schema_version": 1,
                 "name": "admin",
                 "user_name": "admin",
                 "password": "admin",
                 "email": "admin@gmail.com",
                 "role": "admin"
             })),
+    params(
+        ("X-Captcha-Token" = Option<String>, Header, description = "CAPTCHA response token, required when `captcha` is on")
+    ),
     responses( 
         (
             status=200, 
-            description= "Account created successfully", 
-            body= Status 
+            description= "Account created successfully, `data.id` is the new user's id", 
+            body= BaseResDto<CreatedResDto> 
         ),
         (
             status=400, 
-            description= "Validation Errors", 
+            description= "Validation Errors, or `CAPTCHA_REQUIRED` when the CAPTCHA is missing or failed", 
             body= Status
         ),
         (
@@ -60,26 +87,15 @@ use crate::{
     )
 )]
 pub async fn register(
-  user: web::Json<UserRegisterReqDto>,
+  user: VersionedJson<UserRegisterReqDto>,
+  tx: DbTransaction,
+  http_req: HttpRequest,
   data: web::Data<AppState>,
 ) -> impl Responder {
-  if user.password.is_empty()
-    || user.user_name.is_empty()
-    || user.name.is_empty()
-    || user.email.is_empty()
-  {
-    return HttpResponse::BadRequest().json(Status::uqique_constraint_voilation(
-      StatusMessage::WrongParams.to_str(),
-    ));
-  }
-
-  if user.user_name.len() > 150 {
-    return HttpResponse::BadRequest().json(Status::uqique_constraint_voilation(
-      StatusMessage::UserNameExeedMaxLength(150).to_str(),
-    ));
+  if let Err(status) = captcha::require_captcha(&data, &http_req).await {
+    return status.into_http_response();
   }
-
-  let mut repo = UserRepo::new(&data);
+  let mut repo = data.user_repo_in(&tx);
 
   if let Ok(Some(_)) = repo.get_by_username(&user.user_name).await {
     return HttpResponse::Conflict().json(Status::uqique_constraint_voilation(
@@ -87,12 +103,19 @@ pub async fn register(
     ));
   }
 
-  if let Err(e) = repo.create(&user).await {
-    return HttpResponse::BadRequest().json(Status::bad_request(format!("{}", e)));
-  }
-  HttpResponse::Ok().json(Status::success())
+  let id = match repo.create(&user).await {
+    Ok(id) => id,
+    Err(e) => {
+      return HttpResponse::BadRequest().json(Status::bad_request(format!("{}", e)));
+    }
+  };
+  data.events.publish(DomainEvent::UserRegistered {
+    user_name: user.user_name.clone(),
+  });
+  HttpResponse::Ok().json(Status::success_with_data(CreatedResDto { id }))
 }
 
+document!(login);
 #[utoipa::path(
     post,
     path = "/api/v1/auth/login",
@@ -105,15 +128,28 @@ pub async fn register(
                 "user_name": "nith",
                 "password": "nith"
             })),
+    params(
+        ("X-Captcha-Token" = Option<String>, Header, description = "CAPTCHA response token, required after `captcha.failed_logins_before_captcha` failed logins")
+    ),
     responses( 
         (
             status=200, 
             description= "Login successfully", 
             body= Status 
         ),
+        (
+            status=403, 
+            description= "Account is disabled", 
+            body= Status
+        ),
         (
             status=400, 
-            description= "Validation Errors", 
+            description= "Validation Errors, or `CAPTCHA_REQUIRED` when the CAPTCHA is missing or failed", 
+            body= Status
+        ),
+        (
+            status=429, 
+            description= "The client IP failed `security.login_tarpit_block_after` times, `RATE_LIMITED` with `Retry-After`", 
             body= Status
         ),
         (
@@ -125,34 +161,427 @@ pub async fn register(
 )]
 pub async fn login(
   user: web::Json<LoginReqDto>,
+  http_req: HttpRequest,
   data: web::Data<AppState>,
 ) -> impl Responder {
-  let mut repo = UserRepo::new(&data);
+  let mut repo = data.user_repo();
   if user.user_name.is_empty() || user.password.is_empty() {
     return HttpResponse::Unauthorized().json(Status::unauthorized(StatusMessage::Unauthorized));
   }
+  let ip = client_ip(&http_req, &data.runtime().rate_limit);
+  if let Some(res) = hold_in_tarpit(&data, &http_req, ip, &user.user_name).await {
+    return res;
+  }
+  // Checked before the password so guessing can't go on without solving it
+  if captcha::login_needs_captcha(&data, &user.user_name)
+    && let Err(status) = captcha::require_captcha(&data, &http_req).await
+  {
+    return status.into_http_response();
+  }
 
+  let mut actor_id = None;
   if let Ok(Some(db_user)) = repo.get_by_username(&user.user_name).await {
+    actor_id = Some(db_user.id);
     if PasswordHashing::verify_password(&user.password, &db_user.password) {
-      let jwt_util = JwtUtil::new(&data.config.jwt);
-      if let Ok(token) = jwt_util.create_token(&UserDto::from(db_user)) {
-        let now = OffsetDateTime::now_utc();
-        let expiration = now + Duration::minutes(data.config.jwt.expiration_minutes as i64);
-        let cookie = Cookie::build("auth", &token)
-          .path("/")
-          .http_only(true)
-          .expires(expiration)
-          .finish();
-        return HttpResponse::Ok()
-          .cookie(cookie)
-          .json(Status::success_with_data(LoginResDto { token }));
+      return sign_in(&data, &http_req, ip, db_user, None).await;
+    }
+  }
+
+  record_failed_login(&data, &http_req, ip, &user.user_name, actor_id, None).await;
+  HttpResponse::Ok().json(Status::unauthorized(StatusMessage::Unauthorized))
+}
+
+// Waits out the tarpit delay of the client IP, or answers 429 once it is blocked
+async fn hold_in_tarpit(
+  data: &AppState,
+  http_req: &HttpRequest,
+  ip: Option<IpAddr>,
+  user_name: &str,
+) -> Option<HttpResponse> {
+  let retry_after = data.login_tarpit.hold(ip).await.err()?;
+  data.security_log.emit(
+    SecurityEvent::new(SecurityEventType::LoginBlocked)
+      .user_name(user_name)
+      .with_request(http_req),
+  );
+  let mut res = Status::rate_limited(retry_after).into_http_response();
+  res
+    .headers_mut()
+    .insert(RETRY_AFTER, HeaderValue::from(retry_after));
+  Some(res)
+}
+
+// Issues the token and cookie of a user whose credentials were checked. `method` names the
+// way they signed in in the audit log and security events, `None` for a password.
+async fn sign_in(
+  data: &AppState,
+  http_req: &HttpRequest,
+  ip: Option<IpAddr>,
+  db_user: User,
+  method: Option<&str>,
+) -> HttpResponse {
+  let rate_limit = data.runtime().rate_limit.clone();
+  let actor_id = Some(db_user.id);
+  let user_name = db_user.user_name.clone();
+  if !db_user.is_active {
+    let details = match method {
+      Some(method) => format!("account disabled; {}", method),
+      None => "account disabled".to_string(),
+    };
+    AuditRepo::new(data)
+      .record(
+        AuditLogEntity::new(AuditAction::LoginFailed, actor_id, &user_name)
+          .with_details(details)
+          .with_request(http_req),
+      )
+      .await;
+    LoginHistoryRepo::new(data)
+      .record(
+        LoginAttemptEntity::new(&user_name, actor_id, LoginOutcome::AccountDisabled)
+          .with_request(http_req, &rate_limit),
+      )
+      .await;
+    data.security_log.emit(
+      SecurityEvent::new(SecurityEventType::LoginFailed)
+        .actor(actor_id)
+        .user_name(&user_name)
+        .details("account disabled")
+        .with_request(http_req),
+    );
+    return Status::account_disabled().into_http_response();
+  }
+  // Missing permissions only narrow what the token allows, so don't fail the login
+  let permissions = data
+    .permission_repo()
+    .get_user_permissions(db_user.id)
+    .await
+    .unwrap_or_else(|e| {
+      eprintln!("Failed to load permissions of user {}: {}", db_user.id, e);
+      Vec::new()
+    });
+  let role_names = data
+    .role_repo()
+    .get_user_role_names(db_user.id)
+    .await
+    .unwrap_or_default();
+  let jwt_util = JwtUtil::from_state(data).bound_to_device(http_req);
+  let user_dto = UserDto::from(db_user).with_roles(role_names);
+  let token = match jwt_util.create_token(&user_dto, &permissions) {
+    Ok(token) => token,
+    Err(e) => return Status::server_error(e.to_string()).into_http_response(),
+  };
+
+  let mut entry = AuditLogEntity::new(AuditAction::Login, actor_id, &user_name);
+  let mut event = SecurityEvent::new(SecurityEventType::LoginSucceeded)
+    .actor(actor_id)
+    .user_name(&user_name);
+  if let Some(method) = method {
+    entry = entry.with_details(method);
+    event = event.details(method);
+  }
+  AuditRepo::new(data)
+    .record(entry.with_request(http_req))
+    .await;
+  LoginHistoryRepo::new(data)
+    .record(
+      LoginAttemptEntity::new(&user_name, actor_id, LoginOutcome::Success)
+        .with_request(http_req, &rate_limit),
+    )
+    .await;
+  data.failed_logins.remove(&user_name.to_lowercase());
+  data.login_tarpit.clear(ip).await;
+  data.security_log.emit(event.with_request(http_req));
+
+  let now = OffsetDateTime::now_utc();
+  let expiration = now + Duration::minutes(data.config.jwt.expiration_minutes as i64);
+  let cookie = Cookie::build("auth", &token)
+    .path("/")
+    .http_only(true)
+    .expires(expiration)
+    .finish();
+  HttpResponse::Ok()
+    .cookie(cookie)
+    .json(Status::success_with_data(LoginResDto { token }))
+}
+
+// Counts a wrong password or code towards the CAPTCHA and the tarpit, and records it
+async fn record_failed_login(
+  data: &AppState,
+  http_req: &HttpRequest,
+  ip: Option<IpAddr>,
+  user_name: &str,
+  actor_id: Option<i32>,
+  method: Option<&str>,
+) {
+  let mut entry = AuditLogEntity::new(AuditAction::LoginFailed, actor_id, user_name);
+  if let Some(method) = method {
+    entry = entry.with_details(method);
+  }
+  AuditRepo::new(data)
+    .record(entry.with_request(http_req))
+    .await;
+  LoginHistoryRepo::new(data)
+    .record(
+      LoginAttemptEntity::new(user_name, actor_id, LoginOutcome::InvalidCredentials)
+        .with_request(http_req, &data.runtime().rate_limit),
+    )
+    .await;
+  captcha::record_failed_login(data, user_name);
+  data.login_tarpit.record_failure(ip).await;
+  let details = match method {
+    Some(method) => format!("invalid credentials; {}", method),
+    None => "invalid credentials".to_string(),
+  };
+  data.security_log.emit(
+    SecurityEvent::new(SecurityEventType::LoginFailed)
+      .actor(actor_id)
+      .user_name(user_name)
+      .details(details)
+      .with_request(http_req),
+  );
+}
+
+// Random digits, the leading ones may be zero
+fn generate_login_code(length: u32) -> String {
+  (0..length)
+    .map(|_| char::from(b'0' + (OsRng.next_u32() % 10) as u8))
+    .collect()
+}
+
+// Checked against when there is no code to verify, so an unknown user or a missing code takes as
+// long to answer as a wrong code
+static DUMMY_CODE_HASH: LazyLock<String> =
+  LazyLock::new(|| PasswordHashing::hash_password("000000").unwrap_or_default());
+
+document!(request_otp);
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/request_otp",
+    tag = "Authentication",
+    params(
+        ("X-Captcha-Token" = Option<String>, Header, description = "CAPTCHA response token, required after `captcha.failed_logins_before_captcha` failed logins")
+    ),
+    request_body(
+        content = RequestOtpReqDto,
+        description = "",
+        example = json!({
+          "user_name": "nith"
+        })),
+    responses( 
+        (
+            status=200, 
+            description= "A code was emailed if the user exists, is active and wasn't sent `otp_login.max_codes_per_window` codes already. The answer is the same otherwise, so it can't be used to find user names", 
+            body= Status 
+        ),
+        (
+            status=400, 
+            description= "`CAPTCHA_REQUIRED`, the CAPTCHA token is missing or was not accepted", 
+            body= Status
+        ),
+        (
+            status=404, 
+            description= "`otp_login.enabled` is off", 
+            body= Status
+        ),
+        (
+            status=429, 
+            description= "The client IP failed `security.login_tarpit_block_after` times, `RATE_LIMITED` with `Retry-After`", 
+            body= Status
+        ),
+    )
+)]
+pub async fn request_otp(
+  req: web::Json<RequestOtpReqDto>,
+  http_req: HttpRequest,
+  data: web::Data<AppState>,
+) -> impl Responder {
+  let setting = &data.config.otp_login;
+  if !setting.enabled {
+    return Status::not_found(StatusMessage::NotFound("OTP login".into())).into_http_response();
+  }
+  let user_name = req.user_name.trim();
+  let ip = client_ip(&http_req, &data.runtime().rate_limit);
+  if let Some(res) = hold_in_tarpit(&data, &http_req, ip, user_name).await {
+    return res;
+  }
+  if captcha::login_needs_captcha(&data, user_name)
+    && let Err(status) = captcha::require_captcha(&data, &http_req).await
+  {
+    return status.into_http_response();
+  }
+
+  let mut repo = data.user_repo();
+  let db_user = match repo.get_by_username(user_name).await {
+    Ok(Some(db_user)) if db_user.is_active => db_user,
+    Ok(_) => {
+      // Hashed anyway so the answer takes as long as for a user who was sent a code
+      let _ = PasswordHashing::hash_password(&generate_login_code(setting.code_length));
+      return HttpResponse::Ok().json(Status::success());
+    }
+    Err(e) => {
+      return Status::bad_request(format!("Failed to send the login code: {}", e))
+        .or_query_timeout(&e)
+        .into_http_response();
+    }
+  };
+
+  let code = generate_login_code(setting.code_length);
+  let code_hash = match PasswordHashing::hash_password(&code) {
+    Ok(hash) => hash,
+    Err(e) => return Status::server_error(e.to_string()).into_http_response(),
+  };
+  match repo
+    .create_login_code(
+      db_user.id,
+      &code_hash,
+      setting.expiration_minutes,
+      setting.max_codes_per_window,
+      setting.window_minutes,
+    )
+    .await
+  {
+    Ok(true) => {}
+    // Same answer as a sent code, the user already has enough of them in their mailbox
+    Ok(false) => return HttpResponse::Ok().json(Status::success()),
+    Err(e) => {
+      return Status::bad_request(format!("Failed to send the login code: {}", e))
+        .or_query_timeout(&e)
+        .into_http_response();
+    }
+  }
+  data.notifier.send_email_detached(
+    NotificationKind::LoginCode,
+    db_user.id,
+    db_user.email,
+    EmailTemplate::LoginCode {
+      code,
+      expires_minutes: setting.expiration_minutes,
+    },
+  );
+  AuditRepo::new(&data)
+    .record(
+      AuditLogEntity::new(
+        AuditAction::RequestLoginCode,
+        Some(db_user.id),
+        &db_user.user_name,
+      )
+      .with_request(&http_req),
+    )
+    .await;
+  HttpResponse::Ok().json(Status::success())
+}
+
+document!(login_otp);
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/login_otp",
+    tag = "Authentication",
+    params(
+        ("X-Captcha-Token" = Option<String>, Header, description = "CAPTCHA response token, required after `captcha.failed_logins_before_captcha` failed logins")
+    ),
+    request_body(
+        content = LoginOtpReqDto,
+        description = "",
+        example = json!({
+          "user_name": "nith",
+          "code": "042917"
+        })),
+    responses( 
+        (
+            status=200, 
+            description= "Login successfully, or `UNAUTHORIZED` when the code is wrong, expired or was tried too often", 
+            body= Status 
+        ),
+        (
+            status=400, 
+            description= "`CAPTCHA_REQUIRED`, the CAPTCHA token is missing or was not accepted", 
+            body= Status
+        ),
+        (
+            status=403, 
+            description= "Account is disabled", 
+            body= Status
+        ),
+        (
+            status=404, 
+            description= "`otp_login.enabled` is off", 
+            body= Status
+        ),
+        (
+            status=429, 
+            description= "The client IP failed `security.login_tarpit_block_after` times, `RATE_LIMITED` with `Retry-After`", 
+            body= Status
+        ),
+    )
+)]
+pub async fn login_otp(
+  req: web::Json<LoginOtpReqDto>,
+  http_req: HttpRequest,
+  data: web::Data<AppState>,
+) -> impl Responder {
+  let setting = &data.config.otp_login;
+  if !setting.enabled {
+    return Status::not_found(StatusMessage::NotFound("OTP login".into())).into_http_response();
+  }
+  let user_name = req.user_name.trim();
+  if user_name.is_empty() || req.code.is_empty() {
+    return HttpResponse::Unauthorized().json(Status::unauthorized(StatusMessage::Unauthorized));
+  }
+  let ip = client_ip(&http_req, &data.runtime().rate_limit);
+  if let Some(res) = hold_in_tarpit(&data, &http_req, ip, user_name).await {
+    return res;
+  }
+  if captcha::login_needs_captcha(&data, user_name)
+    && let Err(status) = captcha::require_captcha(&data, &http_req).await
+  {
+    return status.into_http_response();
+  }
+
+  let mut repo = data.user_repo();
+  let db_user = repo.get_by_username(user_name).await.ok().flatten();
+  let actor_id = db_user.as_ref().map(|db_user| db_user.id);
+  // A spent code stays until its window is over, so requesting another one doesn't give back
+  // the attempts
+  let login_code = match &db_user {
+    Some(db_user) => repo.get_login_code(db_user.id).await.ok().flatten(),
+    None => None,
+  }
+  .filter(|login_code| {
+    login_code.expires_at >= Utc::now() && login_code.attempts < setting.max_attempts
+  });
+  let code_hash = login_code
+    .as_ref()
+    .map_or(DUMMY_CODE_HASH.as_str(), |login_code| {
+      login_code.code_hash.as_str()
+    });
+  let is_match = PasswordHashing::verify_password(req.code.trim(), code_hash);
+
+  if let (Some(db_user), Some(login_code)) = (db_user, login_code) {
+    if !is_match {
+      let _ = repo.increment_login_code_attempts(db_user.id).await;
+    } else {
+      // Used once, whether or not the account can still sign in. Of two requests racing with
+      // the same code only the one that removes it goes on
+      match repo
+        .consume_login_code(db_user.id, &login_code.code_hash, setting.max_attempts)
+        .await
+      {
+        Ok(true) => return sign_in(&data, &http_req, ip, db_user, Some("otp")).await,
+        Ok(false) => {}
+        Err(e) => {
+          return Status::bad_request(format!("Failed to sign in: {}", e))
+            .or_query_timeout(&e)
+            .into_http_response();
+        }
       }
     }
   }
 
+  record_failed_login(&data, &http_req, ip, user_name, actor_id, Some("otp")).await;
   HttpResponse::Ok().json(Status::unauthorized(StatusMessage::Unauthorized))
 }
 
+document!(logout);
 #[utoipa::path(
     post,
     path = "/api/v1/auth/logut",
@@ -5,16 +5,18 @@ use actix_web::{
 
 use crate::{
   features::{
-    auth::auth_handler::{login, logout, register},
+    auth::auth_handler::{login, login_otp, logout, register, request_otp},
     users::user_entity::UserRole,
   },
-  middleware::auth::RequireAuth,
+  middleware::{auth::RequireAuth, transaction::TransactionScope},
 };
 
 pub fn auth_routes() -> Scope {
   web::scope("/auth")
-    .route("/register", web::post().to(register))
+    .route("/register", web::post().to(register).wrap(TransactionScope))
     .route("/login", web::post().to(login))
+    .route("/request_otp", web::post().to(request_otp))
+    .route("/login_otp", web::post().to(login_otp))
     .route(
       "/logout",
       web::post().to(logout).wrap(RequireAuth::allow_roles(vec![
@@ -0,0 +1,42 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::Validate;
+
+use crate::{
+  dto::base_res_dto::Status,
+  features::{
+    roles::roles_dto::{AssignUserRoleReqDto, CreateRoleReqDto, UpdateRoleReqDto},
+    users::user_dto::SetUserActiveReqDto,
+  },
+};
+
+// Upper bound on operations in one batch request
+pub const MAX_BATCH_OPERATIONS: u64 = 100;
+
+/// One sub-operation, `op` picks the kind and the other fields are that endpoint's body.
+#[derive(Deserialize, Serialize, Clone, ToSchema)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum BatchOperation {
+  CreateRole(CreateRoleReqDto),
+  UpdateRole(UpdateRoleReqDto),
+  AssignUserRole(AssignUserRoleReqDto),
+  ActivateUser(SetUserActiveReqDto),
+  DeactivateUser(SetUserActiveReqDto),
+}
+
+#[derive(Deserialize, ToSchema, Validate)]
+pub struct BatchReqDto {
+  /// Run every operation in one transaction, the first failure rolls all of them back
+  #[serde(default)]
+  pub atomic: bool,
+  #[validate(length(min = 1, max = MAX_BATCH_OPERATIONS))]
+  pub operations: Vec<BatchOperation>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct BatchResDto {
+  /// False when an atomic batch was rolled back
+  pub committed: bool,
+  /// Outcome of each operation, in request order
+  pub results: Vec<Status>,
+}
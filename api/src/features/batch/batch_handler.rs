@@ -0,0 +1,358 @@
+use actix_web::{HttpRequest, HttpResponse, Responder, http::StatusCode, web};
+use validator::{Validate, ValidationErrors};
+
+use crate::{
+  app_settings::QuotaSetting,
+  app_state::AppState,
+  document,
+  dto::{
+    base_res_dto::{BaseResDto, Status},
+    sort::SortDir,
+    validated_json::{ValidatedJson, ValidationFailed},
+  },
+  error::StatusMessage,
+  events::DomainEvent,
+  features::{
+    audit::{
+      audit_entity::{AuditAction, AuditLogEntity},
+      audit_repo::AuditRepo,
+    },
+    batch::batch_dto::{BatchOperation, BatchReqDto, BatchResDto},
+    roles::{roles_dto::RoleSortBy, roles_entity::RoleEntity, roles_repo::RoleRepository},
+    users::user_repo::UserRepository,
+  },
+  middleware::{auth::Authenticated, transaction::DbTransaction},
+  notifications::NotificationKind,
+  security_log::{SecurityEvent, SecurityEventType},
+  utils::mailer::EmailTemplate,
+};
+
+// What a successful operation changed, its audit entry and events wait until it is committed
+enum Completed {
+  RoleCreated { name: String },
+  RoleUpdated { id: i32, name: String },
+  UserRoleAssigned { user_id: i32, role_id: i32 },
+  UserActiveSet { id: i32, is_active: bool },
+}
+
+fn invalid(errors: ValidationErrors) -> Status {
+  let fields = ValidationFailed::from(errors).0;
+  Status {
+    message: fields
+      .iter()
+      .map(|f| format!("{}: {}", f.field, f.message))
+      .collect::<Vec<_>>()
+      .join("; "),
+    ..Status::validation_failed()
+  }
+}
+
+fn failed(action: &str, e: anyhow::Error) -> Status {
+  Status::bad_request(format!("Failed to {}: {}", action, e)).or_query_timeout(&e)
+}
+
+// Same checks as the single-item endpoints
+async fn run(
+  op: &BatchOperation,
+  users: &mut dyn UserRepository,
+  roles: &mut dyn RoleRepository,
+  quota: &QuotaSetting,
+  current_user: &Authenticated,
+) -> Result<Completed, Status> {
+  match op {
+    BatchOperation::CreateRole(role) => {
+      role.validate().map_err(invalid)?;
+      let existing = roles
+        .get_by_name(&role.name)
+        .await
+        .map_err(|e| failed("create role", e))?;
+      if existing.is_some() {
+        return Err(Status::bad_request(StatusMessage::Existed(format!(
+          "Role with name '{}'",
+          role.name
+        ))));
+      }
+      if let Some(max_roles) = quota.max_roles {
+        let all_roles = roles
+          .get_roles(RoleSortBy::Id, SortDir::Asc)
+          .await
+          .map_err(|e| failed("create role", e))?;
+        if all_roles.len() as i32 >= max_roles {
+          return Err(Status::quota_exceeded(StatusMessage::QuotaExceeded(
+            "Number of roles".into(),
+            max_roles,
+          )));
+        }
+      }
+      let entity = RoleEntity::from(role.clone());
+      roles
+        .create_role(&entity)
+        .await
+        .map_err(|e| failed("create role", e))?;
+      Ok(Completed::RoleCreated { name: entity.name })
+    }
+    BatchOperation::UpdateRole(role) => {
+      role.validate().map_err(invalid)?;
+      let existing = roles
+        .get_by_id(role.id)
+        .await
+        .map_err(|e| failed("update role", e))?;
+      if existing.is_none() {
+        return Err(Status::not_found(StatusMessage::NotFound(format!(
+          "Role with id '{}'",
+          role.id
+        ))));
+      }
+      if let Ok(Some(_)) = roles.get_by_name(&role.name).await {
+        return Err(Status::bad_request(StatusMessage::Existed(format!(
+          "Role name '{}'",
+          role.name
+        ))));
+      }
+      let entity = RoleEntity::from(role.clone());
+      roles
+        .update_role(&entity)
+        .await
+        .map_err(|e| failed("update role", e))?;
+      Ok(Completed::RoleUpdated {
+        id: entity.id,
+        name: entity.name,
+      })
+    }
+    BatchOperation::AssignUserRole(r) => {
+      if roles.is_user_role_exist(r.user_id, r.role_id).await {
+        return Err(Status::uqique_constraint_voilation(
+          "User already has that role",
+        ));
+      }
+      if let Some(max_members) = quota.max_members_per_role {
+        let total = roles
+          .count_users_in_role(r.role_id)
+          .await
+          .map_err(|e| failed("assign user to role", e))?;
+        if total >= max_members {
+          return Err(Status::quota_exceeded(StatusMessage::QuotaExceeded(
+            "Number of users in role".into(),
+            max_members,
+          )));
+        }
+      }
+      roles
+        .assign_user_role(r.user_id, r.role_id)
+        .await
+        .map_err(|e| failed("assign user to role", e))?;
+      Ok(Completed::UserRoleAssigned {
+        user_id: r.user_id,
+        role_id: r.role_id,
+      })
+    }
+    BatchOperation::ActivateUser(r) | BatchOperation::DeactivateUser(r) => {
+      let is_active = matches!(op, BatchOperation::ActivateUser(_));
+      // Admins must not lock themselves out
+      if r.id == current_user.id {
+        return Err(Status::bad_request(StatusMessage::WrongParams.to_str()));
+      }
+      let existing = users
+        .get_by_id(r.id)
+        .await
+        .map_err(|e| failed("update user", e))?;
+      if existing.is_none() {
+        return Err(Status::not_found(StatusMessage::NotFound("User".into())));
+      }
+      users
+        .set_active(r.id, is_active)
+        .await
+        .map_err(|e| failed("update user", e))?;
+      Ok(Completed::UserActiveSet {
+        id: r.id,
+        is_active,
+      })
+    }
+  }
+}
+
+async fn after_commit(
+  completed: Completed,
+  current_user: &Authenticated,
+  http_req: &HttpRequest,
+  data: &AppState,
+) {
+  let entry = match completed {
+    Completed::RoleCreated { name } => AuditLogEntity::new(
+      AuditAction::CreateRole,
+      Some(current_user.id),
+      format!("role:{}", name),
+    ),
+    Completed::RoleUpdated { id, name } => {
+      data.invalidate_access_cache();
+      AuditLogEntity::new(
+        AuditAction::UpdateRole,
+        Some(current_user.id),
+        format!("role:{}", id),
+      )
+      .with_details(format!("name={}", name))
+    }
+    Completed::UserRoleAssigned { user_id, role_id } => {
+      data.invalidate_access_cache();
+      data.events.publish(DomainEvent::RoleAssigned {
+        role_id,
+        user_ids: vec![user_id],
+      });
+      if let (Ok(Some(user)), Ok(Some(role))) = (
+        data.user_repo().get_by_id(user_id).await,
+        data.role_repo().get_by_id(role_id).await,
+      ) {
+        data.notifier.send_email_detached(
+          NotificationKind::SecurityAlert,
+          user.id,
+          user.email,
+          EmailTemplate::RoleChanged { role: role.name },
+        );
+      }
+      data.security_log.emit(
+        SecurityEvent::new(SecurityEventType::RoleGranted)
+          .actor(Some(current_user.id))
+          .target(format!("user:{}", user_id))
+          .details(format!("role_id={}", role_id))
+          .with_request(http_req),
+      );
+      AuditLogEntity::new(
+        AuditAction::AssignUserRole,
+        Some(current_user.id),
+        format!("user:{}", user_id),
+      )
+      .with_details(format!("role_id={}", role_id))
+    }
+    Completed::UserActiveSet { id, is_active } => {
+      let action = if is_active {
+        AuditAction::ActivateUser
+      } else {
+        data.security_log.emit(
+          SecurityEvent::new(SecurityEventType::TokensRevoked)
+            .actor(Some(current_user.id))
+            .target(format!("user:{}", id))
+            .details("user deactivated")
+            .with_request(http_req),
+        );
+        AuditAction::DeactivateUser
+      };
+      AuditLogEntity::new(action, Some(current_user.id), format!("user:{}", id))
+    }
+  };
+  AuditRepo::new(data)
+    .record(entry.with_request(http_req))
+    .await;
+}
+
+document!(run_batch);
+#[utoipa::path(
+    post,
+    path = "/api/v1/batch",
+    tag = "Batch",
+    request_body(
+        content = BatchReqDto,
+        description = "",
+        example = json!({
+          "atomic": true,
+          "operations": [
+            { "op": "create_role", "name": "support", "description": "Answers tickets" },
+            { "op": "assign_user_role", "user_id": 1, "role_id": 2 },
+            { "op": "deactivate_user", "id": 3 }
+          ]
+        })),
+    responses(
+        (
+            status=200,
+            description= "Every operation was run, see each result",
+            body= BaseResDto<BatchResDto>
+        ),
+        (
+            status=400,
+            description= "Validation Errors, or an atomic batch rolled back",
+            body= BaseResDto<BatchResDto>
+        ),
+        (
+            status=403,
+            description= "Permission denied",
+            body= Status
+        ),
+    )
+)]
+pub async fn run_batch(
+  req: ValidatedJson<BatchReqDto>,
+  current_user: Authenticated,
+  http_req: HttpRequest,
+  data: web::Data<AppState>,
+) -> impl Responder {
+  let quota = &data.config.quota;
+
+  if !req.atomic {
+    // Each operation commits on its own, one failing leaves the others in place
+    let mut results = Vec::with_capacity(req.operations.len());
+    for op in &req.operations {
+      let (mut users, mut roles) = (data.user_repo(), data.role_repo());
+      let result = run(op, users.as_mut(), roles.as_mut(), quota, &current_user).await;
+      results.push(match result {
+        Ok(completed) => {
+          after_commit(completed, &current_user, &http_req, &data).await;
+          Status::success()
+        }
+        Err(status) => status,
+      });
+    }
+    return HttpResponse::Ok().json(Status::success_with_data(BatchResDto {
+      committed: true,
+      results,
+    }));
+  }
+
+  let tx = match DbTransaction::start(&data).await {
+    Ok(tx) => tx,
+    Err(e) => return Status::server_error(e.to_string()).into_http_response(),
+  };
+  let (mut users, mut roles) = (data.user_repo_in(&tx), data.role_repo_in(&tx));
+  let mut completed = Vec::with_capacity(req.operations.len());
+  let mut failure = None;
+  for (index, op) in req.operations.iter().enumerate() {
+    match run(op, users.as_mut(), roles.as_mut(), quota, &current_user).await {
+      Ok(done) => completed.push(done),
+      Err(status) => {
+        failure = Some((index, status));
+        break;
+      }
+    }
+  }
+  drop((users, roles));
+
+  if let Some((index, status)) = failure {
+    if let Err(e) = tx.rollback().await {
+      eprintln!("Failed to roll back batch: {}", e);
+    }
+    let http_status = StatusCode::from_u16(status.status).unwrap_or(StatusCode::BAD_REQUEST);
+    let mut results = vec![Status::rolled_back(); req.operations.len()];
+    results[index] = status.clone();
+    return HttpResponse::build(http_status).json(BaseResDto {
+      data: Some(BatchResDto {
+        committed: false,
+        results,
+      }),
+      status: Status {
+        message: format!("Operation {} failed: {}", index, status.message),
+        ..status
+      },
+    });
+  }
+
+  if let Err(e) = tx.commit().await {
+    return Status::server_error(format!("Failed to commit batch: {}", e)).into_http_response();
+  }
+  drop(tx);
+  let results = vec![Status::success(); completed.len()];
+  for done in completed {
+    after_commit(done, &current_user, &http_req, &data).await;
+  }
+  HttpResponse::Ok().json(Status::success_with_data(BatchResDto {
+    committed: true,
+    results,
+  }))
+}
@@ -0,0 +1,15 @@
+use actix_web::{Scope, web};
+
+use crate::{
+  features::{batch::batch_handler::run_batch, users::user_entity::UserRole},
+  middleware::auth::RequireAuth,
+};
+
+pub fn batch_routes() -> Scope {
+  web::scope("/batch").route(
+    "",
+    web::post()
+      .to(run_batch)
+      .wrap(RequireAuth::allow_roles(vec![UserRole::Admin])),
+  )
+}
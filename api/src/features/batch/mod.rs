@@ -0,0 +1,3 @@
+pub mod batch_dto;
+pub mod batch_handler;
+pub mod batch_route;
@@ -0,0 +1,24 @@
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+// --- Request Dto --- //
+#[derive(Deserialize, Serialize, Debug, ToSchema, IntoParams)]
+pub struct DevTokenReqDto {
+  pub role: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum EmailPreviewFormat {
+  #[default]
+  Html,
+  Text,
+}
+
+#[derive(Deserialize, Serialize, Debug, ToSchema, IntoParams)]
+pub struct EmailPreviewReqDto {
+  /// Language tag, e.g. `km`, the request's `Accept-Language` when left out
+  pub locale: Option<String>,
+  #[serde(default)]
+  pub format: EmailPreviewFormat,
+}
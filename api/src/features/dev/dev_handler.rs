@@ -0,0 +1,176 @@
+use actix_web::{HttpResponse, Responder, web};
+
+use crate::{
+  app_state::AppState,
+  document,
+  dto::base_res_dto::{BaseResDto, Status},
+  error::StatusMessage,
+  features::{
+    auth::auth_dto::LoginResDto,
+    dev::dev_dto::{DevTokenReqDto, EmailPreviewFormat, EmailPreviewReqDto},
+    users::{
+      user_dto::{UserDto, UserRegisterReqDto},
+      user_entity::UserRole,
+    },
+  },
+  i18n::{self, Locale},
+  utils::{jwt_util::JwtUtil, mailer::EmailTemplate},
+};
+
+const DEV_TOKEN_EXPIRATION_MINUTES: i64 = 15;
+
+document!(dev_token);
+#[utoipa::path(
+    get,
+    path = "/api/v1/dev/token",
+    tag = "Dev",
+    params(DevTokenReqDto),
+    responses( 
+        (
+            status=200, 
+            description= "Dev token minted successfully", 
+            body= BaseResDto<LoginResDto> 
+        ),
+        (
+            status=400, 
+            description= "Validation Errors", 
+            body= Status
+        ),
+        (
+            status=404, 
+            description= "Not available outside dev environments", 
+            body= Status
+        ),
+        (
+            status=500, 
+            description= "Internal Server Error", 
+            body= Status 
+        ),
+    )
+)]
+pub async fn dev_token(
+  query: web::Query<DevTokenReqDto>,
+  data: web::Data<AppState>,
+) -> impl Responder {
+  // The route is only mounted in dev, this guards against it being wired up elsewhere
+  if !data.config.is_dev() {
+    return Status::not_found(StatusMessage::NotFound("Endpoint".into())).into_http_response();
+  }
+
+  let role = match UserRole::try_from_str(&query.role) {
+    Some(role) => role,
+    None => return Status::bad_request(StatusMessage::WrongParams.to_str()).into_http_response(),
+  };
+
+  let mut repo = data.user_repo();
+  let user_name = format!("dev_{}", role.to_str());
+
+  // Seed a throwaway user for the role on first use
+  if let Ok(None) = repo.get_by_username(&user_name).await {
+    let dev_user = UserRegisterReqDto {
+      user_name: user_name.clone(),
+      password: uuid::Uuid::new_v4().to_string(),
+      email: format!("{}@dev.local", user_name),
+      name: format!("Dev {}", role.to_str()),
+      role: role.to_str().to_string(),
+    };
+    if let Err(e) = repo.create(&dev_user).await {
+      return Status::server_error(format!("Failed to create dev user: {}", e))
+        .or_query_timeout(&e)
+        .into_http_response();
+    }
+  }
+
+  match repo.get_by_username(&user_name).await {
+    Ok(Some(db_user)) => {
+      let permissions = data
+        .permission_repo()
+        .get_user_permissions(db_user.id)
+        .await
+        .unwrap_or_default();
+      let role_names = data
+        .role_repo()
+        .get_user_role_names(db_user.id)
+        .await
+        .unwrap_or_default();
+      let jwt_util = JwtUtil::from_state(&data);
+      match jwt_util.create_token_with_expiration(
+        &UserDto::from(db_user).with_roles(role_names),
+        &permissions,
+        DEV_TOKEN_EXPIRATION_MINUTES,
+      ) {
+        Ok(token) => HttpResponse::Ok().json(Status::success_with_data(LoginResDto { token })),
+        Err(e) => Status::server_error(format!("Failed to create token: {}", e))
+          .or_query_timeout(&e)
+          .into_http_response(),
+      }
+    }
+    Ok(None) => Status::not_found(StatusMessage::NotFound("User".into())).into_http_response(),
+    Err(e) => Status::server_error(format!("Failed to get dev user: {}", e))
+      .or_query_timeout(&e)
+      .into_http_response(),
+  }
+}
+
+document!(email_preview);
+#[utoipa::path(
+    get,
+    path = "/api/v1/dev/email-preview/{name}",
+    tag = "Dev",
+    params(
+        ("name" = String, Path, description = "Template name, e.g. `verification` or `password_reset`"),
+        EmailPreviewReqDto
+    ),
+    responses( 
+        (
+            status=200, 
+            description= "The template rendered with placeholder values", 
+            content_type= "text/html",
+            body= String 
+        ),
+        (
+            status=404, 
+            description= "Unknown template or language, or not a dev environment", 
+            body= Status
+        ),
+    )
+)]
+pub async fn email_preview(
+  name: web::Path<String>,
+  query: web::Query<EmailPreviewReqDto>,
+  data: web::Data<AppState>,
+) -> impl Responder {
+  if !data.config.is_dev() {
+    return Status::not_found(StatusMessage::NotFound("Endpoint".into())).into_http_response();
+  }
+
+  let Some(template) = EmailTemplate::samples()
+    .into_iter()
+    .find(|template| template.name() == name.as_str())
+  else {
+    return Status::not_found(StatusMessage::NotFound("Template".into())).into_http_response();
+  };
+  let locale = match &query.locale {
+    Some(tag) => match Locale::from_tag(tag) {
+      Some(locale) => locale,
+      None => {
+        return Status::not_found(StatusMessage::NotFound("Language".into())).into_http_response();
+      }
+    },
+    None => i18n::current(),
+  };
+
+  match template.render(locale) {
+    Ok(email) => match query.format {
+      EmailPreviewFormat::Html => HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(email.html),
+      EmailPreviewFormat::Text => HttpResponse::Ok()
+        .content_type("text/plain; charset=utf-8")
+        .body(format!("Subject: {}\n\n{}", email.subject, email.text)),
+    },
+    Err(e) => {
+      Status::server_error(format!("Failed to render template: {}", e)).into_http_response()
+    }
+  }
+}
@@ -0,0 +1,9 @@
+use actix_web::{Scope, web};
+
+use crate::features::dev::dev_handler::{dev_token, email_preview};
+
+pub fn dev_routes() -> Scope {
+  web::scope("/dev")
+    .route("/token", web::get().to(dev_token))
+    .route("/email-preview/{name}", web::get().to(email_preview))
+}
@@ -0,0 +1,3 @@
+pub mod dev_dto;
+pub mod dev_handler;
+pub mod dev_route;
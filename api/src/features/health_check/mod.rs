@@ -1,7 +1,113 @@
-use actix_web::{HttpResponse, Responder, get};
+use std::time::Instant;
 
-use crate::dto::base_res_dto::Status;
+use actix_web::{HttpResponse, Responder, get, web};
+use chrono::{DateTime, Utc};
+use domner_tech_sql_client::CommandType;
+use serde::Serialize;
+use utoipa::ToSchema;
 
+use crate::{
+  app_settings::AppSetting,
+  app_state::AppState,
+  db::SqlRepo,
+  document,
+  dto::base_res_dto::{BaseResDto, Status},
+  features::users::user_entity::UserRole,
+  middleware::auth::RequireAuth,
+};
+
+const REDACTED: &str = "***";
+// A dependency answering slower than this is reported as degraded
+const DEGRADED_AFTER_MS: u128 = 1000;
+
+#[derive(Serialize, Clone, ToSchema)]
+pub struct FeatureFlagDto {
+  pub name: String,
+  pub enabled: bool,
+  pub percentage: Option<u8>,
+  pub roles: Vec<String>,
+}
+
+// Non-secret view of the loaded appsettings
+#[derive(Serialize, Clone, ToSchema)]
+pub struct ConfigProfileDto {
+  pub environment: String,
+  pub rust_log: String,
+  pub server: String,
+  pub db_pool_name: String,
+  pub db_pool_size: u32,
+  pub db_conn_str: String,
+  pub jwt_issuer: String,
+  pub jwt_audience: String,
+  pub jwt_secret_key: String,
+  pub jwt_expiration_minutes: usize,
+  pub notification_channels: Vec<String>,
+}
+
+impl From<&AppSetting> for ConfigProfileDto {
+  fn from(config: &AppSetting) -> Self {
+    let notification = &config.notification;
+    let notification_channels = [
+      ("email", true),
+      ("webhook", notification.webhook.is_some()),
+      ("sms", notification.sms.is_some()),
+    ]
+    .iter()
+    .filter(|(_, configured)| *configured)
+    .map(|(name, _)| name.to_string())
+    .collect();
+
+    Self {
+      environment: config.environment.clone(),
+      rust_log: config.rust_log.clone(),
+      server: format!("{}:{}", config.server.host, config.server.port),
+      db_pool_name: config.database.sql_server.pool_name.clone(),
+      db_pool_size: config.database.sql_server.pool_size,
+      db_conn_str: REDACTED.to_string(),
+      jwt_issuer: config.jwt.issuer.clone(),
+      jwt_audience: config.jwt.audience.clone(),
+      jwt_secret_key: REDACTED.to_string(),
+      jwt_expiration_minutes: config.jwt.expiration_minutes,
+      notification_channels,
+    }
+  }
+}
+
+// Ordered from best to worst so the overall state is the worst dependency
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthState {
+  Healthy,
+  Degraded,
+  Unhealthy,
+}
+
+#[derive(Serialize, Clone, ToSchema)]
+pub struct DependencyHealthDto {
+  pub name: String,
+  pub kind: String,
+  pub status: HealthState,
+  pub latency_ms: u128,
+  pub error: Option<String>,
+}
+
+#[derive(Serialize, Clone, ToSchema)]
+pub struct ReadinessResDto {
+  pub status: HealthState,
+  pub dependencies: Vec<DependencyHealthDto>,
+  pub checked_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Clone, ToSchema)]
+pub struct MetaResDto {
+  pub version: String,
+  pub git_sha: String,
+  pub build_timestamp: Option<DateTime<Utc>>,
+  pub feature_flags: Vec<FeatureFlagDto>,
+  pub config: ConfigProfileDto,
+}
+
+document!(health_checker_handler);
 #[utoipa::path(
     get,
     path = "/api/v1/healthz",
@@ -14,3 +120,140 @@ use crate::dto::base_res_dto::Status;
 pub async fn health_checker_handler() -> impl Responder {
   HttpResponse::Ok().json(Status::success())
 }
+
+document!(liveness_handler);
+#[utoipa::path(
+    get,
+    path = "/livez",
+    tag = "Health Checker Endpoint",
+    responses(
+        (status = 200, description= "The process is up and serving requests", body = Status),
+    )
+)]
+#[get("/livez")]
+pub async fn liveness_handler() -> impl Responder {
+  HttpResponse::Ok().json(Status::success())
+}
+
+document!(readyz_handler);
+#[utoipa::path(
+    get,
+    path = "/readyz",
+    tag = "Health Checker Endpoint",
+    responses(
+        (status = 200, description= "Pools are initialized and migrations applied", body = Status),
+        (status = 503, description= "Startup has not completed yet", body = Status),
+    )
+)]
+#[get("/readyz")]
+pub async fn readyz_handler(data: web::Data<AppState>) -> impl Responder {
+  if data.is_ready() {
+    HttpResponse::Ok().json(Status::success())
+  } else {
+    Status::unhealthy("Startup has not completed yet").into_http_response()
+  }
+}
+
+// Checks out a client from the pool and runs SELECT 1 on it
+pub async fn check_pool(data: &AppState, pool_name: String) -> DependencyHealthDto {
+  let started = Instant::now();
+  let result = match data.db_manager.get_client(&pool_name).await {
+    Ok(mut client) => {
+      SqlRepo::execute_command_none_query(&mut client, "SELECT 1", &[], CommandType::Text).await
+    }
+    Err(e) => Err(e),
+  };
+  let latency_ms = started.elapsed().as_millis();
+
+  let (status, error) = match result {
+    Ok(_) if latency_ms > DEGRADED_AFTER_MS => (HealthState::Degraded, None),
+    Ok(_) => (HealthState::Healthy, None),
+    Err(e) => (HealthState::Unhealthy, Some(e.to_string())),
+  };
+  DependencyHealthDto {
+    name: pool_name,
+    kind: "sql_server".to_string(),
+    status,
+    latency_ms,
+    error,
+  }
+}
+
+document!(readiness_handler);
+#[utoipa::path(
+    get,
+    path = "/api/v1/healthz/ready",
+    tag = "Health Checker Endpoint",
+    responses(
+        (status = 200, description= "Every dependency answers, possibly slowly", body = BaseResDto<ReadinessResDto>),
+        (status = 503, description= "At least one dependency is unreachable", body = BaseResDto<ReadinessResDto>),
+    )
+)]
+#[get("/healthz/ready")]
+pub async fn readiness_handler(data: web::Data<AppState>) -> impl Responder {
+  let checks = data
+    .db_manager
+    .pool_names()
+    .into_iter()
+    .map(|pool_name| check_pool(&data, pool_name));
+  let dependencies = futures::future::join_all(checks).await;
+
+  let status = dependencies
+    .iter()
+    .map(|dependency| dependency.status)
+    .max()
+    .unwrap_or(HealthState::Unhealthy);
+  let readiness = ReadinessResDto {
+    status,
+    dependencies,
+    checked_at: Utc::now(),
+  };
+
+  match status {
+    HealthState::Unhealthy => HttpResponse::ServiceUnavailable().json(BaseResDto {
+      data: Some(readiness),
+      status: Status::unhealthy("One or more dependencies are unreachable"),
+    }),
+    _ => HttpResponse::Ok().json(Status::success_with_data(readiness)),
+  }
+}
+
+document!(meta_handler);
+#[utoipa::path(
+    get,
+    path = "/api/v1/meta",
+    tag = "Health Checker Endpoint",
+    responses(
+        (status = 200, description= "Build and deployment metadata", body = BaseResDto<MetaResDto>),
+        (status = 403, description= "Permission denied", body = Status),
+    )
+)]
+#[get("/meta", wrap = "RequireAuth::allow_roles(vec![UserRole::Admin])")]
+pub async fn meta_handler(data: web::Data<AppState>) -> impl Responder {
+  let runtime = data.runtime();
+  let mut feature_flags: Vec<FeatureFlagDto> = runtime
+    .feature_flags
+    .iter()
+    .map(|(name, flag)| FeatureFlagDto {
+      name: name.clone(),
+      enabled: flag.enabled,
+      percentage: flag.percentage,
+      roles: flag.roles.clone(),
+    })
+    .collect();
+  feature_flags.sort_by(|a, b| a.name.cmp(&b.name));
+
+  HttpResponse::Ok().json(Status::success_with_data(MetaResDto {
+    version: env!("CARGO_PKG_VERSION").to_string(),
+    git_sha: env!("BUILD_GIT_SHA").to_string(),
+    build_timestamp: env!("BUILD_TIMESTAMP")
+      .parse::<i64>()
+      .ok()
+      .and_then(|secs| DateTime::from_timestamp(secs, 0)),
+    feature_flags,
+    config: ConfigProfileDto {
+      rust_log: runtime.rust_log.clone(),
+      ..ConfigProfileDto::from(&data.config)
+    },
+  }))
+}
@@ -0,0 +1,69 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use validator::Validate;
+
+use crate::{
+  dto::paged_res_dto::MAX_PAGE_SIZE,
+  features::login_history::login_history_entity::{LoginAttemptEntity, LoginOutcome},
+};
+
+#[derive(Deserialize, Serialize, Clone, ToSchema)]
+pub struct LoginAttemptDto {
+  pub id: i32,
+  pub user_id: Option<i32>,
+  pub user_name: String,
+  /// `success`, `invalid_credentials` or `account_disabled`
+  pub outcome: String,
+  pub ip_address: Option<String>,
+  pub user_agent: Option<String>,
+  pub created_at: DateTime<Utc>,
+}
+
+impl From<LoginAttemptEntity> for LoginAttemptDto {
+  fn from(value: LoginAttemptEntity) -> Self {
+    Self {
+      id: value.id,
+      user_id: value.user_id,
+      user_name: value.user_name,
+      outcome: value.outcome,
+      ip_address: value.ip_address,
+      user_agent: value.user_agent,
+      created_at: value.created_at,
+    }
+  }
+}
+
+// --- Request Dto --- //
+
+#[derive(Deserialize, Serialize, Debug, ToSchema, IntoParams, Validate)]
+pub struct GetMyLoginsReqDto {
+  #[serde(default = "default_page")]
+  #[validate(range(min = 1))]
+  pub page: i32,
+  #[serde(default = "default_page_size")]
+  #[validate(range(min = 1, max = MAX_PAGE_SIZE))]
+  pub page_size: i32,
+}
+
+#[derive(Deserialize, Serialize, Debug, ToSchema, IntoParams, Validate)]
+pub struct GetLoginHistoryReqDto {
+  pub user_id: Option<i32>,
+  pub outcome: Option<LoginOutcome>,
+  #[serde(default = "default_page")]
+  #[validate(range(min = 1))]
+  pub page: i32,
+  #[serde(default = "default_page_size")]
+  #[validate(range(min = 1, max = MAX_PAGE_SIZE))]
+  pub page_size: i32,
+}
+
+// Default value for page
+fn default_page() -> i32 {
+  1
+}
+
+// Default value for page_size
+fn default_page_size() -> i32 {
+  20
+}
@@ -0,0 +1,85 @@
+use actix_web::{HttpRequest, http::header};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use domner_tech_sql_client::pool_manager::DbRow;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+  app_settings::RateLimitSetting,
+  db::{FromRow, RowExt},
+  middleware::rate_limit::client_ip,
+};
+
+// Column sizes of dbo.login_history, longer values are cut instead of failing the insert
+const USER_NAME_MAX: usize = 50;
+const USER_AGENT_MAX: usize = 512;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum LoginOutcome {
+  Success,
+  InvalidCredentials,
+  AccountDisabled,
+}
+
+impl LoginOutcome {
+  pub fn to_str(&self) -> &'static str {
+    match self {
+      LoginOutcome::Success => "success",
+      LoginOutcome::InvalidCredentials => "invalid_credentials",
+      LoginOutcome::AccountDisabled => "account_disabled",
+    }
+  }
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct LoginAttemptEntity {
+  pub id: i32,
+  /// `None` when the user name doesn't exist
+  pub user_id: Option<i32>,
+  pub user_name: String,
+  pub outcome: String,
+  pub ip_address: Option<String>,
+  pub user_agent: Option<String>,
+  pub created_at: DateTime<Utc>,
+}
+
+impl LoginAttemptEntity {
+  pub fn new(user_name: &str, user_id: Option<i32>, outcome: LoginOutcome) -> Self {
+    Self {
+      id: 0,
+      user_id,
+      user_name: user_name.chars().take(USER_NAME_MAX).collect(),
+      outcome: outcome.to_str().to_string(),
+      ip_address: None,
+      user_agent: None,
+      created_at: Utc::now(),
+    }
+  }
+
+  // Client address is resolved the same way as for rate limiting
+  pub fn with_request(mut self, req: &HttpRequest, rate_limit: &RateLimitSetting) -> Self {
+    self.ip_address = client_ip(req, rate_limit).map(|ip| ip.to_string());
+    self.user_agent = req
+      .headers()
+      .get(header::USER_AGENT)
+      .and_then(|value| value.to_str().ok())
+      .map(|value| value.chars().take(USER_AGENT_MAX).collect());
+    self
+  }
+}
+
+impl FromRow for LoginAttemptEntity {
+  fn from_row(row: &DbRow<'_>) -> Result<Self> {
+    Ok(Self {
+      id: row.column("id")?,
+      user_id: row.nullable("user_id")?,
+      user_name: row.column("user_name")?,
+      outcome: row.column("outcome")?,
+      ip_address: row.nullable("ip_address")?,
+      user_agent: row.nullable("user_agent")?,
+      created_at: row.column("created_at")?,
+    })
+  }
+}
@@ -0,0 +1,101 @@
+use actix_web::{HttpResponse, Responder, web};
+
+use crate::{
+  app_state::AppState,
+  document,
+  dto::{
+    base_res_dto::{BaseResDto, Status},
+    paged_res_dto::PagedResDto,
+    validated_json::ValidatedQuery,
+  },
+  features::login_history::{
+    login_history_dto::{GetLoginHistoryReqDto, GetMyLoginsReqDto, LoginAttemptDto},
+    login_history_repo::LoginHistoryRepo,
+  },
+  middleware::auth::Authenticated,
+};
+
+async fn get_paged(filter: &GetLoginHistoryReqDto, data: &AppState) -> HttpResponse {
+  match LoginHistoryRepo::new(data).get_paged(filter).await {
+    Ok((attempts, total_count)) => {
+      HttpResponse::Ok().json(Status::success_with_data(PagedResDto::new(
+        attempts.into_iter().map(LoginAttemptDto::from).collect(),
+        filter.page,
+        filter.page_size,
+        total_count,
+      )))
+    }
+    Err(e) => Status::bad_request(format!("Failed to get login history: {}", e))
+      .or_query_timeout(&e)
+      .into_http_response(),
+  }
+}
+
+document!(get_my_logins);
+#[utoipa::path(
+    get,
+    path = "/api/v1/user/me/logins",
+    tag = "Users",
+    params(GetMyLoginsReqDto),
+    responses(
+        (
+            status=200,
+            description= "Logins to the current account, failed ones included, newest first",
+            body= BaseResDto<PagedResDto<LoginAttemptDto>>
+        ),
+        (
+            status=400,
+            description= "Validation Errors",
+            body= Status
+        ),
+        (
+            status=401,
+            description= "Unauthorized",
+            body= Status
+        ),
+    )
+)]
+pub async fn get_my_logins(
+  query: ValidatedQuery<GetMyLoginsReqDto>,
+  current_user: Authenticated,
+  data: web::Data<AppState>,
+) -> impl Responder {
+  let filter = GetLoginHistoryReqDto {
+    user_id: Some(current_user.id),
+    outcome: None,
+    page: query.page,
+    page_size: query.page_size,
+  };
+  get_paged(&filter, &data).await
+}
+
+document!(get_login_history);
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/logins",
+    tag = "Admin",
+    params(GetLoginHistoryReqDto),
+    responses(
+        (
+            status=200,
+            description= "Login attempts of every user, newest first",
+            body= BaseResDto<PagedResDto<LoginAttemptDto>>
+        ),
+        (
+            status=400,
+            description= "Validation Errors",
+            body= Status
+        ),
+        (
+            status=403,
+            description= "Permission denied",
+            body= Status
+        ),
+    )
+)]
+pub async fn get_login_history(
+  query: ValidatedQuery<GetLoginHistoryReqDto>,
+  data: web::Data<AppState>,
+) -> impl Responder {
+  get_paged(&query, &data).await
+}
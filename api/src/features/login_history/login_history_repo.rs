@@ -0,0 +1,91 @@
+use crate::{
+  app_state::AppState,
+  db::{DbConnection, SqlRepo},
+  features::login_history::{
+    login_history_dto::GetLoginHistoryReqDto, login_history_entity::LoginAttemptEntity,
+  },
+};
+
+use anyhow::Result;
+use domner_tech_sql_client::{CommandType, UnifiedToSql};
+
+pub struct LoginHistoryRepo<'a> {
+  pub app_state: &'a AppState,
+}
+
+impl<'a> LoginHistoryRepo<'a> {
+  pub fn new(app_state: &'a AppState) -> Self {
+    Self { app_state }
+  }
+
+  // Fails instead of panicking, a login must still be answered when the history can't be written
+  async fn get_client(&self) -> Result<DbConnection> {
+    self
+      .app_state
+      .db_manager
+      .get_client(&self.app_state.config.database.sql_server.pool_name)
+      .await
+  }
+
+  // A successful attempt also sets users.last_login_at
+  pub async fn create(&mut self, attempt: &LoginAttemptEntity) -> Result<u64> {
+    let mut client_pool = self.get_client().await?;
+
+    // Empty values are stored as NULL by the proc
+    let user_id = attempt.user_id.unwrap_or_default();
+    let ip_address = attempt.ip_address.clone().unwrap_or_default();
+    let user_agent = attempt.user_agent.clone().unwrap_or_default();
+    let params: Vec<&dyn UnifiedToSql> = vec![
+      &user_id,
+      &attempt.user_name,
+      &attempt.outcome,
+      &ip_address,
+      &user_agent,
+    ];
+    let result = SqlRepo::execute_command_none_query(
+      &mut client_pool,
+      "[dbo].[create_login_attempt]",
+      &params,
+      CommandType::StoreProcedure,
+    )
+    .await?;
+    Ok(result)
+  }
+
+  // Like audit entries, failing to record never fails the login itself
+  pub async fn record(&mut self, attempt: LoginAttemptEntity) {
+    if let Err(e) = self.create(&attempt).await {
+      eprintln!(
+        "Failed to record login attempt of '{}': {}",
+        attempt.user_name, e
+      );
+    }
+  }
+
+  // Newest first
+  pub async fn get_paged(
+    &mut self,
+    filter: &GetLoginHistoryReqDto,
+  ) -> Result<(Vec<LoginAttemptEntity>, i32)> {
+    let mut client_pool = self.get_client().await?;
+
+    // 0 and empty strings mean "no filter"
+    let user_id = filter.user_id.unwrap_or_default();
+    let outcome = filter
+      .outcome
+      .map(|outcome| outcome.to_str())
+      .unwrap_or_default();
+    let params: Vec<&dyn UnifiedToSql> = vec![&user_id, &outcome, &filter.page, &filter.page_size];
+    SqlRepo::execute_paged_query::<LoginAttemptEntity>(
+      &mut client_pool,
+      "[dbo].[select_login_attempts_paged]",
+      &params,
+      CommandType::StoreProcedure,
+      // Same filter as the proc
+      "SELECT COUNT(*) AS value FROM [dbo].[login_history] \
+       WHERE (@P1 = 0 OR user_id = @P1) AND (@P2 = '' OR outcome = @P2)",
+      &params[..2],
+    )
+    .await
+  }
+}
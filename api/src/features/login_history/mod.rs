@@ -0,0 +1,4 @@
+pub mod login_history_dto;
+pub mod login_history_entity;
+pub mod login_history_handler;
+pub mod login_history_repo;
@@ -0,0 +1,28 @@
+use actix_web::{HttpResponse, Responder, get, web};
+
+use crate::{app_state::AppState, db::metrics, document};
+
+document!(metrics_handler);
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    tag = "Health Checker Endpoint",
+    responses(
+        (
+            status = 200,
+            description = "Query latency and errors per command, pool usage and held back logins, in the Prometheus text format",
+            content_type = "text/plain",
+            body = String
+        ),
+    )
+)]
+#[get("/metrics")]
+pub async fn metrics_handler(data: web::Data<AppState>) -> impl Responder {
+  let mut body = String::new();
+  metrics::render_prometheus(&data.db_manager.query_metrics().snapshot(), &mut body);
+  metrics::render_pools(&data.db_manager.stats(), &mut body);
+  data.login_tarpit.render_prometheus(&mut body);
+  HttpResponse::Ok()
+    .content_type("text/plain; version=0.0.4")
+    .body(body)
+}
@@ -1,4 +1,15 @@
+pub mod admin;
+pub mod audit;
 pub mod auth;
+pub mod batch;
+pub mod dev;
 pub mod health_check;
+pub mod login_history;
+pub mod metrics;
+pub mod oidc;
+pub mod operations;
+pub mod permissions;
+pub mod realtime;
 pub mod roles;
+pub mod saml;
 pub mod users;
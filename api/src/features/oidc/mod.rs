@@ -0,0 +1,3 @@
+pub mod oidc_dto;
+pub mod oidc_handler;
+pub mod oidc_route;
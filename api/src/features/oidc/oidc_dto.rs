@@ -0,0 +1,68 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::utils::custom_claims::CustomClaims;
+
+// --- Request Dto --- //
+
+/// RFC 7662 introspection request, sent as a form.
+#[derive(Deserialize, Serialize, Debug, ToSchema)]
+pub struct IntrospectReqDto {
+  pub token: String,
+  /// Accepted for compatibility, only access tokens exist
+  pub token_type_hint: Option<String>,
+}
+
+// --- Response Dto --- //
+
+/// Subset of the OpenID Connect discovery document covering what this API implements.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct OpenIdConfigurationResDto {
+  /// `jwt.issuer`, the `iss` of every token
+  pub issuer: String,
+  pub introspection_endpoint: String,
+  pub introspection_endpoint_auth_methods_supported: Vec<String>,
+  pub id_token_signing_alg_values_supported: Vec<String>,
+  pub subject_types_supported: Vec<String>,
+  pub claims_supported: Vec<String>,
+}
+
+/// Admin behind an impersonation token (RFC 8693 `act`).
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct ActorDto {
+  pub sub: String,
+}
+
+/// RFC 7662 introspection response, only `active` is set for a token that isn't.
+#[derive(Serialize, Deserialize, Debug, Default, ToSchema)]
+pub struct IntrospectResDto {
+  pub active: bool,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub token_type: Option<String>,
+  /// Id of the user the token acts as
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub sub: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub username: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub exp: Option<usize>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub iss: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub aud: Option<String>,
+  /// The token's permissions, space separated
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub scope: Option<String>,
+  /// Current role names of the user
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub roles: Option<Vec<String>>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub act: Option<ActorDto>,
+  /// Device fingerprint of a bound token, for gateways that check it themselves
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub dfp: Option<String>,
+  /// `jwt.static_claims` and enricher claims of the token
+  #[serde(flatten)]
+  #[schema(value_type = Object)]
+  pub custom: CustomClaims,
+}
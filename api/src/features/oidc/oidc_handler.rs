@@ -0,0 +1,156 @@
+use actix_web::{
+  HttpRequest, HttpResponse, Responder,
+  http::header::{self, CacheControl, CacheDirective},
+  web,
+};
+
+use crate::{
+  api_version::ApiVersion,
+  app_state::AppState,
+  document,
+  dto::base_res_dto::Status,
+  error::StatusMessage,
+  features::oidc::oidc_dto::{
+    ActorDto, IntrospectReqDto, IntrospectResDto, OpenIdConfigurationResDto,
+  },
+  middleware::{
+    auth::load_subject,
+    docs_access::{basic_credentials, same_secret},
+  },
+  utils::{custom_claims::RESERVED_CLAIMS, jwt_util::JwtUtil},
+};
+
+// `auth.public_url`, or the scheme and host the request came in on
+fn public_url(req: &HttpRequest, data: &AppState) -> String {
+  match &data.config.auth.public_url {
+    Some(public_url) => public_url.trim_end_matches('/').to_string(),
+    None => {
+      let info = req.connection_info();
+      format!("{}://{}", info.scheme(), info.host())
+    }
+  }
+}
+
+document!(openid_configuration);
+#[utoipa::path(
+    get,
+    path = "/.well-known/openid-configuration",
+    tag = "Authentication",
+    responses(
+        (
+            status=200,
+            description= "Where and how tokens of this API can be validated",
+            body= OpenIdConfigurationResDto
+        ),
+    )
+)]
+pub async fn openid_configuration(req: HttpRequest, data: web::Data<AppState>) -> impl Responder {
+  let claims_supported = RESERVED_CLAIMS
+    .iter()
+    .map(|claim| claim.to_string())
+    .chain(data.config.jwt.static_claims.keys().cloned())
+    .collect();
+  HttpResponse::Ok().json(OpenIdConfigurationResDto {
+    issuer: data.config.jwt.issuer.clone(),
+    introspection_endpoint: format!(
+      "{}{}/auth/introspect",
+      public_url(&req, &data),
+      ApiVersion::LATEST.prefix()
+    ),
+    introspection_endpoint_auth_methods_supported: vec!["client_secret_basic".to_string()],
+    id_token_signing_alg_values_supported: vec!["HS256".to_string()],
+    subject_types_supported: vec!["public".to_string()],
+    claims_supported,
+  })
+}
+
+// One of `auth.introspection_clients`, every client is compared so timing doesn't tell which
+fn is_introspection_client(req: &HttpRequest, data: &AppState) -> bool {
+  let Some((client_id, client_secret)) = basic_credentials(req) else {
+    return false;
+  };
+  data
+    .config
+    .auth
+    .introspection_clients
+    .iter()
+    .fold(false, |found, client| {
+      found
+        | (same_secret(&client_id, &client.client_id)
+          & same_secret(&client_secret, &client.client_secret))
+    })
+}
+
+// What the API itself would accept: a valid token whose user is still active
+async fn introspect(data: &AppState, token: &str) -> Result<IntrospectResDto, actix_web::Error> {
+  let Ok(claims) = JwtUtil::from_state(data).decode_token(token) else {
+    return Ok(IntrospectResDto::default());
+  };
+  let user = match load_subject(data, &claims).await {
+    Ok((user, _)) => user,
+    // A failed lookup isn't an answer about the token
+    Err(e) if e.as_response_error().status_code().is_server_error() => return Err(e),
+    Err(_) => return Ok(IntrospectResDto::default()),
+  };
+  Ok(IntrospectResDto {
+    active: true,
+    token_type: Some("Bearer".to_string()),
+    sub: Some(user.id.to_string()),
+    username: Some(user.user_name.clone()),
+    exp: Some(claims.exp),
+    iss: Some(claims.iss),
+    aud: Some(claims.aud),
+    scope: Some(claims.permissions.join(" ")),
+    roles: Some(user.roles),
+    act: claims.act_as.map(|_| ActorDto {
+      sub: claims.sub.to_string(),
+    }),
+    dfp: claims.dfp,
+    custom: claims.custom,
+  })
+}
+
+document!(introspect_token);
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/introspect",
+    tag = "Authentication",
+    request_body(
+        content = IntrospectReqDto,
+        content_type = "application/x-www-form-urlencoded",
+        description = "Token to inspect, the caller authenticates with HTTP Basic auth"
+    ),
+    responses(
+        (
+            status=200,
+            description= "State of the token, `{\"active\": false}` when it isn't usable",
+            body= IntrospectResDto
+        ),
+        (
+            status=401,
+            description= "Not one of `auth.introspection_clients`",
+            body= Status
+        ),
+    ),
+    security(("basic_auth" = []))
+)]
+pub async fn introspect_token(
+  req: HttpRequest,
+  form: web::Form<IntrospectReqDto>,
+  data: web::Data<AppState>,
+) -> impl Responder {
+  if !is_introspection_client(&req, &data) {
+    let mut res = Status::unauthorized(StatusMessage::Unauthorized.to_str()).into_http_response();
+    res.headers_mut().insert(
+      header::WWW_AUTHENTICATE,
+      header::HeaderValue::from_static("Basic realm=\"introspection\""),
+    );
+    return res;
+  }
+  match introspect(&data, &form.token).await {
+    Ok(introspection) => HttpResponse::Ok()
+      .insert_header(CacheControl(vec![CacheDirective::NoStore]))
+      .json(introspection),
+    Err(e) => e.error_response(),
+  }
+}
@@ -0,0 +1,12 @@
+use actix_web::{Resource, Scope, web};
+
+use crate::features::oidc::oidc_handler::{introspect_token, openid_configuration};
+
+/// `/.well-known`, mounted at the root where clients look for it.
+pub fn well_known_routes() -> Scope {
+  web::scope("/.well-known").route("/openid-configuration", web::get().to(openid_configuration))
+}
+
+pub fn introspect_route() -> Resource {
+  web::resource("/auth/introspect").route(web::post().to(introspect_token))
+}
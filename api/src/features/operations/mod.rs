@@ -0,0 +1,3 @@
+pub mod operations_dto;
+pub mod operations_handler;
+pub mod operations_route;
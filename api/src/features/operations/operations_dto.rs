@@ -0,0 +1,43 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::utils::operations::{Operation, OperationStatus};
+
+// ---------- Response Dto --------- //
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub struct OperationResDto {
+  pub id: String,
+  /// What is being done, e.g. `user_report`
+  pub kind: String,
+  pub status: OperationStatus,
+  /// Percent done, 100 once it succeeded
+  pub progress: u8,
+  pub created_at: DateTime<Utc>,
+  pub finished_at: Option<DateTime<Utc>>,
+  /// Why it failed, set once `status` is `failed`
+  pub error: Option<String>,
+  /// Where the result file is downloaded, set once `status` is `succeeded`
+  pub result_url: Option<String>,
+}
+
+impl OperationResDto {
+  /// `prefix` is the API version the operation is polled under, e.g. `/api/v1`.
+  pub fn new(operation: Operation, prefix: &str) -> Self {
+    let result_url = operation
+      .result
+      .as_ref()
+      .map(|_| format!("{}/operations/{}/result", prefix, operation.id));
+    OperationResDto {
+      id: operation.id,
+      kind: operation.kind,
+      status: operation.status,
+      progress: operation.progress,
+      created_at: operation.created_at,
+      finished_at: operation.finished_at,
+      error: operation.error,
+      result_url,
+    }
+  }
+}
@@ -0,0 +1,128 @@
+use actix_web::{
+  HttpRequest, HttpResponse, Responder,
+  http::header::{CONTENT_TYPE, ContentDisposition, DispositionParam, DispositionType},
+  web,
+};
+
+use crate::{
+  api_version::ApiVersion,
+  app_state::AppState,
+  document,
+  dto::base_res_dto::{BaseResDto, Status},
+  error::StatusMessage,
+  features::{operations::operations_dto::OperationResDto, users::user_entity::UserRole},
+  middleware::auth::Authenticated,
+  utils::operations::Operation,
+};
+
+/// `/api/<version>` of the request, operation URLs are handed out under the same version.
+pub fn version_prefix(req: &HttpRequest) -> String {
+  ApiVersion::of_path(req.path())
+    .unwrap_or(ApiVersion::LATEST)
+    .prefix()
+}
+
+// Someone else's operation answers 404 as if it didn't exist, admins see every operation
+fn find_operation(data: &AppState, id: &str, current_user: &Authenticated) -> Option<Operation> {
+  data.operations.get(id).filter(|operation| {
+    operation.owner_id == current_user.id || current_user.has_role(UserRole::Admin.to_str())
+  })
+}
+
+document!(get_operation);
+#[utoipa::path(
+    get,
+    path = "/api/v1/operations/{id}",
+    tag = "Operations",
+    params(("id" = String, Path, description = "Operation id, as returned with the 202")),
+    responses( 
+        (
+            status=200, 
+            description= "Status and progress of the operation, with `result_url` once it succeeded", 
+            body= BaseResDto<OperationResDto>
+        ),
+        (
+            status=401, 
+            description= "Unauthorized", 
+            body= Status
+        ),
+        (
+            status=404, 
+            description= "No such operation, or it expired after `operations.retention_secs`", 
+            body= Status
+        ),
+    )
+)]
+pub async fn get_operation(
+  id: web::Path<String>,
+  req: HttpRequest,
+  current_user: Authenticated,
+  data: web::Data<AppState>,
+) -> impl Responder {
+  match find_operation(&data, &id, &current_user) {
+    Some(operation) => HttpResponse::Ok().json(Status::success_with_data(OperationResDto::new(
+      operation,
+      &version_prefix(&req),
+    ))),
+    None => {
+      Status::not_found(StatusMessage::NotFound("Operation".to_string())).into_http_response()
+    }
+  }
+}
+
+document!(get_operation_result);
+#[utoipa::path(
+    get,
+    path = "/api/v1/operations/{id}/result",
+    tag = "Operations",
+    params(("id" = String, Path, description = "Operation id, as returned with the 202")),
+    responses( 
+        (
+            status=200, 
+            description= "The file the operation produced, as a download", 
+            content(
+                (Vec<u8> = "application/pdf"),
+                (Vec<u8> = "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet")
+            )
+        ),
+        (
+            status=401, 
+            description= "Unauthorized", 
+            body= Status
+        ),
+        (
+            status=404, 
+            description= "No such operation, it hasn't succeeded yet, or it expired", 
+            body= Status
+        ),
+        (
+            status=500, 
+            description= "Internal Server Error", 
+            body= Status 
+        ),
+    )
+)]
+pub async fn get_operation_result(
+  id: web::Path<String>,
+  current_user: Authenticated,
+  data: web::Data<AppState>,
+) -> impl Responder {
+  let Some(result) = find_operation(&data, &id, &current_user).and_then(|op| op.result) else {
+    return Status::not_found(StatusMessage::NotFound("Operation result".to_string()))
+      .into_http_response();
+  };
+  match data.storage.get(&result.key).await {
+    Ok(Some(object)) => HttpResponse::Ok()
+      .insert_header((CONTENT_TYPE, object.content_type))
+      .insert_header(ContentDisposition {
+        disposition: DispositionType::Attachment,
+        parameters: vec![DispositionParam::Filename(result.file_name)],
+      })
+      .body(object.bytes),
+    Ok(None) => Status::not_found(StatusMessage::NotFound("Operation result".to_string()))
+      .into_http_response(),
+    Err(e) => {
+      Status::server_error(format!("Failed to read operation result: {}", e)).into_http_response()
+    }
+  }
+}
@@ -0,0 +1,22 @@
+use actix_web::{Scope, web};
+
+use crate::{
+  features::{
+    operations::operations_handler::{get_operation, get_operation_result},
+    users::user_entity::UserRole,
+  },
+  middleware::auth::RequireAuth,
+};
+
+fn any_user() -> RequireAuth {
+  RequireAuth::allow_roles(vec![UserRole::User, UserRole::Moderator, UserRole::Admin])
+}
+
+pub fn operations_routes() -> Scope {
+  web::scope("/operations")
+    .route("/{id}", web::get().to(get_operation).wrap(any_user()))
+    .route(
+      "/{id}/result",
+      web::get().to(get_operation_result).wrap(any_user()),
+    )
+}
@@ -0,0 +1,5 @@
+pub mod permissions_dto;
+pub mod permissions_entity;
+pub mod permissions_handler;
+pub mod permissions_repo;
+pub mod permissions_route;
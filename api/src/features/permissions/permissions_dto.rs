@@ -0,0 +1,73 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::{Validate, ValidationError};
+
+use crate::features::permissions::permissions_entity::PermissionEntity;
+
+#[derive(Deserialize, Serialize, Clone, ToSchema)]
+pub struct PermissionDto {
+  pub id: i32,
+  pub name: String,
+  pub description: Option<String>,
+}
+
+impl From<&PermissionEntity> for PermissionDto {
+  fn from(value: &PermissionEntity) -> Self {
+    Self {
+      id: value.id,
+      name: value.name.clone(),
+      description: value.description.clone(),
+    }
+  }
+}
+
+// --- Request Dto --- //
+
+// Permission names follow `resource:action`, e.g. `user:read`
+fn validate_permission_name(name: &str) -> Result<(), ValidationError> {
+  let valid = match name.split_once(':') {
+    Some((resource, action)) => {
+      !resource.is_empty() && !action.is_empty() && !action.contains(':') && !name.contains(' ')
+    }
+    None => false,
+  };
+  if valid {
+    Ok(())
+  } else {
+    Err(ValidationError::new("permission_name").with_message("Must be `resource:action`".into()))
+  }
+}
+
+#[derive(Deserialize, Clone, ToSchema, Validate)]
+pub struct CreatePermissionReqDto {
+  /// `resource:action`, e.g. `user:read`
+  #[validate(length(max = 100), custom(function = validate_permission_name))]
+  pub name: String,
+  #[validate(length(max = 255))]
+  pub description: Option<String>,
+}
+
+#[derive(Deserialize, Clone, ToSchema, Validate)]
+pub struct UpdatePermissionReqDto {
+  pub id: i32,
+  #[validate(length(max = 100), custom(function = validate_permission_name))]
+  pub name: String,
+  #[validate(length(max = 255))]
+  pub description: Option<String>,
+}
+
+#[derive(Deserialize, Clone, ToSchema)]
+pub struct DeletePermissionReqDto {
+  pub id: i32,
+}
+
+#[derive(Deserialize, Clone, ToSchema)]
+pub struct GetRolePermissionsReqDto {
+  pub role_id: i32,
+}
+
+#[derive(Deserialize, Clone, ToSchema)]
+pub struct RolePermissionReqDto {
+  pub role_id: i32,
+  pub permission_id: i32,
+}
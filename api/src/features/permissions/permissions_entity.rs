@@ -0,0 +1,56 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use domner_tech_sql_client::pool_manager::DbRow;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  db::{FromRow, RowExt},
+  features::permissions::permissions_dto::{CreatePermissionReqDto, UpdatePermissionReqDto},
+};
+
+// A named capability such as `user:read` or `role:write`
+#[derive(Deserialize, Serialize, Clone)]
+pub struct PermissionEntity {
+  pub id: i32,
+  pub name: String,
+  pub description: Option<String>,
+  pub created_at: DateTime<Utc>,
+  pub updated_at: DateTime<Utc>,
+}
+
+impl FromRow for PermissionEntity {
+  fn from_row(row: &DbRow<'_>) -> Result<Self> {
+    Ok(Self {
+      id: row.column("id")?,
+      name: row.column("name")?,
+      description: row.nullable("description")?,
+      created_at: row.column("created_at")?,
+      // NULL until the permission is first updated
+      updated_at: row.nullable("updated_at")?.unwrap_or_default(),
+    })
+  }
+}
+
+impl From<CreatePermissionReqDto> for PermissionEntity {
+  fn from(value: CreatePermissionReqDto) -> Self {
+    Self {
+      id: 0,
+      name: value.name,
+      description: value.description,
+      created_at: Utc::now(),
+      updated_at: Utc::now(),
+    }
+  }
+}
+
+impl From<UpdatePermissionReqDto> for PermissionEntity {
+  fn from(value: UpdatePermissionReqDto) -> Self {
+    Self {
+      id: value.id,
+      name: value.name,
+      description: value.description,
+      created_at: Utc::now(),
+      updated_at: Utc::now(),
+    }
+  }
+}
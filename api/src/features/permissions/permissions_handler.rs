@@ -0,0 +1,673 @@
+use actix_web::{HttpRequest, HttpResponse, Responder, web};
+
+use crate::{
+  app_state::AppState,
+  document,
+  dto::{
+    base_res_dto::{BaseResDto, Status},
+    validated_json::ValidatedJson,
+  },
+  error::StatusMessage,
+  features::{
+    audit::{
+      audit_entity::{AuditAction, AuditLogEntity},
+      audit_repo::AuditRepo,
+    },
+    permissions::{
+      permissions_dto::{
+        CreatePermissionReqDto, DeletePermissionReqDto, GetRolePermissionsReqDto, PermissionDto,
+        RolePermissionReqDto, UpdatePermissionReqDto,
+      },
+      permissions_entity::PermissionEntity,
+    },
+  },
+  middleware::auth::Authenticated,
+};
+
+document!(get_permissions);
+#[utoipa::path(
+    post,
+    path = "/api/v1/permission/all",
+    tag = "Permissions",
+    request_body(
+        content = (),
+        description = "",
+        example = json!({})),
+    responses( 
+        (
+            status=200, 
+            description= "Get permissions successfully", 
+            body= BaseResDto<Vec<PermissionDto>> 
+        ),
+        (
+            status=400, 
+            description= "Validation Errors", 
+            body= Status
+        ),
+    )
+)]
+pub async fn get_permissions(data: web::Data<AppState>) -> impl Responder {
+  let mut repo = data.permission_repo();
+  match repo.get_permissions().await {
+    Ok(permissions) => {
+      let permissions_dto: Vec<PermissionDto> =
+        permissions.iter().map(PermissionDto::from).collect();
+      HttpResponse::Ok().json(Status::success_with_data(permissions_dto))
+    }
+    Err(e) => Status::bad_request(format!("Failed to get permissions: {}", e))
+      .or_query_timeout(&e)
+      .into_http_response(),
+  }
+}
+
+document!(create_permission);
+#[utoipa::path(
+    post,
+    path = "/api/v1/permission/create",
+    tag = "Permissions",
+    request_body(
+        content = CreatePermissionReqDto,
+        description = "",
+        example = json!(
+            {
+                "name": "user:read",
+                "description": "Read users"
+            })),
+    responses( 
+        (
+            status=200, 
+            description= "Permission created successfully", 
+            body= Status 
+        ),
+        (
+            status=400, 
+            description= "Validation Errors", 
+            body= Status
+        ),
+    )
+)]
+pub async fn create_permission(
+  permission: ValidatedJson<CreatePermissionReqDto>,
+  current_user: Authenticated,
+  http_req: HttpRequest,
+  data: web::Data<AppState>,
+) -> impl Responder {
+  let mut repo = data.permission_repo();
+  match repo.get_by_name(&permission.name).await {
+    Ok(Some(_)) => Status::bad_request(StatusMessage::Existed(format!(
+      "Permission with name '{}'",
+      permission.name
+    )))
+    .into_http_response(),
+    Ok(None) => {
+      let entity = PermissionEntity::from(permission.into_inner());
+      match repo.create_permission(&entity).await {
+        Ok(_) => {
+          AuditRepo::new(&data)
+            .record(
+              AuditLogEntity::new(
+                AuditAction::CreatePermission,
+                Some(current_user.id),
+                format!("permission:{}", entity.name),
+              )
+              .with_request(&http_req),
+            )
+            .await;
+          HttpResponse::Ok().json(Status::success())
+        }
+        Err(e) => Status::bad_request(format!("Failed to create permission: {}", e))
+          .or_query_timeout(&e)
+          .into_http_response(),
+      }
+    }
+    Err(e) => Status::bad_request(format!("Failed to create permission: {}", e))
+      .or_query_timeout(&e)
+      .into_http_response(),
+  }
+}
+
+document!(update_permission);
+#[utoipa::path(
+    post,
+    path = "/api/v1/permission/update",
+    tag = "Permissions",
+    request_body(
+        content = UpdatePermissionReqDto,
+        description = "",
+        example = json!(
+            {
+                "id": 1,
+                "name": "user:read",
+                "description": "Read users"
+            })),
+    responses( 
+        (
+            status=200, 
+            description= "Permission updated successfully", 
+            body= Status 
+        ),
+        (
+            status=400, 
+            description= "Validation Errors", 
+            body= Status
+        ),
+        (
+            status=404, 
+            description= "Permission not found", 
+            body= Status
+        ),
+    )
+)]
+pub async fn update_permission(
+  permission: ValidatedJson<UpdatePermissionReqDto>,
+  current_user: Authenticated,
+  http_req: HttpRequest,
+  data: web::Data<AppState>,
+) -> impl Responder {
+  let mut repo = data.permission_repo();
+  match repo.get_by_id(permission.id).await {
+    Ok(Some(_)) => {
+      if let Ok(Some(existed)) = repo.get_by_name(&permission.name).await
+        && existed.id != permission.id
+      {
+        return Status::bad_request(StatusMessage::Existed(format!(
+          "Permission name '{}'",
+          permission.name
+        )))
+        .into_http_response();
+      }
+
+      let entity = PermissionEntity::from(permission.into_inner());
+      match repo.update_permission(&entity).await {
+        Ok(_) => {
+          data.invalidate_access_cache();
+          AuditRepo::new(&data)
+            .record(
+              AuditLogEntity::new(
+                AuditAction::UpdatePermission,
+                Some(current_user.id),
+                format!("permission:{}", entity.id),
+              )
+              .with_details(format!("name={}", entity.name))
+              .with_request(&http_req),
+            )
+            .await;
+          HttpResponse::Ok().json(Status::success())
+        }
+        Err(e) => Status::bad_request(format!("Failed to update permission: {}", e))
+          .or_query_timeout(&e)
+          .into_http_response(),
+      }
+    }
+    Ok(None) => Status::not_found(StatusMessage::NotFound(format!(
+      "Permission with id '{}'",
+      permission.id
+    )))
+    .into_http_response(),
+    Err(e) => Status::bad_request(format!("Failed to update permission: {}", e))
+      .or_query_timeout(&e)
+      .into_http_response(),
+  }
+}
+
+document!(delete_permission);
+#[utoipa::path(
+    post,
+    path = "/api/v1/permission/delete",
+    tag = "Permissions",
+    request_body(
+        content = DeletePermissionReqDto,
+        description = "",
+        example = json!({ "id": 1 })),
+    responses( 
+        (
+            status=200, 
+            description= "Permission deleted successfully", 
+            body= Status 
+        ),
+        (
+            status=404, 
+            description= "Permission not found", 
+            body= Status
+        ),
+    )
+)]
+pub async fn delete_permission(
+  r: web::Json<DeletePermissionReqDto>,
+  current_user: Authenticated,
+  http_req: HttpRequest,
+  data: web::Data<AppState>,
+) -> impl Responder {
+  let mut repo = data.permission_repo();
+  match repo.get_by_id(r.id).await {
+    Ok(Some(permission)) => match repo.delete_permission(r.id).await {
+      Ok(_) => {
+        data.invalidate_access_cache();
+        AuditRepo::new(&data)
+          .record(
+            AuditLogEntity::new(
+              AuditAction::DeletePermission,
+              Some(current_user.id),
+              format!("permission:{}", r.id),
+            )
+            .with_details(format!("name={}", permission.name))
+            .with_request(&http_req),
+          )
+          .await;
+        HttpResponse::Ok().json(Status::success())
+      }
+      Err(e) => Status::bad_request(format!("Failed to delete permission: {}", e))
+        .or_query_timeout(&e)
+        .into_http_response(),
+    },
+    Ok(None) => Status::not_found(StatusMessage::NotFound(format!(
+      "Permission with id '{}'",
+      r.id
+    )))
+    .into_http_response(),
+    Err(e) => Status::bad_request(format!("Failed to delete permission: {}", e))
+      .or_query_timeout(&e)
+      .into_http_response(),
+  }
+}
+
+document!(get_role_permissions);
+#[utoipa::path(
+    post,
+    path = "/api/v1/permission/role_permissions",
+    tag = "Permissions",
+    request_body(
+        content = GetRolePermissionsReqDto,
+        description = "",
+        example = json!({ "role_id": 1 })),
+    responses( 
+        (
+            status=200, 
+            description= "Get role permissions successfully", 
+            body= BaseResDto<Vec<PermissionDto>> 
+        ),
+        (
+            status=400, 
+            description= "Validation Errors", 
+            body= Status
+        ),
+    )
+)]
+pub async fn get_role_permissions(
+  r: web::Json<GetRolePermissionsReqDto>,
+  data: web::Data<AppState>,
+) -> impl Responder {
+  let mut repo = data.permission_repo();
+  match repo.get_role_permissions(r.role_id).await {
+    Ok(permissions) => {
+      let permissions_dto: Vec<PermissionDto> =
+        permissions.iter().map(PermissionDto::from).collect();
+      HttpResponse::Ok().json(Status::success_with_data(permissions_dto))
+    }
+    Err(e) => Status::bad_request(format!("Failed to get role permissions: {}", e))
+      .or_query_timeout(&e)
+      .into_http_response(),
+  }
+}
+
+// Both the role and the permission must exist before (un)linking them
+async fn ensure_role_and_permission(
+  r: &RolePermissionReqDto,
+  data: &AppState,
+) -> Result<(), HttpResponse> {
+  match data.role_repo().get_by_id(r.role_id).await {
+    Ok(Some(_)) => {}
+    Ok(None) => {
+      return Err(
+        Status::not_found(StatusMessage::NotFound(format!(
+          "Role with id '{}'",
+          r.role_id
+        )))
+        .into_http_response(),
+      );
+    }
+    Err(e) => return Err(Status::bad_request(e.to_string()).into_http_response()),
+  }
+  match data.permission_repo().get_by_id(r.permission_id).await {
+    Ok(Some(_)) => Ok(()),
+    Ok(None) => Err(
+      Status::not_found(StatusMessage::NotFound(format!(
+        "Permission with id '{}'",
+        r.permission_id
+      )))
+      .into_http_response(),
+    ),
+    Err(e) => Err(Status::bad_request(e.to_string()).into_http_response()),
+  }
+}
+
+document!(assign_role_permission);
+#[utoipa::path(
+    post,
+    path = "/api/v1/permission/assign",
+    tag = "Permissions",
+    request_body(
+        content = RolePermissionReqDto,
+        description = "",
+        example = json!({ "role_id": 1, "permission_id": 1 })),
+    responses( 
+        (
+            status=200, 
+            description= "Permission granted to role successfully", 
+            body= Status 
+        ),
+        (
+            status=404, 
+            description= "Role or permission not found", 
+            body= Status
+        ),
+    )
+)]
+pub async fn assign_role_permission(
+  r: web::Json<RolePermissionReqDto>,
+  current_user: Authenticated,
+  http_req: HttpRequest,
+  data: web::Data<AppState>,
+) -> impl Responder {
+  if let Err(res) = ensure_role_and_permission(&r, &data).await {
+    return res;
+  }
+
+  let mut repo = data.permission_repo();
+  match repo
+    .assign_role_permission(r.role_id, r.permission_id)
+    .await
+  {
+    Ok(_) => {
+      data.invalidate_access_cache();
+      AuditRepo::new(&data)
+        .record(
+          AuditLogEntity::new(
+            AuditAction::AssignRolePermission,
+            Some(current_user.id),
+            format!("role:{}", r.role_id),
+          )
+          .with_details(format!("permission_id={}", r.permission_id))
+          .with_request(&http_req),
+        )
+        .await;
+      HttpResponse::Ok().json(Status::success())
+    }
+    Err(e) => Status::bad_request(format!("Failed to grant permission: {}", e))
+      .or_query_timeout(&e)
+      .into_http_response(),
+  }
+}
+
+document!(revoke_role_permission);
+#[utoipa::path(
+    post,
+    path = "/api/v1/permission/revoke",
+    tag = "Permissions",
+    request_body(
+        content = RolePermissionReqDto,
+        description = "",
+        example = json!({ "role_id": 1, "permission_id": 1 })),
+    responses( 
+        (
+            status=200, 
+            description= "Permission revoked from role successfully", 
+            body= Status 
+        ),
+        (
+            status=404, 
+            description= "Role or permission not found", 
+            body= Status
+        ),
+    )
+)]
+pub async fn revoke_role_permission(
+  r: web::Json<RolePermissionReqDto>,
+  current_user: Authenticated,
+  http_req: HttpRequest,
+  data: web::Data<AppState>,
+) -> impl Responder {
+  if let Err(res) = ensure_role_and_permission(&r, &data).await {
+    return res;
+  }
+
+  let mut repo = data.permission_repo();
+  match repo
+    .revoke_role_permission(r.role_id, r.permission_id)
+    .await
+  {
+    Ok(_) => {
+      data.invalidate_access_cache();
+      AuditRepo::new(&data)
+        .record(
+          AuditLogEntity::new(
+            AuditAction::RevokeRolePermission,
+            Some(current_user.id),
+            format!("role:{}", r.role_id),
+          )
+          .with_details(format!("permission_id={}", r.permission_id))
+          .with_request(&http_req),
+        )
+        .await;
+      HttpResponse::Ok().json(Status::success())
+    }
+    Err(e) => Status::bad_request(format!("Failed to revoke permission: {}", e))
+      .or_query_timeout(&e)
+      .into_http_response(),
+  }
+}
+
+document!(list_permissions);
+#[utoipa::path(
+    get,
+    path = "/api/v1/permissions",
+    tag = "Permissions",
+    responses( 
+        (
+            status=200, 
+            description= "Get permissions successfully", 
+            body= BaseResDto<Vec<PermissionDto>>
+        ),
+    )
+)]
+pub async fn list_permissions(data: web::Data<AppState>) -> impl Responder {
+  get_permissions(data).await
+}
+
+document!(post_permission);
+#[utoipa::path(
+    post,
+    path = "/api/v1/permissions",
+    tag = "Permissions",
+    request_body(
+        content = CreatePermissionReqDto,
+        description = "",
+        example = json!({
+          "name": "report:read",
+          "description": "Read reports"
+        })),
+    responses( 
+        (
+            status=200, 
+            description= "Permission created successfully", 
+            body= Status
+        ),
+        (
+            status=400, 
+            description= "Validation Errors", 
+            body= Status
+        ),
+    )
+)]
+pub async fn post_permission(
+  permission: ValidatedJson<CreatePermissionReqDto>,
+  current_user: Authenticated,
+  http_req: HttpRequest,
+  data: web::Data<AppState>,
+) -> impl Responder {
+  create_permission(permission, current_user, http_req, data).await
+}
+
+document!(put_permission);
+#[utoipa::path(
+    put,
+    path = "/api/v1/permissions/{id}",
+    tag = "Permissions",
+    params(("id" = i32, Path, description = "Permission id")),
+    request_body(
+        content = CreatePermissionReqDto,
+        description = "",
+        example = json!({
+          "name": "report:read",
+          "description": "Read reports"
+        })),
+    responses( 
+        (
+            status=200, 
+            description= "Permission updated successfully", 
+            body= Status
+        ),
+        (
+            status=400, 
+            description= "Validation Errors", 
+            body= Status
+        ),
+    )
+)]
+pub async fn put_permission(
+  id: web::Path<i32>,
+  permission: ValidatedJson<CreatePermissionReqDto>,
+  current_user: Authenticated,
+  http_req: HttpRequest,
+  data: web::Data<AppState>,
+) -> impl Responder {
+  let permission = permission.into_inner();
+  let permission = UpdatePermissionReqDto {
+    id: id.into_inner(),
+    name: permission.name,
+    description: permission.description,
+  };
+  update_permission(ValidatedJson(permission), current_user, http_req, data).await
+}
+
+document!(remove_permission);
+#[utoipa::path(
+    delete,
+    path = "/api/v1/permissions/{id}",
+    tag = "Permissions",
+    params(("id" = i32, Path, description = "Permission id")),
+    responses( 
+        (
+            status=200, 
+            description= "Permission deleted successfully", 
+            body= Status
+        ),
+        (
+            status=404, 
+            description= "Permission not found", 
+            body= Status
+        ),
+    )
+)]
+pub async fn remove_permission(
+  id: web::Path<i32>,
+  current_user: Authenticated,
+  http_req: HttpRequest,
+  data: web::Data<AppState>,
+) -> impl Responder {
+  let r = DeletePermissionReqDto {
+    id: id.into_inner(),
+  };
+  delete_permission(web::Json(r), current_user, http_req, data).await
+}
+
+document!(list_role_permissions);
+#[utoipa::path(
+    get,
+    path = "/api/v1/roles/{id}/permissions",
+    tag = "Permissions",
+    params(("id" = i32, Path, description = "Role id")),
+    responses( 
+        (
+            status=200, 
+            description= "Get role permissions successfully", 
+            body= BaseResDto<Vec<PermissionDto>>
+        ),
+    )
+)]
+pub async fn list_role_permissions(
+  id: web::Path<i32>,
+  data: web::Data<AppState>,
+) -> impl Responder {
+  let r = GetRolePermissionsReqDto {
+    role_id: id.into_inner(),
+  };
+  get_role_permissions(web::Json(r), data).await
+}
+
+document!(put_role_permission);
+#[utoipa::path(
+    put,
+    path = "/api/v1/roles/{role_id}/permissions/{permission_id}",
+    tag = "Permissions",
+    params(
+        ("role_id" = i32, Path, description = "Role id"),
+        ("permission_id" = i32, Path, description = "Permission id")
+    ),
+    responses( 
+        (
+            status=200, 
+            description= "Permission granted to the role", 
+            body= Status
+        ),
+        (
+            status=404, 
+            description= "Role or permission not found", 
+            body= Status
+        ),
+    )
+)]
+pub async fn put_role_permission(
+  path: web::Path<(i32, i32)>,
+  current_user: Authenticated,
+  http_req: HttpRequest,
+  data: web::Data<AppState>,
+) -> impl Responder {
+  let (role_id, permission_id) = path.into_inner();
+  let r = RolePermissionReqDto {
+    role_id,
+    permission_id,
+  };
+  assign_role_permission(web::Json(r), current_user, http_req, data).await
+}
+
+document!(remove_role_permission);
+#[utoipa::path(
+    delete,
+    path = "/api/v1/roles/{role_id}/permissions/{permission_id}",
+    tag = "Permissions",
+    params(
+        ("role_id" = i32, Path, description = "Role id"),
+        ("permission_id" = i32, Path, description = "Permission id")
+    ),
+    responses( 
+        (
+            status=200, 
+            description= "Permission revoked from the role", 
+            body= Status
+        ),
+    )
+)]
+pub async fn remove_role_permission(
+  path: web::Path<(i32, i32)>,
+  current_user: Authenticated,
+  http_req: HttpRequest,
+  data: web::Data<AppState>,
+) -> impl Responder {
+  let (role_id, permission_id) = path.into_inner();
+  let r = RolePermissionReqDto {
+    role_id,
+    permission_id,
+  };
+  revoke_role_permission(web::Json(r), current_user, http_req, data).await
+}
@@ -0,0 +1,261 @@
+use crate::{
+  app_state::AppState,
+  db::{DbConnection, RowExt, SqlRepo},
+  features::permissions::permissions_entity::PermissionEntity,
+};
+
+use anyhow::Result;
+use domner_tech_sql_client::{CommandType, UnifiedToSql};
+use futures::future::LocalBoxFuture;
+
+/// Data access for permissions and their grants to roles, see `UserRepository`.
+pub trait PermissionRepository {
+  fn create_permission<'b>(
+    &'b mut self,
+    permission: &'b PermissionEntity,
+  ) -> LocalBoxFuture<'b, Result<u64>>;
+
+  fn update_permission<'b>(
+    &'b mut self,
+    permission: &'b PermissionEntity,
+  ) -> LocalBoxFuture<'b, Result<u64>>;
+
+  // Also removes the permission from every role
+  fn delete_permission<'b>(&'b mut self, id: i32) -> LocalBoxFuture<'b, Result<u64>>;
+
+  fn get_by_id<'b>(&'b mut self, id: i32) -> LocalBoxFuture<'b, Result<Option<PermissionEntity>>>;
+
+  fn get_by_name<'b>(
+    &'b mut self,
+    name: &'b str,
+  ) -> LocalBoxFuture<'b, Result<Option<PermissionEntity>>>;
+
+  fn get_permissions<'b>(&'b mut self) -> LocalBoxFuture<'b, Result<Vec<PermissionEntity>>>;
+
+  fn get_role_permissions<'b>(
+    &'b mut self,
+    role_id: i32,
+  ) -> LocalBoxFuture<'b, Result<Vec<PermissionEntity>>>;
+
+  // Effective permissions, the union over every role assigned to the user
+  fn get_user_permissions<'b>(
+    &'b mut self,
+    user_id: i32,
+  ) -> LocalBoxFuture<'b, Result<Vec<String>>>;
+
+  fn assign_role_permission<'b>(
+    &'b mut self,
+    role_id: i32,
+    permission_id: i32,
+  ) -> LocalBoxFuture<'b, Result<u64>>;
+
+  fn revoke_role_permission<'b>(
+    &'b mut self,
+    role_id: i32,
+    permission_id: i32,
+  ) -> LocalBoxFuture<'b, Result<u64>>;
+}
+
+pub struct PermissionRepo<'a> {
+  pub app_state: &'a AppState,
+}
+
+impl<'a> PermissionRepo<'a> {
+  pub fn new(app_state: &'a AppState) -> Self {
+    Self { app_state }
+  }
+
+  async fn get_client(&self) -> DbConnection {
+    match self
+      .app_state
+      .db_manager
+      .get_client(&self.app_state.config.database.sql_server.pool_name)
+      .await
+    {
+      Ok(client) => client,
+      Err(e) => panic!("Failed to get DB client: {}", e),
+    }
+  }
+}
+
+impl<'a> PermissionRepository for PermissionRepo<'a> {
+  fn create_permission<'b>(
+    &'b mut self,
+    permission: &'b PermissionEntity,
+  ) -> LocalBoxFuture<'b, Result<u64>> {
+    Box::pin(async move {
+      let mut client_pool = self.get_client().await;
+
+      let description = permission.description.clone().unwrap_or_default();
+      let params: Vec<&dyn UnifiedToSql> = vec![&permission.name, &description];
+      let result = SqlRepo::execute_command_none_query(
+        &mut client_pool,
+        "[dbo].[create_permission]",
+        &params,
+        CommandType::StoreProcedure,
+      )
+      .await?;
+      Ok(result)
+    })
+  }
+
+  fn update_permission<'b>(
+    &'b mut self,
+    permission: &'b PermissionEntity,
+  ) -> LocalBoxFuture<'b, Result<u64>> {
+    Box::pin(async move {
+      let mut client_pool = self.get_client().await;
+
+      let description = permission.description.clone().unwrap_or_default();
+      let params: Vec<&dyn UnifiedToSql> = vec![&permission.id, &permission.name, &description];
+      let result = SqlRepo::execute_command_none_query(
+        &mut client_pool,
+        "[dbo].[update_permission]",
+        &params,
+        CommandType::StoreProcedure,
+      )
+      .await?;
+      Ok(result)
+    })
+  }
+
+  // Also removes the permission from every role
+  fn delete_permission<'b>(&'b mut self, id: i32) -> LocalBoxFuture<'b, Result<u64>> {
+    Box::pin(async move {
+      let mut client_pool = self.get_client().await;
+
+      let result = SqlRepo::execute_command_none_query(
+        &mut client_pool,
+        "[dbo].[delete_permission]",
+        &[&id],
+        CommandType::StoreProcedure,
+      )
+      .await?;
+      Ok(result)
+    })
+  }
+
+  fn get_by_id<'b>(&'b mut self, id: i32) -> LocalBoxFuture<'b, Result<Option<PermissionEntity>>> {
+    Box::pin(async move {
+      let mut client_pool = self.get_client().await;
+
+      let permission = SqlRepo::execute_single_query_as::<PermissionEntity>(
+        &mut client_pool,
+        "[dbo].[select_permission_by_id]",
+        &[&id],
+        CommandType::StoreProcedure,
+      )
+      .await?;
+      Ok(permission)
+    })
+  }
+
+  fn get_by_name<'b>(
+    &'b mut self,
+    name: &'b str,
+  ) -> LocalBoxFuture<'b, Result<Option<PermissionEntity>>> {
+    Box::pin(async move {
+      let mut client_pool = self.get_client().await;
+
+      let permission = SqlRepo::execute_single_query_as::<PermissionEntity>(
+        &mut client_pool,
+        "[dbo].[select_permission_by_name]",
+        &[&name],
+        CommandType::StoreProcedure,
+      )
+      .await?;
+      Ok(permission)
+    })
+  }
+
+  fn get_permissions<'b>(&'b mut self) -> LocalBoxFuture<'b, Result<Vec<PermissionEntity>>> {
+    Box::pin(async move {
+      let mut client_pool = self.get_client().await;
+
+      let permissions = SqlRepo::execute_query_as::<PermissionEntity>(
+        &mut client_pool,
+        "[dbo].[select_permissions]",
+        &[],
+        CommandType::StoreProcedure,
+      )
+      .await?;
+      Ok(permissions)
+    })
+  }
+
+  fn get_role_permissions<'b>(
+    &'b mut self,
+    role_id: i32,
+  ) -> LocalBoxFuture<'b, Result<Vec<PermissionEntity>>> {
+    Box::pin(async move {
+      let mut client_pool = self.get_client().await;
+
+      let permissions = SqlRepo::execute_query_as::<PermissionEntity>(
+        &mut client_pool,
+        "[dbo].[select_role_permissions]",
+        &[&role_id],
+        CommandType::StoreProcedure,
+      )
+      .await?;
+      Ok(permissions)
+    })
+  }
+
+  // Effective permissions, the union over every role assigned to the user
+  fn get_user_permissions<'b>(
+    &'b mut self,
+    user_id: i32,
+  ) -> LocalBoxFuture<'b, Result<Vec<String>>> {
+    Box::pin(async move {
+      let mut client_pool = self.get_client().await;
+
+      let permissions = SqlRepo::execute_command_query(
+        &mut client_pool,
+        "[dbo].[select_user_permissions]",
+        &[&user_id],
+        CommandType::StoreProcedure,
+        |row| row.column("name"),
+      )
+      .await?;
+      Ok(permissions)
+    })
+  }
+
+  fn assign_role_permission<'b>(
+    &'b mut self,
+    role_id: i32,
+    permission_id: i32,
+  ) -> LocalBoxFuture<'b, Result<u64>> {
+    Box::pin(async move {
+      let mut client_pool = self.get_client().await;
+
+      let result = SqlRepo::execute_command_none_query(
+        &mut client_pool,
+        "[dbo].[assign_role_permission]",
+        &[&role_id, &permission_id],
+        CommandType::StoreProcedure,
+      )
+      .await?;
+      Ok(result)
+    })
+  }
+
+  fn revoke_role_permission<'b>(
+    &'b mut self,
+    role_id: i32,
+    permission_id: i32,
+  ) -> LocalBoxFuture<'b, Result<u64>> {
+    Box::pin(async move {
+      let mut client_pool = self.get_client().await;
+
+      let result = SqlRepo::execute_command_none_query(
+        &mut client_pool,
+        "[dbo].[revoke_role_permission]",
+        &[&role_id, &permission_id],
+        CommandType::StoreProcedure,
+      )
+      .await?;
+      Ok(result)
+    })
+  }
+}
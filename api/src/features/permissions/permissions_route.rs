@@ -0,0 +1,83 @@
+use actix_web::{Scope, web};
+
+use crate::features::permissions::permissions_handler::{
+  assign_role_permission, create_permission, delete_permission, get_permissions,
+  get_role_permissions, list_permissions, post_permission, put_permission, remove_permission,
+  revoke_role_permission, update_permission,
+};
+use crate::middleware::auth::RequirePermission;
+
+pub fn permissions_routes() -> Scope {
+  web::scope("/permissions")
+    .route(
+      "",
+      web::get()
+        .to(list_permissions)
+        .wrap(RequirePermission::new("permission:read")),
+    )
+    .route(
+      "",
+      web::post()
+        .to(post_permission)
+        .wrap(RequirePermission::new("permission:create")),
+    )
+    .route(
+      "/{id}",
+      web::put()
+        .to(put_permission)
+        .wrap(RequirePermission::new("permission:update")),
+    )
+    .route(
+      "/{id}",
+      web::delete()
+        .to(remove_permission)
+        .wrap(RequirePermission::new("permission:delete")),
+    )
+}
+
+// Deprecated aliases of `/permissions` and `/roles/{id}/permissions`, kept until clients moved
+pub fn permission_routes() -> Scope {
+  web::scope("/permission")
+    .route(
+      "/all",
+      web::post()
+        .to(get_permissions)
+        .wrap(RequirePermission::new("permission:read")),
+    )
+    .route(
+      "/create",
+      web::post()
+        .to(create_permission)
+        .wrap(RequirePermission::new("permission:create")),
+    )
+    .route(
+      "/update",
+      web::post()
+        .to(update_permission)
+        .wrap(RequirePermission::new("permission:update")),
+    )
+    .route(
+      "/delete",
+      web::post()
+        .to(delete_permission)
+        .wrap(RequirePermission::new("permission:delete")),
+    )
+    .route(
+      "/role_permissions",
+      web::post()
+        .to(get_role_permissions)
+        .wrap(RequirePermission::new("permission:read")),
+    )
+    .route(
+      "/assign",
+      web::post()
+        .to(assign_role_permission)
+        .wrap(RequirePermission::new("permission:assign")),
+    )
+    .route(
+      "/revoke",
+      web::post()
+        .to(revoke_role_permission)
+        .wrap(RequirePermission::new("permission:assign")),
+    )
+}
@@ -0,0 +1,2 @@
+pub mod realtime_handler;
+pub mod realtime_route;
@@ -0,0 +1,136 @@
+use std::time::Duration;
+
+use actix_web::{
+  HttpRequest, HttpResponse,
+  http::header::CACHE_CONTROL,
+  web::{self, Bytes},
+};
+use actix_ws::Message;
+use futures::{StreamExt, stream};
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::{app_state::AppState, events::EventEnvelope};
+
+const LAST_EVENT_ID_HEADER: &str = "Last-Event-ID";
+
+// Keeps proxies from dropping idle connections and notices clients that vanished
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Upgrades to a WebSocket that receives every domain event as a JSON text frame.
+/// Messages sent by the client are ignored apart from ping and close.
+pub async fn events_socket(
+  req: HttpRequest,
+  body: web::Payload,
+  data: web::Data<AppState>,
+) -> actix_web::Result<HttpResponse> {
+  let (response, mut session, mut messages) = actix_ws::handle(&req, body)?;
+  let mut events = data.events.subscribe();
+
+  actix_web::rt::spawn(async move {
+    let mut heartbeat = tokio::time::interval(HEARTBEAT_INTERVAL);
+    loop {
+      tokio::select! {
+        event = events.recv() => match event {
+          Ok(envelope) => {
+            let Ok(json) = serde_json::to_string(&envelope) else {
+              continue;
+            };
+            if session.text(json).await.is_err() {
+              return;
+            }
+          }
+          // The client was too slow, tell it how many events it missed and carry on
+          Err(RecvError::Lagged(skipped)) => {
+            let notice = serde_json::json!({ "type": "lagged", "skipped": skipped });
+            if session.text(notice.to_string()).await.is_err() {
+              return;
+            }
+          }
+          Err(RecvError::Closed) => break,
+        },
+        message = messages.recv() => match message {
+          Some(Ok(Message::Ping(bytes))) => {
+            if session.pong(&bytes).await.is_err() {
+              return;
+            }
+          }
+          Some(Ok(Message::Close(reason))) => {
+            let _ = session.close(reason).await;
+            return;
+          }
+          Some(Ok(_)) => {}
+          Some(Err(_)) | None => break,
+        },
+        _ = heartbeat.tick() => {
+          if session.ping(b"").await.is_err() {
+            return;
+          }
+        }
+      }
+    }
+    let _ = session.close(None).await;
+  });
+
+  Ok(response)
+}
+
+fn sse_frame(envelope: &EventEnvelope) -> Bytes {
+  let data = serde_json::to_string(envelope).unwrap_or_default();
+  Bytes::from(format!(
+    "id: {}\nevent: {}\ndata: {}\n\n",
+    envelope.id,
+    envelope.event.kind(),
+    data
+  ))
+}
+
+fn lagged_frame(skipped: u64) -> Bytes {
+  Bytes::from(format!(
+    "event: lagged\ndata: {{\"type\":\"lagged\",\"skipped\":{}}}\n\n",
+    skipped
+  ))
+}
+
+/// Server-Sent Events feed of the same events as `/ws`. A reconnecting client sends
+/// `Last-Event-ID` and first receives what it missed from the replay buffer.
+pub async fn events_stream(req: HttpRequest, data: web::Data<AppState>) -> HttpResponse {
+  let last_event_id = req
+    .headers()
+    .get(LAST_EVENT_ID_HEADER)
+    .and_then(|h| h.to_str().ok())
+    .and_then(|id| id.trim().parse::<u64>().ok());
+
+  let (mut head, live) = match last_event_id {
+    Some(last_event_id) => {
+      let resumed = data.events.resume(last_event_id);
+      let mut head: Vec<Bytes> = Vec::new();
+      if resumed.missed > 0 {
+        head.push(lagged_frame(resumed.missed));
+      }
+      head.extend(resumed.replay.iter().map(sse_frame));
+      (head, resumed.live)
+    }
+    None => (Vec::new(), data.events.subscribe()),
+  };
+  // Tells EventSource how long to wait before reconnecting
+  head.insert(0, Bytes::from_static(b"retry: 3000\n\n"));
+
+  let heartbeat = tokio::time::interval(HEARTBEAT_INTERVAL);
+  let live = stream::unfold((live, heartbeat), |(mut live, mut heartbeat)| async move {
+    let frame = tokio::select! {
+      event = live.recv() => match event {
+        Ok(envelope) => sse_frame(&envelope),
+        Err(RecvError::Lagged(skipped)) => lagged_frame(skipped),
+        Err(RecvError::Closed) => return None,
+      },
+      // Comment lines keep the connection open through proxies
+      _ = heartbeat.tick() => Bytes::from_static(b": keep-alive\n\n"),
+    };
+    Some((Ok::<_, actix_web::Error>(frame), (live, heartbeat)))
+  });
+
+  HttpResponse::Ok()
+    .content_type("text/event-stream")
+    .insert_header((CACHE_CONTROL, "no-cache"))
+    .streaming(stream::iter(head.into_iter().map(Ok)).chain(live))
+}
@@ -0,0 +1,28 @@
+use actix_web::{Scope, web};
+
+use crate::{
+  features::{
+    realtime::realtime_handler::{events_socket, events_stream},
+    users::user_entity::UserRole,
+  },
+  middleware::auth::RequireAuth,
+};
+
+// Browsers can't set Authorization on a WebSocket handshake, the session cookie works
+pub fn realtime_routes() -> Scope {
+  web::scope("/ws").route(
+    "",
+    web::get()
+      .to(events_socket)
+      .wrap(RequireAuth::allow_roles(vec![UserRole::Admin])),
+  )
+}
+
+pub fn events_routes() -> Scope {
+  web::scope("/events").route(
+    "",
+    web::get()
+      .to(events_stream)
+      .wrap(RequireAuth::allow_roles(vec![UserRole::Admin])),
+  )
+}
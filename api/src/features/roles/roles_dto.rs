@@ -1,7 +1,11 @@
 use serde::{Deserialize, Serialize};
-use utoipa::ToSchema;
+use utoipa::{IntoParams, ToSchema};
+use validator::Validate;
 
-use crate::features::roles::roles_entity::{RoleEntity, UserRoleEntity, UserRolesEntity};
+use crate::{
+  dto::sort::SortDir,
+  features::roles::roles_entity::{RoleEntity, UserRoleEntity, UserRolesEntity},
+};
 
 #[derive(Deserialize, Serialize, Clone, ToSchema)]
 pub struct UserRoleDto {
@@ -17,16 +21,38 @@ pub struct RoleDto {
   pub description: Option<String>,
 }
 
-#[derive(Deserialize, Clone, ToSchema)]
+#[derive(Deserialize, Debug, Default, ToSchema, IntoParams, Validate)]
+pub struct GetRolesReqDto {
+  #[serde(default)]
+  pub sort_by: RoleSortBy,
+  #[serde(default)]
+  pub sort_dir: SortDir,
+}
+
+/// Columns roles can be sorted by.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RoleSortBy {
+  #[default]
+  Id,
+  Name,
+  CreatedAt,
+}
+
+#[derive(Deserialize, Serialize, Clone, ToSchema, Validate)]
 pub struct CreateRoleReqDto {
+  #[validate(length(min = 1, max = 50))]
   pub name: String,
+  #[validate(length(max = 255))]
   pub description: Option<String>,
 }
 
-#[derive(Deserialize, Clone, ToSchema)]
+#[derive(Deserialize, Serialize, Clone, ToSchema, Validate)]
 pub struct UpdateRoleReqDto {
   pub id: i32,
+  #[validate(length(min = 1, max = 50))]
   pub name: String,
+  #[validate(length(max = 255))]
   pub description: Option<String>,
 }
 
@@ -35,12 +61,42 @@ pub struct GetUserRolesReqDto {
   pub user_id: i32,
 }
 
-#[derive(Deserialize, Clone, ToSchema)]
+#[derive(Deserialize, Serialize, Clone, ToSchema)]
 pub struct AssignUserRoleReqDto {
   pub user_id: i32,
   pub role_id: i32,
 }
 
+#[derive(Deserialize, Clone, ToSchema, Validate)]
+pub struct AssignUsersRoleReqDto {
+  pub role_id: i32,
+  /// At most 1000, the members among them are looked up in one statement
+  #[validate(length(max = 1000))]
+  pub user_ids: Vec<i32>,
+}
+
+#[derive(Deserialize, Clone, ToSchema)]
+pub struct DeleteRoleReqDto {
+  pub id: i32,
+  /// Also remove the role from every user still assigned to it
+  #[serde(default)]
+  pub force: bool,
+}
+
+#[derive(Deserialize, Clone, ToSchema, Validate)]
+pub struct RoleUsersReqDto {
+  /// At most 1000, see `AssignUsersRoleReqDto`
+  #[validate(length(max = 1000))]
+  pub user_ids: Vec<i32>,
+}
+
+#[derive(Deserialize, Clone, IntoParams)]
+pub struct DeleteRoleQueryDto {
+  /// Also remove the role from every user still assigned to it
+  #[serde(default)]
+  pub force: bool,
+}
+
 impl From<UserRoleEntity> for UserRoleDto {
   fn from(value: UserRoleEntity) -> Self {
     Self {
@@ -1,8 +1,12 @@
-use chrono::{DateTime, NaiveDateTime, Utc};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
 use domner_tech_sql_client::pool_manager::DbRow;
 use serde::{Deserialize, Serialize};
 
-use crate::features::roles::roles_dto::{CreateRoleReqDto, RoleDto, UpdateRoleReqDto, UserRoleDto};
+use crate::{
+  db::{FromRow, RowExt},
+  features::roles::roles_dto::{CreateRoleReqDto, RoleDto, UpdateRoleReqDto, UserRoleDto},
+};
 
 #[derive(Deserialize, Serialize, Clone)]
 pub struct UserRoleEntity {
@@ -27,39 +31,16 @@ pub struct UserRolesEntity {
   pub is_in_role: bool,
 }
 
-impl From<&DbRow<'_>> for RoleEntity {
-  fn from(row: &DbRow) -> Self {
-    let naive_created_at: NaiveDateTime = row
-      .get_mssql::<NaiveDateTime>("created_at")
-      .expect("Failed to get created_at")
-      .unwrap_or_default();
-    let created_at: DateTime<Utc> =
-      DateTime::<Utc>::from_naive_utc_and_offset(naive_created_at, Utc);
-
-    let naive_updated_at: NaiveDateTime = row
-      .get_mssql::<NaiveDateTime>("updated_at")
-      .expect("Failed to get updated_at")
-      .unwrap_or_default();
-    let updated_at: DateTime<Utc> =
-      DateTime::<Utc>::from_naive_utc_and_offset(naive_updated_at, Utc);
-
-    Self {
-      id: row
-        .get_mssql::<i32>("id")
-        .expect("Failed to get id")
-        .unwrap_or_default(), // fallback if null
-      name: row
-        .get_mssql::<&str>("name")
-        .expect("Failed to get name")
-        .unwrap_or_default()
-        .to_string(),
-      description: row
-        .get_mssql::<&str>("description")
-        .unwrap_or_default()
-        .map(|s| s.to_string()),
-      created_at,
-      updated_at,
-    }
+impl FromRow for RoleEntity {
+  fn from_row(row: &DbRow<'_>) -> Result<Self> {
+    Ok(Self {
+      id: row.column("id")?,
+      name: row.column("name")?,
+      description: row.nullable("description")?,
+      created_at: row.column("created_at")?,
+      // NULL until the role is first updated
+      updated_at: row.nullable("updated_at")?.unwrap_or_default(),
+    })
   }
 }
 
@@ -75,22 +56,13 @@ impl From<RoleDto> for RoleEntity {
   }
 }
 
-impl From<&DbRow<'_>> for UserRoleEntity {
-  fn from(value: &DbRow) -> Self {
-    Self {
-      id: value
-        .get_mssql::<i32>("id")
-        .expect("Failed to get id")
-        .unwrap_or_default(),
-      user_id: value
-        .get_mssql::<i32>("user_id")
-        .expect("Failed to get id")
-        .unwrap_or_default(),
-      role_id: value
-        .get_mssql::<i32>("role_id")
-        .expect("Failed to get id")
-        .unwrap_or_default(),
-    }
+impl FromRow for UserRoleEntity {
+  fn from_row(row: &DbRow<'_>) -> Result<Self> {
+    Ok(Self {
+      id: row.column("id")?,
+      user_id: row.column("user_id")?,
+      role_id: row.column("role_id")?,
+    })
   }
 }
 
@@ -128,22 +100,12 @@ impl From<UpdateRoleReqDto> for RoleEntity {
   }
 }
 
-impl From<&DbRow<'_>> for UserRolesEntity {
-  fn from(row: &DbRow) -> Self {
-    Self {
-      role_id: row
-        .get_mssql::<i32>("role_id")
-        .expect("Failed to get role_id")
-        .unwrap_or_default(), // fallback if null
-      role_name: row
-        .get_mssql::<&str>("role_name")
-        .expect("Failed to get role_name")
-        .unwrap_or_default()
-        .to_string(),
-      is_in_role: row
-        .get_mssql::<bool>("is_in_role")
-        .expect("Failed to get is_in_role")
-        .unwrap_or_default(),
-    }
+impl FromRow for UserRolesEntity {
+  fn from_row(row: &DbRow<'_>) -> Result<Self> {
+    Ok(Self {
+      role_id: row.column("role_id")?,
+      role_name: row.column("role_name")?,
+      is_in_role: row.column("is_in_role")?,
+    })
   }
 }
@@ -1,22 +1,38 @@
-use actix_web::{HttpResponse, Responder, web};
+use actix_web::{HttpRequest, HttpResponse, Responder, web};
 
 use crate::{
   app_state::AppState,
-  dto::base_res_dto::{BaseResDto, Status},
+  document,
+  dto::{
+    base_res_dto::{BaseResDto, Status},
+    created_res_dto::CreatedResDto,
+    field_selection::{FieldSelection, FieldsParam},
+    sort::SortDir,
+    validated_json::{ValidatedJson, ValidatedQuery},
+  },
   error::StatusMessage,
+  events::DomainEvent,
   features::{
+    audit::{
+      audit_entity::{AuditAction, AuditLogEntity},
+      audit_repo::AuditRepo,
+    },
     roles::{
       roles_dto::{
-        AssignUserRoleReqDto, CreateRoleReqDto, GetUserRolesReqDto, RoleDto, UpdateRoleReqDto,
-        UserRolesResDto,
+        AssignUserRoleReqDto, AssignUsersRoleReqDto, CreateRoleReqDto, DeleteRoleQueryDto,
+        DeleteRoleReqDto, GetRolesReqDto, GetUserRolesReqDto, RoleDto, RoleSortBy, RoleUsersReqDto,
+        UpdateRoleReqDto, UserRolesResDto,
       },
       roles_entity::RoleEntity,
-      roles_repo::RoleRepo,
     },
-    users::user_repo::UserRepo,
   },
+  middleware::{auth::Authenticated, transaction::DbTransaction},
+  notifications::NotificationKind,
+  security_log::{SecurityEvent, SecurityEventType},
+  utils::mailer::EmailTemplate,
 };
 
+document!(create_role);
 #[utoipa::path(
     post,
     path = "/api/v1/role/create",
@@ -32,14 +48,19 @@ use crate::{
     responses( 
         (
             status=200, 
-            description= "Role created successfully", 
-            body= Status 
+            description= "Role created successfully, `data.id` is the new role's id", 
+            body= BaseResDto<CreatedResDto> 
         ),
         (
             status=400, 
             description= "Validation Errors", 
             body= Status
         ),
+        (
+            status=422, 
+            description= "Quota exceeded", 
+            body= Status
+        ),
         (
             status=500, 
             description= "Internal Server Error", 
@@ -48,14 +69,16 @@ use crate::{
     )
 )]
 pub async fn create_role(
-  role: web::Json<CreateRoleReqDto>,
+  role: ValidatedJson<CreateRoleReqDto>,
+  current_user: Authenticated,
+  http_req: HttpRequest,
   data: web::Data<AppState>,
 ) -> impl Responder {
-  let mut repo = RoleRepo::new(&data);
+  let mut repo = data.role_repo();
 
   match repo.get_by_name(&role.name).await {
     Ok(role_existed) => {
-      if let Some(_) = role_existed {
+      if role_existed.is_some() {
         return Status::bad_request(StatusMessage::Existed(format!(
           "Role with name '{}'",
           role.name
@@ -63,16 +86,51 @@ pub async fn create_role(
         .into_http_response();
       }
 
+      if let Some(max_roles) = data.config.quota.max_roles {
+        match repo.get_roles(RoleSortBy::Id, SortDir::Asc).await {
+          Ok(roles) if roles.len() as i32 >= max_roles => {
+            return Status::quota_exceeded(StatusMessage::QuotaExceeded(
+              "Number of roles".into(),
+              max_roles,
+            ))
+            .into_http_response();
+          }
+          Ok(_) => {}
+          Err(e) => {
+            return Status::bad_request(format!("Failed to create role: {}", e))
+              .or_query_timeout(&e)
+              .into_http_response();
+          }
+        }
+      }
+
       let entity = RoleEntity::from(role.into_inner());
       match repo.create_role(&entity).await {
-        Ok(_) => HttpResponse::Ok().json(Status::success()),
-        Err(e) => Status::bad_request(format!("Failed to create role: {}", e)).into_http_response(),
+        Ok(id) => {
+          AuditRepo::new(&data)
+            .record(
+              AuditLogEntity::new(
+                AuditAction::CreateRole,
+                Some(current_user.id),
+                format!("role:{}", entity.name),
+              )
+              .with_request(&http_req),
+            )
+            .await;
+          HttpResponse::Ok().json(Status::success_with_data(CreatedResDto { id }))
+        }
+        Err(e) => Status::bad_request(format!("Failed to create role: {}", e))
+          .or_query_timeout(&e)
+          .into_http_response(),
       }
     }
-    Err(e) => Status::bad_request(format!("Failed to create role: {}", e)).into_http_response(),
+    Err(e) => Status::bad_request(format!("Failed to create role: {}", e))
+      .or_query_timeout(&e)
+      .into_http_response(),
   }
 }
 
+document!(update_role);
 #[utoipa::path(
     post,
     path = "/api/v1/role/update",
@@ -105,14 +163,16 @@ pub async fn create_role(
     )
 )]
 pub async fn update_role(
-  role: web::Json<UpdateRoleReqDto>,
+  role: ValidatedJson<UpdateRoleReqDto>,
+  current_user: Authenticated,
+  http_req: HttpRequest,
   data: web::Data<AppState>,
 ) -> impl Responder {
-  let mut repo = RoleRepo::new(&data);
+  let mut repo = data.role_repo();
 
   match repo.get_by_id(role.id).await {
     Ok(role_existed) => {
-      if let Some(_) = role_existed {
+      if role_existed.is_some() {
         if let Ok(Some(_)) = repo.get_by_name(&role.name).await {
           return Status::bad_request(
             StatusMessage::Existed(format!("Role name '{}'", role.name)).to_str(),
@@ -122,19 +182,37 @@ pub async fn update_role(
 
         let entity = RoleEntity::from(role.into_inner());
         return match repo.update_role(&entity).await {
-          Ok(_) => HttpResponse::Ok().json(Status::success()),
-          Err(e) => {
-            Status::bad_request(format!("Failed to update role: {}", e)).into_http_response()
+          Ok(_) => {
+            // Role names are cached for authorization
+            data.invalidate_access_cache();
+            AuditRepo::new(&data)
+              .record(
+                AuditLogEntity::new(
+                  AuditAction::UpdateRole,
+                  Some(current_user.id),
+                  format!("role:{}", entity.id),
+                )
+                .with_details(format!("name={}", entity.name))
+                .with_request(&http_req),
+              )
+              .await;
+            HttpResponse::Ok().json(Status::success())
           }
+          Err(e) => Status::bad_request(format!("Failed to update role: {}", e))
+            .or_query_timeout(&e)
+            .into_http_response(),
         };
       }
       Status::not_found(StatusMessage::NotFound(format!("Role with id '{}'", role.id)).to_str())
         .into_http_response()
     }
-    Err(e) => Status::bad_request(format!("failed to update role: {}", e)).into_http_response(),
+    Err(e) => Status::bad_request(format!("failed to update role: {}", e))
+      .or_query_timeout(&e)
+      .into_http_response(),
   }
 }
 
+document!(get_user_roles);
 #[utoipa::path(
     post,
     path = "/api/v1/role/user_roles",
@@ -168,16 +246,14 @@ pub async fn get_user_roles(
   r: web::Json<GetUserRolesReqDto>,
   data: web::Data<AppState>,
 ) -> impl Responder {
-  let mut repo = RoleRepo::new(&data);
-  let mut user_repo = UserRepo::new(&data);
+  let mut repo = data.role_repo();
+  let mut user_repo = data.user_repo();
   match user_repo.get_by_id(r.user_id).await {
     Ok(user_option) => {
       if let Some(user) = user_option {
         if let Ok(user_roles) = repo.get_user_roles(user.id).await {
-          let user_roles_dto: Vec<UserRolesResDto> = user_roles
-            .iter()
-            .map(|ur| UserRolesResDto::from(ur))
-            .collect();
+          let user_roles_dto: Vec<UserRolesResDto> =
+            user_roles.iter().map(UserRolesResDto::from).collect();
           return HttpResponse::Ok().json(Status::success_with_data(user_roles_dto));
         }
         return HttpResponse::Ok().json(Status::success_with_data(Vec::<UserRolesResDto>::new()));
@@ -188,14 +264,18 @@ pub async fn get_user_roles(
       )))
       .into_http_response()
     }
-    Err(e) => Status::bad_request(format!("Failed to get user roles: {}", e)).into_http_response(),
+    Err(e) => Status::bad_request(format!("Failed to get user roles: {}", e))
+      .or_query_timeout(&e)
+      .into_http_response(),
   }
 }
 
+document!(get_roles);
 #[utoipa::path(
     post,
-    path = "/api/v1/role/roles",
+    path = "/api/v1/role/all",
     tag = "Roles",
+    params(GetRolesReqDto, FieldsParam),
     request_body(
         content = (),
         description = "",
@@ -218,16 +298,26 @@ pub async fn get_user_roles(
         ),
     )
 )]
-pub async fn get_roles(data: web::Data<AppState>) -> impl Responder {
-  let mut repo = RoleRepo::new(&data);
-  if let Ok(roles) = repo.get_roles().await {
-    let roles_dto: Vec<RoleDto> = roles.iter().map(|r| RoleDto::from(r)).collect();
+pub async fn get_roles(
+  query: ValidatedQuery<GetRolesReqDto>,
+  fields: FieldSelection<RoleDto>,
+  data: web::Data<AppState>,
+) -> impl Responder {
+  let mut repo = data.role_repo();
+  if let Ok(roles) = repo.get_roles(query.sort_by, query.sort_dir).await {
+    let roles_dto = serde_json::Value::Array(
+      roles
+        .iter()
+        .map(|r| fields.apply(&RoleDto::from(r)))
+        .collect(),
+    );
     return HttpResponse::Ok().json(Status::success_with_data(roles_dto));
   }
 
   HttpResponse::Ok().json(Status::success_with_data(Vec::<RoleDto>::new()))
 }
 
+document!(assign_user_role);
 #[utoipa::path(
     post,
     path = "/api/v1/role/assign_user_role",
@@ -251,6 +341,11 @@ pub async fn get_roles(data: web::Data<AppState>) -> impl Responder {
             description= "Validation Errors", 
             body= Status
         ),
+        (
+            status=422, 
+            description= "Quota exceeded", 
+            body= Status
+        ),
         (
             status=500, 
             description= "Internal Server Error", 
@@ -260,15 +355,533 @@ pub async fn get_roles(data: web::Data<AppState>) -> impl Responder {
 )]
 pub async fn assign_user_role(
   r: web::Json<AssignUserRoleReqDto>,
+  current_user: Authenticated,
+  http_req: HttpRequest,
   data: web::Data<AppState>,
 ) -> impl Responder {
-  let mut repo = RoleRepo::new(&data);
+  let mut repo = data.role_repo();
   if repo.is_user_role_exist(r.user_id, r.role_id).await {
     return Status::uqique_constraint_voilation("User already has that role").into_http_response();
   }
+  if let Some(max_members) = data.config.quota.max_members_per_role {
+    match repo.count_users_in_role(r.role_id).await {
+      Ok(total) if total >= max_members => {
+        return Status::quota_exceeded(StatusMessage::QuotaExceeded(
+          "Number of users in role".into(),
+          max_members,
+        ))
+        .into_http_response();
+      }
+      Ok(_) => {}
+      Err(e) => {
+        return Status::bad_request(format!("Failed to assign user to role: {}", e))
+          .or_query_timeout(&e)
+          .into_http_response();
+      }
+    }
+  }
   if let Err(e) = repo.assign_user_role(r.user_id, r.role_id).await {
     return Status::bad_request(format!("Failed to assign user to role: {}", e))
+      .or_query_timeout(&e)
+      .into_http_response();
+  }
+  data.invalidate_access_cache();
+  data.events.publish(DomainEvent::RoleAssigned {
+    role_id: r.role_id,
+    user_ids: vec![r.user_id],
+  });
+  AuditRepo::new(&data)
+    .record(
+      AuditLogEntity::new(
+        AuditAction::AssignUserRole,
+        Some(current_user.id),
+        format!("user:{}", r.user_id),
+      )
+      .with_details(format!("role_id={}", r.role_id))
+      .with_request(&http_req),
+    )
+    .await;
+  data.security_log.emit(
+    SecurityEvent::new(SecurityEventType::RoleGranted)
+      .actor(Some(current_user.id))
+      .target(format!("user:{}", r.user_id))
+      .details(format!("role_id={}", r.role_id))
+      .with_request(&http_req),
+  );
+
+  // Let the user know their permissions changed
+  if let (Ok(Some(user)), Ok(Some(role))) = (
+    data.user_repo().get_by_id(r.user_id).await,
+    repo.get_by_id(r.role_id).await,
+  ) {
+    data.notifier.send_email_detached(
+      NotificationKind::SecurityAlert,
+      user.id,
+      user.email,
+      EmailTemplate::RoleChanged { role: role.name },
+    );
+  }
+  HttpResponse::Ok().json(Status::success())
+}
+
+document!(assign_users_role);
+#[utoipa::path(
+    post,
+    path = "/api/v1/role/assign_users",
+    tag = "Roles",
+    request_body(
+        content = AssignUsersRoleReqDto,
+        description = "",
+        example = json!(
+          {
+            "role_id": 1,
+            "user_ids": [1, 2, 3]
+          })),
+    responses( 
+        (
+            status=200, 
+            description= "Assign users to role successfully", 
+            body= Status 
+        ),
+        (
+            status=400, 
+            description= "Validation Errors", 
+            body= Status
+        ),
+        (
+            status=404, 
+            description= "Role not found", 
+            body= Status
+        ),
+        (
+            status=422, 
+            description= "Quota exceeded", 
+            body= Status
+        ),
+    )
+)]
+pub async fn assign_users_role(
+  r: ValidatedJson<AssignUsersRoleReqDto>,
+  current_user: Authenticated,
+  tx: DbTransaction,
+  http_req: HttpRequest,
+  data: web::Data<AppState>,
+) -> impl Responder {
+  let mut repo = data.role_repo_in(&tx);
+
+  match repo.get_by_id(r.role_id).await {
+    Ok(Some(_)) => {}
+    Ok(None) => {
+      return Status::not_found(StatusMessage::NotFound(format!(
+        "Role with id '{}'",
+        r.role_id
+      )))
+      .into_http_response();
+    }
+    Err(e) => {
+      return Status::bad_request(format!("Failed to assign users to role: {}", e))
+        .or_query_timeout(&e)
+        .into_http_response();
+    }
+  }
+
+  // Skip duplicates in the request and users that already have the role
+  let mut user_ids: Vec<i32> = Vec::new();
+  for user_id in r.user_ids.iter().copied() {
+    if !user_ids.contains(&user_id) {
+      user_ids.push(user_id);
+    }
+  }
+  match repo.get_role_members_among(r.role_id, &user_ids).await {
+    Ok(members) => user_ids.retain(|user_id| !members.contains(user_id)),
+    Err(e) => {
+      return Status::bad_request(format!("Failed to assign users to role: {}", e))
+        .or_query_timeout(&e)
+        .into_http_response();
+    }
+  }
+  if user_ids.is_empty() {
+    return HttpResponse::Ok().json(Status::success());
+  }
+
+  if let Some(max_members) = data.config.quota.max_members_per_role {
+    match repo.count_users_in_role(r.role_id).await {
+      Ok(total) if total + user_ids.len() as i32 > max_members => {
+        return Status::quota_exceeded(StatusMessage::QuotaExceeded(
+          "Number of users in role".into(),
+          max_members,
+        ))
+        .into_http_response();
+      }
+      Ok(_) => {}
+      Err(e) => {
+        return Status::bad_request(format!("Failed to assign users to role: {}", e))
+          .or_query_timeout(&e)
+          .into_http_response();
+      }
+    }
+  }
+
+  if let Err(e) = repo.assign_users_to_role(r.role_id, &user_ids).await {
+    return Status::bad_request(format!("Failed to assign users to role: {}", e))
+      .or_query_timeout(&e)
+      .into_http_response();
+  }
+  data.invalidate_access_cache();
+  data.events.publish(DomainEvent::RoleAssigned {
+    role_id: r.role_id,
+    user_ids: user_ids.clone(),
+  });
+  AuditRepo::new(&data)
+    .record(
+      AuditLogEntity::new(
+        AuditAction::AssignUserRole,
+        Some(current_user.id),
+        format!("role:{}", r.role_id),
+      )
+      .with_details(format!("user_ids={:?}", user_ids))
+      .with_request(&http_req),
+    )
+    .await;
+  data.security_log.emit(
+    SecurityEvent::new(SecurityEventType::RoleGranted)
+      .actor(Some(current_user.id))
+      .target(format!("role:{}", r.role_id))
+      .details(format!("user_ids={:?}", user_ids))
+      .with_request(&http_req),
+  );
+  HttpResponse::Ok().json(Status::success())
+}
+
+document!(delete_role);
+#[utoipa::path(
+    post,
+    path = "/api/v1/role/delete",
+    tag = "Roles",
+    request_body(
+        content = DeleteRoleReqDto,
+        description = "",
+        example = json!(
+          {
+            "id": 1,
+            "force": false
+          })),
+    responses( 
+        (
+            status=200, 
+            description= "Role deleted successfully", 
+            body= Status 
+        ),
+        (
+            status=400, 
+            description= "Validation Errors", 
+            body= Status
+        ),
+        (
+            status=404, 
+            description= "Role not found", 
+            body= Status
+        ),
+        (
+            status=409, 
+            description= "Role is still assigned to users", 
+            body= Status
+        ),
+    )
+)]
+pub async fn delete_role(
+  r: web::Json<DeleteRoleReqDto>,
+  current_user: Authenticated,
+  tx: DbTransaction,
+  http_req: HttpRequest,
+  data: web::Data<AppState>,
+) -> impl Responder {
+  let mut repo = data.role_repo_in(&tx);
+
+  let role = match repo.get_by_id(r.id).await {
+    Ok(Some(role)) => role,
+    Ok(None) => {
+      return Status::not_found(StatusMessage::NotFound(format!("Role with id '{}'", r.id)))
+        .into_http_response();
+    }
+    Err(e) => {
+      return Status::bad_request(format!("Failed to delete role: {}", e))
+        .or_query_timeout(&e)
+        .into_http_response();
+    }
+  };
+
+  let total = match repo.count_users_in_role(r.id).await {
+    Ok(total) => total,
+    Err(e) => {
+      return Status::bad_request(format!("Failed to delete role: {}", e))
+        .or_query_timeout(&e)
+        .into_http_response();
+    }
+  };
+  if total > 0 && !r.force {
+    return Status::uqique_constraint_voilation(StatusMessage::RoleInUse(total))
       .into_http_response();
   }
+
+  // Both statements share the request transaction, a failure rolls back the unassignments too
+  if total > 0
+    && let Err(e) = repo.delete_user_roles_by_role(r.id).await
+  {
+    return Status::bad_request(format!("Failed to delete role: {}", e))
+      .or_query_timeout(&e)
+      .into_http_response();
+  }
+  if let Err(e) = repo.delete_role(r.id).await {
+    return Status::bad_request(format!("Failed to delete role: {}", e))
+      .or_query_timeout(&e)
+      .into_http_response();
+  }
+
+  data.invalidate_access_cache();
+  AuditRepo::new(&data)
+    .record(
+      AuditLogEntity::new(
+        AuditAction::DeleteRole,
+        Some(current_user.id),
+        format!("role:{}", r.id),
+      )
+      .with_details(format!("name={}, unassigned_users={}", role.name, total))
+      .with_request(&http_req),
+    )
+    .await;
   HttpResponse::Ok().json(Status::success())
 }
+
+document!(list_roles);
+#[utoipa::path(
+    get,
+    path = "/api/v1/roles",
+    tag = "Roles",
+    params(GetRolesReqDto, FieldsParam),
+    responses( 
+        (
+            status=200, 
+            description= "get roles successfully", 
+            body= BaseResDto<Vec<RoleDto>> 
+        ),
+    )
+)]
+pub async fn list_roles(
+  query: ValidatedQuery<GetRolesReqDto>,
+  fields: FieldSelection<RoleDto>,
+  data: web::Data<AppState>,
+) -> impl Responder {
+  get_roles(query, fields, data).await
+}
+
+document!(post_role);
+#[utoipa::path(
+    post,
+    path = "/api/v1/roles",
+    tag = "Roles",
+    request_body(
+        content = CreateRoleReqDto,
+        description = "",
+        example = json!(
+            {
+                "name": "read",
+                "description": "Has access to read only with all features"
+            })),
+    responses( 
+        (
+            status=200, 
+            description= "Role created successfully", 
+            body= Status 
+        ),
+        (
+            status=400, 
+            description= "Validation Errors", 
+            body= Status
+        ),
+    )
+)]
+pub async fn post_role(
+  role: ValidatedJson<CreateRoleReqDto>,
+  current_user: Authenticated,
+  http_req: HttpRequest,
+  data: web::Data<AppState>,
+) -> impl Responder {
+  create_role(role, current_user, http_req, data).await
+}
+
+document!(put_role);
+#[utoipa::path(
+    put,
+    path = "/api/v1/roles/{id}",
+    tag = "Roles",
+    params(("id" = i32, Path, description = "Role id")),
+    request_body(
+        content = CreateRoleReqDto,
+        description = "",
+        example = json!(
+            {
+                "name": "read",
+                "description": "Has access to read only with all features"
+            })),
+    responses( 
+        (
+            status=200, 
+            description= "Role updated successfully", 
+            body= Status 
+        ),
+        (
+            status=400, 
+            description= "Validation Errors", 
+            body= Status
+        ),
+    )
+)]
+pub async fn put_role(
+  id: web::Path<i32>,
+  role: ValidatedJson<CreateRoleReqDto>,
+  current_user: Authenticated,
+  http_req: HttpRequest,
+  data: web::Data<AppState>,
+) -> impl Responder {
+  let role = role.into_inner();
+  let role = UpdateRoleReqDto {
+    id: id.into_inner(),
+    name: role.name,
+    description: role.description,
+  };
+  update_role(ValidatedJson(role), current_user, http_req, data).await
+}
+
+document!(remove_role);
+#[utoipa::path(
+    delete,
+    path = "/api/v1/roles/{id}",
+    tag = "Roles",
+    params(("id" = i32, Path, description = "Role id"), DeleteRoleQueryDto),
+    responses( 
+        (
+            status=200, 
+            description= "Role deleted successfully", 
+            body= Status 
+        ),
+        (
+            status=404, 
+            description= "Role not found", 
+            body= Status
+        ),
+        (
+            status=409, 
+            description= "Role still assigned to users", 
+            body= Status
+        ),
+    )
+)]
+pub async fn remove_role(
+  id: web::Path<i32>,
+  query: web::Query<DeleteRoleQueryDto>,
+  current_user: Authenticated,
+  tx: DbTransaction,
+  http_req: HttpRequest,
+  data: web::Data<AppState>,
+) -> impl Responder {
+  let r = DeleteRoleReqDto {
+    id: id.into_inner(),
+    force: query.force,
+  };
+  delete_role(web::Json(r), current_user, tx, http_req, data).await
+}
+
+document!(post_role_users);
+#[utoipa::path(
+    post,
+    path = "/api/v1/roles/{id}/users",
+    tag = "Roles",
+    params(("id" = i32, Path, description = "Role id")),
+    request_body(
+        content = RoleUsersReqDto,
+        description = "",
+        example = json!({
+          "user_ids": [1, 2, 3]
+        })),
+    responses( 
+        (
+            status=200, 
+            description= "Role assigned to every user", 
+            body= Status 
+        ),
+        (
+            status=400, 
+            description= "Validation Errors", 
+            body= Status
+        ),
+    )
+)]
+pub async fn post_role_users(
+  id: web::Path<i32>,
+  body: ValidatedJson<RoleUsersReqDto>,
+  current_user: Authenticated,
+  tx: DbTransaction,
+  http_req: HttpRequest,
+  data: web::Data<AppState>,
+) -> impl Responder {
+  let r = AssignUsersRoleReqDto {
+    role_id: id.into_inner(),
+    user_ids: body.into_inner().user_ids,
+  };
+  assign_users_role(ValidatedJson(r), current_user, tx, http_req, data).await
+}
+
+document!(put_user_role);
+#[utoipa::path(
+    put,
+    path = "/api/v1/roles/{role_id}/users/{user_id}",
+    tag = "Roles",
+    params(
+        ("role_id" = i32, Path, description = "Role id"),
+        ("user_id" = i32, Path, description = "User id")
+    ),
+    responses( 
+        (
+            status=200, 
+            description= "Role assigned successfully", 
+            body= Status 
+        ),
+        (
+            status=400, 
+            description= "Validation Errors", 
+            body= Status
+        ),
+    )
+)]
+pub async fn put_user_role(
+  path: web::Path<(i32, i32)>,
+  current_user: Authenticated,
+  http_req: HttpRequest,
+  data: web::Data<AppState>,
+) -> impl Responder {
+  let (role_id, user_id) = path.into_inner();
+  let r = AssignUserRoleReqDto { user_id, role_id };
+  assign_user_role(web::Json(r), current_user, http_req, data).await
+}
+
+document!(list_user_roles);
+#[utoipa::path(
+    get,
+    path = "/api/v1/users/{id}/roles",
+    tag = "Roles",
+    params(("id" = i32, Path, description = "User id")),
+    responses( 
+        (
+            status=200, 
+            description= "Get user roles successfully", 
+            body= BaseResDto<Vec<UserRolesResDto>> 
+        ),
+    )
+)]
+pub async fn list_user_roles(id: web::Path<i32>, data: web::Data<AppState>) -> impl Responder {
+  let r = GetUserRolesReqDto {
+    user_id: id.into_inner(),
+  };
+  get_user_roles(web::Json(r), data).await
+}
@@ -1,144 +1,430 @@
 use crate::{
   app_state::AppState,
-  features::roles::roles_entity::{RoleEntity, UserRoleEntity, UserRolesEntity},
+  db::{
+    RetryPolicy, RowExt, SqlRepo,
+    soft_delete::{NOT_DELETED, ROLES},
+  },
+  dto::sort::SortDir,
+  features::roles::{
+    roles_dto::RoleSortBy,
+    roles_entity::{RoleEntity, UserRolesEntity},
+  },
+  middleware::transaction::{DbClient, DbTransaction},
+  utils::{bulk_insert::execute_bulk_insert, redis_cache::RedisCache},
 };
 
 use anyhow::Result;
-use domner_tech_sql_client::{CommandType, SqlRepo, UnifiedToSql, pool_manager::PooledClient};
+use domner_tech_sql_client::{CommandType, UnifiedToSql};
+use futures::future::LocalBoxFuture;
+
+/// Data access for roles and user-role assignments, see `UserRepository`.
+pub trait RoleRepository {
+  // Returns the id of the new role
+  fn create_role<'b>(&'b mut self, role: &'b RoleEntity) -> LocalBoxFuture<'b, Result<i32>>;
+
+  fn update_role<'b>(&'b mut self, role: &'b RoleEntity) -> LocalBoxFuture<'b, Result<u64>>;
+
+  fn get_by_name<'b>(&'b mut self, name: &'b str)
+  -> LocalBoxFuture<'b, Result<Option<RoleEntity>>>;
+
+  fn get_by_id<'b>(&'b mut self, id: i32) -> LocalBoxFuture<'b, Result<Option<RoleEntity>>>;
+
+  fn get_roles<'b>(
+    &'b mut self,
+    sort_by: RoleSortBy,
+    sort_dir: SortDir,
+  ) -> LocalBoxFuture<'b, Result<Vec<RoleEntity>>>;
+
+  fn get_user_roles<'b>(
+    &'b mut self,
+    user_id: i32,
+  ) -> LocalBoxFuture<'b, Result<Vec<UserRolesEntity>>>;
+
+  // Names of the roles assigned to the user through user_roles
+  fn get_user_role_names<'b>(&'b mut self, user_id: i32)
+  -> LocalBoxFuture<'b, Result<Vec<String>>>;
+
+  fn is_user_role_exist<'b>(&'b mut self, user_id: i32, role_id: i32) -> LocalBoxFuture<'b, bool>;
+
+  // Those of `user_ids` that already have the role, each id is bound as its own parameter so
+  // callers keep them under SQL Server's 2100
+  fn get_role_members_among<'b>(
+    &'b mut self,
+    role_id: i32,
+    user_ids: &'b [i32],
+  ) -> LocalBoxFuture<'b, Result<Vec<i32>>>;
+
+  fn assign_user_role<'b>(
+    &'b mut self,
+    user_id: i32,
+    role_id: i32,
+  ) -> LocalBoxFuture<'b, Result<u64>>;
+
+  // One parameterized statement per chunk instead of a proc call per user
+  fn assign_users_to_role<'b>(
+    &'b mut self,
+    role_id: i32,
+    user_ids: &'b [i32],
+  ) -> LocalBoxFuture<'b, Result<u64>>;
+
+  fn count_users_in_role<'b>(&'b mut self, role_id: i32) -> LocalBoxFuture<'b, Result<i32>>;
+
+  fn delete_user_roles_by_role<'b>(&'b mut self, role_id: i32) -> LocalBoxFuture<'b, Result<u64>>;
+
+  // Soft delete, the row and its permissions are purged by the scheduler later on
+  fn delete_role<'b>(&'b mut self, id: i32) -> LocalBoxFuture<'b, Result<u64>>;
+}
+
+const USER_ROLES_CACHE_PREFIX: &str = "user_roles:";
+
+fn cache_key(user_id: i32) -> String {
+  format!("{}{}", USER_ROLES_CACHE_PREFIX, user_id)
+}
+
+// The only column names that can end up in a role listing's ORDER BY
+fn sort_column(sort_by: RoleSortBy) -> &'static str {
+  match sort_by {
+    RoleSortBy::Id => "id",
+    RoleSortBy::Name => "name",
+    RoleSortBy::CreatedAt => "created_at",
+  }
+}
 
 pub struct RoleRepo<'a> {
   pub app_state: &'a AppState,
+  pub tx: Option<DbTransaction>,
 }
 
 impl<'a> RoleRepo<'a> {
   pub fn new(app_state: &'a AppState) -> Self {
-    Self { app_state }
+    Self {
+      app_state,
+      tx: None,
+    }
   }
 
-  async fn get_client(&self) -> PooledClient {
-    match self
-      .app_state
-      .db_manager
-      .get_client(&self.app_state.config.database.sql_server.pool_name)
-      .await
-    {
-      Ok(client) => client,
-      Err(e) => panic!("Failed to get DB client: {}", e),
+  // Run every query of this repo inside the request's transaction
+  pub fn with_transaction(app_state: &'a AppState, tx: &DbTransaction) -> Self {
+    Self {
+      app_state,
+      tx: Some(tx.clone()),
+    }
+  }
+
+  async fn get_client(&self) -> DbClient {
+    DbClient::acquire(self.app_state, self.tx.as_ref()).await
+  }
+
+  // Reads outside a transaction are retried on a fresh connection, inside one they run once
+  fn retry_policy(&self) -> RetryPolicy {
+    match self.tx {
+      Some(_) => RetryPolicy::none(),
+      None => RetryPolicy::from_setting(&self.app_state.config.database.sql_server),
     }
   }
 
-  pub async fn create_role(&mut self, role: &RoleEntity) -> Result<u64> {
-    let mut client_pool = self.get_client().await;
-
-    let description = role.description.clone().unwrap_or_default();
-    let params: Vec<&dyn UnifiedToSql> = vec![&role.name, &description];
-    let result = SqlRepo::execute_command_none_query(
-      &mut client_pool,
-      "[dbo].[create_role]",
-      &params,
-      CommandType::StoreProcedure,
-    )
-    .await?;
-    Ok(result)
-  }
-
-  pub async fn update_role(&mut self, role: &RoleEntity) -> Result<u64> {
-    let mut client_pool = self.get_client().await;
-
-    let description = role.description.clone().unwrap_or_default();
-    let params: Vec<&dyn UnifiedToSql> = vec![&role.id, &role.name, &description];
-    let result = SqlRepo::execute_command_none_query(
-      &mut client_pool,
-      "[dbo].[update_role]",
-      &params,
-      CommandType::StoreProcedure,
-    )
-    .await?;
-    Ok(result)
-  }
-
-  pub async fn get_by_name(&mut self, name: &str) -> Result<Option<RoleEntity>> {
-    let mut client_pool = self.get_client().await;
-
-    let role = SqlRepo::execute_command_single_query(
-      &mut client_pool,
-      "[dbo].[select_role_by_name]",
-      &[&name],
-      CommandType::StoreProcedure,
-      |row| RoleEntity::from(row),
-    )
-    .await?;
-    Ok(role)
-  }
-
-  pub async fn get_by_id(&mut self, id: i32) -> Result<Option<RoleEntity>> {
-    let mut client_pool = self.get_client().await;
-
-    let role = SqlRepo::execute_command_single_query(
-      &mut client_pool,
-      "[dbo].[select_role_by_id]",
-      &[&id],
-      CommandType::StoreProcedure,
-      |row| RoleEntity::from(row),
-    )
-    .await?;
-    Ok(role)
-  }
-
-  pub async fn get_roles(&mut self) -> Result<Vec<RoleEntity>> {
-    let mut client_pool = self.get_client().await;
-
-    let roles = SqlRepo::execute_command_query(
-      &mut client_pool,
-      "[dbo].[select_roles]",
-      &[],
-      CommandType::StoreProcedure,
-      |row| RoleEntity::from(row),
-    )
-    .await?;
-    Ok(roles)
-  }
-
-  pub async fn get_user_roles(&mut self, user_id: i32) -> Result<Vec<UserRolesEntity>> {
-    let mut client_pool = self.get_client().await;
-
-    let user_roles = SqlRepo::execute_command_query(
-      &mut client_pool,
-      "[dbo].[select_user_role]",
-      &[&user_id],
-      CommandType::StoreProcedure,
-      |row| UserRolesEntity::from(row),
-    )
-    .await?;
-    Ok(user_roles)
-  }
-
-  pub async fn is_user_role_exist(&mut self, user_id: i32, role_id: i32) -> bool {
-    let mut client_pool = self.get_client().await;
-
-    let user_role = SqlRepo::execute_command_single_query(
-      &mut client_pool,
-      "[dbo].[is_user_role_exist]",
-      &[&user_id, &role_id],
-      CommandType::StoreProcedure,
-      |row| UserRoleEntity::from(row),
-    )
-    .await;
-    if let Ok(Some(ur)) = user_role {
-      return ur.id > 0;
+  // Reads inside a transaction skip the cache so they see the transaction's own writes
+  fn read_cache(&self) -> Option<&RedisCache> {
+    match self.tx {
+      Some(_) => None,
+      None => self.app_state.redis.as_ref(),
     }
-    return false;
-  }
-
-  pub async fn assign_user_role(&mut self, user_id: i32, role_id: i32) -> Result<u64> {
-    let mut client_pool = self.get_client().await;
-    let result = SqlRepo::execute_command_none_query(
-      &mut client_pool,
-      "[dbo].[assign_user_role]",
-      &[&user_id, &role_id],
-      CommandType::StoreProcedure,
-    )
-    .await?;
-    Ok(result)
+  }
+
+  async fn invalidate(&self, user_ids: &[i32]) {
+    for user_id in user_ids {
+      self.app_state.invalidate_user(*user_id);
+    }
+    if let Some(cache) = &self.app_state.redis {
+      let keys: Vec<String> = user_ids.iter().map(|id| cache_key(*id)).collect();
+      cache.delete(&keys).await;
+    }
+  }
+
+  // Every user's role list includes every role, so adding, renaming or deleting one
+  // changes all of them
+  async fn invalidate_all(&self) {
+    self.app_state.invalidate_access_cache();
+    if let Some(cache) = &self.app_state.redis {
+      cache.delete_prefix(USER_ROLES_CACHE_PREFIX).await;
+    }
+  }
+}
+
+impl<'a> RoleRepository for RoleRepo<'a> {
+  fn create_role<'b>(&'b mut self, role: &'b RoleEntity) -> LocalBoxFuture<'b, Result<i32>> {
+    Box::pin(async move {
+      let mut client_pool = self.get_client().await;
+
+      let description = role.description.clone().unwrap_or_default();
+      let params: Vec<&dyn UnifiedToSql> = vec![&role.name, &description];
+      let id = SqlRepo::execute_insert_returning_id(
+        &mut client_pool,
+        "[dbo].[create_role]",
+        &params,
+        CommandType::StoreProcedure,
+      )
+      .await?;
+      self.invalidate_all().await;
+      Ok(id)
+    })
+  }
+
+  fn update_role<'b>(&'b mut self, role: &'b RoleEntity) -> LocalBoxFuture<'b, Result<u64>> {
+    Box::pin(async move {
+      let mut client_pool = self.get_client().await;
+
+      let description = role.description.clone().unwrap_or_default();
+      let params: Vec<&dyn UnifiedToSql> = vec![&role.id, &role.name, &description];
+      let result = SqlRepo::execute_command_none_query(
+        &mut client_pool,
+        "[dbo].[update_role]",
+        &params,
+        CommandType::StoreProcedure,
+      )
+      .await?;
+      self.invalidate_all().await;
+      Ok(result)
+    })
+  }
+
+  fn get_by_name<'b>(
+    &'b mut self,
+    name: &'b str,
+  ) -> LocalBoxFuture<'b, Result<Option<RoleEntity>>> {
+    Box::pin(async move {
+      let repo = &*self;
+      SqlRepo::execute_with_retry(self.retry_policy(), || async move {
+        let mut client_pool = repo.get_client().await;
+        SqlRepo::execute_single_query_as::<RoleEntity>(
+          &mut client_pool,
+          "[dbo].[select_role_by_name]",
+          &[&name],
+          CommandType::StoreProcedure,
+        )
+        .await
+      })
+      .await
+    })
+  }
+
+  fn get_by_id<'b>(&'b mut self, id: i32) -> LocalBoxFuture<'b, Result<Option<RoleEntity>>> {
+    Box::pin(async move {
+      let repo = &*self;
+      SqlRepo::execute_with_retry(self.retry_policy(), || async move {
+        let mut client_pool = repo.get_client().await;
+        SqlRepo::execute_single_query_as::<RoleEntity>(
+          &mut client_pool,
+          "[dbo].[select_role_by_id]",
+          &[&id],
+          CommandType::StoreProcedure,
+        )
+        .await
+      })
+      .await
+    })
+  }
+
+  fn get_roles<'b>(
+    &'b mut self,
+    sort_by: RoleSortBy,
+    sort_dir: SortDir,
+  ) -> LocalBoxFuture<'b, Result<Vec<RoleEntity>>> {
+    Box::pin(async move {
+      let query = format!(
+        "SELECT * FROM [dbo].[roles] WHERE {} {}",
+        NOT_DELETED,
+        sort_dir.order_by(sort_column(sort_by))
+      );
+      let (repo, query) = (&*self, query.as_str());
+      SqlRepo::execute_with_retry(self.retry_policy(), || async move {
+        let mut client_pool = repo.get_client().await;
+        SqlRepo::execute_query_as::<RoleEntity>(&mut client_pool, query, &[], CommandType::Text)
+          .await
+      })
+      .await
+    })
+  }
+
+  fn get_user_roles<'b>(
+    &'b mut self,
+    user_id: i32,
+  ) -> LocalBoxFuture<'b, Result<Vec<UserRolesEntity>>> {
+    Box::pin(async move {
+      if let Some(cache) = self.read_cache()
+        && let Some(user_roles) = cache.get::<Vec<UserRolesEntity>>(&cache_key(user_id)).await
+      {
+        return Ok(user_roles);
+      }
+      let repo = &*self;
+      let user_roles = SqlRepo::execute_with_retry(self.retry_policy(), || async move {
+        let mut client_pool = repo.get_client().await;
+        SqlRepo::execute_query_as::<UserRolesEntity>(
+          &mut client_pool,
+          "[dbo].[select_user_role]",
+          &[&user_id],
+          CommandType::StoreProcedure,
+        )
+        .await
+      })
+      .await?;
+      if let Some(cache) = self.read_cache() {
+        cache.set(&cache_key(user_id), &user_roles).await;
+      }
+      Ok(user_roles)
+    })
+  }
+
+  // Names of the roles assigned to the user through user_roles
+  fn get_user_role_names<'b>(
+    &'b mut self,
+    user_id: i32,
+  ) -> LocalBoxFuture<'b, Result<Vec<String>>> {
+    Box::pin(async move {
+      let user_roles = self.get_user_roles(user_id).await?;
+      Ok(
+        user_roles
+          .into_iter()
+          .filter(|ur| ur.is_in_role)
+          .map(|ur| ur.role_name)
+          .collect(),
+      )
+    })
+  }
+
+  fn is_user_role_exist<'b>(&'b mut self, user_id: i32, role_id: i32) -> LocalBoxFuture<'b, bool> {
+    Box::pin(async move {
+      let mut client_pool = self.get_client().await;
+
+      let exists = SqlRepo::execute_scalar::<bool>(
+        &mut client_pool,
+        "SELECT CAST(CASE WHEN EXISTS (
+           SELECT 1 FROM dbo.user_roles WHERE user_id = @P1 AND role_id = @P2
+         ) THEN 1 ELSE 0 END AS BIT) AS value",
+        &[&user_id, &role_id],
+        CommandType::Text,
+      )
+      .await;
+      matches!(exists, Ok(Some(true)))
+    })
+  }
+
+  fn get_role_members_among<'b>(
+    &'b mut self,
+    role_id: i32,
+    user_ids: &'b [i32],
+  ) -> LocalBoxFuture<'b, Result<Vec<i32>>> {
+    Box::pin(async move {
+      if user_ids.is_empty() {
+        return Ok(Vec::new());
+      }
+      let mut client_pool = self.get_client().await;
+
+      let placeholders = (2..user_ids.len() + 2)
+        .map(|i| format!("@P{}", i))
+        .collect::<Vec<_>>()
+        .join(", ");
+      let query = format!(
+        "SELECT user_id FROM dbo.user_roles WHERE role_id = @P1 AND user_id IN ({})",
+        placeholders
+      );
+      let mut params: Vec<&dyn UnifiedToSql> = vec![&role_id];
+      params.extend(user_ids.iter().map(|user_id| user_id as &dyn UnifiedToSql));
+      let members = SqlRepo::execute_command_query(
+        &mut client_pool,
+        &query,
+        &params,
+        CommandType::Text,
+        |row| row.column("user_id"),
+      )
+      .await?;
+      Ok(members)
+    })
+  }
+
+  fn assign_user_role<'b>(
+    &'b mut self,
+    user_id: i32,
+    role_id: i32,
+  ) -> LocalBoxFuture<'b, Result<u64>> {
+    Box::pin(async move {
+      let mut client_pool = self.get_client().await;
+      let result = SqlRepo::execute_command_none_query(
+        &mut client_pool,
+        "[dbo].[assign_user_role]",
+        &[&user_id, &role_id],
+        CommandType::StoreProcedure,
+      )
+      .await?;
+      self.invalidate(&[user_id]).await;
+      Ok(result)
+    })
+  }
+
+  // One parameterized statement per chunk instead of a proc call per user
+  fn assign_users_to_role<'b>(
+    &'b mut self,
+    role_id: i32,
+    user_ids: &'b [i32],
+  ) -> LocalBoxFuture<'b, Result<u64>> {
+    Box::pin(async move {
+      let mut client_pool = self.get_client().await;
+
+      let rows: Vec<Vec<&dyn UnifiedToSql>> = user_ids
+        .iter()
+        .map(|user_id| vec![user_id as &dyn UnifiedToSql, &role_id])
+        .collect();
+      let result = execute_bulk_insert(
+        &mut client_pool,
+        "dbo.user_roles",
+        &["user_id", "role_id"],
+        &rows,
+      )
+      .await?;
+      self.invalidate(user_ids).await;
+      Ok(result)
+    })
+  }
+
+  fn count_users_in_role<'b>(&'b mut self, role_id: i32) -> LocalBoxFuture<'b, Result<i32>> {
+    Box::pin(async move {
+      let mut client_pool = self.get_client().await;
+
+      let total = SqlRepo::execute_command_single_query(
+        &mut client_pool,
+        "[dbo].[count_users_in_role]",
+        &[&role_id],
+        CommandType::StoreProcedure,
+        |row| row.column("total"),
+      )
+      .await?;
+      Ok(total.unwrap_or_default())
+    })
+  }
+
+  fn delete_user_roles_by_role<'b>(&'b mut self, role_id: i32) -> LocalBoxFuture<'b, Result<u64>> {
+    Box::pin(async move {
+      let mut client_pool = self.get_client().await;
+
+      let result = SqlRepo::execute_command_none_query(
+        &mut client_pool,
+        "[dbo].[delete_user_roles_by_role]",
+        &[&role_id],
+        CommandType::StoreProcedure,
+      )
+      .await?;
+      self.invalidate_all().await;
+      Ok(result)
+    })
+  }
+
+  fn delete_role<'b>(&'b mut self, id: i32) -> LocalBoxFuture<'b, Result<u64>> {
+    Box::pin(async move {
+      let mut client_pool = self.get_client().await;
+
+      let result = SqlRepo::soft_delete(&mut client_pool, &ROLES, id).await?;
+      self.invalidate_all().await;
+      Ok(result)
+    })
   }
 }
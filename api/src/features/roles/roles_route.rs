@@ -1,39 +1,119 @@
 use actix_web::{Scope, web};
 
+use crate::features::permissions::permissions_handler::{
+  list_role_permissions, put_role_permission, remove_role_permission,
+};
 use crate::features::roles::roles_handler::{
-  assign_user_role, create_role, get_roles, get_user_roles, update_role,
+  assign_user_role, assign_users_role, create_role, delete_role, get_roles, get_user_roles,
+  list_roles, post_role, post_role_users, put_role, put_user_role, remove_role, update_role,
 };
-use crate::{features::users::user_entity::UserRole, middleware::auth::RequireAuth};
+use crate::middleware::{auth::RequirePermission, transaction::TransactionScope};
+
+pub fn roles_routes() -> Scope {
+  web::scope("/roles")
+    .route(
+      "",
+      web::get()
+        .to(list_roles)
+        .wrap(RequirePermission::new("role:read")),
+    )
+    .route(
+      "",
+      web::post()
+        .to(post_role)
+        .wrap(RequirePermission::new("role:create")),
+    )
+    .route(
+      "/{id}",
+      web::put()
+        .to(put_role)
+        .wrap(RequirePermission::new("role:update")),
+    )
+    .route(
+      "/{id}",
+      web::delete()
+        .to(remove_role)
+        .wrap(TransactionScope)
+        .wrap(RequirePermission::new("role:delete")),
+    )
+    .route(
+      "/{id}/users",
+      web::post()
+        .to(post_role_users)
+        .wrap(TransactionScope)
+        .wrap(RequirePermission::new("role:assign")),
+    )
+    .route(
+      "/{role_id}/users/{user_id}",
+      web::put()
+        .to(put_user_role)
+        .wrap(RequirePermission::new("role:assign")),
+    )
+    .route(
+      "/{id}/permissions",
+      web::get()
+        .to(list_role_permissions)
+        .wrap(RequirePermission::new("permission:read")),
+    )
+    .route(
+      "/{role_id}/permissions/{permission_id}",
+      web::put()
+        .to(put_role_permission)
+        .wrap(RequirePermission::new("permission:assign")),
+    )
+    .route(
+      "/{role_id}/permissions/{permission_id}",
+      web::delete()
+        .to(remove_role_permission)
+        .wrap(RequirePermission::new("permission:assign")),
+    )
+}
+
+// Kept as deprecated aliases of `/roles` until clients moved over
 pub fn role_routes() -> Scope {
   web::scope("/role")
     .route(
       "/all",
       web::post()
         .to(get_roles)
-        .wrap(RequireAuth::allow_roles(vec![UserRole::Admin])),
+        .wrap(RequirePermission::new("role:read")),
     )
     .route(
       "/create",
       web::post()
         .to(create_role)
-        .wrap(RequireAuth::allow_roles(vec![UserRole::Admin])),
+        .wrap(RequirePermission::new("role:create")),
     )
     .route(
       "/update",
       web::post()
         .to(update_role)
-        .wrap(RequireAuth::allow_roles(vec![UserRole::Admin])),
+        .wrap(RequirePermission::new("role:update")),
     )
     .route(
       "/user_roles",
       web::post()
         .to(get_user_roles)
-        .wrap(RequireAuth::allow_roles(vec![UserRole::Admin])),
+        .wrap(RequirePermission::new("role:read")),
     )
     .route(
       "/assign_user_role",
       web::post()
         .to(assign_user_role)
-        .wrap(RequireAuth::allow_roles(vec![UserRole::Admin])),
+        .wrap(RequirePermission::new("role:assign")),
+    )
+    .route(
+      "/assign_users",
+      web::post()
+        .to(assign_users_role)
+        .wrap(TransactionScope)
+        .wrap(RequirePermission::new("role:assign")),
+    )
+    .route(
+      "/delete",
+      web::post()
+        .to(delete_role)
+        .wrap(TransactionScope)
+        .wrap(RequirePermission::new("role:delete")),
     )
 }
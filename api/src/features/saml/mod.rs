@@ -0,0 +1,4 @@
+pub mod saml_dto;
+pub mod saml_handler;
+pub mod saml_response;
+pub mod saml_route;
@@ -0,0 +1,31 @@
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+// --- Request Dto --- //
+
+#[derive(Deserialize, Serialize, Debug, ToSchema, IntoParams)]
+pub struct SamlLoginReqDto {
+  /// Path of this site to send the browser to after the login, e.g. `/dashboard`.
+  /// Without it the ACS answers with the token as JSON.
+  pub return_to: Option<String>,
+}
+
+impl SamlLoginReqDto {
+  // Only paths on this site, so the login can't be used to redirect elsewhere
+  pub fn is_valid_return_to(&self) -> bool {
+    self
+      .return_to
+      .as_deref()
+      .is_none_or(|path| path.starts_with('/') && !path.starts_with("//") && !path.contains('\\'))
+  }
+}
+
+/// Form the IdP makes the browser post to the ACS (HTTP-POST binding).
+#[derive(Deserialize, Serialize, Debug, ToSchema)]
+pub struct SamlAcsReqDto {
+  /// Base64 encoded `samlp:Response`
+  #[serde(rename = "SAMLResponse")]
+  pub saml_response: String,
+  #[serde(rename = "RelayState")]
+  pub relay_state: Option<String>,
+}
@@ -0,0 +1,418 @@
+use std::io::Write;
+
+use actix_web::{
+  HttpRequest, HttpResponse, Responder,
+  cookie::{
+    Cookie,
+    time::{Duration, OffsetDateTime},
+  },
+  http::header,
+  web,
+};
+use anyhow::{Context, Result};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use chrono::{SecondsFormat, Utc};
+use flate2::{Compression, write::DeflateEncoder};
+use url::Url;
+use uuid::Uuid;
+
+use crate::{
+  app_settings::SamlSetting,
+  app_state::AppState,
+  document,
+  dto::base_res_dto::{BaseResDto, Status},
+  error::StatusMessage,
+  features::{
+    audit::{
+      audit_entity::{AuditAction, AuditLogEntity},
+      audit_repo::AuditRepo,
+    },
+    auth::auth_dto::LoginResDto,
+    login_history::{
+      login_history_entity::{LoginAttemptEntity, LoginOutcome},
+      login_history_repo::LoginHistoryRepo,
+    },
+    saml::{
+      saml_dto::{SamlAcsReqDto, SamlLoginReqDto},
+      saml_response::{self, SamlIdentity},
+    },
+    users::{
+      user_dto::{UserDto, UserRegisterReqDto},
+      user_entity::{User, UserRole},
+    },
+  },
+  security_log::{SecurityEvent, SecurityEventType},
+  utils::{jwt_util::JwtUtil, xml_dsig},
+};
+
+const POST_BINDING: &str = "urn:oasis:names:tc:SAML:2.0:bindings:HTTP-POST";
+
+fn escape_xml(value: &str) -> String {
+  value
+    .replace('&', "&amp;")
+    .replace('<', "&lt;")
+    .replace('>', "&gt;")
+    .replace('"', "&quot;")
+}
+
+fn saml_setting(data: &AppState) -> Result<&SamlSetting, Status> {
+  data
+    .config
+    .saml
+    .as_ref()
+    .ok_or_else(|| Status::not_found(StatusMessage::NotFound("SAML".into())))
+}
+
+document!(saml_metadata);
+#[utoipa::path(
+    get,
+    path = "/api/v1/auth/saml/metadata",
+    tag = "Authentication",
+    responses(
+        (
+            status=200,
+            description= "Service provider metadata to register this API with the IdP",
+            content_type= "application/samlmetadata+xml",
+            body= String
+        ),
+        (
+            status=404,
+            description= "SAML is not configured",
+            body= Status
+        ),
+    )
+)]
+pub async fn saml_metadata(data: web::Data<AppState>) -> impl Responder {
+  let setting = match saml_setting(&data) {
+    Ok(setting) => setting,
+    Err(status) => return status.into_http_response(),
+  };
+  let metadata = format!(
+    concat!(
+      r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+      r#"<md:EntityDescriptor xmlns:md="urn:oasis:names:tc:SAML:2.0:metadata" entityID="{}">"#,
+      r#"<md:SPSSODescriptor AuthnRequestsSigned="false" WantAssertionsSigned="true" "#,
+      r#"protocolSupportEnumeration="urn:oasis:names:tc:SAML:2.0:protocol">"#,
+      r#"<md:AssertionConsumerService Binding="{}" Location="{}" index="0" isDefault="true"/>"#,
+      r#"</md:SPSSODescriptor></md:EntityDescriptor>"#
+    ),
+    escape_xml(&setting.entity_id),
+    POST_BINDING,
+    escape_xml(&setting.acs_url),
+  );
+  HttpResponse::Ok()
+    .content_type("application/samlmetadata+xml")
+    .body(metadata)
+}
+
+// HTTP-Redirect binding: raw deflate, then base64, then the query string
+fn login_redirect(setting: &SamlSetting, request_id: &str) -> Result<Url> {
+  let request = format!(
+    concat!(
+      r#"<samlp:AuthnRequest xmlns:samlp="urn:oasis:names:tc:SAML:2.0:protocol" "#,
+      r#"xmlns:saml="urn:oasis:names:tc:SAML:2.0:assertion" ID="{}" Version="2.0" "#,
+      r#"IssueInstant="{}" Destination="{}" AssertionConsumerServiceURL="{}" "#,
+      r#"ProtocolBinding="{}"><saml:Issuer>{}</saml:Issuer></samlp:AuthnRequest>"#
+    ),
+    request_id,
+    Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+    escape_xml(&setting.idp_sso_url),
+    escape_xml(&setting.acs_url),
+    POST_BINDING,
+    escape_xml(&setting.entity_id),
+  );
+  let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+  encoder.write_all(request.as_bytes())?;
+  let deflated = encoder.finish()?;
+
+  let mut url = Url::parse(&setting.idp_sso_url).context("Invalid saml.idp_sso_url")?;
+  url
+    .query_pairs_mut()
+    .append_pair("SAMLRequest", &STANDARD.encode(deflated));
+  Ok(url)
+}
+
+document!(saml_login);
+#[utoipa::path(
+    get,
+    path = "/api/v1/auth/saml/login",
+    tag = "Authentication",
+    params(SamlLoginReqDto),
+    responses(
+        (
+            status=302,
+            description= "Redirect to the IdP's sign-in page"
+        ),
+        (
+            status=400,
+            description= "return_to is not a path of this site",
+            body= Status
+        ),
+        (
+            status=404,
+            description= "SAML is not configured",
+            body= Status
+        ),
+    )
+)]
+pub async fn saml_login(
+  query: web::Query<SamlLoginReqDto>,
+  data: web::Data<AppState>,
+) -> impl Responder {
+  let setting = match saml_setting(&data) {
+    Ok(setting) => setting,
+    Err(status) => return status.into_http_response(),
+  };
+  if !query.is_valid_return_to() {
+    return Status::bad_request(StatusMessage::WrongParams).into_http_response();
+  }
+
+  // IDs must not start with a digit
+  let request_id = format!("_{}", Uuid::new_v4().simple());
+  let url = match login_redirect(setting, &request_id) {
+    Ok(url) => url,
+    Err(e) => return Status::server_error(format!("{:#}", e)).into_http_response(),
+  };
+  // The ACS only accepts answers to requests issued here, each one once
+  data
+    .saml_requests
+    .set(request_id, query.into_inner().return_to);
+  HttpResponse::Found()
+    .insert_header((header::LOCATION, url.to_string()))
+    .finish()
+}
+
+fn read_identity(form: &SamlAcsReqDto, setting: &SamlSetting) -> Result<SamlIdentity> {
+  let encoded: String = form
+    .saml_response
+    .chars()
+    .filter(|c| !c.is_ascii_whitespace())
+    .collect();
+  let xml = String::from_utf8(
+    STANDARD
+      .decode(encoded)
+      .context("SAMLResponse is not base64")?,
+  )
+  .context("SAMLResponse is not UTF-8")?;
+  let pem = std::fs::read(&setting.idp_cert_path)
+    .with_context(|| format!("Failed to read {}", setting.idp_cert_path))?;
+  let idp_key = xml_dsig::public_key_from_pem(&pem)?;
+  saml_response::read_response(&xml, setting, &idp_key, Utc::now())
+}
+
+// The user the assertion is about, created first when `auto_provision` allows it
+async fn resolve_user(
+  data: &AppState,
+  setting: &SamlSetting,
+  identity: &SamlIdentity,
+) -> Result<Option<User>> {
+  let user_name = match &setting.user_name_attribute {
+    Some(attribute) => identity
+      .attribute(attribute)
+      .with_context(|| format!("Assertion has no '{}' attribute", attribute))?,
+    None => identity.name_id.as_str(),
+  };
+  let mut repo = data.user_repo();
+  if let Some(user) = repo.get_by_username(user_name).await? {
+    return Ok(Some(user));
+  }
+  if !setting.auto_provision {
+    return Ok(None);
+  }
+
+  let attribute = |name: &Option<String>| name.as_deref().and_then(|name| identity.attribute(name));
+  let user = UserRegisterReqDto {
+    user_name: user_name.to_string(),
+    // Never used, the account signs in through the IdP
+    password: format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple()),
+    email: attribute(&setting.email_attribute)
+      .unwrap_or_default()
+      .to_string(),
+    name: attribute(&setting.display_name_attribute)
+      .unwrap_or(user_name)
+      .to_string(),
+    role: UserRole::User.to_str().to_string(),
+  };
+  repo.create(&user).await?;
+  repo.get_by_username(user_name).await
+}
+
+// Adds the roles `role_mapping` grants for the assertion's role attribute values
+async fn apply_role_mapping(
+  data: &AppState,
+  setting: &SamlSetting,
+  identity: &SamlIdentity,
+  user_id: i32,
+) -> Result<()> {
+  let Some(values) = setting
+    .role_attribute
+    .as_ref()
+    .and_then(|attribute| identity.attributes.get(attribute))
+  else {
+    return Ok(());
+  };
+  let mut role_repo = data.role_repo();
+  for role_name in values
+    .iter()
+    .filter_map(|value| setting.role_mapping.get(value))
+    .flatten()
+  {
+    let Some(role) = role_repo.get_by_name(role_name).await? else {
+      eprintln!("saml.role_mapping names unknown role '{}'", role_name);
+      continue;
+    };
+    if !role_repo.is_user_role_exist(user_id, role.id).await {
+      role_repo.assign_user_role(user_id, role.id).await?;
+    }
+  }
+  data.invalidate_user(user_id);
+  Ok(())
+}
+
+document!(saml_acs);
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/saml/acs",
+    tag = "Authentication",
+    request_body(
+        content = SamlAcsReqDto,
+        content_type = "application/x-www-form-urlencoded",
+        description = "Posted by the browser on behalf of the IdP"
+    ),
+    responses(
+        (
+            status=200,
+            description= "Login successfully, the token is also set as the auth cookie",
+            body= BaseResDto<LoginResDto>
+        ),
+        (
+            status=303,
+            description= "Login successfully, redirect to the return_to given to /saml/login"
+        ),
+        (
+            status=401,
+            description= "The SAML response was rejected or the user is unknown",
+            body= Status
+        ),
+        (
+            status=403,
+            description= "Account is disabled",
+            body= Status
+        ),
+    )
+)]
+pub async fn saml_acs(
+  form: web::Form<SamlAcsReqDto>,
+  http_req: HttpRequest,
+  data: web::Data<AppState>,
+) -> impl Responder {
+  let setting = match saml_setting(&data) {
+    Ok(setting) => setting,
+    Err(status) => return status.into_http_response(),
+  };
+  let rejected = || Status::unauthorized(StatusMessage::SsoRejected).into_http_response();
+  // Reasons are only logged, they would help forging a response
+  let identity = match read_identity(&form, setting) {
+    Ok(identity) => identity,
+    Err(e) => {
+      eprintln!("Rejected SAML response: {:#}", e);
+      return rejected();
+    }
+  };
+  let Some(return_to) = data.saml_requests.take(&identity.in_response_to) else {
+    eprintln!(
+      "Rejected SAML response: '{}' is unknown, expired or already used",
+      identity.in_response_to
+    );
+    return rejected();
+  };
+
+  let mut audit_repo = AuditRepo::new(&data);
+  let mut history_repo = LoginHistoryRepo::new(&data);
+  let rate_limit = data.runtime().rate_limit.clone();
+  let user = match resolve_user(&data, setting, &identity).await {
+    Ok(Some(user)) => user,
+    Ok(None) => {
+      history_repo
+        .record(
+          LoginAttemptEntity::new(&identity.name_id, None, LoginOutcome::InvalidCredentials)
+            .with_request(&http_req, &rate_limit),
+        )
+        .await;
+      eprintln!("Rejected SAML login of unknown user '{}'", identity.name_id);
+      return rejected();
+    }
+    Err(e) => return Status::server_error(format!("{:#}", e)).into_http_response(),
+  };
+  let actor_id = Some(user.id);
+  if !user.is_active {
+    history_repo
+      .record(
+        LoginAttemptEntity::new(&user.user_name, actor_id, LoginOutcome::AccountDisabled)
+          .with_request(&http_req, &rate_limit),
+      )
+      .await;
+    return Status::account_disabled().into_http_response();
+  }
+  if let Err(e) = apply_role_mapping(&data, setting, &identity, user.id).await {
+    return Status::server_error(format!("Failed to apply the role mapping: {}", e))
+      .or_query_timeout(&e)
+      .into_http_response();
+  }
+
+  let permissions = data
+    .permission_repo()
+    .get_user_permissions(user.id)
+    .await
+    .unwrap_or_default();
+  let role_names = data
+    .role_repo()
+    .get_user_role_names(user.id)
+    .await
+    .unwrap_or_default();
+  let user_dto = UserDto::from(user).with_roles(role_names);
+  let token = match JwtUtil::from_state(&data)
+    .bound_to_device(&http_req)
+    .create_token(&user_dto, &permissions)
+  {
+    Ok(token) => token,
+    Err(e) => return Status::server_error(e.to_string()).into_http_response(),
+  };
+  audit_repo
+    .record(
+      AuditLogEntity::new(AuditAction::Login, actor_id, &user_dto.user_name)
+        .with_details("saml")
+        .with_request(&http_req),
+    )
+    .await;
+  data.security_log.emit(
+    SecurityEvent::new(SecurityEventType::LoginSucceeded)
+      .actor(actor_id)
+      .user_name(&user_dto.user_name)
+      .details("saml")
+      .with_request(&http_req),
+  );
+  history_repo
+    .record(
+      LoginAttemptEntity::new(&user_dto.user_name, actor_id, LoginOutcome::Success)
+        .with_request(&http_req, &rate_limit),
+    )
+    .await;
+
+  let cookie = Cookie::build(data.config.cookie.name.clone(), token.clone())
+    .path("/")
+    .http_only(true)
+    .expires(
+      OffsetDateTime::now_utc() + Duration::minutes(data.config.jwt.expiration_minutes as i64),
+    )
+    .finish();
+  match return_to {
+    Some(path) => HttpResponse::SeeOther()
+      .cookie(cookie)
+      .insert_header((header::LOCATION, path))
+      .finish(),
+    None => HttpResponse::Ok()
+      .cookie(cookie)
+      .json(Status::success_with_data(LoginResDto { token })),
+  }
+}
@@ -0,0 +1,194 @@
+use std::collections::HashMap;
+
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Duration, Utc};
+use roxmltree::{Document, Node};
+
+use crate::{app_settings::SamlSetting, utils::xml_dsig};
+
+const PROTOCOL_NS: &str = "urn:oasis:names:tc:SAML:2.0:protocol";
+const ASSERTION_NS: &str = "urn:oasis:names:tc:SAML:2.0:assertion";
+const STATUS_SUCCESS: &str = "urn:oasis:names:tc:SAML:2.0:status:Success";
+const BEARER: &str = "urn:oasis:names:tc:SAML:2.0:cm:bearer";
+
+/// What a valid assertion says about the user.
+pub struct SamlIdentity {
+  pub name_id: String,
+  /// Attribute values by attribute name
+  pub attributes: HashMap<String, Vec<String>>,
+  /// ID of our AuthnRequest the assertion answers
+  pub in_response_to: String,
+}
+
+impl SamlIdentity {
+  pub fn attribute(&self, name: &str) -> Option<&str> {
+    self
+      .attributes
+      .get(name)
+      .and_then(|values| values.first())
+      .map(String::as_str)
+  }
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, ns: &str, name: &str) -> Option<Node<'a, 'input>> {
+  node
+    .children()
+    .find(|child| child.is_element() && child.has_tag_name((ns, name)))
+}
+
+// The whole text of `node`. The signature doesn't cover comments, so text split by one is
+// refused: `admin@corp.com<!---->.evil.com` must not be read as `admin@corp.com` when the IdP
+// vouched for `admin@corp.com.evil.com`
+fn text_of(node: Option<Node>) -> Result<Option<String>> {
+  let Some(node) = node else {
+    return Ok(None);
+  };
+  let mut texts = node.children().filter(|child| !child.is_element());
+  match (texts.next(), texts.next()) {
+    (None, _) => Ok(None),
+    (Some(text), None) if text.is_text() => Ok(text.text().map(|text| text.trim().to_string())),
+    _ => bail!(
+      "<{}> holds a comment or more than one text node",
+      node.tag_name().name()
+    ),
+  }
+}
+
+fn instant(node: Node, name: &str) -> Result<Option<DateTime<Utc>>> {
+  node
+    .attribute(name)
+    .map(|value| {
+      DateTime::parse_from_rfc3339(value)
+        .map(|instant| instant.with_timezone(&Utc))
+        .with_context(|| format!("Invalid {} '{}'", name, value))
+    })
+    .transpose()
+}
+
+/// Validates a decoded `SAMLResponse` and returns the identity of its assertion.
+///
+/// Either the assertion or the whole response must be signed with the IdP's key, and only
+/// what is covered by that signature is read.
+pub fn read_response(
+  xml: &str,
+  setting: &SamlSetting,
+  idp_key: &[u8],
+  now: DateTime<Utc>,
+) -> Result<SamlIdentity> {
+  let doc = Document::parse(xml).context("Response is not valid XML")?;
+  let response = doc.root_element();
+  if !response.has_tag_name((PROTOCOL_NS, "Response")) {
+    bail!("Root element is not a samlp:Response");
+  }
+  if let Some(destination) = response.attribute("Destination")
+    && destination != setting.acs_url
+  {
+    bail!("Response is meant for '{}'", destination);
+  }
+
+  let status = child(response, PROTOCOL_NS, "Status")
+    .and_then(|status| child(status, PROTOCOL_NS, "StatusCode"))
+    .and_then(|code| code.attribute("Value"));
+  if status != Some(STATUS_SUCCESS) {
+    bail!("IdP answered with status {:?}", status);
+  }
+
+  if child(response, ASSERTION_NS, "EncryptedAssertion").is_some() {
+    bail!("Encrypted assertions are not supported");
+  }
+  let mut assertions = response
+    .children()
+    .filter(|child| child.is_element() && child.has_tag_name((ASSERTION_NS, "Assertion")));
+  let (Some(assertion), None) = (assertions.next(), assertions.next()) else {
+    bail!("Exactly one assertion is expected");
+  };
+
+  if xml_dsig::signature_of(assertion).is_some() {
+    xml_dsig::verify_enveloped(assertion, idp_key)?;
+  } else if xml_dsig::signature_of(response).is_some() {
+    xml_dsig::verify_enveloped(response, idp_key)?;
+  } else {
+    bail!("Neither the response nor the assertion is signed");
+  }
+
+  let issuer = text_of(child(assertion, ASSERTION_NS, "Issuer"))?;
+  if issuer.as_deref() != Some(setting.idp_entity_id.as_str()) {
+    bail!("Assertion was issued by {:?}", issuer);
+  }
+
+  let skew = Duration::seconds(setting.clock_skew_secs as i64);
+  let conditions =
+    child(assertion, ASSERTION_NS, "Conditions").context("Conditions are missing")?;
+  if instant(conditions, "NotBefore")?.is_some_and(|not_before| now + skew < not_before) {
+    bail!("Assertion is not valid yet");
+  }
+  if instant(conditions, "NotOnOrAfter")?
+    .is_some_and(|not_on_or_after| now - skew >= not_on_or_after)
+  {
+    bail!("Assertion has expired");
+  }
+  let mut for_us = false;
+  for audience in conditions
+    .children()
+    .filter(|node| node.has_tag_name((ASSERTION_NS, "AudienceRestriction")))
+    .flat_map(|restriction| restriction.children())
+    .filter(|node| node.has_tag_name((ASSERTION_NS, "Audience")))
+  {
+    for_us |= text_of(Some(audience))?.as_deref() == Some(setting.entity_id.as_str());
+  }
+  if !for_us {
+    bail!("Assertion is not meant for '{}'", setting.entity_id);
+  }
+
+  let subject = child(assertion, ASSERTION_NS, "Subject").context("Subject is missing")?;
+  let name_id = text_of(child(subject, ASSERTION_NS, "NameID"))?
+    .filter(|name_id| !name_id.is_empty())
+    .context("NameID is missing")?;
+
+  // A bearer confirmation addressed to our ACS, answering one of our requests
+  let mut in_response_to = None;
+  for confirmation in subject
+    .children()
+    .filter(|node| node.has_tag_name((ASSERTION_NS, "SubjectConfirmation")))
+    .filter(|node| node.attribute("Method") == Some(BEARER))
+  {
+    let Some(data) = child(confirmation, ASSERTION_NS, "SubjectConfirmationData") else {
+      continue;
+    };
+    let expired =
+      instant(data, "NotOnOrAfter")?.is_none_or(|not_on_or_after| now - skew >= not_on_or_after);
+    if data.attribute("Recipient") == Some(setting.acs_url.as_str()) && !expired {
+      in_response_to = data.attribute("InResponseTo").map(str::to_string);
+      break;
+    }
+  }
+  let in_response_to = in_response_to.context("No usable bearer subject confirmation")?;
+
+  let mut attributes: HashMap<String, Vec<String>> = HashMap::new();
+  for statement in assertion
+    .children()
+    .filter(|node| node.has_tag_name((ASSERTION_NS, "AttributeStatement")))
+  {
+    for attribute in statement
+      .children()
+      .filter(|node| node.has_tag_name((ASSERTION_NS, "Attribute")))
+    {
+      let Some(name) = attribute.attribute("Name") else {
+        continue;
+      };
+      let values = attributes.entry(name.to_string()).or_default();
+      for value in attribute
+        .children()
+        .filter(|node| node.has_tag_name((ASSERTION_NS, "AttributeValue")))
+      {
+        values.extend(text_of(Some(value))?);
+      }
+    }
+  }
+
+  Ok(SamlIdentity {
+    name_id,
+    attributes,
+    in_response_to,
+  })
+}
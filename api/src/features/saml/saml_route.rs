@@ -0,0 +1,17 @@
+use actix_web::{Scope, web};
+
+use crate::features::saml::saml_handler::{saml_acs, saml_login, saml_metadata};
+
+// Signed responses with a few attributes stay well below this
+const ACS_FORM_LIMIT: usize = 256 * 1024;
+
+pub fn saml_routes() -> Scope {
+  web::scope("/auth/saml")
+    .route("/metadata", web::get().to(saml_metadata))
+    .route("/login", web::get().to(saml_login))
+    .service(
+      web::resource("/acs")
+        .app_data(web::FormConfig::default().limit(ACS_FORM_LIMIT))
+        .route(web::post().to(saml_acs)),
+    )
+}
@@ -0,0 +1,239 @@
+use actix_web::{
+  HttpResponse, Responder,
+  http::header::{CACHE_CONTROL, CONTENT_TYPE},
+  web::{self, BytesMut},
+};
+use futures::StreamExt;
+
+use crate::{
+  app_state::AppState,
+  document,
+  dto::base_res_dto::{BaseResDto, Status},
+  error::StatusMessage,
+  middleware::auth::Authenticated,
+  storage::{
+    StoredObject,
+    images::{ImageJob, variant_key},
+  },
+};
+
+fn avatar_key(user_id: i32) -> String {
+  format!("avatars/{}", user_id)
+}
+
+// Uploads wait here, unserved, until the image worker has stripped their metadata
+fn staging_key(user_id: i32) -> String {
+  format!("uploads/avatars/{}", user_id)
+}
+
+/// The content type is taken from the file's magic bytes, the request's `Content-Type` is not trusted.
+fn sniff_image(bytes: &[u8]) -> Option<&'static str> {
+  if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+    Some("image/png")
+  } else if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+    Some("image/jpeg")
+  } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+    Some("image/gif")
+  } else if bytes.len() >= 12 && &bytes[..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+    Some("image/webp")
+  } else {
+    None
+  }
+}
+
+document!(upload_my_avatar);
+#[utoipa::path(
+    put,
+    path = "/api/v1/users/me/avatar",
+    tag = "Users",
+    request_body(
+        content = Vec<u8>,
+        description = "A PNG, JPEG, GIF or WebP image",
+        content_type = "application/octet-stream"),
+    responses( 
+        (
+            status=200, 
+            description= "Avatar queued, it is served once resized, usually within seconds", 
+            body= BaseResDto<String>
+        ),
+        (
+            status=400, 
+            description= "Not a supported image", 
+            body= Status
+        ),
+        (
+            status=413, 
+            description= "Larger than `storage.max_avatar_bytes`", 
+            body= Status
+        ),
+        (
+            status=500, 
+            description= "Internal Server Error", 
+            body= Status 
+        ),
+    )
+)]
+// Read as a stream so `storage.max_avatar_bytes` applies instead of `limits.payload_max_bytes`
+pub async fn upload_my_avatar(
+  mut payload: web::Payload,
+  current_user: Authenticated,
+  data: web::Data<AppState>,
+) -> impl Responder {
+  let limit = data.config.storage.max_avatar_bytes;
+  let mut body = BytesMut::new();
+  while let Some(chunk) = payload.next().await {
+    let chunk = match chunk {
+      Ok(chunk) => chunk,
+      Err(e) => return Status::bad_request(format!("Invalid body: {}", e)).into_http_response(),
+    };
+    if body.len() + chunk.len() > limit {
+      return Status::payload_too_large(limit).into_http_response();
+    }
+    body.extend_from_slice(&chunk);
+  }
+  let body = body.freeze();
+  let Some(content_type) = sniff_image(&body) else {
+    return Status::bad_request(StatusMessage::UnsupportedImage).into_http_response();
+  };
+
+  let object = StoredObject {
+    content_type: content_type.to_string(),
+    bytes: body,
+  };
+  let source_key = staging_key(current_user.id);
+  if let Err(e) = data.storage.put(&source_key, object).await {
+    return Status::server_error(format!("Failed to store avatar: {}", e)).into_http_response();
+  }
+  let job = ImageJob {
+    source_key,
+    target_key: avatar_key(current_user.id),
+  };
+  match data.images.enqueue(job) {
+    Ok(()) => Status::success().into_http_response(),
+    Err(e) => Status::server_error(e.to_string()).into_http_response(),
+  }
+}
+
+async fn serve(data: &AppState, key: &str) -> HttpResponse {
+  match data.storage.get(key).await {
+    Ok(Some(object)) => HttpResponse::Ok()
+      .insert_header((CONTENT_TYPE, object.content_type))
+      .insert_header((CACHE_CONTROL, "private, max-age=300"))
+      .body(object.bytes),
+    Ok(None) => {
+      Status::not_found(StatusMessage::NotFound("Avatar".to_string())).into_http_response()
+    }
+    Err(e) => Status::server_error(format!("Failed to read avatar: {}", e)).into_http_response(),
+  }
+}
+
+document!(get_user_avatar);
+#[utoipa::path(
+    get,
+    path = "/api/v1/users/{id}/avatar",
+    tag = "Users",
+    params(("id" = i32, Path, description = "User id")),
+    responses( 
+        (
+            status=200, 
+            description= "The avatar at its uploaded size, JPEG for photos and PNG otherwise", 
+            content(
+                (Vec<u8> = "image/png"),
+                (Vec<u8> = "image/jpeg")
+            )
+        ),
+        (
+            status=404, 
+            description= "The user has no avatar", 
+            body= Status
+        ),
+        (
+            status=500, 
+            description= "Internal Server Error", 
+            body= Status 
+        ),
+    )
+)]
+pub async fn get_user_avatar(id: web::Path<i32>, data: web::Data<AppState>) -> impl Responder {
+  serve(&data, &avatar_key(id.into_inner())).await
+}
+
+document!(get_user_avatar_variant);
+#[utoipa::path(
+    get,
+    path = "/api/v1/users/{id}/avatar/{size}",
+    tag = "Users",
+    params(
+        ("id" = i32, Path, description = "User id"),
+        ("size" = u32, Path, description = "One of `storage.images.variant_sizes`")
+    ),
+    responses( 
+        (
+            status=200, 
+            description= "The avatar resized to at most `size` pixels on its longest side", 
+            content(
+                (Vec<u8> = "image/png"),
+                (Vec<u8> = "image/jpeg")
+            )
+        ),
+        (
+            status=404, 
+            description= "The user has no avatar or `size` isn't configured", 
+            body= Status
+        ),
+        (
+            status=500, 
+            description= "Internal Server Error", 
+            body= Status 
+        ),
+    )
+)]
+pub async fn get_user_avatar_variant(
+  path: web::Path<(i32, u32)>,
+  data: web::Data<AppState>,
+) -> impl Responder {
+  let (id, size) = path.into_inner();
+  if !data.images.variant_sizes().contains(&size) {
+    return Status::not_found(StatusMessage::NotFound("Avatar".to_string())).into_http_response();
+  }
+  serve(&data, &variant_key(&avatar_key(id), size)).await
+}
+
+document!(delete_my_avatar);
+#[utoipa::path(
+    delete,
+    path = "/api/v1/users/me/avatar",
+    tag = "Users",
+    responses( 
+        (
+            status=200, 
+            description= "Avatar removed, also when there was none", 
+            body= BaseResDto<String>
+        ),
+        (
+            status=500, 
+            description= "Internal Server Error", 
+            body= Status 
+        ),
+    )
+)]
+pub async fn delete_my_avatar(
+  current_user: Authenticated,
+  data: web::Data<AppState>,
+) -> impl Responder {
+  let key = avatar_key(current_user.id);
+  let mut keys = vec![staging_key(current_user.id), key.clone()];
+  keys.extend(
+    data
+      .images
+      .variant_sizes()
+      .iter()
+      .map(|&size| variant_key(&key, size)),
+  );
+  for key in &keys {
+    if let Err(e) = data.storage.delete(key).await {
+      return Status::server_error(format!("Failed to delete avatar: {}", e)).into_http_response();
+    }
+  }
+  Status::success().into_http_response()
+}
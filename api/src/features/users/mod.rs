@@ -1,3 +1,4 @@
+pub mod avatar_handler;
 pub mod user_dto;
 pub mod user_entity;
 pub mod user_handler;
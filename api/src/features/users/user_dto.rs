@@ -1,6 +1,14 @@
-use crate::features::users::user_entity::{User, UserRole};
+use crate::{
+  dto::{paged_res_dto::MAX_PAGE_SIZE, sort::SortDir, versioned_dto::VersionedDto},
+  features::{
+    roles::roles_dto::UserRolesResDto,
+    users::user_entity::{User, UserRole},
+  },
+};
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use utoipa::ToSchema;
+use utoipa::{IntoParams, ToSchema};
+use validator::Validate;
 
 #[derive(Serialize, Deserialize, Clone, ToSchema)]
 pub struct UserDto {
@@ -9,6 +17,28 @@ pub struct UserDto {
   pub name: String,
   pub email: String,
   pub role: UserRole,
+  pub is_active: bool,
+  /// Effective role names: the legacy `role` plus roles assigned from the roles table
+  #[serde(default)]
+  pub roles: Vec<String>,
+  #[serde(default)]
+  pub last_login_at: Option<DateTime<Utc>>,
+}
+
+impl UserDto {
+  pub fn has_role(&self, role: &str) -> bool {
+    self.roles.iter().any(|r| r.eq_ignore_ascii_case(role))
+  }
+
+  // Merges role names resolved from the roles table
+  pub fn with_roles(mut self, names: Vec<String>) -> Self {
+    for name in names {
+      if !self.has_role(&name) {
+        self.roles.push(name);
+      }
+    }
+    self
+  }
 }
 
 impl From<User> for UserDto {
@@ -18,31 +48,157 @@ impl From<User> for UserDto {
       name: user.name,
       user_name: user.user_name,
       email: user.email,
+      roles: vec![user.role.to_str().to_string()],
       role: user.role,
+      is_active: user.is_active,
+      last_login_at: user.last_login_at,
     }
   }
 }
 
 // --- Request Dto --- //
 
-#[derive(Deserialize, Serialize, Debug, ToSchema)]
+#[derive(Deserialize, Serialize, Debug, ToSchema, Validate)]
 pub struct UserRegisterReqDto {
+  #[validate(length(min = 1, max = 50))]
   pub user_name: String,
+  #[validate(length(min = 1))]
   pub password: String,
+  #[validate(email, length(max = 255))]
   pub email: String,
+  #[validate(length(min = 1, max = 100))]
   pub name: String,
+  #[validate(length(min = 1, max = 50))]
   pub role: String,
 }
 
+impl VersionedDto for UserRegisterReqDto {
+  const LATEST_VERSION: u32 = 1;
+}
+
+#[derive(Deserialize, Serialize, Debug, ToSchema, IntoParams, Validate)]
+pub struct GetUsersReqDto {
+  #[serde(default = "default_page")]
+  #[validate(range(min = 1))]
+  pub page: i32,
+  #[serde(default = "default_page_size")]
+  #[validate(range(min = 1, max = MAX_PAGE_SIZE))]
+  pub page_size: i32,
+  #[serde(default)]
+  pub sort_by: UserSortBy,
+  #[serde(default)]
+  pub sort_dir: SortDir,
+}
+
+/// Columns users can be sorted by.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum UserSortBy {
+  #[default]
+  Id,
+  UserName,
+  Name,
+  Email,
+  Role,
+  IsActive,
+  CreatedAt,
+}
+
+// Default value for page
+fn default_page() -> i32 {
+  1
+}
+
+// Default value for page_size
+fn default_page_size() -> i32 {
+  20
+}
+
+#[derive(Deserialize, Serialize, Debug, ToSchema, IntoParams)]
+pub struct ExportUsersReqDto {
+  /// `csv` or `json`, defaults to `json`
+  pub format: Option<String>,
+}
+
 #[derive(Deserialize, Serialize, Debug, ToSchema)]
 pub struct GetUserByIdReqDto {
   pub id: i32,
 }
 
-#[derive(Deserialize, Serialize, Debug, ToSchema)]
+#[derive(Deserialize, Serialize, Debug, Clone, ToSchema)]
+pub struct SetUserActiveReqDto {
+  pub id: i32,
+}
+
+#[derive(Deserialize, Serialize, Debug, ToSchema, Validate)]
 pub struct UpdateUserReqDto {
   pub user_name: String,
+  #[validate(length(min = 1, max = 100))]
+  pub name: Option<String>,
+  pub email: Option<String>,
+  pub role: Option<String>,
+}
+
+/// Body of `PUT /users/{id}`, only the fields that are present are changed.
+#[derive(Deserialize, Serialize, Debug, ToSchema, Validate)]
+pub struct UserChangesReqDto {
+  #[validate(length(min = 1, max = 100))]
   pub name: Option<String>,
   pub email: Option<String>,
   pub role: Option<String>,
 }
+
+impl From<UpdateUserReqDto> for UserChangesReqDto {
+  fn from(value: UpdateUserReqDto) -> Self {
+    Self {
+      name: value.name,
+      email: value.email,
+      role: value.role,
+    }
+  }
+}
+
+#[derive(Deserialize, Serialize, Debug, ToSchema, Validate)]
+pub struct ChangeEmailReqDto {
+  #[validate(email, length(max = 255))]
+  pub new_email: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, ToSchema, Validate)]
+pub struct ChangePasswordReqDto {
+  #[validate(length(min = 1))]
+  pub current_password: String,
+  #[validate(length(min = 1))]
+  pub new_password: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, ToSchema)]
+pub struct ConfirmEmailChangeReqDto {
+  pub token: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, ToSchema, Validate)]
+pub struct ForgotPasswordReqDto {
+  #[validate(length(min = 1))]
+  pub user_name: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, ToSchema, Validate)]
+pub struct ResetPasswordReqDto {
+  #[validate(length(min = 1))]
+  pub token: String,
+  #[validate(length(min = 1))]
+  pub new_password: String,
+}
+
+// --- Response Dto --- //
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub struct MeResDto {
+  pub user: UserDto,
+  pub roles: Vec<UserRolesResDto>,
+  /// Effective permissions carried by the current token
+  pub permissions: Vec<String>,
+  /// Feature flags enabled for this user
+  pub features: Vec<String>,
+}
@@ -1,73 +1,91 @@
-use chrono::{DateTime, NaiveDateTime, Utc};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
 use domner_tech_sql_client::pool_manager::DbRow;
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
+use crate::db::{FromRow, RowExt};
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct User {
   pub id: i32,
   pub user_name: String,
   pub name: String,
+  /// Argon2 hash, never serialized so it stays out of the Redis cache; empty on a `User`
+  /// served from there, read it with `UserRepository::get_password_hash`
+  #[serde(skip)]
   pub password: String,
   pub email: String,
   pub role: UserRole,
+  pub is_active: bool,
   pub created_at: DateTime<Utc>,
   pub updated_at: DateTime<Utc>,
+  /// Last successful login, `None` until the user logged in once
+  #[serde(default)]
+  pub last_login_at: Option<DateTime<Utc>>,
 }
 
-impl From<&DbRow<'_>> for User {
-  fn from(row: &DbRow<'_>) -> Self {
-    let naive_created_at: NaiveDateTime = row
-      .get_mssql::<NaiveDateTime>("created_at")
-      .expect("Failed to get created_at")
-      .unwrap_or_default();
+impl FromRow for User {
+  fn from_row(row: &DbRow<'_>) -> Result<Self> {
+    Ok(Self {
+      id: row.column("id")?,
+      name: row.column("name")?,
+      email: row.column("email")?,
+      user_name: row.column("user_name")?,
+      password: row.column("password")?,
+      role: UserRole::from_name(&row.column::<String>("role")?),
+      is_active: row.column("is_active")?,
+      created_at: row.column("created_at")?,
+      // NULL until the user is first updated
+      updated_at: row.nullable("updated_at")?.unwrap_or_default(),
+      last_login_at: row.nullable("last_login_at")?,
+    })
+  }
+}
 
-    let created_at: DateTime<Utc> =
-      DateTime::<Utc>::from_naive_utc_and_offset(naive_created_at, Utc);
+// A pending email change, only applied once the new address is confirmed
+#[derive(Serialize, Deserialize, Clone)]
+pub struct EmailChange {
+  pub user_id: i32,
+  pub new_email: String,
+  pub token: String,
+  pub expires_at: DateTime<Utc>,
+}
 
-    let naive_updated_at: NaiveDateTime = row
-      .get_mssql::<NaiveDateTime>("updated_at")
-      .expect("Failed to get updated_at")
-      .unwrap_or_default();
-    let updated_at: DateTime<Utc> =
-      DateTime::<Utc>::from_naive_utc_and_offset(naive_updated_at, Utc);
-    Self {
-      id: row
-        .get_mssql::<i32>("id")
-        .expect("Failed to get id")
-        .unwrap_or_default(), // fallback if null
-      name: row
-        .get_mssql::<&str>("name")
-        .expect("Failed to get name")
-        .unwrap_or_default()
-        .to_string(),
-      email: row
-        .get_mssql::<&str>("email")
-        .expect("Failed to get email")
-        .unwrap_or_default()
-        .to_string(),
-      user_name: row
-        .get_mssql::<&str>("user_name")
-        .expect("Failed to get user_name")
-        .unwrap_or_default()
-        .to_string(),
-      password: row
-        .get_mssql::<&str>("password")
-        .expect("Failed to get password")
-        .unwrap_or_default()
-        .to_string(),
-      role: UserRole::from_str(
-        row
-          .get_mssql::<&str>("role")
-          .expect("Failed to get role")
-          .unwrap(),
-      ),
-      created_at: created_at,
-      updated_at: updated_at,
-    }
+impl FromRow for EmailChange {
+  fn from_row(row: &DbRow<'_>) -> Result<Self> {
+    Ok(Self {
+      user_id: row.column("user_id")?,
+      new_email: row.column("new_email")?,
+      token: row.column("token")?,
+      expires_at: row.column("expires_at")?,
+    })
+  }
+}
+
+// A pending one-time login code, see `/auth/request_otp`
+#[derive(Clone)]
+pub struct LoginCode {
+  pub user_id: i32,
+  pub code_hash: String,
+  /// Wrong codes tried so far
+  pub attempts: i32,
+  pub expires_at: DateTime<Utc>,
+}
+
+impl FromRow for LoginCode {
+  fn from_row(row: &DbRow<'_>) -> Result<Self> {
+    Ok(Self {
+      user_id: row.column("user_id")?,
+      code_hash: row.column("code_hash")?,
+      attempts: row.column("attempts")?,
+      expires_at: row.column("expires_at")?,
+    })
   }
 }
 
+// The legacy role stored on the user row. Authorization goes by role names
+// (see `UserDto::roles`) so roles created at runtime take effect too.
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, ToSchema)]
 pub enum UserRole {
   Admin,
@@ -84,7 +102,17 @@ impl UserRole {
     }
   }
 
-  pub fn from_str(s: &str) -> Self {
+  pub fn try_from_str(s: &str) -> Option<Self> {
+    match s {
+      "admin" => Some(UserRole::Admin),
+      "moderator" => Some(UserRole::Moderator),
+      "user" => Some(UserRole::User),
+      _ => None,
+    }
+  }
+
+  // Built-in roles, anything else is a role defined in the roles table
+  pub fn from_name(s: &str) -> Self {
     match s {
       "admin" => UserRole::Admin,
       "moderator" => UserRole::Moderator,
@@ -92,3 +120,9 @@ impl UserRole {
     }
   }
 }
+
+impl From<UserRole> for String {
+  fn from(role: UserRole) -> Self {
+    role.to_str().to_string()
+  }
+}
@@ -1,30 +1,115 @@
-use actix_web::{HttpResponse, Responder, web};
+use actix_web::{
+  HttpRequest, HttpResponse, Responder,
+  http::header::{ContentDisposition, DispositionParam, DispositionType},
+  web::{self, Bytes},
+};
+
+use chrono::Utc;
+use futures::{StreamExt, future::ready, stream};
+use sha2::{Digest, Sha256};
 
 use crate::{
   app_state::AppState,
   commons::status_code_const::StatusCodeConst,
-  dto::base_res_dto::{BaseResDto, Status},
+  document,
+  dto::{
+    base_res_dto::{BaseResDto, Status},
+    field_selection::{FieldSelection, FieldsParam},
+    paged_res_dto::PagedResDto,
+    validated_json::{ValidatedJson, ValidatedQuery},
+  },
   error::StatusMessage,
-  features::users::{
-    user_dto::{GetUserByIdReqDto, UpdateUserReqDto, UserDto},
-    user_entity::UserRole,
-    user_repo::UserRepo,
+  events::DomainEvent,
+  features::{
+    audit::{
+      audit_entity::{AuditAction, AuditLogEntity},
+      audit_repo::AuditRepo,
+    },
+    roles::roles_dto::UserRolesResDto,
+    users::{
+      user_dto::{
+        ChangeEmailReqDto, ChangePasswordReqDto, ConfirmEmailChangeReqDto, ExportUsersReqDto,
+        ForgotPasswordReqDto, GetUserByIdReqDto, GetUsersReqDto, MeResDto, ResetPasswordReqDto,
+        SetUserActiveReqDto, UpdateUserReqDto, UserChangesReqDto, UserDto,
+      },
+      user_entity::{User, UserRole},
+      user_repo::{UserRepo, UserRepository},
+    },
   },
+  middleware::{auth::Authenticated, transaction::DbTransaction},
+  notifications::NotificationKind,
+  security_log::{SecurityEvent, SecurityEventType},
+  utils::{feature_flags, mailer::EmailTemplate, password_hashing::PasswordHashing},
 };
 
+const EXPORT_PAGE_SIZE: i32 = 500;
+const EMAIL_CHANGE_EXPIRATION_MINUTES: i32 = 60;
+const PASSWORD_RESET_EXPIRATION_MINUTES: i32 = 30;
+
+// Admins keep full access, everyone else needs the permission on their token
+fn is_granted(user: &Authenticated, permission: &str) -> bool {
+  user.has_role(UserRole::Admin.to_str()) || user.has_permission(permission)
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ExportFormat {
+  Csv,
+  Json,
+}
+
+fn csv_field(value: &str) -> String {
+  if value.contains([',', '"', '\n', '\r']) {
+    format!("\"{}\"", value.replace('"', "\"\""))
+  } else {
+    value.to_string()
+  }
+}
+
+// Render one chunk of users, `written` tracks whether a JSON separator is needed
+fn render_export_rows(format: ExportFormat, written: &mut bool, users: Vec<UserDto>) -> String {
+  let mut buf = String::new();
+  for user in users {
+    match format {
+      ExportFormat::Csv => buf.push_str(&format!(
+        "{},{},{},{},{}\n",
+        user.id,
+        csv_field(&user.user_name),
+        csv_field(&user.name),
+        csv_field(&user.email),
+        user.role.to_str()
+      )),
+      ExportFormat::Json => {
+        if *written {
+          buf.push(',');
+        }
+        buf.push_str(&serde_json::to_string(&user).unwrap_or_default());
+      }
+    }
+    *written = true;
+  }
+  buf
+}
+
+document!(get_users);
 #[utoipa::path(
     post,
     path = "/api/v1/user/all",
     tag = "Users",
+    params(FieldsParam),
     request_body(
-        content = (),
+        content = GetUsersReqDto,
         description = "",
-        example = json!({})),
+        example = json!({
+          "page": 1,
+          "page_size": 20,
+          "sort_by": "user_name",
+          "sort_dir": "asc"
+        })),
     responses( 
         (
             status=200, 
             description= "Get users successfully", 
-            body= BaseResDto<Vec<UserDto>>
+            body= BaseResDto<PagedResDto<UserDto>>
         ),
         (
             status=400, 
@@ -38,24 +123,43 @@ use crate::{
         ),
     )
 )]
-pub async fn get_users(data: web::Data<AppState>) -> impl Responder {
-  let mut repo = UserRepo::new(&data);
+pub async fn get_users(
+  req: ValidatedJson<GetUsersReqDto>,
+  fields: FieldSelection<UserDto>,
+  data: web::Data<AppState>,
+) -> impl Responder {
+  let mut repo = data.user_repo();
 
-  match repo.get_users().await {
-    Ok(users) => HttpResponse::Ok().json(BaseResDto::<Vec<UserDto>> {
-      data: Some(users.into_iter().map(|u| UserDto::from(u)).collect()),
-      status: Status {
-        message: "Users retrieved successfully".to_string(),
-        code: StatusCodeConst::SUCCESS.to_string(),
-        status: 200,
-      },
-    }),
+  match repo
+    .get_users_paged(req.page, req.page_size, req.sort_by, req.sort_dir)
+    .await
+  {
+    Ok((users, total_count)) => {
+      HttpResponse::Ok().json(BaseResDto::<PagedResDto<serde_json::Value>> {
+        data: Some(PagedResDto::new(
+          users
+            .into_iter()
+            .map(|u| fields.apply(&UserDto::from(u)))
+            .collect(),
+          req.page,
+          req.page_size,
+          total_count,
+        )),
+        status: Status {
+          message: "Users retrieved successfully".to_string(),
+          code: StatusCodeConst::SUCCESS.to_string(),
+          status: 200,
+          request_id: None,
+        },
+      })
+    }
     Err(e) => {
       HttpResponse::BadRequest().json(Status::bad_request(format!("Failed to get users: {}", e)))
     }
   }
 }
 
+document!(get_user_by_id);
 #[utoipa::path(
     post,
     path = "/api/v1/user/by_id",
@@ -77,6 +181,11 @@ pub async fn get_users(data: web::Data<AppState>) -> impl Responder {
             description= "Validation Errors", 
             body= Status
         ),
+        (
+            status=403, 
+            description= "Permission denied", 
+            body= Status
+        ),
         (
             status=500, 
             description= "Internal Server Error", 
@@ -86,9 +195,14 @@ pub async fn get_users(data: web::Data<AppState>) -> impl Responder {
 )]
 pub async fn get_user_by_id(
   id: web::Json<GetUserByIdReqDto>,
+  current_user: Authenticated,
   data: web::Data<AppState>,
 ) -> impl Responder {
-  let mut repo = UserRepo::new(&data);
+  if id.id != current_user.id && !is_granted(&current_user, "user:read") {
+    return Status::forbidden().into_http_response();
+  }
+
+  let mut repo = data.user_repo();
 
   match repo.get_by_id(id.id).await {
     Ok(user) => {
@@ -104,6 +218,7 @@ pub async fn get_user_by_id(
   }
 }
 
+document!(update_user);
 #[utoipa::path(
     post,
     path = "/api/v1/user/update",
@@ -129,6 +244,11 @@ pub async fn get_user_by_id(
             description= "Validation Errors", 
             body= Status
         ),
+        (
+            status=403, 
+            description= "Permission denied", 
+            body= Status
+        ),
         (
             status=500, 
             description= "Internal Server Error", 
@@ -137,36 +257,1009 @@ pub async fn get_user_by_id(
     )
 )]
 pub async fn update_user(
-  user_update: web::Json<UpdateUserReqDto>,
+  user_update: ValidatedJson<UpdateUserReqDto>,
+  current_user: Authenticated,
+  http_req: HttpRequest,
   data: web::Data<AppState>,
 ) -> impl Responder {
-  let mut repo = UserRepo::new(&data);
+  let found = data
+    .user_repo()
+    .get_by_username(&user_update.user_name)
+    .await;
+  let changes = UserChangesReqDto::from(user_update.into_inner());
+  apply_user_update(found, &changes, &current_user, &http_req, &data).await
+}
 
-  match repo.get_by_username(&user_update.user_name).await {
-    Ok(user) => {
-      if let Some(mut u) = user {
-        if let Some(new_email) = &user_update.email {
-          u.email = new_email.clone();
-        }
-        if let Some(new_name) = &user_update.name {
-          u.name = new_name.clone();
-        }
-        if let Some(new_role) = &user_update.role {
-          let parsed_role = UserRole::from_str(new_role);
-          u.role = parsed_role;
-        }
+// Shared by the user_name based legacy route and `PUT /users/{id}`
+async fn apply_user_update(
+  found: anyhow::Result<Option<User>>,
+  changes: &UserChangesReqDto,
+  current_user: &Authenticated,
+  http_req: &HttpRequest,
+  data: &AppState,
+) -> HttpResponse {
+  let mut u = match found {
+    Ok(Some(u)) => u,
+    Ok(None) => {
+      return HttpResponse::BadRequest()
+        .json(Status::not_found(StatusMessage::NotFound("User".into())));
+    }
+    Err(e) => {
+      return HttpResponse::BadRequest()
+        .json(Status::bad_request(format!("Failed to update user: {}", e)));
+    }
+  };
+  if u.id != current_user.id && !is_granted(current_user, "user:write") {
+    return Status::forbidden().into_http_response();
+  }
+  if changes.role.is_some() && !is_granted(current_user, "role:write") {
+    return Status::forbidden().into_http_response();
+  }
 
-        match repo.update_user(&UserDto::from(u.clone())).await {
-          Ok(_) => HttpResponse::Ok().json(Status::success()),
-          Err(e) => HttpResponse::BadRequest()
-            .json(Status::bad_request(format!("Failed to update user: {}", e))),
-        }
-      } else {
-        HttpResponse::BadRequest().json(Status::not_found(StatusMessage::NotFound("User".into())))
+  // Email changes must go through the verified change_email flow
+  if let Some(new_email) = &changes.email
+    && !new_email.eq_ignore_ascii_case(&u.email)
+  {
+    return Status::bad_request(StatusMessage::EmailChangeRequiresVerification)
+      .into_http_response();
+  }
+  if let Some(new_name) = &changes.name {
+    u.name = new_name.clone();
+  }
+  if let Some(new_role) = &changes.role {
+    let parsed_role = UserRole::from_name(new_role);
+    u.role = parsed_role;
+  }
+
+  let mut repo = data.user_repo();
+  match repo.update_user(&UserDto::from(u.clone())).await {
+    Ok(_) => {
+      if changes.role.is_some() {
+        data.invalidate_access_cache();
       }
+      data
+        .events
+        .publish(DomainEvent::UserUpdated { user_id: u.id });
+      AuditRepo::new(data)
+        .record(
+          AuditLogEntity::new(
+            AuditAction::UpdateUser,
+            Some(current_user.id),
+            format!("user:{}", u.id),
+          )
+          .with_request(http_req),
+        )
+        .await;
+      HttpResponse::Ok().json(Status::success())
     }
     Err(e) => {
       HttpResponse::BadRequest().json(Status::bad_request(format!("Failed to update user: {}", e)))
     }
   }
 }
+
+document!(get_me);
+#[utoipa::path(
+    get,
+    path = "/api/v1/user/me",
+    tag = "Users",
+    responses( 
+        (
+            status=200, 
+            description= "Get current user successfully", 
+            body= BaseResDto<MeResDto>
+        ),
+        (
+            status=401, 
+            description= "Unauthorized", 
+            body= Status
+        ),
+        (
+            status=500, 
+            description= "Internal Server Error", 
+            body= Status 
+        ),
+    )
+)]
+pub async fn get_me(user: Authenticated, data: web::Data<AppState>) -> impl Responder {
+  let mut role_repo = data.role_repo();
+
+  match role_repo.get_user_roles(user.id).await {
+    Ok(user_roles) => HttpResponse::Ok().json(Status::success_with_data(MeResDto {
+      user: (*user).clone(),
+      roles: user_roles
+        .iter()
+        .filter(|ur| ur.is_in_role)
+        .map(UserRolesResDto::from)
+        .collect(),
+      permissions: user.permissions.clone(),
+      features: feature_flags::enabled_flags(&data.runtime().feature_flags, &user),
+    })),
+    Err(e) => Status::bad_request(format!("Failed to get user roles: {}", e))
+      .or_query_timeout(&e)
+      .into_http_response(),
+  }
+}
+
+document!(export_users);
+#[utoipa::path(
+    get,
+    path = "/api/v1/user/export",
+    tag = "Users",
+    params(ExportUsersReqDto),
+    responses( 
+        (
+            status=200, 
+            description= "Users exported as a streamed csv or json file", 
+            content(
+                (String = "text/csv"),
+                (Vec<UserDto> = "application/json")
+            )
+        ),
+        (
+            status=400, 
+            description= "Validation Errors", 
+            body= Status
+        ),
+    )
+)]
+pub async fn export_users(
+  query: web::Query<ExportUsersReqDto>,
+  data: web::Data<AppState>,
+) -> impl Responder {
+  let format = match query.format.as_deref().unwrap_or("json") {
+    "csv" => ExportFormat::Csv,
+    "json" => ExportFormat::Json,
+    _ => return Status::bad_request(StatusMessage::WrongParams.to_str()).into_http_response(),
+  };
+
+  let (header, footer) = match format {
+    ExportFormat::Csv => ("id,user_name,name,email,role\n", ""),
+    ExportFormat::Json => ("[", "]"),
+  };
+
+  // Rows arrive one by one from the DB stream, only a chunk of them is held at a time
+  let mut written = false;
+  let rows = UserRepo::stream_users(data.into_inner(), EXPORT_PAGE_SIZE)
+    .ready_chunks(EXPORT_PAGE_SIZE as usize)
+    .map(move |chunk| -> Result<Bytes, std::io::Error> {
+      let users = chunk
+        .into_iter()
+        .map(|user| user.map(UserDto::from))
+        .collect::<anyhow::Result<Vec<UserDto>>>()
+        .map_err(|e| std::io::Error::other(e.to_string()))?;
+      Ok(Bytes::from(render_export_rows(format, &mut written, users)))
+    });
+  let body = stream::once(ready(Ok(Bytes::from_static(header.as_bytes()))))
+    .chain(rows)
+    .chain(stream::once(ready(Ok(Bytes::from_static(
+      footer.as_bytes(),
+    )))));
+
+  let (content_type, file_name) = match format {
+    ExportFormat::Csv => ("text/csv", "users.csv"),
+    ExportFormat::Json => ("application/json", "users.json"),
+  };
+  HttpResponse::Ok()
+    .content_type(content_type)
+    .insert_header(ContentDisposition {
+      disposition: DispositionType::Attachment,
+      parameters: vec![DispositionParam::Filename(file_name.to_string())],
+    })
+    .streaming(body)
+}
+
+document!(activate_user);
+#[utoipa::path(
+    post,
+    path = "/api/v1/user/activate",
+    tag = "Users",
+    request_body(
+        content = SetUserActiveReqDto,
+        description = "",
+        example = json!({
+          "id": 1
+        })),
+    responses( 
+        (
+            status=200, 
+            description= "Activate user successfully", 
+            body= Status
+        ),
+        (
+            status=400, 
+            description= "Validation Errors", 
+            body= Status
+        ),
+        (
+            status=404, 
+            description= "User not found", 
+            body= Status
+        ),
+    )
+)]
+pub async fn activate_user(
+  req: web::Json<SetUserActiveReqDto>,
+  current_user: Authenticated,
+  http_req: HttpRequest,
+  data: web::Data<AppState>,
+) -> impl Responder {
+  set_user_active(req.id, true, &current_user, &http_req, &data).await
+}
+
+document!(deactivate_user);
+#[utoipa::path(
+    post,
+    path = "/api/v1/user/deactivate",
+    tag = "Users",
+    request_body(
+        content = SetUserActiveReqDto,
+        description = "",
+        example = json!({
+          "id": 1
+        })),
+    responses( 
+        (
+            status=200, 
+            description= "Deactivate user successfully", 
+            body= Status
+        ),
+        (
+            status=400, 
+            description= "Validation Errors", 
+            body= Status
+        ),
+        (
+            status=404, 
+            description= "User not found", 
+            body= Status
+        ),
+    )
+)]
+pub async fn deactivate_user(
+  req: web::Json<SetUserActiveReqDto>,
+  current_user: Authenticated,
+  http_req: HttpRequest,
+  data: web::Data<AppState>,
+) -> impl Responder {
+  set_user_active(req.id, false, &current_user, &http_req, &data).await
+}
+
+async fn set_user_active(
+  id: i32,
+  is_active: bool,
+  current_user: &UserDto,
+  http_req: &HttpRequest,
+  data: &AppState,
+) -> HttpResponse {
+  // Admins must not lock themselves out
+  if id == current_user.id {
+    return Status::bad_request(StatusMessage::WrongParams.to_str()).into_http_response();
+  }
+
+  let mut repo = data.user_repo();
+  match repo.get_by_id(id).await {
+    Ok(Some(_)) => match repo.set_active(id, is_active).await {
+      Ok(_) => {
+        let action = if is_active {
+          AuditAction::ActivateUser
+        } else {
+          // The auth middleware refuses inactive users, so their tokens stop working now
+          data.security_log.emit(
+            SecurityEvent::new(SecurityEventType::TokensRevoked)
+              .actor(Some(current_user.id))
+              .target(format!("user:{}", id))
+              .details("user deactivated")
+              .with_request(http_req),
+          );
+          AuditAction::DeactivateUser
+        };
+        AuditRepo::new(data)
+          .record(
+            AuditLogEntity::new(action, Some(current_user.id), format!("user:{}", id))
+              .with_request(http_req),
+          )
+          .await;
+        HttpResponse::Ok().json(Status::success())
+      }
+      Err(e) => Status::bad_request(format!("Failed to update user: {}", e))
+        .or_query_timeout(&e)
+        .into_http_response(),
+    },
+    Ok(None) => Status::not_found(StatusMessage::NotFound("User".into())).into_http_response(),
+    Err(e) => Status::bad_request(format!("Failed to update user: {}", e))
+      .or_query_timeout(&e)
+      .into_http_response(),
+  }
+}
+
+document!(change_email);
+#[utoipa::path(
+    post,
+    path = "/api/v1/user/change_email",
+    tag = "Users",
+    request_body(
+        content = ChangeEmailReqDto,
+        description = "",
+        example = json!({
+          "new_email": "new@gmail.com"
+        })),
+    responses( 
+        (
+            status=200, 
+            description= "Confirmation token sent to the new email", 
+            body= Status
+        ),
+        (
+            status=400, 
+            description= "Validation Errors", 
+            body= Status
+        ),
+        (
+            status=401, 
+            description= "Unauthorized", 
+            body= Status
+        ),
+    )
+)]
+pub async fn change_email(
+  req: ValidatedJson<ChangeEmailReqDto>,
+  current_user: Authenticated,
+  http_req: HttpRequest,
+  data: web::Data<AppState>,
+) -> impl Responder {
+  let new_email = req.new_email.trim();
+  if new_email.eq_ignore_ascii_case(&current_user.email) {
+    return Status::bad_request(StatusMessage::WrongParams.to_str()).into_http_response();
+  }
+
+  // The current email stays active until the new one is confirmed
+  let token = uuid::Uuid::new_v4().simple().to_string();
+  let mut repo = data.user_repo();
+  if let Err(e) = repo
+    .create_email_change(
+      current_user.id,
+      new_email,
+      &token,
+      EMAIL_CHANGE_EXPIRATION_MINUTES,
+    )
+    .await
+  {
+    return Status::bad_request(format!("Failed to change email: {}", e))
+      .or_query_timeout(&e)
+      .into_http_response();
+  }
+
+  data.notifier.send_email_detached(
+    NotificationKind::EmailVerification,
+    current_user.id,
+    new_email,
+    EmailTemplate::Verification {
+      token,
+      expires_minutes: EMAIL_CHANGE_EXPIRATION_MINUTES,
+    },
+  );
+  AuditRepo::new(&data)
+    .record(
+      AuditLogEntity::new(
+        AuditAction::RequestEmailChange,
+        Some(current_user.id),
+        format!("user:{}", current_user.id),
+      )
+      .with_request(&http_req),
+    )
+    .await;
+  HttpResponse::Ok().json(Status::success())
+}
+
+document!(confirm_email);
+#[utoipa::path(
+    post,
+    path = "/api/v1/user/confirm_email",
+    tag = "Users",
+    request_body(
+        content = ConfirmEmailChangeReqDto,
+        description = "",
+        example = json!({
+          "token": "3f1c2a9e5b7d4c8e9a0b1c2d3e4f5a6b"
+        })),
+    responses( 
+        (
+            status=200, 
+            description= "Email changed successfully", 
+            body= Status
+        ),
+        (
+            status=400, 
+            description= "Token expired", 
+            body= Status
+        ),
+        (
+            status=404, 
+            description= "Email change request not found", 
+            body= Status
+        ),
+    )
+)]
+pub async fn confirm_email(
+  req: web::Json<ConfirmEmailChangeReqDto>,
+  tx: DbTransaction,
+  http_req: HttpRequest,
+  data: web::Data<AppState>,
+) -> impl Responder {
+  let mut repo = data.user_repo_in(&tx);
+
+  let email_change = match repo.get_email_change_by_token(&req.token).await {
+    Ok(Some(email_change)) => email_change,
+    Ok(None) => {
+      return Status::not_found(StatusMessage::NotFound("Email change request".into()))
+        .into_http_response();
+    }
+    Err(e) => {
+      return Status::bad_request(format!("Failed to change email: {}", e))
+        .or_query_timeout(&e)
+        .into_http_response();
+    }
+  };
+  if email_change.expires_at < Utc::now() {
+    return Status::bad_request(StatusMessage::TokenExpired).into_http_response();
+  }
+
+  let old_email = match repo.get_by_id(email_change.user_id).await {
+    Ok(Some(user)) => user.email,
+    Ok(None) => {
+      return Status::not_found(StatusMessage::NotFound("User".into())).into_http_response();
+    }
+    Err(e) => {
+      return Status::bad_request(format!("Failed to change email: {}", e))
+        .or_query_timeout(&e)
+        .into_http_response();
+    }
+  };
+
+  let result = match repo
+    .update_email(email_change.user_id, &email_change.new_email)
+    .await
+  {
+    Ok(_) => repo.delete_email_change(email_change.user_id).await,
+    Err(e) => Err(e),
+  };
+  if let Err(e) = result {
+    return Status::bad_request(format!("Failed to change email: {}", e))
+      .or_query_timeout(&e)
+      .into_http_response();
+  }
+
+  // Let the previous address know in case the change wasn't expected
+  data.notifier.send_email_detached(
+    NotificationKind::SecurityAlert,
+    email_change.user_id,
+    old_email,
+    EmailTemplate::EmailChanged {
+      new_email: email_change.new_email.clone(),
+    },
+  );
+  AuditRepo::new(&data)
+    .record(
+      AuditLogEntity::new(
+        AuditAction::ChangeEmail,
+        Some(email_change.user_id),
+        format!("user:{}", email_change.user_id),
+      )
+      .with_request(&http_req),
+    )
+    .await;
+  HttpResponse::Ok().json(Status::success())
+}
+
+// The current password counts as the newest of the `history_size` that can't be reused
+async fn is_recent_password(
+  repo: &mut dyn UserRepository,
+  user_id: i32,
+  current_hash: &str,
+  password: &str,
+  history_size: u32,
+) -> anyhow::Result<bool> {
+  if history_size == 0 {
+    return Ok(false);
+  }
+  if PasswordHashing::verify_password(password, current_hash) {
+    return Ok(true);
+  }
+  let history = repo
+    .get_password_history(user_id, history_size as i32 - 1)
+    .await?;
+  Ok(
+    history
+      .iter()
+      .any(|hash| PasswordHashing::verify_password(password, hash)),
+  )
+}
+
+document!(change_password);
+#[utoipa::path(
+    post,
+    path = "/api/v1/user/change_password",
+    tag = "Users",
+    request_body(
+        content = ChangePasswordReqDto,
+        description = "",
+        example = json!({
+          "current_password": "old-password",
+          "new_password": "new-password"
+        })),
+    responses( 
+        (
+            status=200, 
+            description= "Password changed successfully", 
+            body= Status
+        ),
+        (
+            status=400, 
+            description= "Wrong current password, or a recently used new one", 
+            body= Status
+        ),
+        (
+            status=401, 
+            description= "Unauthorized", 
+            body= Status
+        ),
+    )
+)]
+pub async fn change_password(
+  req: ValidatedJson<ChangePasswordReqDto>,
+  current_user: Authenticated,
+  http_req: HttpRequest,
+  data: web::Data<AppState>,
+) -> impl Responder {
+  let mut repo = data.user_repo();
+  let user = match repo.get_by_id(current_user.id).await {
+    Ok(Some(user)) => user,
+    Ok(None) => {
+      return Status::not_found(StatusMessage::NotFound("User".into())).into_http_response();
+    }
+    Err(e) => {
+      return Status::bad_request(format!("Failed to change password: {}", e))
+        .or_query_timeout(&e)
+        .into_http_response();
+    }
+  };
+  // `user` may come from the cache, which never holds the hash
+  let current_hash = match repo.get_password_hash(user.id).await {
+    Ok(Some(hash)) => hash,
+    Ok(None) => {
+      return Status::not_found(StatusMessage::NotFound("User".into())).into_http_response();
+    }
+    Err(e) => {
+      return Status::bad_request(format!("Failed to change password: {}", e))
+        .or_query_timeout(&e)
+        .into_http_response();
+    }
+  };
+  if !PasswordHashing::verify_password(&req.current_password, &current_hash) {
+    return Status::bad_request(StatusMessage::WrongPassword).into_http_response();
+  }
+
+  let history_size = data.config.password.history_size;
+  match is_recent_password(
+    repo.as_mut(),
+    user.id,
+    &current_hash,
+    &req.new_password,
+    history_size,
+  )
+  .await
+  {
+    Ok(true) => {
+      return Status::bad_request(StatusMessage::PasswordReused(history_size)).into_http_response();
+    }
+    Ok(false) => {}
+    Err(e) => {
+      return Status::bad_request(format!("Failed to change password: {}", e))
+        .or_query_timeout(&e)
+        .into_http_response();
+    }
+  }
+
+  let password_hash = match PasswordHashing::hash_password(&req.new_password) {
+    Ok(hash) => hash,
+    Err(e) => {
+      return Status::server_error(format!("Failed to hash password: {}", e)).into_http_response();
+    }
+  };
+  // The hash being replaced is one of the `history_size` that can't come back
+  let keep = history_size.saturating_sub(1) as i32;
+  if let Err(e) = repo.update_password(user.id, &password_hash, keep).await {
+    return Status::bad_request(format!("Failed to change password: {}", e))
+      .or_query_timeout(&e)
+      .into_http_response();
+  }
+
+  data.notifier.send_email_detached(
+    NotificationKind::SecurityAlert,
+    user.id,
+    user.email,
+    EmailTemplate::PasswordChanged,
+  );
+  AuditRepo::new(&data)
+    .record(
+      AuditLogEntity::new(
+        AuditAction::ChangePassword,
+        Some(user.id),
+        format!("user:{}", user.id),
+      )
+      .with_request(&http_req),
+    )
+    .await;
+  HttpResponse::Ok().json(Status::success())
+}
+
+// Only the hash of a reset token is stored, it is looked up by it so it can't be salted
+fn reset_token_hash(token: &str) -> String {
+  format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+document!(forgot_password);
+#[utoipa::path(
+    post,
+    path = "/api/v1/user/forgot_password",
+    tag = "Users",
+    request_body(
+        content = ForgotPasswordReqDto,
+        description = "",
+        example = json!({
+          "user_name": "nith"
+        })),
+    responses( 
+        (
+            status=200, 
+            description= "A reset token was emailed if the user exists and is active", 
+            body= Status
+        ),
+        (
+            status=400, 
+            description= "Validation Errors", 
+            body= Status
+        ),
+    )
+)]
+pub async fn forgot_password(
+  req: ValidatedJson<ForgotPasswordReqDto>,
+  http_req: HttpRequest,
+  data: web::Data<AppState>,
+) -> impl Responder {
+  let mut repo = data.user_repo();
+  // Same answer for unknown and disabled users, so this can't be used to look accounts up
+  let user = match repo.get_by_username(req.user_name.trim()).await {
+    Ok(Some(user)) if user.is_active => user,
+    Ok(_) => return HttpResponse::Ok().json(Status::success()),
+    Err(e) => {
+      return Status::bad_request(format!("Failed to reset password: {}", e))
+        .or_query_timeout(&e)
+        .into_http_response();
+    }
+  };
+
+  let token = uuid::Uuid::new_v4().simple().to_string();
+  if let Err(e) = repo
+    .create_password_reset(
+      user.id,
+      &reset_token_hash(&token),
+      PASSWORD_RESET_EXPIRATION_MINUTES,
+    )
+    .await
+  {
+    return Status::bad_request(format!("Failed to reset password: {}", e))
+      .or_query_timeout(&e)
+      .into_http_response();
+  }
+
+  data.notifier.send_email_detached(
+    NotificationKind::PasswordReset,
+    user.id,
+    user.email,
+    EmailTemplate::PasswordReset {
+      token,
+      expires_minutes: PASSWORD_RESET_EXPIRATION_MINUTES,
+    },
+  );
+  AuditRepo::new(&data)
+    .record(
+      AuditLogEntity::new(
+        AuditAction::RequestPasswordReset,
+        Some(user.id),
+        format!("user:{}", user.id),
+      )
+      .with_request(&http_req),
+    )
+    .await;
+  HttpResponse::Ok().json(Status::success())
+}
+
+document!(reset_password);
+#[utoipa::path(
+    post,
+    path = "/api/v1/user/reset_password",
+    tag = "Users",
+    request_body(
+        content = ResetPasswordReqDto,
+        description = "",
+        example = json!({
+          "token": "3f1c2a9e5b7d4c8e9a0b1c2d3e4f5a6b",
+          "new_password": "new-password"
+        })),
+    responses( 
+        (
+            status=200, 
+            description= "Password reset successfully", 
+            body= Status
+        ),
+        (
+            status=400, 
+            description= "Token unknown, expired or already used, or a recently used new password", 
+            body= Status
+        ),
+        (
+            status=403, 
+            description= "Account is disabled", 
+            body= Status
+        ),
+    )
+)]
+pub async fn reset_password(
+  req: ValidatedJson<ResetPasswordReqDto>,
+  tx: DbTransaction,
+  http_req: HttpRequest,
+  data: web::Data<AppState>,
+) -> impl Responder {
+  let mut repo = data.user_repo_in(&tx);
+
+  // Consumed before anything else so a token works once, a refused password rolls it back
+  let user_id = match repo
+    .consume_password_reset(&reset_token_hash(&req.token))
+    .await
+  {
+    Ok(Some(user_id)) => user_id,
+    Ok(None) => return Status::bad_request(StatusMessage::TokenExpired).into_http_response(),
+    Err(e) => {
+      return Status::bad_request(format!("Failed to reset password: {}", e))
+        .or_query_timeout(&e)
+        .into_http_response();
+    }
+  };
+  let user = match repo.get_by_id(user_id).await {
+    Ok(Some(user)) if user.is_active => user,
+    Ok(Some(_)) => return Status::account_disabled().into_http_response(),
+    Ok(None) => {
+      return Status::not_found(StatusMessage::NotFound("User".into())).into_http_response();
+    }
+    Err(e) => {
+      return Status::bad_request(format!("Failed to reset password: {}", e))
+        .or_query_timeout(&e)
+        .into_http_response();
+    }
+  };
+  let current_hash = match repo.get_password_hash(user.id).await {
+    Ok(Some(hash)) => hash,
+    Ok(None) => {
+      return Status::not_found(StatusMessage::NotFound("User".into())).into_http_response();
+    }
+    Err(e) => {
+      return Status::bad_request(format!("Failed to reset password: {}", e))
+        .or_query_timeout(&e)
+        .into_http_response();
+    }
+  };
+
+  let history_size = data.config.password.history_size;
+  match is_recent_password(
+    repo.as_mut(),
+    user.id,
+    &current_hash,
+    &req.new_password,
+    history_size,
+  )
+  .await
+  {
+    Ok(true) => {
+      return Status::bad_request(StatusMessage::PasswordReused(history_size)).into_http_response();
+    }
+    Ok(false) => {}
+    Err(e) => {
+      return Status::bad_request(format!("Failed to reset password: {}", e))
+        .or_query_timeout(&e)
+        .into_http_response();
+    }
+  }
+
+  let password_hash = match PasswordHashing::hash_password(&req.new_password) {
+    Ok(hash) => hash,
+    Err(e) => {
+      return Status::server_error(format!("Failed to hash password: {}", e)).into_http_response();
+    }
+  };
+  let keep = history_size.saturating_sub(1) as i32;
+  if let Err(e) = repo.update_password(user.id, &password_hash, keep).await {
+    return Status::bad_request(format!("Failed to reset password: {}", e))
+      .or_query_timeout(&e)
+      .into_http_response();
+  }
+
+  data.notifier.send_email_detached(
+    NotificationKind::SecurityAlert,
+    user.id,
+    user.email,
+    EmailTemplate::PasswordChanged,
+  );
+  AuditRepo::new(&data)
+    .record(
+      AuditLogEntity::new(
+        AuditAction::ResetPassword,
+        Some(user.id),
+        format!("user:{}", user.id),
+      )
+      .with_request(&http_req),
+    )
+    .await;
+  HttpResponse::Ok().json(Status::success())
+}
+
+document!(list_users);
+#[utoipa::path(
+    get,
+    path = "/api/v1/users",
+    tag = "Users",
+    params(GetUsersReqDto, FieldsParam),
+    responses( 
+        (
+            status=200, 
+            description= "Get users successfully", 
+            body= BaseResDto<PagedResDto<UserDto>>
+        ),
+        (
+            status=400, 
+            description= "Validation Errors", 
+            body= Status
+        ),
+    )
+)]
+pub async fn list_users(
+  query: ValidatedQuery<GetUsersReqDto>,
+  fields: FieldSelection<UserDto>,
+  data: web::Data<AppState>,
+) -> impl Responder {
+  get_users(ValidatedJson(query.into_inner()), fields, data).await
+}
+
+document!(get_user);
+#[utoipa::path(
+    get,
+    path = "/api/v1/users/{id}",
+    tag = "Users",
+    params(("id" = i32, Path, description = "User id")),
+    responses( 
+        (
+            status=200, 
+            description= "Get user successfully", 
+            body= BaseResDto<UserDto>
+        ),
+        (
+            status=403, 
+            description= "Permission denied", 
+            body= Status
+        ),
+        (
+            status=404, 
+            description= "User not found", 
+            body= Status
+        ),
+    )
+)]
+pub async fn get_user(
+  id: web::Path<i32>,
+  current_user: Authenticated,
+  data: web::Data<AppState>,
+) -> impl Responder {
+  let id = GetUserByIdReqDto {
+    id: id.into_inner(),
+  };
+  get_user_by_id(web::Json(id), current_user, data).await
+}
+
+document!(put_user);
+#[utoipa::path(
+    put,
+    path = "/api/v1/users/{id}",
+    tag = "Users",
+    params(("id" = i32, Path, description = "User id")),
+    request_body(
+        content = UserChangesReqDto,
+        description = "",
+        example = json!({
+          "name": "nith update",
+          "role": "admin"
+        })),
+    responses( 
+        (
+            status=200, 
+            description= "Update user successfully", 
+            body= Status
+        ),
+        (
+            status=400, 
+            description= "Validation Errors", 
+            body= Status
+        ),
+        (
+            status=403, 
+            description= "Permission denied", 
+            body= Status
+        ),
+    )
+)]
+pub async fn put_user(
+  id: web::Path<i32>,
+  changes: ValidatedJson<UserChangesReqDto>,
+  current_user: Authenticated,
+  http_req: HttpRequest,
+  data: web::Data<AppState>,
+) -> impl Responder {
+  let found = data.user_repo().get_by_id(id.into_inner()).await;
+  apply_user_update(found, &changes, &current_user, &http_req, &data).await
+}
+
+document!(activate_user_by_id);
+#[utoipa::path(
+    post,
+    path = "/api/v1/users/{id}/activate",
+    tag = "Users",
+    params(("id" = i32, Path, description = "User id")),
+    responses( 
+        (
+            status=200, 
+            description= "Activate user successfully", 
+            body= Status
+        ),
+        (
+            status=404, 
+            description= "User not found", 
+            body= Status
+        ),
+    )
+)]
+pub async fn activate_user_by_id(
+  id: web::Path<i32>,
+  current_user: Authenticated,
+  http_req: HttpRequest,
+  data: web::Data<AppState>,
+) -> impl Responder {
+  set_user_active(id.into_inner(), true, &current_user, &http_req, &data).await
+}
+
+document!(deactivate_user_by_id);
+#[utoipa::path(
+    post,
+    path = "/api/v1/users/{id}/deactivate",
+    tag = "Users",
+    params(("id" = i32, Path, description = "User id")),
+    responses( 
+        (
+            status=200, 
+            description= "Deactivate user successfully", 
+            body= Status
+        ),
+        (
+            status=404, 
+            description= "User not found", 
+            body= Status
+        ),
+    )
+)]
+pub async fn deactivate_user_by_id(
+  id: web::Path<i32>,
+  current_user: Authenticated,
+  http_req: HttpRequest,
+  data: web::Data<AppState>,
+) -> impl Responder {
+  set_user_active(id.into_inner(), false, &current_user, &http_req, &data).await
+}
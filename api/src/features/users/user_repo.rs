@@ -1,122 +1,637 @@
+use std::sync::Arc;
+
 use crate::{
   app_state::AppState,
+  db::{RetryPolicy, RowExt, SqlRepo, stream::paged_stream},
+  dto::sort::SortDir,
   features::users::{
-    user_dto::{UserDto, UserRegisterReqDto},
-    user_entity::User,
+    user_dto::{UserDto, UserRegisterReqDto, UserSortBy},
+    user_entity::{EmailChange, LoginCode, User},
   },
-  utils::password_hashing::PasswordHashing,
+  middleware::transaction::{DbClient, DbTransaction},
+  utils::{password_hashing::PasswordHashing, redis_cache::RedisCache},
 };
 
 use anyhow::Result;
-use domner_tech_sql_client::{CommandType, SqlRepo, UnifiedToSql, pool_manager::PooledClient};
+use domner_tech_sql_client::{CommandType, UnifiedToSql};
+use futures::{Stream, future::LocalBoxFuture};
+
+/// Data access for users, implemented against SQL Server by `UserRepo`.
+/// Handlers get it from `AppState` so tests can swap in an in-memory implementation.
+pub trait UserRepository {
+  // Returns the id of the new user
+  fn create<'b>(&'b mut self, user: &'b UserRegisterReqDto) -> LocalBoxFuture<'b, Result<i32>>;
+
+  // May be served from the cache, so `password` can be empty
+  fn get_by_id<'b>(&'b mut self, id: i32) -> LocalBoxFuture<'b, Result<Option<User>>>;
+
+  // Always read from the database, the cache never holds password hashes
+  fn get_password_hash<'b>(&'b mut self, id: i32) -> LocalBoxFuture<'b, Result<Option<String>>>;
+
+  fn get_by_username<'b>(
+    &'b mut self,
+    username: &'b str,
+  ) -> LocalBoxFuture<'b, Result<Option<User>>>;
+
+  fn get_users<'b>(&'b mut self) -> LocalBoxFuture<'b, Result<Vec<User>>>;
+
+  fn get_users_paged<'b>(
+    &'b mut self,
+    page: i32,
+    page_size: i32,
+    sort_by: UserSortBy,
+    sort_dir: SortDir,
+  ) -> LocalBoxFuture<'b, Result<(Vec<User>, i32)>>;
+
+  fn set_active<'b>(&'b mut self, id: i32, is_active: bool) -> LocalBoxFuture<'b, Result<u64>>;
+
+  fn update_user<'b>(&'b mut self, user: &'b UserDto) -> LocalBoxFuture<'b, Result<u64>>;
+
+  // Replaces any pending email change of the user
+  fn create_email_change<'b>(
+    &'b mut self,
+    user_id: i32,
+    new_email: &'b str,
+    token: &'b str,
+    expiration_minutes: i32,
+  ) -> LocalBoxFuture<'b, Result<u64>>;
+
+  fn get_email_change_by_token<'b>(
+    &'b mut self,
+    token: &'b str,
+  ) -> LocalBoxFuture<'b, Result<Option<EmailChange>>>;
+
+  fn delete_email_change<'b>(&'b mut self, user_id: i32) -> LocalBoxFuture<'b, Result<u64>>;
+
+  fn update_email<'b>(
+    &'b mut self,
+    user_id: i32,
+    email: &'b str,
+  ) -> LocalBoxFuture<'b, Result<u64>>;
+
+  // Hashes of the passwords before the current one, newest first
+  fn get_password_history<'b>(
+    &'b mut self,
+    user_id: i32,
+    count: i32,
+  ) -> LocalBoxFuture<'b, Result<Vec<String>>>;
+
+  // Replaces the hash, the old one goes to the history which keeps its newest `keep` entries
+  fn update_password<'b>(
+    &'b mut self,
+    user_id: i32,
+    password_hash: &'b str,
+    keep: i32,
+  ) -> LocalBoxFuture<'b, Result<u64>>;
+
+  // Replaces any pending login code of the user, keeping the attempts counted against it.
+  // `false` when the user was already sent `max_codes` codes in the last `window_minutes`
+  fn create_login_code<'b>(
+    &'b mut self,
+    user_id: i32,
+    code_hash: &'b str,
+    expiration_minutes: i32,
+    max_codes: i32,
+    window_minutes: i32,
+  ) -> LocalBoxFuture<'b, Result<bool>>;
+
+  fn get_login_code<'b>(
+    &'b mut self,
+    user_id: i32,
+  ) -> LocalBoxFuture<'b, Result<Option<LoginCode>>>;
+
+  // Counts a wrong code against the pending one
+  fn increment_login_code_attempts<'b>(
+    &'b mut self,
+    user_id: i32,
+  ) -> LocalBoxFuture<'b, Result<u64>>;
+
+  // Removes the code if it is still the pending `code_hash`, unexpired and tried fewer than
+  // `max_attempts` times. `false` when it no longer is, e.g. another request used it first
+  fn consume_login_code<'b>(
+    &'b mut self,
+    user_id: i32,
+    code_hash: &'b str,
+    max_attempts: i32,
+  ) -> LocalBoxFuture<'b, Result<bool>>;
+
+  // Replaces any pending password reset of the user
+  fn create_password_reset<'b>(
+    &'b mut self,
+    user_id: i32,
+    token_hash: &'b str,
+    expiration_minutes: i32,
+  ) -> LocalBoxFuture<'b, Result<u64>>;
+
+  // Removes the reset and returns its user, `None` when the token is unknown or expired
+  fn consume_password_reset<'b>(
+    &'b mut self,
+    token_hash: &'b str,
+  ) -> LocalBoxFuture<'b, Result<Option<i32>>>;
+}
+
+fn cache_key(id: i32) -> String {
+  format!("user:{}", id)
+}
+
+// The only column names that can end up in a user listing's ORDER BY
+fn sort_column(sort_by: UserSortBy) -> &'static str {
+  match sort_by {
+    UserSortBy::Id => "id",
+    UserSortBy::UserName => "user_name",
+    UserSortBy::Name => "name",
+    UserSortBy::Email => "email",
+    UserSortBy::Role => "role",
+    UserSortBy::IsActive => "is_active",
+    UserSortBy::CreatedAt => "created_at",
+  }
+}
 
 pub struct UserRepo<'a> {
   pub app_state: &'a AppState,
+  pub tx: Option<DbTransaction>,
 }
 
 impl<'a> UserRepo<'a> {
   pub fn new(app_state: &'a AppState) -> Self {
-    Self { app_state }
+    Self {
+      app_state,
+      tx: None,
+    }
   }
 
-  async fn get_client(&self) -> PooledClient {
-    match self
-      .app_state
-      .db_manager
-      .get_client(&self.app_state.config.database.sql_server.pool_name)
-      .await
-    {
-      Ok(client) => client,
-      Err(e) => panic!("Failed to get DB client: {}", e),
+  // Run every query of this repo inside the request's transaction
+  pub fn with_transaction(app_state: &'a AppState, tx: &DbTransaction) -> Self {
+    Self {
+      app_state,
+      tx: Some(tx.clone()),
     }
   }
 
-  pub async fn create(&mut self, user: &UserRegisterReqDto) -> Result<()> {
-    let user_existed = self.get_by_username(&user.user_name).await?;
+  async fn get_client(&self) -> DbClient {
+    DbClient::acquire(self.app_state, self.tx.as_ref()).await
+  }
+
+  // Reads outside a transaction are retried on a fresh connection, inside one they run once
+  fn retry_policy(&self) -> RetryPolicy {
+    match self.tx {
+      Some(_) => RetryPolicy::none(),
+      None => RetryPolicy::from_setting(&self.app_state.config.database.sql_server),
+    }
+  }
 
-    if user_existed.is_some() {
-      return Err(anyhow::anyhow!("Username already exists"));
+  // Reads inside a transaction skip the cache so they see the transaction's own writes
+  fn read_cache(&self) -> Option<&RedisCache> {
+    match self.tx {
+      Some(_) => None,
+      None => self.app_state.redis.as_ref(),
     }
+  }
+
+  async fn invalidate(&self, id: i32) {
+    self.app_state.invalidate_user(id);
+    if let Some(cache) = &self.app_state.redis {
+      cache.delete(&[cache_key(id)]).await;
+    }
+  }
+
+  // Every user as a stream, fetched a page per round trip so memory stays bounded;
+  // owns the state so the stream can outlive the request handler
+  pub fn stream_users(
+    app_state: Arc<AppState>,
+    page_size: i32,
+  ) -> impl Stream<Item = Result<User>> {
+    paged_stream(page_size, move |page| {
+      let app_state = app_state.clone();
+      async move {
+        let mut repo = UserRepo::new(&app_state);
+        let (users, _) = repo
+          .get_users_paged(page, page_size, UserSortBy::Id, SortDir::Asc)
+          .await?;
+        Ok(users)
+      }
+    })
+  }
+}
+
+impl<'a> UserRepository for UserRepo<'a> {
+  fn create<'b>(&'b mut self, user: &'b UserRegisterReqDto) -> LocalBoxFuture<'b, Result<i32>> {
+    Box::pin(async move {
+      let user_existed = self.get_by_username(&user.user_name).await?;
+
+      if user_existed.is_some() {
+        return Err(anyhow::anyhow!("Username already exists"));
+      }
+
+      // Hash the password before storing
+      let hashed_password = PasswordHashing::hash_password(&user.password)
+        .map_err(|e| anyhow::anyhow!("Failed to hash password: {}", e))?;
+
+      let params: Vec<&dyn UnifiedToSql> = vec![
+        &user.name,
+        &user.user_name,
+        &user.email,
+        &hashed_password,
+        &user.role,
+      ];
+
+      let mut client_pool = self.get_client().await;
+
+      SqlRepo::execute_insert_returning_id(
+        &mut client_pool,
+        "[dbo].[create_user]",
+        &params,
+        CommandType::StoreProcedure,
+      )
+      .await
+    })
+  }
+
+  // Read-through, runs for every authenticated request
+  fn get_by_id<'b>(&'b mut self, id: i32) -> LocalBoxFuture<'b, Result<Option<User>>> {
+    Box::pin(async move {
+      if let Some(cache) = self.read_cache()
+        && let Some(user) = cache.get::<User>(&cache_key(id)).await
+      {
+        return Ok(Some(user));
+      }
+      let repo = &*self;
+      let user = SqlRepo::execute_with_retry(self.retry_policy(), || async move {
+        let mut client_pool = repo.get_client().await;
+        SqlRepo::execute_prepared_single_query_as::<User>(
+          &mut client_pool,
+          "[dbo].[select_user]",
+          &[&id],
+        )
+        .await
+      })
+      .await?;
+      if let (Some(cache), Some(user)) = (self.read_cache(), &user) {
+        cache.set(&cache_key(id), user).await;
+      }
+      Ok(user)
+    })
+  }
+
+  fn get_password_hash<'b>(&'b mut self, id: i32) -> LocalBoxFuture<'b, Result<Option<String>>> {
+    Box::pin(async move {
+      let repo = &*self;
+      let user = SqlRepo::execute_with_retry(self.retry_policy(), || async move {
+        let mut client_pool = repo.get_client().await;
+        SqlRepo::execute_prepared_single_query_as::<User>(
+          &mut client_pool,
+          "[dbo].[select_user]",
+          &[&id],
+        )
+        .await
+      })
+      .await?;
+      Ok(user.map(|user| user.password))
+    })
+  }
+
+  fn get_by_username<'b>(
+    &'b mut self,
+    username: &'b str,
+  ) -> LocalBoxFuture<'b, Result<Option<User>>> {
+    Box::pin(async move {
+      let repo = &*self;
+      SqlRepo::execute_with_retry(self.retry_policy(), || async move {
+        let mut client_pool = repo.get_client().await;
+        SqlRepo::execute_prepared_single_query_as::<User>(
+          &mut client_pool,
+          "[dbo].[select_user_by_user_name]",
+          &[&username],
+        )
+        .await
+      })
+      .await
+    })
+  }
+
+  fn get_users<'b>(&'b mut self) -> LocalBoxFuture<'b, Result<Vec<User>>> {
+    Box::pin(async move {
+      let repo = &*self;
+      SqlRepo::execute_with_retry(self.retry_policy(), || async move {
+        let mut client_pool = repo.get_client().await;
+        SqlRepo::execute_query_as::<User>(
+          &mut client_pool,
+          "[dbo].[select_users]",
+          &[],
+          CommandType::StoreProcedure,
+        )
+        .await
+      })
+      .await
+    })
+  }
+
+  fn get_users_paged<'b>(
+    &'b mut self,
+    page: i32,
+    page_size: i32,
+    sort_by: UserSortBy,
+    sort_dir: SortDir,
+  ) -> LocalBoxFuture<'b, Result<(Vec<User>, i32)>> {
+    Box::pin(async move {
+      let mut client_pool = self.get_client().await;
+
+      // Same query as [dbo].[select_users_paged] with the requested order
+      let query = format!(
+        "SELECT *, COUNT(*) OVER () AS total_count FROM [dbo].[users] {} \
+         OFFSET (@P1 - 1) * @P2 ROWS FETCH NEXT @P2 ROWS ONLY",
+        sort_dir.order_by(sort_column(sort_by))
+      );
+      SqlRepo::execute_paged_query::<User>(
+        &mut client_pool,
+        &query,
+        &[&page, &page_size],
+        CommandType::Text,
+        "SELECT COUNT(*) AS value FROM [dbo].[users]",
+        &[],
+      )
+      .await
+    })
+  }
+
+  fn set_active<'b>(&'b mut self, id: i32, is_active: bool) -> LocalBoxFuture<'b, Result<u64>> {
+    Box::pin(async move {
+      let mut client_pool = self.get_client().await;
+
+      let result = SqlRepo::execute_command_none_query(
+        &mut client_pool,
+        "[dbo].[update_user_active]",
+        &[&id, &is_active],
+        CommandType::StoreProcedure,
+      )
+      .await?;
+      self.invalidate(id).await;
+      Ok(result)
+    })
+  }
+
+  fn update_user<'b>(&'b mut self, user: &'b UserDto) -> LocalBoxFuture<'b, Result<u64>> {
+    Box::pin(async move {
+      let mut client_pool = self.get_client().await;
+
+      let role = user.role.to_str();
+      let params: Vec<&dyn UnifiedToSql> = vec![&user.name, &user.user_name, &user.email, &role];
+
+      let result = SqlRepo::execute_command_none_query(
+        &mut client_pool,
+        "[dbo].[update_user]",
+        &params,
+        CommandType::StoreProcedure,
+      )
+      .await?;
+      self.invalidate(user.id).await;
+      Ok(result)
+    })
+  }
+
+  // Replaces any pending email change of the user
+  fn create_email_change<'b>(
+    &'b mut self,
+    user_id: i32,
+    new_email: &'b str,
+    token: &'b str,
+    expiration_minutes: i32,
+  ) -> LocalBoxFuture<'b, Result<u64>> {
+    Box::pin(async move {
+      let mut client_pool = self.get_client().await;
+
+      let params: Vec<&dyn UnifiedToSql> = vec![&user_id, &new_email, &token, &expiration_minutes];
+      let result = SqlRepo::execute_command_none_query(
+        &mut client_pool,
+        "[dbo].[create_email_change_request]",
+        &params,
+        CommandType::StoreProcedure,
+      )
+      .await?;
+      Ok(result)
+    })
+  }
+
+  fn get_email_change_by_token<'b>(
+    &'b mut self,
+    token: &'b str,
+  ) -> LocalBoxFuture<'b, Result<Option<EmailChange>>> {
+    Box::pin(async move {
+      let mut client_pool = self.get_client().await;
+
+      let email_change = SqlRepo::execute_single_query_as::<EmailChange>(
+        &mut client_pool,
+        "[dbo].[select_email_change_request]",
+        &[&token],
+        CommandType::StoreProcedure,
+      )
+      .await?;
+      Ok(email_change)
+    })
+  }
+
+  fn delete_email_change<'b>(&'b mut self, user_id: i32) -> LocalBoxFuture<'b, Result<u64>> {
+    Box::pin(async move {
+      let mut client_pool = self.get_client().await;
+
+      let result = SqlRepo::execute_command_none_query(
+        &mut client_pool,
+        "[dbo].[delete_email_change_request]",
+        &[&user_id],
+        CommandType::StoreProcedure,
+      )
+      .await?;
+      Ok(result)
+    })
+  }
+
+  fn update_email<'b>(
+    &'b mut self,
+    user_id: i32,
+    email: &'b str,
+  ) -> LocalBoxFuture<'b, Result<u64>> {
+    Box::pin(async move {
+      let mut client_pool = self.get_client().await;
+
+      let result = SqlRepo::execute_command_none_query(
+        &mut client_pool,
+        "[dbo].[update_user_email]",
+        &[&user_id, &email],
+        CommandType::StoreProcedure,
+      )
+      .await?;
+      self.invalidate(user_id).await;
+      Ok(result)
+    })
+  }
+
+  fn get_password_history<'b>(
+    &'b mut self,
+    user_id: i32,
+    count: i32,
+  ) -> LocalBoxFuture<'b, Result<Vec<String>>> {
+    Box::pin(async move {
+      let mut client_pool = self.get_client().await;
+
+      let hashes = SqlRepo::execute_command_query(
+        &mut client_pool,
+        "[dbo].[select_password_history]",
+        &[&user_id, &count],
+        CommandType::StoreProcedure,
+        |row| row.column("password"),
+      )
+      .await?;
+      Ok(hashes)
+    })
+  }
+
+  fn update_password<'b>(
+    &'b mut self,
+    user_id: i32,
+    password_hash: &'b str,
+    keep: i32,
+  ) -> LocalBoxFuture<'b, Result<u64>> {
+    Box::pin(async move {
+      let mut client_pool = self.get_client().await;
+
+      let params: Vec<&dyn UnifiedToSql> = vec![&user_id, &password_hash, &keep];
+      let result = SqlRepo::execute_command_none_query(
+        &mut client_pool,
+        "[dbo].[update_user_password]",
+        &params,
+        CommandType::StoreProcedure,
+      )
+      .await?;
+      self.invalidate(user_id).await;
+      Ok(result)
+    })
+  }
+
+  fn create_login_code<'b>(
+    &'b mut self,
+    user_id: i32,
+    code_hash: &'b str,
+    expiration_minutes: i32,
+    max_codes: i32,
+    window_minutes: i32,
+  ) -> LocalBoxFuture<'b, Result<bool>> {
+    Box::pin(async move {
+      let mut client_pool = self.get_client().await;
+
+      let params: Vec<&dyn UnifiedToSql> = vec![
+        &user_id,
+        &code_hash,
+        &expiration_minutes,
+        &max_codes,
+        &window_minutes,
+      ];
+      let issued = SqlRepo::execute_command_query(
+        &mut client_pool,
+        "[dbo].[create_login_code]",
+        &params,
+        CommandType::StoreProcedure,
+        |row| row.column::<bool>("issued"),
+      )
+      .await?;
+      Ok(issued.first().copied().unwrap_or(false))
+    })
+  }
+
+  fn get_login_code<'b>(
+    &'b mut self,
+    user_id: i32,
+  ) -> LocalBoxFuture<'b, Result<Option<LoginCode>>> {
+    Box::pin(async move {
+      let mut client_pool = self.get_client().await;
+
+      let login_code = SqlRepo::execute_single_query_as::<LoginCode>(
+        &mut client_pool,
+        "[dbo].[select_login_code]",
+        &[&user_id],
+        CommandType::StoreProcedure,
+      )
+      .await?;
+      Ok(login_code)
+    })
+  }
+
+  fn increment_login_code_attempts<'b>(
+    &'b mut self,
+    user_id: i32,
+  ) -> LocalBoxFuture<'b, Result<u64>> {
+    Box::pin(async move {
+      let mut client_pool = self.get_client().await;
+
+      let result = SqlRepo::execute_command_none_query(
+        &mut client_pool,
+        "[dbo].[increment_login_code_attempts]",
+        &[&user_id],
+        CommandType::StoreProcedure,
+      )
+      .await?;
+      Ok(result)
+    })
+  }
+
+  fn consume_login_code<'b>(
+    &'b mut self,
+    user_id: i32,
+    code_hash: &'b str,
+    max_attempts: i32,
+  ) -> LocalBoxFuture<'b, Result<bool>> {
+    Box::pin(async move {
+      let mut client_pool = self.get_client().await;
+
+      let params: Vec<&dyn UnifiedToSql> = vec![&user_id, &code_hash, &max_attempts];
+      let consumed = SqlRepo::execute_command_query(
+        &mut client_pool,
+        "[dbo].[consume_login_code]",
+        &params,
+        CommandType::StoreProcedure,
+        |row| row.column::<bool>("consumed"),
+      )
+      .await?;
+      Ok(consumed.first().copied().unwrap_or(false))
+    })
+  }
+
+  fn create_password_reset<'b>(
+    &'b mut self,
+    user_id: i32,
+    token_hash: &'b str,
+    expiration_minutes: i32,
+  ) -> LocalBoxFuture<'b, Result<u64>> {
+    Box::pin(async move {
+      let mut client_pool = self.get_client().await;
+
+      let params: Vec<&dyn UnifiedToSql> = vec![&user_id, &token_hash, &expiration_minutes];
+      let result = SqlRepo::execute_command_none_query(
+        &mut client_pool,
+        "[dbo].[create_password_reset_request]",
+        &params,
+        CommandType::StoreProcedure,
+      )
+      .await?;
+      Ok(result)
+    })
+  }
+
+  fn consume_password_reset<'b>(
+    &'b mut self,
+    token_hash: &'b str,
+  ) -> LocalBoxFuture<'b, Result<Option<i32>>> {
+    Box::pin(async move {
+      let mut client_pool = self.get_client().await;
 
-    // Hash the password before storing
-    let hashed_password = PasswordHashing::hash_password(&user.password)
-      .map_err(|e| anyhow::anyhow!("Failed to hash password: {}", e))?;
-
-    let params: Vec<&dyn UnifiedToSql> = vec![
-      &user.name,
-      &user.user_name,
-      &user.email,
-      &hashed_password,
-      &user.role,
-    ];
-
-    let mut client_pool = self.get_client().await;
-
-    SqlRepo::execute_command_none_query(
-      &mut client_pool,
-      "[dbo].[create_user]",
-      &params,
-      CommandType::StoreProcedure,
-    )
-    .await?;
-    Ok(())
-  }
-
-  pub async fn get_by_id(&mut self, id: i32) -> Result<Option<User>> {
-    let mut client_pool = self.get_client().await;
-
-    let user = SqlRepo::execute_command_single_query(
-      &mut client_pool,
-      "[dbo].[select_user]",
-      &[&id],
-      CommandType::StoreProcedure,
-      |row| User::from(row),
-    )
-    .await?;
-    Ok(user)
-  }
-
-  pub async fn get_by_username(&mut self, username: &str) -> Result<Option<User>> {
-    let mut client_pool = self.get_client().await;
-
-    let user = SqlRepo::execute_command_single_query(
-      &mut client_pool,
-      "[dbo].[select_user_by_user_name]",
-      &[&username],
-      CommandType::StoreProcedure,
-      |row| User::from(row),
-    )
-    .await?;
-    Ok(user)
-  }
-
-  pub async fn get_users(&mut self) -> Result<Vec<User>> {
-    let mut client_pool = self.get_client().await;
-
-    let users = SqlRepo::execute_command_query(
-      &mut client_pool,
-      "[dbo].[select_users]",
-      &[],
-      CommandType::StoreProcedure,
-      |row| User::from(row),
-    )
-    .await?;
-    Ok(users)
-  }
-
-  pub async fn update_user(&mut self, user: &UserDto) -> Result<u64> {
-    let mut client_pool = self.get_client().await;
-
-    let role = user.role.to_str();
-    let params: Vec<&dyn UnifiedToSql> = vec![&user.name, &user.user_name, &user.email, &role];
-
-    let result = SqlRepo::execute_command_none_query(
-      &mut client_pool,
-      "[dbo].[update_user]",
-      &params,
-      CommandType::StoreProcedure,
-    )
-    .await?;
-    Ok(result)
+      let user_ids = SqlRepo::execute_command_query(
+        &mut client_pool,
+        "[dbo].[consume_password_reset_request]",
+        &[&token_hash],
+        CommandType::StoreProcedure,
+        |row| row.column::<i32>("user_id"),
+      )
+      .await?;
+      Ok(user_ids.first().copied())
+    })
   }
 }
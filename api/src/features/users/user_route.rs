@@ -4,39 +4,160 @@ use actix_web::{
 };
 
 use crate::{
-  features::users::{
-    user_entity::UserRole,
-    user_handler::{get_user_by_id, get_users, update_user},
+  api_version::deprecated_alias,
+  features::{
+    login_history::login_history_handler::get_my_logins,
+    roles::roles_handler::list_user_roles,
+    users::{
+      avatar_handler::{
+        delete_my_avatar, get_user_avatar, get_user_avatar_variant, upload_my_avatar,
+      },
+      user_entity::UserRole,
+      user_handler::{
+        activate_user, activate_user_by_id, change_email, change_password, confirm_email,
+        deactivate_user, deactivate_user_by_id, export_users, forgot_password, get_me, get_user,
+        get_user_by_id, get_users, list_users, put_user, reset_password, update_user,
+      },
+    },
+  },
+  middleware::{
+    auth::{RequireAuth, RequirePermission},
+    feature_gate::RequireFeature,
+    transaction::TransactionScope,
   },
-  middleware::auth::RequireAuth,
 };
 
+fn any_user() -> RequireAuth {
+  RequireAuth::allow_roles(vec![UserRole::User, UserRole::Moderator, UserRole::Admin])
+}
+
+pub fn users_routes() -> Scope {
+  web::scope("/users")
+    .route(
+      "",
+      web::get()
+        .to(list_users)
+        .wrap(RequirePermission::new("user:read")),
+    )
+    // Registered before `/{id}` so they aren't taken for an id
+    .route("/me", web::get().to(get_me).wrap(any_user()))
+    .route(
+      "/me/avatar",
+      web::put().to(upload_my_avatar).wrap(any_user()),
+    )
+    .route(
+      "/me/avatar",
+      web::delete().to(delete_my_avatar).wrap(any_user()),
+    )
+    .route(
+      "/export",
+      web::get()
+        .to(export_users)
+        .wrap(RequirePermission::new("user:export")),
+    )
+    .route("/{id}", web::get().to(get_user).wrap(any_user()))
+    .route("/{id}", web::put().to(put_user).wrap(any_user()))
+    .route(
+      "/{id}/avatar",
+      web::get().to(get_user_avatar).wrap(any_user()),
+    )
+    .route(
+      "/{id}/avatar/{size}",
+      web::get().to(get_user_avatar_variant).wrap(any_user()),
+    )
+    .route(
+      "/{id}/activate",
+      web::post()
+        .to(activate_user_by_id)
+        .wrap(RequirePermission::new("user:activate")),
+    )
+    .route(
+      "/{id}/deactivate",
+      web::post()
+        .to(deactivate_user_by_id)
+        .wrap(RequirePermission::new("user:activate")),
+    )
+    .route(
+      "/{id}/roles",
+      web::get()
+        .to(list_user_roles)
+        .wrap(RequirePermission::new("role:read")),
+    )
+}
+
+// Routes wrapped in `deprecated_alias` are kept until clients moved to `/users`
 pub fn user_routes() -> Scope {
   web::scope("/user")
     .route(
       "/all",
       web::post()
         .to(get_users)
-        .wrap(RequireAuth::allow_roles(vec![UserRole::Admin])),
+        .wrap(RequirePermission::new("user:read"))
+        .wrap(deprecated_alias()),
+    )
+    .route(
+      "/activate",
+      web::post()
+        .to(activate_user)
+        .wrap(RequirePermission::new("user:activate"))
+        .wrap(deprecated_alias()),
+    )
+    .route(
+      "/deactivate",
+      web::post()
+        .to(deactivate_user)
+        .wrap(RequirePermission::new("user:activate"))
+        .wrap(deprecated_alias()),
+    )
+    .route(
+      "/export",
+      web::get()
+        .to(export_users)
+        .wrap(RequirePermission::new("user:export"))
+        .wrap(deprecated_alias()),
     )
+    .route(
+      "/me",
+      web::get()
+        .to(get_me)
+        .wrap(any_user())
+        .wrap(deprecated_alias()),
+    )
+    .route("/me/logins", web::get().to(get_my_logins).wrap(any_user()))
     .route(
       "/by_id",
       web::post()
         .to(get_user_by_id)
-        .wrap(RequireAuth::allow_roles(vec![
-          UserRole::User,
-          UserRole::Moderator,
-          UserRole::Admin,
-        ])),
+        .wrap(any_user())
+        .wrap(deprecated_alias()),
     )
     .route(
       "/update",
       web::post()
         .to(update_user)
-        .wrap(RequireAuth::allow_roles(vec![
-          UserRole::User,
-          UserRole::Moderator,
-          UserRole::Admin,
-        ])),
+        .wrap(any_user())
+        .wrap(deprecated_alias()),
+    )
+    .route(
+      "/change_email",
+      web::post()
+        .to(change_email)
+        .wrap(RequireFeature::new("email_change"))
+        .wrap(any_user()),
+    )
+    .route(
+      "/change_password",
+      web::post().to(change_password).wrap(any_user()),
+    )
+    // Reached from the emailed token, so it doesn't require a session
+    .route(
+      "/confirm_email",
+      web::post().to(confirm_email).wrap(TransactionScope),
+    )
+    // Reached by someone who can't sign in, same as `/confirm_email`
+    .route("/forgot_password", web::post().to(forgot_password))
+    .route(
+      "/reset_password",
+      web::post().to(reset_password).wrap(TransactionScope),
     )
 }
@@ -0,0 +1,69 @@
+use crate::error::StatusMessage;
+
+pub fn message(message: &StatusMessage) -> String {
+  match message {
+    StatusMessage::Success => "Success".to_string(),
+    StatusMessage::ServerError => "Server error, Please try again later".to_string(),
+    StatusMessage::NotFound(item_name) => format!("{} not found", item_name),
+    StatusMessage::Unauthorized => "Invalid credentials".to_string(),
+    StatusMessage::PermissionDenied => "Permission denied".to_string(),
+    StatusMessage::UserNameExisted => "Username already existed".to_string(),
+    StatusMessage::Existed(ex) => format!("{} already existed", ex),
+    StatusMessage::WrongParams => "Invalid input".to_string(),
+    StatusMessage::DecodeTokenErr => "Decoded token failed".to_string(),
+    StatusMessage::TokenMissing => "Unauthorized, token missing".to_string(),
+    StatusMessage::UnsupportedSchemaVersion(version) => {
+      format!("Unsupported schema_version {}", version)
+    }
+    StatusMessage::QuotaExceeded(item_name, max) => {
+      format!("{} cannot exceed {}", item_name, max)
+    }
+    StatusMessage::AccountDisabled => "Account is disabled".to_string(),
+    StatusMessage::TokenExpired => "Token has expired".to_string(),
+    StatusMessage::EmailChangeRequiresVerification => {
+      "Email can only be changed through /user/change_email".to_string()
+    }
+    StatusMessage::RoleInUse(total) => format!(
+      "Role is still assigned to {} user(s), use force to remove the assignments",
+      total
+    ),
+    StatusMessage::QueryTimeout => "The database took too long to respond".to_string(),
+    StatusMessage::PayloadTooLarge(limit) => {
+      format!("Request body cannot exceed {} bytes", limit)
+    }
+    StatusMessage::JsonTooDeep(limit) => {
+      format!("JSON body cannot be nested deeper than {} levels", limit)
+    }
+    StatusMessage::ValidationFailed => "One or more fields are invalid".to_string(),
+    StatusMessage::RouteNotFound => "No route matches this URL".to_string(),
+    StatusMessage::MethodNotAllowed => "Method not allowed on this route".to_string(),
+    StatusMessage::RateLimited(retry_after) => {
+      format!("Too many requests, retry after {} seconds", retry_after)
+    }
+    StatusMessage::BatchRolledBack => {
+      "Not applied, another operation of the batch failed".to_string()
+    }
+    StatusMessage::CannotImpersonate => {
+      "Only active users who are not admins, other than yourself, can be impersonated".to_string()
+    }
+    StatusMessage::NotImpersonating => "This session is not impersonating anyone".to_string(),
+    StatusMessage::AdminSessionExpired => {
+      "The admin session this impersonation started from has ended, sign in again".to_string()
+    }
+    StatusMessage::WrongPassword => "The current password is incorrect".to_string(),
+    StatusMessage::PasswordReused(count) => {
+      format!(
+        "The new password must differ from your last {} passwords",
+        count
+      )
+    }
+    StatusMessage::SsoRejected => {
+      "Single sign-on failed, the identity provider's response was rejected".to_string()
+    }
+    StatusMessage::DeviceMismatch => {
+      "Token was issued to another device, please log in again".to_string()
+    }
+    StatusMessage::CaptchaRequired => "CAPTCHA verification is missing or failed".to_string(),
+    StatusMessage::UnsupportedImage => "Image must be a PNG, JPEG, GIF or WebP file".to_string(),
+  }
+}
@@ -0,0 +1,63 @@
+use crate::error::StatusMessage;
+
+// Item names such as "User" come from the handlers and stay untranslated
+pub fn message(message: &StatusMessage) -> String {
+  match message {
+    StatusMessage::Success => "ជោគជ័យ".to_string(),
+    StatusMessage::ServerError => "មានបញ្ហានៅម៉ាស៊ីនមេ សូមព្យាយាមម្ដងទៀតនៅពេលក្រោយ".to_string(),
+    StatusMessage::NotFound(item_name) => format!("រកមិនឃើញ {}", item_name),
+    StatusMessage::Unauthorized => "ព័ត៌មានចូលប្រើមិនត្រឹមត្រូវ".to_string(),
+    StatusMessage::PermissionDenied => "អ្នកគ្មានសិទ្ធិ".to_string(),
+    StatusMessage::UserNameExisted => "ឈ្មោះអ្នកប្រើមានរួចហើយ".to_string(),
+    StatusMessage::Existed(ex) => format!("{} មានរួចហើយ", ex),
+    StatusMessage::WrongParams => "ទិន្នន័យបញ្ចូលមិនត្រឹមត្រូវ".to_string(),
+    StatusMessage::DecodeTokenErr => "ការបកស្រាយ token បានបរាជ័យ".to_string(),
+    StatusMessage::TokenMissing => "មិនមានសិទ្ធិ ខ្វះ token".to_string(),
+    StatusMessage::UnsupportedSchemaVersion(version) => {
+      format!("មិនគាំទ្រ schema_version {}", version)
+    }
+    StatusMessage::QuotaExceeded(item_name, max) => {
+      format!("{} មិនអាចលើសពី {}", item_name, max)
+    }
+    StatusMessage::AccountDisabled => "គណនីត្រូវបានបិទ".to_string(),
+    StatusMessage::TokenExpired => "Token បានផុតកំណត់".to_string(),
+    StatusMessage::EmailChangeRequiresVerification => {
+      "អ៊ីមែលអាចប្ដូរបានតែតាមរយៈ /user/change_email ប៉ុណ្ណោះ".to_string()
+    }
+    StatusMessage::RoleInUse(total) => format!(
+      "តួនាទីនេះនៅតែត្រូវបានផ្ដល់ឱ្យអ្នកប្រើ {} នាក់ សូមប្រើ force ដើម្បីដកចេញ",
+      total
+    ),
+    StatusMessage::QueryTimeout => "មូលដ្ឋានទិន្នន័យឆ្លើយតបយឺតពេក".to_string(),
+    StatusMessage::PayloadTooLarge(limit) => {
+      format!("ទំហំសំណើមិនអាចលើសពី {} បៃ", limit)
+    }
+    StatusMessage::JsonTooDeep(limit) => {
+      format!("JSON មិនអាចមានកម្រិតជ្រៅលើសពី {} ជាន់", limit)
+    }
+    StatusMessage::ValidationFailed => "មានវាលមួយ ឬច្រើនមិនត្រឹមត្រូវ".to_string(),
+    StatusMessage::RouteNotFound => "រកមិនឃើញផ្លូវសម្រាប់ URL នេះ".to_string(),
+    StatusMessage::MethodNotAllowed => "មិនអនុញ្ញាត method នេះលើផ្លូវនេះទេ".to_string(),
+    StatusMessage::RateLimited(retry_after) => {
+      format!("សំណើច្រើនពេក សូមព្យាយាមម្ដងទៀតក្នុងរយៈពេល {} វិនាទី", retry_after)
+    }
+    StatusMessage::BatchRolledBack => {
+      "មិនត្រូវបានអនុវត្ត ព្រោះប្រតិបត្តិការផ្សេងទៀតក្នុង batch បានបរាជ័យ".to_string()
+    }
+    StatusMessage::CannotImpersonate => {
+      "អាចធ្វើជាអ្នកប្រើដែលសកម្ម និងមិនមែនជា admin ផ្សេងពីខ្លួនអ្នកបានតែប៉ុណ្ណោះ".to_string()
+    }
+    StatusMessage::NotImpersonating => "វគ្គនេះមិនកំពុងធ្វើជាអ្នកប្រើណាម្នាក់ទេ".to_string(),
+    StatusMessage::AdminSessionExpired => {
+      "វគ្គ admin ដែលការធ្វើជាអ្នកប្រើនេះបានចាប់ផ្តើម បានបញ្ចប់ហើយ សូមចូលម្តងទៀត".to_string()
+    }
+    StatusMessage::WrongPassword => "ពាក្យសម្ងាត់បច្ចុប្បន្នមិនត្រឹមត្រូវ".to_string(),
+    StatusMessage::PasswordReused(count) => {
+      format!("ពាក្យសម្ងាត់ថ្មីត្រូវខុសពីពាក្យសម្ងាត់ {} ចុងក្រោយរបស់អ្នក", count)
+    }
+    StatusMessage::SsoRejected => "ការចូលតាម SSO បរាជ័យ ការឆ្លើយតបរបស់អ្នកផ្តល់អត្តសញ្ញាណត្រូវបានបដិសេធ".to_string(),
+    StatusMessage::DeviceMismatch => "Token ត្រូវបានចេញឱ្យឧបករណ៍ផ្សេង សូមចូលម្តងទៀត".to_string(),
+    StatusMessage::CaptchaRequired => "ការផ្ទៀងផ្ទាត់ CAPTCHA ខ្វះ ឬបរាជ័យ".to_string(),
+    StatusMessage::UnsupportedImage => "រូបភាពត្រូវតែជាឯកសារ PNG, JPEG, GIF ឬ WebP".to_string(),
+  }
+}
@@ -0,0 +1,64 @@
+pub mod en;
+pub mod km;
+
+/// Languages error messages are translated to, `En` when the client asks for none of them.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Locale {
+  #[default]
+  En,
+  Km,
+}
+
+impl Locale {
+  pub const ALL: [Locale; 2] = [Locale::En, Locale::Km];
+
+  pub fn tag(&self) -> &'static str {
+    match self {
+      Locale::En => "en",
+      Locale::Km => "km",
+    }
+  }
+
+  /// Matches the primary subtag only, so `en-US` and `km-KH` resolve too.
+  pub fn from_tag(tag: &str) -> Option<Locale> {
+    let primary = tag.split('-').next()?.trim();
+    Locale::ALL
+      .into_iter()
+      .find(|locale| locale.tag().eq_ignore_ascii_case(primary))
+  }
+
+  /// Picks the supported language with the highest `q` from an `Accept-Language` header.
+  pub fn negotiate(accept_language: &str) -> Locale {
+    let mut best: Option<(Locale, f32)> = None;
+    for entry in accept_language.split(',') {
+      let mut parts = entry.split(';');
+      let Some(locale) = parts.next().and_then(Locale::from_tag) else {
+        continue;
+      };
+      let quality = parts
+        .find_map(|param| param.trim().strip_prefix("q="))
+        .and_then(|q| q.trim().parse::<f32>().ok())
+        .unwrap_or(1.0);
+      // `q=0` means "not this one", equal weights keep the client's order
+      if quality > 0.0 && best.is_none_or(|(_, q)| quality > q) {
+        best = Some((locale, quality));
+      }
+    }
+    best.map(|(locale, _)| locale).unwrap_or_default()
+  }
+}
+
+tokio::task_local! {
+  static CURRENT_LOCALE: Locale;
+}
+
+// Language of the request currently being handled, English outside of a request
+pub fn current() -> Locale {
+  CURRENT_LOCALE
+    .try_with(|locale| *locale)
+    .unwrap_or_default()
+}
+
+pub async fn scope<F: Future>(locale: Locale, f: F) -> F::Output {
+  CURRENT_LOCALE.scope(locale, f).await
+}
@@ -0,0 +1,27 @@
+//! The API server's modules, shared by the `api` server binary and the `cli` admin binary.
+
+pub mod api_version;
+pub mod app_settings;
+pub mod app_state;
+pub mod cli;
+pub mod commons;
+pub mod config_watcher;
+pub mod db;
+pub mod dto;
+pub mod error;
+pub mod events;
+pub mod features;
+pub mod i18n;
+pub mod middleware;
+pub mod migrations;
+pub mod notifications;
+pub mod reports;
+pub mod repositories;
+pub mod scheduler;
+pub mod secrets;
+pub mod security_log;
+pub mod seed;
+pub mod storage;
+pub mod swaggers;
+pub mod templates;
+pub mod utils;
@@ -1,27 +1,30 @@
-mod app_settings;
-mod app_state;
-mod commons;
-mod dto;
-mod error;
-mod features;
-mod middleware;
-mod swaggers;
-mod utils;
+use std::time::Duration;
 
-use actix_cors::Cors;
-use actix_web::{App, HttpServer, http::header, middleware::Logger, web};
-use utoipa::OpenApi;
-use utoipa_rapidoc::RapiDoc;
-use utoipa_redoc::{Redoc, Servable};
-use utoipa_swagger_ui::SwaggerUi;
+use actix_web::{App, HttpServer, web};
+use clap::Parser;
 
-use crate::{
+use api::{
+  api_version,
+  app_settings::CONFIG_PATH,
   app_state::AppState,
+  cli, config_watcher,
   features::{
-    auth::auth_route::auth_routes, health_check::health_checker_handler,
-    roles::roles_route::role_routes, users::user_route::user_routes,
+    health_check::{liveness_handler, readyz_handler},
+    metrics::metrics_handler,
+    oidc::oidc_route::well_known_routes,
   },
-  swaggers::ApiDoc,
+  middleware::{
+    access_log::AccessLog,
+    cors::CorsPolicy,
+    docs_access::DocsGuard,
+    error_envelope::{self, error_envelope},
+    locale::NegotiateLocale,
+    payload_limit::{self, JsonDepthLimit},
+    request_id::AssignRequestId,
+    security_headers::security_headers,
+  },
+  migrations, scheduler, seed, swaggers,
+  utils::tls,
 };
 
 #[actix_web::main]
@@ -29,11 +32,16 @@ async fn main() -> std::io::Result<()> {
   unsafe {
     openssl_probe::init_openssl_env_vars();
   }
+  // Admin subcommands, the same ones the `cli` binary runs
+  if std::env::args().len() > 1 {
+    std::process::exit(cli::execute(cli::Cli::parse()).await);
+  }
+
   // Load AppState from JSON file
-  let state = match AppState::load_setting("appsettings.json").await {
+  let state = match AppState::load_setting(CONFIG_PATH).await {
     Ok(state) => web::Data::new(state),
     Err(e) => {
-      eprintln!("Failed to initialize app state: {}", e);
+      eprintln!("Failed to initialize app state: {:#}", e);
       std::process::exit(1);
     }
   };
@@ -43,41 +51,149 @@ async fn main() -> std::io::Result<()> {
       std::env::set_var("RUST_LOG", format!("actix_web={}", state.config.rust_log));
     }
   }
+  config_watcher::apply_log_level(&state.config.rust_log);
 
   let host = state.config.server.host.clone();
   let port = state.config.server.port;
-  let open_api = ApiDoc::openapi();
+  let shutdown_timeout = state.config.server.shutdown_timeout_secs;
+  let tls = state.config.server.tls.clone();
+  let is_dev = state.config.is_dev();
+  let docs_setting = state.config.docs.clone();
+  let docs_enabled = docs_setting.enabled_for(&state.config.environment);
+  let api_docs = swaggers::ApiDocs::build();
+  let cors_policy = match CorsPolicy::from_setting(&state.config) {
+    Ok(cors_policy) => cors_policy,
+    Err(e) => {
+      eprintln!("Invalid CORS settings: {}", e);
+      std::process::exit(1);
+    }
+  };
+  config_watcher::spawn(state.clone(), cors_policy.clone(), CONFIG_PATH);
+  // Shared by all workers so a client's count doesn't depend on which worker serves it
+  let rate_limit = state.rate_limit.clone();
+  let limits = state.config.limits.clone();
+  let security = state.config.security.clone();
+  let access_log = state.config.access_log.clone();
+  let metrics_enabled = state.config.metrics.enabled;
+  let startup_state = state.clone();
+  // Each enabled task waits for `mark_ready` before its first run
+  scheduler::spawn(state.clone());
+  let shutdown_state = state.clone();
   let server = HttpServer::new(move || {
-    let cors = Cors::default()
-      .allowed_origin("http://localhost:3000")
-      .allowed_origin("http://localhost:8000")
-      .allowed_methods(vec!["GET", "POST"])
-      .allowed_headers(vec![
-        header::CONTENT_TYPE,
-        header::AUTHORIZATION,
-        header::ACCEPT,
-      ])
-      .supports_credentials();
+    let cors = cors_policy.cors();
     App::new()
       .app_data(state.clone())
+      .app_data(payload_limit::json_config(&limits))
+      .app_data(payload_limit::payload_config(&limits))
+      .app_data(error_envelope::path_config())
+      .app_data(error_envelope::query_config())
+      .wrap(DocsGuard::new(&docs_setting))
+      .wrap(JsonDepthLimit::new(&limits))
+      .wrap(rate_limit.clone())
+      .wrap(error_envelope())
       .wrap(cors)
-      .wrap(Logger::default())
-      // Public routes here
-      .service(
-        web::scope("/api/v1")
-          .service(health_checker_handler)
-          .service(auth_routes())
-          .service(user_routes())
-          .service(role_routes()),
-      )
-      .service(Redoc::with_url("/redoc", open_api.clone()))
-      .service(RapiDoc::new("/api-docs/openapi.json").path("/redoc"))
-      .service(SwaggerUi::new("/{_:.*}").url("/api-docs/openapi.json", open_api.clone()))
+      .wrap(AssignRequestId)
+      // Also outside AssignRequestId, so the error bodies it renders are translated
+      .wrap(NegotiateLocale)
+      // Outside AssignRequestId so middleware errors rendered there get the headers too
+      .wrap(security_headers(&security))
+      .wrap(AccessLog::new(&access_log))
+      // Public routes here, one scope per API version
+      .configure(|cfg| api_version::configure(cfg, is_dev))
+      // Probes stay outside /api/v1 so orchestrators don't depend on the API version
+      .service(liveness_handler)
+      .service(readyz_handler)
+      .configure(|cfg| {
+        if metrics_enabled {
+          cfg.service(metrics_handler);
+        }
+      })
+      // Discovery documents live at the root, not under a version
+      .service(well_known_routes())
+      .configure(|cfg| {
+        if docs_enabled {
+          swaggers::configure(cfg, &docs_setting, &api_docs);
+        }
+      })
+      .default_service(web::to(error_envelope::route_not_found))
   })
-  .bind((host.clone(), port))?;
+  // Client certificates of the TLS listener, see `server.tls.client_auth`
+  .on_connect(tls::read_client_certificate)
+  .shutdown_timeout(shutdown_timeout);
+
+  let server = match &tls {
+    Some(tls) => {
+      let tls_config = match tls::load_server_config(tls) {
+        Ok(tls_config) => tls_config,
+        Err(e) => {
+          eprintln!("Failed to load TLS certificate: {:#}", e);
+          std::process::exit(1);
+        }
+      };
+      server.bind_rustls_0_23((host.clone(), port), tls_config)?
+    }
+    None => server.bind((host.clone(), port))?,
+  };
   // Log the running address
-  println!("Server is running at http://{}:{}", host, port);
+  let scheme = if tls.is_some() { "https" } else { "http" };
+  println!("Server is running at {}://{}:{}", scheme, host, port);
+
+  // Optional plain listener that only redirects to the HTTPS one
+  let redirect_server = match tls
+    .as_ref()
+    .and_then(|tls| tls.redirect_http_port.zip(tls.public_host.clone()))
+  {
+    Some((http_port, public_host)) => {
+      let target = tls::HttpsRedirect {
+        host: public_host,
+        port,
+      };
+      let redirect = HttpServer::new(move || {
+        App::new()
+          .app_data(web::Data::new(target.clone()))
+          .default_service(web::to(tls::redirect_to_https))
+      })
+      .bind((host.clone(), http_port))?;
+      println!("Redirecting http://{}:{} to HTTPS", host, http_port);
+      Some(redirect.run())
+    }
+    None => None,
+  };
+
+  // Pools and migrations come up after the listener, /readyz answers 503 until they are done
+  actix_web::rt::spawn(async move {
+    if let Err(e) = startup_state.init_db_manager().await {
+      eprintln!("Failed to initialize app state: {}", e);
+      std::process::exit(1);
+    }
+    if startup_state.config.database.migrate_on_startup
+      && let Err(e) = migrations::run_pending(&startup_state).await
+    {
+      eprintln!("Failed to apply migrations: {}", e);
+      std::process::exit(1);
+    }
+    if startup_state.config.database.seed_on_startup
+      && let Err(e) = seed::run_all(&startup_state).await
+    {
+      eprintln!("Failed to seed the database: {}", e);
+      std::process::exit(1);
+    }
+    startup_state.mark_ready();
+  });
+
+  // Run the server (blocking), on SIGTERM actix stops accepting and waits for in-flight handlers
+  let result = match redirect_server {
+    Some(redirect_server) => futures::future::try_join(server.run(), redirect_server)
+      .await
+      .map(|_| ()),
+    None => server.run().await,
+  };
 
-  // Run the server (blocking)
-  server.run().await
+  // Then hand every connection back before the pools are dropped with the state
+  shutdown_state
+    .db_manager
+    .shutdown(Duration::from_secs(shutdown_timeout))
+    .await;
+  drop(shutdown_state);
+  result
 }
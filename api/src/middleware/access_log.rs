@@ -0,0 +1,159 @@
+use std::{rc::Rc, time::Instant};
+
+use actix_web::{
+  HttpMessage, HttpRequest,
+  body::{BodySize, MessageBody},
+  dev::{Service, ServiceRequest, ServiceResponse, Transform},
+  http::{StatusCode, header::USER_AGENT},
+  web,
+};
+use chrono::Utc;
+use futures::future::{LocalBoxFuture, Ready, ready};
+use serde_json::json;
+
+use crate::{
+  app_settings::AccessLogSetting,
+  app_state::AppState,
+  features::users::user_dto::UserDto,
+  middleware::{auth::Impersonation, rate_limit::client_ip, request_id::RequestId},
+};
+
+// Label of the first bucket `latency_ms` falls under, e.g. `50-100ms`, or `2500ms+` past the last
+fn latency_bucket(latency_ms: u64, buckets: &[u64]) -> String {
+  let mut lower = 0;
+  for &upper in buckets {
+    if latency_ms < upper {
+      return format!("{}-{}ms", lower, upper);
+    }
+    lower = upper;
+  }
+  format!("{}ms+", lower)
+}
+
+// Read after the handler ran, so the auth middleware has stored who made the request. The
+// path is logged without its query string, which may carry tokens.
+fn log_line(
+  req: &HttpRequest,
+  status: StatusCode,
+  size: Option<u64>,
+  started: Instant,
+  setting: &AccessLogSetting,
+) -> String {
+  let latency_ms = started.elapsed().as_millis() as u64;
+  let extensions = req.extensions();
+  let user = extensions.get::<UserDto>();
+  let ip = req
+    .app_data::<web::Data<AppState>>()
+    .and_then(|data| client_ip(req, &data.runtime().rate_limit))
+    .map(|ip| ip.to_string());
+  json!({
+    "at": Utc::now().to_rfc3339(),
+    "request_id": extensions.get::<RequestId>().map(|id| id.0.clone()),
+    "method": req.method().as_str(),
+    "path": req.path(),
+    "route": req.match_pattern(),
+    "status": status.as_u16(),
+    "latency_ms": latency_ms,
+    "latency_bucket": latency_bucket(latency_ms, &setting.latency_buckets_ms),
+    "bytes": size,
+    "user_id": user.map(|user| user.id),
+    "role": user.map(|user| user.role.to_str()),
+    "impersonated_by": extensions.get::<Impersonation>().map(|i| i.admin_id),
+    "ip": ip,
+    "user_agent": req.headers().get(USER_AGENT).and_then(|h| h.to_str().ok()),
+  })
+  .to_string()
+}
+
+/// Writes one structured line per request with who made it and how long it took.
+#[derive(Clone)]
+pub struct AccessLog {
+  setting: Rc<AccessLogSetting>,
+}
+
+impl AccessLog {
+  pub fn new(setting: &AccessLogSetting) -> Self {
+    Self {
+      setting: Rc::new(setting.clone()),
+    }
+  }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for AccessLog
+where
+  S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+  B: MessageBody + 'static,
+{
+  type Response = ServiceResponse<B>;
+
+  type Error = actix_web::Error;
+
+  type Transform = AccessLogMiddleware<S>;
+
+  type InitError = ();
+
+  type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+  fn new_transform(&self, service: S) -> Self::Future {
+    ready(Ok(AccessLogMiddleware {
+      service: Rc::new(service),
+      setting: Rc::clone(&self.setting),
+    }))
+  }
+}
+
+pub struct AccessLogMiddleware<S> {
+  service: Rc<S>,
+  setting: Rc<AccessLogSetting>,
+}
+
+impl<S, B> Service<ServiceRequest> for AccessLogMiddleware<S>
+where
+  S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+  B: MessageBody + 'static,
+{
+  type Response = ServiceResponse<B>;
+
+  type Error = actix_web::Error;
+
+  type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+  fn poll_ready(
+    &self,
+    ctx: &mut core::task::Context<'_>,
+  ) -> std::task::Poll<Result<(), Self::Error>> {
+    self.service.poll_ready(ctx)
+  }
+
+  fn call(&self, req: ServiceRequest) -> Self::Future {
+    let srv = Rc::clone(&self.service);
+    if !self.setting.enabled || self.setting.skip_paths.iter().any(|p| p == req.path()) {
+      return Box::pin(srv.call(req));
+    }
+
+    let setting = Rc::clone(&self.setting);
+    let http_req = req.request().clone();
+    let started = Instant::now();
+    Box::pin(async move {
+      let result = srv.call(req).await;
+      let line = match &result {
+        Ok(res) => {
+          let size = match res.response().body().size() {
+            BodySize::Sized(size) => Some(size),
+            _ => None,
+          };
+          log_line(res.request(), res.status(), size, started, &setting)
+        }
+        Err(e) => log_line(
+          &http_req,
+          e.as_response_error().status_code(),
+          None,
+          started,
+          &setting,
+        ),
+      };
+      println!("{}", line);
+      result
+    })
+  }
+}
@@ -4,7 +4,7 @@ use actix_web::{
   FromRequest, HttpMessage, body,
   dev::{Service, ServiceRequest, ServiceResponse, Transform},
   error::{ErrorForbidden, ErrorInternalServerError, ErrorNotFound, ErrorUnauthorized},
-  http, web,
+  web,
 };
 use futures::{
   FutureExt,
@@ -15,11 +15,58 @@ use crate::{
   app_state::AppState,
   dto::base_res_dto::Status,
   error::StatusMessage,
-  features::users::{user_dto::UserDto, user_entity::UserRole, user_repo::UserRepo},
-  utils::jwt_util::JwtUtil,
+  features::{
+    auth::auth_dto::Claims,
+    users::{user_dto::UserDto, user_entity::UserRole},
+  },
+  middleware::credentials::CredentialExtractor,
+  utils::{
+    custom_claims::CustomClaims, device_fingerprint, jwt_util::JwtUtil, tls::ClientCertificate,
+  },
 };
 
-pub struct Authenticated(UserDto);
+// Effective permissions of the authenticated user, taken from the token claims
+#[derive(Clone, Default)]
+pub struct Permissions(pub Vec<String>);
+
+// Custom claims of the token, see `ClaimsEnricher`
+#[derive(Clone, Default)]
+pub struct TokenClaims(pub CustomClaims);
+
+/// Set on requests made with an /admin/impersonate token, `admin_id` is the admin behind them.
+#[derive(Clone, Copy)]
+pub struct Impersonation {
+  pub admin_id: i32,
+  /// Expiry of the admin's own token the impersonation started from, `None` when the admin
+  /// authenticated with a client certificate
+  pub admin_expires_at: Option<usize>,
+}
+
+// Expiry of the token the request was made with, absent for certificate callers
+#[derive(Clone, Copy)]
+pub struct TokenExpiry(pub usize);
+
+pub struct Authenticated {
+  user: UserDto,
+  pub permissions: Vec<String>,
+  /// Admin acting as `user`, `None` outside of impersonation
+  pub impersonated_by: Option<i32>,
+  /// `jwt.static_claims` and enricher claims as of when the token was issued
+  pub claims: CustomClaims,
+  /// Unix time the token expires at, `None` for certificate callers
+  pub expires_at: Option<usize>,
+}
+
+impl Authenticated {
+  pub fn has_permission(&self, permission: &str) -> bool {
+    self.permissions.iter().any(|p| p == permission)
+  }
+
+  /// A custom claim of the token, e.g. `current_user.claim("tenant")`.
+  pub fn claim(&self, name: &str) -> Option<&serde_json::Value> {
+    self.claims.get(name)
+  }
+}
 
 impl FromRequest for Authenticated {
   type Error = actix_web::Error;
@@ -27,9 +74,20 @@ impl FromRequest for Authenticated {
   type Future = Ready<Result<Self, Self::Error>>;
 
   fn from_request(req: &actix_web::HttpRequest, _: &mut actix_web::dev::Payload) -> Self::Future {
-    let value = req.extensions().get::<UserDto>().cloned();
+    let extensions = req.extensions();
+    let value = extensions.get::<UserDto>().cloned();
+    let permissions = extensions.get::<Permissions>().cloned().unwrap_or_default();
+    let impersonation = extensions.get::<Impersonation>().copied();
+    let claims = extensions.get::<TokenClaims>().cloned().unwrap_or_default();
+    let expiry = extensions.get::<TokenExpiry>().copied();
     let result = match value {
-      Some(user) => Ok(Authenticated(user)),
+      Some(user) => Ok(Authenticated {
+        user,
+        permissions: permissions.0,
+        impersonated_by: impersonation.map(|i| i.admin_id),
+        claims: claims.0,
+        expires_at: expiry.map(|e| e.0),
+      }),
       None => Err(ErrorInternalServerError(Status::server_error(
         "Authentication error",
       ))),
@@ -42,18 +100,20 @@ impl ops::Deref for Authenticated {
   type Target = UserDto;
 
   fn deref(&self) -> &Self::Target {
-    &self.0
+    &self.user
   }
 }
 
+// Role names are matched case-insensitively against the user's effective roles, so both
+// `UserRole::Admin` and roles created through /role/create can be used here
 pub struct RequireAuth {
-  pub allow_roles: Rc<Vec<UserRole>>,
+  pub allow_roles: Rc<Vec<String>>,
 }
 
 impl RequireAuth {
-  pub fn allow_roles(allow_roles: Vec<UserRole>) -> Self {
+  pub fn allow_roles<R: Into<String>>(allow_roles: Vec<R>) -> Self {
     Self {
-      allow_roles: Rc::new(allow_roles),
+      allow_roles: Rc::new(allow_roles.into_iter().map(Into::into).collect()),
     }
   }
 }
@@ -83,7 +143,7 @@ where
 
 pub struct AuthMiddleware<S> {
   service: Rc<S>,
-  allow_roles: Rc<Vec<UserRole>>,
+  allow_roles: Rc<Vec<String>>,
 }
 
 impl<S> Service<ServiceRequest> for AuthMiddleware<S>
@@ -105,46 +165,304 @@ where
   }
 
   fn call(&self, req: ServiceRequest) -> Self::Future {
-    let app_state = req.app_data::<web::Data<AppState>>().unwrap();
-
-    let token = req
-      .cookie(&app_state.config.cookie.name)
-      .map(|c| c.value().to_string())
-      .or_else(|| {
-        req
-          .headers()
-          .get(http::header::AUTHORIZATION)
-          .map(|h| h.to_str().unwrap().split_at(7).1.to_string())
-      });
-    if token.is_none() {
-      return Box::pin(ready(Err(ErrorUnauthorized(Status::token_missing()))));
-    }
-
-    let jwt_util = JwtUtil::new(&app_state.config.jwt);
-    let user_claims = match jwt_util.decode_token(&token.unwrap()) {
-      Ok(claims) => claims,
-      Err(e) => {
-        return Box::pin(ready(Err(ErrorUnauthorized(Status::unauthorized(
-          format!("{}, {}", StatusMessage::DecodeTokenErr.to_str(), e),
-        )))));
-      }
+    let app_state = req.app_data::<web::Data<AppState>>().unwrap().clone();
+    let credential = match read_credential(&req, &app_state) {
+      Ok(credential) => credential,
+      Err(e) => return Box::pin(ready(Err(e))),
     };
 
-    let app_state_cloned = app_state.clone();
     let allow_roles = self.allow_roles.clone();
     let srv = Rc::clone(&self.service);
 
     async move {
-      let mut user_repo = UserRepo::new(&app_state_cloned);
-      let result = user_repo
-        .get_by_id(user_claims.sub)
+      let mut principal = load_principal(&app_state, credential).await?;
+
+      if allow_roles.iter().any(|role| principal.user.has_role(role)) {
+        let permissions = match principal.token_permissions.take() {
+          Some(permissions) => permissions,
+          None => resolve_permissions(&app_state, principal.user.id).await?,
+        };
+        principal.attach(&req, permissions);
+        let res = srv.call(req).await?;
+        Ok(res)
+      } else {
+        Err(ErrorForbidden(Status::forbidden()))
+      }
+    }
+    .boxed_local()
+  }
+}
+
+// How a request proves who it is made as
+enum Credential {
+  Token(Claims),
+  /// User name mapped to the client certificate in `server.tls.client_auth.principals`
+  Certificate(String),
+}
+
+// The token when there is one, else a mapped client certificate
+fn read_credential(
+  req: &ServiceRequest,
+  app_state: &AppState,
+) -> Result<Credential, actix_web::Error> {
+  if let Some(token) = CredentialExtractor::new(&app_state.config).extract(req.request()) {
+    let claims = decode_claims(&token, app_state)?;
+    // A bound token only works from the device it was issued to, even if binding was
+    // turned off since
+    if let Some(dfp) = &claims.dfp
+      && *dfp
+        != device_fingerprint::fingerprint(req.request(), &app_state.config.jwt.device_binding)
+    {
+      return Err(ErrorUnauthorized(Status::device_mismatch()));
+    }
+    return Ok(Credential::Token(claims));
+  }
+  let client_auth = app_state
+    .config
+    .server
+    .tls
+    .as_ref()
+    .and_then(|tls| tls.client_auth.as_ref());
+  let principal = client_auth.and_then(|client_auth| {
+    req
+      .conn_data::<ClientCertificate>()
+      .and_then(|certificate| certificate.principal(client_auth))
+  });
+  match principal {
+    Some(user_name) => Ok(Credential::Certificate(user_name.to_string())),
+    None => Err(ErrorUnauthorized(Status::token_missing())),
+  }
+}
+
+// Validates the token taken from the request
+fn decode_claims(token: &str, app_state: &AppState) -> Result<Claims, actix_web::Error> {
+  let jwt_util = JwtUtil::from_state(app_state);
+  jwt_util.decode_token(token).map_err(|e| {
+    ErrorUnauthorized(Status::unauthorized(format!(
+      "{}, {}",
+      StatusMessage::DecodeTokenErr.to_str(),
+      e
+    )))
+  })
+}
+
+// Loads the user with roles resolved from the DB, so role changes apply without re-login
+// and keeps the result for a few seconds to save a stored proc call per request
+async fn load_active_user(app_state: &AppState, user_id: i32) -> Result<UserDto, actix_web::Error> {
+  if let Some(user) = app_state.user_cache.get(&user_id) {
+    return Ok(user);
+  }
+  let mut user_repo = app_state.user_repo();
+  let result = user_repo.get_by_id(user_id).await.map_err(|e| {
+    actix_web::Error::from(Status::server_error(e.to_string()).or_query_timeout(&e))
+  })?;
+
+  let user = result.ok_or(ErrorNotFound(Status::not_found("User")))?;
+
+  if !user.is_active {
+    return Err(ErrorForbidden(Status::account_disabled()));
+  }
+
+  let role_names = match app_state.role_cache.get(&user_id) {
+    Some(role_names) => role_names,
+    None => {
+      let role_names = app_state
+        .role_repo()
+        .get_user_role_names(user_id)
+        .await
+        .map_err(|e| {
+          actix_web::Error::from(Status::server_error(e.to_string()).or_query_timeout(&e))
+        })?;
+      app_state.role_cache.set(user_id, role_names.clone());
+      role_names
+    }
+  };
+  let user = UserDto::from(user).with_roles(role_names);
+  app_state.user_cache.set(user_id, user.clone());
+  Ok(user)
+}
+
+/// The user the token acts as. An impersonation token only works while the admin behind it is
+/// still an active admin, so revoking the admin also ends their impersonations.
+pub async fn load_subject(
+  app_state: &AppState,
+  claims: &Claims,
+) -> Result<(UserDto, Option<Impersonation>), actix_web::Error> {
+  if claims.act_as.is_some() {
+    let admin = load_active_user(app_state, claims.sub).await?;
+    if !admin.has_role(UserRole::Admin.to_str()) {
+      return Err(ErrorForbidden(Status::forbidden()));
+    }
+  }
+  let user = load_active_user(app_state, claims.user_id()).await?;
+  let impersonation = claims.act_as.map(|_| Impersonation {
+    admin_id: claims.sub,
+    admin_expires_at: claims.act_exp,
+  });
+  Ok((user, impersonation))
+}
+
+// The authenticated user with what the credential carried
+struct Principal {
+  user: UserDto,
+  impersonation: Option<Impersonation>,
+  /// Permissions in the token, `None` for certificate callers
+  token_permissions: Option<Vec<String>>,
+  claims: CustomClaims,
+  expiry: Option<TokenExpiry>,
+}
+
+impl Principal {
+  // Makes the principal available to `Authenticated`
+  fn attach(self, req: &ServiceRequest, permissions: Vec<String>) {
+    let mut extensions = req.extensions_mut();
+    if let Some(impersonation) = self.impersonation {
+      extensions.insert::<Impersonation>(impersonation);
+    }
+    extensions.insert::<UserDto>(self.user);
+    extensions.insert::<Permissions>(Permissions(permissions));
+    extensions.insert::<TokenClaims>(TokenClaims(self.claims));
+    if let Some(expiry) = self.expiry {
+      extensions.insert::<TokenExpiry>(expiry);
+    }
+  }
+}
+
+async fn load_principal(
+  app_state: &AppState,
+  credential: Credential,
+) -> Result<Principal, actix_web::Error> {
+  match credential {
+    Credential::Token(claims) => {
+      let (user, impersonation) = load_subject(app_state, &claims).await?;
+      Ok(Principal {
+        user,
+        impersonation,
+        token_permissions: Some(claims.permissions),
+        claims: claims.custom,
+        expiry: Some(TokenExpiry(claims.exp)),
+      })
+    }
+    Credential::Certificate(user_name) => {
+      let result = app_state
+        .user_repo()
+        .get_by_username(&user_name)
         .await
-        .map_err(|e| ErrorInternalServerError(Status::server_error(e.to_string())))?;
+        .map_err(|e| {
+          actix_web::Error::from(Status::server_error(e.to_string()).or_query_timeout(&e))
+        })?;
+      let user_id = result
+        .map(|user| user.id)
+        .ok_or(ErrorNotFound(Status::not_found("User")))?;
+      Ok(Principal {
+        user: load_active_user(app_state, user_id).await?,
+        impersonation: None,
+        token_permissions: None,
+        claims: CustomClaims::new(),
+        expiry: None,
+      })
+    }
+  }
+}
+
+// Permissions resolved from the DB rather than the token, so grants apply without re-login
+async fn resolve_permissions(
+  app_state: &AppState,
+  user_id: i32,
+) -> Result<Vec<String>, actix_web::Error> {
+  if let Some(permissions) = app_state.permission_cache.get(&user_id) {
+    return Ok(permissions);
+  }
+
+  let permissions = app_state
+    .permission_repo()
+    .get_user_permissions(user_id)
+    .await
+    .map_err(|e| {
+      actix_web::Error::from(Status::server_error(e.to_string()).or_query_timeout(&e))
+    })?;
+  app_state.permission_cache.set(user_id, permissions.clone());
+  Ok(permissions)
+}
+
+/// Allows users holding `permission`, e.g. `RequirePermission::new("role:create")`.
+/// Admins always pass so existing deployments keep working before permissions are granted.
+pub struct RequirePermission {
+  pub permission: Rc<String>,
+}
 
-      let user = result.ok_or(ErrorNotFound(Status::not_found("User")))?;
+impl RequirePermission {
+  pub fn new(permission: impl Into<String>) -> Self {
+    Self {
+      permission: Rc::new(permission.into()),
+    }
+  }
+}
+
+impl<S> Transform<S, ServiceRequest> for RequirePermission
+where
+  S: Service<ServiceRequest, Response = ServiceResponse<body::BoxBody>, Error = actix_web::Error>
+    + 'static,
+{
+  type Response = ServiceResponse<body::BoxBody>;
+
+  type Error = actix_web::Error;
+
+  type Transform = PermissionMiddleware<S>;
+
+  type InitError = ();
+
+  type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+  fn new_transform(&self, service: S) -> Self::Future {
+    ready(Ok(PermissionMiddleware {
+      service: Rc::new(service),
+      permission: self.permission.clone(),
+    }))
+  }
+}
+
+pub struct PermissionMiddleware<S> {
+  service: Rc<S>,
+  permission: Rc<String>,
+}
+
+impl<S> Service<ServiceRequest> for PermissionMiddleware<S>
+where
+  S: Service<ServiceRequest, Response = ServiceResponse<body::BoxBody>, Error = actix_web::Error>
+    + 'static,
+{
+  type Response = ServiceResponse<body::BoxBody>;
+
+  type Error = actix_web::Error;
+
+  type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+  fn poll_ready(
+    &self,
+    ctx: &mut core::task::Context<'_>,
+  ) -> std::task::Poll<Result<(), Self::Error>> {
+    self.service.poll_ready(ctx)
+  }
+
+  fn call(&self, req: ServiceRequest) -> Self::Future {
+    let app_state = req.app_data::<web::Data<AppState>>().unwrap().clone();
+    let credential = match read_credential(&req, &app_state) {
+      Ok(credential) => credential,
+      Err(e) => return Box::pin(ready(Err(e))),
+    };
+
+    let permission = self.permission.clone();
+    let srv = Rc::clone(&self.service);
+
+    async move {
+      let principal = load_principal(&app_state, credential).await?;
+      let permissions = resolve_permissions(&app_state, principal.user.id).await?;
 
-      if allow_roles.contains(&user.role) {
-        req.extensions_mut().insert::<UserDto>(UserDto::from(user));
+      if principal.user.has_role(UserRole::Admin.to_str())
+        || permissions.iter().any(|p| p == permission.as_str())
+      {
+        principal.attach(&req, permissions);
         let res = srv.call(req).await?;
         Ok(res)
       } else {
@@ -0,0 +1,134 @@
+use std::sync::Arc;
+
+use actix_cors::Cors;
+use actix_web::http::{Method, header::HeaderName};
+use anyhow::{Result, anyhow};
+use arc_swap::ArcSwap;
+use regex::Regex;
+
+use crate::{
+  app_settings::{AppSetting, CorsSetting},
+  middleware::request_id::REQUEST_ID_HEADER,
+};
+
+const REGEX_PREFIX: &str = "regex:";
+
+enum OriginRule {
+  Any,
+  Exact(String),
+  Wildcard { prefix: String, suffix: String },
+  Pattern(Regex),
+}
+
+impl OriginRule {
+  fn parse(origin: &str) -> Result<Self> {
+    if origin == "*" {
+      return Ok(OriginRule::Any);
+    }
+    if let Some(pattern) = origin.strip_prefix(REGEX_PREFIX) {
+      let regex = Regex::new(pattern)
+        .map_err(|e| anyhow!("Invalid CORS origin regex '{}': {}", pattern, e))?;
+      return Ok(OriginRule::Pattern(regex));
+    }
+    match origin.split_once('*') {
+      Some((_, suffix)) if suffix.contains('*') => {
+        Err(anyhow!("CORS origin '{}' may contain only one '*'", origin))
+      }
+      Some((prefix, suffix)) => Ok(OriginRule::Wildcard {
+        prefix: prefix.to_string(),
+        suffix: suffix.to_string(),
+      }),
+      None => Ok(OriginRule::Exact(origin.trim_end_matches('/').to_string())),
+    }
+  }
+
+  fn matches(&self, origin: &str) -> bool {
+    match self {
+      OriginRule::Any => true,
+      OriginRule::Exact(allowed) => allowed.eq_ignore_ascii_case(origin),
+      // The wildcard stands for subdomain labels, never for a path or port separator
+      OriginRule::Wildcard { prefix, suffix } => {
+        origin.len() > prefix.len() + suffix.len()
+          && origin.starts_with(prefix.as_str())
+          && origin.ends_with(suffix.as_str())
+          && !origin[prefix.len()..origin.len() - suffix.len()].contains(['/', ':'])
+      }
+      OriginRule::Pattern(regex) => regex.is_match(origin),
+    }
+  }
+}
+
+fn parse_origins(setting: &CorsSetting, environment: &str) -> Result<Vec<OriginRule>> {
+  setting
+    .origins_for(environment)
+    .iter()
+    .map(|origin| OriginRule::parse(origin))
+    .collect()
+}
+
+/// CORS settings validated once at startup, `cors()` builds the middleware for each worker.
+/// Only the origins can be reloaded, methods and headers are fixed when the workers start.
+#[derive(Clone)]
+pub struct CorsPolicy {
+  origins: Arc<ArcSwap<Vec<OriginRule>>>,
+  methods: Vec<Method>,
+  headers: Vec<HeaderName>,
+  supports_credentials: bool,
+  max_age_secs: Option<usize>,
+}
+
+impl CorsPolicy {
+  pub fn from_setting(config: &AppSetting) -> Result<Self> {
+    let setting = &config.cors;
+    let origins = parse_origins(setting, &config.environment)?;
+    let methods = setting
+      .allowed_methods
+      .iter()
+      .map(|method| {
+        Method::from_bytes(method.to_uppercase().as_bytes())
+          .map_err(|_| anyhow!("Invalid CORS method '{}'", method))
+      })
+      .collect::<Result<Vec<_>>>()?;
+    let headers = setting
+      .allowed_headers
+      .iter()
+      .map(|name| {
+        HeaderName::from_bytes(name.as_bytes())
+          .map_err(|_| anyhow!("Invalid CORS header '{}'", name))
+      })
+      .collect::<Result<Vec<_>>>()?;
+
+    Ok(Self {
+      origins: Arc::new(ArcSwap::from_pointee(origins)),
+      methods,
+      headers,
+      supports_credentials: setting.supports_credentials,
+      max_age_secs: setting.max_age_secs,
+    })
+  }
+
+  // Every worker's middleware shares the swapped list, so new origins apply at once
+  pub fn reload_origins(&self, setting: &CorsSetting, environment: &str) -> Result<()> {
+    let origins = parse_origins(setting, environment)?;
+    self.origins.store(Arc::new(origins));
+    Ok(())
+  }
+
+  pub fn cors(&self) -> Cors {
+    let origins = self.origins.clone();
+    let mut cors = Cors::default()
+      .allowed_origin_fn(move |origin, _| {
+        origin
+          .to_str()
+          .is_ok_and(|origin| origins.load().iter().any(|rule| rule.matches(origin)))
+      })
+      .allowed_methods(self.methods.clone())
+      .allowed_headers(self.headers.clone())
+      .expose_headers(vec![REQUEST_ID_HEADER])
+      .max_age(self.max_age_secs);
+    if self.supports_credentials {
+      cors = cors.supports_credentials();
+    }
+    cors
+  }
+}
@@ -0,0 +1,51 @@
+use actix_web::{HttpRequest, http::header};
+
+use crate::app_settings::{AppSetting, CredentialSource};
+
+const BEARER: &str = "bearer ";
+
+/// Finds the token of a request in the configured `auth.credential_sources`.
+/// A missing, non UTF-8 or malformed value just moves on to the next source.
+pub struct CredentialExtractor<'a> {
+  sources: &'a [CredentialSource],
+  cookie_name: &'a str,
+}
+
+impl<'a> CredentialExtractor<'a> {
+  pub fn new(config: &'a AppSetting) -> Self {
+    Self {
+      sources: &config.auth.credential_sources,
+      cookie_name: &config.cookie.name,
+    }
+  }
+
+  pub fn extract(&self, req: &HttpRequest) -> Option<String> {
+    self
+      .sources
+      .iter()
+      .find_map(|source| self.read(req, source))
+  }
+
+  fn read(&self, req: &HttpRequest, source: &CredentialSource) -> Option<String> {
+    match source {
+      CredentialSource::Cookie => req
+        .cookie(self.cookie_name)
+        .map(|cookie| cookie.value().to_string()),
+      CredentialSource::Bearer => header_value(req, header::AUTHORIZATION.as_str())
+        .filter(|value| value.len() > BEARER.len() && value.is_char_boundary(BEARER.len()))
+        // The scheme is case-insensitive
+        .filter(|value| value[..BEARER.len()].eq_ignore_ascii_case(BEARER))
+        .map(|value| value[BEARER.len()..].trim().to_string()),
+      CredentialSource::Header { name } => header_value(req, name).map(str::to_string),
+    }
+    .filter(|token| !token.is_empty())
+  }
+}
+
+fn header_value<'r>(req: &'r HttpRequest, name: &str) -> Option<&'r str> {
+  req
+    .headers()
+    .get(name)
+    .and_then(|value| value.to_str().ok())
+    .map(str::trim)
+}
@@ -0,0 +1,149 @@
+use std::rc::Rc;
+
+use actix_web::{
+  HttpRequest, HttpResponse, body,
+  dev::{Service, ServiceRequest, ServiceResponse, Transform},
+  http::header,
+};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use futures::future::{LocalBoxFuture, Ready, ready};
+use sha2::{Digest, Sha256};
+
+use crate::{
+  app_settings::{BasicAuthSetting, DocsAccess, DocsSetting},
+  dto::base_res_dto::Status,
+  error::StatusMessage,
+  features::users::user_entity::UserRole,
+  middleware::auth::{AuthMiddleware, RequireAuth},
+};
+
+/// Paths served by `swaggers::configure`.
+pub const DOCS_PREFIXES: [&str; 4] = ["/swagger-ui", "/api-docs", "/redoc", "/rapidoc"];
+
+fn is_docs_path(path: &str) -> bool {
+  DOCS_PREFIXES
+    .iter()
+    .any(|prefix| path == *prefix || path.starts_with(&format!("{}/", prefix)))
+}
+
+/// Compares digests so the time taken doesn't depend on how much of the value matched.
+pub fn same_secret(given: &str, expected: &str) -> bool {
+  Sha256::digest(given.as_bytes()) == Sha256::digest(expected.as_bytes())
+}
+
+/// User name and password of an `Authorization: Basic` header.
+pub fn basic_credentials(req: &HttpRequest) -> Option<(String, String)> {
+  let credentials = req
+    .headers()
+    .get(header::AUTHORIZATION)
+    .and_then(|value| value.to_str().ok())
+    .and_then(|value| value.strip_prefix("Basic "))
+    .and_then(|encoded| STANDARD.decode(encoded.trim()).ok())
+    .and_then(|decoded| String::from_utf8(decoded).ok())?;
+  credentials
+    .split_once(':')
+    .map(|(username, password)| (username.to_string(), password.to_string()))
+}
+
+fn is_basic_authorized(req: &ServiceRequest, basic: &BasicAuthSetting) -> bool {
+  match basic_credentials(req.request()) {
+    Some((username, password)) => {
+      same_secret(&username, &basic.username) & same_secret(&password, &basic.password)
+    }
+    None => false,
+  }
+}
+
+// Applies `docs.access` to the documentation paths, every other request passes through.
+// Wrap it directly around the app, before any other middleware.
+pub struct DocsGuard {
+  pub access: DocsAccess,
+  pub basic_auth: Option<Rc<BasicAuthSetting>>,
+}
+
+impl DocsGuard {
+  pub fn new(setting: &DocsSetting) -> Self {
+    Self {
+      access: setting.access,
+      basic_auth: setting.basic_auth.clone().map(Rc::new),
+    }
+  }
+}
+
+impl<S> Transform<S, ServiceRequest> for DocsGuard
+where
+  S: Service<ServiceRequest, Response = ServiceResponse<body::BoxBody>, Error = actix_web::Error>
+    + 'static,
+{
+  type Response = ServiceResponse<body::BoxBody>;
+
+  type Error = actix_web::Error;
+
+  type Transform = DocsGuardMiddleware<S>;
+
+  type InitError = ();
+
+  type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+  fn new_transform(&self, service: S) -> Self::Future {
+    let service = Rc::new(service);
+    // The docs go through the same checks as the admin routes
+    let admin = match self.access {
+      DocsAccess::Admin => RequireAuth::allow_roles(vec![UserRole::Admin])
+        .new_transform(Rc::clone(&service))
+        .into_inner()
+        .ok(),
+      _ => None,
+    };
+    ready(Ok(DocsGuardMiddleware {
+      service,
+      admin,
+      access: self.access,
+      basic_auth: self.basic_auth.clone(),
+    }))
+  }
+}
+
+pub struct DocsGuardMiddleware<S> {
+  service: Rc<S>,
+  admin: Option<AuthMiddleware<Rc<S>>>,
+  access: DocsAccess,
+  basic_auth: Option<Rc<BasicAuthSetting>>,
+}
+
+impl<S> Service<ServiceRequest> for DocsGuardMiddleware<S>
+where
+  S: Service<ServiceRequest, Response = ServiceResponse<body::BoxBody>, Error = actix_web::Error>
+    + 'static,
+{
+  type Response = ServiceResponse<body::BoxBody>;
+
+  type Error = actix_web::Error;
+
+  type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+  fn poll_ready(
+    &self,
+    ctx: &mut core::task::Context<'_>,
+  ) -> std::task::Poll<Result<(), Self::Error>> {
+    self.service.poll_ready(ctx)
+  }
+
+  fn call(&self, req: ServiceRequest) -> Self::Future {
+    if !is_docs_path(req.path()) {
+      return Box::pin(self.service.call(req));
+    }
+
+    match (self.access, &self.admin, &self.basic_auth) {
+      (DocsAccess::Admin, Some(admin), _) => admin.call(req),
+      (DocsAccess::Basic, _, Some(basic)) if !is_basic_authorized(&req, basic) => {
+        // Browsers show their login prompt on this challenge
+        let res = HttpResponse::Unauthorized()
+          .insert_header((header::WWW_AUTHENTICATE, "Basic realm=\"API docs\""))
+          .json(Status::unauthorized(StatusMessage::Unauthorized));
+        Box::pin(ready(Ok(req.into_response(res))))
+      }
+      _ => Box::pin(self.service.call(req)),
+    }
+  }
+}
@@ -0,0 +1,56 @@
+use actix_web::{
+  HttpRequest, HttpResponse,
+  dev::ServiceResponse,
+  http::{StatusCode, header::CONTENT_TYPE},
+  middleware::{ErrorHandlerResponse, ErrorHandlers},
+  web,
+};
+
+use crate::dto::base_res_dto::{BaseResDto, Status};
+
+/// Default service, unknown routes answer 404 with the standard `Status` body.
+pub async fn route_not_found() -> HttpResponse {
+  Status::from_http_status(StatusCode::NOT_FOUND).into_http_response()
+}
+
+pub fn path_config() -> web::PathConfig {
+  web::PathConfig::default().error_handler(|err, _req: &HttpRequest| {
+    Status::bad_request(format!("Invalid path parameter: {}", err)).into()
+  })
+}
+
+pub fn query_config() -> web::QueryConfig {
+  web::QueryConfig::default().error_handler(|err, _req: &HttpRequest| {
+    Status::bad_request(format!("Invalid query string: {}", err)).into()
+  })
+}
+
+fn is_json(res: &ServiceResponse<impl Sized>) -> bool {
+  res
+    .headers()
+    .get(CONTENT_TYPE)
+    .and_then(|v| v.to_str().ok())
+    .is_some_and(|v| v.starts_with("application/json"))
+}
+
+// Replaces the plain text body of an error actix produced itself, such as the 405 of a route
+// registered for another method. Responses that already carry JSON are left untouched.
+fn into_status_body<B>(res: ServiceResponse<B>) -> actix_web::Result<ErrorHandlerResponse<B>> {
+  if is_json(&res) {
+    return Ok(ErrorHandlerResponse::Response(res.map_into_left_body()));
+  }
+  let status = res.status();
+  let (req, _) = res.into_parts();
+  let body = HttpResponse::build(status).json(BaseResDto::<()> {
+    data: None,
+    status: Status::from_http_status(status),
+  });
+  Ok(ErrorHandlerResponse::Response(
+    ServiceResponse::new(req, body).map_into_right_body(),
+  ))
+}
+
+/// Wraps every 4xx/5xx response without a JSON body in the `BaseResDto` envelope.
+pub fn error_envelope<B: 'static>() -> ErrorHandlers<B> {
+  ErrorHandlers::new().default_handler(into_status_body)
+}
@@ -0,0 +1,89 @@
+use std::rc::Rc;
+
+use actix_web::{
+  HttpMessage, body,
+  dev::{Service, ServiceRequest, ServiceResponse, Transform},
+  error::ErrorNotFound,
+  web,
+};
+use futures::future::{LocalBoxFuture, Ready, ready};
+
+use crate::{
+  app_state::AppState, dto::base_res_dto::Status, features::users::user_dto::UserDto,
+  utils::feature_flags,
+};
+
+// Hides a route (404) from users outside the flag's rollout.
+// Wrap it inside `RequireAuth` so the user is known when rules are evaluated.
+pub struct RequireFeature {
+  pub flag: Rc<String>,
+}
+
+impl RequireFeature {
+  pub fn new(flag: impl Into<String>) -> Self {
+    Self {
+      flag: Rc::new(flag.into()),
+    }
+  }
+}
+
+impl<S> Transform<S, ServiceRequest> for RequireFeature
+where
+  S: Service<ServiceRequest, Response = ServiceResponse<body::BoxBody>, Error = actix_web::Error>
+    + 'static,
+{
+  type Response = ServiceResponse<body::BoxBody>;
+
+  type Error = actix_web::Error;
+
+  type Transform = FeatureGateMiddleware<S>;
+
+  type InitError = ();
+
+  type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+  fn new_transform(&self, service: S) -> Self::Future {
+    ready(Ok(FeatureGateMiddleware {
+      service: Rc::new(service),
+      flag: self.flag.clone(),
+    }))
+  }
+}
+
+pub struct FeatureGateMiddleware<S> {
+  service: Rc<S>,
+  flag: Rc<String>,
+}
+
+impl<S> Service<ServiceRequest> for FeatureGateMiddleware<S>
+where
+  S: Service<ServiceRequest, Response = ServiceResponse<body::BoxBody>, Error = actix_web::Error>
+    + 'static,
+{
+  type Response = ServiceResponse<body::BoxBody>;
+
+  type Error = actix_web::Error;
+
+  type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+  fn poll_ready(
+    &self,
+    ctx: &mut core::task::Context<'_>,
+  ) -> std::task::Poll<Result<(), Self::Error>> {
+    self.service.poll_ready(ctx)
+  }
+
+  fn call(&self, req: ServiceRequest) -> Self::Future {
+    let app_state = req.app_data::<web::Data<AppState>>().unwrap();
+    let enabled = feature_flags::is_enabled(
+      &app_state.runtime().feature_flags,
+      &self.flag,
+      req.extensions().get::<UserDto>(),
+    );
+
+    if !enabled {
+      return Box::pin(ready(Err(ErrorNotFound(Status::not_found("Resource")))));
+    }
+    Box::pin(self.service.call(req))
+  }
+}
@@ -0,0 +1,77 @@
+use std::rc::Rc;
+
+use actix_web::{
+  body::{BoxBody, MessageBody},
+  dev::{Service, ServiceRequest, ServiceResponse, Transform},
+  http::header::{ACCEPT_LANGUAGE, CONTENT_LANGUAGE, HeaderValue},
+};
+use futures::future::{LocalBoxFuture, Ready, ready};
+
+use crate::i18n::{self, Locale};
+
+// Resolves the request's language from Accept-Language so `StatusMessage`s are rendered in it,
+// and tells the client which one was used with Content-Language
+pub struct NegotiateLocale;
+
+impl<S, B> Transform<S, ServiceRequest> for NegotiateLocale
+where
+  S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+  B: MessageBody + 'static,
+{
+  type Response = ServiceResponse<BoxBody>;
+
+  type Error = actix_web::Error;
+
+  type Transform = NegotiateLocaleMiddleware<S>;
+
+  type InitError = ();
+
+  type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+  fn new_transform(&self, service: S) -> Self::Future {
+    ready(Ok(NegotiateLocaleMiddleware {
+      service: Rc::new(service),
+    }))
+  }
+}
+
+pub struct NegotiateLocaleMiddleware<S> {
+  service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for NegotiateLocaleMiddleware<S>
+where
+  S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+  B: MessageBody + 'static,
+{
+  type Response = ServiceResponse<BoxBody>;
+
+  type Error = actix_web::Error;
+
+  type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+  fn poll_ready(
+    &self,
+    ctx: &mut core::task::Context<'_>,
+  ) -> std::task::Poll<Result<(), Self::Error>> {
+    self.service.poll_ready(ctx)
+  }
+
+  fn call(&self, req: ServiceRequest) -> Self::Future {
+    let locale = req
+      .headers()
+      .get(ACCEPT_LANGUAGE)
+      .and_then(|h| h.to_str().ok())
+      .map(Locale::negotiate)
+      .unwrap_or_default();
+    let srv = Rc::clone(&self.service);
+
+    Box::pin(i18n::scope(locale, async move {
+      let mut res = srv.call(req).await?.map_into_boxed_body();
+      res
+        .headers_mut()
+        .insert(CONTENT_LANGUAGE, HeaderValue::from_static(locale.tag()));
+      Ok(res)
+    }))
+  }
+}
@@ -1 +1,13 @@
+pub mod access_log;
 pub mod auth;
+pub mod cors;
+pub mod credentials;
+pub mod docs_access;
+pub mod error_envelope;
+pub mod feature_gate;
+pub mod locale;
+pub mod payload_limit;
+pub mod rate_limit;
+pub mod request_id;
+pub mod security_headers;
+pub mod transaction;
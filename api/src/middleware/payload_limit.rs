@@ -0,0 +1,164 @@
+use std::rc::Rc;
+
+use actix_web::{
+  HttpMessage, HttpRequest,
+  body::{BoxBody, MessageBody},
+  dev::{Payload, Service, ServiceRequest, ServiceResponse, Transform},
+  error::JsonPayloadError,
+  mime, web,
+};
+use futures::{
+  StreamExt,
+  future::{LocalBoxFuture, Ready, ready},
+};
+
+use crate::{app_settings::PayloadLimitSetting, dto::base_res_dto::Status, error::StatusMessage};
+
+/// `web::Json` limit with errors in the standard `Status` body instead of plain text.
+pub fn json_config(setting: &PayloadLimitSetting) -> web::JsonConfig {
+  let limit = setting.json_max_bytes;
+  web::JsonConfig::default()
+    .limit(limit)
+    .error_handler(move |err, _req: &HttpRequest| {
+      let status = match &err {
+        JsonPayloadError::Overflow { .. } | JsonPayloadError::OverflowKnownLength { .. } => {
+          Status::payload_too_large(limit)
+        }
+        JsonPayloadError::ContentType => {
+          Status::bad_request("Content-Type must be application/json")
+        }
+        _ => Status::bad_request(format!("Invalid JSON body: {}", err)),
+      };
+      status.into()
+    })
+}
+
+pub fn payload_config(setting: &PayloadLimitSetting) -> web::PayloadConfig {
+  web::PayloadConfig::new(setting.payload_max_bytes)
+}
+
+// Deepest object/array nesting, brackets inside strings are skipped
+fn json_depth(body: &[u8]) -> usize {
+  let (mut depth, mut max_depth) = (0usize, 0usize);
+  let (mut in_string, mut escaped) = (false, false);
+  for &byte in body {
+    if in_string {
+      match byte {
+        _ if escaped => escaped = false,
+        b'\\' => escaped = true,
+        b'"' => in_string = false,
+        _ => {}
+      }
+      continue;
+    }
+    match byte {
+      b'"' => in_string = true,
+      b'{' | b'[' => {
+        depth += 1;
+        max_depth = max_depth.max(depth);
+      }
+      b'}' | b']' => depth = depth.saturating_sub(1),
+      _ => {}
+    }
+  }
+  max_depth
+}
+
+// Buffers JSON bodies (up to the JSON size limit) to refuse overly nested documents before
+// serde recurses into them, then hands the same bytes on to the `web::Json` extractor
+#[derive(Clone)]
+pub struct JsonDepthLimit {
+  max_bytes: usize,
+  max_depth: usize,
+}
+
+impl JsonDepthLimit {
+  pub fn new(setting: &PayloadLimitSetting) -> Self {
+    Self {
+      max_bytes: setting.json_max_bytes,
+      max_depth: setting.json_max_depth,
+    }
+  }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for JsonDepthLimit
+where
+  S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+  B: MessageBody + 'static,
+{
+  type Response = ServiceResponse<BoxBody>;
+
+  type Error = actix_web::Error;
+
+  type Transform = JsonDepthLimitMiddleware<S>;
+
+  type InitError = ();
+
+  type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+  fn new_transform(&self, service: S) -> Self::Future {
+    ready(Ok(JsonDepthLimitMiddleware {
+      service: Rc::new(service),
+      limit: self.clone(),
+    }))
+  }
+}
+
+pub struct JsonDepthLimitMiddleware<S> {
+  service: Rc<S>,
+  limit: JsonDepthLimit,
+}
+
+impl<S, B> Service<ServiceRequest> for JsonDepthLimitMiddleware<S>
+where
+  S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+  B: MessageBody + 'static,
+{
+  type Response = ServiceResponse<BoxBody>;
+
+  type Error = actix_web::Error;
+
+  type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+  fn poll_ready(
+    &self,
+    ctx: &mut core::task::Context<'_>,
+  ) -> std::task::Poll<Result<(), Self::Error>> {
+    self.service.poll_ready(ctx)
+  }
+
+  fn call(&self, mut req: ServiceRequest) -> Self::Future {
+    let srv = Rc::clone(&self.service);
+    let is_json = req
+      .mime_type()
+      .ok()
+      .flatten()
+      .is_some_and(|mime| mime.subtype() == mime::JSON || mime.suffix() == Some(mime::JSON));
+    if !is_json {
+      return Box::pin(async move { Ok(srv.call(req).await?.map_into_boxed_body()) });
+    }
+
+    let limit = self.limit.clone();
+    Box::pin(async move {
+      let mut payload = req.take_payload();
+      let mut body = web::BytesMut::new();
+      while let Some(chunk) = payload.next().await {
+        let chunk = chunk?;
+        if body.len() + chunk.len() > limit.max_bytes {
+          let res = Status::payload_too_large(limit.max_bytes).into_http_response();
+          return Ok(req.into_response(res));
+        }
+        body.extend_from_slice(&chunk);
+      }
+
+      if json_depth(&body) > limit.max_depth {
+        let res = Status::bad_request(StatusMessage::JsonTooDeep(limit.max_depth).to_str())
+          .into_http_response();
+        return Ok(req.into_response(res));
+      }
+
+      req.set_payload(Payload::from(body.freeze()));
+      Ok(srv.call(req).await?.map_into_boxed_body())
+    })
+  }
+}
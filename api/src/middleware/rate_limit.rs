@@ -0,0 +1,180 @@
+use std::{
+  collections::HashMap,
+  net::IpAddr,
+  rc::Rc,
+  sync::{
+    Arc, Mutex,
+    atomic::{AtomicU64, Ordering},
+  },
+  time::{Duration, Instant},
+};
+
+use actix_web::{
+  HttpRequest,
+  body::{BoxBody, MessageBody},
+  dev::{Service, ServiceRequest, ServiceResponse, Transform},
+  http::header::{HeaderValue, RETRY_AFTER},
+  web,
+};
+use futures::future::{LocalBoxFuture, Ready, ready};
+
+use crate::{app_settings::RateLimitSetting, app_state::AppState, dto::base_res_dto::Status};
+
+// Expired windows are dropped once this many clients are tracked
+const PRUNE_THRESHOLD: usize = 10_000;
+
+struct Window {
+  started: Instant,
+  count: u32,
+}
+
+// Answers 429 with Retry-After once a client IP used up its requests for the current window.
+// Limits are read from the runtime settings on every request so they can be reloaded.
+#[derive(Clone, Default)]
+pub struct RateLimit {
+  windows: Arc<Mutex<HashMap<IpAddr, Window>>>,
+  // Requests answered with 429 since startup
+  limited: Arc<AtomicU64>,
+}
+
+impl RateLimit {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Requests refused with 429 since startup, by every worker sharing this limiter.
+  pub fn limited_total(&self) -> u64 {
+    self.limited.load(Ordering::Relaxed)
+  }
+
+  // Counts the request, or returns the seconds until the client's window resets
+  fn check(&self, ip: IpAddr, setting: &RateLimitSetting) -> Result<(), u64> {
+    let now = Instant::now();
+    let window = Duration::from_secs(setting.window_secs);
+    let Ok(mut windows) = self.windows.lock() else {
+      return Ok(());
+    };
+    if windows.len() >= PRUNE_THRESHOLD {
+      windows.retain(|_, w| now.duration_since(w.started) < window);
+    }
+
+    let entry = windows.entry(ip).or_insert(Window {
+      started: now,
+      count: 0,
+    });
+    if now.duration_since(entry.started) >= window {
+      entry.started = now;
+      entry.count = 0;
+    }
+    if entry.count >= setting.requests_per_window {
+      let remaining = window.saturating_sub(now.duration_since(entry.started));
+      return Err(remaining.as_secs().max(1));
+    }
+    entry.count += 1;
+    Ok(())
+  }
+}
+
+/// Address the request came from. With `rate_limit.use_forwarded_for`, `X-Forwarded-For` is
+/// walked from the right while the hop it names is one of `trusted_proxies`, so entries a
+/// client wrote itself are never reached.
+pub fn client_ip(req: &HttpRequest, setting: &RateLimitSetting) -> Option<IpAddr> {
+  let peer = req.peer_addr().map(|addr| addr.ip())?;
+  if !setting.use_forwarded_for {
+    return Some(peer);
+  }
+  let trusted = |ip: &IpAddr| setting.trusted_proxies.iter().any(|net| net.contains(ip));
+  let forwarded: Vec<&str> = req
+    .headers()
+    .get_all("x-forwarded-for")
+    .filter_map(|h| h.to_str().ok())
+    .flat_map(|h| h.split(','))
+    .collect();
+
+  let mut ip = peer;
+  for entry in forwarded.iter().rev() {
+    if !trusted(&ip) {
+      break;
+    }
+    // A garbled entry ends the chain, the last proxy is the best known address
+    let Ok(hop) = entry.trim().parse() else {
+      break;
+    };
+    ip = hop;
+  }
+  Some(ip)
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RateLimit
+where
+  S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+  B: MessageBody + 'static,
+{
+  type Response = ServiceResponse<BoxBody>;
+
+  type Error = actix_web::Error;
+
+  type Transform = RateLimitMiddleware<S>;
+
+  type InitError = ();
+
+  type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+  fn new_transform(&self, service: S) -> Self::Future {
+    ready(Ok(RateLimitMiddleware {
+      service: Rc::new(service),
+      limiter: self.clone(),
+    }))
+  }
+}
+
+pub struct RateLimitMiddleware<S> {
+  service: Rc<S>,
+  limiter: RateLimit,
+}
+
+impl<S, B> Service<ServiceRequest> for RateLimitMiddleware<S>
+where
+  S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+  B: MessageBody + 'static,
+{
+  type Response = ServiceResponse<BoxBody>;
+
+  type Error = actix_web::Error;
+
+  type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+  fn poll_ready(
+    &self,
+    ctx: &mut core::task::Context<'_>,
+  ) -> std::task::Poll<Result<(), Self::Error>> {
+    self.service.poll_ready(ctx)
+  }
+
+  fn call(&self, req: ServiceRequest) -> Self::Future {
+    let limited = req
+      .app_data::<web::Data<AppState>>()
+      .map(|app_state| app_state.runtime())
+      .filter(|runtime| runtime.rate_limit.enabled)
+      .and_then(|runtime| {
+        let setting = &runtime.rate_limit;
+        let ip = client_ip(req.request(), setting)?;
+        if setting.allowlist.contains(&ip) {
+          return None;
+        }
+        self.limiter.check(ip, setting).err()
+      });
+
+    if let Some(retry_after) = limited {
+      self.limiter.limited.fetch_add(1, Ordering::Relaxed);
+      let mut res = Status::rate_limited(retry_after).into_http_response();
+      res
+        .headers_mut()
+        .insert(RETRY_AFTER, HeaderValue::from(retry_after));
+      return Box::pin(ready(Ok(req.into_response(res))));
+    }
+
+    let srv = Rc::clone(&self.service);
+    Box::pin(async move { Ok(srv.call(req).await?.map_into_boxed_body()) })
+  }
+}
@@ -0,0 +1,131 @@
+use std::rc::Rc;
+
+use actix_web::{
+  FromRequest, HttpMessage, HttpRequest,
+  body::{BoxBody, MessageBody},
+  dev::{Service, ServiceRequest, ServiceResponse, Transform},
+  http::header::{HeaderName, HeaderValue},
+};
+use futures::future::{LocalBoxFuture, Ready, ready};
+
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+// Ids supplied by callers are only trusted when they look like ids
+const MAX_REQUEST_ID_LENGTH: usize = 128;
+
+tokio::task_local! {
+  static CURRENT_REQUEST_ID: String;
+}
+
+/// Id of the request being served, also readable as an extractor in handlers.
+#[derive(Clone)]
+pub struct RequestId(pub String);
+
+impl RequestId {
+  pub fn of(req: &HttpRequest) -> Option<String> {
+    req.extensions().get::<RequestId>().map(|id| id.0.clone())
+  }
+}
+
+impl FromRequest for RequestId {
+  type Error = actix_web::Error;
+
+  type Future = Ready<Result<Self, Self::Error>>;
+
+  fn from_request(req: &HttpRequest, _: &mut actix_web::dev::Payload) -> Self::Future {
+    let id = RequestId::of(req).unwrap_or_default();
+    ready(Ok(RequestId(id)))
+  }
+}
+
+// The id of the request currently being handled, so error bodies can carry it
+pub fn current() -> Option<String> {
+  CURRENT_REQUEST_ID.try_with(|id| id.clone()).ok()
+}
+
+fn is_valid_request_id(id: &str) -> bool {
+  !id.is_empty()
+    && id.len() <= MAX_REQUEST_ID_LENGTH
+    && id
+      .chars()
+      .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':'))
+}
+
+// Reuses the caller's X-Request-Id (or generates one), stores it in the request extensions
+// and echoes it on the response
+pub struct AssignRequestId;
+
+impl<S, B> Transform<S, ServiceRequest> for AssignRequestId
+where
+  S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+  B: MessageBody + 'static,
+{
+  type Response = ServiceResponse<BoxBody>;
+
+  type Error = actix_web::Error;
+
+  type Transform = RequestIdMiddleware<S>;
+
+  type InitError = ();
+
+  type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+  fn new_transform(&self, service: S) -> Self::Future {
+    ready(Ok(RequestIdMiddleware {
+      service: Rc::new(service),
+    }))
+  }
+}
+
+pub struct RequestIdMiddleware<S> {
+  service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for RequestIdMiddleware<S>
+where
+  S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+  B: MessageBody + 'static,
+{
+  type Response = ServiceResponse<BoxBody>;
+
+  type Error = actix_web::Error;
+
+  type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+  fn poll_ready(
+    &self,
+    ctx: &mut core::task::Context<'_>,
+  ) -> std::task::Poll<Result<(), Self::Error>> {
+    self.service.poll_ready(ctx)
+  }
+
+  fn call(&self, req: ServiceRequest) -> Self::Future {
+    let request_id = req
+      .headers()
+      .get(REQUEST_ID_HEADER)
+      .and_then(|h| h.to_str().ok())
+      .filter(|id| is_valid_request_id(id))
+      .map(|id| id.to_string())
+      .unwrap_or_else(|| uuid::Uuid::new_v4().simple().to_string());
+
+    req
+      .extensions_mut()
+      .insert::<RequestId>(RequestId(request_id.clone()));
+    let http_req = req.request().clone();
+    let srv = Rc::clone(&self.service);
+
+    Box::pin(CURRENT_REQUEST_ID.scope(request_id.clone(), async move {
+      // Errors are rendered here so they get the header too
+      let mut res = match srv.call(req).await {
+        Ok(res) => res.map_into_boxed_body(),
+        Err(e) => ServiceResponse::new(http_req, e.error_response()),
+      };
+      if let Ok(value) = HeaderValue::from_str(&request_id) {
+        res
+          .headers_mut()
+          .insert(HeaderName::from_static("x-request-id"), value);
+      }
+      Ok(res)
+    }))
+  }
+}
@@ -0,0 +1,28 @@
+use actix_web::{
+  http::header::{
+    CONTENT_SECURITY_POLICY, REFERRER_POLICY, STRICT_TRANSPORT_SECURITY, X_CONTENT_TYPE_OPTIONS,
+    X_FRAME_OPTIONS,
+  },
+  middleware::DefaultHeaders,
+};
+
+use crate::app_settings::SecuritySetting;
+
+// Values are checked by `AppSetting::validate`, so adding them cannot panic here
+pub fn security_headers(setting: &SecuritySetting) -> DefaultHeaders {
+  let mut headers = DefaultHeaders::new()
+    .add((X_CONTENT_TYPE_OPTIONS, "nosniff"))
+    .add((X_FRAME_OPTIONS, setting.frame_options.as_str()))
+    .add((REFERRER_POLICY, setting.referrer_policy.as_str()));
+  if setting.hsts_enabled {
+    let mut hsts = format!("max-age={}", setting.hsts_max_age_secs);
+    if setting.hsts_include_subdomains {
+      hsts.push_str("; includeSubDomains");
+    }
+    headers = headers.add((STRICT_TRANSPORT_SECURITY, hsts));
+  }
+  if let Some(csp) = &setting.content_security_policy {
+    headers = headers.add((CONTENT_SECURITY_POLICY, csp.as_str()));
+  }
+  headers
+}
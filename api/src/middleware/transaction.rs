@@ -0,0 +1,226 @@
+use std::{
+  ops::{Deref, DerefMut},
+  rc::Rc,
+  sync::Arc,
+};
+
+use actix_web::{
+  FromRequest, HttpMessage, body,
+  dev::{Service, ServiceRequest, ServiceResponse, Transform},
+  error::{ErrorInternalServerError, ErrorServiceUnavailable},
+  web,
+};
+use anyhow::Result;
+use domner_tech_sql_client::CommandType;
+use futures::{
+  FutureExt,
+  future::{LocalBoxFuture, Ready, ready},
+};
+use tokio::sync::{Mutex, OwnedMutexGuard};
+
+use crate::{
+  app_state::AppState,
+  db::{DbConnection, PoolExhausted, SqlRepo},
+  dto::base_res_dto::Status,
+};
+
+// A pooled client with an open transaction, shared by every repo of one request.
+// Holds no client for repositories that don't run on SQL Server
+#[derive(Clone)]
+pub struct DbTransaction(Option<Arc<Mutex<DbConnection>>>);
+
+impl DbTransaction {
+  async fn begin(mut client: DbConnection) -> Result<Self> {
+    SqlRepo::execute_command_none_query(&mut client, "BEGIN TRANSACTION", &[], CommandType::Text)
+      .await?;
+    Ok(Self(Some(Arc::new(Mutex::new(client)))))
+  }
+
+  /// A transaction without a connection, commit and rollback do nothing. What
+  /// `RepositoryProvider::begin` answers for repositories that keep their rows elsewhere.
+  pub fn detached() -> Self {
+    Self(None)
+  }
+
+  async fn finish(&self, commit: bool) -> Result<()> {
+    let Some(client) = &self.0 else {
+      return Ok(());
+    };
+    let statement = if commit {
+      "IF @@TRANCOUNT > 0 COMMIT TRANSACTION"
+    } else {
+      "IF @@TRANCOUNT > 0 ROLLBACK TRANSACTION"
+    };
+    let mut client = client.lock().await;
+    SqlRepo::execute_command_none_query(&mut client, statement, &[], CommandType::Text).await?;
+    Ok(())
+  }
+
+  /// Opens a transaction outside of `TransactionScope`, for handlers that only sometimes need
+  /// one. The caller ends it with `commit` or `rollback`.
+  pub async fn start(app_state: &AppState) -> Result<Self> {
+    let client = app_state
+      .db_manager
+      .get_client(&app_state.config.database.sql_server.pool_name)
+      .await?;
+    Self::begin(client).await
+  }
+
+  pub async fn commit(&self) -> Result<()> {
+    self.finish(true).await
+  }
+
+  pub async fn rollback(&self) -> Result<()> {
+    self.finish(false).await
+  }
+
+  /// The transaction's client, `None` when it is detached.
+  pub async fn client(&self) -> Option<OwnedMutexGuard<DbConnection>> {
+    match &self.0 {
+      Some(client) => Some(client.clone().lock_owned().await),
+      None => None,
+    }
+  }
+}
+
+impl FromRequest for DbTransaction {
+  type Error = actix_web::Error;
+
+  type Future = Ready<Result<Self, Self::Error>>;
+
+  fn from_request(req: &actix_web::HttpRequest, _: &mut actix_web::dev::Payload) -> Self::Future {
+    let value = req.extensions().get::<DbTransaction>().cloned();
+    let result = match value {
+      Some(tx) => Ok(tx),
+      None => Err(ErrorInternalServerError(Status::server_error(
+        "Transaction scope is not configured for this route",
+      ))),
+    };
+    ready(result)
+  }
+}
+
+// Either a fresh client from the pool or the request's transaction client;
+// the pooled connection is boxed so both variants stay pointer-sized
+pub enum DbClient {
+  Pooled(Box<DbConnection>),
+  Transaction(OwnedMutexGuard<DbConnection>),
+}
+
+impl DbClient {
+  pub async fn acquire(app_state: &AppState, tx: Option<&DbTransaction>) -> Self {
+    // A detached transaction leaves the query on its own connection
+    if let Some(tx) = tx
+      && let Some(client) = tx.client().await
+    {
+      return DbClient::Transaction(client);
+    }
+    match app_state
+      .db_manager
+      .get_client(&app_state.config.database.sql_server.pool_name)
+      .await
+    {
+      Ok(client) => DbClient::Pooled(Box::new(client)),
+      Err(e) => panic!("Failed to get DB client: {}", e),
+    }
+  }
+}
+
+impl Deref for DbClient {
+  type Target = DbConnection;
+
+  fn deref(&self) -> &Self::Target {
+    match self {
+      DbClient::Pooled(client) => client,
+      DbClient::Transaction(guard) => guard,
+    }
+  }
+}
+
+impl DerefMut for DbClient {
+  fn deref_mut(&mut self) -> &mut Self::Target {
+    match self {
+      DbClient::Pooled(client) => client,
+      DbClient::Transaction(guard) => guard,
+    }
+  }
+}
+
+// Opt-in per route: commits when the handler answers 2xx, rolls back otherwise
+pub struct TransactionScope;
+
+impl<S> Transform<S, ServiceRequest> for TransactionScope
+where
+  S: Service<ServiceRequest, Response = ServiceResponse<body::BoxBody>, Error = actix_web::Error>
+    + 'static,
+{
+  type Response = ServiceResponse<body::BoxBody>;
+
+  type Error = actix_web::Error;
+
+  type Transform = TransactionMiddleware<S>;
+
+  type InitError = ();
+
+  type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+  fn new_transform(&self, service: S) -> Self::Future {
+    ready(Ok(TransactionMiddleware {
+      service: Rc::new(service),
+    }))
+  }
+}
+
+pub struct TransactionMiddleware<S> {
+  service: Rc<S>,
+}
+
+impl<S> Service<ServiceRequest> for TransactionMiddleware<S>
+where
+  S: Service<ServiceRequest, Response = ServiceResponse<body::BoxBody>, Error = actix_web::Error>
+    + 'static,
+{
+  type Response = ServiceResponse<body::BoxBody>;
+
+  type Error = actix_web::Error;
+
+  type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+  fn poll_ready(
+    &self,
+    ctx: &mut core::task::Context<'_>,
+  ) -> std::task::Poll<Result<(), Self::Error>> {
+    self.service.poll_ready(ctx)
+  }
+
+  fn call(&self, req: ServiceRequest) -> Self::Future {
+    let app_state = req.app_data::<web::Data<AppState>>().unwrap().clone();
+    let srv = Rc::clone(&self.service);
+
+    async move {
+      let tx = app_state
+        .repositories
+        .begin(&app_state)
+        .await
+        .map_err(|e| match e.downcast_ref::<PoolExhausted>() {
+          Some(exhausted) => ErrorServiceUnavailable(Status::pool_exhausted(exhausted.to_string())),
+          None => ErrorInternalServerError(Status::server_error(e.to_string())),
+        })?;
+
+      req.extensions_mut().insert::<DbTransaction>(tx.clone());
+      let result = srv.call(req).await;
+
+      let commit = matches!(&result, Ok(res) if res.status().is_success());
+      if let Err(e) = tx.finish(commit).await {
+        eprintln!("Failed to finish transaction: {}", e);
+        if commit {
+          return Err(ErrorInternalServerError(Status::server_error(
+            e.to_string(),
+          )));
+        }
+      }
+      result
+    }
+    .boxed_local()
+  }
+}
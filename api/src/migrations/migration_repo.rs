@@ -0,0 +1,115 @@
+use crate::{
+  app_state::AppState,
+  db::{DbConnection, RowExt, SqlRepo},
+  migrations::{Migration, split_batches},
+};
+
+use anyhow::Result;
+use domner_tech_sql_client::CommandType;
+
+pub struct MigrationRepo<'a> {
+  pub app_state: &'a AppState,
+  pub pool_name: &'a str,
+}
+
+impl<'a> MigrationRepo<'a> {
+  pub fn new(app_state: &'a AppState, pool_name: &'a str) -> Self {
+    Self {
+      app_state,
+      pool_name,
+    }
+  }
+
+  async fn get_client(&self) -> DbConnection {
+    match self.app_state.db_manager.get_client(self.pool_name).await {
+      Ok(client) => client,
+      Err(e) => panic!("Failed to get DB client: {}", e),
+    }
+  }
+
+  pub async fn ensure_history_table(&mut self) -> Result<()> {
+    let mut client_pool = self.get_client().await;
+
+    SqlRepo::execute_command_none_query(
+      &mut client_pool,
+      "IF OBJECT_ID(N'dbo.schema_migrations', N'U') IS NULL
+       CREATE TABLE dbo.schema_migrations (
+         version INT NOT NULL PRIMARY KEY,
+         name NVARCHAR(255) NOT NULL,
+         applied_at DATETIME2 NOT NULL DEFAULT SYSUTCDATETIME()
+       )",
+      &[],
+      CommandType::Text,
+    )
+    .await?;
+    Ok(())
+  }
+
+  pub async fn get_applied_versions(&mut self) -> Result<Vec<i32>> {
+    let mut client_pool = self.get_client().await;
+
+    let versions = SqlRepo::execute_command_query(
+      &mut client_pool,
+      "SELECT version FROM dbo.schema_migrations ORDER BY version",
+      &[],
+      CommandType::Text,
+      |row| row.column("version"),
+    )
+    .await?;
+    Ok(versions)
+  }
+
+  // Runs the script and records it in one transaction; returns false when another
+  // instance applied the same version first
+  pub async fn apply(&mut self, migration: &Migration) -> Result<bool> {
+    let mut client_pool = self.get_client().await;
+    // Scripts may rebuild large tables, don't cut them off halfway
+    client_pool.set_query_timeout(None);
+
+    SqlRepo::execute_command_none_query(
+      &mut client_pool,
+      "BEGIN TRANSACTION;
+       EXEC sp_getapplock @Resource = 'schema_migrations', @LockMode = 'Exclusive', @LockOwner = 'Transaction'",
+      &[],
+      CommandType::Text,
+    )
+    .await?;
+
+    let result = Self::apply_locked(&mut client_pool, migration).await;
+    let statement = match result {
+      Ok(true) => "IF @@TRANCOUNT > 0 COMMIT TRANSACTION",
+      _ => "IF @@TRANCOUNT > 0 ROLLBACK TRANSACTION",
+    };
+    SqlRepo::execute_command_none_query(&mut client_pool, statement, &[], CommandType::Text)
+      .await?;
+    result
+  }
+
+  async fn apply_locked(client_pool: &mut DbConnection, migration: &Migration) -> Result<bool> {
+    let already_applied = SqlRepo::execute_command_single_query(
+      client_pool,
+      "SELECT version FROM dbo.schema_migrations WHERE version = @P1",
+      &[&migration.version],
+      CommandType::Text,
+      |row| row.column::<i32>("version"),
+    )
+    .await?;
+    if already_applied.is_some() {
+      return Ok(false);
+    }
+
+    for batch in split_batches(migration.sql) {
+      SqlRepo::execute_command_none_query(client_pool, &batch, &[], CommandType::Text)
+        .await
+        .map_err(|e| anyhow::anyhow!("Migration {} failed: {}", migration.version, e))?;
+    }
+    SqlRepo::execute_command_none_query(
+      client_pool,
+      "INSERT INTO dbo.schema_migrations (version, name) VALUES (@P1, @P2)",
+      &[&migration.version, &migration.name],
+      CommandType::Text,
+    )
+    .await?;
+    Ok(true)
+  }
+}
@@ -0,0 +1,142 @@
+pub mod migration_repo;
+
+use anyhow::Result;
+
+use crate::{app_state::AppState, migrations::migration_repo::MigrationRepo};
+
+// Versioned scripts, applied in order; batches are separated by `GO` lines
+pub struct Migration {
+  pub version: i32,
+  pub name: &'static str,
+  pub sql: &'static str,
+}
+
+pub const MIGRATIONS: &[Migration] = &[
+  Migration {
+    version: 1,
+    name: "baseline",
+    sql: include_str!("../../migrations/sql/0001_baseline.sql"),
+  },
+  Migration {
+    version: 2,
+    name: "purge_expired_tokens",
+    sql: include_str!("../../migrations/sql/0002_purge_expired_tokens.sql"),
+  },
+  Migration {
+    version: 3,
+    name: "soft_delete_roles",
+    sql: include_str!("../../migrations/sql/0003_soft_delete_roles.sql"),
+  },
+  Migration {
+    version: 4,
+    name: "login_history",
+    sql: include_str!("../../migrations/sql/0004_login_history.sql"),
+  },
+  Migration {
+    version: 5,
+    name: "password_history",
+    sql: include_str!("../../migrations/sql/0005_password_history.sql"),
+  },
+  Migration {
+    version: 6,
+    name: "user_report",
+    sql: include_str!("../../migrations/sql/0006_user_report.sql"),
+  },
+  Migration {
+    version: 7,
+    name: "return_created_ids",
+    sql: include_str!("../../migrations/sql/0007_return_created_ids.sql"),
+  },
+  Migration {
+    version: 8,
+    name: "login_codes",
+    sql: include_str!("../../migrations/sql/0008_login_codes.sql"),
+  },
+  Migration {
+    version: 9,
+    name: "login_code_throttle",
+    sql: include_str!("../../migrations/sql/0009_login_code_throttle.sql"),
+  },
+  Migration {
+    version: 10,
+    name: "user_report_sets",
+    sql: include_str!("../../migrations/sql/0010_user_report_sets.sql"),
+  },
+  Migration {
+    version: 11,
+    name: "password_reset",
+    sql: include_str!("../../migrations/sql/0011_password_reset.sql"),
+  },
+  Migration {
+    version: 12,
+    name: "consume_login_code",
+    sql: include_str!("../../migrations/sql/0012_consume_login_code.sql"),
+  },
+];
+
+// Split a script into the batches SQL Server executes separately
+pub fn split_batches(sql: &str) -> Vec<String> {
+  let mut batches = Vec::new();
+  let mut current = String::new();
+  for line in sql.lines() {
+    if line.trim().eq_ignore_ascii_case("GO") {
+      if !current.trim().is_empty() {
+        batches.push(current.trim().to_string());
+      }
+      current.clear();
+    } else {
+      current.push_str(line);
+      current.push('\n');
+    }
+  }
+  if !current.trim().is_empty() {
+    batches.push(current.trim().to_string());
+  }
+  batches
+}
+
+// Apply every migration not yet recorded in schema_migrations to every configured database,
+// each keeps its own history; returns the applied versions
+pub async fn run_pending(state: &AppState) -> Result<Vec<i32>> {
+  let mut newly_applied = Vec::new();
+  // A read-only database is a secondary of one that is migrated
+  for setting in state
+    .config
+    .database
+    .all_connections()
+    .filter(|setting| !setting.read_only)
+  {
+    let mut repo = MigrationRepo::new(state, &setting.pool_name);
+    repo.ensure_history_table().await?;
+    let applied = repo.get_applied_versions().await?;
+
+    for migration in MIGRATIONS.iter().filter(|m| !applied.contains(&m.version)) {
+      if repo.apply(migration).await? {
+        println!(
+          "Applied migration {} {} to pool '{}'",
+          migration.version, migration.name, setting.pool_name
+        );
+        newly_applied.push(migration.version);
+      }
+    }
+  }
+  Ok(newly_applied)
+}
+
+// `migrate` CLI subcommand, returns the process exit code
+pub async fn run(state: &AppState) -> i32 {
+  match run_pending(state).await {
+    Ok(applied) if applied.is_empty() => {
+      println!("Database is up to date");
+      0
+    }
+    Ok(applied) => {
+      println!("Applied {} migration(s)", applied.len());
+      0
+    }
+    Err(e) => {
+      eprintln!("Migration failed: {}", e);
+      1
+    }
+  }
+}
@@ -0,0 +1,39 @@
+use anyhow::Result;
+use futures::{FutureExt, future::BoxFuture};
+
+use crate::{
+  notifications::{Notification, NotificationChannel},
+  utils::mailer::{Mailer, OutgoingEmail},
+};
+
+// Hands notifications to the mailer's queue, delivery and retries happen there
+pub struct EmailChannel {
+  mailer: Mailer,
+}
+
+impl EmailChannel {
+  pub fn new(mailer: Mailer) -> Self {
+    Self { mailer }
+  }
+
+  async fn queue_email(&self, notification: &Notification) -> Result<()> {
+    let to = notification
+      .email
+      .as_ref()
+      .ok_or_else(|| anyhow::anyhow!("User {} has no email", notification.user_id))?;
+
+    self.mailer.enqueue(OutgoingEmail {
+      to: to.clone(),
+      subject: notification.subject.clone(),
+      body: notification.body.clone(),
+      html: notification.html.clone(),
+    });
+    Ok(())
+  }
+}
+
+impl NotificationChannel for EmailChannel {
+  fn send<'a>(&'a self, notification: &'a Notification) -> BoxFuture<'a, Result<()>> {
+    self.queue_email(notification).boxed()
+  }
+}
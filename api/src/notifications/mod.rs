@@ -0,0 +1,144 @@
+pub mod email_channel;
+pub mod sms_channel;
+pub mod webhook_channel;
+
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::Result;
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  app_settings::NotificationSetting,
+  i18n::{self, Locale},
+  notifications::{
+    email_channel::EmailChannel, sms_channel::SmsChannel, webhook_channel::WebhookChannel,
+  },
+  utils::mailer::{EmailTemplate, Mailer},
+};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationKind {
+  PasswordReset,
+  SecurityAlert,
+  EmailVerification,
+  /// One-time codes of the passwordless login
+  LoginCode,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum ChannelKind {
+  Email,
+  Webhook,
+  Sms,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct Notification {
+  pub kind: NotificationKind,
+  pub user_id: i32,
+  pub email: Option<String>,
+  pub phone: Option<String>,
+  pub subject: String,
+  pub body: String,
+  /// HTML version of `body`, only used by the email channel
+  #[serde(skip)]
+  pub html: Option<String>,
+}
+
+impl Notification {
+  /// `template` rendered in `locale`, addressed to `email`.
+  pub fn email(
+    kind: NotificationKind,
+    user_id: i32,
+    email: impl Into<String>,
+    template: &EmailTemplate,
+    locale: Locale,
+  ) -> Result<Self> {
+    let rendered = template.render(locale)?;
+    Ok(Self {
+      kind,
+      user_id,
+      email: Some(email.into()),
+      phone: None,
+      subject: rendered.subject,
+      body: rendered.text,
+      html: Some(rendered.html),
+    })
+  }
+}
+
+/// An outbound delivery mechanism for notifications.
+pub trait NotificationChannel: Send + Sync {
+  fn send<'a>(&'a self, notification: &'a Notification) -> BoxFuture<'a, Result<()>>;
+}
+
+// Routes each notification kind to the channel configured for it
+#[derive(Clone, Default)]
+pub struct Notifier {
+  channels: HashMap<ChannelKind, Arc<dyn NotificationChannel>>,
+  routes: HashMap<NotificationKind, ChannelKind>,
+}
+
+impl Notifier {
+  // Email always goes through the mailer, which only logs in `mailer.dev_mode`
+  pub fn from_setting(setting: &NotificationSetting, mailer: &Mailer) -> Self {
+    let mut channels: HashMap<ChannelKind, Arc<dyn NotificationChannel>> = HashMap::new();
+    channels.insert(
+      ChannelKind::Email,
+      Arc::new(EmailChannel::new(mailer.clone())),
+    );
+    if let Some(webhook) = &setting.webhook {
+      channels.insert(
+        ChannelKind::Webhook,
+        Arc::new(WebhookChannel::new(webhook.clone())),
+      );
+    }
+    if let Some(sms) = &setting.sms {
+      channels.insert(ChannelKind::Sms, Arc::new(SmsChannel::new(sms.clone())));
+    }
+
+    Self {
+      channels,
+      routes: setting.routes.clone(),
+    }
+  }
+
+  pub async fn send(&self, notification: &Notification) -> Result<()> {
+    let channel_kind = self
+      .routes
+      .get(&notification.kind)
+      .ok_or_else(|| anyhow::anyhow!("No channel configured for {:?}", notification.kind))?;
+    let channel = self
+      .channels
+      .get(channel_kind)
+      .ok_or_else(|| anyhow::anyhow!("Channel {:?} is not configured", channel_kind))?;
+    channel.send(notification).await
+  }
+
+  /// Renders `template` in the language of the current request and sends it in the background.
+  pub fn send_email_detached(
+    &self,
+    kind: NotificationKind,
+    user_id: i32,
+    email: impl Into<String>,
+    template: EmailTemplate,
+  ) {
+    match Notification::email(kind, user_id, email, &template, i18n::current()) {
+      Ok(notification) => self.send_detached(notification),
+      Err(e) => eprintln!("Failed to render the {} email: {}", template.name(), e),
+    }
+  }
+
+  // Send in the background, a failed notification must never fail the request
+  pub fn send_detached(&self, notification: Notification) {
+    let notifier = self.clone();
+    actix_web::rt::spawn(async move {
+      if let Err(e) = notifier.send(&notification).await {
+        eprintln!("Failed to send {:?} notification: {}", notification.kind, e);
+      }
+    });
+  }
+}
@@ -0,0 +1,55 @@
+use anyhow::Result;
+use futures::{FutureExt, future::BoxFuture};
+
+use crate::{
+  app_settings::SmsChannelSetting,
+  notifications::{Notification, NotificationChannel},
+};
+
+// Twilio-style HTTP API: form POST to {api_url}/Accounts/{account_sid}/Messages.json
+pub struct SmsChannel {
+  setting: SmsChannelSetting,
+  client: reqwest::Client,
+}
+
+impl SmsChannel {
+  pub fn new(setting: SmsChannelSetting) -> Self {
+    Self {
+      setting,
+      client: reqwest::Client::new(),
+    }
+  }
+
+  async fn post(&self, notification: &Notification) -> Result<()> {
+    let to = notification
+      .phone
+      .as_ref()
+      .ok_or_else(|| anyhow::anyhow!("User {} has no phone number", notification.user_id))?;
+
+    let url = format!(
+      "{}/Accounts/{}/Messages.json",
+      self.setting.api_url.trim_end_matches('/'),
+      self.setting.account_sid
+    );
+    let body = format!("{}\n{}", notification.subject, notification.body);
+    self
+      .client
+      .post(url)
+      .basic_auth(&self.setting.account_sid, Some(&self.setting.auth_token))
+      .form(&[
+        ("To", to.as_str()),
+        ("From", self.setting.from_number.as_str()),
+        ("Body", body.as_str()),
+      ])
+      .send()
+      .await?
+      .error_for_status()?;
+    Ok(())
+  }
+}
+
+impl NotificationChannel for SmsChannel {
+  fn send<'a>(&'a self, notification: &'a Notification) -> BoxFuture<'a, Result<()>> {
+    self.post(notification).boxed()
+  }
+}
@@ -0,0 +1,36 @@
+use anyhow::Result;
+use futures::{FutureExt, future::BoxFuture};
+
+use crate::{
+  app_settings::WebhookChannelSetting,
+  notifications::{Notification, NotificationChannel},
+};
+
+pub struct WebhookChannel {
+  setting: WebhookChannelSetting,
+  client: reqwest::Client,
+}
+
+impl WebhookChannel {
+  pub fn new(setting: WebhookChannelSetting) -> Self {
+    Self {
+      setting,
+      client: reqwest::Client::new(),
+    }
+  }
+
+  async fn post(&self, notification: &Notification) -> Result<()> {
+    let mut request = self.client.post(&self.setting.url).json(notification);
+    if let Some(token) = &self.setting.bearer_token {
+      request = request.bearer_auth(token);
+    }
+    request.send().await?.error_for_status()?;
+    Ok(())
+  }
+}
+
+impl NotificationChannel for WebhookChannel {
+  fn send<'a>(&'a self, notification: &'a Notification) -> BoxFuture<'a, Result<()>> {
+    self.post(notification).boxed()
+  }
+}
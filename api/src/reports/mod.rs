@@ -0,0 +1,72 @@
+pub mod pdf;
+pub mod xlsx;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportFormat {
+  Pdf,
+  Xlsx,
+}
+
+impl ReportFormat {
+  pub fn content_type(&self) -> &'static str {
+    match self {
+      ReportFormat::Pdf => "application/pdf",
+      ReportFormat::Xlsx => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+    }
+  }
+
+  pub fn extension(&self) -> &'static str {
+    match self {
+      ReportFormat::Pdf => "pdf",
+      ReportFormat::Xlsx => "xlsx",
+    }
+  }
+}
+
+#[derive(Clone, Debug)]
+pub enum ReportCell {
+  Text(String),
+  Number(f64),
+  DateTime(DateTime<Utc>),
+}
+
+impl ReportCell {
+  /// How the cell reads where there are no typed cells, e.g. in the PDF.
+  pub fn display(&self) -> String {
+    match self {
+      ReportCell::Text(text) => text.clone(),
+      ReportCell::Number(number) => number.to_string(),
+      ReportCell::DateTime(at) => at.format("%Y-%m-%d %H:%M").to_string(),
+    }
+  }
+}
+
+/// A titled table, a worksheet in XLSX and a heading with its table in PDF.
+#[derive(Clone, Debug)]
+pub struct ReportSection {
+  pub title: String,
+  pub columns: Vec<String>,
+  pub rows: Vec<Vec<ReportCell>>,
+}
+
+/// A report as data only, so every format lays out the same content.
+#[derive(Clone, Debug)]
+pub struct Report {
+  pub title: String,
+  pub generated_at: DateTime<Utc>,
+  pub sections: Vec<ReportSection>,
+}
+
+/// Renders `report` to the bytes of a `format` file. CPU bound, call it off the async workers.
+pub fn render(report: &Report, format: ReportFormat) -> Result<Vec<u8>> {
+  match format {
+    ReportFormat::Pdf => pdf::render(report),
+    ReportFormat::Xlsx => xlsx::render(report),
+  }
+}
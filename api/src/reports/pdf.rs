@@ -0,0 +1,103 @@
+use anyhow::Result;
+use printpdf::{
+  BuiltinFont, IndirectFontRef, Mm, PdfDocument, PdfDocumentReference, PdfLayerReference,
+};
+
+use crate::reports::Report;
+
+const PAGE_WIDTH: f32 = 210.0;
+const PAGE_HEIGHT: f32 = 297.0;
+const MARGIN: f32 = 15.0;
+const LINE_HEIGHT: f32 = 5.5;
+const FONT_SIZE: f32 = 9.0;
+// Average Helvetica glyph width at FONT_SIZE, used to cut text that would overflow its column
+const CHAR_WIDTH: f32 = 1.75;
+
+// Writes lines top to bottom, starting a new A4 page when one is full
+struct Writer {
+  doc: PdfDocumentReference,
+  layer: PdfLayerReference,
+  regular: IndirectFontRef,
+  bold: IndirectFontRef,
+  y: f32,
+}
+
+impl Writer {
+  fn new(title: &str) -> Result<Self> {
+    let (doc, page, layer) = PdfDocument::new(title, Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Layer 1");
+    let layer = doc.get_page(page).get_layer(layer);
+    let regular = doc.add_builtin_font(BuiltinFont::Helvetica)?;
+    let bold = doc.add_builtin_font(BuiltinFont::HelveticaBold)?;
+    Ok(Self {
+      doc,
+      layer,
+      regular,
+      bold,
+      y: PAGE_HEIGHT - MARGIN,
+    })
+  }
+
+  fn next_line(&mut self, height: f32) {
+    self.y -= height;
+    if self.y < MARGIN {
+      let (page, layer) = self
+        .doc
+        .add_page(Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Layer 1");
+      self.layer = self.doc.get_page(page).get_layer(layer);
+      self.y = PAGE_HEIGHT - MARGIN - height;
+    }
+  }
+
+  fn text(&mut self, text: &str, size: f32, bold: bool) {
+    let font = if bold { &self.bold } else { &self.regular };
+    self
+      .layer
+      .use_text(text, size, Mm(MARGIN), Mm(self.y), font);
+  }
+
+  fn row(&mut self, cells: &[String], bold: bool) {
+    let width = (PAGE_WIDTH - 2.0 * MARGIN) / cells.len().max(1) as f32;
+    let max_chars = ((width - 2.0) / CHAR_WIDTH).max(1.0) as usize;
+    let font = if bold { &self.bold } else { &self.regular };
+    for (i, cell) in cells.iter().enumerate() {
+      let text = if cell.chars().count() > max_chars {
+        format!("{}…", cell.chars().take(max_chars - 1).collect::<String>())
+      } else {
+        cell.clone()
+      };
+      let x = MARGIN + i as f32 * width;
+      self
+        .layer
+        .use_text(text, FONT_SIZE, Mm(x), Mm(self.y), font);
+    }
+  }
+}
+
+/// A4 pages with each section as a heading and an evenly spaced table. The built-in fonts
+/// only cover Windows-1252, other characters are left out.
+pub fn render(report: &Report) -> Result<Vec<u8>> {
+  let mut writer = Writer::new(&report.title)?;
+  writer.text(&report.title, 16.0, true);
+  writer.next_line(LINE_HEIGHT * 1.5);
+  writer.text(
+    &format!(
+      "Generated {} UTC",
+      report.generated_at.format("%Y-%m-%d %H:%M")
+    ),
+    FONT_SIZE,
+    false,
+  );
+
+  for section in &report.sections {
+    writer.next_line(LINE_HEIGHT * 2.5);
+    writer.text(&section.title, 12.0, true);
+    writer.next_line(LINE_HEIGHT * 1.5);
+    writer.row(&section.columns, true);
+    for cells in &section.rows {
+      writer.next_line(LINE_HEIGHT);
+      let cells: Vec<String> = cells.iter().map(|cell| cell.display()).collect();
+      writer.row(&cells, false);
+    }
+  }
+  Ok(writer.doc.save_to_bytes()?)
+}
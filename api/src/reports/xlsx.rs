@@ -0,0 +1,48 @@
+use anyhow::Result;
+use rust_xlsxwriter::{ExcelDateTime, Format, Workbook};
+
+use crate::reports::{Report, ReportCell};
+
+// Excel refuses longer sheet names and a few characters in them
+const MAX_SHEET_NAME: usize = 31;
+
+fn sheet_name(title: &str) -> String {
+  title
+    .chars()
+    .filter(|c| !matches!(c, '[' | ']' | ':' | '*' | '?' | '/' | '\\'))
+    .take(MAX_SHEET_NAME)
+    .collect()
+}
+
+/// One worksheet per section with typed cells, so numbers and dates can be sorted and summed.
+pub fn render(report: &Report) -> Result<Vec<u8>> {
+  let mut workbook = Workbook::new();
+  let bold = Format::new().set_bold();
+  let date = Format::new().set_num_format("yyyy-mm-dd hh:mm");
+
+  for section in &report.sections {
+    let sheet = workbook.add_worksheet();
+    sheet.set_name(sheet_name(&section.title))?;
+    for (col, column) in section.columns.iter().enumerate() {
+      sheet.write_string_with_format(0, col as u16, column, &bold)?;
+    }
+    for (row, cells) in section.rows.iter().enumerate() {
+      let row = row as u32 + 1;
+      for (col, cell) in cells.iter().enumerate() {
+        let col = col as u16;
+        match cell {
+          ReportCell::Text(text) => sheet.write_string(row, col, text)?,
+          ReportCell::Number(number) => sheet.write_number(row, col, *number)?,
+          ReportCell::DateTime(at) => sheet.write_with_format(
+            row,
+            col,
+            &ExcelDateTime::from_timestamp(at.timestamp())?,
+            &date,
+          )?,
+        };
+      }
+    }
+    sheet.autofit();
+  }
+  Ok(workbook.save_to_buffer()?)
+}
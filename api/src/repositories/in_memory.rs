@@ -0,0 +1,873 @@
+use std::{
+  cmp::Ordering,
+  collections::{BTreeMap, BTreeSet, HashMap},
+  sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use futures::future::LocalBoxFuture;
+
+use crate::{
+  app_state::AppState,
+  dto::sort::SortDir,
+  features::{
+    permissions::{permissions_entity::PermissionEntity, permissions_repo::PermissionRepository},
+    roles::{
+      roles_dto::RoleSortBy,
+      roles_entity::{RoleEntity, UserRolesEntity},
+      roles_repo::RoleRepository,
+    },
+    users::{
+      user_dto::{UserDto, UserRegisterReqDto, UserSortBy},
+      user_entity::{EmailChange, LoginCode, User, UserRole},
+      user_repo::UserRepository,
+    },
+  },
+  middleware::transaction::DbTransaction,
+  repositories::RepositoryProvider,
+  utils::password_hashing::PasswordHashing,
+};
+
+/// Users, roles and permissions kept in memory, for running handlers without SQL Server.
+/// Mirrors what the stored procs do; clones share the rows.
+///
+/// Transactions are detached, so a handler that fails halfway keeps what it wrote.
+#[derive(Clone, Default)]
+pub struct InMemoryRepositories {
+  store: Arc<Mutex<Store>>,
+}
+
+#[derive(Default)]
+struct Store {
+  last_id: i32,
+  users: BTreeMap<i32, User>,
+  // Newest first
+  password_history: HashMap<i32, Vec<String>>,
+  email_changes: HashMap<i32, EmailChange>,
+  login_codes: HashMap<i32, PendingLoginCode>,
+  // user_id -> (token_hash, expires_at)
+  password_resets: HashMap<i32, (String, DateTime<Utc>)>,
+  roles: BTreeMap<i32, RoleEntity>,
+  // (user_id, role_id)
+  user_roles: BTreeSet<(i32, i32)>,
+  permissions: BTreeMap<i32, PermissionEntity>,
+  // (role_id, permission_id)
+  role_permissions: BTreeSet<(i32, i32)>,
+}
+
+impl Store {
+  // Ids are unique across tables, which no caller minds
+  fn next_id(&mut self) -> i32 {
+    self.last_id += 1;
+    self.last_id
+  }
+}
+
+struct PendingLoginCode {
+  code: LoginCode,
+  issued_count: i32,
+  window_started_at: DateTime<Utc>,
+}
+
+// Nothing awaits while holding the lock, and a panicking test shouldn't poison the next one
+fn lock(store: &Mutex<Store>) -> MutexGuard<'_, Store> {
+  store.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+impl RepositoryProvider for InMemoryRepositories {
+  fn users<'a>(
+    &self,
+    app_state: &'a AppState,
+    _tx: Option<&DbTransaction>,
+  ) -> Box<dyn UserRepository + 'a> {
+    Box::new(InMemoryUserRepo {
+      app_state,
+      store: self.store.clone(),
+    })
+  }
+
+  fn roles<'a>(
+    &self,
+    app_state: &'a AppState,
+    _tx: Option<&DbTransaction>,
+  ) -> Box<dyn RoleRepository + 'a> {
+    Box::new(InMemoryRoleRepo {
+      app_state,
+      store: self.store.clone(),
+    })
+  }
+
+  fn permissions<'a>(&self, _app_state: &'a AppState) -> Box<dyn PermissionRepository + 'a> {
+    Box::new(InMemoryPermissionRepo {
+      store: self.store.clone(),
+    })
+  }
+
+  fn begin<'a>(&'a self, _app_state: &'a AppState) -> LocalBoxFuture<'a, Result<DbTransaction>> {
+    Box::pin(async { Ok(DbTransaction::detached()) })
+  }
+}
+
+struct InMemoryUserRepo<'a> {
+  app_state: &'a AppState,
+  store: Arc<Mutex<Store>>,
+}
+
+// Order of a user listing, ties broken by id like `SortDir::order_by`
+fn compare_users(a: &User, b: &User, sort_by: UserSortBy) -> Ordering {
+  let by_column = match sort_by {
+    UserSortBy::Id => Ordering::Equal,
+    UserSortBy::UserName => a.user_name.cmp(&b.user_name),
+    UserSortBy::Name => a.name.cmp(&b.name),
+    UserSortBy::Email => a.email.cmp(&b.email),
+    UserSortBy::Role => a.role.to_str().cmp(b.role.to_str()),
+    UserSortBy::IsActive => a.is_active.cmp(&b.is_active),
+    UserSortBy::CreatedAt => a.created_at.cmp(&b.created_at),
+  };
+  by_column.then(a.id.cmp(&b.id))
+}
+
+impl<'a> UserRepository for InMemoryUserRepo<'a> {
+  fn create<'b>(&'b mut self, user: &'b UserRegisterReqDto) -> LocalBoxFuture<'b, Result<i32>> {
+    Box::pin(async move {
+      let hashed_password = PasswordHashing::hash_password(&user.password)
+        .map_err(|e| anyhow::anyhow!("Failed to hash password: {}", e))?;
+      let mut store = lock(&self.store);
+      if store
+        .users
+        .values()
+        .any(|u| u.user_name.eq_ignore_ascii_case(&user.user_name))
+      {
+        return Err(anyhow::anyhow!("Username already exists"));
+      }
+      let id = store.next_id();
+      let now = Utc::now();
+      store.users.insert(
+        id,
+        User {
+          id,
+          user_name: user.user_name.clone(),
+          name: user.name.clone(),
+          password: hashed_password,
+          email: user.email.clone(),
+          role: UserRole::from_name(&user.role),
+          is_active: true,
+          created_at: now,
+          updated_at: now,
+          last_login_at: None,
+        },
+      );
+      Ok(id)
+    })
+  }
+
+  fn get_by_id<'b>(&'b mut self, id: i32) -> LocalBoxFuture<'b, Result<Option<User>>> {
+    Box::pin(async move { Ok(lock(&self.store).users.get(&id).cloned()) })
+  }
+
+  fn get_password_hash<'b>(&'b mut self, id: i32) -> LocalBoxFuture<'b, Result<Option<String>>> {
+    Box::pin(async move {
+      Ok(
+        lock(&self.store)
+          .users
+          .get(&id)
+          .map(|user| user.password.clone()),
+      )
+    })
+  }
+
+  fn get_by_username<'b>(
+    &'b mut self,
+    username: &'b str,
+  ) -> LocalBoxFuture<'b, Result<Option<User>>> {
+    Box::pin(async move {
+      Ok(
+        lock(&self.store)
+          .users
+          .values()
+          .find(|user| user.user_name.eq_ignore_ascii_case(username))
+          .cloned(),
+      )
+    })
+  }
+
+  fn get_users<'b>(&'b mut self) -> LocalBoxFuture<'b, Result<Vec<User>>> {
+    Box::pin(async move { Ok(lock(&self.store).users.values().cloned().collect()) })
+  }
+
+  fn get_users_paged<'b>(
+    &'b mut self,
+    page: i32,
+    page_size: i32,
+    sort_by: UserSortBy,
+    sort_dir: SortDir,
+  ) -> LocalBoxFuture<'b, Result<(Vec<User>, i32)>> {
+    Box::pin(async move {
+      let mut users: Vec<User> = lock(&self.store).users.values().cloned().collect();
+      users.sort_by(|a, b| match sort_dir {
+        SortDir::Asc => compare_users(a, b, sort_by),
+        SortDir::Desc => compare_users(b, a, sort_by),
+      });
+      let total = users.len() as i32;
+      let skip = ((page - 1).max(0) * page_size.max(0)) as usize;
+      let page = users
+        .into_iter()
+        .skip(skip)
+        .take(page_size.max(0) as usize)
+        .collect();
+      Ok((page, total))
+    })
+  }
+
+  fn set_active<'b>(&'b mut self, id: i32, is_active: bool) -> LocalBoxFuture<'b, Result<u64>> {
+    Box::pin(async move {
+      let updated = match lock(&self.store).users.get_mut(&id) {
+        Some(user) => {
+          user.is_active = is_active;
+          user.updated_at = Utc::now();
+          1
+        }
+        None => 0,
+      };
+      self.app_state.invalidate_user(id);
+      Ok(updated)
+    })
+  }
+
+  // Matched by user name like [dbo].[update_user]
+  fn update_user<'b>(&'b mut self, user: &'b UserDto) -> LocalBoxFuture<'b, Result<u64>> {
+    Box::pin(async move {
+      let mut store = lock(&self.store);
+      let Some(stored) = store
+        .users
+        .values_mut()
+        .find(|u| u.user_name.eq_ignore_ascii_case(&user.user_name))
+      else {
+        return Ok(0);
+      };
+      stored.name = user.name.clone();
+      stored.email = user.email.clone();
+      stored.role = user.role.clone();
+      stored.updated_at = Utc::now();
+      drop(store);
+      self.app_state.invalidate_user(user.id);
+      Ok(1)
+    })
+  }
+
+  fn create_email_change<'b>(
+    &'b mut self,
+    user_id: i32,
+    new_email: &'b str,
+    token: &'b str,
+    expiration_minutes: i32,
+  ) -> LocalBoxFuture<'b, Result<u64>> {
+    Box::pin(async move {
+      lock(&self.store).email_changes.insert(
+        user_id,
+        EmailChange {
+          user_id,
+          new_email: new_email.to_string(),
+          token: token.to_string(),
+          expires_at: Utc::now() + Duration::minutes(expiration_minutes as i64),
+        },
+      );
+      Ok(1)
+    })
+  }
+
+  fn get_email_change_by_token<'b>(
+    &'b mut self,
+    token: &'b str,
+  ) -> LocalBoxFuture<'b, Result<Option<EmailChange>>> {
+    Box::pin(async move {
+      Ok(
+        lock(&self.store)
+          .email_changes
+          .values()
+          .find(|change| change.token == token)
+          .cloned(),
+      )
+    })
+  }
+
+  fn delete_email_change<'b>(&'b mut self, user_id: i32) -> LocalBoxFuture<'b, Result<u64>> {
+    Box::pin(async move {
+      let removed = lock(&self.store).email_changes.remove(&user_id);
+      Ok(removed.map_or(0, |_| 1))
+    })
+  }
+
+  fn update_email<'b>(
+    &'b mut self,
+    user_id: i32,
+    email: &'b str,
+  ) -> LocalBoxFuture<'b, Result<u64>> {
+    Box::pin(async move {
+      let updated = match lock(&self.store).users.get_mut(&user_id) {
+        Some(user) => {
+          user.email = email.to_string();
+          user.updated_at = Utc::now();
+          1
+        }
+        None => 0,
+      };
+      self.app_state.invalidate_user(user_id);
+      Ok(updated)
+    })
+  }
+
+  fn get_password_history<'b>(
+    &'b mut self,
+    user_id: i32,
+    count: i32,
+  ) -> LocalBoxFuture<'b, Result<Vec<String>>> {
+    Box::pin(async move {
+      let store = lock(&self.store);
+      let history = store.password_history.get(&user_id);
+      Ok(
+        history
+          .into_iter()
+          .flatten()
+          .take(count.max(0) as usize)
+          .cloned()
+          .collect(),
+      )
+    })
+  }
+
+  fn update_password<'b>(
+    &'b mut self,
+    user_id: i32,
+    password_hash: &'b str,
+    keep: i32,
+  ) -> LocalBoxFuture<'b, Result<u64>> {
+    Box::pin(async move {
+      let mut store = lock(&self.store);
+      let Some(user) = store.users.get_mut(&user_id) else {
+        return Ok(0);
+      };
+      let old = std::mem::replace(&mut user.password, password_hash.to_string());
+      user.updated_at = Utc::now();
+      let history = store.password_history.entry(user_id).or_default();
+      if keep > 0 {
+        history.insert(0, old);
+      }
+      history.truncate(keep.max(0) as usize);
+      drop(store);
+      self.app_state.invalidate_user(user_id);
+      Ok(1)
+    })
+  }
+
+  fn create_login_code<'b>(
+    &'b mut self,
+    user_id: i32,
+    code_hash: &'b str,
+    expiration_minutes: i32,
+    max_codes: i32,
+    window_minutes: i32,
+  ) -> LocalBoxFuture<'b, Result<bool>> {
+    Box::pin(async move {
+      let now = Utc::now();
+      let expires_at = now + Duration::minutes(expiration_minutes as i64);
+      let mut store = lock(&self.store);
+      // Same as [dbo].[create_login_code]: attempts and the issued count last for the window
+      let window_start = now - Duration::minutes(window_minutes as i64);
+      if store
+        .login_codes
+        .get(&user_id)
+        .is_some_and(|pending| pending.window_started_at <= window_start)
+      {
+        store.login_codes.remove(&user_id);
+      }
+      if let Some(pending) = store.login_codes.get_mut(&user_id) {
+        if pending.issued_count >= max_codes {
+          return Ok(false);
+        }
+        pending.code.code_hash = code_hash.to_string();
+        pending.code.expires_at = expires_at;
+        pending.issued_count += 1;
+        return Ok(true);
+      }
+      store.login_codes.insert(
+        user_id,
+        PendingLoginCode {
+          code: LoginCode {
+            user_id,
+            code_hash: code_hash.to_string(),
+            attempts: 0,
+            expires_at,
+          },
+          issued_count: 1,
+          window_started_at: now,
+        },
+      );
+      Ok(true)
+    })
+  }
+
+  fn get_login_code<'b>(
+    &'b mut self,
+    user_id: i32,
+  ) -> LocalBoxFuture<'b, Result<Option<LoginCode>>> {
+    Box::pin(async move {
+      Ok(
+        lock(&self.store)
+          .login_codes
+          .get(&user_id)
+          .map(|pending| pending.code.clone()),
+      )
+    })
+  }
+
+  fn increment_login_code_attempts<'b>(
+    &'b mut self,
+    user_id: i32,
+  ) -> LocalBoxFuture<'b, Result<u64>> {
+    Box::pin(async move {
+      match lock(&self.store).login_codes.get_mut(&user_id) {
+        Some(pending) => {
+          pending.code.attempts += 1;
+          Ok(1)
+        }
+        None => Ok(0),
+      }
+    })
+  }
+
+  fn consume_login_code<'b>(
+    &'b mut self,
+    user_id: i32,
+    code_hash: &'b str,
+    max_attempts: i32,
+  ) -> LocalBoxFuture<'b, Result<bool>> {
+    Box::pin(async move {
+      let mut store = lock(&self.store);
+      let usable = store.login_codes.get(&user_id).is_some_and(|pending| {
+        pending.code.code_hash == code_hash
+          && pending.code.expires_at >= Utc::now()
+          && pending.code.attempts < max_attempts
+      });
+      if usable {
+        store.login_codes.remove(&user_id);
+      }
+      Ok(usable)
+    })
+  }
+
+  fn create_password_reset<'b>(
+    &'b mut self,
+    user_id: i32,
+    token_hash: &'b str,
+    expiration_minutes: i32,
+  ) -> LocalBoxFuture<'b, Result<u64>> {
+    Box::pin(async move {
+      let expires_at = Utc::now() + Duration::minutes(expiration_minutes as i64);
+      lock(&self.store)
+        .password_resets
+        .insert(user_id, (token_hash.to_string(), expires_at));
+      Ok(1)
+    })
+  }
+
+  fn consume_password_reset<'b>(
+    &'b mut self,
+    token_hash: &'b str,
+  ) -> LocalBoxFuture<'b, Result<Option<i32>>> {
+    Box::pin(async move {
+      let now = Utc::now();
+      let mut store = lock(&self.store);
+      let user_id = store
+        .password_resets
+        .iter()
+        .find(|(_, (hash, expires_at))| hash == token_hash && *expires_at > now)
+        .map(|(user_id, _)| *user_id);
+      if let Some(user_id) = user_id {
+        store.password_resets.remove(&user_id);
+      }
+      Ok(user_id)
+    })
+  }
+}
+
+struct InMemoryRoleRepo<'a> {
+  app_state: &'a AppState,
+  store: Arc<Mutex<Store>>,
+}
+
+fn compare_roles(a: &RoleEntity, b: &RoleEntity, sort_by: RoleSortBy) -> Ordering {
+  let by_column = match sort_by {
+    RoleSortBy::Id => Ordering::Equal,
+    RoleSortBy::Name => a.name.cmp(&b.name),
+    RoleSortBy::CreatedAt => a.created_at.cmp(&b.created_at),
+  };
+  by_column.then(a.id.cmp(&b.id))
+}
+
+impl<'a> InMemoryRoleRepo<'a> {
+  fn invalidate(&self, user_ids: &[i32]) {
+    for user_id in user_ids {
+      self.app_state.invalidate_user(*user_id);
+    }
+  }
+}
+
+impl<'a> RoleRepository for InMemoryRoleRepo<'a> {
+  fn create_role<'b>(&'b mut self, role: &'b RoleEntity) -> LocalBoxFuture<'b, Result<i32>> {
+    Box::pin(async move {
+      let mut store = lock(&self.store);
+      let id = store.next_id();
+      let now = Utc::now();
+      store.roles.insert(
+        id,
+        RoleEntity {
+          id,
+          name: role.name.clone(),
+          description: role.description.clone().filter(|d| !d.is_empty()),
+          created_at: now,
+          updated_at: now,
+        },
+      );
+      drop(store);
+      self.app_state.invalidate_access_cache();
+      Ok(id)
+    })
+  }
+
+  fn update_role<'b>(&'b mut self, role: &'b RoleEntity) -> LocalBoxFuture<'b, Result<u64>> {
+    Box::pin(async move {
+      let updated = match lock(&self.store).roles.get_mut(&role.id) {
+        Some(stored) => {
+          stored.name = role.name.clone();
+          stored.description = role.description.clone().filter(|d| !d.is_empty());
+          stored.updated_at = Utc::now();
+          1
+        }
+        None => 0,
+      };
+      self.app_state.invalidate_access_cache();
+      Ok(updated)
+    })
+  }
+
+  fn get_by_name<'b>(
+    &'b mut self,
+    name: &'b str,
+  ) -> LocalBoxFuture<'b, Result<Option<RoleEntity>>> {
+    Box::pin(async move {
+      Ok(
+        lock(&self.store)
+          .roles
+          .values()
+          .find(|role| role.name.eq_ignore_ascii_case(name))
+          .cloned(),
+      )
+    })
+  }
+
+  fn get_by_id<'b>(&'b mut self, id: i32) -> LocalBoxFuture<'b, Result<Option<RoleEntity>>> {
+    Box::pin(async move { Ok(lock(&self.store).roles.get(&id).cloned()) })
+  }
+
+  fn get_roles<'b>(
+    &'b mut self,
+    sort_by: RoleSortBy,
+    sort_dir: SortDir,
+  ) -> LocalBoxFuture<'b, Result<Vec<RoleEntity>>> {
+    Box::pin(async move {
+      let mut roles: Vec<RoleEntity> = lock(&self.store).roles.values().cloned().collect();
+      roles.sort_by(|a, b| match sort_dir {
+        SortDir::Asc => compare_roles(a, b, sort_by),
+        SortDir::Desc => compare_roles(b, a, sort_by),
+      });
+      Ok(roles)
+    })
+  }
+
+  fn get_user_roles<'b>(
+    &'b mut self,
+    user_id: i32,
+  ) -> LocalBoxFuture<'b, Result<Vec<UserRolesEntity>>> {
+    Box::pin(async move {
+      let store = lock(&self.store);
+      Ok(
+        store
+          .roles
+          .values()
+          .map(|role| UserRolesEntity {
+            role_id: role.id,
+            role_name: role.name.clone(),
+            is_in_role: store.user_roles.contains(&(user_id, role.id)),
+          })
+          .collect(),
+      )
+    })
+  }
+
+  fn get_user_role_names<'b>(
+    &'b mut self,
+    user_id: i32,
+  ) -> LocalBoxFuture<'b, Result<Vec<String>>> {
+    Box::pin(async move {
+      let user_roles = self.get_user_roles(user_id).await?;
+      Ok(
+        user_roles
+          .into_iter()
+          .filter(|ur| ur.is_in_role)
+          .map(|ur| ur.role_name)
+          .collect(),
+      )
+    })
+  }
+
+  fn is_user_role_exist<'b>(&'b mut self, user_id: i32, role_id: i32) -> LocalBoxFuture<'b, bool> {
+    Box::pin(async move { lock(&self.store).user_roles.contains(&(user_id, role_id)) })
+  }
+
+  fn get_role_members_among<'b>(
+    &'b mut self,
+    role_id: i32,
+    user_ids: &'b [i32],
+  ) -> LocalBoxFuture<'b, Result<Vec<i32>>> {
+    Box::pin(async move {
+      let store = lock(&self.store);
+      Ok(
+        user_ids
+          .iter()
+          .copied()
+          .filter(|user_id| store.user_roles.contains(&(*user_id, role_id)))
+          .collect(),
+      )
+    })
+  }
+
+  fn assign_user_role<'b>(
+    &'b mut self,
+    user_id: i32,
+    role_id: i32,
+  ) -> LocalBoxFuture<'b, Result<u64>> {
+    Box::pin(async move {
+      // The table's unique key refuses a second row the same way
+      if !lock(&self.store).user_roles.insert((user_id, role_id)) {
+        return Err(anyhow::anyhow!(
+          "User {} already has role {}",
+          user_id,
+          role_id
+        ));
+      }
+      self.invalidate(&[user_id]);
+      Ok(1)
+    })
+  }
+
+  fn assign_users_to_role<'b>(
+    &'b mut self,
+    role_id: i32,
+    user_ids: &'b [i32],
+  ) -> LocalBoxFuture<'b, Result<u64>> {
+    Box::pin(async move {
+      let mut store = lock(&self.store);
+      if let Some(user_id) = user_ids
+        .iter()
+        .find(|user_id| store.user_roles.contains(&(**user_id, role_id)))
+      {
+        return Err(anyhow::anyhow!(
+          "User {} already has role {}",
+          user_id,
+          role_id
+        ));
+      }
+      for user_id in user_ids {
+        store.user_roles.insert((*user_id, role_id));
+      }
+      drop(store);
+      self.invalidate(user_ids);
+      Ok(user_ids.len() as u64)
+    })
+  }
+
+  fn count_users_in_role<'b>(&'b mut self, role_id: i32) -> LocalBoxFuture<'b, Result<i32>> {
+    Box::pin(async move {
+      let store = lock(&self.store);
+      Ok(
+        store
+          .user_roles
+          .iter()
+          .filter(|(_, id)| *id == role_id)
+          .count() as i32,
+      )
+    })
+  }
+
+  fn delete_user_roles_by_role<'b>(&'b mut self, role_id: i32) -> LocalBoxFuture<'b, Result<u64>> {
+    Box::pin(async move {
+      let mut store = lock(&self.store);
+      let before = store.user_roles.len();
+      store.user_roles.retain(|(_, id)| *id != role_id);
+      let removed = (before - store.user_roles.len()) as u64;
+      drop(store);
+      self.app_state.invalidate_access_cache();
+      Ok(removed)
+    })
+  }
+
+  // The soft-deleted row is never read again, so it goes right away with its grants
+  fn delete_role<'b>(&'b mut self, id: i32) -> LocalBoxFuture<'b, Result<u64>> {
+    Box::pin(async move {
+      let mut store = lock(&self.store);
+      if store.roles.remove(&id).is_none() {
+        return Ok(0);
+      }
+      store.user_roles.retain(|(_, role_id)| *role_id != id);
+      store.role_permissions.retain(|(role_id, _)| *role_id != id);
+      drop(store);
+      self.app_state.invalidate_access_cache();
+      Ok(1)
+    })
+  }
+}
+
+struct InMemoryPermissionRepo {
+  store: Arc<Mutex<Store>>,
+}
+
+impl PermissionRepository for InMemoryPermissionRepo {
+  fn create_permission<'b>(
+    &'b mut self,
+    permission: &'b PermissionEntity,
+  ) -> LocalBoxFuture<'b, Result<u64>> {
+    Box::pin(async move {
+      let mut store = lock(&self.store);
+      let id = store.next_id();
+      let now = Utc::now();
+      store.permissions.insert(
+        id,
+        PermissionEntity {
+          id,
+          name: permission.name.clone(),
+          description: permission.description.clone().filter(|d| !d.is_empty()),
+          created_at: now,
+          updated_at: now,
+        },
+      );
+      Ok(1)
+    })
+  }
+
+  fn update_permission<'b>(
+    &'b mut self,
+    permission: &'b PermissionEntity,
+  ) -> LocalBoxFuture<'b, Result<u64>> {
+    Box::pin(async move {
+      match lock(&self.store).permissions.get_mut(&permission.id) {
+        Some(stored) => {
+          stored.name = permission.name.clone();
+          stored.description = permission.description.clone().filter(|d| !d.is_empty());
+          stored.updated_at = Utc::now();
+          Ok(1)
+        }
+        None => Ok(0),
+      }
+    })
+  }
+
+  fn delete_permission<'b>(&'b mut self, id: i32) -> LocalBoxFuture<'b, Result<u64>> {
+    Box::pin(async move {
+      let mut store = lock(&self.store);
+      store
+        .role_permissions
+        .retain(|(_, permission_id)| *permission_id != id);
+      Ok(store.permissions.remove(&id).map_or(0, |_| 1))
+    })
+  }
+
+  fn get_by_id<'b>(&'b mut self, id: i32) -> LocalBoxFuture<'b, Result<Option<PermissionEntity>>> {
+    Box::pin(async move { Ok(lock(&self.store).permissions.get(&id).cloned()) })
+  }
+
+  fn get_by_name<'b>(
+    &'b mut self,
+    name: &'b str,
+  ) -> LocalBoxFuture<'b, Result<Option<PermissionEntity>>> {
+    Box::pin(async move {
+      Ok(
+        lock(&self.store)
+          .permissions
+          .values()
+          .find(|permission| permission.name.eq_ignore_ascii_case(name))
+          .cloned(),
+      )
+    })
+  }
+
+  fn get_permissions<'b>(&'b mut self) -> LocalBoxFuture<'b, Result<Vec<PermissionEntity>>> {
+    Box::pin(async move {
+      let mut permissions: Vec<PermissionEntity> =
+        lock(&self.store).permissions.values().cloned().collect();
+      permissions.sort_by(|a, b| a.name.cmp(&b.name));
+      Ok(permissions)
+    })
+  }
+
+  fn get_role_permissions<'b>(
+    &'b mut self,
+    role_id: i32,
+  ) -> LocalBoxFuture<'b, Result<Vec<PermissionEntity>>> {
+    Box::pin(async move {
+      let store = lock(&self.store);
+      let mut permissions: Vec<PermissionEntity> = store
+        .role_permissions
+        .iter()
+        .filter(|(id, _)| *id == role_id)
+        .filter_map(|(_, permission_id)| store.permissions.get(permission_id).cloned())
+        .collect();
+      permissions.sort_by(|a, b| a.name.cmp(&b.name));
+      Ok(permissions)
+    })
+  }
+
+  fn get_user_permissions<'b>(
+    &'b mut self,
+    user_id: i32,
+  ) -> LocalBoxFuture<'b, Result<Vec<String>>> {
+    Box::pin(async move {
+      let store = lock(&self.store);
+      let names: BTreeSet<String> = store
+        .role_permissions
+        .iter()
+        .filter(|(role_id, _)| store.user_roles.contains(&(user_id, *role_id)))
+        .filter_map(|(_, permission_id)| store.permissions.get(permission_id))
+        .map(|permission| permission.name.clone())
+        .collect();
+      Ok(names.into_iter().collect())
+    })
+  }
+
+  fn assign_role_permission<'b>(
+    &'b mut self,
+    role_id: i32,
+    permission_id: i32,
+  ) -> LocalBoxFuture<'b, Result<u64>> {
+    Box::pin(async move {
+      let inserted = lock(&self.store)
+        .role_permissions
+        .insert((role_id, permission_id));
+      Ok(inserted as u64)
+    })
+  }
+
+  fn revoke_role_permission<'b>(
+    &'b mut self,
+    role_id: i32,
+    permission_id: i32,
+  ) -> LocalBoxFuture<'b, Result<u64>> {
+    Box::pin(async move {
+      let removed = lock(&self.store)
+        .role_permissions
+        .remove(&(role_id, permission_id));
+      Ok(removed as u64)
+    })
+  }
+}
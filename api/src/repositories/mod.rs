@@ -0,0 +1,69 @@
+pub mod in_memory;
+
+use anyhow::Result;
+use futures::future::LocalBoxFuture;
+
+use crate::{
+  app_state::AppState,
+  features::{
+    permissions::permissions_repo::{PermissionRepo, PermissionRepository},
+    roles::roles_repo::{RoleRepo, RoleRepository},
+    users::user_repo::{UserRepo, UserRepository},
+  },
+  middleware::transaction::DbTransaction,
+};
+
+/// Builds the repositories handlers work with. The SQL Server implementation is the
+/// default; tests register `InMemoryRepositories` to run handlers without a database.
+pub trait RepositoryProvider: Send + Sync {
+  fn users<'a>(
+    &self,
+    app_state: &'a AppState,
+    tx: Option<&DbTransaction>,
+  ) -> Box<dyn UserRepository + 'a>;
+
+  fn roles<'a>(
+    &self,
+    app_state: &'a AppState,
+    tx: Option<&DbTransaction>,
+  ) -> Box<dyn RoleRepository + 'a>;
+
+  fn permissions<'a>(&self, app_state: &'a AppState) -> Box<dyn PermissionRepository + 'a>;
+
+  /// Opens the transaction `TransactionScope` runs a request in.
+  fn begin<'a>(&'a self, app_state: &'a AppState) -> LocalBoxFuture<'a, Result<DbTransaction>>;
+}
+
+pub struct SqlServerRepositories;
+
+impl RepositoryProvider for SqlServerRepositories {
+  fn users<'a>(
+    &self,
+    app_state: &'a AppState,
+    tx: Option<&DbTransaction>,
+  ) -> Box<dyn UserRepository + 'a> {
+    match tx {
+      Some(tx) => Box::new(UserRepo::with_transaction(app_state, tx)),
+      None => Box::new(UserRepo::new(app_state)),
+    }
+  }
+
+  fn roles<'a>(
+    &self,
+    app_state: &'a AppState,
+    tx: Option<&DbTransaction>,
+  ) -> Box<dyn RoleRepository + 'a> {
+    match tx {
+      Some(tx) => Box::new(RoleRepo::with_transaction(app_state, tx)),
+      None => Box::new(RoleRepo::new(app_state)),
+    }
+  }
+
+  fn permissions<'a>(&self, app_state: &'a AppState) -> Box<dyn PermissionRepository + 'a> {
+    Box::new(PermissionRepo::new(app_state))
+  }
+
+  fn begin<'a>(&'a self, app_state: &'a AppState) -> LocalBoxFuture<'a, Result<DbTransaction>> {
+    Box::pin(DbTransaction::start(app_state))
+  }
+}
@@ -0,0 +1,84 @@
+use crate::{
+  app_state::AppState,
+  db::{DbConnection, SqlRepo, soft_delete::SOFT_DELETE_TABLES},
+};
+
+use anyhow::Result;
+use domner_tech_sql_client::CommandType;
+
+pub struct MaintenanceRepo<'a> {
+  pub app_state: &'a AppState,
+}
+
+impl<'a> MaintenanceRepo<'a> {
+  pub fn new(app_state: &'a AppState) -> Self {
+    Self { app_state }
+  }
+
+  // Fails instead of panicking, a background task has no request to answer with a 500
+  async fn get_client(&self) -> Result<DbConnection> {
+    self
+      .app_state
+      .db_manager
+      .get_client(&self.app_state.config.database.sql_server.pool_name)
+      .await
+  }
+
+  // Email change tokens past their expiry can never be confirmed, returns how many were removed
+  pub async fn purge_expired_email_changes(&mut self) -> Result<u64> {
+    let mut client_pool = self.get_client().await?;
+
+    let result = SqlRepo::execute_command_none_query(
+      &mut client_pool,
+      "[dbo].[purge_expired_email_change_requests]",
+      &[],
+      CommandType::StoreProcedure,
+    )
+    .await?;
+    Ok(result)
+  }
+
+  // Password reset tokens past their expiry, returns how many were removed
+  pub async fn purge_expired_password_resets(&mut self) -> Result<u64> {
+    let mut client_pool = self.get_client().await?;
+
+    let result = SqlRepo::execute_command_none_query(
+      &mut client_pool,
+      "[dbo].[purge_expired_password_reset_requests]",
+      &[],
+      CommandType::StoreProcedure,
+    )
+    .await?;
+    Ok(result)
+  }
+
+  // Login codes past their expiry whose throttling window is over, returns how many were removed
+  pub async fn purge_expired_login_codes(&mut self, window_minutes: i32) -> Result<u64> {
+    let mut client_pool = self.get_client().await?;
+
+    let result = SqlRepo::execute_command_none_query(
+      &mut client_pool,
+      "[dbo].[purge_expired_login_codes]",
+      &[&window_minutes],
+      CommandType::StoreProcedure,
+    )
+    .await?;
+    Ok(result)
+  }
+
+  // Rows soft deleted before the retention window, returns how many were removed per table
+  pub async fn purge_soft_deleted(
+    &mut self,
+    retention_days: u32,
+  ) -> Result<Vec<(&'static str, u64)>> {
+    let mut client_pool = self.get_client().await?;
+    let retention_days = i32::try_from(retention_days).unwrap_or(i32::MAX);
+
+    let mut purged = Vec::with_capacity(SOFT_DELETE_TABLES.len());
+    for table in SOFT_DELETE_TABLES {
+      let total = SqlRepo::purge_deleted(&mut client_pool, table, retention_days).await?;
+      purged.push((table.name, total));
+    }
+    Ok(purged)
+  }
+}
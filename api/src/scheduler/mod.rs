@@ -0,0 +1,292 @@
+pub mod maintenance_repo;
+
+use std::{
+  sync::{Arc, Mutex},
+  time::{Duration, Instant},
+};
+
+use actix_web::web;
+use anyhow::Result;
+use chrono::{DateTime, NaiveTime, TimeDelta, Utc};
+
+use crate::{
+  app_settings::{ScheduledTaskSetting, SchedulerSetting},
+  app_state::AppState,
+  features::admin::admin_repo::AdminRepo,
+  scheduler::maintenance_repo::MaintenanceRepo,
+};
+
+// How often a task waiting for startup checks whether the pools are up
+const READY_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Task {
+  PurgeExpiredTokens,
+  RecycleIdleConnections,
+  DailyStats,
+  PurgeSoftDeleted,
+}
+
+impl Task {
+  pub const ALL: [Task; 4] = [
+    Task::PurgeExpiredTokens,
+    Task::RecycleIdleConnections,
+    Task::DailyStats,
+    Task::PurgeSoftDeleted,
+  ];
+
+  pub fn name(&self) -> &'static str {
+    match self {
+      Task::PurgeExpiredTokens => "purge_expired_tokens",
+      Task::RecycleIdleConnections => "recycle_idle_connections",
+      Task::DailyStats => "daily_stats",
+      Task::PurgeSoftDeleted => "purge_soft_deleted",
+    }
+  }
+
+  fn setting<'a>(&self, setting: &'a SchedulerSetting) -> &'a ScheduledTaskSetting {
+    match self {
+      Task::PurgeExpiredTokens => &setting.purge_expired_tokens,
+      Task::RecycleIdleConnections => &setting.recycle_idle_connections,
+      Task::DailyStats => &setting.daily_stats,
+      Task::PurgeSoftDeleted => &setting.purge_soft_deleted,
+    }
+  }
+
+  // Returns a one line summary of what was done, kept as the task's last outcome
+  async fn run(&self, state: &AppState) -> Result<String> {
+    match self {
+      Task::PurgeExpiredTokens => {
+        let mut repo = MaintenanceRepo::new(state);
+        let purged = repo.purge_expired_email_changes().await?;
+        let password_resets = repo.purge_expired_password_resets().await?;
+        let login_codes = repo
+          .purge_expired_login_codes(state.config.otp_login.window_minutes)
+          .await?;
+        Ok(format!(
+          "Purged {} expired email change token(s), {} password reset token(s) and {} login code(s)",
+          purged, password_resets, login_codes
+        ))
+      }
+      Task::RecycleIdleConnections => {
+        let recycled: Vec<String> = state
+          .db_manager
+          .recycle_stale()
+          .await
+          .into_iter()
+          .map(|(name, reason)| format!("{} ({})", name, reason))
+          .collect();
+        let evicted = match &state.config.database.tenants {
+          Some(tenants) => state
+            .db_manager
+            .evict_idle_tenant_pools(Duration::from_secs(tenants.idle_timeout_secs)),
+          None => Vec::new(),
+        };
+        let mut summary = if recycled.is_empty() {
+          "No idle or expired pool to recycle".to_string()
+        } else {
+          format!("Recycled pool(s): {}", recycled.join(", "))
+        };
+        if !evicted.is_empty() {
+          summary.push_str(&format!(
+            ", closed idle tenant pool(s): {}",
+            evicted.join(", ")
+          ));
+        }
+        Ok(summary)
+      }
+      Task::DailyStats => {
+        let by_role = AdminRepo::new(state).get_user_role_counts().await?;
+        let total: i32 = by_role.iter().map(|r| r.total).sum();
+        let roles: Vec<String> = by_role
+          .iter()
+          .map(|r| format!("{} {}", r.role, r.total))
+          .collect();
+        let pools: Vec<String> = state
+          .db_manager
+          .stats()
+          .iter()
+          .map(|p| {
+            format!(
+              "pool '{}' {} checkouts, {} timeouts, {} reconnects",
+              p.pool_name, p.checkouts, p.timeouts, p.reconnects
+            )
+          })
+          .collect();
+        Ok(format!(
+          "{} users ({}); {}",
+          total,
+          roles.join(", "),
+          pools.join("; ")
+        ))
+      }
+      Task::PurgeSoftDeleted => {
+        let retention_days = state.config.scheduler.soft_delete_retention_days;
+        let purged = MaintenanceRepo::new(state)
+          .purge_soft_deleted(retention_days)
+          .await?;
+        let tables: Vec<String> = purged
+          .iter()
+          .map(|(table, total)| format!("{} {}", table, total))
+          .collect();
+        Ok(format!(
+          "Purged rows deleted over {} day(s) ago: {}",
+          retention_days,
+          tables.join(", ")
+        ))
+      }
+    }
+  }
+}
+
+/// When a task runs, parsed from its `ScheduledTaskSetting`.
+#[derive(Clone, Copy, Debug)]
+pub enum Schedule {
+  Every(Duration),
+  DailyAt(NaiveTime),
+}
+
+impl Schedule {
+  // `AppSetting::validate` already rejected a malformed `at`
+  fn from_setting(setting: &ScheduledTaskSetting) -> Schedule {
+    match setting
+      .at
+      .as_deref()
+      .and_then(|at| NaiveTime::parse_from_str(at, "%H:%M").ok())
+    {
+      Some(at) => Schedule::DailyAt(at),
+      None => Schedule::Every(Duration::from_secs(setting.interval_secs.max(1))),
+    }
+  }
+
+  pub fn next_after(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+    match self {
+      Schedule::Every(interval) => now + TimeDelta::from_std(*interval).unwrap_or(TimeDelta::MAX),
+      Schedule::DailyAt(at) => {
+        let today = now.date_naive().and_time(*at).and_utc();
+        if today > now {
+          today
+        } else {
+          today + TimeDelta::days(1)
+        }
+      }
+    }
+  }
+
+  pub fn describe(&self) -> String {
+    match self {
+      Schedule::Every(interval) => format!("every {}s", interval.as_secs()),
+      Schedule::DailyAt(at) => format!("daily at {} UTC", at.format("%H:%M")),
+    }
+  }
+}
+
+/// Last-run bookkeeping of one task, what `GET /admin/scheduler` reports.
+#[derive(Clone)]
+pub struct TaskReport {
+  pub task: Task,
+  pub enabled: bool,
+  pub schedule: Schedule,
+  pub next_run_at: Option<DateTime<Utc>>,
+  pub last_started_at: Option<DateTime<Utc>>,
+  pub last_duration_ms: Option<u128>,
+  pub last_outcome: Option<String>,
+  pub last_error: Option<String>,
+  pub runs: u64,
+  pub failures: u64,
+}
+
+/// Runs the maintenance tasks enabled in `SchedulerSetting`, one background loop per task.
+#[derive(Clone)]
+pub struct Scheduler {
+  reports: Arc<Mutex<Vec<TaskReport>>>,
+}
+
+impl Scheduler {
+  pub fn from_setting(setting: &SchedulerSetting) -> Self {
+    let reports = Task::ALL
+      .iter()
+      .map(|task| {
+        let task_setting = task.setting(setting);
+        TaskReport {
+          task: *task,
+          enabled: setting.enabled && task_setting.enabled,
+          schedule: Schedule::from_setting(task_setting),
+          next_run_at: None,
+          last_started_at: None,
+          last_duration_ms: None,
+          last_outcome: None,
+          last_error: None,
+          runs: 0,
+          failures: 0,
+        }
+      })
+      .collect();
+    Self {
+      reports: Arc::new(Mutex::new(reports)),
+    }
+  }
+
+  pub fn reports(&self) -> Vec<TaskReport> {
+    self
+      .reports
+      .lock()
+      .map(|reports| reports.clone())
+      .unwrap_or_default()
+  }
+
+  fn update(&self, task: Task, f: impl FnOnce(&mut TaskReport)) {
+    if let Ok(mut reports) = self.reports.lock()
+      && let Some(report) = reports.iter_mut().find(|r| r.task == task)
+    {
+      f(report);
+    }
+  }
+}
+
+// Waits for startup to finish, then runs `task` on its schedule until the process exits
+async fn run_task(state: web::Data<AppState>, task: Task, schedule: Schedule) {
+  while !state.is_ready() {
+    tokio::time::sleep(READY_POLL_INTERVAL).await;
+  }
+  let scheduler = &state.scheduler;
+
+  loop {
+    let next_run_at = schedule.next_after(Utc::now());
+    scheduler.update(task, |report| report.next_run_at = Some(next_run_at));
+    let wait = (next_run_at - Utc::now()).to_std().unwrap_or_default();
+    tokio::time::sleep(wait).await;
+
+    let started_at = Utc::now();
+    let started = Instant::now();
+    let result = task.run(&state).await;
+    let elapsed = started.elapsed().as_millis();
+    match &result {
+      Ok(outcome) => println!("Scheduled task {}: {}", task.name(), outcome),
+      Err(e) => eprintln!("Scheduled task {} failed: {}", task.name(), e),
+    }
+    scheduler.update(task, |report| {
+      report.runs += 1;
+      report.last_started_at = Some(started_at);
+      report.last_duration_ms = Some(elapsed);
+      match result {
+        Ok(outcome) => {
+          report.last_outcome = Some(outcome);
+          report.last_error = None;
+        }
+        Err(e) => {
+          report.failures += 1;
+          report.last_error = Some(e.to_string());
+        }
+      }
+    });
+  }
+}
+
+pub fn spawn(state: web::Data<AppState>) {
+  for report in state.scheduler.reports() {
+    if report.enabled {
+      actix_web::rt::spawn(run_task(state.clone(), report.task, report.schedule));
+    }
+  }
+}
@@ -0,0 +1,103 @@
+use anyhow::{Context, Result};
+use chrono::Utc;
+use futures::{FutureExt, future::BoxFuture};
+use serde_json::{Value, json};
+
+use crate::{
+  secrets::SecretResolver,
+  utils::aws_sigv4::{self, AwsCredentials, SignedRequest},
+};
+
+const SERVICE: &str = "secretsmanager";
+const TARGET: &str = "secretsmanager.GetSecretValue";
+const CONTENT_TYPE: &str = "application/x-amz-json-1.1";
+
+// `aws-sm://prod/api` returns the whole secret string, `aws-sm://prod/api#jwt_secret` one key
+// of a JSON secret. Credentials come from the standard AWS_* environment variables.
+pub struct AwsSecretsManagerResolver {
+  region: String,
+  credentials: AwsCredentials,
+  client: reqwest::Client,
+}
+
+impl AwsSecretsManagerResolver {
+  pub fn from_env() -> Option<Self> {
+    let region = std::env::var("AWS_REGION")
+      .or_else(|_| std::env::var("AWS_DEFAULT_REGION"))
+      .ok()?;
+    Some(Self {
+      region,
+      credentials: AwsCredentials {
+        access_key_id: std::env::var("AWS_ACCESS_KEY_ID").unwrap_or_default(),
+        secret_access_key: std::env::var("AWS_SECRET_ACCESS_KEY").unwrap_or_default(),
+        session_token: std::env::var("AWS_SESSION_TOKEN").ok(),
+      },
+      client: reqwest::Client::new(),
+    })
+  }
+
+  // Signature Version 4 for a single JSON POST to the regional endpoint
+  async fn get_secret_value(&self, secret_id: &str) -> Result<String> {
+    let host = format!("{}.{}.amazonaws.com", SERVICE, self.region);
+    let body = json!({ "SecretId": secret_id }).to_string();
+    let now = Utc::now();
+
+    let mut headers = vec![
+      ("content-type", CONTENT_TYPE.to_string()),
+      ("host", host.clone()),
+      ("x-amz-date", aws_sigv4::amz_date(now)),
+    ];
+    if let Some(token) = &self.credentials.session_token {
+      headers.push(("x-amz-security-token", token.clone()));
+    }
+    headers.push(("x-amz-target", TARGET.to_string()));
+
+    let authorization = SignedRequest {
+      method: "POST",
+      canonical_uri: "/",
+      canonical_query: "",
+      headers: &headers,
+      payload_hash: &aws_sigv4::payload_hash(body.as_bytes()),
+    }
+    .authorization(&self.credentials, &self.region, SERVICE, now);
+
+    let mut request = self
+      .client
+      .post(format!("https://{}/", host))
+      .header("authorization", authorization)
+      .body(body);
+    for (name, value) in headers.iter().filter(|(name, _)| *name != "host") {
+      request = request.header(*name, value);
+    }
+    let response: Value = request.send().await?.error_for_status()?.json().await?;
+    response["SecretString"]
+      .as_str()
+      .map(|value| value.to_string())
+      .with_context(|| format!("Secret {} has no SecretString", secret_id))
+  }
+
+  async fn read(&self, path: &str) -> Result<String> {
+    let (secret_id, key) = match path.split_once('#') {
+      Some((secret_id, key)) => (secret_id, Some(key)),
+      None => (path, None),
+    };
+    let secret = self.get_secret_value(secret_id).await?;
+    match key {
+      None => Ok(secret),
+      Some(key) => {
+        let fields: Value = serde_json::from_str(&secret)
+          .with_context(|| format!("Secret {} is not a JSON object", secret_id))?;
+        fields[key]
+          .as_str()
+          .map(|value| value.to_string())
+          .with_context(|| format!("Key '{}' not found in secret {}", key, secret_id))
+      }
+    }
+  }
+}
+
+impl SecretResolver for AwsSecretsManagerResolver {
+  fn resolve<'a>(&'a self, path: &'a str) -> BoxFuture<'a, Result<String>> {
+    self.read(path).boxed()
+  }
+}
@@ -0,0 +1,139 @@
+pub mod aws_secrets_manager;
+pub mod vault;
+
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::{Context, Result};
+use futures::{FutureExt, future::BoxFuture};
+
+use crate::{
+  app_settings::{AppSetting, SecurityLogSink, StorageBackendSetting},
+  secrets::{aws_secrets_manager::AwsSecretsManagerResolver, vault::VaultResolver},
+};
+
+/// Looks up a secret referenced as `<scheme>://<path>` in appsettings.
+pub trait SecretResolver: Send + Sync {
+  fn resolve<'a>(&'a self, path: &'a str) -> BoxFuture<'a, Result<String>>;
+}
+
+// `env://JWT_SECRET` reads an environment variable
+struct EnvResolver;
+
+impl SecretResolver for EnvResolver {
+  fn resolve<'a>(&'a self, path: &'a str) -> BoxFuture<'a, Result<String>> {
+    async move {
+      std::env::var(path).with_context(|| format!("Environment variable {} is not set", path))
+    }
+    .boxed()
+  }
+}
+
+// `file:///run/secrets/jwt` reads a mounted secret file, trailing newline dropped
+struct FileResolver;
+
+impl SecretResolver for FileResolver {
+  fn resolve<'a>(&'a self, path: &'a str) -> BoxFuture<'a, Result<String>> {
+    async move {
+      let value = tokio::fs::read_to_string(path)
+        .await
+        .with_context(|| format!("Failed to read secret file {}", path))?;
+      Ok(value.trim_end_matches(['\r', '\n']).to_string())
+    }
+    .boxed()
+  }
+}
+
+// Resolvers by URI scheme, values with any other scheme are used as written
+pub struct SecretResolvers {
+  resolvers: HashMap<String, Arc<dyn SecretResolver>>,
+}
+
+impl SecretResolvers {
+  /// `env` and `file` always, `vault` when `VAULT_ADDR` is set and `aws-sm` when a region is.
+  pub fn from_env() -> Self {
+    let mut resolvers = Self {
+      resolvers: HashMap::new(),
+    };
+    resolvers.register("env", Arc::new(EnvResolver));
+    resolvers.register("file", Arc::new(FileResolver));
+    if let Some(vault) = VaultResolver::from_env() {
+      resolvers.register("vault", Arc::new(vault));
+    }
+    if let Some(aws) = AwsSecretsManagerResolver::from_env() {
+      resolvers.register("aws-sm", Arc::new(aws));
+    }
+    resolvers
+  }
+
+  pub fn register(&mut self, scheme: &str, resolver: Arc<dyn SecretResolver>) {
+    self.resolvers.insert(scheme.to_string(), resolver);
+  }
+
+  /// The secret `value` refers to, or `value` itself when it isn't a reference.
+  pub async fn resolve(&self, value: &str) -> Result<String> {
+    let Some((scheme, path)) = value.split_once("://") else {
+      return Ok(value.to_string());
+    };
+    let Some(resolver) = self.resolvers.get(scheme) else {
+      return Ok(value.to_string());
+    };
+    resolver
+      .resolve(path)
+      .await
+      .with_context(|| format!("Failed to resolve secret {}://{}", scheme, path))
+  }
+
+  async fn resolve_value(&self, value: &mut String) -> Result<()> {
+    *value = self.resolve(value).await?;
+    Ok(())
+  }
+
+  /// Replaces secret references in the connection strings, JWT key, introspection client
+  /// secrets, CAPTCHA key, S3 key, docs password and channel credentials.
+  pub async fn resolve_setting(&self, config: &mut AppSetting) -> Result<()> {
+    self
+      .resolve_value(&mut config.database.sql_server.conn_str)
+      .await?;
+    for db in config.database.databases.values_mut() {
+      self.resolve_value(&mut db.conn_str).await?;
+    }
+    if let Some(tenants) = config.database.tenants.as_mut() {
+      self.resolve_value(&mut tenants.conn_str_template).await?;
+    }
+    self.resolve_value(&mut config.jwt.secret_key).await?;
+    for client in config.auth.introspection_clients.iter_mut() {
+      self.resolve_value(&mut client.client_secret).await?;
+    }
+    self.resolve_value(&mut config.captcha.secret_key).await?;
+    if let StorageBackendSetting::S3(s3) = &mut config.storage.backend {
+      self.resolve_value(&mut s3.secret_access_key).await?;
+    }
+
+    if let Some(redis) = config.redis.as_mut() {
+      self.resolve_value(&mut redis.url).await?;
+    }
+    if let Some(smtp) = config.mailer.smtp.as_mut() {
+      self.resolve_value(&mut smtp.password).await?;
+    }
+    if let Some(basic) = config.docs.basic_auth.as_mut() {
+      self.resolve_value(&mut basic.password).await?;
+    }
+    let notification = &mut config.notification;
+    if let Some(token) = notification
+      .webhook
+      .as_mut()
+      .and_then(|webhook| webhook.bearer_token.as_mut())
+    {
+      self.resolve_value(token).await?;
+    }
+    if let Some(sms) = notification.sms.as_mut() {
+      self.resolve_value(&mut sms.auth_token).await?;
+    }
+    if let Some(SecurityLogSink::Webhook(webhook)) = config.security_log.sink.as_mut()
+      && let Some(token) = webhook.bearer_token.as_mut()
+    {
+      self.resolve_value(token).await?;
+    }
+    Ok(())
+  }
+}
@@ -0,0 +1,53 @@
+use anyhow::{Context, Result, anyhow};
+use futures::{FutureExt, future::BoxFuture};
+use serde_json::Value;
+
+use crate::secrets::SecretResolver;
+
+// Reads KV v2 secrets, `vault://kv/api/jwt_secret` is field `jwt_secret` of `api` in mount `kv`
+pub struct VaultResolver {
+  addr: String,
+  token: String,
+  client: reqwest::Client,
+}
+
+impl VaultResolver {
+  pub fn from_env() -> Option<Self> {
+    let addr = std::env::var("VAULT_ADDR").ok()?;
+    Some(Self {
+      addr: addr.trim_end_matches('/').to_string(),
+      token: std::env::var("VAULT_TOKEN").unwrap_or_default(),
+      client: reqwest::Client::new(),
+    })
+  }
+
+  async fn read(&self, path: &str) -> Result<String> {
+    let (mount, rest) = path
+      .split_once('/')
+      .ok_or_else(|| anyhow!("Expected vault://<mount>/<path>/<field>"))?;
+    let (secret_path, field) = rest
+      .rsplit_once('/')
+      .ok_or_else(|| anyhow!("Expected vault://<mount>/<path>/<field>"))?;
+
+    let url = format!("{}/v1/{}/data/{}", self.addr, mount, secret_path);
+    let body: Value = self
+      .client
+      .get(&url)
+      .header("X-Vault-Token", &self.token)
+      .send()
+      .await?
+      .error_for_status()?
+      .json()
+      .await?;
+    body["data"]["data"][field]
+      .as_str()
+      .map(|value| value.to_string())
+      .with_context(|| format!("Field '{}' not found in {}", field, secret_path))
+  }
+}
+
+impl SecretResolver for VaultResolver {
+  fn resolve<'a>(&'a self, path: &'a str) -> BoxFuture<'a, Result<String>> {
+    self.read(path).boxed()
+  }
+}
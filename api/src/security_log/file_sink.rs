@@ -0,0 +1,41 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use futures::{FutureExt, future::BoxFuture};
+use tokio::{fs::OpenOptions, io::AsyncWriteExt};
+
+use crate::security_log::{SecurityEvent, SecurityEventSink};
+
+pub struct FileSink {
+  path: PathBuf,
+}
+
+impl FileSink {
+  pub fn new(path: impl AsRef<Path>) -> Self {
+    Self {
+      path: path.as_ref().to_path_buf(),
+    }
+  }
+
+  // Reopened for every event so a rotated file is picked up without a restart
+  async fn append(&self, event: &SecurityEvent) -> Result<()> {
+    if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+      tokio::fs::create_dir_all(dir).await?;
+    }
+    let mut line = serde_json::to_vec(event)?;
+    line.push(b'\n');
+    let mut file = OpenOptions::new()
+      .create(true)
+      .append(true)
+      .open(&self.path)
+      .await?;
+    file.write_all(&line).await?;
+    Ok(())
+  }
+}
+
+impl SecurityEventSink for FileSink {
+  fn write<'a>(&'a self, event: &'a SecurityEvent) -> BoxFuture<'a, Result<()>> {
+    self.append(event).boxed()
+  }
+}
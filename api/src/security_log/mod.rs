@@ -0,0 +1,232 @@
+pub mod file_sink;
+pub mod syslog_sink;
+pub mod webhook_sink;
+
+use std::{sync::Arc, time::Duration};
+
+use actix_web::{HttpMessage, HttpRequest, http::header::USER_AGENT, web};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+use serde::Serialize;
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+use crate::{
+  app_settings::{SecurityLogSetting, SecurityLogSink},
+  app_state::AppState,
+  middleware::{auth::Impersonation, rate_limit::client_ip, request_id::RequestId},
+  security_log::{file_sink::FileSink, syslog_sink::SyslogSink, webhook_sink::WebhookSink},
+};
+
+/// Bumped whenever a field of `SecurityEvent` is renamed, removed or changes meaning.
+/// Adding a field or an event type keeps the version.
+pub const SCHEMA_VERSION: u32 = 1;
+
+// Delay before the first retry, doubled on every further attempt
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(1);
+const USER_AGENT_MAX: usize = 512;
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SecurityEventType {
+  LoginSucceeded,
+  LoginFailed,
+  /// Refused by the login tarpit without checking the password
+  LoginBlocked,
+  RoleGranted,
+  /// The user was deactivated, which ends every token they hold
+  TokensRevoked,
+  ImpersonationStarted,
+  ImpersonationStopped,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SecuritySeverity {
+  Info,
+  Notice,
+  Warning,
+}
+
+impl SecurityEventType {
+  pub fn to_str(&self) -> &'static str {
+    match self {
+      SecurityEventType::LoginSucceeded => "login_succeeded",
+      SecurityEventType::LoginFailed => "login_failed",
+      SecurityEventType::LoginBlocked => "login_blocked",
+      SecurityEventType::RoleGranted => "role_granted",
+      SecurityEventType::TokensRevoked => "tokens_revoked",
+      SecurityEventType::ImpersonationStarted => "impersonation_started",
+      SecurityEventType::ImpersonationStopped => "impersonation_stopped",
+    }
+  }
+
+  pub fn severity(&self) -> SecuritySeverity {
+    match self {
+      SecurityEventType::LoginSucceeded | SecurityEventType::ImpersonationStopped => {
+        SecuritySeverity::Info
+      }
+      SecurityEventType::RoleGranted
+      | SecurityEventType::TokensRevoked
+      | SecurityEventType::ImpersonationStarted => SecuritySeverity::Notice,
+      SecurityEventType::LoginFailed | SecurityEventType::LoginBlocked => SecuritySeverity::Warning,
+    }
+  }
+}
+
+/// One security event as shipped to the sink. Every field is always present, `null` when it
+/// doesn't apply, so a SIEM can map the schema once.
+#[derive(Debug, Serialize, Clone)]
+pub struct SecurityEvent {
+  pub schema_version: u32,
+  pub event_id: String,
+  pub time: DateTime<Utc>,
+  #[serde(rename = "type")]
+  pub event_type: SecurityEventType,
+  pub severity: SecuritySeverity,
+  /// User who did it, the admin for grants and impersonations
+  pub actor_id: Option<i32>,
+  /// User name the event is about, as typed for failed logins
+  pub user_name: Option<String>,
+  /// What was acted on, e.g. `user:42` or `role:3`
+  pub target: Option<String>,
+  pub details: Option<String>,
+  pub ip: Option<String>,
+  pub user_agent: Option<String>,
+  pub request_id: Option<String>,
+  /// Admin behind an impersonated request
+  pub impersonated_by: Option<i32>,
+}
+
+impl SecurityEvent {
+  pub fn new(event_type: SecurityEventType) -> Self {
+    Self {
+      schema_version: SCHEMA_VERSION,
+      event_id: Uuid::new_v4().to_string(),
+      time: Utc::now(),
+      event_type,
+      severity: event_type.severity(),
+      actor_id: None,
+      user_name: None,
+      target: None,
+      details: None,
+      ip: None,
+      user_agent: None,
+      request_id: None,
+      impersonated_by: None,
+    }
+  }
+
+  pub fn actor(mut self, actor_id: Option<i32>) -> Self {
+    self.actor_id = actor_id;
+    self
+  }
+
+  pub fn user_name(mut self, user_name: impl Into<String>) -> Self {
+    self.user_name = Some(user_name.into());
+    self
+  }
+
+  pub fn target(mut self, target: impl Into<String>) -> Self {
+    self.target = Some(target.into());
+    self
+  }
+
+  pub fn details(mut self, details: impl Into<String>) -> Self {
+    self.details = Some(details.into());
+    self
+  }
+
+  // The IP is resolved like for rate limiting, so behind a proxy it is the forwarded one
+  pub fn with_request(mut self, req: &HttpRequest) -> Self {
+    self.ip = req
+      .app_data::<web::Data<AppState>>()
+      .and_then(|data| client_ip(req, &data.runtime().rate_limit))
+      .map(|ip| ip.to_string());
+    self.user_agent = req
+      .headers()
+      .get(USER_AGENT)
+      .and_then(|value| value.to_str().ok())
+      .map(|value| value.chars().take(USER_AGENT_MAX).collect());
+    self.request_id = RequestId::of(req);
+    self.impersonated_by = req
+      .extensions()
+      .get::<Impersonation>()
+      .map(|impersonation| impersonation.admin_id);
+    self
+  }
+}
+
+/// Where security events end up.
+pub trait SecurityEventSink: Send + Sync {
+  fn write<'a>(&'a self, event: &'a SecurityEvent) -> BoxFuture<'a, Result<()>>;
+}
+
+/// Queues security events and writes them to the configured sink from a background worker,
+/// apart from the access log and the audit table. Cheap to clone, every clone feeds the same
+/// queue. Without a sink, events are dropped.
+#[derive(Clone, Default)]
+pub struct SecurityLog {
+  sender: Option<mpsc::Sender<SecurityEvent>>,
+}
+
+impl SecurityLog {
+  /// Starts the worker when a sink is configured, must be called inside the runtime.
+  pub fn start(setting: &SecurityLogSetting) -> Self {
+    let Some(sink) = &setting.sink else {
+      return Self::default();
+    };
+    let sink: Arc<dyn SecurityEventSink> = match sink {
+      SecurityLogSink::File { path } => Arc::new(FileSink::new(path)),
+      SecurityLogSink::Syslog { address, app_name } => Arc::new(SyslogSink::new(address, app_name)),
+      SecurityLogSink::Webhook(webhook) => Arc::new(WebhookSink::new(webhook.clone())),
+    };
+    let (sender, receiver) = mpsc::channel(setting.queue_capacity.max(1));
+    actix_web::rt::spawn(run_worker(receiver, sink, setting.max_attempts.max(1)));
+    Self {
+      sender: Some(sender),
+    }
+  }
+
+  /// Queues `event` without waiting, it is dropped with a log line when the queue is full.
+  pub fn emit(&self, event: SecurityEvent) {
+    let Some(sender) = &self.sender else {
+      return;
+    };
+    if let Err(e) = sender.try_send(event) {
+      eprintln!("Failed to queue security event: {}", e);
+    }
+  }
+}
+
+async fn run_worker(
+  mut receiver: mpsc::Receiver<SecurityEvent>,
+  sink: Arc<dyn SecurityEventSink>,
+  max_attempts: u32,
+) {
+  while let Some(event) = receiver.recv().await {
+    let mut delay = RETRY_BASE_DELAY;
+    for attempt in 1..=max_attempts {
+      match sink.write(&event).await {
+        Ok(()) => break,
+        Err(e) if attempt == max_attempts => {
+          eprintln!(
+            "Giving up on security event {} after {} attempt(s): {}",
+            event.event_id, attempt, e
+          );
+        }
+        Err(e) => {
+          eprintln!(
+            "Failed to write security event {}, retrying in {}s: {}",
+            event.event_id,
+            delay.as_secs(),
+            e
+          );
+          tokio::time::sleep(delay).await;
+          delay *= 2;
+        }
+      }
+    }
+  }
+}
@@ -0,0 +1,69 @@
+use anyhow::Result;
+use futures::{FutureExt, future::BoxFuture};
+use tokio::net::{UdpSocket, lookup_host};
+
+use crate::security_log::{SecurityEvent, SecurityEventSink, SecuritySeverity};
+
+// authpriv, meant for security messages that shouldn't land in the general syslog
+const FACILITY_AUTHPRIV: u8 = 10;
+
+/// RFC 5424 over UDP with the event as JSON in the message part.
+pub struct SyslogSink {
+  address: String,
+  app_name: String,
+  hostname: String,
+}
+
+impl SyslogSink {
+  pub fn new(address: &str, app_name: &str) -> Self {
+    Self {
+      address: address.to_string(),
+      app_name: app_name.to_string(),
+      hostname: std::env::var("HOSTNAME")
+        .ok()
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "-".to_string()),
+    }
+  }
+
+  fn format(&self, event: &SecurityEvent) -> Result<String> {
+    let severity = match event.severity {
+      SecuritySeverity::Info => 6,
+      SecuritySeverity::Notice => 5,
+      SecuritySeverity::Warning => 4,
+    };
+    // <PRI>VERSION TIMESTAMP HOSTNAME APP-NAME PROCID MSGID STRUCTURED-DATA MSG
+    Ok(format!(
+      "<{}>1 {} {} {} {} {} - {}",
+      FACILITY_AUTHPRIV * 8 + severity,
+      event.time.to_rfc3339(),
+      self.hostname,
+      self.app_name,
+      std::process::id(),
+      event.event_type.to_str(),
+      serde_json::to_string(event)?
+    ))
+  }
+
+  async fn send(&self, event: &SecurityEvent) -> Result<()> {
+    let message = self.format(event)?;
+    let target = lookup_host(&self.address)
+      .await?
+      .next()
+      .ok_or_else(|| anyhow::anyhow!("'{}' did not resolve to an address", self.address))?;
+    let local = if target.is_ipv4() {
+      "0.0.0.0:0"
+    } else {
+      "[::]:0"
+    };
+    let socket = UdpSocket::bind(local).await?;
+    socket.send_to(message.as_bytes(), target).await?;
+    Ok(())
+  }
+}
+
+impl SecurityEventSink for SyslogSink {
+  fn write<'a>(&'a self, event: &'a SecurityEvent) -> BoxFuture<'a, Result<()>> {
+    self.send(event).boxed()
+  }
+}
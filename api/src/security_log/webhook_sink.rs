@@ -0,0 +1,36 @@
+use anyhow::Result;
+use futures::{FutureExt, future::BoxFuture};
+
+use crate::{
+  app_settings::WebhookChannelSetting,
+  security_log::{SecurityEvent, SecurityEventSink},
+};
+
+pub struct WebhookSink {
+  setting: WebhookChannelSetting,
+  client: reqwest::Client,
+}
+
+impl WebhookSink {
+  pub fn new(setting: WebhookChannelSetting) -> Self {
+    Self {
+      setting,
+      client: reqwest::Client::new(),
+    }
+  }
+
+  async fn post(&self, event: &SecurityEvent) -> Result<()> {
+    let mut request = self.client.post(&self.setting.url).json(event);
+    if let Some(token) = &self.setting.bearer_token {
+      request = request.bearer_auth(token);
+    }
+    request.send().await?.error_for_status()?;
+    Ok(())
+  }
+}
+
+impl SecurityEventSink for WebhookSink {
+  fn write<'a>(&'a self, event: &'a SecurityEvent) -> BoxFuture<'a, Result<()>> {
+    self.post(event).boxed()
+  }
+}
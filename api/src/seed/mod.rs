@@ -0,0 +1,133 @@
+use anyhow::Result;
+
+use crate::{
+  app_state::AppState,
+  features::{
+    roles::{roles_dto::CreateRoleReqDto, roles_entity::RoleEntity},
+    users::{user_dto::UserRegisterReqDto, user_entity::UserRole},
+  },
+};
+
+// Known password of the demo users, they are only ever created in dev environments
+pub const DEMO_PASSWORD: &str = "Demo@12345";
+
+/// Rows the API expects to exist. Each seeder checks before inserting, so running them again
+/// only adds what is missing.
+#[derive(Clone, Copy, Debug)]
+pub enum Seeder {
+  /// The `admin`, `moderator` and `user` roles of the roles table
+  DefaultRoles,
+  /// One user per default role, dev environments only
+  DemoUsers,
+}
+
+impl Seeder {
+  pub const ALL: [Seeder; 2] = [Seeder::DefaultRoles, Seeder::DemoUsers];
+
+  pub fn name(&self) -> &'static str {
+    match self {
+      Seeder::DefaultRoles => "default_roles",
+      Seeder::DemoUsers => "demo_users",
+    }
+  }
+
+  fn applies_to(&self, state: &AppState) -> bool {
+    match self {
+      Seeder::DefaultRoles => true,
+      Seeder::DemoUsers => state.config.is_dev(),
+    }
+  }
+
+  // Returns the number of rows created
+  async fn run(&self, state: &AppState) -> Result<usize> {
+    match self {
+      Seeder::DefaultRoles => seed_default_roles(state).await,
+      Seeder::DemoUsers => seed_demo_users(state).await,
+    }
+  }
+}
+
+fn default_roles() -> [(UserRole, &'static str); 3] {
+  [
+    (UserRole::Admin, "Full access to every feature"),
+    (UserRole::Moderator, "Manages users and their roles"),
+    (UserRole::User, "Regular account"),
+  ]
+}
+
+async fn seed_default_roles(state: &AppState) -> Result<usize> {
+  let mut repo = state.role_repo();
+  let mut created = 0;
+  for (role, description) in default_roles() {
+    if repo.get_by_name(role.to_str()).await?.is_some() {
+      continue;
+    }
+    let entity = RoleEntity::from(CreateRoleReqDto {
+      name: role.to_str().to_string(),
+      description: Some(description.to_string()),
+    });
+    repo.create_role(&entity).await?;
+    created += 1;
+  }
+  Ok(created)
+}
+
+// `demo_admin`, `demo_moderator` and `demo_user`, each assigned the role of the same name
+async fn seed_demo_users(state: &AppState) -> Result<usize> {
+  let mut users = state.user_repo();
+  let mut roles = state.role_repo();
+  let mut created = 0;
+  for (role, _) in default_roles() {
+    let user_name = format!("demo_{}", role.to_str());
+    let user = match users.get_by_username(&user_name).await? {
+      Some(user) => user,
+      None => {
+        users
+          .create(&UserRegisterReqDto {
+            name: format!("Demo {}", role.to_str()),
+            email: format!("{}@example.com", user_name),
+            user_name: user_name.clone(),
+            password: DEMO_PASSWORD.to_string(),
+            role: role.to_str().to_string(),
+          })
+          .await?;
+        created += 1;
+        users
+          .get_by_username(&user_name)
+          .await?
+          .ok_or_else(|| anyhow::anyhow!("Demo user {} was not created", user_name))?
+      }
+    };
+    // Seeded by `DefaultRoles` first, a missing role means it was deleted since
+    let Some(role) = roles.get_by_name(role.to_str()).await? else {
+      continue;
+    };
+    if !roles.is_user_role_exist(user.id, role.id).await {
+      roles.assign_user_role(user.id, role.id).await?;
+    }
+  }
+  Ok(created)
+}
+
+// Run every seeder that applies to the environment, stops at the first failure
+pub async fn run_all(state: &AppState) -> Result<()> {
+  for seeder in Seeder::ALL {
+    if !seeder.applies_to(state) {
+      continue;
+    }
+    let created = seeder.run(state).await?;
+    println!("Seeded {}: {} row(s) created", seeder.name(), created);
+  }
+  Ok(())
+}
+
+// `seed` CLI subcommand, returns the process exit code
+pub async fn run(state: &AppState) -> i32 {
+  match run_all(state).await {
+    Ok(_) => 0,
+    Err(e) => {
+      eprintln!("Seeding failed: {}", e);
+      1
+    }
+  }
+}
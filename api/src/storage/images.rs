@@ -0,0 +1,160 @@
+use std::{io::Cursor, sync::Arc};
+
+use actix_web::web::Bytes;
+use anyhow::{Context, Result, bail};
+use image::{
+  DynamicImage, ImageDecoder, ImageFormat, ImageReader, Limits, codecs::jpeg::JpegEncoder,
+  imageops::FilterType,
+};
+use tokio::sync::mpsc;
+
+use crate::{
+  app_settings::ImageProcessingSetting,
+  storage::{Storage, StoredObject},
+};
+
+/// An upload waiting in `source_key` to be processed into `target_key` and its variants.
+pub struct ImageJob {
+  pub source_key: String,
+  pub target_key: String,
+}
+
+/// Key of the variant of `key` whose longest side is `size` pixels, e.g. `avatars/42-64`.
+pub fn variant_key(key: &str, size: u32) -> String {
+  format!("{}-{}", key, size)
+}
+
+/// Re-encodes uploaded images and writes their resized variants from a background worker,
+/// so decoding a large photo never holds up a request. Cheap to clone, every clone feeds
+/// the same queue.
+#[derive(Clone)]
+pub struct ImageProcessor {
+  sender: mpsc::Sender<ImageJob>,
+  variant_sizes: Arc<Vec<u32>>,
+}
+
+impl ImageProcessor {
+  /// Starts the worker, must be called inside the runtime.
+  pub fn start(setting: &ImageProcessingSetting, storage: Arc<dyn Storage>) -> Self {
+    let (sender, receiver) = mpsc::channel(setting.queue_capacity.max(1));
+    actix_web::rt::spawn(run_worker(receiver, storage, setting.clone()));
+    Self {
+      sender,
+      variant_sizes: Arc::new(setting.variant_sizes.clone()),
+    }
+  }
+
+  pub fn variant_sizes(&self) -> &[u32] {
+    &self.variant_sizes
+  }
+
+  /// Images waiting for the worker.
+  pub fn queued(&self) -> usize {
+    self.sender.max_capacity() - self.sender.capacity()
+  }
+
+  /// Queues `job` without waiting, fails when the queue is full.
+  pub fn enqueue(&self, job: ImageJob) -> Result<()> {
+    self
+      .sender
+      .try_send(job)
+      .map_err(|e| anyhow::anyhow!("Failed to queue image: {}", e))
+  }
+}
+
+struct Processed {
+  full: StoredObject,
+  variants: Vec<(u32, StoredObject)>,
+}
+
+fn encode(image: &DynamicImage, jpeg: bool, quality: u8) -> Result<StoredObject> {
+  let mut buffer = Cursor::new(Vec::new());
+  let content_type = if jpeg {
+    // JPEG has no alpha channel
+    image
+      .to_rgb8()
+      .write_with_encoder(JpegEncoder::new_with_quality(&mut buffer, quality))?;
+    "image/jpeg"
+  } else {
+    image.write_to(&mut buffer, ImageFormat::Png)?;
+    "image/png"
+  };
+  Ok(StoredObject {
+    content_type: content_type.to_string(),
+    bytes: Bytes::from(buffer.into_inner()),
+  })
+}
+
+// Only the pixels are written back, which drops EXIF and any other metadata; the EXIF
+// orientation is applied first so photos stay upright. Photos stay JPEG, the rest become PNG.
+fn process(bytes: &[u8], setting: &ImageProcessingSetting) -> Result<Processed> {
+  let mut reader = ImageReader::new(Cursor::new(bytes)).with_guessed_format()?;
+  let mut limits = Limits::default();
+  limits.max_image_width = Some(setting.max_dimension);
+  limits.max_image_height = Some(setting.max_dimension);
+  reader.limits(limits);
+  let jpeg = reader.format() == Some(ImageFormat::Jpeg);
+
+  let mut decoder = reader.into_decoder()?;
+  let orientation = decoder.orientation()?;
+  let mut image = DynamicImage::from_decoder(decoder)?;
+  image.apply_orientation(orientation);
+
+  let mut variants = Vec::with_capacity(setting.variant_sizes.len());
+  for &size in &setting.variant_sizes {
+    // Smaller images are not scaled up, the variant is then the same as the full image
+    let variant = if image.width() <= size && image.height() <= size {
+      image.clone()
+    } else {
+      image.resize(size, size, FilterType::Lanczos3)
+    };
+    variants.push((size, encode(&variant, jpeg, setting.jpeg_quality)?));
+  }
+  Ok(Processed {
+    full: encode(&image, jpeg, setting.jpeg_quality)?,
+    variants,
+  })
+}
+
+async fn run_job(
+  storage: &dyn Storage,
+  setting: &ImageProcessingSetting,
+  job: &ImageJob,
+) -> Result<()> {
+  let Some(source) = storage.get(&job.source_key).await? else {
+    bail!("'{}' is no longer stored", job.source_key);
+  };
+  let processed = {
+    let setting = setting.clone();
+    tokio::task::spawn_blocking(move || process(&source.bytes, &setting))
+      .await
+      .context("Image processing panicked")??
+  };
+
+  for (size, variant) in processed.variants {
+    storage
+      .put(&variant_key(&job.target_key, size), variant)
+      .await?;
+  }
+  storage.put(&job.target_key, processed.full).await?;
+  storage.delete(&job.source_key).await
+}
+
+async fn run_worker(
+  mut receiver: mpsc::Receiver<ImageJob>,
+  storage: Arc<dyn Storage>,
+  setting: ImageProcessingSetting,
+) {
+  while let Some(job) = receiver.recv().await {
+    if let Err(e) = run_job(storage.as_ref(), &setting, &job).await {
+      eprintln!(
+        "Failed to process image '{}' into '{}': {:#}",
+        job.source_key, job.target_key, e
+      );
+      // A broken upload would otherwise stay in staging until it's replaced
+      if let Err(e) = storage.delete(&job.source_key).await {
+        eprintln!("Failed to delete '{}': {}", job.source_key, e);
+      }
+    }
+  }
+}
@@ -0,0 +1,100 @@
+use std::{
+  io::ErrorKind,
+  path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result, bail};
+use futures::{FutureExt, future::BoxFuture};
+
+use crate::storage::{Storage, StoredObject, check_key};
+
+// The content type of `<key>` is kept next to it in `<key>.content-type`
+const CONTENT_TYPE_SUFFIX: &str = ".content-type";
+const PARTIAL_SUFFIX: &str = ".partial";
+
+/// Files under a directory of the local disk, for a single instance or a shared volume.
+pub struct LocalStorage {
+  root: PathBuf,
+}
+
+impl LocalStorage {
+  pub fn new(root: impl AsRef<Path>) -> Self {
+    Self {
+      root: root.as_ref().to_path_buf(),
+    }
+  }
+
+  fn paths(&self, key: &str) -> Result<(PathBuf, PathBuf)> {
+    check_key(key)?;
+    if key.ends_with(CONTENT_TYPE_SUFFIX) || key.ends_with(PARTIAL_SUFFIX) {
+      bail!(
+        "Storage key '{}' clashes with the files kept next to objects",
+        key
+      );
+    }
+    let path = self.root.join(key);
+    let content_type = self.root.join(format!("{}{}", key, CONTENT_TYPE_SUFFIX));
+    Ok((path, content_type))
+  }
+
+  async fn write(&self, key: &str, object: StoredObject) -> Result<()> {
+    let (path, content_type_path) = self.paths(key)?;
+    if let Some(parent) = path.parent() {
+      tokio::fs::create_dir_all(parent)
+        .await
+        .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    // Written next to the target and renamed, so readers never see half a file
+    let mut partial = path.clone().into_os_string();
+    partial.push(PARTIAL_SUFFIX);
+    let partial = PathBuf::from(partial);
+    tokio::fs::write(&partial, &object.bytes)
+      .await
+      .with_context(|| format!("Failed to write {}", partial.display()))?;
+    tokio::fs::write(&content_type_path, object.content_type.as_bytes()).await?;
+    tokio::fs::rename(&partial, &path).await?;
+    Ok(())
+  }
+
+  async fn read(&self, key: &str) -> Result<Option<StoredObject>> {
+    let (path, content_type_path) = self.paths(key)?;
+    let bytes = match tokio::fs::read(&path).await {
+      Ok(bytes) => bytes,
+      Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+      Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    };
+    let content_type = tokio::fs::read_to_string(&content_type_path)
+      .await
+      .unwrap_or_else(|_| "application/octet-stream".to_string());
+    Ok(Some(StoredObject {
+      content_type,
+      bytes: bytes.into(),
+    }))
+  }
+
+  async fn remove(&self, key: &str) -> Result<()> {
+    let (path, content_type_path) = self.paths(key)?;
+    for path in [path, content_type_path] {
+      match tokio::fs::remove_file(&path).await {
+        Ok(()) => {}
+        Err(e) if e.kind() == ErrorKind::NotFound => {}
+        Err(e) => return Err(e).with_context(|| format!("Failed to delete {}", path.display())),
+      }
+    }
+    Ok(())
+  }
+}
+
+impl Storage for LocalStorage {
+  fn put<'a>(&'a self, key: &'a str, object: StoredObject) -> BoxFuture<'a, Result<()>> {
+    self.write(key, object).boxed()
+  }
+
+  fn get<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<Option<StoredObject>>> {
+    self.read(key).boxed()
+  }
+
+  fn delete<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<()>> {
+    self.remove(key).boxed()
+  }
+}
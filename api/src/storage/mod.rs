@@ -0,0 +1,55 @@
+pub mod images;
+pub mod local;
+pub mod s3;
+
+use std::sync::Arc;
+
+use actix_web::web::Bytes;
+use anyhow::{Result, bail};
+use futures::future::BoxFuture;
+
+use crate::{
+  app_settings::StorageBackendSetting,
+  storage::{local::LocalStorage, s3::S3Storage},
+};
+
+/// A stored file with the content type it was uploaded with.
+#[derive(Clone, Debug)]
+pub struct StoredObject {
+  pub content_type: String,
+  pub bytes: Bytes,
+}
+
+/// Where uploaded and generated files live, picked by `storage.type`.
+/// Keys are relative paths such as `avatars/42`, see `check_key`.
+pub trait Storage: Send + Sync {
+  fn put<'a>(&'a self, key: &'a str, object: StoredObject) -> BoxFuture<'a, Result<()>>;
+  /// `None` when nothing is stored under `key`.
+  fn get<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<Option<StoredObject>>>;
+  /// Succeeds when nothing is stored under `key`.
+  fn delete<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<()>>;
+}
+
+pub fn from_setting(setting: &StorageBackendSetting) -> Arc<dyn Storage> {
+  match setting {
+    StorageBackendSetting::Local { root } => Arc::new(LocalStorage::new(root)),
+    StorageBackendSetting::S3(s3) => Arc::new(S3Storage::new(s3.clone())),
+  }
+}
+
+/// Keys are built by the API, this keeps a mistake from reaching outside the storage root.
+pub fn check_key(key: &str) -> Result<()> {
+  let valid = !key.is_empty()
+    && key.split('/').all(|segment| {
+      !segment.is_empty()
+        && segment != "."
+        && segment != ".."
+        && segment
+          .chars()
+          .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    });
+  if !valid {
+    bail!("Invalid storage key '{}'", key);
+  }
+  Ok(())
+}
@@ -0,0 +1,165 @@
+use anyhow::{Context, Result};
+use chrono::Utc;
+use futures::{FutureExt, future::BoxFuture};
+use reqwest::{Method, StatusCode, header};
+use url::Url;
+
+use crate::{
+  app_settings::S3StorageSetting,
+  storage::{Storage, StoredObject, check_key},
+  utils::aws_sigv4::{self, AwsCredentials, SignedRequest},
+};
+
+const SERVICE: &str = "s3";
+
+/// Objects in an S3 bucket, or any S3-compatible service such as MinIO or R2.
+pub struct S3Storage {
+  setting: S3StorageSetting,
+  credentials: AwsCredentials,
+  client: reqwest::Client,
+}
+
+// Every byte but the unreserved ones and `/` is percent-encoded, as SigV4 expects
+fn encode_path(path: &str) -> String {
+  path
+    .bytes()
+    .map(|b| match b {
+      b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+        (b as char).to_string()
+      }
+      b => format!("%{:02X}", b),
+    })
+    .collect()
+}
+
+impl S3Storage {
+  pub fn new(setting: S3StorageSetting) -> Self {
+    Self {
+      credentials: AwsCredentials {
+        access_key_id: setting.access_key_id.clone(),
+        secret_access_key: setting.secret_access_key.clone(),
+        session_token: setting.session_token.clone(),
+      },
+      setting,
+      client: reqwest::Client::new(),
+    }
+  }
+
+  // Path style `endpoint/bucket/key`, or virtual-hosted `bucket.endpoint/key`
+  fn object_url(&self, key: &str) -> Result<Url> {
+    check_key(key)?;
+    let mut url = Url::parse(&self.setting.endpoint).context("Invalid storage.endpoint")?;
+    let key = match self.setting.prefix.trim_matches('/') {
+      "" => key.to_string(),
+      prefix => format!("{}/{}", prefix, key),
+    };
+    if self.setting.path_style {
+      url.set_path(&format!("/{}/{}", self.setting.bucket, encode_path(&key)));
+    } else {
+      let host = url.host_str().context("storage.endpoint has no host")?;
+      url
+        .set_host(Some(&format!("{}.{}", self.setting.bucket, host)))
+        .context("Invalid bucket name")?;
+      url.set_path(&format!("/{}", encode_path(&key)));
+    }
+    Ok(url)
+  }
+
+  async fn send(
+    &self,
+    method: Method,
+    key: &str,
+    object: Option<StoredObject>,
+  ) -> Result<reqwest::Response> {
+    let url = self.object_url(key)?;
+    let host = match url.port() {
+      Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+      None => url.host_str().unwrap_or_default().to_string(),
+    };
+    let now = Utc::now();
+    let body = object
+      .as_ref()
+      .map(|object| object.bytes.clone())
+      .unwrap_or_default();
+    let payload_hash = aws_sigv4::payload_hash(&body);
+
+    let mut headers = Vec::new();
+    if let Some(object) = &object {
+      headers.push(("content-type", object.content_type.clone()));
+    }
+    headers.push(("host", host));
+    headers.push(("x-amz-content-sha256", payload_hash.clone()));
+    headers.push(("x-amz-date", aws_sigv4::amz_date(now)));
+    if let Some(token) = &self.credentials.session_token {
+      headers.push(("x-amz-security-token", token.clone()));
+    }
+
+    let authorization = SignedRequest {
+      method: method.as_str(),
+      canonical_uri: url.path(),
+      canonical_query: "",
+      headers: &headers,
+      payload_hash: &payload_hash,
+    }
+    .authorization(&self.credentials, &self.setting.region, SERVICE, now);
+
+    let mut request = self
+      .client
+      .request(method, url)
+      .header(header::AUTHORIZATION, authorization)
+      .body(body);
+    for (name, value) in headers.iter().filter(|(name, _)| *name != "host") {
+      request = request.header(*name, value);
+    }
+    Ok(request.send().await?)
+  }
+
+  async fn put_object(&self, key: &str, object: StoredObject) -> Result<()> {
+    self
+      .send(Method::PUT, key, Some(object))
+      .await?
+      .error_for_status()?;
+    Ok(())
+  }
+
+  async fn get_object(&self, key: &str) -> Result<Option<StoredObject>> {
+    let response = self.send(Method::GET, key, None).await?;
+    if response.status() == StatusCode::NOT_FOUND {
+      return Ok(None);
+    }
+    let response = response.error_for_status()?;
+    let content_type = response
+      .headers()
+      .get(header::CONTENT_TYPE)
+      .and_then(|value| value.to_str().ok())
+      .unwrap_or("application/octet-stream")
+      .to_string();
+    Ok(Some(StoredObject {
+      content_type,
+      bytes: response.bytes().await?,
+    }))
+  }
+
+  async fn delete_object(&self, key: &str) -> Result<()> {
+    // S3 answers 204 whether or not the object existed
+    let response = self.send(Method::DELETE, key, None).await?;
+    if response.status() != StatusCode::NOT_FOUND {
+      response.error_for_status()?;
+    }
+    Ok(())
+  }
+}
+
+impl Storage for S3Storage {
+  fn put<'a>(&'a self, key: &'a str, object: StoredObject) -> BoxFuture<'a, Result<()>> {
+    self.put_object(key, object).boxed()
+  }
+
+  fn get<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<Option<StoredObject>>> {
+    self.get_object(key).boxed()
+  }
+
+  fn delete<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<()>> {
+    self.delete_object(key).boxed()
+  }
+}
@@ -1,59 +1,63 @@
+pub mod registry;
+
+use actix_web::{HttpResponse, web};
 use utoipa::{
   Modify, OpenApi,
-  openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme},
+  openapi::{
+    ContentBuilder, Deprecated, PathItem, Paths, Ref, RefOr, ResponseBuilder, Schema,
+    path::Operation,
+    security::{HttpAuthScheme, HttpBuilder, SecurityScheme},
+  },
 };
+use utoipa_rapidoc::RapiDoc;
+use utoipa_redoc::{Redoc, Servable};
+use utoipa_swagger_ui::{SwaggerUi, Url};
+
+use registry::CollectedPaths;
 
 use crate::{
-  dto::base_res_dto::{BaseResDto, Status},
+  api_version::ApiVersion,
+  app_settings::DocsSetting,
+  commons::status_code_const::StatusCodeConst,
+  dto::{
+    base_res_dto::{BaseResDto, ErrorResDto, Status},
+    sort::SortDir,
+    validated_json::FieldErrorDto,
+  },
   features::{
-    auth::{auth_dto::LoginReqDto, auth_handler},
-    health_check,
-    roles::{
-      roles_dto::{
-        AssignUserRoleReqDto, CreateRoleReqDto, GetUserRolesReqDto, RoleDto, UpdateRoleReqDto,
-        UserRolesResDto,
-      },
-      roles_handler,
-    },
-    users::{
-      user_dto::{GetUserByIdReqDto, UpdateUserReqDto, UserDto, UserRegisterReqDto},
-      user_handler,
+    dev::dev_dto::DevTokenReqDto,
+    login_history::{
+      login_history_dto::{GetLoginHistoryReqDto, GetMyLoginsReqDto},
+      login_history_entity::LoginOutcome,
     },
+    roles::roles_dto::RoleSortBy,
+    users::user_dto::{ExportUsersReqDto, UserDto, UserSortBy},
   },
 };
 
+// Paths and the schemas of their bodies come from `document!`, listed here are only the schemas
+// nothing collects: enums referenced by query parameters and shapes not returned by a handler
 #[derive(OpenApi)]
 #[openapi(
-    paths(
-        auth_handler::register, auth_handler::login,
-        auth_handler::logout, health_check::health_checker_handler,
-        roles_handler::assign_user_role, roles_handler::create_role,
-        roles_handler::get_roles, roles_handler::get_user_roles,
-        roles_handler::update_role, user_handler::get_user_by_id,
-        user_handler::get_users, user_handler::update_user
-    ),
     components(schemas(
         Status,
-        UserRegisterReqDto,
-        GetUserByIdReqDto,
-        UpdateUserReqDto,
-        LoginReqDto,
-        CreateRoleReqDto,
-        UpdateRoleReqDto,
-        GetUserRolesReqDto,
-        AssignUserRoleReqDto,
-        BaseResDto<Vec<UserRolesResDto>>,
-        BaseResDto<Vec<RoleDto>>,
+        ErrorResDto,
+        SortDir,
+        UserSortBy,
+        RoleSortBy,
+        LoginOutcome,
+        GetMyLoginsReqDto,
+        GetLoginHistoryReqDto,
+        ExportUsersReqDto,
+        DevTokenReqDto,
         BaseResDto<Vec<UserDto>>,
-        GetUserByIdReqDto,
-        UpdateUserReqDto,
+        BaseResDto<Vec<FieldErrorDto>>,
     )),
     tags(
         (name = "Rust Crud Api Learning", description = "Rust Crud Api Learning")
     ),
-    modifiers(&SecurityAddon)
+    modifiers(&CollectedPaths, &ErrorResponses, &SecurityAddon)
 )]
-
 pub struct ApiDoc;
 
 pub struct SecurityAddon;
@@ -79,7 +83,199 @@ impl Modify for SecurityAddon {
       );
     }
 
+    // Resource servers calling /auth/introspect
+    if !components.security_schemes.contains_key("basic_auth") {
+      components.security_schemes.insert(
+        "basic_auth".to_string(),
+        SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Basic).build()),
+      );
+    }
+
     // Restore components with schemas and new security scheme
     openapi.components = Some(components);
   }
 }
+
+// Errors any route can answer with, added to the operations that don't document them
+const SHARED_ERRORS: [(&str, &str); 5] = [
+  ("401", "Token missing, invalid or expired"),
+  ("403", "Permission denied"),
+  ("404", "Not found"),
+  ("409", "Conflicts with existing data"),
+  ("500", "Server error"),
+];
+
+/// Documents error responses with the body the server actually sends, `ErrorResDto`, and lists
+/// the `StatusCodeConst` values as the enum of `Status.code`.
+pub struct ErrorResponses;
+
+impl Modify for ErrorResponses {
+  fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+    let status_ref = RefOr::Ref(Ref::from_schema_name("Status"));
+    let error_body = || {
+      ContentBuilder::new()
+        .schema(Some(Ref::from_schema_name("ErrorResDto")))
+        .build()
+    };
+    for item in openapi.paths.paths.values_mut() {
+      for operation in operations_mut(item) {
+        let responses = &mut operation.responses.responses;
+        // Handlers document errors as `Status`, but it is sent inside the `status` field
+        for (code, response) in responses.iter_mut() {
+          let is_error = code.parse::<u16>().is_ok_and(|code| code >= 400);
+          if let RefOr::T(response) = response
+            && is_error
+            && let Some(content) = response.content.get_mut("application/json")
+            && content.schema.as_ref() == Some(&status_ref)
+          {
+            *content = error_body();
+          }
+        }
+        for (code, description) in SHARED_ERRORS {
+          responses.entry(code.to_string()).or_insert_with(|| {
+            RefOr::T(
+              ResponseBuilder::new()
+                .description(description)
+                .content("application/json", error_body())
+                .build(),
+            )
+          });
+        }
+      }
+    }
+
+    let codes = StatusCodeConst::ALL.map(|code| code.into()).to_vec();
+    if let Some(RefOr::T(Schema::Object(status))) = openapi
+      .components
+      .as_mut()
+      .and_then(|components| components.schemas.get_mut("Status"))
+      && let Some(RefOr::T(Schema::Object(code))) = status.properties.get_mut("code")
+    {
+      code.enum_values = Some(codes);
+    }
+  }
+}
+
+// Handlers document their v1 path, other versions get the same operations re-rooted
+const DOCUMENTED_PREFIX: &str = "/api/v1";
+
+// Verb-in-path routes superseded by the `/users`, `/roles` and `/permissions` resources
+const LEGACY_SCOPES: [&str; 3] = ["/user/", "/role/", "/permission/"];
+
+// Email and password changes and login history have no resource-style replacement yet
+fn is_legacy_alias(path: &str) -> bool {
+  LEGACY_SCOPES.iter().any(|scope| path.starts_with(scope))
+    && !path.ends_with("/change_email")
+    && !path.ends_with("/change_password")
+    && !path.ends_with("/confirm_email")
+    && !path.ends_with("/forgot_password")
+    && !path.ends_with("/reset_password")
+    && !path.ends_with("/me/logins")
+}
+
+fn operations_mut(item: &mut PathItem) -> impl Iterator<Item = &mut Operation> {
+  [
+    &mut item.get,
+    &mut item.put,
+    &mut item.post,
+    &mut item.delete,
+    &mut item.options,
+    &mut item.head,
+    &mut item.patch,
+    &mut item.trace,
+  ]
+  .into_iter()
+  .flatten()
+}
+
+fn mark_deprecated(item: &mut PathItem) {
+  for operation in operations_mut(item) {
+    operation.deprecated = Some(Deprecated::True);
+  }
+}
+
+/// The API document of one version, operations of deprecated versions are flagged.
+pub fn openapi_for(version: ApiVersion) -> utoipa::openapi::OpenApi {
+  let mut openapi = ApiDoc::openapi();
+  let mut paths = Paths::new();
+  for (path, mut item) in std::mem::take(&mut openapi.paths.paths) {
+    let path = match path.strip_prefix(DOCUMENTED_PREFIX) {
+      Some(rest) => {
+        if version.is_deprecated() || is_legacy_alias(rest) {
+          mark_deprecated(&mut item);
+        }
+        format!("{}{}", version.prefix(), rest)
+      }
+      // Probes and other unversioned routes
+      None => path,
+    };
+    paths.paths.insert(path, item);
+  }
+  openapi.paths = paths;
+  openapi.info.title = format!("{} ({})", openapi.info.title, version.name());
+  openapi
+}
+
+// v1 keeps its original document url
+fn doc_url(version: ApiVersion) -> &'static str {
+  match version {
+    ApiVersion::V1 => "/api-docs/openapi.json",
+    ApiVersion::V2 => "/api-docs/v2/openapi.json",
+  }
+}
+
+/// The document of every version, built once and shared by the workers.
+#[derive(Clone)]
+pub struct ApiDocs {
+  // Latest first so Swagger UI opens on it
+  versions: Vec<(ApiVersion, utoipa::openapi::OpenApi)>,
+}
+
+impl ApiDocs {
+  pub fn build() -> Self {
+    let mut versions = ApiVersion::ALL.to_vec();
+    versions.reverse();
+    Self {
+      versions: versions
+        .into_iter()
+        .map(|version| (version, openapi_for(version)))
+        .collect(),
+    }
+  }
+
+  fn latest(&self) -> utoipa::openapi::OpenApi {
+    self.versions[0].1.clone()
+  }
+}
+
+/// Mounts the documentation pages turned on in `docs`, under `docs_access::DOCS_PREFIXES`.
+/// Swagger UI also serves the OpenAPI documents; when it is off they get their own routes so
+/// RapiDoc can still load them.
+pub fn configure(cfg: &mut web::ServiceConfig, setting: &DocsSetting, docs: &ApiDocs) {
+  if setting.redoc {
+    cfg.service(Redoc::with_url("/redoc", docs.latest()));
+  }
+  if setting.rapidoc {
+    cfg.service(RapiDoc::new(doc_url(ApiVersion::LATEST)).path("/rapidoc"));
+  }
+  if setting.swagger_ui {
+    let urls = docs
+      .versions
+      .iter()
+      .map(|(version, doc)| (Url::new(version.name(), doc_url(*version)), doc.clone()))
+      .collect();
+    cfg.service(SwaggerUi::new("/swagger-ui/{_:.*}").urls(urls));
+  } else if setting.rapidoc {
+    for (version, doc) in &docs.versions {
+      cfg.service(
+        web::resource(doc_url(*version))
+          .app_data(web::Data::new(doc.clone()))
+          .route(web::get().to(serve_doc)),
+      );
+    }
+  }
+}
+
+async fn serve_doc(doc: web::Data<utoipa::openapi::OpenApi>) -> HttpResponse {
+  HttpResponse::Ok().json(doc.as_ref())
+}
@@ -0,0 +1,89 @@
+//! Handlers documented with `#[utoipa::path]` add themselves to `ApiDoc` with `document!`, so a
+//! new endpoint shows up in the docs without also being listed in `swaggers/mod.rs`.
+
+use utoipa::{
+  Modify,
+  openapi::{
+    RefOr,
+    path::{HttpMethod, Operation},
+    schema::Schema,
+  },
+};
+
+#[doc(hidden)]
+pub use inventory;
+#[doc(hidden)]
+pub use paste;
+
+type Schemas = Vec<(String, RefOr<Schema>)>;
+
+/// The OpenAPI operation of one handler, submitted by `document!`.
+pub struct DocumentedPath {
+  path: fn() -> String,
+  methods: fn() -> Vec<HttpMethod>,
+  operation: fn() -> Operation,
+  tags: fn() -> Vec<&'static str>,
+  schemas: fn(&mut Schemas),
+}
+
+impl DocumentedPath {
+  pub const fn of<P>() -> Self
+  where
+    P: utoipa::Path + utoipa::__dev::Tags<'static> + utoipa::__dev::SchemaReferences,
+  {
+    DocumentedPath {
+      path: P::path,
+      methods: P::methods,
+      operation: P::operation,
+      tags: P::tags,
+      schemas: P::schemas,
+    }
+  }
+}
+
+inventory::collect!(DocumentedPath);
+
+/// Adds the `#[utoipa::path]` handlers named in the current module to `ApiDoc`, together with
+/// the schemas of their request and response bodies.
+///
+/// ```ignore
+/// document!(get_me);
+/// #[utoipa::path(get, path = "/api/v1/user/me", ...)]
+/// pub async fn get_me(...) -> impl Responder { ... }
+/// ```
+#[macro_export]
+macro_rules! document {
+  ($($handler:ident),+ $(,)?) => {
+    $crate::swaggers::registry::paste::paste! {
+      $(
+        $crate::swaggers::registry::inventory::submit! {
+          $crate::swaggers::registry::DocumentedPath::of::<[<__path_ $handler>]>()
+        }
+      )+
+    }
+  };
+}
+
+/// Merges every submitted handler into the document, schemas listed on `ApiDoc` win.
+pub struct CollectedPaths;
+
+impl Modify for CollectedPaths {
+  fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+    let mut schemas = Schemas::new();
+    for documented in inventory::iter::<DocumentedPath> {
+      // The `tag` of `#[utoipa::path]` is kept apart from the operation
+      let mut operation = (documented.operation)();
+      let tags = operation.tags.get_or_insert_with(Vec::new);
+      tags.extend((documented.tags)().into_iter().map(str::to_string));
+      openapi
+        .paths
+        .add_path_operation((documented.path)(), (documented.methods)(), operation);
+      (documented.schemas)(&mut schemas);
+    }
+
+    let components = openapi.components.get_or_insert_with(Default::default);
+    for (name, schema) in schemas {
+      components.schemas.entry(name).or_insert(schema);
+    }
+  }
+}
@@ -0,0 +1,82 @@
+//! Askama templates, compiled in from `templates/` so a broken one fails the build.
+//! Emails have one set of files per locale: `email/<tag>.subject.txt`, `<tag>.txt` and
+//! `<tag>.html`, each matching on `EmailTemplate` the way `i18n` matches on `StatusMessage`.
+
+use askama::Template;
+
+use crate::{i18n::Locale, utils::mailer::EmailTemplate};
+
+/// An email rendered in one locale, `html` goes next to `text` as an alternative part.
+#[derive(Debug, Clone)]
+pub struct RenderedEmail {
+  pub subject: String,
+  pub text: String,
+  pub html: String,
+}
+
+#[derive(Template)]
+#[template(path = "email/en.subject.txt")]
+struct EnSubject<'a> {
+  email: &'a EmailTemplate,
+}
+
+#[derive(Template)]
+#[template(path = "email/en.txt")]
+struct EnText<'a> {
+  email: &'a EmailTemplate,
+}
+
+#[derive(Template)]
+#[template(path = "email/en.html")]
+struct EnHtml<'a> {
+  email: &'a EmailTemplate,
+  lang: &'a str,
+  subject: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "email/km.subject.txt")]
+struct KmSubject<'a> {
+  email: &'a EmailTemplate,
+}
+
+#[derive(Template)]
+#[template(path = "email/km.txt")]
+struct KmText<'a> {
+  email: &'a EmailTemplate,
+}
+
+#[derive(Template)]
+#[template(path = "email/km.html")]
+struct KmHtml<'a> {
+  email: &'a EmailTemplate,
+  lang: &'a str,
+  subject: &'a str,
+}
+
+pub fn render_email(email: &EmailTemplate, locale: Locale) -> askama::Result<RenderedEmail> {
+  let lang = locale.tag();
+  let (subject, text) = match locale {
+    Locale::En => (EnSubject { email }.render()?, EnText { email }.render()?),
+    Locale::Km => (KmSubject { email }.render()?, KmText { email }.render()?),
+  };
+  let html = match locale {
+    Locale::En => EnHtml {
+      email,
+      lang,
+      subject: &subject,
+    }
+    .render()?,
+    Locale::Km => KmHtml {
+      email,
+      lang,
+      subject: &subject,
+    }
+    .render()?,
+  };
+  Ok(RenderedEmail {
+    subject,
+    text,
+    html,
+  })
+}
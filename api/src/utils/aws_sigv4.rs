@@ -0,0 +1,95 @@
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+
+/// Credentials from the standard AWS_* environment variables, or from config.
+#[derive(Clone)]
+pub struct AwsCredentials {
+  pub access_key_id: String,
+  pub secret_access_key: String,
+  pub session_token: Option<String>,
+}
+
+pub fn hex(bytes: &[u8]) -> String {
+  bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Hex SHA-256 of a request body, as sent in `x-amz-content-sha256`.
+pub fn payload_hash(body: &[u8]) -> String {
+  hex(&Sha256::digest(body))
+}
+
+/// `x-amz-date` value for `now`.
+pub fn amz_date(now: DateTime<Utc>) -> String {
+  now.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+fn hmac_sha256(key: &[u8], data: &str) -> Vec<u8> {
+  let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key length");
+  mac.update(data.as_bytes());
+  mac.finalize().into_bytes().to_vec()
+}
+
+/// One request to sign with Signature Version 4.
+pub struct SignedRequest<'a> {
+  pub method: &'a str,
+  /// URI-encoded path, e.g. `/bucket/avatars/1`
+  pub canonical_uri: &'a str,
+  /// Sorted and URI-encoded query string, empty when there is none
+  pub canonical_query: &'a str,
+  /// Lowercase names sorted by name, `host` and `x-amz-date` included
+  pub headers: &'a [(&'a str, String)],
+  pub payload_hash: &'a str,
+}
+
+impl SignedRequest<'_> {
+  /// The `Authorization` header value for `service` in `region`, signed at `now`.
+  pub fn authorization(
+    &self,
+    credentials: &AwsCredentials,
+    region: &str,
+    service: &str,
+    now: DateTime<Utc>,
+  ) -> String {
+    let date = now.format("%Y%m%d").to_string();
+    let canonical_headers: String = self
+      .headers
+      .iter()
+      .map(|(name, value)| format!("{}:{}\n", name, value.trim()))
+      .collect();
+    let signed_headers = self
+      .headers
+      .iter()
+      .map(|(name, _)| *name)
+      .collect::<Vec<_>>()
+      .join(";");
+    let canonical_request = format!(
+      "{}\n{}\n{}\n{}\n{}\n{}",
+      self.method,
+      self.canonical_uri,
+      self.canonical_query,
+      canonical_headers,
+      signed_headers,
+      self.payload_hash
+    );
+    let scope = format!("{}/{}/{}/aws4_request", date, region, service);
+    let string_to_sign = format!(
+      "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+      amz_date(now),
+      scope,
+      hex(&Sha256::digest(canonical_request.as_bytes()))
+    );
+
+    let key = [date.as_str(), region, service, "aws4_request"]
+      .iter()
+      .fold(
+        format!("AWS4{}", credentials.secret_access_key).into_bytes(),
+        |key, part| hmac_sha256(&key, part),
+      );
+    let signature = hex(&hmac_sha256(&key, &string_to_sign));
+    format!(
+      "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+      credentials.access_key_id, scope, signed_headers, signature
+    )
+  }
+}
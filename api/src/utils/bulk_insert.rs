@@ -0,0 +1,78 @@
+use anyhow::Result;
+use domner_tech_sql_client::{CommandType, UnifiedToSql};
+
+use crate::db::{DbConnection, SqlRepo};
+
+// SQL Server allows 2100 parameters per request, keep headroom for the driver
+const MAX_PARAMS_PER_STATEMENT: usize = 2000;
+// A single table value constructor accepts at most 1000 rows
+const MAX_ROWS_PER_STATEMENT: usize = 1000;
+
+fn quote_identifier(name: &str) -> Result<String> {
+  let parts: Vec<&str> = name.split('.').collect();
+  if parts
+    .iter()
+    .any(|p| p.is_empty() || !p.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'))
+  {
+    return Err(anyhow::anyhow!("Invalid SQL identifier '{}'", name));
+  }
+  Ok(
+    parts
+      .iter()
+      .map(|p| format!("[{}]", p))
+      .collect::<Vec<_>>()
+      .join("."),
+  )
+}
+
+/// Insert `rows` into `table` with every value bound as a parameter.
+/// Large batches are split so each statement stays under SQL Server's parameter and row limits.
+/// Returns the total number of affected rows.
+pub async fn execute_bulk_insert(
+  client: &mut DbConnection,
+  table: &str,
+  columns: &[&str],
+  rows: &[Vec<&dyn UnifiedToSql>],
+) -> Result<u64> {
+  if columns.is_empty() {
+    return Err(anyhow::anyhow!(
+      "Bulk insert into '{}' needs columns",
+      table
+    ));
+  }
+  if let Some(row) = rows.iter().find(|row| row.len() != columns.len()) {
+    return Err(anyhow::anyhow!(
+      "Bulk insert row has {} values, expected {}",
+      row.len(),
+      columns.len()
+    ));
+  }
+
+  let table = quote_identifier(table)?;
+  let column_list = columns
+    .iter()
+    .map(|c| quote_identifier(c))
+    .collect::<Result<Vec<_>>>()?
+    .join(", ");
+  let rows_per_chunk = (MAX_PARAMS_PER_STATEMENT / columns.len()).clamp(1, MAX_ROWS_PER_STATEMENT);
+
+  let mut total = 0;
+  for chunk in rows.chunks(rows_per_chunk) {
+    let values = (0..chunk.len())
+      .map(|row_index| {
+        let placeholders = (1..=columns.len())
+          .map(|col| format!("@P{}", row_index * columns.len() + col))
+          .collect::<Vec<_>>()
+          .join(", ");
+        format!("({})", placeholders)
+      })
+      .collect::<Vec<_>>()
+      .join(", ");
+    let query = format!("INSERT INTO {} ({}) VALUES {}", table, column_list, values);
+    let params: Vec<&dyn UnifiedToSql> = chunk.iter().flatten().copied().collect();
+
+    total +=
+      SqlRepo::execute_command_none_query(client, &query, &params, CommandType::Text).await?;
+  }
+  Ok(total)
+}
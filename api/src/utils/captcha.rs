@@ -0,0 +1,128 @@
+use std::net::IpAddr;
+
+use actix_web::HttpRequest;
+use anyhow::Result;
+use futures::{FutureExt, future::BoxFuture};
+use serde::Deserialize;
+
+use crate::{
+  app_settings::{CaptchaProvider, CaptchaSetting},
+  app_state::AppState,
+  dto::base_res_dto::Status,
+  middleware::rate_limit::client_ip,
+};
+
+/// Checks the response token of a CAPTCHA widget. Set one on `AppState::captcha` to use a
+/// provider other than the built-in ones.
+pub trait CaptchaVerifier: Send + Sync {
+  /// `Ok(false)` when the provider rejected the token.
+  fn verify<'a>(&'a self, token: &'a str, remote_ip: Option<IpAddr>)
+  -> BoxFuture<'a, Result<bool>>;
+}
+
+#[derive(Deserialize)]
+struct SiteVerifyResponse {
+  success: bool,
+  // Only sent by reCAPTCHA v3
+  score: Option<f64>,
+}
+
+// reCAPTCHA, hCaptcha and Turnstile share the same siteverify API
+pub struct SiteVerifyClient {
+  url: &'static str,
+  secret_key: String,
+  min_score: Option<f64>,
+  client: reqwest::Client,
+}
+
+impl SiteVerifyClient {
+  pub fn new(setting: &CaptchaSetting) -> Self {
+    let url = match setting.provider {
+      CaptchaProvider::Recaptcha => "https://www.google.com/recaptcha/api/siteverify",
+      CaptchaProvider::Hcaptcha => "https://api.hcaptcha.com/siteverify",
+      CaptchaProvider::Turnstile => "https://challenges.cloudflare.com/turnstile/v0/siteverify",
+    };
+    Self {
+      url,
+      secret_key: setting.secret_key.clone(),
+      min_score: setting.min_score,
+      client: reqwest::Client::new(),
+    }
+  }
+
+  async fn post(&self, token: &str, remote_ip: Option<IpAddr>) -> Result<bool> {
+    let remote_ip = remote_ip.map(|ip| ip.to_string());
+    let mut form = vec![("secret", self.secret_key.as_str()), ("response", token)];
+    if let Some(remote_ip) = &remote_ip {
+      form.push(("remoteip", remote_ip));
+    }
+    let answer: SiteVerifyResponse = self
+      .client
+      .post(self.url)
+      .form(&form)
+      .send()
+      .await?
+      .error_for_status()?
+      .json()
+      .await?;
+    let score_ok = match (self.min_score, answer.score) {
+      (Some(min_score), Some(score)) => score >= min_score,
+      _ => true,
+    };
+    Ok(answer.success && score_ok)
+  }
+}
+
+impl CaptchaVerifier for SiteVerifyClient {
+  fn verify<'a>(
+    &'a self,
+    token: &'a str,
+    remote_ip: Option<IpAddr>,
+  ) -> BoxFuture<'a, Result<bool>> {
+    self.post(token, remote_ip).boxed()
+  }
+}
+
+/// Verifies the CAPTCHA token `req` carries in `captcha.token_header`. Passes when CAPTCHA
+/// is off in this environment.
+pub async fn require_captcha(data: &AppState, req: &HttpRequest) -> Result<(), Status> {
+  let Some(verifier) = &data.captcha else {
+    return Ok(());
+  };
+  let token = req
+    .headers()
+    .get(&data.config.captcha.token_header)
+    .and_then(|value| value.to_str().ok())
+    .map(str::trim)
+    .filter(|token| !token.is_empty())
+    .ok_or_else(Status::captcha_required)?;
+  let remote_ip = client_ip(req, &data.runtime().rate_limit);
+  match verifier.verify(token, remote_ip).await {
+    Ok(true) => Ok(()),
+    Ok(false) => Err(Status::captcha_required()),
+    // Fails closed, an outage of the provider shouldn't open the door to bots
+    Err(e) => Err(Status::server_error(format!(
+      "CAPTCHA verification failed: {}",
+      e
+    ))),
+  }
+}
+
+/// Whether logins as `user_name` need a CAPTCHA after the failures recorded so far.
+pub fn login_needs_captcha(data: &AppState, user_name: &str) -> bool {
+  data.captcha.is_some()
+    && data
+      .failed_logins
+      .get(&user_name.to_lowercase())
+      .unwrap_or_default()
+      >= data.config.captcha.failed_logins_before_captcha
+}
+
+/// Counts a failed login towards `captcha.failed_logins_before_captcha`.
+pub fn record_failed_login(data: &AppState, user_name: &str) {
+  if data.captcha.is_some() {
+    let key = user_name.to_lowercase();
+    let failures = data.failed_logins.get(&key).unwrap_or_default();
+    data.failed_logins.set(key, failures + 1);
+  }
+}
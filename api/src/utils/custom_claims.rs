@@ -0,0 +1,42 @@
+use std::collections::BTreeMap;
+
+use serde_json::Value;
+
+use crate::features::users::user_dto::UserDto;
+
+/// Application claims carried next to the standard ones, e.g. `{"tenant": "acme"}`.
+pub type CustomClaims = BTreeMap<String, Value>;
+
+/// Claims the token itself is made of, custom claims can't replace them.
+pub const RESERVED_CLAIMS: [&str; 12] = [
+  "sub",
+  "exp",
+  "iat",
+  "nbf",
+  "jti",
+  "iss",
+  "aud",
+  "roles",
+  "permissions",
+  "act_as",
+  "act_exp",
+  "dfp",
+];
+
+/// Adds application claims to every token minted for `user`, so services reading the token
+/// get that context without calling back. Set one on `AppState::claims_enricher`; it runs
+/// after `jwt.static_claims` were added and may change or remove them.
+pub trait ClaimsEnricher: Send + Sync {
+  fn enrich(&self, user: &UserDto, claims: &mut CustomClaims);
+}
+
+/// Default enricher, tokens only get `jwt.static_claims`.
+pub struct NoClaimsEnricher;
+
+impl ClaimsEnricher for NoClaimsEnricher {
+  fn enrich(&self, _: &UserDto, _: &mut CustomClaims) {}
+}
+
+pub fn is_reserved(name: &str) -> bool {
+  RESERVED_CLAIMS.contains(&name)
+}
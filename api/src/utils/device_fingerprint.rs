@@ -0,0 +1,22 @@
+use actix_web::{HttpRequest, http::header};
+use sha2::{Digest, Sha256};
+
+use crate::app_settings::DeviceBindingSetting;
+
+/// Hex SHA-256 of the request's User-Agent and `client_hint_header`, a missing one counts as
+/// empty. Only the hash goes in the token, so it doesn't reveal the device.
+pub fn fingerprint(req: &HttpRequest, setting: &DeviceBindingSetting) -> String {
+  let header_value = |name: &str| {
+    req
+      .headers()
+      .get(name)
+      .map(|value| value.as_bytes().to_vec())
+      .unwrap_or_default()
+  };
+  let mut hasher = Sha256::new();
+  hasher.update(header_value(header::USER_AGENT.as_str()));
+  // Keeps "ab" + "c" apart from "a" + "bc"
+  hasher.update([0]);
+  hasher.update(header_value(&setting.client_hint_header));
+  format!("{:x}", hasher.finalize())
+}
@@ -0,0 +1,56 @@
+use std::collections::HashMap;
+
+use crate::{app_settings::FeatureFlagSetting, features::users::user_dto::UserDto};
+
+// FNV-1a over flag name + user id, stable across restarts and builds so a user
+// keeps the same bucket while a rollout percentage is ramped up
+fn rollout_bucket(flag: &str, user_id: i32) -> u8 {
+  let mut hash: u32 = 0x811c9dc5;
+  for byte in flag.bytes().chain(user_id.to_le_bytes()) {
+    hash ^= byte as u32;
+    hash = hash.wrapping_mul(0x01000193);
+  }
+  (hash % 100) as u8
+}
+
+pub fn is_enabled(
+  flags: &HashMap<String, FeatureFlagSetting>,
+  flag: &str,
+  user: Option<&UserDto>,
+) -> bool {
+  let Some(setting) = flags.get(flag) else {
+    return true;
+  };
+  if !setting.enabled {
+    return false;
+  }
+
+  if let Some(user) = user
+    && setting
+      .roles
+      .iter()
+      .any(|r| r.eq_ignore_ascii_case(user.role.to_str()))
+  {
+    return true;
+  }
+
+  match setting.percentage {
+    Some(percentage) => match user {
+      Some(user) => rollout_bucket(flag, user.id) < percentage,
+      None => percentage >= 100,
+    },
+    // Role-only rollout
+    None => setting.roles.is_empty(),
+  }
+}
+
+// Configured flags that are on for the user, for clients to toggle their UI
+pub fn enabled_flags(flags: &HashMap<String, FeatureFlagSetting>, user: &UserDto) -> Vec<String> {
+  let mut enabled: Vec<String> = flags
+    .keys()
+    .filter(|flag| is_enabled(flags, flag, Some(user)))
+    .cloned()
+    .collect();
+  enabled.sort();
+  enabled
+}
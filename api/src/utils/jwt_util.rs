@@ -1,12 +1,23 @@
 use crate::{
   app_settings::JwtSetting,
+  app_state::AppState,
   features::{auth::auth_dto::Claims, users::user_dto::UserDto},
+  utils::{
+    custom_claims::{self, ClaimsEnricher, CustomClaims, NoClaimsEnricher},
+    device_fingerprint,
+  },
 };
+use actix_web::HttpRequest;
 use anyhow::Result;
 use chrono::{Duration, Utc};
 use jsonwebtoken::{Algorithm, EncodingKey, Header, Validation, decode, encode};
 pub struct JwtUtil<'a> {
   pub jwt_config: &'a JwtSetting,
+  enricher: &'a dyn ClaimsEnricher,
+  // Picks the `accepted_by_environment` entry, `None` only accepts `accepted`
+  environment: Option<&'a str>,
+  // Put in the `dfp` claim of minted tokens, see `bound_to_device`
+  device: Option<String>,
 }
 
 impl<'a> JwtUtil<'a> {
@@ -17,13 +28,16 @@ impl<'a> JwtUtil<'a> {
   /// * `JwtUtil` - A new instance of JwtUtil.
   /// # Example
   /// ```
-  /// let jwt_settings = JwtSetting {
-  ///   secret_key: "your_secret_key".to_string(),
-  ///   expiration_minutes: 60,
-  ///   issuer: "your_issuer".to_string(),
-  ///   audience: "your_audience".to_string(),
-  /// };
-  /// let jwt_util = JwtUtil::new(jwt_settings);
+  /// # use api::{app_settings::JwtSetting, utils::jwt_util::JwtUtil};
+  /// let jwt_settings: JwtSetting = serde_json::from_str(
+  ///   r#"{
+  ///     "secret_key": "your_secret_key",
+  ///     "expiration_minutes": 60,
+  ///     "issuer": "your_issuer",
+  ///     "audience": "your_audience"
+  ///   }"#,
+  /// ).unwrap();
+  /// let jwt_util = JwtUtil::new(&jwt_settings);
   /// ```
   /// # Errors
   /// This function does not return errors.
@@ -40,23 +54,101 @@ impl<'a> JwtUtil<'a> {
   /// # Date
   /// * 2025-08-25
   pub fn new(jwt_config: &'a JwtSetting) -> Self {
-    Self { jwt_config }
+    Self {
+      jwt_config,
+      enricher: &NoClaimsEnricher,
+      environment: None,
+      device: None,
+    }
+  }
+
+  /// Create a JwtUtil that also runs the application's claims enricher on minted tokens
+  /// and validates against the issuers and audiences accepted in the current environment.
+  /// # Arguments
+  /// * `app_state` - The app state holding the JWT settings and `claims_enricher`.
+  /// # Returns
+  /// * `JwtUtil` - A new instance of JwtUtil.
+  /// # Example
+  /// ```no_run
+  /// # use api::{app_state::AppState, features::users::user_dto::UserDto, utils::jwt_util::JwtUtil};
+  /// # fn example(data: &AppState, user: &UserDto, permissions: &[String]) -> anyhow::Result<()> {
+  /// let token = JwtUtil::from_state(data).create_token(user, permissions)?;
+  /// # Ok(())
+  /// # }
+  /// ```
+  /// # Notes
+  /// Prefer it over `new` inside the server.
+  pub fn from_state(app_state: &'a AppState) -> Self {
+    Self {
+      jwt_config: &app_state.config.jwt,
+      enricher: app_state.claims_enricher.as_ref(),
+      environment: Some(&app_state.config.environment),
+      device: None,
+    }
+  }
+
+  /// Bind the tokens it creates to the device `req` comes from, when `jwt.device_binding` is on.
+  /// # Arguments
+  /// * `req` - The request the token is issued for, e.g. the login request.
+  /// # Returns
+  /// * `JwtUtil` - The same JwtUtil, minting tokens with the `dfp` claim.
+  /// # Example
+  /// ```no_run
+  /// # use actix_web::HttpRequest;
+  /// # use api::{app_state::AppState, features::users::user_dto::UserDto, utils::jwt_util::JwtUtil};
+  /// # fn example(data: &AppState, http_req: &HttpRequest, user: &UserDto) -> anyhow::Result<()> {
+  /// let token = JwtUtil::from_state(data)
+  ///   .bound_to_device(http_req)
+  ///   .create_token(user, &[])?;
+  /// # Ok(())
+  /// # }
+  /// ```
+  /// # Notes
+  /// The auth middleware rejects a bound token sent from another device.
+  pub fn bound_to_device(mut self, req: &HttpRequest) -> Self {
+    let binding = &self.jwt_config.device_binding;
+    if binding.enabled {
+      self.device = Some(device_fingerprint::fingerprint(req, binding));
+    }
+    self
+  }
+
+  // `jwt.static_claims` first, then the enricher, reserved names are dropped from both
+  fn custom_claims(&self, user: &UserDto) -> CustomClaims {
+    let mut claims = self.jwt_config.static_claims.clone();
+    self.enricher.enrich(user, &mut claims);
+    claims.retain(|name, _| !custom_claims::is_reserved(name));
+    claims
   }
 
   /// Create a JWT token for the given user.
   /// # Arguments
   /// * `user` - A reference to a UserDto struct representing the user for whom the token is to be created.
+  /// * `permissions` - The user's effective permissions, embedded in the `permissions` claim.
   /// # Returns
   /// * `Result<String>` - A Result containing the JWT token as a String if successful, or an error if the token creation fails.
   /// # Example
   /// ```
+  /// # use api::{
+  /// #   app_settings::JwtSetting,
+  /// #   features::users::{user_dto::UserDto, user_entity::UserRole},
+  /// #   utils::jwt_util::JwtUtil,
+  /// # };
+  /// # let jwt_settings: JwtSetting = serde_json::from_str(
+  /// #   r#"{"secret_key": "secret", "expiration_minutes": 60, "issuer": "api", "audience": "web"}"#,
+  /// # ).unwrap();
+  /// # let jwt_util = JwtUtil::new(&jwt_settings);
   /// let user = UserDto {
   ///   id: 1,
   ///   user_name: "testuser".to_string(),
   ///   name: "Test User".to_string(),
   ///   email: "test@gmail.com".to_string(),
+  ///   role: UserRole::User,
+  ///   is_active: true,
+  ///   roles: vec!["user".to_string()],
+  ///   last_login_at: None,
   /// };
-  /// let token = jwt_util.create_token(&user);
+  /// let token = jwt_util.create_token(&user, &["user:read".to_string()]).unwrap();
   /// ```
   /// # Errors
   /// This function returns an error if the token creation fails.
@@ -72,9 +164,53 @@ impl<'a> JwtUtil<'a> {
   /// * ROS Sokcheanith
   /// # Date
   /// * 2025-08-25
-  pub fn create_token(&self, user: &UserDto) -> Result<String> {
+  pub fn create_token(&self, user: &UserDto, permissions: &[String]) -> Result<String> {
+    self.create_token_with_expiration(user, permissions, self.jwt_config.expiration_minutes as i64)
+  }
+
+  /// Create a JWT token for the given user that expires after the given number of minutes.
+  /// # Arguments
+  /// * `user` - A reference to a UserDto struct representing the user for whom the token is to be created.
+  /// * `permissions` - The user's effective permissions, embedded in the `permissions` claim.
+  /// * `expiration_minutes` - The lifetime of the token in minutes, overriding the configured value.
+  /// # Returns
+  /// * `Result<String>` - A Result containing the JWT token as a String if successful, or an error if the token creation fails.
+  /// # Example
+  /// ```
+  /// # use api::{
+  /// #   app_settings::JwtSetting,
+  /// #   features::users::{user_dto::UserDto, user_entity::UserRole},
+  /// #   utils::jwt_util::JwtUtil,
+  /// # };
+  /// # let jwt_settings: JwtSetting = serde_json::from_str(
+  /// #   r#"{"secret_key": "secret", "expiration_minutes": 60, "issuer": "api", "audience": "web"}"#,
+  /// # ).unwrap();
+  /// # let jwt_util = JwtUtil::new(&jwt_settings);
+  /// # let user = UserDto {
+  /// #   id: 1,
+  /// #   user_name: "testuser".to_string(),
+  /// #   name: "Test User".to_string(),
+  /// #   email: "test@gmail.com".to_string(),
+  /// #   role: UserRole::User,
+  /// #   is_active: true,
+  /// #   roles: vec!["user".to_string()],
+  /// #   last_login_at: None,
+  /// # };
+  /// let token = jwt_util.create_token_with_expiration(&user, &[], 15).unwrap();
+  /// assert_eq!(jwt_util.decode_token(&token).unwrap().sub, 1);
+  /// ```
+  /// # Errors
+  /// This function returns an error if the token creation fails.
+  /// # Notes
+  /// Used for short-lived tokens such as the dev token endpoint.
+  pub fn create_token_with_expiration(
+    &self,
+    user: &UserDto,
+    permissions: &[String],
+    expiration_minutes: i64,
+  ) -> Result<String> {
     let expiration = Utc::now()
-      .checked_add_signed(Duration::minutes(self.jwt_config.expiration_minutes as i64))
+      .checked_add_signed(Duration::minutes(expiration_minutes))
       .expect("Invalid expiration time")
       .timestamp();
     let claims = Claims {
@@ -82,6 +218,85 @@ impl<'a> JwtUtil<'a> {
       exp: expiration as usize,
       iss: self.jwt_config.issuer.clone(),
       aud: self.jwt_config.audience.clone(),
+      roles: user.roles.clone(),
+      permissions: permissions.to_vec(),
+      act_as: None,
+      act_exp: None,
+      dfp: self.device.clone(),
+      custom: self.custom_claims(user),
+    };
+
+    let token = encode(
+      &Header::new(Algorithm::HS256),
+      &claims,
+      &EncodingKey::from_secret(self.jwt_config.secret_key.as_ref()),
+    )?;
+    Ok(token)
+  }
+
+  /// Create a short-lived token letting an admin act as another user.
+  /// # Arguments
+  /// * `admin_id` - The id of the admin impersonating, kept in the `sub` claim.
+  /// * `user` - The user to act as, kept in the `act_as` claim.
+  /// * `permissions` - The impersonated user's effective permissions.
+  /// * `admin_expires_at` - Expiry of the admin's own token, the impersonation token never
+  ///   outlives it and /admin/stop_impersonation hands back a token expiring at that time.
+  /// # Returns
+  /// * `Result<String>` - A Result containing the JWT token as a String if successful, or an error if the token creation fails.
+  /// # Example
+  /// ```
+  /// # use api::{
+  /// #   app_settings::JwtSetting,
+  /// #   features::users::{user_dto::UserDto, user_entity::UserRole},
+  /// #   utils::jwt_util::JwtUtil,
+  /// # };
+  /// # let jwt_settings: JwtSetting = serde_json::from_str(
+  /// #   r#"{"secret_key": "secret", "expiration_minutes": 60, "issuer": "api", "audience": "web"}"#,
+  /// # ).unwrap();
+  /// # let jwt_util = JwtUtil::new(&jwt_settings);
+  /// # let user = UserDto {
+  /// #   id: 1,
+  /// #   user_name: "testuser".to_string(),
+  /// #   name: "Test User".to_string(),
+  /// #   email: "test@gmail.com".to_string(),
+  /// #   role: UserRole::User,
+  /// #   is_active: true,
+  /// #   roles: vec!["user".to_string()],
+  /// #   last_login_at: None,
+  /// # };
+  /// let token = jwt_util.create_impersonation_token(2, &user, &[], None).unwrap();
+  /// let claims = jwt_util.decode_token(&token).unwrap();
+  /// assert_eq!((claims.sub, claims.act_as), (2, Some(1)));
+  /// ```
+  /// # Errors
+  /// This function returns an error if the token creation fails.
+  /// # Notes
+  /// The token expires after `impersonation_minutes` at most, the middleware checks that `sub`
+  /// is still an admin on every request.
+  pub fn create_impersonation_token(
+    &self,
+    admin_id: i32,
+    user: &UserDto,
+    permissions: &[String],
+    admin_expires_at: Option<usize>,
+  ) -> Result<String> {
+    let expiration = Utc::now()
+      .checked_add_signed(Duration::minutes(
+        self.jwt_config.impersonation_minutes as i64,
+      ))
+      .expect("Invalid expiration time")
+      .timestamp() as usize;
+    let claims = Claims {
+      sub: admin_id,
+      exp: admin_expires_at.map_or(expiration, |admin_exp| expiration.min(admin_exp)),
+      iss: self.jwt_config.issuer.clone(),
+      aud: self.jwt_config.audience.clone(),
+      roles: user.roles.clone(),
+      permissions: permissions.to_vec(),
+      act_as: Some(user.id),
+      act_exp: admin_expires_at,
+      dfp: self.device.clone(),
+      custom: self.custom_claims(user),
     };
 
     let token = encode(
@@ -99,11 +314,34 @@ impl<'a> JwtUtil<'a> {
   /// * `Result<Claims>` - A Result containing the Claims struct if the token is valid, or an error if the token is invalid or decoding fails.
   /// # Example
   /// ```
-  /// let token = "your_jwt_token";
-  /// let claims = jwt_util.decode_token(token);
+  /// # use api::{
+  /// #   app_settings::JwtSetting,
+  /// #   features::users::{user_dto::UserDto, user_entity::UserRole},
+  /// #   utils::jwt_util::JwtUtil,
+  /// # };
+  /// # let jwt_settings: JwtSetting = serde_json::from_str(
+  /// #   r#"{"secret_key": "secret", "expiration_minutes": 60, "issuer": "api", "audience": "web"}"#,
+  /// # ).unwrap();
+  /// # let jwt_util = JwtUtil::new(&jwt_settings);
+  /// # let user = UserDto {
+  /// #   id: 1,
+  /// #   user_name: "testuser".to_string(),
+  /// #   name: "Test User".to_string(),
+  /// #   email: "test@gmail.com".to_string(),
+  /// #   role: UserRole::User,
+  /// #   is_active: true,
+  /// #   roles: vec!["user".to_string()],
+  /// #   last_login_at: None,
+  /// # };
+  /// let token = jwt_util.create_token(&user, &[]).unwrap();
+  /// let claims = jwt_util.decode_token(&token).unwrap();
+  /// assert_eq!(claims.sub, user.id);
+  /// assert!(jwt_util.decode_token("your_jwt_token").is_err());
   /// ```
   /// # Errors
   /// This function returns an error if the token is invalid or decoding fails.
+  /// # Notes
+  /// The token's issuer and audience must be among `JwtSetting::accepted_for`.
   /// # Panics
   /// This function does not panic.
   /// # Safety
@@ -118,8 +356,11 @@ impl<'a> JwtUtil<'a> {
   /// * 2025-08-25
   pub fn decode_token(&self, token: &str) -> Result<Claims> {
     let mut validation = Validation::new(Algorithm::HS256);
-    validation.set_audience(&[self.jwt_config.audience.as_str()]);
-    validation.set_issuer(&[self.jwt_config.issuer.clone()]);
+    let accepted = self
+      .jwt_config
+      .accepted_for(self.environment.unwrap_or_default());
+    validation.set_audience(&accepted.audiences);
+    validation.set_issuer(&accepted.issuers);
     let key = &jsonwebtoken::DecodingKey::from_secret(self.jwt_config.secret_key.as_ref());
     let token_data = decode::<Claims>(token, key, &validation);
     match token_data {
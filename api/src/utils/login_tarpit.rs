@@ -0,0 +1,154 @@
+use std::{
+  fmt::Write,
+  net::IpAddr,
+  sync::{
+    Arc,
+    atomic::{AtomicU64, Ordering},
+  },
+  time::Duration,
+};
+
+use crate::{
+  app_settings::SecuritySetting,
+  utils::{redis_cache::RedisCache, ttl_cache::TtlMap},
+};
+
+/// What to do with a login attempt from an IP, given its recent failures.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TarpitVerdict {
+  Allow,
+  /// Answer only after waiting this long
+  Delay(Duration),
+  /// Refuse without checking the password, with the seconds to put in `Retry-After`
+  Block(u64),
+}
+
+/// Adaptive delays for client IPs that keep failing `/auth/login`. Failures are counted in
+/// Redis when configured, so every instance slows the same IP down, and per instance otherwise.
+/// Cheap to clone, every clone shares the counts and totals.
+#[derive(Clone)]
+pub struct LoginTarpit {
+  setting: SecuritySetting,
+  failures: TtlMap<IpAddr, u32>,
+  redis: Option<RedisCache>,
+  delayed: Arc<AtomicU64>,
+  blocked: Arc<AtomicU64>,
+}
+
+impl LoginTarpit {
+  pub fn new(setting: &SecuritySetting, redis: Option<RedisCache>) -> Self {
+    Self {
+      setting: setting.clone(),
+      failures: TtlMap::new(Duration::from_secs(setting.login_tarpit_window_secs)),
+      redis,
+      delayed: Arc::new(AtomicU64::new(0)),
+      blocked: Arc::new(AtomicU64::new(0)),
+    }
+  }
+
+  fn key(ip: IpAddr) -> String {
+    format!("login_failures:{}", ip)
+  }
+
+  async fn failures(&self, ip: IpAddr) -> u32 {
+    if let Some(redis) = &self.redis
+      && let Some(count) = redis.get::<u32>(&Self::key(ip)).await
+    {
+      return count;
+    }
+    self.failures.get(&ip).unwrap_or_default()
+  }
+
+  /// Delay for the failures so far: none up to `login_tarpit_free_failures`, then doubling
+  /// from `login_tarpit_base_delay_ms` up to `login_tarpit_max_delay_ms`.
+  pub fn verdict(&self, failures: u32) -> TarpitVerdict {
+    let setting = &self.setting;
+    if !setting.login_tarpit_enabled || failures <= setting.login_tarpit_free_failures {
+      return TarpitVerdict::Allow;
+    }
+    if setting.login_tarpit_block_after > 0 && failures >= setting.login_tarpit_block_after {
+      return TarpitVerdict::Block(setting.login_tarpit_window_secs);
+    }
+    let doublings = (failures - setting.login_tarpit_free_failures - 1).min(32);
+    let delay_ms = setting
+      .login_tarpit_base_delay_ms
+      .saturating_mul(1 << doublings)
+      .min(setting.login_tarpit_max_delay_ms);
+    TarpitVerdict::Delay(Duration::from_millis(delay_ms))
+  }
+
+  /// Waits out the delay owed by `ip`, or returns the `Retry-After` seconds when it's blocked.
+  pub async fn hold(&self, ip: Option<IpAddr>) -> Result<(), u64> {
+    let Some(ip) = ip.filter(|_| self.setting.login_tarpit_enabled) else {
+      return Ok(());
+    };
+    match self.verdict(self.failures(ip).await) {
+      TarpitVerdict::Allow => Ok(()),
+      TarpitVerdict::Delay(delay) => {
+        self.delayed.fetch_add(1, Ordering::Relaxed);
+        actix_web::rt::time::sleep(delay).await;
+        Ok(())
+      }
+      TarpitVerdict::Block(retry_after) => {
+        self.blocked.fetch_add(1, Ordering::Relaxed);
+        Err(retry_after)
+      }
+    }
+  }
+
+  pub async fn record_failure(&self, ip: Option<IpAddr>) {
+    let Some(ip) = ip.filter(|_| self.setting.login_tarpit_enabled) else {
+      return;
+    };
+    let window = self.setting.login_tarpit_window_secs;
+    if let Some(redis) = &self.redis
+      && redis.increment(&Self::key(ip), window).await.is_some()
+    {
+      return;
+    }
+    let failures = self.failures.get(&ip).unwrap_or_default();
+    self.failures.set(ip, failures.saturating_add(1));
+  }
+
+  /// Forgets the failures of `ip` after it signed in.
+  pub async fn clear(&self, ip: Option<IpAddr>) {
+    let Some(ip) = ip.filter(|_| self.setting.login_tarpit_enabled) else {
+      return;
+    };
+    self.failures.remove(&ip);
+    if let Some(redis) = &self.redis {
+      redis.delete(&[Self::key(ip)]).await;
+    }
+  }
+
+  /// Login attempts held back since startup.
+  pub fn delayed_total(&self) -> u64 {
+    self.delayed.load(Ordering::Relaxed)
+  }
+
+  /// Login attempts refused with 429 since startup.
+  pub fn blocked_total(&self) -> u64 {
+    self.blocked.load(Ordering::Relaxed)
+  }
+
+  /// Delayed and blocked attempts in the Prometheus text format.
+  pub fn render_prometheus(&self, out: &mut String) {
+    let series = [
+      (
+        "auth_login_delayed_total",
+        "Login attempts held back because their IP kept failing.",
+        &self.delayed,
+      ),
+      (
+        "auth_login_blocked_total",
+        "Login attempts refused with 429 because their IP failed too often.",
+        &self.blocked,
+      ),
+    ];
+    for (name, help, counter) in series {
+      let _ = writeln!(out, "# HELP {} {}", name, help);
+      let _ = writeln!(out, "# TYPE {} counter", name);
+      let _ = writeln!(out, "{} {}", name, counter.load(Ordering::Relaxed));
+    }
+  }
+}
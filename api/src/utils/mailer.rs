@@ -0,0 +1,194 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use lettre::{
+  AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+  message::{Mailbox, MultiPart},
+  transport::smtp::authentication::Credentials,
+};
+use tokio::sync::mpsc;
+
+use crate::{
+  app_settings::MailerSetting,
+  i18n::Locale,
+  templates::{self, RenderedEmail},
+};
+
+// Delay before the first retry, doubled on every further attempt
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(2);
+
+/// The emails the API sends, rendered from `templates/email` in the recipient's language.
+#[derive(Debug, Clone)]
+pub enum EmailTemplate {
+  Verification { token: String, expires_minutes: i32 },
+  PasswordReset { token: String, expires_minutes: i32 },
+  RoleChanged { role: String },
+  EmailChanged { new_email: String },
+  PasswordChanged,
+  LoginCode { code: String, expires_minutes: i32 },
+}
+
+impl EmailTemplate {
+  /// Stable name of the template, as used by the dev preview route.
+  pub fn name(&self) -> &'static str {
+    match self {
+      EmailTemplate::Verification { .. } => "verification",
+      EmailTemplate::PasswordReset { .. } => "password_reset",
+      EmailTemplate::RoleChanged { .. } => "role_changed",
+      EmailTemplate::EmailChanged { .. } => "email_changed",
+      EmailTemplate::PasswordChanged => "password_changed",
+      EmailTemplate::LoginCode { .. } => "login_code",
+    }
+  }
+
+  pub fn render(&self, locale: Locale) -> askama::Result<RenderedEmail> {
+    templates::render_email(self, locale)
+  }
+
+  // One of each template with placeholder values, for `cargo run -- email-preview`
+  pub fn samples() -> Vec<EmailTemplate> {
+    vec![
+      EmailTemplate::Verification {
+        token: "<token>".to_string(),
+        expires_minutes: 30,
+      },
+      EmailTemplate::PasswordReset {
+        token: "<token>".to_string(),
+        expires_minutes: 30,
+      },
+      EmailTemplate::RoleChanged {
+        role: "<role>".to_string(),
+      },
+      EmailTemplate::EmailChanged {
+        new_email: "<email>".to_string(),
+      },
+      EmailTemplate::PasswordChanged,
+      EmailTemplate::LoginCode {
+        code: "<code>".to_string(),
+        expires_minutes: 10,
+      },
+    ]
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct OutgoingEmail {
+  pub to: String,
+  pub subject: String,
+  pub body: String,
+  /// Sent as an alternative to `body` when set
+  pub html: Option<String>,
+}
+
+enum Transport {
+  Smtp(AsyncSmtpTransport<Tokio1Executor>),
+  // Dev mode, emails are printed instead of sent
+  Log,
+}
+
+/// Queues emails and sends them from a background worker, so a slow SMTP server never
+/// holds up a request. Cheap to clone, every clone feeds the same queue.
+#[derive(Clone)]
+pub struct Mailer {
+  sender: mpsc::Sender<OutgoingEmail>,
+}
+
+impl Mailer {
+  /// Builds the transport and starts the worker, must be called inside the runtime.
+  pub fn start(setting: &MailerSetting) -> Result<Self> {
+    let from: Mailbox = setting
+      .from
+      .parse()
+      .map_err(|e| anyhow::anyhow!("mailer.from '{}' is not an address: {}", setting.from, e))?;
+    let transport = match (&setting.smtp, setting.dev_mode) {
+      (Some(smtp), false) => Transport::Smtp(
+        AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&smtp.host)?
+          .port(smtp.port)
+          .credentials(Credentials::new(
+            smtp.username.clone(),
+            smtp.password.clone(),
+          ))
+          .build(),
+      ),
+      _ => Transport::Log,
+    };
+
+    let (sender, receiver) = mpsc::channel(setting.queue_capacity.max(1));
+    actix_web::rt::spawn(run_worker(
+      receiver,
+      transport,
+      from,
+      setting.max_attempts.max(1),
+    ));
+    Ok(Self { sender })
+  }
+
+  /// Emails waiting for the worker.
+  pub fn queued(&self) -> usize {
+    self.sender.max_capacity() - self.sender.capacity()
+  }
+
+  /// Queues `email` without waiting, it is dropped with a log line when the queue is full.
+  pub fn enqueue(&self, email: OutgoingEmail) {
+    if let Err(e) = self.sender.try_send(email) {
+      eprintln!("Failed to queue email: {}", e);
+    }
+  }
+}
+
+async fn deliver(transport: &Transport, from: &Mailbox, email: &OutgoingEmail) -> Result<()> {
+  match transport {
+    Transport::Smtp(smtp) => {
+      let builder = Message::builder()
+        .from(from.clone())
+        .to(email.to.parse()?)
+        .subject(&email.subject);
+      let message = match &email.html {
+        Some(html) => builder.multipart(MultiPart::alternative_plain_html(
+          email.body.clone(),
+          html.clone(),
+        ))?,
+        None => builder.body(email.body.clone())?,
+      };
+      smtp.send(message).await?;
+    }
+    Transport::Log => println!(
+      "Email (not sent, mailer.dev_mode) to {}: {}\n{}",
+      email.to, email.subject, email.body
+    ),
+  }
+  Ok(())
+}
+
+async fn run_worker(
+  mut receiver: mpsc::Receiver<OutgoingEmail>,
+  transport: Transport,
+  from: Mailbox,
+  max_attempts: u32,
+) {
+  while let Some(email) = receiver.recv().await {
+    let mut delay = RETRY_BASE_DELAY;
+    for attempt in 1..=max_attempts {
+      match deliver(&transport, &from, &email).await {
+        Ok(()) => break,
+        Err(e) if attempt == max_attempts => {
+          eprintln!(
+            "Giving up on email '{}' to {} after {} attempt(s): {}",
+            email.subject, email.to, attempt, e
+          );
+        }
+        Err(e) => {
+          eprintln!(
+            "Failed to send email '{}' to {}, retrying in {}s: {}",
+            email.subject,
+            email.to,
+            delay.as_secs(),
+            e
+          );
+          tokio::time::sleep(delay).await;
+          delay *= 2;
+        }
+      }
+    }
+  }
+}
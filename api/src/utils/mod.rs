@@ -1,2 +1,15 @@
+pub mod aws_sigv4;
+pub mod bulk_insert;
+pub mod captcha;
+pub mod custom_claims;
+pub mod device_fingerprint;
+pub mod feature_flags;
 pub mod jwt_util;
+pub mod login_tarpit;
+pub mod mailer;
+pub mod operations;
 pub mod password_hashing;
+pub mod redis_cache;
+pub mod tls;
+pub mod ttl_cache;
+pub mod xml_dsig;
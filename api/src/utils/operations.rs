@@ -0,0 +1,215 @@
+use std::{
+  fmt,
+  future::Future,
+  sync::{
+    Arc,
+    atomic::{AtomicUsize, Ordering},
+  },
+  time::Duration,
+};
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Semaphore;
+use utoipa::ToSchema;
+
+use crate::{app_settings::OperationSetting, storage::Storage, utils::ttl_cache::TtlMap};
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OperationStatus {
+  Queued,
+  Running,
+  Succeeded,
+  Failed,
+}
+
+/// File an operation produced, kept in `AppState::storage` until the operation expires.
+#[derive(Clone)]
+pub struct OperationResult {
+  pub key: String,
+  pub file_name: String,
+}
+
+/// A long-running operation as polled through `/operations/{id}`.
+#[derive(Clone)]
+pub struct Operation {
+  pub id: String,
+  /// What is being done, e.g. `user_report`
+  pub kind: String,
+  /// Who started it, the only user besides admins who may poll it
+  pub owner_id: i32,
+  pub status: OperationStatus,
+  /// Percent done, 100 once it succeeded
+  pub progress: u8,
+  pub created_at: DateTime<Utc>,
+  pub finished_at: Option<DateTime<Utc>>,
+  pub error: Option<String>,
+  pub result: Option<OperationResult>,
+}
+
+/// Returned by `Operations::start` when `operations.max_pending` are queued or running.
+#[derive(Debug)]
+pub struct OperationsBusy {
+  pub max_pending: usize,
+}
+
+impl fmt::Display for OperationsBusy {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(
+      f,
+      "{} operations are already queued or running, try again later",
+      self.max_pending
+    )
+  }
+}
+
+impl std::error::Error for OperationsBusy {}
+
+/// Handed to the work of an operation to report how far along it is.
+#[derive(Clone)]
+pub struct OperationProgress {
+  id: String,
+  entries: TtlMap<String, Operation>,
+}
+
+impl OperationProgress {
+  pub fn id(&self) -> &str {
+    &self.id
+  }
+
+  pub fn set(&self, percent: u8) {
+    update(&self.entries, &self.id, |operation| {
+      operation.progress = percent.min(100)
+    });
+  }
+}
+
+fn update(entries: &TtlMap<String, Operation>, id: &str, change: impl FnOnce(&mut Operation)) {
+  if let Some(mut operation) = entries.get(&id.to_string()) {
+    change(&mut operation);
+    entries.set(id.to_string(), operation);
+  }
+}
+
+/// Runs heavy work such as report generation in the background, so the request that started
+/// it answers 202 right away and the client polls for the outcome. Operations live in memory
+/// on this instance, they are gone after `retention_secs` or a restart. Cheap to clone.
+#[derive(Clone)]
+pub struct Operations {
+  entries: TtlMap<String, Operation>,
+  // One permit per operation allowed to run at once
+  slots: Arc<Semaphore>,
+  // Queued and running operations
+  pending: Arc<AtomicUsize>,
+  max_pending: usize,
+  retention: Duration,
+  storage: Arc<dyn Storage>,
+}
+
+impl Operations {
+  pub fn new(setting: &OperationSetting, storage: Arc<dyn Storage>) -> Self {
+    Self {
+      entries: TtlMap::new(Duration::from_secs(setting.retention_secs)),
+      slots: Arc::new(Semaphore::new(setting.max_concurrent.max(1))),
+      pending: Arc::new(AtomicUsize::new(0)),
+      max_pending: setting.max_pending,
+      retention: Duration::from_secs(setting.retention_secs),
+      storage,
+    }
+  }
+
+  /// Queued and running operations, next to `operations.max_pending`.
+  pub fn backlog(&self) -> (usize, usize) {
+    (self.pending.load(Ordering::Relaxed), self.max_pending)
+  }
+
+  /// `None` once the operation expired or when it never existed.
+  pub fn get(&self, id: &str) -> Option<Operation> {
+    self.entries.get(&id.to_string())
+  }
+
+  /// Queues `work` and returns the queued operation right away, the work runs once a slot is
+  /// free. Must be called inside the runtime.
+  pub fn start<F, Fut>(&self, kind: &str, owner_id: i32, work: F) -> Result<Operation>
+  where
+    F: FnOnce(OperationProgress) -> Fut + 'static,
+    Fut: Future<Output = Result<OperationResult>> + 'static,
+  {
+    let max_pending = self.max_pending;
+    if self
+      .pending
+      .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| {
+        (n < max_pending).then_some(n + 1)
+      })
+      .is_err()
+    {
+      return Err(OperationsBusy { max_pending }.into());
+    }
+
+    let operation = Operation {
+      id: uuid::Uuid::new_v4().to_string(),
+      kind: kind.to_string(),
+      owner_id,
+      status: OperationStatus::Queued,
+      progress: 0,
+      created_at: Utc::now(),
+      finished_at: None,
+      error: None,
+      result: None,
+    };
+    self.entries.set(operation.id.clone(), operation.clone());
+
+    let operations = self.clone();
+    let id = operation.id.clone();
+    actix_web::rt::spawn(async move {
+      let _slot = operations.slots.clone().acquire_owned().await;
+      update(&operations.entries, &id, |operation| {
+        operation.status = OperationStatus::Running
+      });
+      let progress = OperationProgress {
+        id: id.clone(),
+        entries: operations.entries.clone(),
+      };
+      let outcome = work(progress).await;
+      operations.pending.fetch_sub(1, Ordering::AcqRel);
+      operations.finish(&id, outcome);
+    });
+    Ok(operation)
+  }
+
+  fn finish(&self, id: &str, outcome: Result<OperationResult>) {
+    match outcome {
+      Ok(result) => {
+        self.expire_result(result.key.clone());
+        update(&self.entries, id, |operation| {
+          operation.status = OperationStatus::Succeeded;
+          operation.progress = 100;
+          operation.finished_at = Some(Utc::now());
+          operation.result = Some(result);
+        });
+      }
+      Err(e) => {
+        eprintln!("Operation {} failed: {:#}", id, e);
+        update(&self.entries, id, |operation| {
+          operation.status = OperationStatus::Failed;
+          operation.finished_at = Some(Utc::now());
+          operation.error = Some(e.to_string());
+        });
+      }
+    }
+  }
+
+  // The result file goes together with the operation
+  fn expire_result(&self, key: String) {
+    let storage = self.storage.clone();
+    let retention = self.retention;
+    actix_web::rt::spawn(async move {
+      tokio::time::sleep(retention).await;
+      if let Err(e) = storage.delete(&key).await {
+        eprintln!("Failed to delete operation result '{}': {}", key, e);
+      }
+    });
+  }
+}
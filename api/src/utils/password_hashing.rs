@@ -13,6 +13,7 @@ impl PasswordHashing {
   /// * `Result<String, Argon2Error>` - The hashed password or an error
   /// # Examples
   /// ```
+  /// # use api::utils::password_hashing::PasswordHashing;
   /// let hashed = PasswordHashing::hash_password("my_password").unwrap();
   /// assert!(PasswordHashing::verify_password("my_password", &hashed));
   /// assert!(!PasswordHashing::verify_password("wrong_password", &hashed));
@@ -53,6 +54,7 @@ impl PasswordHashing {
   /// * `bool` - True if the password matches the hash, false otherwise
   /// # Examples
   /// ```
+  /// # use api::utils::password_hashing::PasswordHashing;
   /// let hashed = PasswordHashing::hash_password("my_password").unwrap();
   /// assert!(PasswordHashing::verify_password("my_password", &hashed));
   /// assert!(!PasswordHashing::verify_password("wrong_password", &hashed));
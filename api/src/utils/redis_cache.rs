@@ -0,0 +1,105 @@
+use anyhow::Result;
+use futures::StreamExt;
+use redis::{AsyncCommands, aio::ConnectionManager};
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::app_settings::RedisSetting;
+
+/// JSON values in Redis shared by every instance of the API. Lookups that fail count as
+/// misses and failed writes are only logged, the database stays the source of truth.
+#[derive(Clone)]
+pub struct RedisCache {
+  manager: ConnectionManager,
+  key_prefix: String,
+  ttl_secs: u64,
+}
+
+impl RedisCache {
+  // The manager reconnects on its own after the first connection succeeded
+  pub async fn connect(setting: &RedisSetting) -> Result<Self> {
+    let client = redis::Client::open(setting.url.as_str())?;
+    Ok(Self {
+      manager: ConnectionManager::new(client).await?,
+      key_prefix: setting.key_prefix.clone(),
+      ttl_secs: setting.ttl_secs.max(1),
+    })
+  }
+
+  fn key(&self, key: &str) -> String {
+    format!("{}{}", self.key_prefix, key)
+  }
+
+  pub async fn get<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+    let mut conn = self.manager.clone();
+    match conn.get::<_, Option<String>>(self.key(key)).await {
+      Ok(value) => value.and_then(|json| serde_json::from_str(&json).ok()),
+      Err(e) => {
+        eprintln!("Redis get '{}' failed: {}", key, e);
+        None
+      }
+    }
+  }
+
+  pub async fn set<T: Serialize>(&self, key: &str, value: &T) {
+    let Ok(json) = serde_json::to_string(value) else {
+      return;
+    };
+    let mut conn = self.manager.clone();
+    if let Err(e) = conn
+      .set_ex::<_, _, ()>(self.key(key), json, self.ttl_secs)
+      .await
+    {
+      eprintln!("Redis set '{}' failed: {}", key, e);
+    }
+  }
+
+  /// Adds one to a counter and restarts its expiry, `None` when Redis can't be reached.
+  pub async fn increment(&self, key: &str, ttl_secs: u64) -> Option<u32> {
+    let key = self.key(key);
+    let mut conn = self.manager.clone();
+    match redis::pipe()
+      .atomic()
+      .incr(&key, 1)
+      .expire(&key, ttl_secs.max(1) as i64)
+      .ignore()
+      .query_async::<(u32,)>(&mut conn)
+      .await
+    {
+      Ok((count,)) => Some(count),
+      Err(e) => {
+        eprintln!("Redis increment '{}' failed: {}", key, e);
+        None
+      }
+    }
+  }
+
+  pub async fn delete(&self, keys: &[String]) {
+    if keys.is_empty() {
+      return;
+    }
+    let keys: Vec<String> = keys.iter().map(|key| self.key(key)).collect();
+    let mut conn = self.manager.clone();
+    if let Err(e) = conn.del::<_, ()>(&keys).await {
+      eprintln!("Redis delete {:?} failed: {}", keys, e);
+    }
+  }
+
+  /// Deletes every key starting with `prefix`, for changes that affect many entries.
+  pub async fn delete_prefix(&self, prefix: &str) {
+    let pattern = format!("{}*", self.key(prefix));
+    let mut conn = self.manager.clone();
+    let keys: Vec<String> = match conn.scan_match::<_, String>(&pattern).await {
+      Ok(iter) => iter.collect().await,
+      Err(e) => {
+        eprintln!("Redis scan '{}' failed: {}", pattern, e);
+        return;
+      }
+    };
+    if keys.is_empty() {
+      return;
+    }
+    if let Err(e) = conn.del::<_, ()>(&keys).await {
+      eprintln!("Redis delete '{}' failed: {}", pattern, e);
+    }
+  }
+}
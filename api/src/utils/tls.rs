@@ -0,0 +1,134 @@
+use std::{any::Any, sync::Arc};
+
+use actix_tls::accept::rustls_0_23::TlsStream;
+use actix_web::{
+  HttpRequest, HttpResponse, dev::Extensions, http::header, rt::net::TcpStream, web,
+};
+use anyhow::{Context, Result};
+use rustls::{
+  RootCertStore, ServerConfig,
+  pki_types::{CertificateDer, PrivateKeyDer, pem::PemObject},
+  server::WebPkiClientVerifier,
+};
+use x509_parser::prelude::{FromDer, X509Certificate};
+
+use crate::app_settings::{ClientAuthSetting, TlsSetting};
+
+/// Subject of the verified certificate a client connected with, read with
+/// `req.conn_data::<ClientCertificate>()`.
+#[derive(Clone)]
+pub struct ClientCertificate {
+  /// e.g. `CN=billing, O=Acme`
+  pub subject: String,
+  pub common_name: Option<String>,
+}
+
+impl ClientCertificate {
+  fn from_der(der: &[u8]) -> Option<Self> {
+    let (_, cert) = X509Certificate::from_der(der).ok()?;
+    let subject = cert.subject();
+    Some(Self {
+      subject: subject.to_string(),
+      common_name: subject
+        .iter_common_name()
+        .next()
+        .and_then(|cn| cn.as_str().ok())
+        .map(str::to_string),
+    })
+  }
+
+  /// User name mapped to the certificate in `client_auth.principals`, the full subject first.
+  pub fn principal<'a>(&self, client_auth: &'a ClientAuthSetting) -> Option<&'a str> {
+    client_auth
+      .principals
+      .get(&self.subject)
+      .or_else(|| {
+        self
+          .common_name
+          .as_ref()
+          .and_then(|cn| client_auth.principals.get(cn))
+      })
+      .map(String::as_str)
+  }
+}
+
+// `HttpServer::on_connect` hook, rustls already verified the chain during the handshake
+pub fn read_client_certificate(conn: &dyn Any, ext: &mut Extensions) {
+  let Some(stream) = conn.downcast_ref::<TlsStream<TcpStream>>() else {
+    return;
+  };
+  let (_, session) = stream.get_ref();
+  let certificate = session
+    .peer_certificates()
+    .and_then(|chain| chain.first())
+    .and_then(|leaf| ClientCertificate::from_der(leaf));
+  if let Some(certificate) = certificate {
+    ext.insert(certificate);
+  }
+}
+
+fn client_verifier(
+  client_auth: &ClientAuthSetting,
+) -> Result<Arc<dyn rustls::server::danger::ClientCertVerifier>> {
+  let mut roots = RootCertStore::empty();
+  for cert in CertificateDer::pem_file_iter(&client_auth.ca_path)
+    .with_context(|| format!("Failed to open CA file: {}", client_auth.ca_path))?
+  {
+    let cert = cert.with_context(|| format!("Failed to parse CA file: {}", client_auth.ca_path))?;
+    roots.add(cert)?;
+  }
+  let builder = WebPkiClientVerifier::builder_with_provider(
+    Arc::new(roots),
+    Arc::new(rustls::crypto::ring::default_provider()),
+  );
+  let verifier = if client_auth.required {
+    builder.build()?
+  } else {
+    builder.allow_unauthenticated().build()?
+  };
+  Ok(verifier)
+}
+
+// Build the rustls config from the PEM files named in the settings
+pub fn load_server_config(setting: &TlsSetting) -> Result<ServerConfig> {
+  let certs = CertificateDer::pem_file_iter(&setting.cert_path)
+    .with_context(|| format!("Failed to open certificate file: {}", setting.cert_path))?
+    .collect::<Result<Vec<_>, _>>()
+    .with_context(|| format!("Failed to parse certificate file: {}", setting.cert_path))?;
+  let key = PrivateKeyDer::from_pem_file(&setting.key_path)
+    .with_context(|| format!("Failed to read private key file: {}", setting.key_path))?;
+
+  let builder =
+    ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+      .with_safe_default_protocol_versions()?;
+  let builder = match &setting.client_auth {
+    Some(client_auth) => builder.with_client_cert_verifier(client_verifier(client_auth)?),
+    None => builder.with_no_client_auth(),
+  };
+  let config = builder.with_single_cert(certs, key)?;
+  Ok(config)
+}
+
+/// Where the plain HTTP listener sends callers, built from `server.tls.public_host` and `server.port`.
+#[derive(Clone)]
+pub struct HttpsRedirect {
+  pub host: String,
+  pub port: u16,
+}
+
+// Sends plain HTTP requests to the same path on the configured HTTPS host, never the `Host`
+// header the caller sent, that would make this an open redirect
+pub async fn redirect_to_https(req: HttpRequest, target: web::Data<HttpsRedirect>) -> HttpResponse {
+  let path = req
+    .uri()
+    .path_and_query()
+    .map(|path| path.as_str())
+    .unwrap_or("/");
+  let location = match target.port {
+    443 => format!("https://{}{}", target.host, path),
+    port => format!("https://{}:{}{}", target.host, port, path),
+  };
+  HttpResponse::PermanentRedirect()
+    .insert_header((header::LOCATION, location))
+    .finish()
+}
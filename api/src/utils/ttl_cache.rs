@@ -0,0 +1,90 @@
+use std::{
+  collections::HashMap,
+  hash::Hash,
+  sync::{Arc, RwLock},
+  time::{Duration, Instant},
+};
+
+/// A single cached value that expires after `ttl`, cheap to clone and share across workers.
+#[derive(Clone)]
+pub struct TtlCell<T> {
+  ttl: Duration,
+  inner: Arc<RwLock<Option<(Instant, T)>>>,
+}
+
+impl<T: Clone> TtlCell<T> {
+  pub fn new(ttl: Duration) -> Self {
+    Self {
+      ttl,
+      inner: Arc::new(RwLock::new(None)),
+    }
+  }
+
+  pub fn get(&self) -> Option<T> {
+    let guard = self.inner.read().ok()?;
+    match guard.as_ref() {
+      Some((stored_at, value)) if stored_at.elapsed() < self.ttl => Some(value.clone()),
+      _ => None,
+    }
+  }
+
+  pub fn set(&self, value: T) {
+    if let Ok(mut guard) = self.inner.write() {
+      *guard = Some((Instant::now(), value));
+    }
+  }
+}
+
+/// Keyed variant of `TtlCell`, each entry expires `ttl` after it was stored.
+#[derive(Clone)]
+pub struct TtlMap<K, V> {
+  ttl: Duration,
+  inner: Arc<RwLock<HashMap<K, (Instant, V)>>>,
+}
+
+impl<K: Eq + Hash, V: Clone> TtlMap<K, V> {
+  pub fn new(ttl: Duration) -> Self {
+    Self {
+      ttl,
+      inner: Arc::new(RwLock::new(HashMap::new())),
+    }
+  }
+
+  pub fn get(&self, key: &K) -> Option<V> {
+    let guard = self.inner.read().ok()?;
+    match guard.get(key) {
+      Some((stored_at, value)) if stored_at.elapsed() < self.ttl => Some(value.clone()),
+      _ => None,
+    }
+  }
+
+  pub fn set(&self, key: K, value: V) {
+    if let Ok(mut guard) = self.inner.write() {
+      // Drop expired entries so the map doesn't grow with every user ever seen
+      let ttl = self.ttl;
+      guard.retain(|_, (stored_at, _)| stored_at.elapsed() < ttl);
+      guard.insert(key, (Instant::now(), value));
+    }
+  }
+
+  pub fn remove(&self, key: &K) {
+    if let Ok(mut guard) = self.inner.write() {
+      guard.remove(key);
+    }
+  }
+
+  /// Removes the entry, returning it unless it already expired.
+  pub fn take(&self, key: &K) -> Option<V> {
+    let mut guard = self.inner.write().ok()?;
+    match guard.remove(key) {
+      Some((stored_at, value)) if stored_at.elapsed() < self.ttl => Some(value),
+      _ => None,
+    }
+  }
+
+  pub fn clear(&self) {
+    if let Ok(mut guard) = self.inner.write() {
+      guard.clear();
+    }
+  }
+}
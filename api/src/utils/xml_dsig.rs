@@ -0,0 +1,293 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::{Context, Result, bail};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use ring::signature::{RSA_PKCS1_2048_8192_SHA256, UnparsedPublicKey};
+use roxmltree::{Node, NodeId};
+use sha2::{Digest, Sha256};
+
+pub const DSIG_NS: &str = "http://www.w3.org/2000/09/xmldsig#";
+const EXC_C14N: &str = "http://www.w3.org/2001/10/xml-exc-c14n#";
+const ENVELOPED_SIGNATURE: &str = "http://www.w3.org/2000/09/xmldsig#enveloped-signature";
+const RSA_SHA256: &str = "http://www.w3.org/2001/04/xmldsig-more#rsa-sha256";
+const SHA256: &str = "http://www.w3.org/2001/04/xmlenc#sha256";
+
+/// Public key of a PEM certificate, in the form `verify_enveloped` takes it.
+pub fn public_key_from_pem(pem: &[u8]) -> Result<Vec<u8>> {
+  let (_, pem) = x509_parser::pem::parse_x509_pem(pem).context("Not a PEM certificate")?;
+  let cert = pem.parse_x509().context("Invalid certificate")?;
+  Ok(cert.public_key().subject_public_key.data.to_vec())
+}
+
+/// The `ds:Signature` child of `element`, if it has one.
+pub fn signature_of<'a, 'input>(element: Node<'a, 'input>) -> Option<Node<'a, 'input>> {
+  child(element, DSIG_NS, "Signature")
+}
+
+/// Checks the enveloped signature of `element` against `public_key` (an RSA key).
+///
+/// Only what SAML IdPs produce is accepted: exclusive canonicalization, RSA-SHA256 and a
+/// single SHA-256 reference to `element` itself by its `ID`. Anything else fails.
+pub fn verify_enveloped(element: Node, public_key: &[u8]) -> Result<()> {
+  let signature = signature_of(element).context("Element is not signed")?;
+  let signed_info = child(signature, DSIG_NS, "SignedInfo").context("SignedInfo is missing")?;
+
+  let c14n_method = child(signed_info, DSIG_NS, "CanonicalizationMethod")
+    .context("CanonicalizationMethod is missing")?;
+  expect_algorithm(c14n_method, EXC_C14N)?;
+  let signature_method =
+    child(signed_info, DSIG_NS, "SignatureMethod").context("SignatureMethod is missing")?;
+  expect_algorithm(signature_method, RSA_SHA256)?;
+
+  let mut references = children(signed_info, DSIG_NS, "Reference");
+  let (Some(reference), None) = (references.next(), references.next()) else {
+    bail!("Exactly one signature reference is expected");
+  };
+
+  // The reference must point at the element the caller is going to trust, and only one
+  // element may carry that ID, otherwise a signed copy could be moved next to a forged one
+  let id = element
+    .attribute("ID")
+    .context("Signed element has no ID")?;
+  if reference.attribute("URI") != Some(&format!("#{}", id)) {
+    bail!("Signature doesn't reference the signed element");
+  }
+  let same_id = element
+    .document()
+    .descendants()
+    .filter(|node| node.attribute("ID") == Some(id))
+    .count();
+  if same_id != 1 {
+    bail!("ID '{}' is not unique", id);
+  }
+
+  let mut prefixes = Vec::new();
+  if let Some(transforms) = child(reference, DSIG_NS, "Transforms") {
+    for transform in children(transforms, DSIG_NS, "Transform") {
+      match transform.attribute("Algorithm") {
+        Some(ENVELOPED_SIGNATURE) => {}
+        Some(EXC_C14N) => prefixes = inclusive_prefixes(transform),
+        other => bail!("Unsupported transform {:?}", other),
+      }
+    }
+  }
+  let digest_method =
+    child(reference, DSIG_NS, "DigestMethod").context("DigestMethod is missing")?;
+  expect_algorithm(digest_method, SHA256)?;
+  let digest_value = decode_base64(child(reference, DSIG_NS, "DigestValue"))?;
+
+  let canonical = canonicalize(element, Some(signature.id()), &prefixes);
+  if Sha256::digest(canonical.as_bytes()).as_slice() != digest_value.as_slice() {
+    bail!("Digest doesn't match, the signed element was changed");
+  }
+
+  let signed_info_c14n = canonicalize(signed_info, None, &inclusive_prefixes(c14n_method));
+  let signature_value = decode_base64(child(signature, DSIG_NS, "SignatureValue"))?;
+  UnparsedPublicKey::new(&RSA_PKCS1_2048_8192_SHA256, public_key)
+    .verify(signed_info_c14n.as_bytes(), &signature_value)
+    .map_err(|_| anyhow::anyhow!("Signature is not valid for the IdP certificate"))
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, ns: &str, name: &str) -> Option<Node<'a, 'input>> {
+  children(node, ns, name).next()
+}
+
+fn children<'a, 'input>(
+  node: Node<'a, 'input>,
+  ns: &str,
+  name: &str,
+) -> impl Iterator<Item = Node<'a, 'input>> {
+  node
+    .children()
+    .filter(move |child| child.is_element() && child.has_tag_name((ns, name)))
+}
+
+fn expect_algorithm(node: Node, algorithm: &str) -> Result<()> {
+  match node.attribute("Algorithm") {
+    Some(found) if found == algorithm => Ok(()),
+    found => bail!("Unsupported algorithm {:?}, expected {}", found, algorithm),
+  }
+}
+
+fn decode_base64(node: Option<Node>) -> Result<Vec<u8>> {
+  let text: String = node
+    .and_then(|node| node.text())
+    .unwrap_or_default()
+    .chars()
+    .filter(|c| !c.is_ascii_whitespace())
+    .collect();
+  STANDARD.decode(text).context("Invalid base64 value")
+}
+
+// `PrefixList` of an `ec:InclusiveNamespaces` child, `#default` stands for the default namespace
+fn inclusive_prefixes(node: Node) -> Vec<String> {
+  node
+    .children()
+    .find(|child| child.has_tag_name((EXC_C14N, "InclusiveNamespaces")))
+    .and_then(|child| child.attribute("PrefixList"))
+    .map(|list| {
+      list
+        .split_whitespace()
+        .map(|prefix| match prefix {
+          "#default" => String::new(),
+          prefix => prefix.to_string(),
+        })
+        .collect()
+    })
+    .unwrap_or_default()
+}
+
+/// Exclusive XML canonicalization without comments of `node`, leaving out `exclude`.
+pub fn canonicalize(node: Node, exclude: Option<NodeId>, inclusive_prefixes: &[String]) -> String {
+  let mut out = String::new();
+  write_element(
+    &mut out,
+    node,
+    exclude,
+    inclusive_prefixes,
+    &BTreeMap::new(),
+  );
+  out
+}
+
+// Qualified names come from the source, roxmltree only keeps namespace URIs
+fn element_qname<'input>(node: Node<'_, 'input>) -> &'input str {
+  let input = node.document().input_text();
+  let start = node.range().start + 1;
+  let len = input[start..]
+    .find(|c: char| c.is_whitespace() || c == '/' || c == '>')
+    .unwrap_or(0);
+  &input[start..start + len]
+}
+
+fn prefix_of(qname: &str) -> &str {
+  qname
+    .split_once(':')
+    .map(|(prefix, _)| prefix)
+    .unwrap_or("")
+}
+
+fn write_element(
+  out: &mut String,
+  node: Node,
+  exclude: Option<NodeId>,
+  inclusive_prefixes: &[String],
+  rendered: &BTreeMap<String, String>,
+) {
+  let input = node.document().input_text();
+  let qname = element_qname(node);
+
+  // Namespaces visibly used by the element and its attributes, plus the inclusive ones
+  let mut used: BTreeSet<&str> = BTreeSet::new();
+  used.insert(prefix_of(qname));
+  let attributes: Vec<_> = node
+    .attributes()
+    .map(|attr| (attr, &input[attr.range_qname()]))
+    .collect();
+  for (_, attr_qname) in &attributes {
+    if attr_qname.contains(':') {
+      used.insert(prefix_of(attr_qname));
+    }
+  }
+  for prefix in inclusive_prefixes {
+    let in_scope = match prefix.as_str() {
+      "" => node.default_namespace().is_some(),
+      prefix => node.lookup_namespace_uri(Some(prefix)).is_some(),
+    };
+    if in_scope {
+      used.insert(prefix);
+    }
+  }
+
+  let mut scope = rendered.clone();
+  let mut declarations = Vec::new();
+  for prefix in used {
+    if prefix == "xml" {
+      continue;
+    }
+    let uri = match prefix {
+      "" => node.default_namespace().unwrap_or_default(),
+      prefix => node.lookup_namespace_uri(Some(prefix)).unwrap_or_default(),
+    };
+    let current = rendered.get(prefix).map(String::as_str).unwrap_or_default();
+    if current != uri {
+      declarations.push((prefix, uri));
+      scope.insert(prefix.to_string(), uri.to_string());
+    }
+  }
+
+  out.push('<');
+  out.push_str(qname);
+  // Sorted by prefix, the default namespace sorts first
+  for (prefix, uri) in declarations {
+    match prefix {
+      "" => out.push_str(" xmlns=\""),
+      prefix => {
+        out.push_str(" xmlns:");
+        out.push_str(prefix);
+        out.push_str("=\"");
+      }
+    }
+    escape_attribute(out, uri);
+    out.push('"');
+  }
+  let mut attributes = attributes;
+  attributes.sort_by_key(|(attr, _)| (attr.namespace().unwrap_or_default(), attr.name()));
+  for (attr, attr_qname) in attributes {
+    out.push(' ');
+    out.push_str(attr_qname);
+    out.push_str("=\"");
+    escape_attribute(out, attr.value());
+    out.push('"');
+  }
+  out.push('>');
+
+  for child in node.children() {
+    if Some(child.id()) == exclude {
+      continue;
+    }
+    if child.is_element() {
+      write_element(out, child, exclude, inclusive_prefixes, &scope);
+    } else if child.is_text() {
+      escape_text(out, child.text().unwrap_or_default());
+    } else if let Some(pi) = child.pi() {
+      out.push_str("<?");
+      out.push_str(pi.target);
+      if let Some(value) = pi.value {
+        out.push(' ');
+        out.push_str(value);
+      }
+      out.push_str("?>");
+    }
+  }
+
+  out.push_str("</");
+  out.push_str(qname);
+  out.push('>');
+}
+
+fn escape_text(out: &mut String, text: &str) {
+  for c in text.chars() {
+    match c {
+      '&' => out.push_str("&amp;"),
+      '<' => out.push_str("&lt;"),
+      '>' => out.push_str("&gt;"),
+      '\r' => out.push_str("&#xD;"),
+      c => out.push(c),
+    }
+  }
+}
+
+fn escape_attribute(out: &mut String, value: &str) {
+  for c in value.chars() {
+    match c {
+      '&' => out.push_str("&amp;"),
+      '<' => out.push_str("&lt;"),
+      '"' => out.push_str("&quot;"),
+      '\t' => out.push_str("&#x9;"),
+      '\n' => out.push_str("&#xA;"),
+      '\r' => out.push_str("&#xD;"),
+      c => out.push(c),
+    }
+  }
+}
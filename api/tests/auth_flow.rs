@@ -0,0 +1,135 @@
+//! Register, login and role checks on the in-memory repositories and on a real SQL Server,
+//! see `common` for the fixtures.
+mod common;
+
+use actix_web::{http::StatusCode, web};
+
+use api::app_state::AppState;
+use common::{
+  DEMO_ADMIN, DEMO_USER, call, get, init_app, login, login_demo, on_both_backends, register,
+};
+
+on_both_backends!(
+  registered_user_can_log_in_and_read_their_profile,
+  protected_routes_require_a_token,
+  only_admins_reach_admin_routes,
+  login_code_is_consumed_once,
+);
+
+async fn registered_user_can_log_in_and_read_their_profile(state: &web::Data<AppState>) {
+  let app = init_app(state).await;
+
+  let (status, body) = register(&app, "alice", "Alice@12345").await;
+  assert_eq!(status, StatusCode::OK, "{}", body);
+  let user_id = body["data"]["id"].clone();
+  assert!(user_id.is_i64(), "{}", body);
+
+  // A taken user name is refused
+  let (status, _) = register(&app, "alice", "Other@12345").await;
+  assert_eq!(status, StatusCode::CONFLICT);
+
+  assert_eq!(login(&app, "alice", "wrong password").await, None);
+  let token = login(&app, "alice", "Alice@12345").await.unwrap();
+
+  let (status, body) = call(&app, get("/api/v2/users/me", Some(&token))).await;
+  assert_eq!(status, StatusCode::OK, "{}", body);
+  assert_eq!(body["data"]["user"]["user_name"], "alice");
+  assert_eq!(body["data"]["user"]["id"], user_id);
+}
+
+async fn protected_routes_require_a_token(state: &web::Data<AppState>) {
+  let app = init_app(state).await;
+
+  let (status, _) = call(&app, get("/api/v2/users/me", None)).await;
+  assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+  let (status, _) = call(&app, get("/api/v2/users/me", Some("not-a-jwt"))).await;
+  assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+async fn only_admins_reach_admin_routes(state: &web::Data<AppState>) {
+  let app = init_app(state).await;
+
+  let user_token = login_demo(&app, DEMO_USER).await;
+  let (status, _) = call(&app, get("/api/v2/roles", Some(&user_token))).await;
+  assert_eq!(status, StatusCode::FORBIDDEN);
+  let (status, _) = call(&app, get("/api/v2/users", Some(&user_token))).await;
+  assert_eq!(status, StatusCode::FORBIDDEN);
+
+  let admin_token = login_demo(&app, DEMO_ADMIN).await;
+  let (status, body) = call(&app, get("/api/v2/roles", Some(&admin_token))).await;
+  assert_eq!(status, StatusCode::OK, "{}", body);
+  // The seeded default roles
+  let names: Vec<_> = body["data"]
+    .as_array()
+    .unwrap()
+    .iter()
+    .filter_map(|role| role["name"].as_str())
+    .collect();
+  for role in ["admin", "moderator", "user"] {
+    assert!(
+      names.contains(&role),
+      "missing role {} in {:?}",
+      role,
+      names
+    );
+  }
+
+  let (status, _) = call(&app, get("/api/v2/users", Some(&admin_token))).await;
+  assert_eq!(status, StatusCode::OK);
+}
+
+// `otp_login` is off in the sample settings, the repository is what makes a code single-use
+async fn login_code_is_consumed_once(state: &web::Data<AppState>) {
+  let app = init_app(state).await;
+  let token = login_demo(&app, DEMO_USER).await;
+  let (_, body) = call(&app, get("/api/v2/users/me", Some(&token))).await;
+  let user_id = body["data"]["user"]["id"].as_i64().unwrap() as i32;
+
+  let mut repo = state.user_repo();
+  assert!(
+    repo
+      .create_login_code(user_id, "first-hash", 10, 3, 60)
+      .await
+      .unwrap()
+  );
+  // A newer code replaced it
+  assert!(
+    repo
+      .create_login_code(user_id, "second-hash", 10, 3, 60)
+      .await
+      .unwrap()
+  );
+  assert!(
+    !repo
+      .consume_login_code(user_id, "first-hash", 5)
+      .await
+      .unwrap()
+  );
+
+  assert!(
+    repo
+      .consume_login_code(user_id, "second-hash", 5)
+      .await
+      .unwrap()
+  );
+  assert!(
+    !repo
+      .consume_login_code(user_id, "second-hash", 5)
+      .await
+      .unwrap()
+  );
+
+  // Given up on once tried `max_attempts` times
+  repo
+    .create_login_code(user_id, "third-hash", 10, 3, 60)
+    .await
+    .unwrap();
+  repo.increment_login_code_attempts(user_id).await.unwrap();
+  assert!(
+    !repo
+      .consume_login_code(user_id, "third-hash", 1)
+      .await
+      .unwrap()
+  );
+}
@@ -0,0 +1,88 @@
+//! Azure AD tokens read from a secret file, with fake unsigned JWTs.
+use std::path::{Path, PathBuf};
+
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use chrono::{Duration, Utc};
+
+use api::{
+  app_settings::{AzureAdSetting, AzureAdTokenSource},
+  db::azure_ad::{AzureAdToken, token_expiry},
+};
+
+fn jwt(subject: &str, expires_in: Duration) -> String {
+  let claims = serde_json::json!({
+    "sub": subject,
+    "exp": (Utc::now() + expires_in).timestamp(),
+  });
+  format!(
+    "{}.{}.",
+    URL_SAFE_NO_PAD.encode(r#"{"alg":"none"}"#),
+    URL_SAFE_NO_PAD.encode(claims.to_string())
+  )
+}
+
+fn token_file(name: &str) -> PathBuf {
+  std::env::temp_dir().join(format!("azure-ad-{}-{}", name, std::process::id()))
+}
+
+fn from_file(path: &Path) -> AzureAdToken {
+  AzureAdToken::new(&AzureAdSetting {
+    token_source: AzureAdTokenSource::Secret {
+      token: format!("file://{}", path.display()),
+    },
+    refresh_before_secs: 300,
+  })
+}
+
+#[test]
+fn expiry_is_read_from_the_exp_claim() {
+  let token = jwt("api", Duration::hours(1));
+  let expires_at = token_expiry(&token).unwrap();
+  assert!(
+    (expires_at - (Utc::now() + Duration::hours(1)))
+      .num_seconds()
+      .abs()
+      <= 1
+  );
+
+  assert!(token_expiry("not-a-jwt").is_err());
+}
+
+#[tokio::test]
+async fn fresh_token_is_kept() {
+  let path = token_file("fresh");
+  let first = jwt("first", Duration::hours(1));
+  std::fs::write(&path, format!("{}\n", first)).unwrap();
+  let token = from_file(&path);
+  assert_eq!(token.get().await.unwrap(), first);
+
+  std::fs::write(&path, jwt("second", Duration::hours(1))).unwrap();
+  assert_eq!(token.get().await.unwrap(), first);
+  std::fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn token_about_to_expire_is_read_again() {
+  let path = token_file("expiring");
+  std::fs::write(&path, jwt("first", Duration::minutes(2))).unwrap();
+  let token = from_file(&path);
+  token.get().await.unwrap();
+
+  let second = jwt("second", Duration::hours(1));
+  std::fs::write(&path, &second).unwrap();
+  // Clones share the token, like the pools rebuilt for one connection
+  assert_eq!(token.clone().get().await.unwrap(), second);
+  std::fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn current_token_is_used_while_refreshing_fails() {
+  let path = token_file("unreadable");
+  let first = jwt("first", Duration::minutes(2));
+  std::fs::write(&path, &first).unwrap();
+  let token = from_file(&path);
+  token.get().await.unwrap();
+
+  std::fs::remove_file(&path).unwrap();
+  assert_eq!(token.get().await.unwrap(), first);
+}
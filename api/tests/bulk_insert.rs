@@ -0,0 +1,53 @@
+//! `execute_bulk_insert` against a real SQL Server, see `common` for the fixtures.
+mod common;
+
+use domner_tech_sql_client::{CommandType, UnifiedToSql};
+
+use api::{
+  db::{RowExt, SqlRepo},
+  middleware::transaction::DbTransaction,
+  utils::bulk_insert::execute_bulk_insert,
+};
+use common::TestDb;
+
+// Statements hold at most 1000 rows, so the duplicate lands in the second one
+const ROWS: i32 = 1500;
+
+#[actix_web::test]
+#[ignore = "starts a SQL Server container, needs Docker"]
+async fn failing_chunk_rolls_back_the_earlier_ones() {
+  let db = TestDb::start().await;
+  let pool_name = &db.state.config.database.sql_server.pool_name;
+  let mut client = db.state.db_manager.get_client(pool_name).await.unwrap();
+  SqlRepo::execute_command_none_query(
+    &mut client,
+    "CREATE TABLE dbo.bulk_insert_probe (id INT NOT NULL PRIMARY KEY)",
+    &[],
+    CommandType::Text,
+  )
+  .await
+  .unwrap();
+
+  let ids: Vec<i32> = (0..ROWS).chain([0]).collect();
+  let rows: Vec<Vec<&dyn UnifiedToSql>> =
+    ids.iter().map(|id| vec![id as &dyn UnifiedToSql]).collect();
+
+  // What `TransactionScope` does around `POST /role/assign_users`
+  let tx = DbTransaction::start(&db.state).await.unwrap();
+  let mut tx_client = tx.client().await.unwrap();
+  let result = execute_bulk_insert(&mut tx_client, "dbo.bulk_insert_probe", &["id"], &rows).await;
+  assert!(result.is_err(), "the duplicate id should be refused");
+  drop(tx_client);
+  tx.rollback().await.unwrap();
+
+  let total = SqlRepo::execute_command_single_query(
+    &mut client,
+    "SELECT COUNT(*) AS total FROM dbo.bulk_insert_probe",
+    &[],
+    CommandType::Text,
+    |row| row.column::<i32>("total"),
+  )
+  .await
+  .unwrap();
+  assert_eq!(total, Some(0), "the first 1000 rows were kept");
+}
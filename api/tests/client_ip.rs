@@ -0,0 +1,74 @@
+use std::net::{IpAddr, SocketAddr};
+
+use actix_web::test::TestRequest;
+
+use api::{app_settings::RateLimitSetting, middleware::rate_limit::client_ip};
+
+fn behind_proxies(trusted_proxies: &[&str]) -> RateLimitSetting {
+  RateLimitSetting {
+    use_forwarded_for: true,
+    trusted_proxies: trusted_proxies
+      .iter()
+      .map(|net| net.parse().unwrap())
+      .collect(),
+    ..RateLimitSetting::default()
+  }
+}
+
+fn resolve(peer: &str, forwarded_for: Option<&str>, setting: &RateLimitSetting) -> IpAddr {
+  let mut req = TestRequest::get().peer_addr(SocketAddr::new(peer.parse().unwrap(), 40000));
+  if let Some(forwarded_for) = forwarded_for {
+    req = req.insert_header(("x-forwarded-for", forwarded_for));
+  }
+  client_ip(&req.to_http_request(), setting).unwrap()
+}
+
+fn ip(addr: &str) -> IpAddr {
+  addr.parse().unwrap()
+}
+
+#[test]
+fn forwarded_for_is_ignored_unless_turned_on() {
+  let setting = RateLimitSetting::default();
+  assert_eq!(
+    resolve("10.0.0.2", Some("203.0.113.7"), &setting),
+    ip("10.0.0.2")
+  );
+}
+
+#[test]
+fn client_is_the_first_untrusted_hop_from_the_right() {
+  let setting = behind_proxies(&["10.0.0.0/8"]);
+  assert_eq!(
+    resolve("10.0.0.2", Some("203.0.113.7, 10.0.0.9"), &setting),
+    ip("203.0.113.7")
+  );
+}
+
+#[test]
+fn addresses_written_by_the_client_are_not_trusted() {
+  let setting = behind_proxies(&["10.0.0.0/8"]);
+  // The client sent `X-Forwarded-For: 1.2.3.4` itself, the proxy appended its real address
+  assert_eq!(
+    resolve("10.0.0.2", Some("1.2.3.4, 198.51.100.20"), &setting),
+    ip("198.51.100.20")
+  );
+}
+
+#[test]
+fn header_from_an_untrusted_peer_is_ignored() {
+  let setting = behind_proxies(&["10.0.0.0/8"]);
+  assert_eq!(
+    resolve("198.51.100.20", Some("203.0.113.7"), &setting),
+    ip("198.51.100.20")
+  );
+}
+
+#[test]
+fn garbled_entry_stops_at_the_last_proxy() {
+  let setting = behind_proxies(&["10.0.0.0/8"]);
+  assert_eq!(
+    resolve("10.0.0.2", Some("203.0.113.7, not-an-ip"), &setting),
+    ip("10.0.0.2")
+  );
+}
@@ -0,0 +1,289 @@
+//! Fixtures shared by the integration tests: the app on in-memory repositories, the app state
+//! pointed at an ephemeral SQL Server, and helpers to call the app the way a client would.
+//!
+//! `TestApp` needs nothing but the test process, every test gets its own rows. `TestDb` gets
+//! its own container instead; Docker has to be running and the SQL Server image is pulled on
+//! the first run. Its tests are `#[ignore]`d so a plain `cargo test` works without Docker, run
+//! them with `cargo test -- --ignored`. `on_both_backends!` runs a flow on each of the two.
+#![allow(dead_code)]
+
+use std::sync::Arc;
+
+use actix_web::{
+  App, HttpResponse,
+  body::{self, MessageBody},
+  dev::{Service, ServiceResponse},
+  http::{StatusCode, header},
+  test, web,
+};
+use serde_json::{Value, json};
+use testcontainers_modules::{
+  mssql_server::MssqlServer,
+  testcontainers::{ContainerAsync, runners::AsyncRunner},
+};
+
+use api::{
+  api_version,
+  app_settings::AppSetting,
+  app_state::AppState,
+  middleware::{
+    error_envelope::{self, error_envelope},
+    locale::NegotiateLocale,
+    payload_limit,
+  },
+  migrations,
+  repositories::in_memory::InMemoryRepositories,
+  seed,
+};
+
+pub use api::seed::DEMO_PASSWORD;
+
+pub const DEMO_ADMIN: &str = "demo_admin";
+pub const DEMO_MODERATOR: &str = "demo_moderator";
+pub const DEMO_USER: &str = "demo_user";
+
+// Never connected to, `TestApp` opens no pool
+const UNUSED_CONN_STR: &str =
+  "Server=tcp:localhost,1433;Database=api;User Id=sa;Password=unused;TrustServerCertificate=true";
+
+/// The app state on `InMemoryRepositories`, seeded with the default roles and demo users.
+pub struct TestApp {
+  pub state: web::Data<AppState>,
+}
+
+impl TestApp {
+  pub async fn start() -> TestApp {
+    Self::start_with(|_| {}).await
+  }
+
+  /// Same as `start`, with `configure` applied to the settings first.
+  pub async fn start_with(configure: impl FnOnce(&mut AppSetting)) -> TestApp {
+    let mut setting = test_setting(UNUSED_CONN_STR);
+    configure(&mut setting);
+    let mut state = AppState::from_setting(setting)
+      .await
+      .expect("Failed to build the app state");
+    state.repositories = Arc::new(InMemoryRepositories::default());
+    seed::run_all(&state).await.unwrap();
+    state.mark_ready();
+
+    TestApp {
+      state: web::Data::new(state),
+    }
+  }
+
+  pub async fn app(
+    &self,
+  ) -> impl Service<
+    actix_http::Request,
+    Response = ServiceResponse<impl MessageBody>,
+    Error = actix_web::Error,
+  > {
+    init_app(&self.state).await
+  }
+}
+
+/// A migrated and seeded database, dropped with the container when the test ends.
+pub struct TestDb {
+  pub state: web::Data<AppState>,
+  // Stops and removes the container once dropped
+  _container: ContainerAsync<MssqlServer>,
+}
+
+impl TestDb {
+  /// Starts SQL Server, applies every migration and runs the seeders, demo users included.
+  pub async fn start() -> TestDb {
+    let container = MssqlServer::default()
+      .with_accept_eula()
+      .start()
+      .await
+      .expect("Failed to start the SQL Server container, is Docker running?");
+    let host = container.get_host().await.unwrap();
+    let port = container.get_host_port_ipv4(1433).await.unwrap();
+    let conn_str = format!(
+      "Server=tcp:{},{};Database=master;User Id=sa;Password={};TrustServerCertificate=true",
+      host,
+      port,
+      MssqlServer::DEFAULT_SA_PASSWORD
+    );
+
+    let state = AppState::from_setting(test_setting(&conn_str))
+      .await
+      .expect("Failed to build the app state");
+    state.init_db_manager().await.unwrap();
+    migrations::run_pending(&state).await.unwrap();
+    seed::run_all(&state).await.unwrap();
+    state.mark_ready();
+
+    TestDb {
+      state: web::Data::new(state),
+      _container: container,
+    }
+  }
+
+  pub async fn app(
+    &self,
+  ) -> impl Service<
+    actix_http::Request,
+    Response = ServiceResponse<impl MessageBody>,
+    Error = actix_web::Error,
+  > {
+    init_app(&self.state).await
+  }
+}
+
+/// The app as `main` serves it, minus the listener-level middleware (CORS, rate limiting,
+/// access log) the flows under test don't depend on.
+pub async fn init_app(
+  state: &web::Data<AppState>,
+) -> impl Service<
+  actix_http::Request,
+  Response = ServiceResponse<impl MessageBody>,
+  Error = actix_web::Error,
+> {
+  let limits = state.config.limits.clone();
+  test::init_service(
+    App::new()
+      .app_data(state.clone())
+      .app_data(payload_limit::json_config(&limits))
+      .app_data(payload_limit::payload_config(&limits))
+      .app_data(error_envelope::path_config())
+      .app_data(error_envelope::query_config())
+      .wrap(error_envelope())
+      .wrap(NegotiateLocale)
+      .configure(|cfg| api_version::configure(cfg, true))
+      .default_service(web::to(error_envelope::route_not_found)),
+  )
+  .await
+}
+
+/// Turns each `async fn flow(state: &web::Data<AppState>)` into a `flow` module with an
+/// `in_memory` test on `TestApp` and an ignored `sql_server` test on `TestDb`, so the same
+/// assertions cover the in-memory repositories and the real procs.
+#[allow(unused_macros)]
+macro_rules! on_both_backends {
+  ($($flow:ident),* $(,)?) => {
+    $(
+      mod $flow {
+        #[actix_web::test]
+        async fn in_memory() {
+          let test_app = crate::common::TestApp::start().await;
+          super::$flow(&test_app.state).await;
+        }
+
+        #[actix_web::test]
+        #[ignore = "starts a SQL Server container, needs Docker"]
+        async fn sql_server() {
+          let db = crate::common::TestDb::start().await;
+          super::$flow(&db.state).await;
+        }
+      }
+    )*
+  };
+}
+#[allow(unused_imports)]
+pub(crate) use on_both_backends;
+
+// The sample settings pointed at `conn_str` with everything talking to the outside off
+fn test_setting(conn_str: &str) -> AppSetting {
+  let sample = include_str!("../../appsettings-sample.json");
+  let mut setting: AppSetting = serde_json::from_str(sample).unwrap();
+  setting.environment = "development".to_string();
+  setting.database.sql_server.conn_str = conn_str.to_string();
+  setting.database.sql_server.pool_size = 4;
+  setting.jwt.secret_key = "integration-tests-secret-key".to_string();
+  setting.jwt.issuer = "api-tests".to_string();
+  setting.jwt.audience = "api-tests".to_string();
+  setting.scheduler.enabled = false;
+  setting.rate_limit.enabled = false;
+  setting.mailer.dev_mode = true;
+  setting.redis = None;
+  setting
+}
+
+/// Status and JSON body of a response. Errors returned by middleware, such as a 403 from
+/// `RequirePermission`, are rendered the way the server would send them.
+pub async fn call<S, B>(app: &S, req: actix_http::Request) -> (StatusCode, Value)
+where
+  S: Service<actix_http::Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+  B: MessageBody,
+{
+  let (status, bytes) = match test::try_call_service(app, req).await {
+    Ok(res) => (res.status(), test::read_body(res).await),
+    Err(e) => {
+      let res: HttpResponse = e.error_response();
+      let status = res.status();
+      let bytes = body::to_bytes(res.into_body()).await.unwrap_or_default();
+      (status, bytes)
+    }
+  };
+  let json = serde_json::from_slice(&bytes).unwrap_or(Value::Null);
+  (status, json)
+}
+
+pub async fn register<S, B>(app: &S, user_name: &str, password: &str) -> (StatusCode, Value)
+where
+  S: Service<actix_http::Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+  B: MessageBody,
+{
+  let req = test::TestRequest::post()
+    .uri("/api/v2/auth/register")
+    .set_json(json!({
+      "user_name": user_name,
+      "password": password,
+      "email": format!("{}@example.com", user_name),
+      "name": user_name,
+      "role": "user",
+    }))
+    .to_request();
+  call(app, req).await
+}
+
+/// The token of a successful login, `None` when the credentials are refused.
+pub async fn login<S, B>(app: &S, user_name: &str, password: &str) -> Option<String>
+where
+  S: Service<actix_http::Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+  B: MessageBody,
+{
+  let req = test::TestRequest::post()
+    .uri("/api/v2/auth/login")
+    .set_json(json!({ "user_name": user_name, "password": password }))
+    .to_request();
+  let (_, body) = call(app, req).await;
+  body["data"]["token"].as_str().map(str::to_string)
+}
+
+/// Logs one of the seeded demo users in, see `DEMO_ADMIN` and friends.
+pub async fn login_demo<S, B>(app: &S, user_name: &str) -> String
+where
+  S: Service<actix_http::Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+  B: MessageBody,
+{
+  login(app, user_name, DEMO_PASSWORD)
+    .await
+    .unwrap_or_else(|| panic!("Seeded user {} could not log in", user_name))
+}
+
+/// A GET request sent with `token` as bearer, or anonymously.
+pub fn get(uri: &str, token: Option<&str>) -> actix_http::Request {
+  bearer(test::TestRequest::get().uri(uri), token).to_request()
+}
+
+/// A POST of `body` sent with `token` as bearer.
+pub fn post(uri: &str, token: &str, body: Value) -> actix_http::Request {
+  bearer(test::TestRequest::post().uri(uri), Some(token))
+    .set_json(body)
+    .to_request()
+}
+
+/// A PUT without body sent with `token` as bearer.
+pub fn put(uri: &str, token: &str) -> actix_http::Request {
+  bearer(test::TestRequest::put().uri(uri), Some(token)).to_request()
+}
+
+fn bearer(req: test::TestRequest, token: Option<&str>) -> test::TestRequest {
+  match token {
+    Some(token) => req.insert_header((header::AUTHORIZATION, format!("Bearer {}", token))),
+    None => req,
+  }
+}
@@ -0,0 +1,71 @@
+//! Permission handlers on the in-memory repositories, see `common` for the fixtures.
+mod common;
+
+use actix_web::http::StatusCode;
+use serde_json::{Value, json};
+
+use common::{DEMO_ADMIN, DEMO_USER, TestApp, call, get, login_demo, post, put};
+
+fn id_of(list: &Value, name: &str) -> i64 {
+  list["data"]
+    .as_array()
+    .unwrap()
+    .iter()
+    .find(|item| item["name"] == name)
+    .and_then(|item| item["id"].as_i64())
+    .unwrap_or_else(|| panic!("{} not found in {}", name, list))
+}
+
+#[actix_web::test]
+async fn granted_permission_opens_the_route() {
+  let test_app = TestApp::start().await;
+  let app = test_app.app().await;
+  let admin_token = login_demo(&app, DEMO_ADMIN).await;
+  let user_token = login_demo(&app, DEMO_USER).await;
+
+  let (status, _) = call(&app, get("/api/v2/roles", Some(&user_token))).await;
+  assert_eq!(status, StatusCode::FORBIDDEN);
+
+  let (status, body) = call(
+    &app,
+    post(
+      "/api/v2/permissions",
+      &admin_token,
+      json!({ "name": "role:read" }),
+    ),
+  )
+  .await;
+  assert_eq!(status, StatusCode::OK, "{}", body);
+
+  let (_, permissions) = call(&app, get("/api/v2/permissions", Some(&admin_token))).await;
+  let (_, roles) = call(&app, get("/api/v2/roles", Some(&admin_token))).await;
+  let uri = format!(
+    "/api/v2/roles/{}/permissions/{}",
+    id_of(&roles, "user"),
+    id_of(&permissions, "role:read")
+  );
+  let (status, body) = call(&app, put(&uri, &admin_token)).await;
+  assert_eq!(status, StatusCode::OK, "{}", body);
+
+  // Checked against the grants on every request, the token stays the same
+  let (status, body) = call(&app, get("/api/v2/roles", Some(&user_token))).await;
+  assert_eq!(status, StatusCode::OK, "{}", body);
+}
+
+#[actix_web::test]
+async fn malformed_permission_name_is_refused() {
+  let test_app = TestApp::start().await;
+  let app = test_app.app().await;
+  let token = login_demo(&app, DEMO_ADMIN).await;
+
+  let (status, _) = call(
+    &app,
+    post(
+      "/api/v2/permissions",
+      &token,
+      json!({ "name": "everything" }),
+    ),
+  )
+  .await;
+  assert_eq!(status, StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,143 @@
+//! Role handlers on the in-memory repositories, see `common` for the fixtures.
+mod common;
+
+use actix_web::http::StatusCode;
+use serde_json::json;
+
+use common::{DEMO_ADMIN, DEMO_USER, TestApp, call, get, login_demo, post, put};
+
+#[actix_web::test]
+async fn created_role_can_be_assigned() {
+  let test_app = TestApp::start().await;
+  let app = test_app.app().await;
+  let admin_token = login_demo(&app, DEMO_ADMIN).await;
+  let user_token = login_demo(&app, DEMO_USER).await;
+
+  let (status, body) = call(
+    &app,
+    post(
+      "/api/v2/roles",
+      &admin_token,
+      json!({ "name": "auditor", "description": "Reads the audit log" }),
+    ),
+  )
+  .await;
+  assert_eq!(status, StatusCode::OK, "{}", body);
+  let role_id = body["data"]["id"].as_i64().unwrap();
+
+  let (_, body) = call(&app, get("/api/v2/users/me", Some(&user_token))).await;
+  let user_id = body["data"]["user"]["id"].as_i64().unwrap();
+  let uri = format!("/api/v2/roles/{}/users/{}", role_id, user_id);
+  let (status, body) = call(&app, put(&uri, &admin_token)).await;
+  assert_eq!(status, StatusCode::OK, "{}", body);
+
+  let (_, body) = call(&app, get("/api/v2/users/me", Some(&user_token))).await;
+  let roles: Vec<_> = body["data"]["roles"]
+    .as_array()
+    .unwrap()
+    .iter()
+    .filter_map(|role| role["role_name"].as_str())
+    .collect();
+  assert!(roles.contains(&"auditor"), "{:?}", roles);
+  assert!(roles.contains(&"user"), "{:?}", roles);
+}
+
+#[actix_web::test]
+async fn role_names_are_unique() {
+  let test_app = TestApp::start().await;
+  let app = test_app.app().await;
+  let token = login_demo(&app, DEMO_ADMIN).await;
+
+  let (status, body) = call(
+    &app,
+    post("/api/v2/roles", &token, json!({ "name": "moderator" })),
+  )
+  .await;
+  assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+}
+
+#[actix_web::test]
+async fn assigning_many_users_skips_members() {
+  let test_app = TestApp::start().await;
+  let app = test_app.app().await;
+  let admin_token = login_demo(&app, DEMO_ADMIN).await;
+  let user_token = login_demo(&app, DEMO_USER).await;
+
+  let (_, body) = call(
+    &app,
+    post("/api/v2/roles", &admin_token, json!({ "name": "auditor" })),
+  )
+  .await;
+  let role_id = body["data"]["id"].as_i64().unwrap();
+  let (_, body) = call(&app, get("/api/v2/users/me", Some(&user_token))).await;
+  let user_id = body["data"]["user"]["id"].as_i64().unwrap();
+  let (_, body) = call(&app, get("/api/v2/users/me", Some(&admin_token))).await;
+  let admin_id = body["data"]["user"]["id"].as_i64().unwrap();
+
+  let uri = format!("/api/v2/roles/{}/users/{}", role_id, user_id);
+  let (status, _) = call(&app, put(&uri, &admin_token)).await;
+  assert_eq!(status, StatusCode::OK);
+
+  // Inserting the existing member again would break the unique key
+  let uri = format!("/api/v2/roles/{}/users", role_id);
+  let body = json!({ "user_ids": [user_id, admin_id, admin_id] });
+  let (status, body) = call(&app, post(&uri, &admin_token, body)).await;
+  assert_eq!(status, StatusCode::OK, "{}", body);
+
+  let user_ids: Vec<i64> = (1..=1001).collect();
+  let body = json!({ "user_ids": user_ids });
+  let (status, _) = call(&app, post(&uri, &admin_token, body)).await;
+  assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[actix_web::test]
+async fn role_count_is_capped() {
+  // The seeded admin, moderator and user roles fill it
+  let test_app = TestApp::start_with(|setting| setting.quota.max_roles = Some(3)).await;
+  let app = test_app.app().await;
+  let token = login_demo(&app, DEMO_ADMIN).await;
+
+  let (status, body) = call(
+    &app,
+    post("/api/v2/roles", &token, json!({ "name": "auditor" })),
+  )
+  .await;
+  assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{}", body);
+  assert_eq!(body["status"]["code"], "QUOTA_EXCEEDED");
+}
+
+#[actix_web::test]
+async fn role_members_are_capped() {
+  let test_app = TestApp::start_with(|setting| setting.quota.max_members_per_role = Some(1)).await;
+  let app = test_app.app().await;
+  let admin_token = login_demo(&app, DEMO_ADMIN).await;
+  let user_token = login_demo(&app, DEMO_USER).await;
+
+  let (_, body) = call(
+    &app,
+    post("/api/v2/roles", &admin_token, json!({ "name": "auditor" })),
+  )
+  .await;
+  let role_id = body["data"]["id"].as_i64().unwrap();
+  let (_, body) = call(&app, get("/api/v2/users/me", Some(&user_token))).await;
+  let user_id = body["data"]["user"]["id"].as_i64().unwrap();
+  let (_, body) = call(&app, get("/api/v2/users/me", Some(&admin_token))).await;
+  let admin_id = body["data"]["user"]["id"].as_i64().unwrap();
+
+  // Two new members at once don't fit
+  let uri = format!("/api/v2/roles/{}/users", role_id);
+  let body = json!({ "user_ids": [user_id, admin_id] });
+  let (status, body) = call(&app, post(&uri, &admin_token, body)).await;
+  assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{}", body);
+  assert_eq!(body["status"]["code"], "QUOTA_EXCEEDED");
+
+  let uri = format!("/api/v2/roles/{}/users/{}", role_id, user_id);
+  let (status, body) = call(&app, put(&uri, &admin_token)).await;
+  assert_eq!(status, StatusCode::OK, "{}", body);
+
+  // Nor does a second one on its own
+  let uri = format!("/api/v2/roles/{}/users/{}", role_id, admin_id);
+  let (status, body) = call(&app, put(&uri, &admin_token)).await;
+  assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{}", body);
+  assert_eq!(body["status"]["code"], "QUOTA_EXCEEDED");
+}
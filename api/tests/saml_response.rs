@@ -0,0 +1,156 @@
+//! SAML responses signed with a throwaway IdP key from `fixtures/saml-idp-key.pem`.
+use std::collections::HashMap;
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+use ring::{
+  rand::SystemRandom,
+  signature::{RSA_PKCS1_SHA256, RsaKeyPair},
+};
+use roxmltree::Document;
+use sha2::{Digest, Sha256};
+
+use api::{
+  app_settings::SamlSetting, features::saml::saml_response::read_response, utils::xml_dsig,
+};
+
+const IDP_KEY: &str = include_str!("fixtures/saml-idp-key.pem");
+const ACS_URL: &str = "https://api.example.com/api/v2/auth/saml/acs";
+
+fn setting() -> SamlSetting {
+  SamlSetting {
+    entity_id: "https://api.example.com".to_string(),
+    acs_url: ACS_URL.to_string(),
+    idp_entity_id: "https://idp.example.com".to_string(),
+    idp_sso_url: "https://idp.example.com/sso".to_string(),
+    idp_cert_path: String::new(),
+    user_name_attribute: None,
+    email_attribute: Some("email".to_string()),
+    display_name_attribute: None,
+    role_attribute: None,
+    role_mapping: HashMap::new(),
+    auto_provision: false,
+    clock_skew_secs: 120,
+  }
+}
+
+fn key_pair() -> RsaKeyPair {
+  let der = IDP_KEY
+    .lines()
+    .filter(|line| !line.starts_with("-----"))
+    .collect::<String>();
+  RsaKeyPair::from_pkcs8(&STANDARD.decode(der).unwrap()).unwrap()
+}
+
+fn instant(at: DateTime<Utc>) -> String {
+  at.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+// A response whose assertion the IdP signed, `{signature}` marks where the signature goes
+fn assertion(name_id: &str, email: &str, now: DateTime<Utc>) -> String {
+  let setting = setting();
+  let later = instant(now + Duration::minutes(5));
+  format!(
+    r#"<samlp:Response xmlns:samlp="urn:oasis:names:tc:SAML:2.0:protocol" ID="_response" Destination="{acs}"><samlp:Status><samlp:StatusCode Value="urn:oasis:names:tc:SAML:2.0:status:Success"/></samlp:Status><saml:Assertion xmlns:saml="urn:oasis:names:tc:SAML:2.0:assertion" ID="_assertion" IssueInstant="{now}"><saml:Issuer>{idp}</saml:Issuer>{{signature}}<saml:Subject><saml:NameID>{name_id}</saml:NameID><saml:SubjectConfirmation Method="urn:oasis:names:tc:SAML:2.0:cm:bearer"><saml:SubjectConfirmationData InResponseTo="_request" Recipient="{acs}" NotOnOrAfter="{later}"/></saml:SubjectConfirmation></saml:Subject><saml:Conditions NotBefore="{now}" NotOnOrAfter="{later}"><saml:AudienceRestriction><saml:Audience>{sp}</saml:Audience></saml:AudienceRestriction></saml:Conditions><saml:AttributeStatement><saml:Attribute Name="email"><saml:AttributeValue>{email}</saml:AttributeValue></saml:Attribute></saml:AttributeStatement></saml:Assertion></samlp:Response>"#,
+    acs = ACS_URL,
+    now = instant(now),
+    later = later,
+    idp = setting.idp_entity_id,
+    sp = setting.entity_id,
+    name_id = name_id,
+    email = email,
+  )
+}
+
+// Signs the assertion the way an IdP does, over the canonical form that leaves out comments
+fn sign(template: &str) -> String {
+  let unsigned = template.replace("{signature}", "");
+  let doc = Document::parse(&unsigned).unwrap();
+  let element = doc
+    .descendants()
+    .find(|node| node.attribute("ID") == Some("_assertion"))
+    .unwrap();
+  let digest = STANDARD.encode(Sha256::digest(
+    xml_dsig::canonicalize(element, None, &[]).as_bytes(),
+  ));
+
+  let signature = format!(
+    r##"<ds:Signature xmlns:ds="http://www.w3.org/2000/09/xmldsig#"><ds:SignedInfo><ds:CanonicalizationMethod Algorithm="http://www.w3.org/2001/10/xml-exc-c14n#"/><ds:SignatureMethod Algorithm="http://www.w3.org/2001/04/xmldsig-more#rsa-sha256"/><ds:Reference URI="#_assertion"><ds:Transforms><ds:Transform Algorithm="http://www.w3.org/2000/09/xmldsig#enveloped-signature"/><ds:Transform Algorithm="http://www.w3.org/2001/10/xml-exc-c14n#"/></ds:Transforms><ds:DigestMethod Algorithm="http://www.w3.org/2001/04/xmlenc#sha256"/><ds:DigestValue>{}</ds:DigestValue></ds:Reference></ds:SignedInfo><ds:SignatureValue>{{value}}</ds:SignatureValue></ds:Signature>"##,
+    digest
+  );
+  let with_signature = template.replace("{signature}", &signature);
+  let doc = Document::parse(&with_signature).unwrap();
+  let signed_info = doc
+    .descendants()
+    .find(|node| node.has_tag_name((xml_dsig::DSIG_NS, "SignedInfo")))
+    .unwrap();
+  let key_pair = key_pair();
+  let mut value = vec![0; key_pair.public().modulus_len()];
+  key_pair
+    .sign(
+      &RSA_PKCS1_SHA256,
+      &SystemRandom::new(),
+      xml_dsig::canonicalize(signed_info, None, &[]).as_bytes(),
+      &mut value,
+    )
+    .unwrap();
+  with_signature.replace("{value}", &STANDARD.encode(value))
+}
+
+fn idp_public_key() -> Vec<u8> {
+  key_pair().public().as_ref().to_vec()
+}
+
+#[test]
+fn signed_assertion_is_read() {
+  let now = Utc::now();
+  let xml = sign(&assertion("alice@corp.com", "alice@corp.com", now));
+
+  let identity = read_response(&xml, &setting(), &idp_public_key(), now).unwrap();
+  assert_eq!(identity.name_id, "alice@corp.com");
+  assert_eq!(identity.attribute("email"), Some("alice@corp.com"));
+  assert_eq!(identity.in_response_to, "_request");
+}
+
+#[test]
+fn changed_assertion_is_refused() {
+  let now = Utc::now();
+  let xml = sign(&assertion("alice@corp.com", "alice@corp.com", now)).replace(
+    ">alice@corp.com</saml:NameID>",
+    ">admin@corp.com</saml:NameID>",
+  );
+
+  assert!(read_response(&xml, &setting(), &idp_public_key(), now).is_err());
+}
+
+// The IdP vouched for `admin@corp.com.evil.com`, a comment added afterwards keeps the signature
+// valid but must not turn the NameID into `admin@corp.com`
+#[test]
+fn commented_name_id_is_refused() {
+  let now = Utc::now();
+  let xml = sign(&assertion(
+    "admin@corp.com.evil.com",
+    "admin@corp.com.evil.com",
+    now,
+  ))
+  .replace(
+    ">admin@corp.com.evil.com</saml:NameID>",
+    ">admin@corp.com<!---->.evil.com</saml:NameID>",
+  );
+
+  let error = read_response(&xml, &setting(), &idp_public_key(), now)
+    .err()
+    .expect("a commented NameID must be refused");
+  assert!(error.to_string().contains("NameID"), "{}", error);
+}
+
+#[test]
+fn commented_attribute_value_is_refused() {
+  let now = Utc::now();
+  let xml = sign(&assertion("mallory", "admin@corp.com<!---->.evil.com", now));
+
+  let error = read_response(&xml, &setting(), &idp_public_key(), now)
+    .err()
+    .expect("a commented AttributeValue must be refused");
+  assert!(error.to_string().contains("AttributeValue"), "{}", error);
+}
@@ -0,0 +1,45 @@
+use api::db::statement_cache::StatementCache;
+
+#[test]
+fn least_recently_used_handle_is_evicted() {
+  let mut cache = StatementCache::new(2);
+  assert_eq!(cache.insert("select_user".to_string(), 1), None);
+  assert_eq!(
+    cache.insert("select_user_by_user_name".to_string(), 2),
+    None
+  );
+
+  // Using select_user leaves select_user_by_user_name as the oldest
+  assert_eq!(cache.get("select_user"), Some(1));
+  assert_eq!(cache.insert("select_roles".to_string(), 3), Some(2));
+  assert_eq!(cache.get("select_user_by_user_name"), None);
+  assert_eq!(cache.get("select_user"), Some(1));
+  assert_eq!(cache.len(), 2);
+}
+
+#[test]
+fn preparing_a_cached_statement_again_hands_back_the_old_handle() {
+  let mut cache = StatementCache::new(2);
+  cache.insert("select_user".to_string(), 1);
+  cache.insert("select_roles".to_string(), 2);
+
+  assert_eq!(cache.insert("select_user".to_string(), 5), Some(1));
+  assert_eq!(cache.get("select_roles"), Some(2));
+  assert_eq!(cache.get("select_user"), Some(5));
+}
+
+#[test]
+fn disabled_cache_keeps_nothing() {
+  let mut cache = StatementCache::new(0);
+  assert_eq!(cache.insert("select_user".to_string(), 1), Some(1));
+  assert!(cache.is_empty());
+  assert_eq!(cache.get("select_user"), None);
+}
+
+#[test]
+fn forgotten_handle_is_not_handed_out() {
+  let mut cache = StatementCache::new(2);
+  cache.insert("select_user".to_string(), 1);
+  cache.remove("select_user");
+  assert_eq!(cache.get("select_user"), None);
+}
@@ -0,0 +1,121 @@
+//! User handlers on the in-memory repositories and on a real SQL Server, see `common` for the
+//! fixtures.
+mod common;
+
+use actix_web::{http::StatusCode, test, web};
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+
+use api::app_state::AppState;
+use common::{
+  DEMO_ADMIN, DEMO_PASSWORD, DEMO_USER, call, get, init_app, login, login_demo, on_both_backends,
+  post,
+};
+
+on_both_backends!(
+  admin_pages_through_users,
+  deactivated_user_is_locked_out,
+  password_reset_token_works_once
+);
+
+fn anonymous_post(uri: &str, body: Value) -> actix_http::Request {
+  test::TestRequest::post()
+    .uri(uri)
+    .set_json(body)
+    .to_request()
+}
+
+// The emailed token never reaches the test, one is planted the way `forgot_password` stores it
+async fn plant_reset_token(state: &web::Data<AppState>, user_id: i32, token: &str) {
+  let token_hash = format!("{:x}", Sha256::digest(token.as_bytes()));
+  state
+    .user_repo()
+    .create_password_reset(user_id, &token_hash, 30)
+    .await
+    .unwrap();
+}
+
+async fn admin_pages_through_users(state: &web::Data<AppState>) {
+  let app = init_app(state).await;
+  let token = login_demo(&app, DEMO_ADMIN).await;
+
+  let (status, body) = call(&app, get("/api/v2/users?page=1&page_size=2", Some(&token))).await;
+  assert_eq!(status, StatusCode::OK, "{}", body);
+  assert_eq!(body["data"]["items"].as_array().unwrap().len(), 2);
+  // The three demo users
+  assert_eq!(body["data"]["total_count"], 3);
+  assert_eq!(body["data"]["total_pages"], 2);
+
+  let (_, body) = call(&app, get("/api/v2/users?page=2&page_size=2", Some(&token))).await;
+  assert_eq!(body["data"]["items"].as_array().unwrap().len(), 1);
+}
+
+async fn deactivated_user_is_locked_out(state: &web::Data<AppState>) {
+  let app = init_app(state).await;
+  let admin_token = login_demo(&app, DEMO_ADMIN).await;
+  let user_token = login_demo(&app, DEMO_USER).await;
+
+  let (_, body) = call(&app, get("/api/v2/users/me", Some(&user_token))).await;
+  let user_id = body["data"]["user"]["id"].as_i64().unwrap();
+
+  let (_, body) = call(&app, get("/api/v2/users/me", Some(&admin_token))).await;
+  let admin_id = body["data"]["user"]["id"].as_i64().unwrap();
+  // Admins can't lock themselves out
+  let uri = format!("/api/v2/users/{}/deactivate", admin_id);
+  let (status, _) = call(&app, post(&uri, &admin_token, json!({}))).await;
+  assert_eq!(status, StatusCode::BAD_REQUEST);
+
+  let uri = format!("/api/v2/users/{}/deactivate", user_id);
+  let (status, body) = call(&app, post(&uri, &admin_token, json!({}))).await;
+  assert_eq!(status, StatusCode::OK, "{}", body);
+
+  // The token issued before is refused from now on
+  let (status, _) = call(&app, get("/api/v2/users/me", Some(&user_token))).await;
+  assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+async fn password_reset_token_works_once(state: &web::Data<AppState>) {
+  let app = init_app(state).await;
+  let user_token = login_demo(&app, DEMO_USER).await;
+  let (_, body) = call(&app, get("/api/v2/users/me", Some(&user_token))).await;
+  let user_id = body["data"]["user"]["id"].as_i64().unwrap() as i32;
+
+  // Unknown users get the same answer, so accounts can't be probed
+  for user_name in [DEMO_USER, "nobody"] {
+    let req = anonymous_post(
+      "/api/v2/user/forgot_password",
+      json!({ "user_name": user_name }),
+    );
+    let (status, body) = call(&app, req).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+  }
+
+  // The current password is one of the recent ones
+  plant_reset_token(state, user_id, "reset-token").await;
+  let reset = |new_password: &str| {
+    anonymous_post(
+      "/api/v2/user/reset_password",
+      json!({ "token": "reset-token", "new_password": new_password }),
+    )
+  };
+  let (status, _) = call(&app, reset(DEMO_PASSWORD)).await;
+  assert_eq!(status, StatusCode::BAD_REQUEST);
+
+  plant_reset_token(state, user_id, "reset-token").await;
+  let (status, body) = call(&app, reset("a-brand-new-password")).await;
+  assert_eq!(status, StatusCode::OK, "{}", body);
+  assert!(login(&app, DEMO_USER, DEMO_PASSWORD).await.is_none());
+  assert!(
+    login(&app, DEMO_USER, "a-brand-new-password")
+      .await
+      .is_some()
+  );
+
+  let (status, _) = call(&app, reset("yet-another-password")).await;
+  assert_eq!(status, StatusCode::BAD_REQUEST);
+  assert!(
+    login(&app, DEMO_USER, "yet-another-password")
+      .await
+      .is_none()
+  );
+}